@@ -0,0 +1,131 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{OpenFlags, VfsError, VfsFileRef, VfsNodeAttr, VfsNodePerm};
+use axfs_vfs::{VfsNodeType, VfsResult};
+
+/// A read-only file whose content is generated on every read.
+///
+/// The content is rendered by a callback, like the files of `procfs`, so it
+/// always reflects the current state of the kernel. As the size is not
+/// known in advance, the file reports a size of 0, and is read until a read
+/// returns 0 bytes.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_devfs::CallbackFile;
+/// use axfs_vfs::VfsNodeOps;
+///
+/// let file = CallbackFile::new(|| String::from("42\n"));
+/// let mut buf = [0; 8];
+/// assert_eq!(file.read_at(0, &mut buf), Ok(3));
+/// assert_eq!(&buf[..3], b"42\n");
+/// ```
+pub struct CallbackFile {
+    render: Box<dyn Fn() -> String + Send + Sync>,
+    opens: OpenState,
+}
+
+impl CallbackFile {
+    /// Creates a file rendered by `render`.
+    ///
+    /// # Arguments
+    ///
+    /// * `render` - The callback returning the whole content of the file
+    pub fn new(render: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self {
+            render: Box::new(render),
+            opens: OpenState::new(),
+        }
+    }
+}
+
+impl axfs_vfs::VfsFileNodeOps for CallbackFile {
+    /// Returns the state counting the open handles of the file.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Opens the file for reading.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`] if `flags` contains
+    /// [`OpenFlags::WRITE`] or [`OpenFlags::TRUNC`].
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        if flags.intersects(OpenFlags::WRITE | OpenFlags::TRUNC) {
+            return Err(VfsError::PermissionDenied);
+        }
+        Ok(None)
+    }
+
+    /// Returns the attributes of a read-only file of size 0.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o444),
+            VfsNodeType::File,
+            0,
+            0,
+        ))
+    }
+
+    /// Renders the content and reads it from `offset`.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let content = (self.render)();
+        let start = content.len().min(offset as usize);
+        let len = buf.len().min(content.len() - start);
+        buf[..len].copy_from_slice(&content.as_bytes()[start..start + len]);
+        Ok(len)
+    }
+
+    /// Writing is not allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`].
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    /// Truncating is not allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`].
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(CallbackFile);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axfs_vfs::VfsNodeOps;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_callback_file() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        let file = CallbackFile::new(|| {
+            let n = CALLS.fetch_add(1, Ordering::Relaxed);
+            alloc::format!("calls {n}\n")
+        });
+        let mut buf = [0; 4];
+        assert_eq!(file.read_at(0, &mut buf), Ok(4));
+        assert_eq!(&buf, b"call");
+        assert_eq!(file.read_at(4, &mut buf), Ok(4));
+        assert_eq!(&buf, b"s 1\n");
+        assert_eq!(file.read_at(100, &mut buf), Ok(0));
+        assert_eq!(file.write_at(0, b"x"), Err(VfsError::PermissionDenied));
+        assert!(file.open(OpenFlags::READ).is_ok());
+        assert_eq!(
+            file.open(OpenFlags::WRITE).err(),
+            Some(VfsError::PermissionDenied)
+        );
+        assert_eq!(file.get_attr().unwrap().perm().bits(), 0o444);
+    }
+}
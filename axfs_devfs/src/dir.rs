@@ -1,12 +1,17 @@
 use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
+use alloc::vec::Vec;
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::path::Path;
+use axfs_vfs::trace::{self, TraceOp};
+use axfs_vfs::{
+    OpenFlags, VfsDirEntry, VfsFileRef, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef,
+    VfsNodeType,
+};
 use axfs_vfs::{VfsError, VfsResult};
+use core::time::Duration;
 use spin::RwLock;
 
-#[cfg(test)]
-use crate::NullDev;
-
 /// The directory node in device filesystem.
 ///
 /// This represents a directory that can contain device nodes.
@@ -17,11 +22,50 @@ use crate::NullDev;
 ///
 /// # Fields
 ///
+/// - `ino` - The inode number of the directory: `1` for the root directory,
+///   and a hash of the path for the others, so numbers are stable across
+///   boots
 /// - `parent` - Weak reference to parent directory
-/// - `children` - Map of child node names to their references
+/// - `children` - Map of child node names to their type, inode number and
+///   reference. The type and inode number are recorded when the child is
+///   added, so listing the directory does not call into the child nodes
+/// - `mtime` - The time the directory was created or last had a node added,
+///   taken from the global [`axfs_vfs::clock`]
+/// - `perm` - The permission of the directory, `0o755` unless set by the
+///   `mode=` mount option for the root directory
+/// - `opens` - The open handles of the directory
 pub struct DirNode {
+    ino: u64,
     parent: RwLock<Weak<dyn VfsNodeOps>>,
-    children: RwLock<BTreeMap<&'static str, VfsNodeRef>>,
+    children: RwLock<BTreeMap<&'static str, Entry>>,
+    mtime: RwLock<Duration>,
+    perm: RwLock<VfsNodePerm>,
+    opens: OpenState,
+}
+
+/// A child of a [`DirNode`].
+struct Entry {
+    ty: VfsNodeType,
+    ino: u64,
+    node: VfsNodeRef,
+}
+
+/// The inode number of the root directory.
+const ROOT_INO: u64 = 1;
+
+/// Returns the synthetic inode number of the child `name` of the directory
+/// with inode number `parent`.
+///
+/// This is the FNV-1a hash of both, with the most significant bit set so it
+/// never collides with the device numbers used as inode numbers by the
+/// devices of this crate.
+pub(crate) fn synthetic_ino(parent: u64, name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in parent.to_le_bytes().iter().chain(name.as_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100_0000_01b3);
+    }
+    hash | 1 << 63
 }
 
 impl DirNode {
@@ -33,12 +77,22 @@ impl DirNode {
     ///
     /// # Returns
     ///
-    /// A new directory node wrapped in an Arc.
+    /// A new directory node wrapped in an Arc, with the inode number of the
+    /// root directory.
     pub(super) fn new(parent: Option<&VfsNodeRef>) -> Arc<Self> {
+        Self::with_ino(parent, ROOT_INO)
+    }
+
+    /// Creates a new directory node with the given inode number.
+    fn with_ino(parent: Option<&VfsNodeRef>, ino: u64) -> Arc<Self> {
         let parent = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
         Arc::new(Self {
+            ino,
             parent: RwLock::new(parent),
             children: RwLock::new(BTreeMap::new()),
+            mtime: RwLock::new(axfs_vfs::clock::now()),
+            perm: RwLock::new(VfsNodePerm::default_dir()),
+            opens: OpenState::new(),
         })
     }
 
@@ -51,6 +105,15 @@ impl DirNode {
         *self.parent.write() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
     }
 
+    /// Sets the permission of this directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `perm` - The new permission
+    pub(super) fn set_perm(&self, perm: VfsNodePerm) {
+        *self.perm.write() = perm;
+    }
+
     /// Creates a subdirectory at this directory.
     ///
     /// # Arguments
@@ -62,8 +125,15 @@ impl DirNode {
     /// A reference to the created directory node.
     pub fn mkdir(self: &Arc<Self>, name: &'static str) -> Arc<Self> {
         let parent = self.clone() as VfsNodeRef;
-        let node = Self::new(Some(&parent));
-        self.children.write().insert(name, node.clone());
+        let ino = synthetic_ino(self.ino, name);
+        let node = Self::with_ino(Some(&parent), ino);
+        let entry = Entry {
+            ty: VfsNodeType::Dir,
+            ino,
+            node: node.clone(),
+        };
+        self.children.write().insert(name, entry);
+        *self.mtime.write() = axfs_vfs::clock::now();
         node
     }
 
@@ -71,23 +141,185 @@ impl DirNode {
     ///
     /// This is the primary method for registering devices in the filesystem.
     ///
+    /// The type and inode number of the node are read once here with
+    /// [`get_attr()`](VfsNodeOps::get_attr). The type defaults to a
+    /// character device if the node fails to report its attributes, and a
+    /// synthetic inode number derived from the path is used if the node
+    /// does not report one.
+    ///
     /// # Arguments
     ///
     /// * `name` - The name of the device node
     /// * `node` - The device node reference to add
     pub fn add(&self, name: &'static str, node: VfsNodeRef) {
-        self.children.write().insert(name, node);
+        let (ty, ino) = node
+            .get_attr()
+            .map_or((VfsNodeType::CharDevice, 0), |attr| {
+                (attr.file_type(), attr.ino())
+            });
+        let ino = match ino {
+            0 => synthetic_ino(self.ino, name),
+            ino => ino,
+        };
+        self.children.write().insert(name, Entry { ty, ino, node });
+        *self.mtime.write() = axfs_vfs::clock::now();
+    }
+
+    /// Removes the child `name` from this directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns the removed node, or `None` if there is no child with this
+    /// name.
+    pub(crate) fn remove_child(&self, name: &str) -> Option<VfsNodeRef> {
+        let entry = self.children.write().remove(name)?;
+        *self.mtime.write() = axfs_vfs::clock::now();
+        Some(entry.node)
+    }
+
+    /// Removes all the children of this directory.
+    ///
+    /// # Returns
+    ///
+    /// The names, types and nodes of the removed children, sorted by name.
+    pub(crate) fn take_children(&self) -> Vec<(&'static str, VfsNodeType, VfsNodeRef)> {
+        let children = core::mem::take(&mut *self.children.write());
+        *self.mtime.write() = axfs_vfs::clock::now();
+        children
+            .into_iter()
+            .map(|(name, entry)| (name, entry.ty, entry.node))
+            .collect()
+    }
+
+    /// Returns all entries of this directory.
+    ///
+    /// Unlike [`read_dir()`](VfsNodeOps::read_dir), the list does not
+    /// include `.` and `..`, and is not limited by a caller-provided buffer.
+    ///
+    /// # Returns
+    ///
+    /// A vector of the names and types of all child nodes, sorted by name.
+    pub fn entries(&self) -> Vec<(&'static str, VfsNodeType)> {
+        self.children
+            .read()
+            .iter()
+            .map(|(name, entry)| (*name, entry.ty))
+            .collect()
+    }
+
+    /// Checks whether a node with the given name exists in this directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to check for
+    ///
+    /// # Returns
+    ///
+    /// `true` if a node with the given name exists, `false` otherwise.
+    pub fn exist(&self, name: &str) -> bool {
+        self.children.read().contains_key(name)
+    }
+
+    /// Checks that the current task may search this directory.
+    ///
+    /// This is only checked with the `enforce-perms` feature, and if the
+    /// current task has a context.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`] if the directory may not be
+    /// searched.
+    fn check_search(&self) -> VfsResult {
+        #[cfg(feature = "enforce-perms")]
+        if let Some(ctx) = axfs_vfs::current_context() {
+            ctx.check(&self.get_attr()?, axfs_vfs::access::Access::EXEC)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the inode number of the parent directory, or of this
+    /// directory if it has no parent.
+    fn parent_ino(&self) -> u64 {
+        self.parent
+            .read()
+            .upgrade()
+            .and_then(|parent| parent.get_attr().ok())
+            .map_or(self.ino, |attr| attr.ino())
+    }
+
+    /// Returns the type of a child node, without calling into the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the child node
+    ///
+    /// # Returns
+    ///
+    /// Returns the type recorded when the node was added, or `None` if there
+    /// is no child with this name.
+    pub fn entry_type(&self, name: &str) -> Option<VfsNodeType> {
+        self.children.read().get(name).map(|entry| entry.ty)
+    }
+
+    /// Calls `f` on every node of this subtree, excluding this directory.
+    pub(crate) fn visit_nodes(&self, f: &mut dyn FnMut(&VfsNodeRef)) {
+        for entry in self.children.read().values() {
+            f(&entry.node);
+            if let Some(dir) = entry.node.as_any().downcast_ref::<DirNode>() {
+                dir.visit_nodes(f);
+            }
+        }
+    }
+
+    /// Returns the number of nodes in this subtree, including this directory.
+    pub(crate) fn node_count(&self) -> usize {
+        1 + self
+            .children
+            .read()
+            .values()
+            .map(
+                |entry| match entry.node.as_any().downcast_ref::<DirNode>() {
+                    Some(dir) => dir.node_count(),
+                    None => 1,
+                },
+            )
+            .sum::<usize>()
     }
 }
 
-impl VfsNodeOps for DirNode {
+impl axfs_vfs::VfsDirNodeOps for DirNode {
+    /// Returns the state counting the open handles of the directory.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Opens this directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::IsADirectory`] if `flags` contains
+    /// [`OpenFlags::WRITE`].
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        if flags.is_writable() {
+            return Err(VfsError::IsADirectory);
+        }
+        Ok(None)
+    }
+
     /// Returns the attributes of this directory.
     ///
     /// # Returns
     ///
-    /// Returns directory attributes with a fixed size of 4096 bytes.
+    /// Returns directory attributes with a fixed size of 4096 bytes and the
+    /// permission of the directory. All timestamps are the time of the last
+    /// change of the directory.
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+        let mtime = *self.mtime.read();
+        let mut attr = VfsNodeAttr::new_dir(4096, 0)
+            .with_ino(self.ino)
+            .with_times(mtime, mtime, mtime);
+        attr.set_perm(*self.perm.read());
+        Ok(attr)
     }
 
     /// Returns the parent directory of this directory.
@@ -110,23 +342,32 @@ impl VfsNodeOps for DirNode {
     /// # Returns
     ///
     /// Returns a reference to the found device node, or an error if not found.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if a component does not exist, or, with
+    /// the `enforce-perms` feature, [`VfsError::PermissionDenied`] if the
+    /// current task may not search a directory.
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
-        let (name, rest) = split_path(path);
+        let (name, rest) = Path::new(path).split_first();
+        if !name.is_empty() {
+            self.check_search()?;
+        }
         let node = match name {
             "" | "." => Ok(self.clone() as VfsNodeRef),
-            ".." => self.parent().ok_or(VfsError::NotFound),
+            ".." => VfsNodeOps::parent(&*self).ok_or(VfsError::NotFound),
             _ => self
                 .children
                 .read()
                 .get(name)
-                .cloned()
+                .map(|entry| entry.node.clone())
                 .ok_or(VfsError::NotFound),
-        }?;
+        };
 
-        if let Some(rest) = rest {
-            node.lookup(rest)
-        } else {
-            Ok(node)
+        // only the component ending the walk is traced
+        match (node, rest) {
+            (Ok(node), Some(rest)) => node.lookup(rest),
+            (node, _) => trace::record(TraceOp::Lookup, self.ino, name, node),
         }
     }
 
@@ -148,11 +389,11 @@ impl VfsNodeOps for DirNode {
         let mut children = children.iter().skip(start_idx.max(2) - 2);
         for (i, ent) in dirents.iter_mut().enumerate() {
             match i + start_idx {
-                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
-                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir).with_ino(self.ino),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir).with_ino(self.parent_ino()),
                 _ => {
-                    if let Some((name, node)) = children.next() {
-                        *ent = VfsDirEntry::new(name, node.get_attr().unwrap().file_type());
+                    if let Some((name, entry)) = children.next() {
+                        *ent = VfsDirEntry::new(name, entry.ty).with_ino(entry.ino);
                     } else {
                         return Ok(i);
                     }
@@ -181,22 +422,31 @@ impl VfsNodeOps for DirNode {
     /// Returns [`VfsError::PermissionDenied`] as dynamic creation is not supported.
     fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
         log::debug!("create {ty:?} at devfs: {path}");
-        let (name, rest) = split_path(path);
+        let (name, rest) = Path::new(path).split_first();
         if let Some(rest) = rest {
             match name {
-                "" | "." => self.create(rest, ty),
-                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                "" | "." => VfsNodeOps::create(self, rest, ty),
+                ".." => VfsNodeOps::parent(self)
+                    .ok_or(VfsError::NotFound)?
+                    .create(rest, ty),
                 _ => self
                     .children
                     .read()
                     .get(name)
                     .ok_or(VfsError::NotFound)?
+                    .node
                     .create(rest, ty),
             }
         } else if name.is_empty() || name == "." || name == ".." {
             Ok(()) // already exists
         } else {
-            Err(VfsError::PermissionDenied) // do not support to create nodes dynamically
+            // do not support to create nodes dynamically
+            trace::record(
+                TraceOp::Create,
+                self.ino,
+                name,
+                Err(VfsError::PermissionDenied),
+            )
         }
     }
 
@@ -218,59 +468,40 @@ impl VfsNodeOps for DirNode {
     /// Returns [`VfsError::PermissionDenied`] as dynamic removal is not supported.
     fn remove(&self, path: &str) -> VfsResult {
         log::debug!("remove at devfs: {path}");
-        let (name, rest) = split_path(path);
+        let (name, rest) = Path::new(path).split_first();
         if let Some(rest) = rest {
             match name {
-                "" | "." => self.remove(rest),
-                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                "" | "." => VfsNodeOps::remove(self, rest),
+                ".." => VfsNodeOps::parent(self)
+                    .ok_or(VfsError::NotFound)?
+                    .remove(rest),
                 _ => self
                     .children
                     .read()
                     .get(name)
                     .ok_or(VfsError::NotFound)?
+                    .node
                     .remove(rest),
             }
         } else {
-            Err(VfsError::PermissionDenied) // do not support to remove nodes dynamically
+            // do not support to remove nodes dynamically
+            trace::record(
+                TraceOp::Remove,
+                self.ino,
+                name,
+                Err(VfsError::PermissionDenied),
+            )
         }
     }
-
-    axfs_vfs::impl_vfs_dir_default! {}
 }
 
-/// Splits a path into first component and the rest.
-///
-/// This helper function is used for path resolution.
-///
-/// # Arguments
-///
-/// * `path` - The path to split
-///
-/// # Returns
-///
-/// A tuple containing the first path component and an optional remainder.
-fn split_path(path: &str) -> (&str, Option<&str>) {
-    let trimmed_path = path.trim_start_matches('/');
-    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
-        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
-    })
-}
+axfs_vfs::impl_vfs_dir_node!(DirNode);
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::NullDev;
 
-    #[test]
-    fn test_split_path() {
-        assert_eq!(split_path("foo/bar"), ("foo", Some("bar")));
-        assert_eq!(split_path("foo"), ("foo", None));
-        assert_eq!(split_path("/foo/bar"), ("foo", Some("bar")));
-        assert_eq!(split_path(""), ("", None));
-        assert_eq!(split_path("/"), ("", None));
-        assert_eq!(split_path("///"), ("", None));
-    }
-
     #[test]
     fn test_dir_node_new() {
         let dir = DirNode::new(None);
@@ -347,6 +578,58 @@ mod tests {
         );
     }
 
+    /// Gives the context set by each test thread.
+    struct ThreadContext;
+
+    std::thread_local! {
+        static CONTEXT: core::cell::RefCell<Option<axfs_vfs::VfsContext>> =
+            const { core::cell::RefCell::new(None) };
+    }
+
+    impl axfs_vfs::ContextProvider for ThreadContext {
+        fn current(&self) -> Option<axfs_vfs::VfsContext> {
+            CONTEXT.with(|ctx| ctx.borrow().clone())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "enforce-perms")]
+    fn test_dir_node_lookup_permissions() {
+        axfs_vfs::set_context_provider(Some(Arc::new(ThreadContext)));
+        let dir = DirNode::new(None);
+        let subdir = dir.mkdir("private");
+        subdir.set_perm(VfsNodePerm::from_bits_truncate(0o700));
+        subdir.add("null", Arc::new(NullDev));
+
+        let user = axfs_vfs::Credentials::new(1000, 1000);
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = Some(axfs_vfs::VfsContext::new(user, 0o022)));
+        assert!(dir.clone().lookup("private").is_ok());
+        assert_eq!(
+            dir.clone().lookup("private/null").err(),
+            Some(VfsError::PermissionDenied)
+        );
+
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = Some(axfs_vfs::VfsContext::root()));
+        assert!(dir.clone().lookup("private/null").is_ok());
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = None);
+        assert!(dir.lookup("private/null").is_ok());
+    }
+
+    #[test]
+    #[cfg(not(feature = "enforce-perms"))]
+    fn test_dir_node_lookup_unchecked() {
+        axfs_vfs::set_context_provider(Some(Arc::new(ThreadContext)));
+        let dir = DirNode::new(None);
+        let subdir = dir.mkdir("private");
+        subdir.set_perm(VfsNodePerm::from_bits_truncate(0o700));
+        subdir.add("null", Arc::new(NullDev));
+
+        let user = axfs_vfs::Credentials::new(1000, 1000);
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = Some(axfs_vfs::VfsContext::new(user, 0o022)));
+        assert!(dir.clone().lookup("private/null").is_ok());
+        CONTEXT.with(|ctx| *ctx.borrow_mut() = None);
+    }
+
     #[test]
     fn test_dir_node_read_dir_empty() {
         let dir = DirNode::new(None);
@@ -373,6 +656,84 @@ mod tests {
         assert_eq!(entries[2].name_as_bytes(), b"null");
     }
 
+    #[test]
+    fn test_dir_node_entries() {
+        let dir = DirNode::new(None);
+        assert!(dir.entries().is_empty());
+        dir.add("zero", Arc::new(crate::ZeroDev));
+        dir.mkdir("pts");
+        dir.add("null", Arc::new(NullDev));
+        assert_eq!(
+            dir.entries(),
+            [
+                ("null", VfsNodeType::CharDevice),
+                ("pts", VfsNodeType::Dir),
+                ("zero", VfsNodeType::CharDevice),
+            ]
+        );
+        assert!(dir.exist("pts"));
+        assert!(!dir.exist("tty"));
+    }
+
+    #[test]
+    fn test_dir_node_entry_type() {
+        let dir = DirNode::new(None);
+        dir.mkdir("subdir");
+        dir.add("null", Arc::new(NullDev));
+        assert_eq!(dir.entry_type("subdir"), Some(VfsNodeType::Dir));
+        assert_eq!(dir.entry_type("null"), Some(VfsNodeType::CharDevice));
+        assert_eq!(dir.entry_type("missing"), None);
+    }
+
+    #[test]
+    fn test_dir_node_read_dir_uses_type_hints() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        /// A device counting the calls to `get_attr()`.
+        struct CountingDev(AtomicUsize);
+
+        impl VfsNodeOps for CountingDev {
+            fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Ok(VfsNodeAttr::new(
+                    axfs_vfs::VfsNodePerm::from_bits_truncate(0o600),
+                    VfsNodeType::BlockDevice,
+                    0,
+                    0,
+                ))
+            }
+
+            axfs_vfs::impl_vfs_non_dir_default! {}
+        }
+
+        let dev = Arc::new(CountingDev(AtomicUsize::new(0)));
+        let dir = DirNode::new(None);
+        dir.add("sda", dev.clone());
+        assert_eq!(dev.0.load(Ordering::Relaxed), 1);
+
+        let mut entries: Vec<VfsDirEntry> = (0..4).map(|_| VfsDirEntry::default()).collect();
+        assert_eq!(dir.read_dir(0, &mut entries).unwrap(), 3);
+        assert_eq!(entries[2].entry_type(), VfsNodeType::BlockDevice);
+        assert_eq!(dev.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_dir_node_times() {
+        use axfs_vfs::clock::{set_global_clock, ManualClock};
+
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(1)));
+        set_global_clock(Some(clock.clone()));
+        let dir = DirNode::new(None);
+        assert_eq!(dir.get_attr().unwrap().mtime(), Duration::from_secs(1));
+
+        clock.set(Duration::from_secs(2));
+        dir.add("null", Arc::new(NullDev));
+        let attr = dir.get_attr().unwrap();
+        assert_eq!(attr.mtime(), Duration::from_secs(2));
+        assert_eq!(attr.ctime(), Duration::from_secs(2));
+        set_global_clock(None);
+    }
+
     #[test]
     fn test_dir_node_create_not_supported() {
         let dir = DirNode::new(None);
@@ -400,4 +761,34 @@ mod tests {
         let dir = DirNode::new(None);
         assert_eq!(dir.remove("null").err(), Some(VfsError::PermissionDenied));
     }
+
+    #[test]
+    fn test_dir_node_inode_numbers() {
+        let root = DirNode::new(None);
+        root.add("null", Arc::new(NullDev));
+        let sub = root.mkdir("sub");
+        assert_eq!(root.get_attr().unwrap().ino(), ROOT_INO);
+        assert_eq!(
+            sub.get_attr().unwrap().ino(),
+            synthetic_ino(ROOT_INO, "sub")
+        );
+        assert_eq!(
+            synthetic_ino(ROOT_INO, "sub"),
+            synthetic_ino(ROOT_INO, "sub")
+        );
+        assert_ne!(
+            synthetic_ino(ROOT_INO, "sub"),
+            synthetic_ino(ROOT_INO, "bus")
+        );
+
+        let mut entries: [VfsDirEntry; 4] = core::array::from_fn(|_| VfsDirEntry::default());
+        assert_eq!(root.read_dir(0, &mut entries).unwrap(), 4);
+        assert_eq!(entries[0].ino(), ROOT_INO);
+        assert_eq!(entries[1].ino(), ROOT_INO);
+        assert_eq!(entries[2].ino(), NullDev.get_attr().unwrap().ino());
+        assert_eq!(entries[3].ino(), sub.get_attr().unwrap().ino());
+
+        assert_eq!(sub.read_dir(1, &mut entries).unwrap(), 1);
+        assert_eq!(entries[0].ino(), ROOT_INO);
+    }
 }
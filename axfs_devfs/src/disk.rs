@@ -0,0 +1,335 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{
+    VfsDirEntry, VfsError, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsResult,
+};
+use spin::RwLock;
+
+use crate::dir::synthetic_ino;
+use crate::DirNode;
+
+/// The stable identifiers of a disk, used to name its links in
+/// `disk/by-id` and `disk/by-label`.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_devfs::DiskInfo;
+///
+/// let info = DiskInfo::new().with_serial("QM00001").with_label("rootfs");
+/// assert_eq!(info.serial(), Some("QM00001"));
+/// assert_eq!(info.label(), Some("rootfs"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskInfo {
+    serial: Option<String>,
+    label: Option<String>,
+}
+
+impl DiskInfo {
+    /// Creates disk identifiers without serial number nor label.
+    pub const fn new() -> Self {
+        Self {
+            serial: None,
+            label: None,
+        }
+    }
+
+    /// Sets the serial number of the disk, which names its link in
+    /// `disk/by-id`.
+    pub fn with_serial(mut self, serial: &str) -> Self {
+        self.serial = Some(serial.into());
+        self
+    }
+
+    /// Sets the label of the filesystem on the disk, which names its link
+    /// in `disk/by-label`.
+    pub fn with_label(mut self, label: &str) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Returns the serial number of the disk.
+    pub fn serial(&self) -> Option<&str> {
+        self.serial.as_deref()
+    }
+
+    /// Returns the label of the filesystem on the disk.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+/// The disks of a device filesystem, by name of their device node in the
+/// root directory.
+pub(crate) type DiskTable = RwLock<BTreeMap<&'static str, DiskInfo>>;
+
+/// The identifier naming the links of a [`LinkDir`].
+#[derive(Clone, Copy)]
+pub(crate) enum LinkKind {
+    /// The serial number, for `disk/by-id`.
+    Id,
+    /// The filesystem label, for `disk/by-label`.
+    Label,
+}
+
+/// A directory of symbolic links to the disks, such as `disk/by-id`.
+///
+/// The links are generated from the disk table on every access, so they
+/// follow the disks as they are added and removed.
+///
+/// # Fields
+///
+/// - `ino` - The inode number of the directory
+/// - `kind` - The identifier naming the links
+/// - `disks` - The disk table of the filesystem
+/// - `parent` - The `disk` directory
+/// - `opens` - The open handles of the directory
+pub(crate) struct LinkDir {
+    ino: u64,
+    kind: LinkKind,
+    disks: Arc<DiskTable>,
+    parent: Weak<DirNode>,
+    opens: OpenState,
+}
+
+/// A symbolic link of a [`LinkDir`] to a disk.
+struct DiskLink {
+    ino: u64,
+    target: String,
+}
+
+/// Returns the link name of an identifier.
+///
+/// As in udev, `/` and `\` are escaped as `\x2f` and `\x5c`, and whitespace
+/// is replaced with `_`, so any identifier is a valid file name. The dots of
+/// `.` and `..` are escaped as `\x2e`, so that the links never shadow the
+/// entries of the directory itself and of its parent.
+fn link_name(id: &str) -> String {
+    if id == "." || id == ".." {
+        return id.replace('.', "\\x2e");
+    }
+    let mut name = String::with_capacity(id.len());
+    for c in id.chars() {
+        match c {
+            '/' => name.push_str("\\x2f"),
+            '\\' => name.push_str("\\x5c"),
+            c if c.is_whitespace() => name.push('_'),
+            c => name.push(c),
+        }
+    }
+    name
+}
+
+impl LinkDir {
+    /// Creates a directory of links to the disks of `disks`, in the `disk`
+    /// directory `parent`.
+    pub(crate) fn new(
+        parent: &Arc<DirNode>,
+        name: &str,
+        kind: LinkKind,
+        disks: Arc<DiskTable>,
+    ) -> Self {
+        let parent_ino = parent.get_attr().map_or(0, |attr| attr.ino());
+        Self {
+            ino: synthetic_ino(parent_ino, name),
+            kind,
+            disks,
+            parent: Arc::downgrade(parent),
+            opens: OpenState::new(),
+        }
+    }
+
+    /// Returns the links of the directory, as pairs of link name and device
+    /// name, sorted by link name.
+    ///
+    /// If several disks have the same identifier, the link points to the
+    /// first one by device name.
+    fn links(&self) -> Vec<(String, &'static str)> {
+        let mut links = BTreeMap::new();
+        for (dev, info) in self.disks.read().iter() {
+            let id = match self.kind {
+                LinkKind::Id => info.serial(),
+                LinkKind::Label => info.label(),
+            };
+            if let Some(id) = id.filter(|id| !id.is_empty()) {
+                links.entry(link_name(id)).or_insert(*dev);
+            }
+        }
+        links.into_iter().collect()
+    }
+}
+
+impl axfs_vfs::VfsDirNodeOps for LinkDir {
+    /// Returns the state counting the open handles of the directory.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Returns the attributes of the directory.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new_dir(4096, 0).with_ino(self.ino))
+    }
+
+    /// Returns the `disk` directory.
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.upgrade().map(|dir| dir as VfsNodeRef)
+    }
+
+    /// Lookups a link with the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if there is no disk with this
+    /// identifier, or [`VfsError::NotADirectory`] if the path continues
+    /// after a link, as links are not followed.
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = path.trim_start_matches('/');
+        let (name, rest) = path
+            .split_once('/')
+            .map_or((path, None), |(n, r)| (n, Some(r)));
+        let node = match name {
+            "" | "." => self.clone() as VfsNodeRef,
+            ".." => VfsNodeOps::parent(&*self).ok_or(VfsError::NotFound)?,
+            _ => {
+                let dev = self
+                    .links()
+                    .into_iter()
+                    .find(|(link, _)| link == name)
+                    .ok_or(VfsError::NotFound)?
+                    .1;
+                return match rest {
+                    Some(rest) if !rest.trim_matches('/').is_empty() => {
+                        Err(VfsError::NotADirectory)
+                    }
+                    _ => Ok(Arc::new(DiskLink {
+                        ino: synthetic_ino(self.ino, name),
+                        target: alloc::format!("../../{dev}"),
+                    })),
+                };
+            }
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    /// Reads the links of the directory.
+    ///
+    /// The first two entries are always `.` and `..`, followed by the links
+    /// sorted by name.
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let parent_ino = VfsNodeOps::parent(self)
+            .and_then(|parent| parent.get_attr().ok())
+            .map_or(self.ino, |attr| attr.ino());
+        let links = self.links();
+        let mut links = links.iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir).with_ino(self.ino),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir).with_ino(parent_ino),
+                _ => match links.next() {
+                    Some((link, _)) => {
+                        *ent = VfsDirEntry::new(link, VfsNodeType::SymLink)
+                            .with_ino(synthetic_ino(self.ino, link))
+                    }
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    /// Creating nodes is not supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`].
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    /// Removing nodes is not supported, links are removed with their disk.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`].
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+}
+
+axfs_vfs::impl_vfs_dir_node!(LinkDir);
+
+impl axfs_vfs::VfsFileNodeOps for DiskLink {
+    /// Returns the attributes of a symbolic link whose size is the length
+    /// of its target.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o777),
+            VfsNodeType::SymLink,
+            self.target.len() as u64,
+            0,
+        )
+        .with_ino(self.ino))
+    }
+
+    /// Reads the target of the link, relative to the link directory, such
+    /// as `../../sda`.
+    fn read_link(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let len = self.target.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.target.as_bytes()[..len]);
+        Ok(len)
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(DiskLink);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_name() {
+        assert_eq!(link_name("QM00001"), "QM00001");
+        assert_eq!(link_name("my disk"), "my_disk");
+        assert_eq!(link_name("a/b\\c"), "a\\x2fb\\x5cc");
+        assert_eq!(link_name("."), "\\x2e");
+        assert_eq!(link_name(".."), "\\x2e\\x2e");
+        assert_eq!(link_name("..."), "...");
+    }
+
+    #[test]
+    fn test_link_dir() {
+        let disk = DirNode::new(None).mkdir("disk");
+        let disks = Arc::new(DiskTable::default());
+        disks
+            .write()
+            .insert("sdb", DiskInfo::new().with_serial("S1").with_label("data"));
+        disks
+            .write()
+            .insert("sda", DiskInfo::new().with_serial("S1"));
+        disks.write().insert("sdc", DiskInfo::new().with_label(""));
+        let by_id = Arc::new(LinkDir::new(&disk, "by-id", LinkKind::Id, disks.clone()));
+        let by_label = Arc::new(LinkDir::new(&disk, "by-label", LinkKind::Label, disks));
+
+        // the first disk by name wins a shared identifier
+        assert_eq!(by_id.links(), [(String::from("S1"), "sda")]);
+        // empty identifiers get no link
+        assert_eq!(by_label.links(), [(String::from("data"), "sdb")]);
+
+        let link = by_label.clone().lookup("data").unwrap();
+        let mut buf = [0; 16];
+        let len = link.read_link(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"../../sdb");
+        assert_eq!(
+            by_label.clone().lookup("data/x").err(),
+            Some(VfsError::NotADirectory)
+        );
+        assert!(by_id.parent().is_some());
+    }
+}
@@ -0,0 +1,20 @@
+use axfs_vfs::VfsNodeType;
+
+/// A receiver of the changes of a [`DeviceFileSystem`](crate::DeviceFileSystem),
+/// such as a device manager updating its state like udev.
+///
+/// The listener is called without any lock of the filesystem held, so it
+/// may access the filesystem.
+pub trait DeviceListener: Send + Sync {
+    /// Called when a node is removed from the filesystem.
+    ///
+    /// When a directory is detached, its children are reported before it,
+    /// deepest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the removed node from the root of the
+    ///   filesystem, such as `"input/event0"`
+    /// * `ty` - The type of the removed node
+    fn removed(&self, path: &str, ty: VfsNodeType);
+}
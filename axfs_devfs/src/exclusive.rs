@@ -0,0 +1,213 @@
+use core::task::Waker;
+
+use axfs_vfs::handle::{OpenCounter, OpenState};
+use axfs_vfs::{
+    FallocateMode, IoSlice, IoSliceMut, OpenFlags, PollEvents, VfsFileRef, VfsNodeAttr, VfsNodeOps,
+    VfsNodeRef, VfsPage, VfsResult,
+};
+
+/// A device that can be opened exclusively.
+///
+/// An open with [`OpenFlags::EXCL`] fails with
+/// [`VfsError::ResourceBusy`](axfs_vfs::VfsError::ResourceBusy) if the
+/// device is open, and the other opens fail the same way until it is
+/// closed, like `O_EXCL` on a Linux block device. This keeps a partitioning
+/// tool or a filesystem driver from sharing a disk with other users. The
+/// operations are forwarded to the device.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_devfs::{ExclusiveDev, ZeroDev};
+/// use axfs_vfs::handle::VfsFileHandle;
+/// use axfs_vfs::{OpenFlags, VfsError};
+///
+/// let disk = Arc::new(ExclusiveDev::new(Arc::new(ZeroDev)));
+/// let owner = VfsFileHandle::open_with(disk.clone(), OpenFlags::READ | OpenFlags::EXCL).unwrap();
+/// assert_eq!(
+///     VfsFileHandle::open_with(disk.clone(), OpenFlags::READ).err(),
+///     Some(VfsError::ResourceBusy)
+/// );
+/// drop(owner);
+/// assert!(VfsFileHandle::open_with(disk, OpenFlags::READ).is_ok());
+/// ```
+pub struct ExclusiveDev {
+    dev: VfsNodeRef,
+    opens: OpenCounter,
+}
+
+impl ExclusiveDev {
+    /// Wraps the device `dev`.
+    pub fn new(dev: VfsNodeRef) -> Self {
+        Self {
+            dev,
+            opens: OpenCounter::new(),
+        }
+    }
+
+    /// Returns the wrapped device.
+    pub fn inner(&self) -> &VfsNodeRef {
+        &self.dev
+    }
+
+    /// Returns the number of opens of the device not closed yet.
+    pub fn open_count(&self) -> usize {
+        self.opens.count()
+    }
+
+    /// Returns whether the device is open exclusively.
+    pub fn is_exclusive(&self) -> bool {
+        self.opens.is_exclusive()
+    }
+}
+
+impl VfsNodeOps for ExclusiveDev {
+    /// Opens the device, exclusively if `flags` contains [`OpenFlags::EXCL`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::ResourceBusy`](axfs_vfs::VfsError::ResourceBusy)
+    /// if the device is open exclusively, or if the open is exclusive and
+    /// the device is open. Returns the error of the device otherwise.
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        self.opens.open(flags)?;
+        self.dev.open(flags).inspect_err(|_| {
+            self.opens.release();
+        })
+    }
+
+    /// Closes the device.
+    fn release(&self) -> VfsResult {
+        self.opens.release();
+        self.dev.release()
+    }
+
+    /// Returns the open state of the device.
+    fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+        self.dev.open_state()
+    }
+
+    /// Returns the attributes of the device.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.dev.get_attr()
+    }
+
+    /// Reads from the device.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.dev.read_at(offset, buf)
+    }
+
+    /// Writes to the device.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.dev.write_at(offset, buf)
+    }
+
+    /// Reads from the device into several buffers.
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        self.dev.read_vectored_at(offset, bufs)
+    }
+
+    /// Writes to the device from several buffers.
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        self.dev.write_vectored_at(offset, bufs)
+    }
+
+    /// Synchronizes the device.
+    fn fsync(&self) -> VfsResult {
+        self.dev.fsync()
+    }
+
+    /// Synchronizes the data of the device.
+    fn fsync_data(&self) -> VfsResult {
+        self.dev.fsync_data()
+    }
+
+    /// Prefetches a range of the device.
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        self.dev.readahead(offset, len)
+    }
+
+    /// Truncates the device.
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.dev.truncate(size)
+    }
+
+    /// Allocates or deallocates a range of the device.
+    fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        self.dev.fallocate(offset, len, mode)
+    }
+
+    /// Sends a control command to the device.
+    fn ioctl(&self, cmd: u32, arg: usize) -> VfsResult<usize> {
+        self.dev.ioctl(cmd, arg)
+    }
+
+    /// Returns the readiness of the device.
+    fn poll(&self) -> VfsResult<PollEvents> {
+        self.dev.poll()
+    }
+
+    /// Registers a waker for the readiness of the device.
+    fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+        self.dev.register_waker(events, waker)
+    }
+
+    /// Returns a page of the device to map in memory.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        self.dev.get_page(offset)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NullDev, ReadOnlyDev};
+    use alloc::sync::Arc;
+    use axfs_vfs::handle::VfsFileHandle;
+    use axfs_vfs::VfsError;
+
+    #[test]
+    fn test_exclusive_dev() {
+        let dev = Arc::new(ExclusiveDev::new(Arc::new(NullDev)));
+        let reader = VfsFileHandle::open_with(dev.clone(), OpenFlags::READ).unwrap();
+        let writer = VfsFileHandle::open_with(dev.clone(), OpenFlags::WRITE).unwrap();
+        assert_eq!(dev.open_count(), 2);
+        assert_eq!(
+            VfsFileHandle::open_with(dev.clone(), OpenFlags::READ | OpenFlags::EXCL).err(),
+            Some(VfsError::ResourceBusy)
+        );
+        // duplicates share the open
+        let dup = reader.clone();
+        drop(reader);
+        drop(writer);
+        assert_eq!(dev.open_count(), 1);
+        drop(dup);
+        assert_eq!(dev.open_count(), 0);
+
+        let owner =
+            VfsFileHandle::open_with(dev.clone(), OpenFlags::WRITE | OpenFlags::EXCL).unwrap();
+        assert!(dev.is_exclusive());
+        assert_eq!(owner.write(b"data"), Ok(4));
+        assert_eq!(
+            VfsFileHandle::open_with(dev.clone(), OpenFlags::READ).err(),
+            Some(VfsError::ResourceBusy)
+        );
+        drop(owner);
+        assert!(!dev.is_exclusive());
+    }
+
+    #[test]
+    fn test_exclusive_dev_failed_open() {
+        let dev = ExclusiveDev::new(Arc::new(ReadOnlyDev::new(Arc::new(NullDev))));
+        assert_eq!(
+            dev.open(OpenFlags::WRITE | OpenFlags::EXCL).err(),
+            Some(VfsError::PermissionDenied)
+        );
+        assert_eq!(dev.open_count(), 0);
+        assert!(dev.open(OpenFlags::READ).is_ok());
+        assert_eq!(dev.open_count(), 1);
+    }
+}
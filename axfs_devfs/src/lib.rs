@@ -10,33 +10,82 @@
 //!
 //! - [`DeviceFileSystem`] - The main device filesystem structure
 //! - [`DirNode`] - Directory node for device organization
+//! - [`DeviceView`] - Filtered view of a device filesystem, such as the `/dev`
+//!   of a container
+//! - [`DiskInfo`] - Identifiers of a disk, naming its links in `disk/by-id`
+//!   and `disk/by-label`
+//! - [`DeviceListener`] - Receiver of the removals of devices
+//! - [`DeviceRegistry`] - Drivers indexed by device number, for device nodes
+//!   of other filesystems
+//! - [`CountedDev`] - Device with usage counters, shown in `.stats`
+//! - [`ExclusiveDev`] - Device refusing other opens while opened with
+//!   `O_EXCL`
+//! - [`CallbackFile`] - Read-only file generated on every read
+//! - [`MemDev`] - Physical memory device (like `/dev/mem`)
 //! - [`NullDev`] - Null device (like `/dev/null`)
+//! - [`PortDev`] - I/O port device (like `/dev/port`)
+//! - [`ReadOnlyDev`] - Read-only view of a device
+//! - [`TtyDev`] - Terminal device with session and job control (like
+//!   `/dev/ttyS0`)
 //! - [`UrandomDev`] - Random number generator device (like `/dev/urandom`)
 //! - [`ZeroDev`] - Zero device (like `/dev/zero`)
+//! - [`PhysAccess`] - Kernel-supplied backend of [`MemDev`] and [`PortDev`]
 //!
 //! # Features
 //!
-//! - Static device registration (devices must be added at creation time)
-//! - Read-only directory structure (cannot create or remove devices dynamically)
+//! - Device registration by the kernel, and hot-unplug of whole subtrees
+//!   with [`DeviceFileSystem::detach()`]
+//! - Read-only directory structure (cannot create or remove devices through
+//!   VFS operations)
 //! - Special device behaviors for null, zero, and random data
+//! - With the `enforce-perms` feature, lookups are checked against the
+//!   [`VfsContext`](axfs_vfs::VfsContext) of the current task, given by the
+//!   provider installed with
+//!   [`set_context_provider`](axfs_vfs::set_context_provider)
 
 #![cfg_attr(not(test), no_std)]
 
 extern crate alloc;
 
+mod callback;
 mod dir;
+mod disk;
+mod event;
+mod exclusive;
+mod mem;
 mod null;
+mod readonly;
+mod registry;
+mod stats;
+mod tty;
 mod urandom;
+mod view;
 mod zero;
 
+pub use self::callback::CallbackFile;
 pub use self::dir::DirNode;
+pub use self::disk::DiskInfo;
+pub use self::event::DeviceListener;
+pub use self::exclusive::ExclusiveDev;
+pub use self::mem::{MemDev, PhysAccess, PortDev};
 pub use self::null::NullDev;
+pub use self::readonly::ReadOnlyDev;
+pub use self::registry::DeviceRegistry;
+pub use self::stats::{CountedDev, DevStats};
+pub use self::tty::{Pid, Signal, SignalSink, TtyDev, TtyDriver};
 pub use self::urandom::UrandomDev;
+pub use self::view::DeviceView;
 pub use self::zero::ZeroDev;
 
+use alloc::string::{String, ToString};
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use alloc::vec::Vec;
+use axfs_vfs::{
+    DeviceId, FileSystemInfo, MountOptions, VfsCapabilities, VfsError, VfsFeatures, VfsNodeOps,
+    VfsNodeRef, VfsNodeRefExt, VfsNodeType, VfsOps, VfsResult,
+};
 use spin::once::Once;
+use spin::RwLock;
 
 /// A device filesystem that manages device nodes.
 ///
@@ -48,9 +97,20 @@ use spin::once::Once;
 ///
 /// - `parent` - The parent filesystem mount point
 /// - `root` - The root directory containing device nodes
+/// - `registry` - The devices added with a device number
+/// - `disks` - The disks added with their identifiers
+/// - `disk_dir` - The `disk` directory, created with the first disk
+/// - `stats_dir` - The `.stats` directory, created with the first device
+///   added with statistics
+/// - `listener` - The receiver of the removals of nodes
 pub struct DeviceFileSystem {
     parent: Once<VfsNodeRef>,
     root: Arc<DirNode>,
+    registry: Arc<DeviceRegistry>,
+    disks: Arc<disk::DiskTable>,
+    disk_dir: Once<Arc<DirNode>>,
+    stats_dir: Once<Arc<DirNode>>,
+    listener: RwLock<Option<Arc<dyn DeviceListener>>>,
 }
 
 impl DeviceFileSystem {
@@ -63,6 +123,11 @@ impl DeviceFileSystem {
         Self {
             parent: Once::new(),
             root: DirNode::new(None),
+            registry: Arc::new(DeviceRegistry::new()),
+            disks: Arc::new(disk::DiskTable::default()),
+            disk_dir: Once::new(),
+            stats_dir: Once::new(),
+            listener: RwLock::new(None),
         }
     }
 
@@ -93,6 +158,232 @@ impl DeviceFileSystem {
     pub fn add(&self, name: &'static str, node: VfsNodeRef) {
         self.root.add(name, node);
     }
+
+    /// Adds a device node to the root directory, with its usage statistics
+    /// in `.stats/<name>`.
+    ///
+    /// The device is wrapped in a [`CountedDev`], and `.stats/<name>` is a
+    /// [`CallbackFile`] showing its counters, such as the number of open
+    /// handles and of bytes read and written. The statistics file is
+    /// removed with the device by [`detach()`](Self::detach).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the device node
+    /// * `node` - The device node reference to add
+    ///
+    /// # Returns
+    ///
+    /// The counters of the device.
+    pub fn add_with_stats(&self, name: &'static str, node: VfsNodeRef) -> Arc<DevStats> {
+        let counted = CountedDev::new(node);
+        let stats = counted.stats().clone();
+        let rendered = stats.clone();
+        let dir = self.stats_dir.call_once(|| self.root.mkdir(".stats"));
+        dir.add(
+            name,
+            Arc::new(CallbackFile::new(move || rendered.to_string())),
+        );
+        self.root.add(name, Arc::new(counted));
+        stats
+    }
+
+    /// Adds a device node to the root directory, and registers it under a
+    /// device number.
+    ///
+    /// Device nodes with this number in other filesystems then dispatch to
+    /// `node`, once they are given the [`registry()`](Self::registry).
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the device node
+    /// * `dev` - The device number
+    /// * `node` - The device node reference to add
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`](axfs_vfs::VfsError::InvalidInput)
+    /// if the node is not a character or block device, or
+    /// [`VfsError::AlreadyExists`](axfs_vfs::VfsError::AlreadyExists) if a
+    /// device of the same type is already registered under `dev`.
+    pub fn add_device(&self, name: &'static str, dev: DeviceId, node: VfsNodeRef) -> VfsResult {
+        let ty = node
+            .get_attr()
+            .map_or(VfsNodeType::CharDevice, |attr| attr.file_type());
+        self.registry.register(ty, dev, node.clone())?;
+        self.root.add(name, node);
+        Ok(())
+    }
+
+    /// Adds a disk to the root directory, with links to it in `disk/by-id`
+    /// and `disk/by-label`.
+    ///
+    /// The links are named after the serial number and the label in `info`,
+    /// and point to the device node, such as `disk/by-label/rootfs` to
+    /// `../../sda`. They are maintained by the filesystem: they are removed
+    /// with [`remove_disk()`](Self::remove_disk), and if several disks have
+    /// the same identifier, the link points to the first one by name that
+    /// is still present.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the device node, such as `"sda"`
+    /// * `node` - The device node reference to add
+    /// * `info` - The identifiers of the disk
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::AlreadyExists`] if the root directory already has
+    /// a node with this name.
+    pub fn add_disk(&self, name: &'static str, node: VfsNodeRef, info: DiskInfo) -> VfsResult {
+        if self.root.exist(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.disk_dir.call_once(|| {
+            let dir = self.root.mkdir("disk");
+            for (name, kind) in [
+                ("by-id", disk::LinkKind::Id),
+                ("by-label", disk::LinkKind::Label),
+            ] {
+                let links = disk::LinkDir::new(&dir, name, kind, self.disks.clone());
+                dir.add(name, Arc::new(links));
+            }
+            dir
+        });
+        self.disks.write().insert(name, info);
+        self.root.add(name, node);
+        Ok(())
+    }
+
+    /// Removes a disk added with [`add_disk()`](Self::add_disk), with its
+    /// links.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the device node
+    ///
+    /// # Returns
+    ///
+    /// Returns the node of the disk, or `None` if there is no disk with this
+    /// name.
+    pub fn remove_disk(&self, name: &str) -> Option<VfsNodeRef> {
+        self.disks.write().remove(name)?;
+        self.root.remove_child(name)
+    }
+
+    /// Sets the receiver of the removals of nodes, or removes it if
+    /// `listener` is `None`.
+    pub fn set_listener(&self, listener: Option<Arc<dyn DeviceListener>>) {
+        *self.listener.write() = listener;
+    }
+
+    /// Detaches a node and its whole subtree from the filesystem, such as
+    /// the `input` directory when a USB hub disappears.
+    ///
+    /// Every removed node is unregistered from the
+    /// [`registry()`](Self::registry), its open handles are revoked (see
+    /// [`axfs_vfs::handle::revoke`]), and its removal is reported to the
+    /// [listener](Self::set_listener), children first. Detached directories
+    /// are emptied, so the devices are freed once their last handle is
+    /// closed, even if a directory is still referenced.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the node from the root directory, such as
+    ///   `"input"` or `"bus/usb/001"`
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of removed nodes, including the node at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `path` refers to the root
+    /// directory or ends with `.` or `..`, [`VfsError::NotFound`] if there
+    /// is no node at `path`, or [`VfsError::NotADirectory`] if its parent is
+    /// not a directory.
+    pub fn detach(&self, path: &str) -> VfsResult<usize> {
+        let path = path.trim_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if matches!(name, "" | "." | "..") {
+            return Err(VfsError::InvalidInput);
+        }
+        let parent = self
+            .root
+            .clone()
+            .lookup(parent)?
+            .downcast::<DirNode>()
+            .map_err(|_| VfsError::NotADirectory)?;
+        let ty = parent.entry_type(name).ok_or(VfsError::NotFound)?;
+        let node = parent.remove_child(name).ok_or(VfsError::NotFound)?;
+
+        let mut removed = Vec::new();
+        collect_subtree(String::from(path), ty, node, &mut removed);
+        if !path.contains('/') {
+            self.disks.write().remove(path);
+            if let Some(dir) = self.stats_dir.get() {
+                dir.remove_child(path);
+            }
+        }
+        for (_, _, node) in &removed {
+            self.registry.unregister_node(node);
+            axfs_vfs::handle::revoke(node.as_ref());
+        }
+        let listener = self.listener.read().clone();
+        if let Some(listener) = listener {
+            for (path, ty, _) in &removed {
+                listener.removed(path, *ty);
+            }
+        }
+        Ok(removed.len())
+    }
+
+    /// Creates a filtered view of the filesystem.
+    ///
+    /// The view only shows the nodes at the `allowed` paths, the nodes inside
+    /// allowed directories, and the directories leading to them. The devices
+    /// are shared with this filesystem, not duplicated.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed` - The paths of the visible nodes, relative to the root
+    ///   directory, such as `"null"` or `"pts/0"`
+    ///
+    /// # Returns
+    ///
+    /// A new view, which can be mounted as a filesystem of its own.
+    pub fn view(&self, allowed: &[&str]) -> DeviceView {
+        DeviceView::new(self.root.clone(), allowed)
+    }
+
+    /// Returns the registry of the devices added with a device number.
+    pub fn registry(&self) -> Arc<DeviceRegistry> {
+        self.registry.clone()
+    }
+}
+
+/// Empties the detached subtree of `node`, and appends its nodes with their
+/// paths and types to `removed`, children first.
+fn collect_subtree(
+    path: String,
+    ty: VfsNodeType,
+    node: VfsNodeRef,
+    removed: &mut Vec<(String, VfsNodeType, VfsNodeRef)>,
+) {
+    if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+        for (name, ty, child) in dir.take_children() {
+            collect_subtree(alloc::format!("{path}/{name}"), ty, child, removed);
+        }
+    }
+    removed.push((path, ty, node));
 }
 
 impl VfsOps for DeviceFileSystem {
@@ -100,15 +391,28 @@ impl VfsOps for DeviceFileSystem {
     ///
     /// This method sets up the parent reference for the root directory.
     ///
+    /// The `mode=` option sets the permission of the root directory, in
+    /// octal, like for Linux `devtmpfs`.
+    ///
     /// # Arguments
     ///
     /// * `_path` - The mount path (not used in device filesystem)
     /// * `mount_point` - The mount point directory node
+    /// * `options` - The options of the mount
     ///
     /// # Returns
     ///
     /// Returns `Ok(())` on success.
-    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] for an option other than `mode=`,
+    /// or an invalid permission.
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef, options: &MountOptions) -> VfsResult {
+        options.check_known(&["mode"])?;
+        if let Some(perm) = options.get_mode("mode")? {
+            self.root.set_perm(perm);
+        }
         if let Some(parent) = mount_point.parent() {
             self.root.set_parent(Some(self.parent.call_once(|| parent)));
         } else {
@@ -117,6 +421,72 @@ impl VfsOps for DeviceFileSystem {
         Ok(())
     }
 
+    /// Shuts the device filesystem down.
+    ///
+    /// The open handles of all devices and directories are revoked, and
+    /// the listener of removals is detached.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())`.
+    fn shutdown(&self) -> VfsResult {
+        axfs_vfs::handle::revoke(self.root.as_ref());
+        self.root.visit_nodes(&mut |node| {
+            axfs_vfs::handle::revoke(node.as_ref());
+        });
+        self.set_listener(None);
+        Ok(())
+    }
+
+    /// Returns the capabilities of the device filesystem.
+    ///
+    /// # Returns
+    ///
+    /// Case-sensitive names of up to [`MAX_NAME_LEN`] bytes, and a read-only
+    /// directory tree since devices cannot be created or removed at runtime.
+    ///
+    /// [`MAX_NAME_LEN`]: axfs_vfs::limits::MAX_NAME_LEN
+    fn capabilities(&self) -> VfsCapabilities {
+        VfsCapabilities::new(
+            VfsFeatures::CASE_SENSITIVE | VfsFeatures::READ_ONLY,
+            axfs_vfs::limits::MAX_NAME_LEN,
+        )
+    }
+
+    /// Returns the name of the filesystem type.
+    ///
+    /// # Returns
+    ///
+    /// Returns `"devfs"`.
+    fn fs_type(&self) -> &str {
+        "devfs"
+    }
+
+    /// Returns the magic number of the filesystem type.
+    ///
+    /// # Returns
+    ///
+    /// Returns `DEVFS_SUPER_MAGIC` (`0x1373`), as the Linux devfs.
+    fn fs_magic(&self) -> u64 {
+        0x1373
+    }
+
+    /// Returns the attributes of the device filesystem.
+    ///
+    /// Devices hold no data in the filesystem, so no blocks are reported.
+    /// The inodes reported are the directories and devices of the tree.
+    ///
+    /// # Returns
+    ///
+    /// Returns the attributes of the filesystem.
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        let files = self.root.node_count() as u64;
+        Ok(
+            FileSystemInfo::new(self.fs_magic(), 4096, axfs_vfs::limits::MAX_NAME_LEN as u64)
+                .with_files(files, 0),
+        )
+    }
+
     /// Returns the root directory of the device filesystem.
     ///
     /// # Returns
@@ -0,0 +1,327 @@
+use alloc::sync::Arc;
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{DeviceId, VfsError, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsResult};
+
+/// The number of addressable I/O ports.
+const PORT_SPACE_SIZE: u64 = 0x1_0000;
+
+/// Raw physical memory and I/O port accessors supplied by the kernel.
+///
+/// This is the backend of [`MemDev`] and [`PortDev`]. The I/O port methods
+/// return [`VfsError::Unsupported`] by default, for architectures without a
+/// separate port address space.
+///
+/// # Safety
+///
+/// The methods are callable from safe code with arbitrary addresses coming
+/// from userspace. Implementors must validate every access, and reject (with
+/// an error) those that would break memory safety of the kernel, e.g.
+/// touching unmapped or kernel-owned memory.
+pub unsafe trait PhysAccess: Send + Sync {
+    /// Reads physical memory starting at `paddr` into `buf`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes read, or an error if the range is not
+    /// accessible.
+    fn read_phys(&self, paddr: u64, buf: &mut [u8]) -> VfsResult<usize>;
+
+    /// Writes `buf` to physical memory starting at `paddr`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes written, or an error if the range is not
+    /// accessible.
+    fn write_phys(&self, paddr: u64, buf: &[u8]) -> VfsResult<usize>;
+
+    /// Reads one byte from each port starting at `port` into `buf`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes read, or an error if the ports are not
+    /// accessible.
+    fn read_ports(&self, _port: u16, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::Unsupported)
+    }
+
+    /// Writes one byte of `buf` to each port starting at `port`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes written, or an error if the ports are not
+    /// accessible.
+    fn write_ports(&self, _port: u16, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::Unsupported)
+    }
+}
+
+/// Returns the attributes shared by the physical access devices.
+///
+/// They are only accessible by the owner (`0o600`), and their inode number is
+/// the raw device number `dev`.
+const fn phys_dev_attr(dev: DeviceId) -> VfsNodeAttr {
+    VfsNodeAttr::new(
+        VfsNodePerm::from_bits_truncate(0o600),
+        VfsNodeType::CharDevice,
+        0,
+        0,
+    )
+    .with_ino(dev.to_raw())
+}
+
+/// A physical memory device behaves like `/dev/mem`.
+///
+/// The file offset is interpreted as a physical address, and all accesses are
+/// forwarded to the [`PhysAccess`] backend supplied by the kernel.
+///
+/// # Unix Equivalent
+///
+/// This device behaves similarly to `/dev/mem` in Unix-like systems.
+pub struct MemDev {
+    backend: Arc<dyn PhysAccess>,
+    opens: OpenState,
+}
+
+impl MemDev {
+    /// Creates a new physical memory device.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The physical memory accessor
+    pub fn new(backend: Arc<dyn PhysAccess>) -> Self {
+        Self {
+            backend,
+            opens: OpenState::new(),
+        }
+    }
+}
+
+impl axfs_vfs::VfsFileNodeOps for MemDev {
+    /// Returns the state counting the open handles of the device.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Returns attributes of the memory device.
+    ///
+    /// # Returns
+    ///
+    /// Returns character device attributes with `0o600` permission.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(phys_dev_attr(DeviceId::new(1, 1)))
+    }
+
+    /// Reads physical memory at the address `offset`.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.backend.read_phys(offset, buf)
+    }
+
+    /// Writes physical memory at the address `offset`.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.backend.write_phys(offset, buf)
+    }
+
+    /// Truncates the memory device (no effect).
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(MemDev);
+
+/// An I/O port device behaves like `/dev/port`.
+///
+/// The file offset is interpreted as a port number, and each byte read or
+/// written accesses the next port. Accesses beyond port `0xffff` hit the end
+/// of the file.
+///
+/// # Unix Equivalent
+///
+/// This device behaves similarly to `/dev/port` in Unix-like systems.
+pub struct PortDev {
+    backend: Arc<dyn PhysAccess>,
+    opens: OpenState,
+}
+
+impl PortDev {
+    /// Creates a new I/O port device.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The I/O port accessor
+    pub fn new(backend: Arc<dyn PhysAccess>) -> Self {
+        Self {
+            backend,
+            opens: OpenState::new(),
+        }
+    }
+}
+
+/// Clamps an access of `len` bytes at `offset` to the port address space.
+///
+/// Returns the first port and the number of accessible bytes.
+fn port_range(offset: u64, len: usize) -> (u16, usize) {
+    if offset >= PORT_SPACE_SIZE {
+        return (0, 0);
+    }
+    let len = len.min((PORT_SPACE_SIZE - offset) as usize);
+    (offset as u16, len)
+}
+
+impl axfs_vfs::VfsFileNodeOps for PortDev {
+    /// Returns the state counting the open handles of the device.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Returns attributes of the port device.
+    ///
+    /// # Returns
+    ///
+    /// Returns character device attributes with `0o600` permission.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(phys_dev_attr(DeviceId::new(1, 4)))
+    }
+
+    /// Reads bytes from consecutive I/O ports starting at `offset`.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        match port_range(offset, buf.len()) {
+            (_, 0) => Ok(0),
+            (port, len) => self.backend.read_ports(port, &mut buf[..len]),
+        }
+    }
+
+    /// Writes bytes to consecutive I/O ports starting at `offset`.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        match port_range(offset, buf.len()) {
+            (_, 0) => Ok(0),
+            (port, len) => self.backend.write_ports(port, &buf[..len]),
+        }
+    }
+
+    /// Truncates the port device (no effect).
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(PortDev);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axfs_vfs::VfsNodeOps;
+    use spin::Mutex;
+
+    /// A fake machine with 256 bytes of memory and 256 bytes of ports.
+    struct FakeMachine {
+        mem: Mutex<[u8; 256]>,
+        ports: Mutex<[u8; 256]>,
+    }
+
+    fn access(space: &Mutex<[u8; 256]>, addr: u64, len: usize) -> VfsResult<usize> {
+        match addr.checked_add(len as u64) {
+            Some(end) if end <= space.lock().len() as u64 => Ok(len),
+            _ => Err(VfsError::BadAddress),
+        }
+    }
+
+    unsafe impl PhysAccess for FakeMachine {
+        fn read_phys(&self, paddr: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            let len = access(&self.mem, paddr, buf.len())?;
+            buf.copy_from_slice(&self.mem.lock()[paddr as usize..][..len]);
+            Ok(len)
+        }
+
+        fn write_phys(&self, paddr: u64, buf: &[u8]) -> VfsResult<usize> {
+            let len = access(&self.mem, paddr, buf.len())?;
+            self.mem.lock()[paddr as usize..][..len].copy_from_slice(buf);
+            Ok(len)
+        }
+
+        fn read_ports(&self, port: u16, buf: &mut [u8]) -> VfsResult<usize> {
+            let len = access(&self.ports, port as _, buf.len())?;
+            buf.copy_from_slice(&self.ports.lock()[port as usize..][..len]);
+            Ok(len)
+        }
+
+        fn write_ports(&self, port: u16, buf: &[u8]) -> VfsResult<usize> {
+            let len = access(&self.ports, port as _, buf.len())?;
+            self.ports.lock()[port as usize..][..len].copy_from_slice(buf);
+            Ok(len)
+        }
+    }
+
+    /// A backend without I/O ports.
+    struct NoPorts;
+
+    unsafe impl PhysAccess for NoPorts {
+        fn read_phys(&self, _paddr: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            Ok(buf.len())
+        }
+
+        fn write_phys(&self, _paddr: u64, buf: &[u8]) -> VfsResult<usize> {
+            Ok(buf.len())
+        }
+    }
+
+    fn machine() -> Arc<FakeMachine> {
+        Arc::new(FakeMachine {
+            mem: Mutex::new([0; 256]),
+            ports: Mutex::new([0; 256]),
+        })
+    }
+
+    #[test]
+    fn test_phys_dev_attr() {
+        let attr = MemDev::new(machine()).get_attr().unwrap();
+        assert_eq!(attr.file_type(), VfsNodeType::CharDevice);
+        assert_eq!(attr.perm().mode(), 0o600);
+        assert_eq!(attr.ino(), DeviceId::new(1, 1).to_raw());
+        let attr = PortDev::new(machine()).get_attr().unwrap();
+        assert_eq!(attr.perm().mode(), 0o600);
+        assert_eq!(attr.ino(), DeviceId::new(1, 4).to_raw());
+    }
+
+    #[test]
+    fn test_mem_dev_read_write() {
+        let machine = machine();
+        let mem = MemDev::new(machine.clone());
+        assert_eq!(mem.write_at(0x10, b"phys").unwrap(), 4);
+        assert_eq!(&machine.mem.lock()[0x10..0x14], b"phys");
+
+        let mut buf = [0; 4];
+        assert_eq!(mem.read_at(0x10, &mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"phys");
+        assert_eq!(mem.read_at(0x100, &mut buf), Err(VfsError::BadAddress));
+    }
+
+    #[test]
+    fn test_port_dev_read_write() {
+        let machine = machine();
+        let port = PortDev::new(machine.clone());
+        assert_eq!(port.write_at(0x60, &[0xab, 0xcd]).unwrap(), 2);
+        assert_eq!(machine.ports.lock()[0x60..0x62], [0xab, 0xcd]);
+
+        let mut buf = [0; 2];
+        assert_eq!(port.read_at(0x60, &mut buf).unwrap(), 2);
+        assert_eq!(buf, [0xab, 0xcd]);
+    }
+
+    #[test]
+    fn test_port_dev_end_of_space() {
+        let port = PortDev::new(machine());
+        let mut buf = [0; 4];
+        assert_eq!(port.read_at(PORT_SPACE_SIZE, &mut buf).unwrap(), 0);
+        assert_eq!(port.write_at(u64::MAX, &buf).unwrap(), 0);
+        assert_eq!(port_range(PORT_SPACE_SIZE - 2, 4), (0xfffe, 2));
+    }
+
+    #[test]
+    fn test_port_dev_unsupported() {
+        let port = PortDev::new(Arc::new(NoPorts));
+        let mut buf = [0; 4];
+        assert_eq!(port.read_at(0, &mut buf), Err(VfsError::Unsupported));
+        assert_eq!(port.write_at(0, &buf), Err(VfsError::Unsupported));
+    }
+}
@@ -1,4 +1,4 @@
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axfs_vfs::{DeviceId, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsResult};
 
 /// A null device behaves like `/dev/null`.
 ///
@@ -16,19 +16,18 @@ use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
 /// This device behaves similarly to `/dev/null` in Unix-like systems.
 pub struct NullDev;
 
-impl VfsNodeOps for NullDev {
+impl axfs_vfs::VfsFileNodeOps for NullDev {
     /// Returns attributes of the null device.
     ///
     /// # Returns
     ///
-    /// Returns character device attributes with zero size.
+    /// Returns character device attributes with zero size. The inode number
+    /// is the raw device number of `/dev/null` (`1:3`), so it is stable.
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::default_file(),
-            VfsNodeType::CharDevice,
-            0,
-            0,
-        ))
+        Ok(
+            VfsNodeAttr::new(VfsNodePerm::default_file(), VfsNodeType::CharDevice, 0, 0)
+                .with_ino(DeviceId::new(1, 3).to_raw()),
+        )
     }
 
     /// Reads from the null device.
@@ -77,13 +76,14 @@ impl VfsNodeOps for NullDev {
     fn truncate(&self, _size: u64) -> VfsResult {
         Ok(())
     }
-
-    axfs_vfs::impl_vfs_non_dir_default! {}
 }
 
+axfs_vfs::impl_vfs_file_node!(NullDev);
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axfs_vfs::VfsNodeOps;
 
     #[test]
     fn test_null_dev_get_attr() {
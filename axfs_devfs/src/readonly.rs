@@ -0,0 +1,211 @@
+use alloc::vec::Vec;
+use core::task::Waker;
+
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{
+    IoSliceMut, OpenFlags, PollEvents, VfsError, VfsFileRef, VfsNodeAttr, VfsNodeOps, VfsNodePerm,
+    VfsNodeRef, VfsPage, VfsResult,
+};
+
+/// A read-only view of a device.
+///
+/// Opening the device for writing fails, and so do writes, while the other
+/// operations are forwarded to the device. This exposes devices such as a
+/// firmware image or a `/dev/mem` for inspection without allowing them to
+/// be modified.
+///
+/// The pages of the device are returned
+/// [read-only](VfsPage::into_read_only), and control commands are rejected
+/// unless they are [allowed](Self::allow_ioctl), since the view cannot tell
+/// which of them modify the device.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_devfs::{ReadOnlyDev, ZeroDev};
+/// use axfs_vfs::{OpenFlags, VfsError, VfsNodeOps};
+///
+/// let zero = ReadOnlyDev::new(Arc::new(ZeroDev));
+/// assert!(zero.open(OpenFlags::READ).is_ok());
+/// assert_eq!(zero.open(OpenFlags::WRITE).err(), Some(VfsError::PermissionDenied));
+/// ```
+pub struct ReadOnlyDev {
+    dev: VfsNodeRef,
+    ioctls: Vec<u32>,
+}
+
+impl ReadOnlyDev {
+    /// Wraps the device `dev`.
+    pub fn new(dev: VfsNodeRef) -> Self {
+        Self {
+            dev,
+            ioctls: Vec::new(),
+        }
+    }
+
+    /// Allows the control command `cmd`, which must not modify the device,
+    /// to be forwarded to it.
+    pub fn allow_ioctl(mut self, cmd: u32) -> Self {
+        if !self.ioctls.contains(&cmd) {
+            self.ioctls.push(cmd);
+        }
+        self
+    }
+
+    /// Returns the wrapped device.
+    pub fn inner(&self) -> &VfsNodeRef {
+        &self.dev
+    }
+}
+
+impl VfsNodeOps for ReadOnlyDev {
+    /// Opens the device for reading.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`] if `flags` contains
+    /// [`OpenFlags::WRITE`] or [`OpenFlags::TRUNC`], or the error of the
+    /// device.
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        if flags.intersects(OpenFlags::WRITE | OpenFlags::TRUNC) {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.dev.open(flags)
+    }
+
+    /// Closes the device.
+    fn release(&self) -> VfsResult {
+        self.dev.release()
+    }
+
+    /// Returns the open state of the device.
+    fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+        self.dev.open_state()
+    }
+
+    /// Returns the attributes of the device, without write permissions.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut attr = self.dev.get_attr()?;
+        let write = VfsNodePerm::OWNER_WRITE | VfsNodePerm::GROUP_WRITE | VfsNodePerm::OTHER_WRITE;
+        attr.set_perm(attr.perm() - write);
+        Ok(attr)
+    }
+
+    /// Reads from the device.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.dev.read_at(offset, buf)
+    }
+
+    /// Reads from the device into several buffers.
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        self.dev.read_vectored_at(offset, bufs)
+    }
+
+    /// Writing is not allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`].
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::PermissionDenied)
+    }
+
+    /// Synchronizes the device.
+    fn fsync(&self) -> VfsResult {
+        self.dev.fsync()
+    }
+
+    /// Synchronizes the data of the device.
+    fn fsync_data(&self) -> VfsResult {
+        self.dev.fsync_data()
+    }
+
+    /// Prefetches a range of the device.
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        self.dev.readahead(offset, len)
+    }
+
+    /// Sends an allowed control command to the device.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`] if `cmd` is not allowed with
+    /// [`allow_ioctl()`](Self::allow_ioctl), or the error of the device.
+    fn ioctl(&self, cmd: u32, arg: usize) -> VfsResult<usize> {
+        if !self.ioctls.contains(&cmd) {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.dev.ioctl(cmd, arg)
+    }
+
+    /// Returns the readiness of the device.
+    fn poll(&self) -> VfsResult<PollEvents> {
+        self.dev.poll()
+    }
+
+    /// Registers a waker for the readiness of the device.
+    fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+        self.dev.register_waker(events, waker)
+    }
+
+    /// Returns a read-only page of the device to map in memory.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        Ok(self.dev.get_page(offset)?.into_read_only())
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ZeroDev;
+    use alloc::sync::Arc;
+
+    #[test]
+    fn test_read_only_dev() {
+        let dev = ReadOnlyDev::new(Arc::new(ZeroDev));
+        assert!(dev.open(OpenFlags::READ).is_ok());
+        assert_eq!(
+            dev.open(OpenFlags::READ | OpenFlags::TRUNC).err(),
+            Some(VfsError::PermissionDenied)
+        );
+        assert_eq!(dev.write_at(0, b"x"), Err(VfsError::PermissionDenied));
+        let mut buf = [1; 4];
+        assert_eq!(dev.read_at(0, &mut buf), Ok(4));
+        assert_eq!(buf, [0; 4]);
+        assert_eq!(dev.get_attr().unwrap().perm().bits() & 0o222, 0);
+        assert_eq!(
+            dev.get_attr().unwrap().ino(),
+            ZeroDev.get_attr().unwrap().ino()
+        );
+    }
+
+    struct Framebuffer;
+
+    impl VfsNodeOps for Framebuffer {
+        fn ioctl(&self, cmd: u32, _arg: usize) -> VfsResult<usize> {
+            Ok(cmd as usize)
+        }
+
+        fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+            Ok(VfsPage::Phys(0x8000_0000 + offset as usize))
+        }
+    }
+
+    #[test]
+    fn test_read_only_dev_mappings() {
+        let dev = ReadOnlyDev::new(Arc::new(Framebuffer)).allow_ioctl(1);
+        assert!(matches!(
+            dev.get_page(4096),
+            Ok(VfsPage::ReadOnlyPhys(0x8000_1000))
+        ));
+        assert_eq!(dev.ioctl(1, 0), Ok(1));
+        assert_eq!(dev.ioctl(2, 0), Err(VfsError::PermissionDenied));
+        assert!(matches!(
+            ReadOnlyDev::new(Arc::new(ZeroDev)).get_page(0),
+            Ok(VfsPage::Zero)
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+
+use axfs_vfs::{DeviceId, DeviceResolver, VfsError, VfsNodeRef, VfsNodeType, VfsResult};
+use spin::RwLock;
+
+/// A registry of the device drivers, indexed by device number.
+///
+/// Character and block devices have separate number spaces, as in Linux.
+/// The registry implements [`DeviceResolver`], so device nodes created in
+/// other filesystems (such as a ramfs-based `/dev`) can dispatch their
+/// operations to the registered drivers.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_devfs::{DeviceRegistry, NullDev};
+/// use axfs_vfs::{DeviceId, DeviceResolver, VfsNodeType};
+/// use std::sync::Arc;
+///
+/// let registry = DeviceRegistry::new();
+/// let null = DeviceId::new(1, 3);
+/// registry
+///     .register(VfsNodeType::CharDevice, null, Arc::new(NullDev))
+///     .unwrap();
+/// assert!(registry.resolve(VfsNodeType::CharDevice, null).is_some());
+/// assert!(registry.resolve(VfsNodeType::BlockDevice, null).is_none());
+/// ```
+#[derive(Default)]
+pub struct DeviceRegistry {
+    devices: RwLock<BTreeMap<(u8, DeviceId), VfsNodeRef>>,
+}
+
+/// Returns the registry key of a device.
+fn key(ty: VfsNodeType, dev: DeviceId) -> VfsResult<(u8, DeviceId)> {
+    match ty {
+        VfsNodeType::CharDevice | VfsNodeType::BlockDevice => Ok((ty as u8, dev)),
+        _ => Err(VfsError::InvalidInput),
+    }
+}
+
+impl DeviceRegistry {
+    /// Creates an empty registry.
+    pub const fn new() -> Self {
+        Self {
+            devices: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Registers the driver of a device.
+    ///
+    /// # Arguments
+    ///
+    /// * `ty` - The type of the device, [`VfsNodeType::CharDevice`] or
+    ///   [`VfsNodeType::BlockDevice`]
+    /// * `dev` - The device number
+    /// * `node` - The node implementing the device
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `ty` is not a device type, or
+    /// [`VfsError::AlreadyExists`] if a device is already registered under
+    /// this number.
+    pub fn register(&self, ty: VfsNodeType, dev: DeviceId, node: VfsNodeRef) -> VfsResult {
+        let key = key(ty, dev)?;
+        let mut devices = self.devices.write();
+        if devices.contains_key(&key) {
+            return Err(VfsError::AlreadyExists);
+        }
+        devices.insert(key, node);
+        Ok(())
+    }
+
+    /// Unregisters a driver under all the numbers it is registered with.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of device numbers `node` was unregistered from.
+    pub(crate) fn unregister_node(&self, node: &VfsNodeRef) -> usize {
+        let mut devices = self.devices.write();
+        let len = devices.len();
+        devices.retain(|_, dev| !Arc::ptr_eq(dev, node));
+        len - devices.len()
+    }
+
+    /// Unregisters the driver of a device.
+    ///
+    /// Device nodes referring to the device fail with
+    /// [`VfsError::NoSuchDevice`] from now on.
+    ///
+    /// # Arguments
+    ///
+    /// * `ty` - The type of the device
+    /// * `dev` - The device number
+    ///
+    /// # Returns
+    ///
+    /// Returns the node of the device, or `None` if it was not registered.
+    pub fn unregister(&self, ty: VfsNodeType, dev: DeviceId) -> Option<VfsNodeRef> {
+        self.devices.write().remove(&key(ty, dev).ok()?)
+    }
+}
+
+impl DeviceResolver for DeviceRegistry {
+    fn resolve(&self, ty: VfsNodeType, dev: DeviceId) -> Option<VfsNodeRef> {
+        self.devices.read().get(&key(ty, dev).ok()?).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NullDev, ZeroDev};
+
+    #[test]
+    fn test_registry() {
+        let registry = DeviceRegistry::new();
+        let dev = DeviceId::new(1, 5);
+        registry
+            .register(VfsNodeType::CharDevice, dev, Arc::new(ZeroDev))
+            .unwrap();
+        assert_eq!(
+            registry.register(VfsNodeType::CharDevice, dev, Arc::new(NullDev)),
+            Err(VfsError::AlreadyExists)
+        );
+        assert_eq!(
+            registry.register(VfsNodeType::File, dev, Arc::new(NullDev)),
+            Err(VfsError::InvalidInput)
+        );
+        // block devices have their own numbers
+        registry
+            .register(VfsNodeType::BlockDevice, dev, Arc::new(NullDev))
+            .unwrap();
+
+        let zero = registry.resolve(VfsNodeType::CharDevice, dev).unwrap();
+        assert!(zero.as_any().is::<ZeroDev>());
+        assert!(registry.unregister(VfsNodeType::CharDevice, dev).is_some());
+        assert!(registry.resolve(VfsNodeType::CharDevice, dev).is_none());
+        assert!(registry.unregister(VfsNodeType::CharDevice, dev).is_none());
+        assert!(registry.resolve(VfsNodeType::BlockDevice, dev).is_some());
+    }
+}
@@ -0,0 +1,275 @@
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::Waker;
+
+use alloc::sync::Arc;
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{
+    FallocateMode, IoSlice, IoSliceMut, OpenFlags, PollEvents, SeekHint, SetAttr, VfsFileRef,
+    VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsPage, VfsResult,
+};
+
+/// The usage counters of a device wrapped in a [`CountedDev`].
+///
+/// The counters only increase, except the number of open handles. They are
+/// written as `key value` lines, such as `reads 12`.
+#[derive(Debug, Default)]
+pub struct DevStats {
+    opens: AtomicU64,
+    releases: AtomicU64,
+    reads: AtomicU64,
+    read_bytes: AtomicU64,
+    writes: AtomicU64,
+    written_bytes: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl DevStats {
+    /// Returns the number of successful opens.
+    pub fn opens(&self) -> u64 {
+        self.opens.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of open handles.
+    pub fn open_count(&self) -> u64 {
+        let releases = self.releases.load(Ordering::Relaxed);
+        self.opens().saturating_sub(releases)
+    }
+
+    /// Returns the number of successful reads.
+    pub fn reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes read.
+    pub fn read_bytes(&self) -> u64 {
+        self.read_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of successful writes.
+    pub fn writes(&self) -> u64 {
+        self.writes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of bytes written.
+    pub fn written_bytes(&self) -> u64 {
+        self.written_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of failed opens, reads and writes.
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// Counts an I/O operation of `result` in `ops` and `bytes`.
+    fn count_io(&self, ops: &AtomicU64, bytes: &AtomicU64, result: &VfsResult<usize>) {
+        match result {
+            Ok(n) => {
+                ops.fetch_add(1, Ordering::Relaxed);
+                bytes.fetch_add(*n as u64, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl fmt::Display for DevStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "opens {}", self.opens())?;
+        writeln!(f, "open_count {}", self.open_count())?;
+        writeln!(f, "reads {}", self.reads())?;
+        writeln!(f, "read_bytes {}", self.read_bytes())?;
+        writeln!(f, "writes {}", self.writes())?;
+        writeln!(f, "written_bytes {}", self.written_bytes())?;
+        writeln!(f, "errors {}", self.errors())
+    }
+}
+
+/// A device whose usage is counted in a [`DevStats`].
+///
+/// All operations are forwarded to the device. The opens, closes, reads
+/// and writes of the node are counted; those of the per-open files returned
+/// by [`open()`](VfsNodeOps::open) go directly to the device, and are not.
+///
+/// Devices added with
+/// [`DeviceFileSystem::add_with_stats()`](crate::DeviceFileSystem::add_with_stats)
+/// are wrapped in this node.
+pub struct CountedDev {
+    dev: VfsNodeRef,
+    stats: Arc<DevStats>,
+}
+
+impl CountedDev {
+    /// Wraps the device `dev`, with counters starting at 0.
+    pub fn new(dev: VfsNodeRef) -> Self {
+        Self {
+            dev,
+            stats: Arc::new(DevStats::default()),
+        }
+    }
+
+    /// Returns the wrapped device.
+    pub fn inner(&self) -> &VfsNodeRef {
+        &self.dev
+    }
+
+    /// Returns the counters of the device.
+    pub fn stats(&self) -> &Arc<DevStats> {
+        &self.stats
+    }
+}
+
+impl VfsNodeOps for CountedDev {
+    /// Opens the device, counting the open.
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        let result = self.dev.open(flags);
+        let counter = match result {
+            Ok(_) => &self.stats.opens,
+            Err(_) => &self.stats.errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Closes the device, counting the close.
+    fn release(&self) -> VfsResult {
+        self.stats.releases.fetch_add(1, Ordering::Relaxed);
+        self.dev.release()
+    }
+
+    /// Returns the open state of the device.
+    fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+        self.dev.open_state()
+    }
+
+    /// Returns the attributes of the device.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.dev.get_attr()
+    }
+
+    /// Changes the attributes of the device.
+    fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+        self.dev.set_attr(attr)
+    }
+
+    /// Reads from the device, counting the read.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let result = self.dev.read_at(offset, buf);
+        self.stats
+            .count_io(&self.stats.reads, &self.stats.read_bytes, &result);
+        result
+    }
+
+    /// Reads from the device into several buffers, counting one read.
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        let result = self.dev.read_vectored_at(offset, bufs);
+        self.stats
+            .count_io(&self.stats.reads, &self.stats.read_bytes, &result);
+        result
+    }
+
+    /// Writes to the device, counting the write.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let result = self.dev.write_at(offset, buf);
+        self.stats
+            .count_io(&self.stats.writes, &self.stats.written_bytes, &result);
+        result
+    }
+
+    /// Writes to the device from several buffers, counting one write.
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        let result = self.dev.write_vectored_at(offset, bufs);
+        self.stats
+            .count_io(&self.stats.writes, &self.stats.written_bytes, &result);
+        result
+    }
+
+    /// Synchronizes the device.
+    fn fsync(&self) -> VfsResult {
+        self.dev.fsync()
+    }
+
+    /// Synchronizes the data of the device.
+    fn fsync_data(&self) -> VfsResult {
+        self.dev.fsync_data()
+    }
+
+    /// Prefetches a range of the device.
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        self.dev.readahead(offset, len)
+    }
+
+    /// Sends a control command to the device.
+    fn ioctl(&self, cmd: u32, arg: usize) -> VfsResult<usize> {
+        self.dev.ioctl(cmd, arg)
+    }
+
+    /// Returns the readiness of the device.
+    fn poll(&self) -> VfsResult<PollEvents> {
+        self.dev.poll()
+    }
+
+    /// Registers a waker for the readiness of the device.
+    fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+        self.dev.register_waker(events, waker)
+    }
+
+    /// Returns a page of the device to map in memory.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        self.dev.get_page(offset)
+    }
+
+    /// Truncates the device.
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.dev.truncate(size)
+    }
+
+    /// Finds the next region of data or the next hole of the device.
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        self.dev.seek_hint(offset, hint)
+    }
+
+    /// Allocates or deallocates a range of the device.
+    fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        self.dev.fallocate(offset, len, mode)
+    }
+
+    axfs_vfs::impl_vfs_non_dir_default! {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NullDev, ZeroDev};
+
+    #[test]
+    fn test_counted_dev() {
+        let dev = CountedDev::new(Arc::new(ZeroDev));
+        assert!(dev.open(OpenFlags::READ).is_ok());
+        let mut buf = [1; 16];
+        assert_eq!(dev.read_at(0, &mut buf), Ok(16));
+        assert_eq!(dev.read_at(0, &mut buf[..4]), Ok(4));
+        assert_eq!(dev.write_at(0, b"abc"), Ok(3));
+        let stats = dev.stats();
+        assert_eq!((stats.opens(), stats.open_count()), (1, 1));
+        assert_eq!((stats.reads(), stats.read_bytes()), (2, 20));
+        assert_eq!((stats.writes(), stats.written_bytes()), (1, 3));
+        dev.release().unwrap();
+        assert_eq!(stats.open_count(), 0);
+        assert_eq!(
+            stats.to_string(),
+            "opens 1\nopen_count 0\nreads 2\nread_bytes 20\nwrites 1\n\
+             written_bytes 3\nerrors 0\n"
+        );
+
+        let null = CountedDev::new(Arc::new(NullDev));
+        assert_eq!(null.get_page(0).err(), NullDev.get_page(0).err());
+        assert_eq!(null.stats().errors(), 0);
+        assert_eq!(
+            null.get_attr().unwrap().ino(),
+            NullDev.get_attr().unwrap().ino()
+        );
+    }
+}
@@ -0,0 +1,600 @@
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::task::Waker;
+
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::poll::{PollEvents, WakerSet};
+use axfs_vfs::{DeviceId, VfsError, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsResult};
+use spin::Mutex;
+
+/// A process, process group or session ID.
+pub type Pid = u32;
+
+/// `ioctl` command getting the foreground process group.
+const TIOCGPGRP: u32 = 0x540f;
+/// `ioctl` command getting the session of the terminal.
+const TIOCGSID: u32 = 0x5429;
+
+/// The input characters generating signals (`VINTR`, `VQUIT` and `VSUSP`).
+const INTR_CHAR: u8 = 0x03;
+const QUIT_CHAR: u8 = 0x1c;
+const SUSP_CHAR: u8 = 0x1a;
+
+/// The job control signals raised by a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    /// `SIGHUP`, the terminal hung up.
+    Hangup,
+    /// `SIGINT`, the interrupt character was typed.
+    Interrupt,
+    /// `SIGQUIT`, the quit character was typed.
+    Quit,
+    /// `SIGCONT`, sent after [`Hangup`](Self::Hangup) to wake stopped
+    /// processes.
+    Continue,
+    /// `SIGTSTP`, the suspend character was typed.
+    Suspend,
+    /// `SIGTTIN`, a background process read from the terminal.
+    BackgroundRead,
+}
+
+impl Signal {
+    /// Returns the Linux number of the signal.
+    pub const fn number(self) -> u32 {
+        match self {
+            Self::Hangup => 1,
+            Self::Interrupt => 2,
+            Self::Quit => 3,
+            Self::Continue => 18,
+            Self::Suspend => 20,
+            Self::BackgroundRead => 21,
+        }
+    }
+}
+
+/// Signal delivery supplied by the kernel to the terminals.
+pub trait SignalSink: Send + Sync {
+    /// Sends `signal` to every process of the process group `pgrp`.
+    fn signal_group(&self, pgrp: Pid, signal: Signal);
+}
+
+/// The output side of a terminal, supplied by its driver (such as a serial
+/// port or the master side of a pseudo-terminal).
+pub trait TtyDriver: Send + Sync {
+    /// Writes `buf` to the terminal.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes written, or an error otherwise.
+    fn write(&self, buf: &[u8]) -> VfsResult<usize>;
+}
+
+/// A terminal device, with the session and job control state of a Unix tty.
+///
+/// A terminal is the controlling terminal of at most one session, and has a
+/// foreground process group in that session. The input of the terminal is
+/// pushed by its driver with [`receive()`](Self::receive): the interrupt,
+/// quit and suspend characters (`^C`, `^\` and `^Z`) are not queued but
+/// signal the foreground process group, and [`hangup()`](Self::hangup)
+/// signals the session. Signals are delivered through the [`SignalSink`]
+/// supplied by the kernel.
+///
+/// The calling process is not known to
+/// [`VfsNodeOps`](axfs_vfs::VfsNodeOps) operations, so the kernel changes
+/// the job control state with the methods of this type, such as
+/// [`set_controlling()`](Self::set_controlling) for `TIOCSCTTY`, and checks
+/// reads of background processes with [`check_read()`](Self::check_read).
+/// The `TIOCGPGRP` and `TIOCGSID` commands are also answered by
+/// [`ioctl()`](axfs_vfs::VfsNodeOps::ioctl), with the ID as result.
+///
+/// Reads fail with [`VfsError::WouldBlock`] while there is no input. The
+/// wakers registered with
+/// [`register_waker()`](axfs_vfs::VfsNodeOps::register_waker) are woken
+/// when input arrives or the terminal hangs up, so async readers need not
+/// poll it.
+///
+/// # Unix Equivalent
+///
+/// This device behaves similarly to `/dev/ttyS0` in Unix-like systems, in
+/// raw mode with `ISIG` set.
+pub struct TtyDev {
+    dev: DeviceId,
+    driver: Arc<dyn TtyDriver>,
+    signals: Arc<dyn SignalSink>,
+    state: Mutex<TtyState>,
+    wakers: WakerSet,
+    opens: OpenState,
+}
+
+/// The mutable state of a [`TtyDev`].
+///
+/// # Fields
+///
+/// - `session` - The session controlled by the terminal
+/// - `foreground` - The foreground process group of the session
+/// - `input` - The received characters not read yet
+/// - `hung_up` - Whether the terminal hung up since it last became a
+///   controlling terminal
+#[derive(Default)]
+struct TtyState {
+    session: Option<Pid>,
+    foreground: Option<Pid>,
+    input: VecDeque<u8>,
+    hung_up: bool,
+}
+
+impl TtyDev {
+    /// Creates a terminal device.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - The device number of the terminal, reported as its inode
+    ///   number, such as `4:64` for `/dev/ttyS0`
+    /// * `driver` - The output side of the terminal
+    /// * `signals` - The signal delivery of the kernel
+    pub fn new(dev: DeviceId, driver: Arc<dyn TtyDriver>, signals: Arc<dyn SignalSink>) -> Self {
+        Self {
+            dev,
+            driver,
+            signals,
+            state: Mutex::new(TtyState::default()),
+            wakers: WakerSet::new(),
+            opens: OpenState::new(),
+        }
+    }
+
+    /// Returns the session controlled by the terminal.
+    pub fn session(&self) -> Option<Pid> {
+        self.state.lock().session
+    }
+
+    /// Returns the foreground process group of the terminal.
+    pub fn foreground(&self) -> Option<Pid> {
+        self.state.lock().foreground
+    }
+
+    /// Makes the terminal the controlling terminal of a session, as
+    /// `TIOCSCTTY` from its session leader.
+    ///
+    /// # Arguments
+    ///
+    /// * `sid` - The session of the caller
+    /// * `pgrp` - The process group of the caller, which becomes the
+    ///   foreground process group
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::OperationNotPermitted`] if the terminal already
+    /// controls another session.
+    pub fn set_controlling(&self, sid: Pid, pgrp: Pid) -> VfsResult {
+        let mut state = self.state.lock();
+        match state.session {
+            Some(session) if session != sid => Err(VfsError::OperationNotPermitted),
+            Some(_) => Ok(()),
+            None => {
+                state.session = Some(sid);
+                state.foreground = Some(pgrp);
+                state.hung_up = false;
+                Ok(())
+            }
+        }
+    }
+
+    /// Detaches the terminal from a session, as `TIOCNOTTY` from its session
+    /// leader.
+    ///
+    /// The foreground process group is sent [`Signal::Hangup`] and
+    /// [`Signal::Continue`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotATty`] if the terminal does not control the
+    /// session `sid`.
+    pub fn release_controlling(&self, sid: Pid) -> VfsResult {
+        let foreground = {
+            let mut state = self.state.lock();
+            if state.session != Some(sid) {
+                return Err(VfsError::NotATty);
+            }
+            state.session = None;
+            state.foreground.take()
+        };
+        if let Some(pgrp) = foreground {
+            self.signals.signal_group(pgrp, Signal::Hangup);
+            self.signals.signal_group(pgrp, Signal::Continue);
+        }
+        Ok(())
+    }
+
+    /// Sets the foreground process group, as `TIOCSPGRP`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sid` - The session of the caller
+    /// * `pgrp` - The new foreground process group, in the same session
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotATty`] if the terminal does not control the
+    /// session `sid`.
+    pub fn set_foreground(&self, sid: Pid, pgrp: Pid) -> VfsResult {
+        let mut state = self.state.lock();
+        if state.session != Some(sid) {
+            return Err(VfsError::NotATty);
+        }
+        state.foreground = Some(pgrp);
+        Ok(())
+    }
+
+    /// Checks that a process may read from the terminal.
+    ///
+    /// A background process group of the controlling session is sent
+    /// [`Signal::BackgroundRead`] instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `pgrp` - The process group of the caller
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::Interrupted`] if the caller is in the background,
+    /// so the read is restarted once the caller is resumed in the
+    /// foreground.
+    pub fn check_read(&self, pgrp: Pid) -> VfsResult {
+        let foreground = self.state.lock().foreground;
+        match foreground {
+            Some(fg) if fg != pgrp => {
+                self.signals.signal_group(pgrp, Signal::BackgroundRead);
+                Err(VfsError::Interrupted)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Pushes input received by the driver.
+    ///
+    /// The interrupt, quit and suspend characters signal the foreground
+    /// process group and discard the pending input, the other characters
+    /// are queued for [`read_at()`](axfs_vfs::VfsNodeOps::read_at), and wake
+    /// the readers waiting for [`PollEvents::IN`].
+    pub fn receive(&self, data: &[u8]) {
+        let (signals, readable) = {
+            let mut state = self.state.lock();
+            let signals = Self::queue_input(&mut state, data);
+            (signals, !state.input.is_empty())
+        };
+        for (pgrp, signal) in signals {
+            self.signals.signal_group(pgrp, signal);
+        }
+        if readable {
+            self.wakers.wake(PollEvents::IN);
+        }
+    }
+
+    /// Queues `data` as input, and returns the signals to send to the
+    /// foreground process group for the signal characters.
+    ///
+    /// The signals are sent by the caller once the state is unlocked, as the
+    /// signal sink may call back into the terminal.
+    fn queue_input(state: &mut TtyState, data: &[u8]) -> Vec<(Pid, Signal)> {
+        let mut signals = Vec::new();
+        for &byte in data {
+            let signal = match byte {
+                INTR_CHAR => Signal::Interrupt,
+                QUIT_CHAR => Signal::Quit,
+                SUSP_CHAR => Signal::Suspend,
+                _ => {
+                    state.input.push_back(byte);
+                    continue;
+                }
+            };
+            state.input.clear();
+            if let Some(pgrp) = state.foreground {
+                signals.push((pgrp, signal));
+            }
+        }
+        signals
+    }
+
+    /// Reports that the terminal hung up, such as a closed pseudo-terminal
+    /// master or a lost carrier.
+    ///
+    /// The session leader and the foreground process group are sent
+    /// [`Signal::Hangup`] and [`Signal::Continue`], and the terminal no
+    /// longer controls the session. Until it controls a session again, reads
+    /// return end of file and [`PollEvents::HUP`] is reported, which wakes
+    /// every waiting waker.
+    pub fn hangup(&self) {
+        let (session, foreground) = {
+            let mut state = self.state.lock();
+            state.input.clear();
+            state.hung_up = true;
+            (state.session.take(), state.foreground.take())
+        };
+        self.wakers.wake(PollEvents::HUP);
+        // the session ID is the process group of the session leader
+        let mut groups = [session, foreground];
+        if session == foreground {
+            groups[1] = None;
+        }
+        for pgrp in groups.into_iter().flatten() {
+            self.signals.signal_group(pgrp, Signal::Hangup);
+            self.signals.signal_group(pgrp, Signal::Continue);
+        }
+    }
+}
+
+impl axfs_vfs::VfsFileNodeOps for TtyDev {
+    /// Returns the state counting the open handles of the device.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Returns attributes of the terminal.
+    ///
+    /// # Returns
+    ///
+    /// Returns character device attributes with zero size, readable and
+    /// writable by the owner and writable by the group (`0o620`). The inode
+    /// number is the raw device number of the terminal.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        Ok(VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o620),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        )
+        .with_ino(self.dev.to_raw()))
+    }
+
+    /// Reads the received input.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes read, `0` once the terminal hung up.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::WouldBlock`] if there is no input, so the caller
+    /// waits for the driver.
+    fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let mut state = self.state.lock();
+        if state.input.is_empty() && !buf.is_empty() && !state.hung_up {
+            return Err(VfsError::WouldBlock);
+        }
+        let len = buf.len().min(state.input.len());
+        for (dst, src) in buf.iter_mut().zip(state.input.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+
+    /// Writes to the terminal through its driver.
+    fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.driver.write(buf)
+    }
+
+    /// Truncates the terminal (no effect).
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Ok(())
+    }
+
+    /// Answers the job control queries `TIOCGPGRP` and `TIOCGSID`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the foreground process group or the session ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotATty`] if the terminal controls no session, or
+    /// for other commands.
+    fn ioctl(&self, cmd: u32, _arg: usize) -> VfsResult<usize> {
+        let state = self.state.lock();
+        let id = match cmd {
+            TIOCGPGRP => state.foreground,
+            TIOCGSID => state.session,
+            _ => None,
+        };
+        id.map(|id| id as usize).ok_or(VfsError::NotATty)
+    }
+
+    /// Returns the readiness of the terminal.
+    ///
+    /// # Returns
+    ///
+    /// Returns [`PollEvents::OUT`], as writes go straight to the driver,
+    /// with [`PollEvents::IN`] if there is input and [`PollEvents::HUP`] if
+    /// the terminal hung up.
+    fn poll(&self) -> VfsResult<PollEvents> {
+        let state = self.state.lock();
+        let mut events = PollEvents::OUT;
+        events.set(PollEvents::IN, !state.input.is_empty());
+        events.set(PollEvents::HUP, state.hung_up);
+        Ok(events)
+    }
+
+    /// Registers a waker, woken when input arrives or the terminal hangs up.
+    fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+        self.wakers.register(events, waker);
+        Ok(())
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(TtyDev);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::sync::Weak;
+    use axfs_vfs::VfsNodeOps;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(Pid, Signal)>>);
+
+    impl SignalSink for Recorder {
+        fn signal_group(&self, pgrp: Pid, signal: Signal) {
+            self.0.lock().push((pgrp, signal));
+        }
+    }
+
+    impl TtyDriver for Recorder {
+        fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+            Ok(buf.len())
+        }
+    }
+
+    fn tty() -> (TtyDev, Arc<Recorder>) {
+        let recorder = Arc::new(Recorder::default());
+        let tty = TtyDev::new(DeviceId::new(4, 64), recorder.clone(), recorder.clone());
+        (tty, recorder)
+    }
+
+    #[test]
+    fn test_tty_controlling_session() {
+        let (tty, signals) = tty();
+        assert_eq!(tty.ioctl(TIOCGSID, 0), Err(VfsError::NotATty));
+        tty.set_controlling(10, 10).unwrap();
+        assert_eq!(
+            tty.set_controlling(20, 20),
+            Err(VfsError::OperationNotPermitted)
+        );
+        assert_eq!(tty.ioctl(TIOCGSID, 0), Ok(10));
+        assert_eq!(tty.set_foreground(20, 21), Err(VfsError::NotATty));
+        tty.set_foreground(10, 11).unwrap();
+        assert_eq!(tty.ioctl(TIOCGPGRP, 0), Ok(11));
+
+        assert_eq!(tty.release_controlling(20), Err(VfsError::NotATty));
+        tty.release_controlling(10).unwrap();
+        assert_eq!(tty.session(), None);
+        assert_eq!(
+            *signals.0.lock(),
+            [(11, Signal::Hangup), (11, Signal::Continue)]
+        );
+    }
+
+    #[test]
+    fn test_tty_input_signals() {
+        let (tty, signals) = tty();
+        // no foreground group to signal yet
+        tty.receive(b"a\x03");
+        assert!(signals.0.lock().is_empty());
+
+        tty.set_controlling(10, 12).unwrap();
+        tty.receive(b"ls\x1cpwd\n\x1a");
+        tty.receive(b"x");
+        assert_eq!(
+            *signals.0.lock(),
+            [(12, Signal::Quit), (12, Signal::Suspend)]
+        );
+        // the signal characters discard the pending input
+        let mut buf = [0; 8];
+        assert_eq!(tty.read_at(0, &mut buf), Ok(1));
+        assert_eq!(buf[0], b'x');
+        assert_eq!(tty.read_at(0, &mut buf), Err(VfsError::WouldBlock));
+    }
+
+    #[test]
+    fn test_tty_signal_reentry() {
+        // a sink looking up the terminal it is signalled from
+        #[derive(Default)]
+        struct Reentrant {
+            tty: spin::Once<Weak<TtyDev>>,
+            seen: Mutex<Vec<Option<Pid>>>,
+        }
+
+        impl SignalSink for Reentrant {
+            fn signal_group(&self, _pgrp: Pid, _signal: Signal) {
+                let tty = self.tty.get().and_then(Weak::upgrade).unwrap();
+                self.seen.lock().push(tty.foreground());
+            }
+        }
+
+        let sink = Arc::new(Reentrant::default());
+        let tty = Arc::new(TtyDev::new(
+            DeviceId::new(4, 64),
+            Arc::new(Recorder::default()),
+            sink.clone(),
+        ));
+        sink.tty.call_once(|| Arc::downgrade(&tty));
+        tty.set_controlling(10, 12).unwrap();
+        tty.receive(b"\x03");
+        assert_eq!(*sink.seen.lock(), [Some(12)]);
+    }
+
+    #[test]
+    fn test_tty_background_read() {
+        let (tty, signals) = tty();
+        assert_eq!(tty.check_read(13), Ok(()));
+        tty.set_controlling(10, 10).unwrap();
+        assert_eq!(tty.check_read(10), Ok(()));
+        assert_eq!(tty.check_read(13), Err(VfsError::Interrupted));
+        assert_eq!(*signals.0.lock(), [(13, Signal::BackgroundRead)]);
+    }
+
+    #[test]
+    fn test_tty_hangup() {
+        let (tty, signals) = tty();
+        tty.set_controlling(10, 10).unwrap();
+        tty.hangup();
+        assert_eq!(
+            *signals.0.lock(),
+            [(10, Signal::Hangup), (10, Signal::Continue)]
+        );
+        signals.0.lock().clear();
+
+        tty.set_controlling(20, 20).unwrap();
+        tty.set_foreground(20, 21).unwrap();
+        tty.hangup();
+        assert_eq!(signals.0.lock().len(), 4);
+        assert_eq!(tty.foreground(), None);
+        assert_eq!(Signal::Hangup.number(), 1);
+    }
+
+    #[test]
+    fn test_tty_wakers() {
+        use alloc::task::Wake;
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        #[derive(Default)]
+        struct Counter(AtomicUsize);
+
+        impl Wake for Counter {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let (tty, _) = tty();
+        let counter = Arc::new(Counter::default());
+        let waker = Waker::from(counter.clone());
+        assert_eq!(tty.poll(), Ok(PollEvents::OUT));
+        tty.register_waker(PollEvents::IN, &waker).unwrap();
+
+        // signal characters alone leave nothing to read
+        tty.receive(b"\x03");
+        assert_eq!(counter.0.load(Ordering::Relaxed), 0);
+        tty.receive(b"a");
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert_eq!(tty.poll(), Ok(PollEvents::IN | PollEvents::OUT));
+        // the waker was consumed
+        tty.receive(b"b");
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+
+        let mut buf = [0; 4];
+        assert_eq!(tty.read_at(0, &mut buf), Ok(2));
+        tty.register_waker(PollEvents::IN, &waker).unwrap();
+        tty.hangup();
+        assert_eq!(counter.0.load(Ordering::Relaxed), 2);
+        assert_eq!(tty.poll(), Ok(PollEvents::OUT | PollEvents::HUP));
+        assert_eq!(tty.read_at(0, &mut buf), Ok(0));
+
+        tty.set_controlling(10, 10).unwrap();
+        assert_eq!(tty.read_at(0, &mut buf), Err(VfsError::WouldBlock));
+    }
+}
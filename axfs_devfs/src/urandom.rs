@@ -1,4 +1,5 @@
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{DeviceId, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsResult};
 use core::sync::atomic::{AtomicU64, Ordering};
 
 /// A urandom device behaves like `/dev/urandom`.
@@ -22,8 +23,23 @@ use core::sync::atomic::{AtomicU64, Ordering};
 ///
 /// Uses a 64-bit LCG with the formula: `seed = seed * m + c`
 /// where `m = 6364136223846793005` and `c = 1`.
+///
+/// # Reseeding
+///
+/// With [`with_reseed_limit()`](Self::with_reseed_limit), the device
+/// reseeds itself after producing the given number of bytes from one seed.
+/// The new seed mixes the current state with a value drawn from the entropy
+/// source set by [`with_entropy_source()`](Self::with_entropy_source), if
+/// any. [`fork()`](Self::fork) forces a reseed, and should be called when
+/// the system is duplicated (a forked VM, a restored snapshot), so that the
+/// copies do not produce the same stream.
 pub struct UrandomDev {
     seed: AtomicU64,
+    seed_bytes: AtomicU64,
+    reseeds: AtomicU64,
+    reseed_limit: u64,
+    entropy: Option<fn() -> u64>,
+    opens: OpenState,
 }
 
 impl UrandomDev {
@@ -39,9 +55,75 @@ impl UrandomDev {
     pub const fn new(seed: u64) -> Self {
         Self {
             seed: AtomicU64::new(seed),
+            seed_bytes: AtomicU64::new(0),
+            reseeds: AtomicU64::new(0),
+            reseed_limit: 0,
+            entropy: None,
+            opens: OpenState::new(),
         }
     }
 
+    /// Sets the number of bytes produced from one seed before a reseed.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The maximum number of bytes per seed, or 0 to never
+    ///   reseed automatically (the default)
+    ///
+    /// # Returns
+    ///
+    /// The device with the new limit.
+    pub const fn with_reseed_limit(mut self, limit: u64) -> Self {
+        self.reseed_limit = limit;
+        self
+    }
+
+    /// Sets the entropy source mixed into the state at every reseed.
+    ///
+    /// # Arguments
+    ///
+    /// * `entropy` - A function returning fresh entropy, such as a timer
+    ///   counter or a hardware random number
+    ///
+    /// # Returns
+    ///
+    /// The device with the new entropy source.
+    pub const fn with_entropy_source(mut self, entropy: fn() -> u64) -> Self {
+        self.entropy = Some(entropy);
+        self
+    }
+
+    /// Returns the number of bytes produced from one seed before a reseed,
+    /// or 0 if the device never reseeds automatically.
+    pub const fn reseed_limit(&self) -> u64 {
+        self.reseed_limit
+    }
+
+    /// Returns the number of reseeds since the device was created.
+    pub fn reseed_count(&self) -> u64 {
+        self.reseeds.load(Ordering::SeqCst)
+    }
+
+    /// Notifies the device that the system has been duplicated.
+    ///
+    /// This forces a reseed, so that the stream after the call differs from
+    /// the one the device would have produced. Copies of a system only
+    /// diverge if their entropy sources return different values.
+    pub fn fork(&self) {
+        self.reseed();
+    }
+
+    /// Replaces the seed by a mix of the current state, the entropy source
+    /// and the number of reseeds, and restarts the byte count.
+    fn reseed(&self) {
+        let entropy = self.entropy.map_or(0, |entropy| entropy());
+        let count = self.reseeds.fetch_add(1, Ordering::SeqCst) + 1;
+        let mixed =
+            self.seed.load(Ordering::SeqCst) ^ entropy ^ count.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        self.seed.store(splitmix64(mixed), Ordering::SeqCst);
+        self.seed_bytes.store(0, Ordering::SeqCst);
+    }
+
     /// Creates a new instance with a default seed.
     ///
     /// The default seed value is `0xa2ce_a2ce`.
@@ -72,6 +154,14 @@ impl UrandomDev {
     }
 }
 
+/// Scrambles `x` with the finalizer of the SplitMix64 generator.
+fn splitmix64(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
 impl Default for UrandomDev {
     /// Creates a default urandom device instance.
     ///
@@ -81,26 +171,31 @@ impl Default for UrandomDev {
     }
 }
 
-impl VfsNodeOps for UrandomDev {
+impl axfs_vfs::VfsFileNodeOps for UrandomDev {
+    /// Returns the state counting the open handles of the device.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
     /// Returns attributes of the urandom device.
     ///
     /// # Returns
     ///
-    /// Returns character device attributes with zero size.
+    /// Returns character device attributes with zero size. The inode number
+    /// is the raw device number of `/dev/urandom` (`1:9`), so it is stable.
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::default_file(),
-            VfsNodeType::CharDevice,
-            0,
-            0,
-        ))
+        Ok(
+            VfsNodeAttr::new(VfsNodePerm::default_file(), VfsNodeType::CharDevice, 0, 0)
+                .with_ino(DeviceId::new(1, 9).to_raw()),
+        )
     }
 
     /// Reads pseudo-random bytes from the device.
     ///
     /// This method fills the buffer with pseudo-random data.
     /// The offset parameter is ignored as this device generates
-    /// fresh random data on each read.
+    /// fresh random data on each read. If a reseed limit is set, the device
+    /// reseeds whenever the limit is reached, even in the middle of a read.
     ///
     /// # Arguments
     ///
@@ -112,6 +207,12 @@ impl VfsNodeOps for UrandomDev {
     /// Always returns the buffer length.
     fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
         for chunk in buf.chunks_mut(8) {
+            if self.reseed_limit != 0 && self.seed_bytes.load(Ordering::SeqCst) >= self.reseed_limit
+            {
+                self.reseed();
+            }
+            self.seed_bytes
+                .fetch_add(chunk.len() as u64, Ordering::SeqCst);
             let random_value = self.next_u64();
             let bytes = random_value.to_ne_bytes();
             for (i, byte) in chunk.iter_mut().enumerate() {
@@ -153,13 +254,14 @@ impl VfsNodeOps for UrandomDev {
     fn truncate(&self, _size: u64) -> VfsResult {
         Ok(())
     }
-
-    axfs_vfs::impl_vfs_non_dir_default! {}
 }
 
+axfs_vfs::impl_vfs_file_node!(UrandomDev);
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axfs_vfs::VfsNodeOps;
 
     #[test]
     fn test_urandom_new() {
@@ -304,4 +406,51 @@ mod tests {
         let all_zeros = buf1.iter().all(|&b| b == 0);
         assert!(!all_zeros);
     }
+
+    #[test]
+    fn test_urandom_reseed_limit() {
+        let limited = UrandomDev::new(12345).with_reseed_limit(16);
+        let plain = UrandomDev::new(12345);
+        assert_eq!(limited.reseed_limit(), 16);
+
+        let mut buf1 = [0; 16];
+        let mut buf2 = [0; 16];
+        limited.read_at(0, &mut buf1).unwrap();
+        plain.read_at(0, &mut buf2).unwrap();
+        assert_eq!(buf1, buf2);
+        assert_eq!(limited.reseed_count(), 0);
+
+        // The limit is reached: the next bytes come from a new seed
+        limited.read_at(0, &mut buf1).unwrap();
+        plain.read_at(0, &mut buf2).unwrap();
+        assert_ne!(buf1, buf2);
+        assert_eq!(limited.reseed_count(), 1);
+
+        let mut big = [0; 64];
+        limited.read_at(0, &mut big).unwrap();
+        assert_eq!(limited.reseed_count(), 5);
+        assert_eq!(plain.reseed_count(), 0);
+    }
+
+    #[test]
+    fn test_urandom_fork() {
+        let parent = UrandomDev::new(12345);
+        let child = UrandomDev::new(12345);
+        child.fork();
+        assert_eq!(child.reseed_count(), 1);
+        let mut buf1 = [0; 32];
+        let mut buf2 = [0; 32];
+        parent.read_at(0, &mut buf1).unwrap();
+        child.read_at(0, &mut buf2).unwrap();
+        assert_ne!(buf1, buf2);
+
+        // Copies forked with different entropy diverge from each other
+        let a = UrandomDev::new(1).with_entropy_source(|| 1);
+        let b = UrandomDev::new(1).with_entropy_source(|| 2);
+        a.fork();
+        b.fork();
+        a.read_at(0, &mut buf1).unwrap();
+        b.read_at(0, &mut buf2).unwrap();
+        assert_ne!(buf1, buf2);
+    }
 }
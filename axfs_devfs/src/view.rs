@@ -0,0 +1,378 @@
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{
+    DirEntries, FileSystemInfo, MountOptions, VfsCapabilities, VfsDirEntry, VfsError, VfsFeatures,
+    VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeRefExt, VfsNodeType, VfsOps, VfsResult,
+};
+use spin::RwLock;
+
+use crate::DirNode;
+
+/// A filtered view of a [`DeviceFileSystem`](crate::DeviceFileSystem).
+///
+/// A view shows a subset of the devices of a device filesystem, such as the
+/// restricted `/dev` of a container. It is a filesystem of its own, but the
+/// devices it shows are the nodes of the device filesystem, so they are not
+/// duplicated, and devices added to the filesystem later appear in the view
+/// if they are allowed. The view is read-only, like the device filesystem.
+///
+/// Views are created with [`DeviceFileSystem::view()`](crate::DeviceFileSystem::view).
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_devfs::{DeviceFileSystem, NullDev, ZeroDev};
+/// use axfs_vfs::{VfsError, VfsOps};
+///
+/// let devfs = DeviceFileSystem::new();
+/// devfs.add("null", Arc::new(NullDev));
+/// devfs.add("zero", Arc::new(ZeroDev));
+///
+/// let view = devfs.view(&["null"]);
+/// let root = view.root_dir();
+/// assert!(root.clone().lookup("null").is_ok());
+/// assert_eq!(root.lookup("zero").err(), Some(VfsError::NotFound));
+/// ```
+pub struct DeviceView {
+    root: Arc<ViewDir>,
+}
+
+/// A directory of a [`DeviceView`].
+///
+/// Every directory reached through a view is a view directory, whatever the
+/// type of the directory of the device filesystem it shows, so that `..`
+/// always goes through the view and never leaves it.
+///
+/// # Fields
+///
+/// - `dir` - The directory of the device filesystem shown by this directory
+/// - `path` - The path of the directory from the root of the view, without
+///   leading or trailing `/` (empty for the root directory)
+/// - `allowed` - The allowed paths of the view, shared by all its
+///   directories
+/// - `parent` - The parent directory, or the mount point parent for the root
+///   directory
+struct ViewDir {
+    dir: VfsNodeRef,
+    path: String,
+    allowed: Arc<BTreeSet<String>>,
+    parent: RwLock<Option<VfsNodeRef>>,
+}
+
+impl DeviceView {
+    /// Creates a view of the directory `root` showing only `allowed` paths.
+    pub(crate) fn new(root: Arc<DirNode>, allowed: &[&str]) -> Self {
+        let root: VfsNodeRef = root;
+        let allowed = allowed
+            .iter()
+            .map(|path| String::from(path.trim_matches('/')))
+            .collect();
+        Self {
+            root: Arc::new(ViewDir {
+                dir: root,
+                path: String::new(),
+                allowed: Arc::new(allowed),
+                parent: RwLock::new(None),
+            }),
+        }
+    }
+
+    /// Returns whether the node at `path` is visible in the view.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the node from the root of the view, such as
+    ///   `"pts/0"`
+    ///
+    /// # Returns
+    ///
+    /// `true` if the node is allowed, or is inside an allowed directory, or
+    /// is a directory containing an allowed node.
+    pub fn is_visible(&self, path: &str) -> bool {
+        is_visible(&self.root.allowed, path.trim_matches('/'))
+    }
+}
+
+/// Returns whether `path` is visible with the `allowed` paths.
+fn is_visible(allowed: &BTreeSet<String>, path: &str) -> bool {
+    allowed
+        .iter()
+        .any(|allowed| allowed.is_empty() || is_within(path, allowed) || is_within(allowed, path))
+}
+
+/// Returns whether `path` is `dir` or inside it.
+fn is_within(path: &str, dir: &str) -> bool {
+    path.strip_prefix(dir)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+impl ViewDir {
+    /// Returns the path of the child `name` of this directory.
+    fn child_path(&self, name: &str) -> String {
+        if self.path.is_empty() {
+            String::from(name)
+        } else {
+            alloc::format!("{}/{name}", self.path)
+        }
+    }
+
+    /// Returns the entries of the visible children of this directory,
+    /// without `.` and `..`.
+    fn visible_entries(&self) -> VfsResult<Vec<VfsDirEntry>> {
+        let mut entries = DirEntries::new(self.dir.as_ref()).collect::<VfsResult<Vec<_>>>()?;
+        entries.retain(|entry| {
+            !matches!(entry.name_as_bytes(), b"." | b"..")
+                && is_visible(&self.allowed, &self.child_path(entry.name()))
+        });
+        Ok(entries)
+    }
+
+    /// Returns the visible child `name` of this directory, wrapping
+    /// directories in a view directory.
+    fn child(self: &Arc<Self>, name: &str) -> VfsResult<VfsNodeRef> {
+        let path = self.child_path(name);
+        if !is_visible(&self.allowed, &path) {
+            return Err(VfsError::NotFound);
+        }
+        let node = self.dir.clone().lookup(name)?;
+        if !node.get_attr()?.is_dir() {
+            return Ok(node);
+        }
+        Ok(Arc::new(ViewDir {
+            dir: node,
+            path,
+            allowed: self.allowed.clone(),
+            parent: RwLock::new(Some(self.clone())),
+        }))
+    }
+}
+
+impl axfs_vfs::VfsDirNodeOps for ViewDir {
+    /// Returns the open state of the directory of the device filesystem.
+    fn open_state(&self) -> Option<&OpenState> {
+        self.dir.open_state().map(|(state, _)| state)
+    }
+
+    /// Does what the directory of the device filesystem does when its last
+    /// open handle is closed.
+    fn on_last_release(&self) -> VfsResult {
+        self.dir.on_last_release()
+    }
+
+    /// Returns the attributes of the directory of the device filesystem.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.dir.get_attr()
+    }
+
+    /// Returns the parent directory of this directory in the view.
+    fn parent(&self) -> Option<VfsNodeRef> {
+        self.parent.read().clone()
+    }
+
+    /// Lookups a visible node with the given path.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if the node does not exist or is not
+    /// visible in the view.
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let path = path.trim_start_matches('/');
+        let (name, rest) = path
+            .split_once('/')
+            .map_or((path, None), |(n, r)| (n, Some(r)));
+        let node = match name {
+            "" | "." => self.clone() as VfsNodeRef,
+            ".." => VfsNodeOps::parent(&*self).ok_or(VfsError::NotFound)?,
+            _ => self.child(name)?,
+        };
+        match rest {
+            Some(rest) => node.lookup(rest),
+            None => Ok(node),
+        }
+    }
+
+    /// Reads the visible entries of this directory.
+    ///
+    /// The first two entries are always `.` and `..`, followed by the
+    /// visible nodes.
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        let ino = self.dir.get_attr()?.ino();
+        let parent_ino = VfsNodeOps::parent(self)
+            .and_then(|parent| parent.get_attr().ok())
+            .map_or(ino, |attr| attr.ino());
+        let entries = self.visible_entries()?;
+        let mut entries = entries.into_iter().skip(start_idx.max(2) - 2);
+        for (i, ent) in dirents.iter_mut().enumerate() {
+            match i + start_idx {
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir).with_ino(ino),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir).with_ino(parent_ino),
+                _ => match entries.next() {
+                    Some(entry) => *ent = entry,
+                    None => return Ok(i),
+                },
+            }
+        }
+        Ok(dirents.len())
+    }
+
+    /// Creating nodes is not supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`].
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+
+    /// Removing nodes is not supported.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`].
+    fn remove(&self, _path: &str) -> VfsResult {
+        Err(VfsError::PermissionDenied)
+    }
+}
+
+axfs_vfs::impl_vfs_dir_node!(ViewDir);
+
+impl VfsOps for DeviceView {
+    /// Mounts the view, setting the parent of its root directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `_path` - The mount path (not used)
+    /// * `mount_point` - The mount point directory node
+    /// * `options` - The options of the mount, none of which is
+    ///   filesystem-specific
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] for filesystem-specific options.
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef, options: &MountOptions) -> VfsResult {
+        options.check_known(&[])?;
+        *self.root.parent.write() = mount_point.parent();
+        Ok(())
+    }
+
+    /// Returns the attributes of the view.
+    ///
+    /// # Returns
+    ///
+    /// The attributes of a device filesystem, counting the visible nodes
+    /// only.
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        fn count(dir: &Arc<ViewDir>) -> VfsResult<u64> {
+            let mut files = 1;
+            for entry in dir.visible_entries()? {
+                files += match dir.child(entry.name()) {
+                    Ok(node) => match node.downcast::<ViewDir>() {
+                        Ok(sub) => count(&sub)?,
+                        Err(_) => 1,
+                    },
+                    Err(_) => 0,
+                };
+            }
+            Ok(files)
+        }
+        Ok(
+            FileSystemInfo::new(self.fs_magic(), 4096, axfs_vfs::limits::MAX_NAME_LEN as u64)
+                .with_files(count(&self.root)?, 0),
+        )
+    }
+
+    /// Returns the capabilities of the view, which are those of the device
+    /// filesystem.
+    fn capabilities(&self) -> VfsCapabilities {
+        VfsCapabilities::new(
+            VfsFeatures::CASE_SENSITIVE | VfsFeatures::READ_ONLY,
+            axfs_vfs::limits::MAX_NAME_LEN,
+        )
+    }
+
+    /// Returns `"devfs"`.
+    fn fs_type(&self) -> &str {
+        "devfs"
+    }
+
+    /// Returns `DEVFS_SUPER_MAGIC` (`0x1373`).
+    fn fs_magic(&self) -> u64 {
+        0x1373
+    }
+
+    /// Returns the root directory of the view.
+    fn root_dir(&self) -> VfsNodeRef {
+        self.root.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DeviceFileSystem, DiskInfo, NullDev, ZeroDev};
+
+    #[test]
+    fn test_is_visible() {
+        let allowed: BTreeSet<String> = ["pts/0", "input"].map(String::from).into();
+        assert!(is_visible(&allowed, "pts"));
+        assert!(is_visible(&allowed, "pts/0"));
+        assert!(!is_visible(&allowed, "pts/1"));
+        assert!(!is_visible(&allowed, "pts/00"));
+        assert!(is_visible(&allowed, "input"));
+        assert!(is_visible(&allowed, "input/mice"));
+        assert!(!is_visible(&allowed, "null"));
+        // an empty path allows everything
+        assert!(is_visible(&[String::new()].into(), "null"));
+    }
+
+    #[test]
+    fn test_view_read_dir() {
+        let root = DirNode::new(None);
+        root.add("null", Arc::new(NullDev));
+        root.add("zero", Arc::new(ZeroDev));
+        root.mkdir("pts").add("0", Arc::new(NullDev));
+        let view = DeviceView::new(root.clone(), &["/zero", "pts/"]);
+
+        let mut entries: [VfsDirEntry; 5] = core::array::from_fn(|_| VfsDirEntry::default());
+        let view_root = view.root_dir();
+        assert_eq!(view_root.read_dir(0, &mut entries), Ok(4));
+        assert_eq!(entries[2].name_as_bytes(), b"pts");
+        assert_eq!(entries[3].name_as_bytes(), b"zero");
+        assert_eq!(entries[0].ino(), root.get_attr().unwrap().ino());
+        assert_eq!(view_root.read_dir(3, &mut entries), Ok(1));
+        assert_eq!(entries[0].name_as_bytes(), b"zero");
+    }
+
+    #[test]
+    fn test_view_link_dir_parent() {
+        let devfs = DeviceFileSystem::new();
+        devfs.add("secret", Arc::new(NullDev));
+        devfs
+            .add_disk("sda", Arc::new(NullDev), DiskInfo::new().with_serial("S1"))
+            .unwrap();
+        let view = devfs.view(&["disk/by-id"]);
+        let view_root = view.root_dir();
+
+        let by_id = view_root.clone().lookup("disk/by-id").unwrap();
+        assert!(by_id.clone().downcast::<ViewDir>().is_ok());
+        let disk = by_id.parent().unwrap();
+        assert!(disk.clone().downcast::<ViewDir>().is_ok());
+        assert!(Arc::ptr_eq(&disk.parent().unwrap(), &view_root));
+        assert_eq!(
+            view_root.clone().lookup("disk/by-id/../../secret").err(),
+            Some(VfsError::NotFound)
+        );
+        assert_eq!(
+            view_root.lookup("disk/by-id/../../sda").err(),
+            Some(VfsError::NotFound)
+        );
+    }
+}
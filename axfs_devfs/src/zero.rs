@@ -1,4 +1,5 @@
-use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
+use axfs_vfs::page::PAGE_SIZE;
+use axfs_vfs::{DeviceId, VfsError, VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsPage, VfsResult};
 
 /// A zero device behaves like `/dev/zero`.
 ///
@@ -11,25 +12,25 @@ use axfs_vfs::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType, VfsResult};
 /// - Read operations: Always return buffer filled with null bytes
 /// - Write operations: Accept all data but discard it
 /// - Truncate operations: Always succeed with no effect
+/// - Memory mappings: Anonymous zero-filled pages
 ///
 /// # Unix Equivalent
 ///
 /// This device behaves similarly to `/dev/zero` in Unix-like systems.
 pub struct ZeroDev;
 
-impl VfsNodeOps for ZeroDev {
+impl axfs_vfs::VfsFileNodeOps for ZeroDev {
     /// Returns attributes of the zero device.
     ///
     /// # Returns
     ///
-    /// Returns character device attributes with zero size.
+    /// Returns character device attributes with zero size. The inode number
+    /// is the raw device number of `/dev/zero` (`1:5`), so it is stable.
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new(
-            VfsNodePerm::default_file(),
-            VfsNodeType::CharDevice,
-            0,
-            0,
-        ))
+        Ok(
+            VfsNodeAttr::new(VfsNodePerm::default_file(), VfsNodeType::CharDevice, 0, 0)
+                .with_ino(DeviceId::new(1, 5).to_raw()),
+        )
     }
 
     /// Reads from the zero device.
@@ -80,12 +81,33 @@ impl VfsNodeOps for ZeroDev {
         Ok(())
     }
 
-    axfs_vfs::impl_vfs_non_dir_default! {}
+    /// Returns a page of the zero device to map in memory.
+    ///
+    /// Mapping `/dev/zero` creates an anonymous mapping, so every page is a
+    /// private page of zeros.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset of the page, a multiple of [`PAGE_SIZE`]
+    ///
+    /// # Returns
+    ///
+    /// Returns [`VfsPage::Zero`], or [`VfsError::InvalidInput`] if `offset`
+    /// is not aligned.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        if !offset.is_multiple_of(PAGE_SIZE as u64) {
+            return Err(VfsError::InvalidInput);
+        }
+        Ok(VfsPage::Zero)
+    }
 }
 
+axfs_vfs::impl_vfs_file_node!(ZeroDev);
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axfs_vfs::VfsNodeOps;
 
     #[test]
     fn test_zero_dev_get_attr() {
@@ -116,6 +138,13 @@ mod tests {
         assert_eq!(buf, [0; 50]);
     }
 
+    #[test]
+    fn test_zero_dev_get_page() {
+        assert!(matches!(ZeroDev.get_page(0), Ok(VfsPage::Zero)));
+        assert!(matches!(ZeroDev.get_page(1 << 30), Ok(VfsPage::Zero)));
+        assert!(matches!(ZeroDev.get_page(1), Err(VfsError::InvalidInput)));
+    }
+
     #[test]
     fn test_zero_dev_read_empty() {
         let zero = ZeroDev;
@@ -3,8 +3,15 @@
 //! These tests verify the functionality of the device filesystem
 //! using actual implementations rather than mocks.
 
-use axfs_devfs::{DeviceFileSystem, NullDev, UrandomDev, ZeroDev};
-use axfs_vfs::{VfsError, VfsNodeOps, VfsNodeType, VfsOps, VfsResult};
+use axfs_devfs::{
+    DeviceFileSystem, DeviceListener, DiskInfo, NullDev, Pid, ReadOnlyDev, Signal, SignalSink,
+    TtyDev, TtyDriver, UrandomDev, ZeroDev,
+};
+use axfs_vfs::handle::VfsFileHandle;
+use axfs_vfs::{
+    DeviceId, DeviceResolver, MountOptions, OpenFlags, VfsError, VfsFeatures, VfsNodeOps,
+    VfsNodeRef, VfsNodeType, VfsOps, VfsResult,
+};
 use std::sync::Arc;
 
 fn test_devfs_ops(devfs: &DeviceFileSystem) -> VfsResult {
@@ -140,6 +147,61 @@ fn test_devfs_root_dir() {
     assert_eq!(root.get_attr().unwrap().file_type(), VfsNodeType::Dir);
 }
 
+#[test]
+fn test_devfs_capabilities() {
+    let fs = DeviceFileSystem::new();
+    let caps = fs.capabilities();
+    assert!(caps.supports(VfsFeatures::READ_ONLY));
+    assert!(caps.supports(VfsFeatures::CASE_SENSITIVE));
+    assert!(!caps.supports(VfsFeatures::SYMLINK));
+    assert!(!caps.supports(VfsFeatures::HARDLINK));
+}
+
+#[test]
+fn test_devfs_mount_mode_option() {
+    let fs = DeviceFileSystem::new();
+    let root = fs.root_dir();
+    let sub = fs.mkdir("input");
+    assert_eq!(root.get_attr().unwrap().perm().bits(), 0o755);
+
+    let opts = MountOptions::parse("mode=0700").unwrap();
+    fs.mount("/dev", root.clone(), &opts).unwrap();
+    assert_eq!(root.get_attr().unwrap().perm().bits(), 0o700);
+    assert_eq!(sub.get_attr().unwrap().perm().bits(), 0o755);
+
+    for bad in ["mode=999", "mode=04000", "size=1m"] {
+        let opts = MountOptions::parse(bad).unwrap();
+        assert_eq!(
+            fs.mount("/dev", root.clone(), &opts),
+            Err(VfsError::InvalidInput)
+        );
+    }
+}
+
+#[test]
+fn test_devfs_fs_type() {
+    let fs = DeviceFileSystem::new();
+    assert_eq!(fs.fs_type(), "devfs");
+    assert_eq!(fs.fs_magic(), 0x1373);
+}
+
+#[test]
+fn test_devfs_add_device() {
+    let fs = DeviceFileSystem::new();
+    fs.add_device("null", DeviceId::new(1, 3), Arc::new(NullDev))
+        .unwrap();
+    assert!(fs.root_dir().lookup("null").is_ok());
+    let registry = fs.registry();
+    assert!(registry
+        .resolve(VfsNodeType::CharDevice, DeviceId::new(1, 3))
+        .is_some());
+    assert_eq!(
+        fs.add_device("null2", DeviceId::new(1, 3), Arc::new(NullDev)),
+        Err(VfsError::AlreadyExists)
+    );
+    assert!(fs.root_dir().lookup("null2").is_err());
+}
+
 // ===== DirNode Tests =====
 
 #[test]
@@ -521,3 +583,397 @@ fn test_mixed_devices_and_directories() {
     assert!(root.clone().lookup("random/urandom").is_ok());
     assert!(root.clone().lookup("random").is_ok());
 }
+
+#[test]
+fn test_inode_numbers_are_stable() {
+    let ino_of = |fs: &DeviceFileSystem| {
+        let root = fs.root_dir();
+        let dir = root.clone().lookup("random").unwrap();
+        let dev = root.lookup("random/urandom").unwrap();
+        (dir.get_attr().unwrap().ino(), dev.get_attr().unwrap().ino())
+    };
+    let build = || {
+        let fs = DeviceFileSystem::new();
+        fs.mkdir("random")
+            .add("urandom", Arc::new(UrandomDev::new(1)));
+        fs
+    };
+
+    let (dir_ino, dev_ino) = ino_of(&build());
+    assert_ne!(dir_ino, 0);
+    assert_ne!(dir_ino, dev_ino);
+    assert_eq!(ino_of(&build()), (dir_ino, dev_ino));
+}
+
+#[test]
+fn test_devfs_statfs() {
+    let fs = DeviceFileSystem::new();
+    fs.add("null", Arc::new(NullDev));
+    fs.mkdir("sub").add("zero", Arc::new(ZeroDev));
+
+    let info = fs.statfs().unwrap();
+    assert_eq!(info.fs_type(), 0x1373);
+    assert_eq!(info.blocks(), 0);
+    assert_eq!(info.name_len(), axfs_vfs::limits::MAX_NAME_LEN as u64);
+    // root, null, sub and zero
+    assert_eq!(info.files(), 4);
+}
+
+#[test]
+fn test_devfs_ioctl() {
+    const TIOCGWINSZ: u32 = 0x5413;
+
+    /// A console reporting its window size as `rows << 16 | cols`.
+    struct Console;
+
+    impl VfsNodeOps for Console {
+        fn ioctl(&self, cmd: u32, _arg: usize) -> VfsResult<usize> {
+            match cmd {
+                TIOCGWINSZ => Ok(25 << 16 | 80),
+                _ => Err(VfsError::NotATty),
+            }
+        }
+
+        axfs_vfs::impl_vfs_non_dir_default! {}
+    }
+
+    let fs = DeviceFileSystem::new();
+    fs.add("null", Arc::new(NullDev));
+    fs.add("console", Arc::new(Console));
+    let root = fs.root_dir();
+
+    let console = root.clone().lookup("console").unwrap();
+    assert_eq!(console.ioctl(TIOCGWINSZ, 0), Ok(25 << 16 | 80));
+    assert_eq!(console.ioctl(0x5401, 0), Err(VfsError::NotATty));
+    let handle = VfsFileHandle::open(console).unwrap();
+    assert_eq!(handle.ioctl(TIOCGWINSZ, 0), Ok(25 << 16 | 80));
+
+    let null = root.clone().lookup("null").unwrap();
+    assert_eq!(null.ioctl(TIOCGWINSZ, 0), Err(VfsError::NotATty));
+    assert_eq!(root.ioctl(TIOCGWINSZ, 0), Err(VfsError::NotATty));
+}
+
+#[test]
+fn test_devfs_view() {
+    let fs = DeviceFileSystem::new();
+    let null: Arc<NullDev> = Arc::new(NullDev);
+    fs.add("null", null.clone());
+    fs.add("zero", Arc::new(ZeroDev));
+    let pts = fs.mkdir("pts");
+    pts.add("0", Arc::new(NullDev));
+    pts.add("1", Arc::new(NullDev));
+
+    let view = fs.view(&["null", "pts/1"]);
+    assert!(view.is_visible("pts"));
+    assert!(!view.is_visible("pts/0"));
+    let root = view.root_dir();
+
+    // devices are shared with the filesystem
+    let node = root.clone().lookup("null").unwrap();
+    assert!(Arc::ptr_eq(&node, &(null as VfsNodeRef)));
+    assert_eq!(root.clone().lookup("zero").err(), Some(VfsError::NotFound));
+    assert_eq!(root.clone().lookup("pts/0").err(), Some(VfsError::NotFound));
+    let tty = root.clone().lookup("pts/1").unwrap();
+    assert_eq!(tty.get_attr().unwrap().file_type(), VfsNodeType::CharDevice);
+
+    // `..` stays in the view
+    let dir = root.clone().lookup("pts").unwrap();
+    assert!(dir.clone().lookup("../zero").is_err());
+    assert!(dir.lookup("../null").is_ok());
+
+    // devices added later appear in the views allowing them
+    fs.add("console", Arc::new(NullDev));
+    assert!(root.clone().lookup("console").is_err());
+    assert!(fs.view(&["console"]).root_dir().lookup("console").is_ok());
+
+    // root, null, pts and pts/1
+    assert_eq!(view.statfs().unwrap().files(), 4);
+    assert_eq!(
+        root.create("new", VfsNodeType::File),
+        Err(VfsError::PermissionDenied)
+    );
+}
+
+#[test]
+fn test_devfs_disk_links() {
+    fn link_target(root: &VfsNodeRef, path: &str) -> String {
+        let link = root.clone().lookup(path).unwrap();
+        assert_eq!(link.get_attr().unwrap().file_type(), VfsNodeType::SymLink);
+        let mut buf = [0; 32];
+        let len = link.read_link(&mut buf).unwrap();
+        String::from_utf8(buf[..len].to_vec()).unwrap()
+    }
+
+    let fs = DeviceFileSystem::new();
+    let root = fs.root_dir();
+    assert_eq!(root.clone().lookup("disk").err(), Some(VfsError::NotFound));
+
+    let info = DiskInfo::new().with_serial("QM00001").with_label("root fs");
+    fs.add_disk("sda", Arc::new(NullDev), info).unwrap();
+    fs.add_disk(
+        "sdb",
+        Arc::new(ZeroDev),
+        DiskInfo::new().with_serial("QM00002"),
+    )
+    .unwrap();
+    assert_eq!(
+        fs.add_disk("sda", Arc::new(NullDev), DiskInfo::new()),
+        Err(VfsError::AlreadyExists)
+    );
+
+    assert_eq!(link_target(&root, "disk/by-id/QM00001"), "../../sda");
+    assert_eq!(link_target(&root, "disk/by-id/QM00002"), "../../sdb");
+    assert_eq!(link_target(&root, "disk/by-label/root_fs"), "../../sda");
+    assert!(root.clone().lookup("disk/by-id/../../sda").is_ok());
+
+    let by_id = root.clone().lookup("disk/by-id").unwrap();
+    let mut entries: Vec<_> = (0..5).map(|_| axfs_vfs::VfsDirEntry::default()).collect();
+    assert_eq!(by_id.read_dir(0, &mut entries), Ok(4));
+    assert_eq!(entries[2].name_as_bytes(), b"QM00001");
+    assert_eq!(entries[3].entry_type(), VfsNodeType::SymLink);
+
+    // the links go away with the disk
+    assert!(fs.remove_disk("sda").is_some());
+    assert!(fs.remove_disk("sda").is_none());
+    assert_eq!(root.clone().lookup("sda").err(), Some(VfsError::NotFound));
+    assert_eq!(
+        root.clone().lookup("disk/by-label/root_fs").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(by_id.read_dir(0, &mut entries), Ok(3));
+}
+
+#[test]
+fn test_devfs_tty_job_control() {
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Kernel {
+        signals: Mutex<Vec<(Pid, Signal)>>,
+        output: Mutex<Vec<u8>>,
+    }
+
+    impl SignalSink for Kernel {
+        fn signal_group(&self, pgrp: Pid, signal: Signal) {
+            self.signals.lock().unwrap().push((pgrp, signal));
+        }
+    }
+
+    impl TtyDriver for Kernel {
+        fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+            self.output.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    const TIOCGPGRP: u32 = 0x540f;
+
+    let kernel = Arc::new(Kernel::default());
+    let tty = Arc::new(TtyDev::new(
+        DeviceId::new(4, 64),
+        kernel.clone(),
+        kernel.clone(),
+    ));
+    let fs = DeviceFileSystem::new();
+    fs.add("ttyS0", tty.clone());
+    let node = fs.root_dir().lookup("ttyS0").unwrap();
+
+    // a shell takes the terminal and runs a job in the foreground
+    tty.set_controlling(100, 100).unwrap();
+    tty.set_foreground(100, 200).unwrap();
+    assert_eq!(node.ioctl(TIOCGPGRP, 0), Ok(200));
+    assert_eq!(node.write_at(0, b"$ "), Ok(2));
+    assert_eq!(*kernel.output.lock().unwrap(), b"$ ");
+
+    // ^C interrupts the job, not the shell
+    tty.receive(b"\x03");
+    assert_eq!(tty.check_read(100), Err(VfsError::Interrupted));
+    tty.set_foreground(100, 100).unwrap();
+    tty.receive(b"exit\n");
+    let mut buf = [0; 8];
+    assert_eq!(node.read_at(0, &mut buf), Ok(5));
+
+    tty.hangup();
+    assert_eq!(
+        *kernel.signals.lock().unwrap(),
+        [
+            (200, Signal::Interrupt),
+            (100, Signal::BackgroundRead),
+            (100, Signal::Hangup),
+            (100, Signal::Continue),
+        ]
+    );
+    assert_eq!(node.ioctl(TIOCGPGRP, 0), Err(VfsError::NotATty));
+}
+
+#[test]
+fn test_devfs_read_only_device() {
+    let fs = DeviceFileSystem::new();
+    fs.add("zero", Arc::new(ReadOnlyDev::new(Arc::new(ZeroDev))));
+    let zero = fs.root_dir().lookup("zero").unwrap();
+
+    assert_eq!(
+        VfsFileHandle::open_with(zero.clone(), OpenFlags::READ | OpenFlags::WRITE).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    let handle = VfsFileHandle::open_with(zero, OpenFlags::READ).unwrap();
+    let mut buf = [1; 8];
+    assert_eq!(handle.read(&mut buf), Ok(8));
+    assert_eq!(buf, [0; 8]);
+    assert_eq!(
+        fs.root_dir().open(OpenFlags::WRITE).err(),
+        Some(VfsError::IsADirectory)
+    );
+}
+
+#[test]
+fn test_devfs_detach_subtree() {
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Udev(Mutex<Vec<(String, VfsNodeType)>>);
+
+    impl DeviceListener for Udev {
+        fn removed(&self, path: &str, ty: VfsNodeType) {
+            self.0.lock().unwrap().push((path.into(), ty));
+        }
+    }
+
+    let fs = DeviceFileSystem::new();
+    let udev = Arc::new(Udev::default());
+    fs.set_listener(Some(udev.clone()));
+    fs.add("null", Arc::new(NullDev));
+    let input = fs.mkdir("input");
+    input.add("mice", Arc::new(ZeroDev));
+    input.mkdir("by-path").add("usb-kbd", Arc::new(NullDev));
+    // a device counting its open handles, so that they can be revoked
+    let event0: VfsNodeRef = Arc::new(UrandomDev::new(1));
+    fs.registry()
+        .register(
+            VfsNodeType::CharDevice,
+            DeviceId::new(13, 64),
+            event0.clone(),
+        )
+        .unwrap();
+    input.add("event0", event0.clone());
+
+    let handle = VfsFileHandle::open(event0.clone()).unwrap();
+    let dir = fs.root_dir().lookup("input").unwrap();
+    assert_eq!(fs.detach("input/"), Ok(5));
+
+    // the subtree is gone, even through a reference kept to the directory
+    let root = fs.root_dir();
+    assert_eq!(root.clone().lookup("input").err(), Some(VfsError::NotFound));
+    assert_eq!(dir.lookup("mice").err(), Some(VfsError::NotFound));
+    assert!(root.clone().lookup("null").is_ok());
+    assert!(fs
+        .registry()
+        .resolve(VfsNodeType::CharDevice, DeviceId::new(13, 64))
+        .is_none());
+    assert_eq!(handle.read(&mut [0; 4]), Err(VfsError::NoSuchDevice));
+
+    let char_dev = VfsNodeType::CharDevice;
+    assert_eq!(
+        *udev.0.lock().unwrap(),
+        [
+            ("input/by-path/usb-kbd".into(), char_dev),
+            ("input/by-path".into(), VfsNodeType::Dir),
+            ("input/event0".into(), char_dev),
+            ("input/mice".into(), char_dev),
+            ("input".into(), VfsNodeType::Dir),
+        ]
+    );
+
+    assert_eq!(fs.detach("input"), Err(VfsError::NotFound));
+    assert_eq!(fs.detach("/"), Err(VfsError::InvalidInput));
+    assert_eq!(fs.detach("null/x"), Err(VfsError::NotADirectory));
+    assert_eq!(fs.detach("null"), Ok(1));
+}
+
+#[test]
+fn test_devfs_shutdown() {
+    let fs = DeviceFileSystem::new();
+    let urandom: VfsNodeRef = Arc::new(UrandomDev::new(1));
+    fs.mkdir("sub").add("urandom", urandom.clone());
+    let handle = VfsFileHandle::open(urandom).unwrap();
+    let root = VfsFileHandle::open_with(fs.root_dir(), OpenFlags::READ).unwrap();
+    assert_eq!(handle.read(&mut [1; 4]), Ok(4));
+
+    fs.shutdown().unwrap();
+    assert_eq!(handle.read(&mut [1; 4]), Err(VfsError::NoSuchDevice));
+    assert!(root.is_revoked());
+}
+
+#[test]
+fn test_devfs_operation_trace() {
+    use axfs_vfs::trace::{self, hash_path, TraceOp, TraceRing};
+
+    let devfs = DeviceFileSystem::new();
+    devfs.add("trace_null", Arc::new(NullDev));
+    let root = devfs.root_dir();
+    let ring = Arc::new(TraceRing::new(256));
+    trace::set_global_ring(Some(ring.clone()));
+    root.clone().lookup("trace_null").unwrap();
+    assert!(root.clone().lookup("trace_missing").is_err());
+    assert_eq!(
+        root.create("trace_new", VfsNodeType::File),
+        Err(VfsError::PermissionDenied)
+    );
+    trace::set_global_ring(None);
+
+    let names = ["trace_null", "trace_missing", "trace_new"].map(hash_path);
+    let ops: Vec<_> = ring
+        .records()
+        .into_iter()
+        .filter(|r| names.contains(&r.path_hash))
+        .map(|r| (r.op, r.result))
+        .collect();
+    assert_eq!(
+        ops,
+        [
+            (TraceOp::Lookup, Ok(())),
+            (TraceOp::Lookup, Err(VfsError::NotFound)),
+            (TraceOp::Create, Err(VfsError::PermissionDenied)),
+        ]
+    );
+}
+
+#[test]
+fn test_devfs_device_stats() {
+    let devfs = DeviceFileSystem::new();
+    devfs.add("null", Arc::new(NullDev));
+    let stats = devfs.add_with_stats("zero", Arc::new(ZeroDev));
+    let root = devfs.root_dir();
+
+    let zero = root.clone().lookup("zero").unwrap();
+    zero.open(OpenFlags::READ).unwrap();
+    let mut buf = [1; 32];
+    assert_eq!(zero.read_at(0, &mut buf), Ok(32));
+    assert_eq!(zero.write_at(0, b"xy"), Ok(2));
+    assert_eq!(stats.open_count(), 1);
+
+    let file = root.clone().lookup(".stats/zero").unwrap();
+    let mut content = [0; 256];
+    let n = file.read_at(0, &mut content).unwrap();
+    let content = std::str::from_utf8(&content[..n]).unwrap();
+    assert!(content.contains("open_count 1\n"));
+    assert!(content.contains("read_bytes 32\n"));
+    assert!(content.contains("written_bytes 2\n"));
+    assert!(root.clone().lookup(".stats/null").is_err());
+    assert_eq!(file.write_at(0, b"0"), Err(VfsError::PermissionDenied));
+
+    zero.release().unwrap();
+    assert!(file.read_at(0, &mut buf).is_ok());
+    assert_eq!(stats.open_count(), 0);
+    assert_eq!(devfs.detach("zero"), Ok(1));
+    assert_eq!(root.lookup(".stats/zero").err(), Some(VfsError::NotFound));
+}
+
+#[test]
+fn test_conformance() {
+    let devfs = DeviceFileSystem::new();
+    devfs.add("null", Arc::new(NullDev));
+    devfs.add("zero", Arc::new(ZeroDev));
+    devfs.mkdir("foo").add("f2", Arc::new(ZeroDev));
+    axfs_vfs_testsuite::run(&devfs);
+}
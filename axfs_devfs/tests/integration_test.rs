@@ -6,7 +6,7 @@
 //! in real-world scenarios.
 
 use axfs_devfs::{DeviceFileSystem, NullDev, UrandomDev, ZeroDev};
-use axfs_vfs::{VfsDirEntry, VfsNodeType, VfsOps};
+use axfs_vfs::{MountOptions, VfsDirEntry, VfsNodeType, VfsOps};
 use std::sync::Arc;
 
 // ============== System-Level Integration Tests ==============
@@ -20,7 +20,7 @@ fn test_system_complete_filesystem_lifecycle() {
 
     // Mount
     let root = fs.root_dir();
-    fs.mount("/", root.clone()).unwrap();
+    fs.mount("/", root.clone(), &MountOptions::new()).unwrap();
 
     // Add standard Unix-like devices
     let null: Arc<NullDev> = Arc::new(NullDev);
@@ -0,0 +1,766 @@
+use alloc::collections::btree_map::Entry;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use axfs_vfs::page::PageFrame;
+use axfs_vfs::{SeekHint, VfsError, VfsResult};
+
+use crate::state::FsState;
+use crate::swap::SwapArea;
+
+/// The size of the pages storing file contents.
+pub(crate) const PAGE_SIZE: usize = axfs_vfs::page::PAGE_SIZE;
+
+/// The maximum size of a file in bytes, like `MAX_LFS_FILESIZE` on Linux.
+///
+/// The offsets of the bytes of a file fit in an `i64`, so computing the end
+/// of a range of the file never overflows.
+pub(crate) const MAX_FILE_SIZE: u64 = i64::MAX as u64;
+
+/// A page of zeros, to clear parts of pages.
+const ZEROS: [u8; PAGE_SIZE] = [0; PAGE_SIZE];
+
+/// A page of file content.
+pub(crate) enum Page {
+    /// The page is in memory. `referenced` is set on every access, and
+    /// cleared by eviction passes. The frame is shared with the memory
+    /// mappings of the page, if any.
+    Resident {
+        data: Arc<PageFrame>,
+        referenced: AtomicBool,
+    },
+    /// The page has been evicted to the given slot of the swap area.
+    Swapped(u64),
+}
+
+impl Page {
+    /// Creates a resident page that has just been accessed.
+    fn resident(data: Arc<PageFrame>) -> Self {
+        Self::Resident {
+            data,
+            referenced: AtomicBool::new(true),
+        }
+    }
+}
+
+/// The content of a file, stored as a sparse map of pages.
+///
+/// Pages that are absent from the map are holes, read as zeros. The bytes of
+/// a page beyond the end of the file are always zeros.
+#[derive(Default)]
+pub(crate) struct FileData {
+    size: u64,
+    pages: BTreeMap<u64, Page>,
+}
+
+/// Returns the indices of the pages overlapping `len` bytes at `offset`,
+/// with `len > 0`.
+fn page_span(offset: u64, len: usize) -> core::ops::RangeInclusive<u64> {
+    let page_size = PAGE_SIZE as u64;
+    offset / page_size..=offset.saturating_add(len as u64 - 1) / page_size
+}
+
+/// Returns the end of `len` bytes at `offset`.
+///
+/// # Errors
+///
+/// Returns [`VfsError::InvalidInput`] if the end is beyond
+/// [`MAX_FILE_SIZE`].
+fn range_end(offset: u64, len: u64) -> VfsResult<u64> {
+    offset
+        .checked_add(len)
+        .filter(|&end| end <= MAX_FILE_SIZE)
+        .ok_or(VfsError::InvalidInput)
+}
+
+impl FileData {
+    /// Returns the size of the content in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the number of pages in memory.
+    pub fn resident_pages(&self) -> usize {
+        self.pages
+            .values()
+            .filter(|p| matches!(p, Page::Resident { .. }))
+            .count()
+    }
+
+    /// Returns whether all pages overlapping `len` bytes at `offset` are in
+    /// memory (or holes).
+    pub fn is_resident(&self, offset: u64, len: usize) -> bool {
+        len == 0
+            || self
+                .pages
+                .range(page_span(offset, len))
+                .all(|(_, p)| matches!(p, Page::Resident { .. }))
+    }
+
+    /// Reads the content at `offset` into `buf`.
+    ///
+    /// Swapped pages are read from the swap area without being faulted in.
+    ///
+    /// Returns the number of bytes read.
+    pub fn read(&self, offset: u64, buf: &mut [u8], fs: &FsState) -> VfsResult<usize> {
+        if offset >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = (buf.len() as u64).min(self.size - offset) as usize;
+        let buf = &mut buf[..len];
+        buf.fill(0);
+        for (&idx, page) in self.pages.range(page_span(offset, len)) {
+            let page_start = idx * PAGE_SIZE as u64;
+            let start = offset.max(page_start);
+            let end = (offset + len as u64).min(page_start + PAGE_SIZE as u64);
+            let dst = &mut buf[(start - offset) as usize..(end - offset) as usize];
+            let src = (start - page_start) as usize;
+            match page {
+                Page::Resident { data, referenced } => {
+                    // SAFETY: writes through the node need the content
+                    // exclusively. Writes through memory mappings are not
+                    // synchronized, as for any shared mapping of a file.
+                    unsafe { data.read(src, dst) };
+                    referenced.store(true, Ordering::Relaxed);
+                }
+                Page::Swapped(slot) => {
+                    let mut tmp = vec![0; PAGE_SIZE];
+                    fs.swap().ok_or(VfsError::Io)?.read(*slot, &mut tmp)?;
+                    dst.copy_from_slice(&tmp[src..src + dst.len()]);
+                }
+            }
+        }
+        Ok(len)
+    }
+
+    /// Brings the swapped pages overlapping `len` bytes at `offset` back in
+    /// memory.
+    pub fn fault_in(&mut self, offset: u64, len: usize, fs: &FsState) -> VfsResult {
+        if len == 0 {
+            return Ok(());
+        }
+        let swapped: Vec<u64> = self
+            .pages
+            .range(page_span(offset, len))
+            .filter(|(_, p)| matches!(p, Page::Swapped(_)))
+            .map(|(&idx, _)| idx)
+            .collect();
+        for idx in swapped {
+            self.page_mut(idx, fs)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the resident page `idx`, allocating or faulting it in if
+    /// needed.
+    fn page_mut(&mut self, idx: u64, fs: &FsState) -> VfsResult<&Arc<PageFrame>> {
+        let page = match self.pages.entry(idx) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                fs.charge_page()?;
+                entry.insert(Page::resident(Arc::new(PageFrame::new())))
+            }
+        };
+        if let Page::Swapped(slot) = *page {
+            let swap = fs.swap().ok_or(VfsError::Io)?;
+            let mut data = PageFrame::new();
+            swap.read(slot, data.get_mut())?;
+            swap.free(slot, fs.secure_wipe());
+            *page = Page::resident(Arc::new(data));
+        }
+        match page {
+            Page::Resident { data, referenced } => {
+                referenced.store(true, Ordering::Relaxed);
+                Ok(data)
+            }
+            Page::Swapped(_) => unreachable!(),
+        }
+    }
+
+    /// Writes `buf` at `offset`, extending the content if needed.
+    ///
+    /// Returns the number of bytes written, which is short if the page limit
+    /// of the filesystem is reached after some bytes were written, or
+    /// [`VfsError::InvalidInput`] if the content would grow beyond
+    /// [`MAX_FILE_SIZE`].
+    pub fn write(&mut self, offset: u64, buf: &[u8], fs: &FsState) -> VfsResult<usize> {
+        range_end(offset, buf.len() as u64)?;
+        let mut pos = 0;
+        while pos < buf.len() {
+            let off = offset + pos as u64;
+            let in_page = (off % PAGE_SIZE as u64) as usize;
+            let n = (PAGE_SIZE - in_page).min(buf.len() - pos);
+            let page = match self.page_mut(off / PAGE_SIZE as u64, fs) {
+                Ok(page) => page,
+                Err(VfsError::StorageFull) if pos > 0 => return Ok(pos),
+                Err(e) => return Err(e),
+            };
+            // SAFETY: the content is borrowed exclusively, see `read()`.
+            unsafe { page.write(in_page, &buf[pos..pos + n]) };
+            pos += n;
+            self.size = self.size.max(off + n as u64);
+        }
+        Ok(buf.len())
+    }
+
+    /// Copies `len` bytes of `src` at `src_off` to `dst_off`, from page to
+    /// page, extending the content if needed.
+    ///
+    /// Holes of `src` stay holes, unless they are copied over data. Swapped
+    /// pages of `src` are read from the swap area of `src_fs` without being
+    /// faulted in.
+    ///
+    /// Returns the number of bytes copied, which is short if the end of
+    /// `src` is reached, or if the page limit of the filesystem is reached
+    /// after some bytes were copied, or [`VfsError::InvalidInput`] if the
+    /// content would grow beyond [`MAX_FILE_SIZE`].
+    pub fn copy_from(
+        &mut self,
+        dst_off: u64,
+        src: &FileData,
+        src_off: u64,
+        len: usize,
+        fs: &FsState,
+        src_fs: &FsState,
+    ) -> VfsResult<usize> {
+        let page_size = PAGE_SIZE as u64;
+        let len = src.size.saturating_sub(src_off).min(len as u64) as usize;
+        range_end(dst_off, len as u64)?;
+        let mut tmp = Vec::new();
+        let mut pos = 0;
+        while pos < len {
+            let (s_off, d_off) = (src_off + pos as u64, dst_off + pos as u64);
+            let (s_in, d_in) = ((s_off % page_size) as usize, (d_off % page_size) as usize);
+            let n = (PAGE_SIZE - s_in).min(PAGE_SIZE - d_in).min(len - pos);
+            let src_ptr = match src.pages.get(&(s_off / page_size)) {
+                None if !self.pages.contains_key(&(d_off / page_size)) => None,
+                None => Some(ZEROS[s_in..].as_ptr()),
+                Some(Page::Resident { data, referenced }) => {
+                    referenced.store(true, Ordering::Relaxed);
+                    // SAFETY: the offset is inside the page.
+                    Some(unsafe { data.as_ptr().add(s_in) }.cast_const())
+                }
+                Some(Page::Swapped(slot)) => {
+                    tmp.resize(PAGE_SIZE, 0);
+                    src_fs.swap().ok_or(VfsError::Io)?.read(*slot, &mut tmp)?;
+                    Some(tmp[s_in..].as_ptr())
+                }
+            };
+            if let Some(src_ptr) = src_ptr {
+                let page = match self.page_mut(d_off / page_size, fs) {
+                    Ok(page) => page,
+                    Err(VfsError::StorageFull) if pos > 0 => return Ok(pos),
+                    Err(e) => return Err(e),
+                };
+                // SAFETY: both ranges are inside their page, which are
+                // distinct as `self` is borrowed mutably. Both contents are
+                // borrowed as in `read()` and `write()`.
+                unsafe { core::ptr::copy_nonoverlapping(src_ptr, page.as_ptr().add(d_in), n) };
+            }
+            pos += n;
+            self.size = self.size.max(d_off + n as u64);
+        }
+        Ok(len)
+    }
+
+    /// Truncates or extends the content to `size` bytes.
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `size` is beyond
+    /// [`MAX_FILE_SIZE`].
+    pub fn truncate(&mut self, size: u64, fs: &FsState) -> VfsResult {
+        range_end(size, 0)?;
+        let page_size = PAGE_SIZE as u64;
+        if size < self.size {
+            for (_, page) in self.pages.split_off(&size.div_ceil(page_size)) {
+                free_page(page, fs);
+            }
+            let tail = (size % page_size) as usize;
+            if tail != 0 && self.pages.contains_key(&(size / page_size)) {
+                let page = self.page_mut(size / page_size, fs)?;
+                // SAFETY: the content is borrowed exclusively, see `read()`.
+                unsafe { page.write(tail, &ZEROS[tail..]) };
+            }
+        }
+        self.size = size;
+        Ok(())
+    }
+
+    /// Allocates the pages of `len` bytes at `offset`, faulting in the
+    /// swapped ones, so that writes to the range do not allocate.
+    ///
+    /// The content is extended to the end of the range, unless `keep_size`
+    /// is set, in which case only the pages before the end are allocated:
+    /// the pages beyond the end are freed by [`trim()`](Self::trim).
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the range ends beyond
+    /// [`MAX_FILE_SIZE`].
+    pub fn allocate(&mut self, offset: u64, len: u64, keep_size: bool, fs: &FsState) -> VfsResult {
+        let page_size = PAGE_SIZE as u64;
+        let end = range_end(offset, len)?;
+        let alloc_end = if keep_size { end.min(self.size) } else { end };
+        if alloc_end > offset {
+            for idx in offset / page_size..=(alloc_end - 1) / page_size {
+                self.page_mut(idx, fs)?;
+            }
+        }
+        if !keep_size {
+            self.size = self.size.max(end);
+        }
+        Ok(())
+    }
+
+    /// Frees the pages of `len` bytes at `offset` and zeroes the parts of
+    /// the pages partially in the range, without changing the size.
+    ///
+    /// Returns the range that was cleared, clipped to the end of the
+    /// content.
+    pub fn punch_hole(&mut self, offset: u64, len: u64, fs: &FsState) -> VfsResult<Range<u64>> {
+        let page_size = PAGE_SIZE as u64;
+        let end = offset.saturating_add(len).min(self.size);
+        if offset >= end {
+            return Ok(offset..offset);
+        }
+        // the bytes of the last page beyond the end are zeros already
+        let full = offset.div_ceil(page_size)..if end == self.size {
+            end.div_ceil(page_size)
+        } else {
+            end / page_size
+        };
+        let freed: Vec<u64> = self
+            .pages
+            .range(full.clone())
+            .map(|(&idx, _)| idx)
+            .collect();
+        for idx in freed {
+            if let Some(page) = self.pages.remove(&idx) {
+                free_page(page, fs);
+            }
+        }
+        for idx in [offset / page_size, (end - 1) / page_size] {
+            if full.contains(&idx) || !self.pages.contains_key(&idx) {
+                continue;
+            }
+            let page_start = idx * page_size;
+            let start = (offset.max(page_start) - page_start) as usize;
+            let stop = (end.min(page_start + page_size) - page_start) as usize;
+            let page = self.page_mut(idx, fs)?;
+            // SAFETY: the content is borrowed exclusively, see `read()`.
+            unsafe { page.write(start, &ZEROS[start..stop]) };
+        }
+        Ok(offset..end)
+    }
+
+    /// Returns the start of the next region of data or the next hole at or
+    /// after `offset`, or `None` if `offset` is not before the end of the
+    /// content or no data follows it.
+    ///
+    /// Regions are tracked at page granularity: a page is data if it is
+    /// allocated, even if it only contains zeros.
+    pub fn seek_hint(&self, offset: u64, hint: SeekHint) -> Option<u64> {
+        let page_size = PAGE_SIZE as u64;
+        if offset >= self.size {
+            return None;
+        }
+        let first = offset / page_size;
+        match hint {
+            SeekHint::Data => {
+                let (&idx, _) = self.pages.range(first..).next()?;
+                let start = (idx * page_size).max(offset);
+                (start < self.size).then_some(start)
+            }
+            SeekHint::Hole => {
+                let mut next = first;
+                for &idx in self.pages.range(first..).map(|(idx, _)| idx) {
+                    if idx != next {
+                        break;
+                    }
+                    next += 1;
+                }
+                Some((next * page_size).clamp(offset, self.size))
+            }
+        }
+    }
+
+    /// Frees all pages, leaving an empty content.
+    pub fn clear(&mut self, fs: &FsState) {
+        for (_, page) in core::mem::take(&mut self.pages) {
+            free_page(page, fs);
+        }
+        self.size = 0;
+    }
+
+    /// Moves the pages not accessed since the previous pass to `swap`, and
+    /// marks the others as not accessed.
+    ///
+    /// At most `budget` pages are evicted, and `budget` is decreased by the
+    /// number of evicted pages.
+    pub fn evict(&mut self, swap: &SwapArea, budget: &mut usize, fs: &FsState) -> VfsResult {
+        for page in self.pages.values_mut() {
+            if *budget == 0 {
+                break;
+            }
+            let Page::Resident { data, referenced } = page else {
+                continue;
+            };
+            // mapped pages stay in memory
+            let Some(data) = Arc::get_mut(data) else {
+                continue;
+            };
+            if referenced.swap(false, Ordering::Relaxed) {
+                continue;
+            }
+            let slot = swap.swap_out(data.get_mut())?;
+            if fs.secure_wipe() {
+                wipe(data.get_mut());
+            }
+            #[cfg(test)]
+            fs.record_released(data.get_mut());
+            *page = Page::Swapped(slot);
+            *budget -= 1;
+        }
+        Ok(())
+    }
+
+    /// Returns whether pages exist beyond the end of the content, or the
+    /// bytes of the last page beyond the end are not zeros.
+    ///
+    /// The last page is only checked if it is in memory.
+    pub fn has_data_beyond_size(&self) -> bool {
+        let page_size = PAGE_SIZE as u64;
+        if self
+            .pages
+            .range(self.size.div_ceil(page_size)..)
+            .next()
+            .is_some()
+        {
+            return true;
+        }
+        let tail = (self.size % page_size) as usize;
+        match self.pages.get(&(self.size / page_size)) {
+            Some(Page::Resident { data, .. }) if tail != 0 => {
+                let mut buf = [0; PAGE_SIZE];
+                // SAFETY: the content is borrowed, see `read()`.
+                unsafe { data.read(tail, &mut buf[tail..]) };
+                buf != ZEROS
+            }
+            _ => false,
+        }
+    }
+
+    /// Frees the pages beyond the end of the content, and zeroes the bytes of
+    /// the last page beyond the end if it is in memory.
+    pub fn trim(&mut self, fs: &FsState) {
+        let page_size = PAGE_SIZE as u64;
+        for (_, page) in self.pages.split_off(&self.size.div_ceil(page_size)) {
+            free_page(page, fs);
+        }
+        let tail = (self.size % page_size) as usize;
+        if let Some(Page::Resident { data, .. }) = self.pages.get_mut(&(self.size / page_size)) {
+            if tail != 0 {
+                // SAFETY: the content is borrowed exclusively, see `read()`.
+                unsafe { data.write(tail, &ZEROS[tail..]) };
+            }
+        }
+    }
+
+    /// Returns the page at `offset` to map in memory, allocating or faulting
+    /// it in if needed.
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `offset` is not aligned, or not
+    /// before the end of the content.
+    pub fn get_page(&mut self, offset: u64, fs: &FsState) -> VfsResult<Arc<PageFrame>> {
+        let page_size = PAGE_SIZE as u64;
+        if !offset.is_multiple_of(page_size) || offset >= self.size {
+            return Err(VfsError::InvalidInput);
+        }
+        Ok(self.page_mut(offset / page_size, fs)?.clone())
+    }
+
+    /// Returns a copy of the resident page `idx`, for tests.
+    #[cfg(test)]
+    pub fn resident_page(&self, idx: u64) -> Option<Vec<u8>> {
+        match self.pages.get(&idx) {
+            Some(Page::Resident { data, .. }) => {
+                let mut buf = vec![0; PAGE_SIZE];
+                // SAFETY: the content is borrowed, see `read()`.
+                unsafe { data.read(0, &mut buf) };
+                Some(buf)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Frees `page` and uncharges it, zeroizing it first if secure wipe is
+/// enabled.
+fn free_page(page: Page, fs: &FsState) {
+    match page {
+        Page::Resident { mut data, .. } => match Arc::get_mut(&mut data) {
+            Some(data) => {
+                if fs.secure_wipe() {
+                    wipe(data.get_mut());
+                }
+                #[cfg(test)]
+                fs.record_released(data.get_mut());
+                fs.uncharge_page();
+            }
+            // mapped pages are still in use, and are freed and uncharged
+            // with the last mapping
+            None => {
+                let secure_wipe = fs.secure_wipe();
+                let uncharge = fs.deferred_uncharge();
+                #[cfg(test)]
+                let released = fs.released();
+                data.set_release_hook(alloc::boxed::Box::new(move |data| {
+                    if secure_wipe {
+                        wipe(data);
+                    }
+                    #[cfg(test)]
+                    released.lock().push(data.to_vec());
+                    uncharge();
+                }));
+            }
+        },
+        Page::Swapped(slot) => {
+            fs.uncharge_page();
+            if let Some(swap) = fs.swap() {
+                swap.free(slot, fs.secure_wipe());
+            }
+        }
+    }
+}
+
+/// Overwrites `buf` with zeros, in a way the compiler cannot elide.
+pub(crate) fn wipe(buf: &mut [u8]) {
+    for byte in buf.iter_mut() {
+        // SAFETY: `byte` is a valid, aligned and exclusive reference.
+        unsafe { core::ptr::write_volatile(byte, 0) };
+    }
+    core::sync::atomic::compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_all(data: &FileData, fs: &FsState) -> Vec<u8> {
+        let mut buf = vec![0; data.size() as usize];
+        assert_eq!(data.read(0, &mut buf, fs).unwrap(), buf.len());
+        buf
+    }
+
+    #[test]
+    fn test_file_data_sparse() {
+        let fs = FsState::default();
+        let mut data = FileData::default();
+        data.write(PAGE_SIZE as u64 * 3 - 2, b"span", &fs).unwrap();
+        assert_eq!(data.size(), PAGE_SIZE as u64 * 3 + 2);
+        // the pages before are holes
+        assert_eq!(data.resident_pages(), 2);
+
+        let content = read_all(&data, &fs);
+        assert!(content[..PAGE_SIZE * 3 - 2].iter().all(|&b| b == 0));
+        assert_eq!(&content[PAGE_SIZE * 3 - 2..], b"span");
+    }
+
+    #[test]
+    fn test_file_data_truncate() {
+        let fs = FsState::default();
+        let mut data = FileData::default();
+        data.write(0, &[1; PAGE_SIZE + 10], &fs).unwrap();
+        data.truncate(5, &fs).unwrap();
+        assert_eq!(data.resident_pages(), 1);
+        assert_eq!(&data.resident_page(0).unwrap()[..6], [1, 1, 1, 1, 1, 0]);
+
+        // extending again exposes zeros, not the old bytes
+        data.truncate(PAGE_SIZE as u64 * 2, &fs).unwrap();
+        let content = read_all(&data, &fs);
+        assert_eq!(&content[..5], [1; 5]);
+        assert!(content[5..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_file_data_allocate() {
+        let fs = FsState::default();
+        let mut data = FileData::default();
+        data.allocate(10, PAGE_SIZE as u64, false, &fs).unwrap();
+        assert_eq!(data.size(), PAGE_SIZE as u64 + 10);
+        assert_eq!(data.resident_pages(), 2);
+        assert_eq!(read_all(&data, &fs), vec![0; PAGE_SIZE + 10]);
+
+        // the pages beyond the end are not reserved with `keep_size`
+        data.allocate(0, PAGE_SIZE as u64 * 4, true, &fs).unwrap();
+        assert_eq!(data.size(), PAGE_SIZE as u64 + 10);
+        assert_eq!(data.resident_pages(), 2);
+        assert!(!data.has_data_beyond_size());
+    }
+
+    #[test]
+    fn test_file_data_punch_hole() {
+        let fs = FsState::default();
+        let mut data = FileData::default();
+        data.write(0, &[1; PAGE_SIZE * 3], &fs).unwrap();
+        let range = data.punch_hole(10, PAGE_SIZE as u64 * 2, &fs).unwrap();
+        assert_eq!(range, 10..PAGE_SIZE as u64 * 2 + 10);
+        // the middle page is freed, the others are zeroed in part
+        assert_eq!(data.resident_pages(), 2);
+        let content = read_all(&data, &fs);
+        assert_eq!(&content[..10], [1; 10]);
+        assert!(content[10..PAGE_SIZE * 2 + 10].iter().all(|&b| b == 0));
+        assert!(content[PAGE_SIZE * 2 + 10..].iter().all(|&b| b == 1));
+        assert_eq!(data.size(), PAGE_SIZE as u64 * 3);
+
+        // punching up to the end frees the last page
+        data.punch_hole(PAGE_SIZE as u64 * 2, u64::MAX / 2, &fs)
+            .unwrap();
+        assert_eq!(data.resident_pages(), 1);
+        assert_eq!(data.size(), PAGE_SIZE as u64 * 3);
+        assert_eq!(
+            data.punch_hole(PAGE_SIZE as u64 * 5, 1, &fs),
+            Ok(PAGE_SIZE as u64 * 5..PAGE_SIZE as u64 * 5)
+        );
+    }
+
+    #[test]
+    fn test_file_data_seek_hint() {
+        let fs = FsState::default();
+        let page = PAGE_SIZE as u64;
+        let mut data = FileData::default();
+        data.write(page * 2 + 5, b"data", &fs).unwrap();
+        data.write(page * 3, b"more", &fs).unwrap();
+        data.truncate(page * 6, &fs).unwrap();
+
+        assert_eq!(data.seek_hint(0, SeekHint::Data), Some(page * 2));
+        assert_eq!(
+            data.seek_hint(page * 2 + 7, SeekHint::Data),
+            Some(page * 2 + 7)
+        );
+        assert_eq!(data.seek_hint(0, SeekHint::Hole), Some(0));
+        assert_eq!(data.seek_hint(page * 2, SeekHint::Hole), Some(page * 4));
+        assert_eq!(data.seek_hint(page * 4, SeekHint::Data), None);
+        assert_eq!(data.seek_hint(page * 5, SeekHint::Hole), Some(page * 5));
+        assert_eq!(data.seek_hint(page * 6, SeekHint::Hole), None);
+
+        // a hole at the end of the content ends with it
+        data.truncate(page * 3 + 10, &fs).unwrap();
+        assert_eq!(
+            data.seek_hint(page * 3, SeekHint::Hole),
+            Some(page * 3 + 10)
+        );
+    }
+
+    #[test]
+    fn test_file_data_max_size() {
+        let fs = FsState::default();
+        let mut data = FileData::default();
+        assert_eq!(data.truncate(u64::MAX, &fs), Err(VfsError::InvalidInput));
+        data.truncate(MAX_FILE_SIZE, &fs).unwrap();
+        let mut buf = [1; 8];
+        assert_eq!(data.read(MAX_FILE_SIZE - 4, &mut buf, &fs), Ok(4));
+        assert_eq!(buf[..4], [0; 4]);
+        assert_eq!(data.read(u64::MAX - 4, &mut buf, &fs), Ok(0));
+
+        assert_eq!(data.write(MAX_FILE_SIZE - 2, b"ab", &fs), Ok(2));
+        assert_eq!(
+            data.write(MAX_FILE_SIZE - 1, b"abcd", &fs),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            data.write(u64::MAX - 1, b"abcd", &fs),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            data.allocate(u64::MAX - 1, 1, false, &fs),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(data.size(), MAX_FILE_SIZE);
+    }
+
+    #[test]
+    fn test_file_data_read_past_end() {
+        let fs = FsState::default();
+        let mut data = FileData::default();
+        data.write(0, b"abc", &fs).unwrap();
+        let mut buf = [0xff; 8];
+        assert_eq!(data.read(1, &mut buf, &fs), Ok(2));
+        assert_eq!(&buf[..2], b"bc");
+        assert_eq!(data.read(3, &mut buf, &fs), Ok(0));
+        assert_eq!(data.read(100, &mut buf, &fs), Ok(0));
+    }
+
+    #[test]
+    fn test_file_data_beyond_size() {
+        let fs = FsState::default();
+        let mut data = FileData::default();
+        data.write(0, &[1; 10], &fs).unwrap();
+        assert!(!data.has_data_beyond_size());
+
+        let mut page = PageFrame::new();
+        page.get_mut().fill(2);
+        fs.charge_page().unwrap();
+        data.pages.insert(3, Page::resident(Arc::new(page)));
+        assert!(data.has_data_beyond_size());
+        data.trim(&fs);
+        assert!(!data.has_data_beyond_size());
+        assert_eq!(data.resident_pages(), 1);
+
+        if let Some(Page::Resident { data, .. }) = data.pages.get_mut(&0) {
+            Arc::get_mut(data).unwrap().get_mut()[20] = 3;
+        }
+        assert!(data.has_data_beyond_size());
+        data.trim(&fs);
+        assert!(!data.has_data_beyond_size());
+        assert_eq!(read_all(&data, &fs), [1; 10]);
+        assert_eq!(fs.allocated_pages(), 1);
+    }
+
+    #[test]
+    fn test_file_data_page_limit() {
+        let fs = FsState::default();
+        fs.set_page_limit(Some(2));
+        let mut data = FileData::default();
+        // the write stops at the end of the second page
+        assert_eq!(
+            data.write(100, &[1; 3 * PAGE_SIZE], &fs),
+            Ok(2 * PAGE_SIZE - 100)
+        );
+        assert_eq!(fs.allocated_pages(), 2);
+        assert_eq!(
+            data.write(2 * PAGE_SIZE as u64, b"x", &fs),
+            Err(VfsError::StorageFull)
+        );
+        // holes take no space
+        assert_eq!(data.truncate(10 * PAGE_SIZE as u64, &fs), Ok(()));
+        assert_eq!(
+            data.allocate(0, 3 * PAGE_SIZE as u64, false, &fs),
+            Err(VfsError::StorageFull)
+        );
+
+        data.punch_hole(0, PAGE_SIZE as u64, &fs).unwrap();
+        assert_eq!(fs.allocated_pages(), 1);
+        assert_eq!(data.write(5 * PAGE_SIZE as u64, b"x", &fs), Ok(1));
+        data.clear(&fs);
+        assert_eq!(fs.allocated_pages(), 0);
+    }
+
+    #[test]
+    fn test_file_data_page_limit_mapped() {
+        let fs = FsState::default();
+        fs.set_page_limit(Some(1));
+        let mut data = FileData::default();
+        assert_eq!(data.write(0, b"x", &fs), Ok(1));
+        let mapping = data.get_page(0, &fs).unwrap();
+        data.truncate(0, &fs).unwrap();
+
+        // the mapped page still takes space
+        assert_eq!(fs.allocated_pages(), 1);
+        assert_eq!(data.write(0, b"y", &fs), Err(VfsError::StorageFull));
+        drop(mapping);
+        assert_eq!(fs.allocated_pages(), 0);
+        assert_eq!(data.write(0, b"y", &fs), Ok(1));
+    }
+}
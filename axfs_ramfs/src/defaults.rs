@@ -0,0 +1,85 @@
+use axfs_vfs::{SetAttr, VfsNodePerm, VfsNodeType};
+
+/// The permissions and owner inherited by the nodes created in a directory.
+///
+/// This works like a POSIX default ACL: once set on a directory with
+/// [`DirNode::set_default_attrs()`](crate::DirNode::set_default_attrs),
+/// files and directories created in it get these attributes instead of the
+/// built-in defaults, and new subdirectories inherit the template itself.
+/// Attributes left to `None` keep the built-in defaults.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_ramfs::{DefaultAttrs, DirNode, RamFileSystem};
+/// use axfs_vfs::{VfsNodeOps, VfsNodePerm, VfsNodeRefExt, VfsNodeType, VfsOps};
+///
+/// let fs = RamFileSystem::new();
+/// fs.root_dir().create("spool", VfsNodeType::Dir).unwrap();
+/// let spool = fs.root_dir().lookup_as::<DirNode>("spool").unwrap();
+/// spool.set_default_attrs(Some(DefaultAttrs {
+///     file_mode: Some(VfsNodePerm::from_bits_truncate(0o660)),
+///     gid: Some(7),
+///     ..Default::default()
+/// }));
+///
+/// spool.create_node("job", VfsNodeType::File).unwrap();
+/// let attr = fs.root_dir().lookup("spool/job").unwrap().get_attr().unwrap();
+/// assert_eq!((attr.perm().mode(), attr.gid()), (0o660, 7));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultAttrs {
+    /// Permissions of the new files and device nodes.
+    pub file_mode: Option<VfsNodePerm>,
+    /// Permissions of the new directories.
+    pub dir_mode: Option<VfsNodePerm>,
+    /// User ID of the owner of the new nodes.
+    pub uid: Option<u32>,
+    /// Group ID of the owner of the new nodes.
+    pub gid: Option<u32>,
+}
+
+impl DefaultAttrs {
+    /// Returns the attributes to set on a new node of type `ty`.
+    pub(crate) fn to_set_attr(self, ty: VfsNodeType) -> SetAttr {
+        let mut attr = SetAttr::new();
+        let mode = match ty {
+            VfsNodeType::Dir => self.dir_mode,
+            _ => self.file_mode,
+        };
+        if let Some(mode) = mode {
+            attr = attr.mode(mode);
+        }
+        if let Some(uid) = self.uid {
+            attr = attr.uid(uid);
+        }
+        if let Some(gid) = self.gid {
+            attr = attr.gid(gid);
+        }
+        attr
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_attrs_to_set_attr() {
+        let defaults = DefaultAttrs {
+            file_mode: Some(VfsNodePerm::from_bits_truncate(0o600)),
+            dir_mode: Some(VfsNodePerm::from_bits_truncate(0o700)),
+            uid: Some(5),
+            gid: None,
+        };
+        let attr = defaults.to_set_attr(VfsNodeType::File);
+        assert_eq!(attr.get_mode().unwrap().mode(), 0o600);
+        assert_eq!((attr.get_uid(), attr.get_gid()), (Some(5), None));
+        let attr = defaults.to_set_attr(VfsNodeType::Dir);
+        assert_eq!(attr.get_mode().unwrap().mode(), 0o700);
+
+        let attr = DefaultAttrs::default().to_set_attr(VfsNodeType::File);
+        assert!(attr.get_mode().is_none());
+        assert_eq!(attr.get_uid(), None);
+    }
+}
@@ -0,0 +1,265 @@
+use alloc::sync::Arc;
+use core::task::Waker;
+
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{
+    DeviceId, FallocateMode, OpenFlags, PollEvents, SeekHint, SetAttr, VfsError, VfsFileRef,
+    VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsPage, VfsResult,
+};
+use spin::Mutex;
+
+use crate::meta::NodeMeta;
+use crate::state::FsState;
+
+/// The device node in RAM filesystem.
+///
+/// The node only records a device type and number. Its operations are
+/// forwarded to the driver returned by the device resolver of the
+/// filesystem (see
+/// [`RamFileSystem::set_device_resolver()`](crate::RamFileSystem::set_device_resolver)),
+/// and fail with [`VfsError::NoSuchDevice`] if there is none.
+///
+/// Whiteouts, marking the entries of the lower layers of an overlay as
+/// removed, are device nodes of type [`VfsNodeType::Whiteout`] and number
+/// `0:0`, which no driver is registered for.
+///
+/// # Fields
+///
+/// - `fs` - The state shared with the other nodes of the filesystem
+/// - `ino` - The inode number of the node
+/// - `ty` - The type of the device
+/// - `dev` - The device number
+/// - `meta` - The permissions, owner and timestamps of the node
+/// - `opens` - The open handles of the node
+pub struct DeviceNode {
+    fs: Arc<FsState>,
+    ino: u64,
+    ty: VfsNodeType,
+    dev: DeviceId,
+    meta: Mutex<NodeMeta>,
+    opens: OpenState,
+}
+
+impl DeviceNode {
+    /// Creates a new device node.
+    ///
+    /// # Arguments
+    ///
+    /// * `fs` - The state of the filesystem the node belongs to
+    /// * `ty` - The type of the device, character or block
+    /// * `dev` - The device number
+    ///
+    /// # Returns
+    ///
+    /// A new device node.
+    pub(super) fn new(fs: Arc<FsState>, ty: VfsNodeType, dev: DeviceId) -> Self {
+        let meta = NodeMeta::new(fs.now(), VfsNodePerm::default_file());
+        Self {
+            ino: fs.alloc_ino(),
+            fs,
+            ty,
+            dev,
+            meta: Mutex::new(meta),
+            opens: OpenState::new(),
+        }
+    }
+
+    /// Returns the inode number of the node.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the device number of the node.
+    pub fn device_id(&self) -> DeviceId {
+        self.dev
+    }
+
+    /// Returns the driver of the device.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NoSuchDevice`] if no driver is registered.
+    fn device(&self) -> VfsResult<VfsNodeRef> {
+        self.fs
+            .device_resolver()
+            .and_then(|resolver| resolver.resolve(self.ty, self.dev))
+            .ok_or(VfsError::NoSuchDevice)
+    }
+}
+
+impl Drop for DeviceNode {
+    fn drop(&mut self) {
+        self.fs.unregister_ino(self.ino);
+    }
+}
+
+impl axfs_vfs::VfsFileNodeOps for DeviceNode {
+    /// Returns the state counting the open handles of the node.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Opens the device.
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of the driver, including its per-open file, or
+    /// [`VfsError::NoSuchDevice`] if no driver is registered.
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        self.device()?.open(flags)
+    }
+
+    /// Closes the device.
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of the driver, or `Ok(())` if the driver has been
+    /// unregistered since the device was opened.
+    fn release(&self) -> VfsResult {
+        self.device().map_or(Ok(()), |dev| dev.release())
+    }
+
+    /// Returns the attributes of the node.
+    ///
+    /// # Returns
+    ///
+    /// Returns the attributes of a device node of size 0, whatever the
+    /// driver reports.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = VfsNodeAttr::builder().ty(self.ty).ino(self.ino).build();
+        Ok(self.meta.lock().fill_attr(attr))
+    }
+
+    /// Returns the generation of the filesystem.
+    fn generation(&self) -> u32 {
+        self.fs.generation()
+    }
+
+    /// Changes the permissions, owner or times of the node.
+    ///
+    /// A size change is forwarded to the driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `attr` - The attributes to change
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or
+    /// [`VfsError::WouldBlock`] if the filesystem is frozen.
+    fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        if let Some(size) = attr.get_size() {
+            VfsNodeOps::truncate(self, size)?;
+        }
+        self.meta.lock().apply(attr, self.fs.now());
+        Ok(())
+    }
+
+    /// Reads from the device.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.device()?.read_at(offset, buf)
+    }
+
+    /// Writes to the device.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.device()?.write_at(offset, buf)
+    }
+
+    /// Synchronizes the device.
+    fn fsync(&self) -> VfsResult {
+        self.device()?.fsync()
+    }
+
+    /// Synchronizes the data of the device.
+    fn fsync_data(&self) -> VfsResult {
+        self.device()?.fsync_data()
+    }
+
+    /// Prefetches a range of the device.
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        self.device()?.readahead(offset, len)
+    }
+
+    /// Sends a control command to the device.
+    fn ioctl(&self, cmd: u32, arg: usize) -> VfsResult<usize> {
+        self.device()?.ioctl(cmd, arg)
+    }
+
+    /// Returns the readiness of the device.
+    fn poll(&self) -> VfsResult<PollEvents> {
+        self.device()?.poll()
+    }
+
+    /// Registers a waker for the readiness of the device.
+    fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+        self.device()?.register_waker(events, waker)
+    }
+
+    /// Returns a page of the device to map in memory.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        self.device()?.get_page(offset)
+    }
+
+    /// Truncates the device.
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.device()?.truncate(size)
+    }
+
+    /// Finds the next region of data or the next hole of the device.
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        self.device()?.seek_hint(offset, hint)
+    }
+
+    /// Allocates or deallocates a range of the device.
+    fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        self.device()?.fallocate(offset, len, mode)
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(DeviceNode);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axfs_vfs::{DeviceResolver, VfsNodeRef};
+
+    struct Resolver(VfsNodeRef);
+
+    impl DeviceResolver for Resolver {
+        fn resolve(&self, ty: VfsNodeType, dev: DeviceId) -> Option<VfsNodeRef> {
+            (ty == VfsNodeType::CharDevice && dev == DeviceId::new(1, 5)).then(|| self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_device_node_dispatch() {
+        let fs = Arc::new(FsState::default());
+        let node = DeviceNode::new(fs.clone(), VfsNodeType::CharDevice, DeviceId::new(1, 5));
+        let other = DeviceNode::new(fs.clone(), VfsNodeType::BlockDevice, DeviceId::new(1, 5));
+        let mut buf = [1; 4];
+        assert_eq!(
+            node.open(OpenFlags::READ).err(),
+            Some(VfsError::NoSuchDevice)
+        );
+        assert_eq!(node.read_at(0, &mut buf), Err(VfsError::NoSuchDevice));
+        assert_eq!(node.poll(), Err(VfsError::NoSuchDevice));
+
+        let file = Arc::new(crate::file::FileNode::new(fs.clone()));
+        file.write_at(0, b"data").unwrap();
+        fs.set_device_resolver(Some(Arc::new(Resolver(file))));
+        assert!(node.open(OpenFlags::READ).is_ok());
+        assert_eq!(node.read_at(0, &mut buf), Ok(4));
+        assert_eq!(&buf, b"data");
+        assert_eq!(node.write_at(4, b"!"), Ok(1));
+        assert_eq!(other.write_at(0, b"!"), Err(VfsError::NoSuchDevice));
+        assert_eq!(node.ioctl(0x5401, 0), Err(VfsError::NotATty));
+        assert_eq!(other.ioctl(0x5401, 0), Err(VfsError::NoSuchDevice));
+        assert_eq!(node.poll(), Ok(PollEvents::IN | PollEvents::OUT));
+
+        let attr = node.get_attr().unwrap();
+        assert_eq!(attr.file_type(), VfsNodeType::CharDevice);
+        assert_eq!(attr.size(), 0);
+        assert_eq!(node.device_id(), DeviceId::new(1, 5));
+    }
+}
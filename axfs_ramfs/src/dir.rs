@@ -1,12 +1,30 @@
+use alloc::borrow::Cow;
 use alloc::collections::BTreeMap;
 use alloc::sync::{Arc, Weak};
-use alloc::{string::String, vec::Vec};
+use alloc::{boxed::Box, format, string::String, vec::Vec};
+use core::ops::Bound;
+use core::sync::atomic::{AtomicBool, Ordering};
 
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType};
-use axfs_vfs::{VfsError, VfsResult};
-use spin::RwLock;
+use axfs_vfs::access::Access;
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::notify::{WatchEvent, WatchMask};
+use axfs_vfs::path::Path;
+use axfs_vfs::trace::{self, TraceOp};
+use axfs_vfs::SnapshotDirStream;
+use axfs_vfs::VfsNodeRefExt;
+use axfs_vfs::{AttrMask, VfsDirEntry, VfsNodeAttr, VfsNodeAttrExt, VfsNodeOps, VfsNodeRef};
+use axfs_vfs::{DeviceId, OpenFlags, VfsFileRef, VfsNodeFlags, VfsNodePerm, VfsNodeType};
+use axfs_vfs::{DirCookie, DirStream, ReadDirOptions, ReadDirPolicy, RenameFlags, SetAttr};
+use axfs_vfs::{Umask, VfsContext, VfsError, VfsResult};
+use spin::{Mutex, RwLock, RwLockWriteGuard};
 
+use crate::defaults::DefaultAttrs;
+use crate::device::DeviceNode;
 use crate::file::FileNode;
+use crate::fsck::{FileLinks, FsckIssue, FsckReport};
+use crate::meta::{check_unlinkable, NodeMeta};
+use crate::state::FsState;
+use crate::symlink::SymlinkNode;
 
 /// The directory node in RAM filesystem.
 ///
@@ -16,12 +34,31 @@ use crate::file::FileNode;
 /// # Fields
 ///
 /// - `this` - Weak reference to self for creating child directories
+/// - `fs` - The state shared with the other nodes of the filesystem
+/// - `ino` - The inode number of the directory
 /// - `parent` - Weak reference to parent directory
+/// - `name` - The name of the directory in its parent, empty for the root
+///   directory
 /// - `children` - Map of child node names to their references
+/// - `meta` - The timestamps of the directory
+/// - `defaults` - The attributes inherited by the nodes created in the
+///   directory
+/// - `mount_point` - Whether a filesystem is mounted on the directory
+/// - `removed` - Whether the directory has been removed, after which no
+///   entry can be added to it
+/// - `opens` - The open handles of the directory
 pub struct DirNode {
     this: Weak<DirNode>,
+    fs: Arc<FsState>,
+    ino: u64,
     parent: RwLock<Weak<dyn VfsNodeOps>>,
+    name: RwLock<String>,
     children: RwLock<BTreeMap<String, VfsNodeRef>>,
+    meta: Mutex<NodeMeta>,
+    defaults: RwLock<Option<DefaultAttrs>>,
+    mount_point: AtomicBool,
+    removed: AtomicBool,
+    opens: OpenState,
 }
 
 impl DirNode {
@@ -30,15 +67,25 @@ impl DirNode {
     /// # Arguments
     ///
     /// * `parent` - Optional weak reference to parent directory
+    /// * `fs` - The state of the filesystem the directory belongs to
     ///
     /// # Returns
     ///
     /// A new directory node wrapped in an Arc.
-    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps>>) -> Arc<Self> {
+    pub(super) fn new(parent: Option<Weak<dyn VfsNodeOps>>, fs: Arc<FsState>) -> Arc<Self> {
+        let meta = NodeMeta::new(fs.now(), VfsNodePerm::default_dir());
         Arc::new_cyclic(|this| Self {
             this: this.clone(),
+            ino: fs.alloc_ino(),
+            fs,
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::<Self>::new())),
+            name: RwLock::new(String::new()),
             children: RwLock::new(BTreeMap::new()),
+            meta: Mutex::new(meta),
+            defaults: RwLock::new(None),
+            mount_point: AtomicBool::new(false),
+            removed: AtomicBool::new(false),
+            opens: OpenState::new(),
         })
     }
 
@@ -51,6 +98,96 @@ impl DirNode {
         *self.parent.write() = parent.map_or(Weak::<Self>::new() as _, Arc::downgrade);
     }
 
+    /// Returns the inode number of the directory.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns a weak reference to this directory.
+    pub(crate) fn weak(&self) -> Weak<Self> {
+        self.this.clone()
+    }
+
+    /// Returns the absolute path of this directory, without the trailing
+    /// `/` (empty for the root directory).
+    ///
+    /// The path is built from the names of the ancestors, which are only
+    /// stable while the renames are locked with [`FsState::lock_renames`].
+    ///
+    /// # Returns
+    ///
+    /// The path, or `None` if the directory is not reachable from the root
+    /// directory.
+    pub(crate) fn path(&self) -> Option<String> {
+        let root = self.fs.root()?;
+        let mut names = Vec::new();
+        let mut cur = self.this.upgrade()?;
+        while !Arc::ptr_eq(&cur, &root) {
+            names.push(cur.name.read().clone());
+            let parent = cur.parent.read().upgrade()?;
+            cur = parent.downcast::<DirNode>().ok()?;
+        }
+        Some(names.iter().rev().map(|name| format!("/{name}")).collect())
+    }
+
+    /// Returns the absolute path of the entry `name` of this directory.
+    fn child_path(&self, name: &str) -> Option<String> {
+        self.path().map(|path| format!("{path}/{name}"))
+    }
+
+    /// Returns the inode number of the parent directory, or of this
+    /// directory if it has no parent.
+    fn parent_ino(&self) -> u64 {
+        self.parent
+            .read()
+            .upgrade()
+            .and_then(|parent| parent.get_attr().ok())
+            .map_or(self.ino, |attr| attr.ino())
+    }
+
+    /// Sets the attributes inherited by the files and directories created
+    /// in this directory from now on.
+    ///
+    /// New subdirectories inherit `defaults` as well. Existing nodes are
+    /// left unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `defaults` - The inherited attributes, or `None` to create nodes
+    ///   with the built-in defaults
+    pub fn set_default_attrs(&self, defaults: Option<DefaultAttrs>) {
+        *self.defaults.write() = defaults;
+    }
+
+    /// Returns the attributes inherited by the nodes created in this
+    /// directory, if set.
+    pub fn default_attrs(&self) -> Option<DefaultAttrs> {
+        *self.defaults.read()
+    }
+
+    /// Checks that this directory has not been removed, while its entries
+    /// are locked.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if the directory has been removed, as
+    /// no entry can be added to it anymore.
+    fn check_not_removed(&self) -> VfsResult {
+        if self.removed.load(Ordering::Acquire) {
+            return Err(VfsError::NotFound);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of subdirectories of this directory.
+    fn subdir_count(&self) -> u64 {
+        self.children
+            .read()
+            .values()
+            .filter(|node| node.as_any().is::<DirNode>())
+            .count() as u64
+    }
+
     /// Returns a list of all entry names in this directory.
     ///
     /// # Returns
@@ -70,7 +207,25 @@ impl DirNode {
     ///
     /// `true` if a node with the given name exists, `false` otherwise.
     pub fn exist(&self, name: &str) -> bool {
-        self.children.read().contains_key(name)
+        self.children.read().contains_key(self.key(name).as_ref())
+    }
+
+    /// Returns the key of the child `name` under the name policy of the
+    /// filesystem.
+    fn key<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        self.fs.name_policy().lookup_key(name)
+    }
+
+    /// Checks the name of a new child against the
+    /// [`limits`](axfs_vfs::limits) and the name policy of the filesystem.
+    ///
+    /// # Returns
+    ///
+    /// Returns the name to store the child with.
+    fn new_name<'a>(&self, name: &'a str) -> VfsResult<Cow<'a, str>> {
+        let name = self.fs.name_policy().apply(name)?;
+        axfs_vfs::limits::check_name(&name)?;
+        Ok(name)
     }
 
     /// Creates a new node with the given name and type in this directory.
@@ -78,7 +233,7 @@ impl DirNode {
     /// # Arguments
     ///
     /// * `name` - The name for the new node
-    /// * `ty` - The type of node to create (file or directory)
+    /// * `ty` - The type of node to create (file, directory or whiteout)
     ///
     /// # Returns
     ///
@@ -87,18 +242,223 @@ impl DirNode {
     /// # Errors
     ///
     /// Returns [`VfsError::AlreadyExists`] if a node with the same name exists.
+    /// Returns [`VfsError::NotFound`] if the directory has been removed.
+    /// Returns [`VfsError::NameTooLong`] if the name is longer than
+    /// [`MAX_NAME_LEN`](axfs_vfs::limits::MAX_NAME_LEN).
+    /// Returns [`VfsError::IllegalBytes`] if the name is rejected by the
+    /// [`NamePolicy`](axfs_vfs::names::NamePolicy) of the filesystem.
     /// Returns [`VfsError::Unsupported`] if the node type is not supported.
+    /// Returns [`VfsError::OperationNotPermitted`] if the directory is
+    /// immutable.
+    /// Returns [`VfsError::WouldBlock`] if the filesystem is frozen.
     pub fn create_node(&self, name: &str, ty: VfsNodeType) -> VfsResult {
+        self.create_node_as(name, ty, None)
+    }
+
+    /// Creates a new node like [`create_node()`](Self::create_node), owned
+    /// by the caller of `ctx`.
+    ///
+    /// The umask of `ctx`, or without a context the umask of the
+    /// filesystem, is applied to the built-in permissions of the node. The
+    /// default attributes of the directory, if set, take
+    /// precedence, as a default ACL does.
+    fn create_node_as(&self, name: &str, ty: VfsNodeType, ctx: Option<&VfsContext>) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().check_changeable()?;
+        let name: &str = &self.new_name(name)?;
         if self.exist(name) {
             log::error!("AlreadyExists {name}");
             return Err(VfsError::AlreadyExists);
         }
-        let node: VfsNodeRef = match ty {
-            VfsNodeType::File => Arc::new(FileNode::new()),
-            VfsNodeType::Dir => Self::new(Some(self.this.clone())),
+        let defaults = self.default_attrs();
+        let (ino, node): (u64, VfsNodeRef) = match ty {
+            VfsNodeType::File => {
+                let file = Arc::new(FileNode::new(self.fs.clone()));
+                (file.ino(), file)
+            }
+            VfsNodeType::Dir => {
+                let dir = Self::new(Some(self.this.clone()), self.fs.clone());
+                dir.set_default_attrs(defaults);
+                *dir.name.write() = name.into();
+                (dir.ino(), dir)
+            }
+            VfsNodeType::Whiteout => {
+                let whiteout = Arc::new(DeviceNode::new(self.fs.clone(), ty, DeviceId::new(0, 0)));
+                (whiteout.ino(), whiteout)
+            }
             _ => return Err(VfsError::Unsupported),
         };
-        self.children.write().insert(name.into(), node);
+        init_attrs(&node, ty, ctx, self.fs.umask(), defaults)?;
+        // checked again under the lock, for concurrent exclusive creations
+        let mut children = self.children.write();
+        self.check_not_removed()?;
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.fs.register_ino(ino, &node);
+        if let Some(file) = node.as_any().downcast_ref::<FileNode>() {
+            file.add_entry(self, name);
+        }
+        children.insert(name.into(), node);
+        drop(children);
+        self.meta.lock().touch_modify(self.fs.now());
+        self.fs.notify(
+            self.ino,
+            WatchMask::CREATE,
+            Some(name),
+            ty == VfsNodeType::Dir,
+        );
+        Ok(())
+    }
+
+    /// Creates a device node with the given name in this directory.
+    ///
+    /// The node inherits the file permissions and the owner of the default
+    /// attributes of the directory, if set.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name for the new node
+    /// * `ty` - The type of the device, character or block
+    /// * `dev` - The device number
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::AlreadyExists`] if a node with the same name exists.
+    /// Returns [`VfsError::NotFound`] if the directory has been removed.
+    /// Returns [`VfsError::InvalidInput`] if `ty` is not a device type.
+    /// Returns [`VfsError::NameTooLong`] if the name is longer than
+    /// [`MAX_NAME_LEN`](axfs_vfs::limits::MAX_NAME_LEN).
+    /// Returns [`VfsError::IllegalBytes`] if the name is rejected by the
+    /// [`NamePolicy`](axfs_vfs::names::NamePolicy) of the filesystem.
+    /// Returns [`VfsError::OperationNotPermitted`] if the directory is
+    /// immutable.
+    /// Returns [`VfsError::WouldBlock`] if the filesystem is frozen.
+    pub fn create_device_node(&self, name: &str, ty: VfsNodeType, dev: DeviceId) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().check_changeable()?;
+        let name: &str = &self.new_name(name)?;
+        if !matches!(ty, VfsNodeType::CharDevice | VfsNodeType::BlockDevice) {
+            return Err(VfsError::InvalidInput);
+        }
+        let node = Arc::new(DeviceNode::new(self.fs.clone(), ty, dev));
+        let node_ref: VfsNodeRef = node.clone();
+        init_attrs(&node_ref, ty, None, self.fs.umask(), self.default_attrs())?;
+        let mut children = self.children.write();
+        self.check_not_removed()?;
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        self.fs
+            .register_ino(node.ino(), &(node.clone() as VfsNodeRef));
+        children.insert(name.into(), node);
+        drop(children);
+        self.meta.lock().touch_modify(self.fs.now());
+        self.fs
+            .notify(self.ino, WatchMask::CREATE, Some(name), false);
+        Ok(())
+    }
+
+    /// Creates a symbolic link with the given name in this directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name for the new link
+    /// * `target` - The path the link points to
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the link was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::AlreadyExists`] if a node with the same name exists.
+    /// Returns [`VfsError::NotFound`] if the directory has been removed.
+    /// Returns [`VfsError::NameTooLong`] if the name is longer than
+    /// [`MAX_NAME_LEN`](axfs_vfs::limits::MAX_NAME_LEN), or the target longer
+    /// than [`MAX_PATH`](axfs_vfs::limits::MAX_PATH).
+    /// Returns [`VfsError::IllegalBytes`] if the name is rejected by the
+    /// [`NamePolicy`](axfs_vfs::names::NamePolicy) of the filesystem.
+    /// Returns [`VfsError::OperationNotPermitted`] if the directory is
+    /// immutable.
+    /// Returns [`VfsError::WouldBlock`] if the filesystem is frozen.
+    pub fn create_symlink_node(&self, name: &str, target: &str) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().check_changeable()?;
+        let name: &str = &self.new_name(name)?;
+        if target.len() > axfs_vfs::limits::MAX_PATH {
+            return Err(VfsError::NameTooLong);
+        }
+        let mut children = self.children.write();
+        self.check_not_removed()?;
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        let node = Arc::new(SymlinkNode::new(self.fs.clone(), target));
+        self.fs
+            .register_ino(node.ino(), &(node.clone() as VfsNodeRef));
+        children.insert(name.into(), node);
+        drop(children);
+        self.meta.lock().touch_modify(self.fs.now());
+        self.fs
+            .notify(self.ino, WatchMask::CREATE, Some(name), false);
+        Ok(())
+    }
+
+    /// Adds an entry with the given name referring to an existing file.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name for the new entry
+    /// * `node` - The node to link, which must be a file of this filesystem
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the entry was added, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::AlreadyExists`] if a node with the same name exists.
+    /// Returns [`VfsError::NotFound`] if the directory has been removed.
+    /// Returns [`VfsError::NameTooLong`] if the name is longer than
+    /// [`MAX_NAME_LEN`](axfs_vfs::limits::MAX_NAME_LEN).
+    /// Returns [`VfsError::IllegalBytes`] if the name is rejected by the
+    /// [`NamePolicy`](axfs_vfs::names::NamePolicy) of the filesystem.
+    /// Returns [`VfsError::OperationNotPermitted`] if the node is not a file,
+    /// the file is immutable or append-only, or the directory is immutable.
+    /// Returns [`VfsError::CrossesDevices`] if the file belongs to another
+    /// filesystem.
+    /// Returns [`VfsError::NotFound`] if the file has been removed from all
+    /// its directories, unless it is a temporary file that was never linked.
+    /// Returns [`VfsError::WouldBlock`] if the filesystem is frozen.
+    pub fn link_node(&self, name: &str, node: VfsNodeRef) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        let name: &str = &self.new_name(name)?;
+        let file = node
+            .as_any()
+            .downcast_ref::<FileNode>()
+            .ok_or(VfsError::OperationNotPermitted)?;
+        if !file.belongs_to(&self.fs) {
+            return Err(VfsError::CrossesDevices);
+        }
+        self.meta.lock().check_changeable()?;
+        check_unlinkable(file)?;
+        let mut children = self.children.write();
+        self.check_not_removed()?;
+        if children.contains_key(name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        file.link(&node)?;
+        file.add_entry(self, name);
+        children.insert(name.into(), node);
+        drop(children);
+        self.meta.lock().touch_modify(self.fs.now());
+        self.fs
+            .notify(self.ino, WatchMask::CREATE, Some(name), false);
         Ok(())
     }
 
@@ -118,27 +478,652 @@ impl DirNode {
     ///
     /// Returns [`VfsError::NotFound`] if the node does not exist.
     /// Returns [`VfsError::DirectoryNotEmpty`] if attempting to remove a non-empty directory.
+    /// Returns [`VfsError::OperationNotPermitted`] if the node is immutable
+    /// or append-only, or the directory is.
+    /// Returns [`VfsError::WouldBlock`] if the filesystem is frozen.
     pub fn remove_node(&self, name: &str) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        let name: &str = &self.key(name);
+        self.meta.lock().check_rewritable()?;
         let mut children = self.children.write();
-        let node = children.get(name).ok_or(VfsError::NotFound)?;
-        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
-            if !dir.children.read().is_empty() {
-                return Err(VfsError::DirectoryNotEmpty);
+        let node = children.get(name).ok_or(VfsError::NotFound)?.clone();
+        check_unlinkable(node.as_ref())?;
+        let entries = lock_empty(node.as_ref())?;
+        if let Some(backend) = self.fs.backend() {
+            if let Some(path) = self.child_path(name) {
+                backend.remove(&path)?;
             }
         }
+        self.forget_node(name, node.as_ref());
+        let is_dir = node.as_any().is::<DirNode>();
         children.remove(name);
+        drop((entries, children));
+        self.meta.lock().touch_modify(self.fs.now());
+        self.fs
+            .notify(self.ino, WatchMask::DELETE, Some(name), is_dir);
+        Ok(())
+    }
+
+    /// Moves the node `src_name` of this directory to `dst_name` in `dst`.
+    ///
+    /// An existing node at the destination is replaced atomically: a file
+    /// can replace a file, and a directory an empty directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_name` - The name of the node to move
+    /// * `dst` - The destination directory, which may be this directory
+    /// * `dst_name` - The new name of the node
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was moved, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if the node does not exist, or `dst`
+    /// has been removed.
+    /// Returns [`VfsError::InvalidInput`] if a name is `.` or `..`, or if a
+    /// directory would be moved into itself.
+    /// Returns [`VfsError::NameTooLong`] if `dst_name` is longer than
+    /// [`MAX_NAME_LEN`](axfs_vfs::limits::MAX_NAME_LEN).
+    /// Returns [`VfsError::IllegalBytes`] if `dst_name` is rejected by the
+    /// [`NamePolicy`](axfs_vfs::names::NamePolicy) of the filesystem.
+    /// Returns [`VfsError::IsADirectory`] or [`VfsError::NotADirectory`] if
+    /// the destination is a directory and the node is not, or the reverse.
+    /// Returns [`VfsError::DirectoryNotEmpty`] if the destination is a
+    /// non-empty directory.
+    /// Returns [`VfsError::OperationNotPermitted`] if one of the nodes is
+    /// immutable or append-only, or one of the directories is.
+    /// Returns [`VfsError::CrossesDevices`] if `dst` belongs to another
+    /// filesystem.
+    /// Returns [`VfsError::WouldBlock`] if the filesystem is frozen.
+    pub fn rename_node(&self, src_name: &str, dst: &DirNode, dst_name: &str) -> VfsResult {
+        self.rename_node_flags(src_name, dst, dst_name, RenameFlags::empty())
+    }
+
+    /// Moves the node `src_name` of this directory to `dst_name` in `dst`,
+    /// with the options of `flags`.
+    ///
+    /// With [`RenameFlags::EXCHANGE`], the two nodes are swapped, and may be
+    /// of different types.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`rename_node()`](Self::rename_node), or:
+    /// - [`VfsError::InvalidInput`] if both flags are set, or a directory
+    ///   would be exchanged with one of its descendants.
+    /// - [`VfsError::AlreadyExists`] if `flags` contains
+    ///   [`RenameFlags::NOREPLACE`] and the destination exists.
+    /// - [`VfsError::NotFound`] if `flags` contains [`RenameFlags::EXCHANGE`]
+    ///   and the destination does not exist.
+    pub fn rename_node_flags(
+        &self,
+        src_name: &str,
+        dst: &DirNode,
+        dst_name: &str,
+        flags: RenameFlags,
+    ) -> VfsResult {
+        if flags.contains(RenameFlags::NOREPLACE | RenameFlags::EXCHANGE) {
+            return Err(VfsError::InvalidInput);
+        }
+        let _mutation = self.fs.check_mutable()?;
+        let src_name: &str = &self.key(src_name);
+        let dst_name: &str = &dst.new_name(dst_name)?;
+        if [src_name, dst_name]
+            .iter()
+            .any(|name| name.is_empty() || *name == "." || *name == "..")
+        {
+            return Err(VfsError::InvalidInput);
+        }
+        if !Arc::ptr_eq(&self.fs, &dst.fs) {
+            return Err(VfsError::CrossesDevices);
+        }
+        self.meta.lock().check_rewritable()?;
+        dst.meta.lock().check_changeable()?;
+
+        // lock the renames between directories, which may change the
+        // ancestors checked below, then both directories: an ancestor before
+        // its descendant, as a removal locks a parent before its child, and
+        // unrelated directories in address order
+        let same = core::ptr::eq(self, dst);
+        let _renames = (!same).then(|| self.fs.lock_renames());
+        let src_first = if dst.is_within(self) {
+            true
+        } else if self.is_within(dst) {
+            false
+        } else {
+            (self as *const Self) < (dst as *const Self)
+        };
+        let (mut src_children, mut dst_children) = if same {
+            (self.children.write(), None)
+        } else if src_first {
+            let src_children = self.children.write();
+            (src_children, Some(dst.children.write()))
+        } else {
+            let dst_children = dst.children.write();
+            (self.children.write(), Some(dst_children))
+        };
+        dst.check_not_removed()?;
+        let node = src_children
+            .get(src_name)
+            .ok_or(VfsError::NotFound)?
+            .clone();
+        check_unlinkable(node.as_ref())?;
+        let moved_dir = node.as_any().downcast_ref::<DirNode>();
+        if moved_dir.is_some_and(|dir| dst.is_within(dir)) {
+            return Err(VfsError::InvalidInput);
+        }
+        let target = match &dst_children {
+            Some(children) => children.get(dst_name).cloned(),
+            None => src_children.get(dst_name).cloned(),
+        };
+        if target.is_some() && flags.contains(RenameFlags::NOREPLACE) {
+            return Err(VfsError::AlreadyExists);
+        }
+        if flags.contains(RenameFlags::EXCHANGE) {
+            let target = target.ok_or(VfsError::NotFound)?;
+            if Arc::ptr_eq(&target, &node) {
+                return Ok(());
+            }
+            check_unlinkable(target.as_ref())?;
+            let target_dir = target.as_any().downcast_ref::<DirNode>();
+            if target_dir.is_some_and(|dir| self.is_within(dir)) {
+                return Err(VfsError::InvalidInput);
+            }
+            self.persist_rename(src_name, dst, dst_name, flags)?;
+            src_children.insert(src_name.into(), target.clone());
+            match &mut dst_children {
+                Some(children) => children.insert(dst_name.into(), node.clone()),
+                None => src_children.insert(dst_name.into(), node.clone()),
+            };
+            self.moved(node.as_ref(), src_name, dst, dst_name);
+            dst.moved(target.as_ref(), dst_name, self, src_name);
+            drop((src_children, dst_children));
+            self.touch_renamed(dst);
+            self.notify_move(src_name, dst, dst_name, moved_dir.is_some());
+            dst.notify_move(dst_name, self, src_name, target_dir.is_some());
+            return Ok(());
+        }
+        if let Some(target) = target {
+            if Arc::ptr_eq(&target, &node) {
+                return Ok(());
+            }
+            let target_dir = target.as_any().downcast_ref::<DirNode>();
+            match (moved_dir.is_some(), target_dir.is_some()) {
+                (false, true) => return Err(VfsError::IsADirectory),
+                (true, false) => return Err(VfsError::NotADirectory),
+                _ => {}
+            }
+            if target_dir.is_some_and(|dir| self.is_within(dir)) {
+                // the target holds this directory, which is locked already
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+            check_unlinkable(target.as_ref())?;
+            let _entries = lock_empty(target.as_ref())?;
+            self.persist_rename(src_name, dst, dst_name, flags)?;
+            dst.forget_node(dst_name, target.as_ref());
+        } else {
+            self.persist_rename(src_name, dst, dst_name, flags)?;
+        }
+
+        src_children.remove(src_name);
+        match &mut dst_children {
+            Some(children) => children.insert(dst_name.into(), node.clone()),
+            None => src_children.insert(dst_name.into(), node.clone()),
+        };
+        self.moved(node.as_ref(), src_name, dst, dst_name);
+        drop((src_children, dst_children));
+        self.touch_renamed(dst);
+        self.notify_move(src_name, dst, dst_name, moved_dir.is_some());
+        Ok(())
+    }
+
+    /// Returns whether this directory is `dir` or one of its descendants.
+    ///
+    /// The parents are only stable while the renames are locked with
+    /// [`FsState::lock_renames`].
+    fn is_within(&self, dir: &DirNode) -> bool {
+        let mut cur = self.this.upgrade().map(|this| this as VfsNodeRef);
+        while let Some(ancestor) = cur {
+            if core::ptr::addr_eq(Arc::as_ptr(&ancestor), dir) {
+                return true;
+            }
+            cur = ancestor.parent();
+        }
+        false
+    }
+
+    /// Reports the move of `src_name` of this directory to `dst_name` in
+    /// `dst` to the persistence backend, if one is attached.
+    fn persist_rename(
+        &self,
+        src_name: &str,
+        dst: &DirNode,
+        dst_name: &str,
+        flags: RenameFlags,
+    ) -> VfsResult {
+        let Some(backend) = self.fs.backend() else {
+            return Ok(());
+        };
+        match (self.child_path(src_name), dst.child_path(dst_name)) {
+            (Some(src_path), Some(dst_path)) => backend.rename(&src_path, &dst_path, flags),
+            _ => Ok(()),
+        }
+    }
+
+    /// Updates the parent or the directory entries of `node` after its
+    /// move from `src_name` of this directory to `dst_name` in `dst`.
+    fn moved(&self, node: &dyn VfsNodeOps, src_name: &str, dst: &DirNode, dst_name: &str) {
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            let parent = dst.this.upgrade().map(|dst| dst as VfsNodeRef);
+            dir.set_parent(parent.as_ref());
+            *dir.name.write() = dst_name.into();
+        } else if let Some(file) = node.as_any().downcast_ref::<FileNode>() {
+            file.move_entry(self, src_name, dst, dst_name);
+        }
+    }
+
+    /// Updates the modification times of this directory and `dst` after a
+    /// rename between them.
+    fn touch_renamed(&self, dst: &DirNode) {
+        let now = self.fs.now();
+        self.meta.lock().touch_modify(now);
+        if !core::ptr::eq(self, dst) {
+            dst.meta.lock().touch_modify(now);
+        }
+    }
+
+    /// Notifies the watches of the move of `src_name` of this directory to
+    /// `dst_name` in `dst`.
+    fn notify_move(&self, src_name: &str, dst: &DirNode, dst_name: &str, is_dir: bool) {
+        let watches = self.fs.watches();
+        let mut event = WatchEvent {
+            mask: WatchMask::MOVED_FROM,
+            name: Some(src_name),
+            cookie: watches.next_cookie(),
+            is_dir,
+        };
+        watches.notify(self.ino, &event);
+        event.mask = WatchMask::MOVED_TO;
+        event.name = Some(dst_name);
+        watches.notify(dst.ino, &event);
+    }
+
+    /// Releases the resources of the node `name` that is unlinked from this
+    /// directory.
+    ///
+    /// Directories must be locked empty with [`lock_empty`], and are marked
+    /// as removed.
+    fn forget_node(&self, name: &str, node: &dyn VfsNodeOps) {
+        if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+            dir.removed.store(true, Ordering::Release);
+            self.fs.unregister_ino(dir.ino);
+        } else if let Some(file) = node.as_any().downcast_ref::<FileNode>() {
+            file.unlink();
+            file.remove_entry(self, name);
+        } else if let Some(link) = node.as_any().downcast_ref::<SymlinkNode>() {
+            self.fs.unregister_ino(link.ino());
+        } else if let Some(dev) = node.as_any().downcast_ref::<DeviceNode>() {
+            self.fs.unregister_ino(dev.ino());
+        }
+    }
+
+    /// Looks up a node without checking the permissions of the caller.
+    ///
+    /// This method supports path components including `.` (current directory)
+    /// and `..` (parent directory).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if a component does not exist, or
+    /// [`VfsError::NameTooLong`] if the path or one of its components
+    /// exceeds the [`limits`](axfs_vfs::limits).
+    fn lookup_unchecked(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        axfs_vfs::limits::check_path(path)?;
+        let (name, rest) = Path::new(path).split_first();
+        let node = match name {
+            "" | "." => Ok(self.clone() as VfsNodeRef),
+            ".." => self.parent().ok_or(VfsError::NotFound),
+            _ => self
+                .children
+                .read()
+                .get(self.key(name).as_ref())
+                .cloned()
+                .ok_or(VfsError::NotFound),
+        };
+
+        // only the component ending the walk is traced
+        match (node, rest) {
+            (Ok(node), Some(rest)) => node.lookup_ctx(None, rest),
+            (node, _) => trace::record(TraceOp::Lookup, self.ino, name, node),
+        }
+    }
+
+    /// Creates a node without checking the permissions of the caller.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::create_node`], or
+    /// [`VfsError::NotFound`] if a parent directory does not exist.
+    fn create_unchecked(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        log::debug!("create {ty:?} at ramfs: {path}");
+        axfs_vfs::limits::check_path(path)?;
+        let (name, rest) = Path::new(path).split_first();
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.create_unchecked(rest, ty),
+                ".." => self
+                    .parent()
+                    .ok_or(VfsError::NotFound)?
+                    .create_ctx(None, rest, ty),
+                _ => {
+                    let subdir = self
+                        .children
+                        .read()
+                        .get(self.key(name).as_ref())
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.create_ctx(None, rest, ty)
+                }
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Ok(()) // already exists
+        } else {
+            trace::record(TraceOp::Create, self.ino, name, self.create_node(name, ty))
+        }
+    }
+
+    /// Removes a node without checking the permissions of the caller.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::remove_node`], or
+    /// [`VfsError::InvalidInput`] to remove `.` or `..`.
+    fn remove_unchecked(&self, path: &str) -> VfsResult {
+        log::debug!("remove at ramfs: {path}");
+        let (name, rest) = Path::new(path).split_first();
+        if let Some(rest) = rest {
+            match name {
+                "" | "." => self.remove_unchecked(rest),
+                ".." => self
+                    .parent()
+                    .ok_or(VfsError::NotFound)?
+                    .remove_ctx(None, rest),
+                _ => {
+                    let subdir = self
+                        .children
+                        .read()
+                        .get(self.key(name).as_ref())
+                        .ok_or(VfsError::NotFound)?
+                        .clone();
+                    subdir.remove_ctx(None, rest)
+                }
+            }
+        } else if name.is_empty() || name == "." || name == ".." {
+            Err(VfsError::InvalidInput) // remove '.' or '..
+        } else {
+            trace::record(TraceOp::Remove, self.ino, name, self.remove_node(name))
+        }
+    }
+
+    /// Looks up the parent directory of `path`, on behalf of the caller of
+    /// `ctx` if any.
+    ///
+    /// # Returns
+    ///
+    /// The parent directory and the last component of `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if the parent directory does not
+    /// exist, [`VfsError::NotADirectory`] if it is not a directory of this
+    /// filesystem, or [`VfsError::PermissionDenied`] if a directory may not
+    /// be searched.
+    pub(crate) fn split_parent<'a>(
+        self: Arc<Self>,
+        ctx: Option<&VfsContext>,
+        path: &'a str,
+    ) -> VfsResult<(Arc<DirNode>, &'a str)> {
+        let path = path.trim_end_matches('/');
+        match path.rsplit_once('/') {
+            Some((parent, name)) => {
+                let parent = self.lookup_ctx(ctx, parent)?;
+                let dir = parent
+                    .downcast::<DirNode>()
+                    .map_err(|_| VfsError::NotADirectory)?;
+                Ok((dir, name))
+            }
+            None => Ok((self, path)),
+        }
+    }
+
+    /// Calls `f` on every file of this subtree.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - The function to call, stopping the walk on error
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the first error returned by `f`.
+    pub(crate) fn visit_files(&self, f: &mut dyn FnMut(&FileNode) -> VfsResult) -> VfsResult {
+        let children: Vec<_> = self.children.read().values().cloned().collect();
+        for node in children {
+            if let Some(file) = node.as_any().downcast_ref::<FileNode>() {
+                f(file)?;
+            } else if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+                dir.visit_files(f)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pushes the dirty data of every file in this subtree to the
+    /// persistence backend.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the first error reported by the
+    /// backend.
+    pub(crate) fn sync_tree(&self) -> VfsResult {
+        self.visit_files(&mut |file| match file.path() {
+            Some(path) => file.sync_to(&path),
+            None => Ok(()),
+        })
+    }
+
+    /// Checks the consistency of this subtree, see
+    /// [`RamFileSystem::fsck()`](crate::RamFileSystem::fsck).
+    ///
+    /// Directories and symbolic links are checked as they are met, while
+    /// files are collected in `files` to be checked once all the entries
+    /// referring to them have been counted.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The absolute path of this directory, without the
+    ///   trailing `/` (empty for the root directory)
+    /// * `repair` - Whether to repair the anomalies found
+    /// * `report` - The report to add the checked nodes and anomalies to
+    /// * `files` - The files met so far
+    pub(crate) fn fsck_tree(
+        &self,
+        prefix: &str,
+        repair: bool,
+        report: &mut FsckReport,
+        files: &mut FileLinks,
+    ) {
+        let children: Vec<_> = self
+            .children
+            .read()
+            .iter()
+            .map(|(name, node)| (name.clone(), node.clone()))
+            .collect();
+        for (name, node) in children {
+            let path = format!("{prefix}/{name}");
+            if let Ok(file) = node.clone().downcast::<FileNode>() {
+                let key = Arc::as_ptr(&file);
+                files.entry(key).or_insert_with(|| (file, path, 0)).2 += 1;
+                continue;
+            }
+            report.nodes += 1;
+            if let Some(dir) = node.as_any().downcast_ref::<DirNode>() {
+                let parent = dir.parent.read().upgrade();
+                let this: *const Self = self;
+                if !parent.is_some_and(|p| core::ptr::addr_eq(Arc::as_ptr(&p), this)) {
+                    report
+                        .issues
+                        .push(FsckIssue::WrongParent { path: path.clone() });
+                    if repair {
+                        *dir.parent.write() = self.this.clone();
+                    }
+                }
+                dir.fsck_tree(&path, repair, report, files);
+            }
+        }
+    }
+}
+
+impl Drop for DirNode {
+    fn drop(&mut self) {
+        self.fs.unregister_ino(self.ino);
+    }
+}
+
+impl axfs_vfs::VfsDirNodeOps for DirNode {
+    /// Returns the state counting the open handles of the directory.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Opens this directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::IsADirectory`] if `flags` contains
+    /// [`OpenFlags::WRITE`], as directories are only modified through their
+    /// operations.
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        if flags.is_writable() {
+            return Err(VfsError::IsADirectory);
+        }
+        Ok(None)
+    }
+
+    /// Returns the attributes of this directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns directory attributes with a fixed size of 4096 bytes.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = VfsNodeAttr::new_dir(4096, 0)
+            .with_ino(self.ino)
+            .with_nlink(2 + self.subdir_count());
+        Ok(self.meta.lock().fill_attr(attr))
+    }
+
+    /// Returns the attributes of this directory selected by `mask`.
+    ///
+    /// The link count requires counting the subdirectories, so it is only
+    /// computed if `mask` contains [`AttrMask::NLINK`]. The other fields of
+    /// `stat()` are always returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `mask` - The fields the caller needs
+    ///
+    /// # Returns
+    ///
+    /// Returns the attributes of this directory.
+    fn get_attr_ext(&self, mask: AttrMask) -> VfsResult<VfsNodeAttrExt> {
+        if mask.contains(AttrMask::NLINK) {
+            return VfsNodeOps::get_attr(self).map(VfsNodeAttrExt::from);
+        }
+        let attr = VfsNodeAttr::new_dir(4096, 0).with_ino(self.ino);
+        let attr = self.meta.lock().fill_attr(attr);
+        Ok(VfsNodeAttrExt::new(
+            attr,
+            AttrMask::BASIC_STATS - AttrMask::NLINK,
+        ))
+    }
+
+    /// Returns the generation of the filesystem.
+    fn generation(&self) -> u32 {
+        self.fs.generation()
+    }
+
+    /// Changes the permissions or times of this directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `attr` - The attributes to change
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::IsADirectory`] if the size is changed.
+    /// Returns [`VfsError::OperationNotPermitted`] if the directory is
+    /// immutable.
+    /// Returns [`VfsError::WouldBlock`] if the filesystem is frozen.
+    fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        if attr.get_size().is_some() {
+            return Err(VfsError::IsADirectory);
+        }
+        let mut meta = self.meta.lock();
+        meta.check_changeable()?;
+        meta.apply(attr, self.fs.now());
+        Ok(())
+    }
+
+    /// Returns the protection flags of this directory.
+    fn get_flags(&self) -> VfsResult<VfsNodeFlags> {
+        Ok(self.meta.lock().flags)
+    }
+
+    /// Replaces the protection flags of this directory.
+    ///
+    /// Entries cannot be added to or removed from an immutable directory,
+    /// and only added to an append-only one.
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The new flags of the directory
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or [`VfsError::WouldBlock`] if the
+    /// filesystem is frozen.
+    fn set_flags(&self, flags: VfsNodeFlags) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().set_flags(flags, self.fs.now());
         Ok(())
     }
-}
 
-impl VfsNodeOps for DirNode {
-    /// Returns the attributes of this directory.
+    /// Returns whether a filesystem is mounted on this directory.
+    fn is_mount_point(&self) -> bool {
+        self.mount_point.load(Ordering::Acquire)
+    }
+
+    /// Marks this directory as a mount point, or clears the mark.
+    ///
+    /// A mount point cannot be removed, renamed or replaced, which fails
+    /// with [`VfsError::ResourceBusy`].
+    ///
+    /// # Arguments
+    ///
+    /// * `mounted` - Whether a filesystem is mounted on the directory
     ///
     /// # Returns
     ///
-    /// Returns directory attributes with a fixed size of 4096 bytes.
-    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_dir(4096, 0))
+    /// Returns `Ok(())`.
+    fn set_mount_point(&self, mounted: bool) -> VfsResult {
+        self.mount_point.store(mounted, Ordering::Release);
+        Ok(())
     }
 
     /// Returns the parent directory of this directory.
@@ -162,23 +1147,45 @@ impl VfsNodeOps for DirNode {
     /// # Returns
     ///
     /// Returns a reference to the found node, or an error if not found.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NameTooLong`] if the path or one of its components
+    /// exceeds the [`limits`](axfs_vfs::limits). With the `enforce-perms`
+    /// feature, returns the errors of [`lookup_ctx()`](VfsNodeOps::lookup_ctx)
+    /// called with the context of the current task.
     fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
-        let (name, rest) = split_path(path);
-        let node = match name {
-            "" | "." => Ok(self.clone() as VfsNodeRef),
-            ".." => self.parent().ok_or(VfsError::NotFound),
-            _ => self
-                .children
-                .read()
-                .get(name)
-                .cloned()
-                .ok_or(VfsError::NotFound),
-        }?;
+        match self.fs.caller() {
+            Some(ctx) => VfsNodeOps::lookup_ctx(self, Some(&ctx), path),
+            None => self.lookup_unchecked(path),
+        }
+    }
 
-        if let Some(rest) = rest {
-            node.lookup(rest)
-        } else {
-            Ok(node)
+    /// Looks up a node, checking that the caller of `ctx` may search every
+    /// directory walked.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context of the caller, or `None` to skip the checks
+    /// * `path` - The relative path to lookup
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`lookup()`](VfsNodeOps::lookup), or
+    /// [`VfsError::PermissionDenied`] if a directory may not be searched.
+    fn lookup_ctx(self: Arc<Self>, ctx: Option<&VfsContext>, path: &str) -> VfsResult<VfsNodeRef> {
+        let Some(ctx) = ctx else {
+            return self.lookup_unchecked(path);
+        };
+        let (name, rest) = Path::new(path).split_first();
+        if name.is_empty() {
+            return Ok(self);
+        }
+        ctx.check(&VfsNodeOps::get_attr(&*self)?, Access::EXEC)?;
+        let node = self.lookup_unchecked(name)?;
+        match rest {
+            Some(rest) => node.lookup_ctx(Some(ctx), rest),
+            None => Ok(node),
         }
     }
 
@@ -200,11 +1207,12 @@ impl VfsNodeOps for DirNode {
         let mut children = children.iter().skip(start_idx.max(2) - 2);
         for (i, ent) in dirents.iter_mut().enumerate() {
             match i + start_idx {
-                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir),
-                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir),
+                0 => *ent = VfsDirEntry::new(".", VfsNodeType::Dir).with_ino(self.ino),
+                1 => *ent = VfsDirEntry::new("..", VfsNodeType::Dir).with_ino(self.parent_ino()),
                 _ => {
                     if let Some((name, node)) = children.next() {
-                        *ent = VfsDirEntry::new(name, node.get_attr().unwrap().file_type());
+                        let attr = node.get_attr().unwrap();
+                        *ent = VfsDirEntry::new(name, attr.file_type()).with_ino(attr.ino());
                     } else {
                         return Ok(i);
                     }
@@ -214,6 +1222,80 @@ impl VfsNodeOps for DirNode {
         Ok(dirents.len())
     }
 
+    /// Reads the directory entries passing the filters of `opts`.
+    ///
+    /// Children are kept sorted by name, so the entries are always returned
+    /// in [`DirOrder::Name`](axfs_vfs::DirOrder::Name) order, and only the
+    /// children starting with the requested prefix are visited.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_idx` - The index of the first matching entry to read
+    /// * `dirents` - A mutable slice to store the directory entries
+    /// * `opts` - The filters of the entries
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of entries read on success, or the error of
+    /// [`get_attr()`](VfsNodeOps::get_attr) of a child otherwise.
+    fn read_dir_opts(
+        &self,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+        opts: &ReadDirOptions,
+    ) -> VfsResult<usize> {
+        let prefix = opts.get_prefix();
+        let children = self.children.read();
+        let dots = [
+            (".", VfsNodeType::Dir, self.ino),
+            ("..", VfsNodeType::Dir, self.parent_ino()),
+        ];
+        let children = children
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .take_while(|(name, _)| name.starts_with(prefix))
+            .map(|(name, node)| {
+                let attr = node.get_attr()?;
+                Ok((name.as_str(), attr.file_type(), attr.ino()))
+            });
+        let mut skip = start_idx;
+        let mut count = 0;
+        for entry in dots.into_iter().map(Ok).chain(children) {
+            if count == dirents.len() {
+                break;
+            }
+            let (name, ty, ino) = entry?;
+            if !opts.matches(name.as_bytes(), ty) {
+                continue;
+            }
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            dirents[count] = VfsDirEntry::new(name, ty).with_ino(ino);
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Opens a stream over the directory entries.
+    ///
+    /// The stream remembers the name of the last returned child, and
+    /// children are kept sorted by name, so it resumes at the next name
+    /// whatever was added or removed meanwhile. With the
+    /// [`ReadDirPolicy::Snapshot`] policy of the filesystem, the stream
+    /// returns a copy of the entries taken at its first read instead.
+    fn open_dir(self: Arc<Self>) -> VfsResult<Box<dyn DirStream>> {
+        let policy = self.fs.read_dir_policy();
+        let stream = Box::new(DirNodeStream {
+            dir: self,
+            pos: StreamPos::Start,
+        });
+        Ok(match policy {
+            ReadDirPolicy::Live => stream,
+            ReadDirPolicy::Snapshot => Box::new(SnapshotDirStream::new(stream)),
+        })
+    }
+
     /// Creates a new node with the given path and type.
     ///
     /// This method recursively creates directories if needed.
@@ -226,133 +1308,506 @@ impl VfsNodeOps for DirNode {
     /// # Returns
     ///
     /// Returns `Ok(())` if creation succeeds, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NameTooLong`] if the path or one of its components
+    /// exceeds the [`limits`](axfs_vfs::limits). With the `enforce-perms`
+    /// feature, returns the errors of [`create_ctx()`](VfsNodeOps::create_ctx)
+    /// called with the context of the current task.
     fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        match self.fs.caller() {
+            Some(ctx) => VfsNodeOps::create_ctx(self, Some(&ctx), path, ty),
+            None => self.create_unchecked(path, ty),
+        }
+    }
+
+    /// Creates a new node with the given path and type, failing if it
+    /// already exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path where the node should be created
+    /// * `ty` - The type of node to create
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`create()`](VfsNodeOps::create), or
+    /// [`VfsError::AlreadyExists`] if the path ends with `.` or `..`.
+    fn create_exclusive(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        // named nodes are checked and created under the lock of the parent
+        match Path::new(path).file_name() {
+            Some(_) => VfsNodeOps::create(self, path.trim_end_matches('/'), ty),
+            None => Err(VfsError::AlreadyExists),
+        }
+    }
+
+    /// Creates a node owned by the caller of `ctx`, checking that it may
+    /// write the parent directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context of the caller, or `None` to skip the checks
+    /// * `path` - The path of the new node
+    /// * `ty` - The type of the new node
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`create()`](VfsNodeOps::create), or
+    /// [`VfsError::PermissionDenied`] if a directory may not be searched,
+    /// or the parent directory may not be written.
+    fn create_ctx(&self, ctx: Option<&VfsContext>, path: &str, ty: VfsNodeType) -> VfsResult {
+        let Some(ctx) = ctx else {
+            return self.create_unchecked(path, ty);
+        };
         log::debug!("create {ty:?} at ramfs: {path}");
-        let (name, rest) = split_path(path);
+        axfs_vfs::limits::check_path(path)?;
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let (dir, name) = this.split_parent(Some(ctx), path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Ok(()); // already exists
+        }
+        ctx.check(&VfsNodeOps::get_attr(&*dir)?, Access::WRITE | Access::EXEC)?;
+        let res = dir.create_node_as(name, ty, Some(ctx));
+        trace::record(TraceOp::Create, dir.ino, name, res)
+    }
+
+    /// Creates a device node at the given path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the new node
+    /// * `ty` - The type of the device, character or block
+    /// * `dev` - The device number
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::create_device_node`], or
+    /// [`VfsError::NotFound`] if a parent directory does not exist.
+    fn mknod(&self, path: &str, ty: VfsNodeType, dev: DeviceId) -> VfsResult {
+        log::debug!("mknod at ramfs: {path}");
+        axfs_vfs::limits::check_path(path)?;
+        let (name, rest) = Path::new(path).split_first();
         if let Some(rest) = rest {
             match name {
-                "" | "." => self.create(rest, ty),
-                ".." => self.parent().ok_or(VfsError::NotFound)?.create(rest, ty),
+                "" | "." => VfsNodeOps::mknod(self, rest, ty, dev),
+                ".." => VfsNodeOps::parent(self)
+                    .ok_or(VfsError::NotFound)?
+                    .mknod(rest, ty, dev),
                 _ => {
                     let subdir = self
                         .children
                         .read()
-                        .get(name)
+                        .get(self.key(name).as_ref())
                         .ok_or(VfsError::NotFound)?
                         .clone();
-                    subdir.create(rest, ty)
+                    subdir.mknod(rest, ty, dev)
                 }
             }
         } else if name.is_empty() || name == "." || name == ".." {
-            Ok(()) // already exists
+            Err(VfsError::AlreadyExists)
         } else {
-            self.create_node(name, ty)
+            let res = self.create_device_node(name, ty, dev);
+            trace::record(TraceOp::Mknod, self.ino, name, res)
         }
     }
 
-    /// Removes a node at the given path.
-    ///
-    /// This method recursively removes nodes along the path.
-    /// Directories must be empty to be removed.
+    /// Creates a symbolic link at the given path, pointing to `target`.
     ///
     /// # Arguments
     ///
-    /// * `path` - The path of the node to remove
+    /// * `path` - The path of the new link
+    /// * `target` - The path the link points to, stored as is
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if removal succeeds, or an error otherwise.
-    fn remove(&self, path: &str) -> VfsResult {
-        log::debug!("remove at ramfs: {path}");
-        let (name, rest) = split_path(path);
+    /// Returns `Ok(())` if the link was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::create_symlink_node`], or
+    /// [`VfsError::NotFound`] if a parent directory does not exist.
+    fn create_symlink(&self, path: &str, target: &str) -> VfsResult {
+        log::debug!("create symlink at ramfs: {path} -> {target}");
+        axfs_vfs::limits::check_path(path)?;
+        let (name, rest) = Path::new(path).split_first();
         if let Some(rest) = rest {
             match name {
-                "" | "." => self.remove(rest),
-                ".." => self.parent().ok_or(VfsError::NotFound)?.remove(rest),
+                "" | "." => VfsNodeOps::create_symlink(self, rest, target),
+                ".." => VfsNodeOps::parent(self)
+                    .ok_or(VfsError::NotFound)?
+                    .create_symlink(rest, target),
                 _ => {
                     let subdir = self
                         .children
                         .read()
-                        .get(name)
+                        .get(self.key(name).as_ref())
                         .ok_or(VfsError::NotFound)?
                         .clone();
-                    subdir.remove(rest)
+                    subdir.create_symlink(rest, target)
                 }
             }
         } else if name.is_empty() || name == "." || name == ".." {
-            Err(VfsError::InvalidInput) // remove '.' or '..
+            Err(VfsError::AlreadyExists)
         } else {
-            self.remove_node(name)
+            let res = self.create_symlink_node(name, target);
+            trace::record(TraceOp::Symlink, self.ino, name, res)
+        }
+    }
+
+    /// Creates a hard link at `dst_path` to the file at `src_path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The path of the existing file
+    /// * `dst_path` - The path of the new entry
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the link was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::link_node`], or
+    /// [`VfsError::NotFound`] if `src_path` or the parent directory of
+    /// `dst_path` does not exist.
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        log::debug!("link at ramfs: {dst_path} -> {src_path}");
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let node = VfsNodeOps::lookup(this.clone(), src_path)?;
+        let (parent, name) = match dst_path.trim_end_matches('/').rsplit_once('/') {
+            Some((parent, name)) => (VfsNodeOps::lookup(this, parent)?, name),
+            None => (this as VfsNodeRef, dst_path),
+        };
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        match parent.as_any().downcast_ref::<DirNode>() {
+            Some(dir) => trace::record(TraceOp::Link, dir.ino, name, dir.link_node(name, node)),
+            None => Err(VfsError::NotADirectory),
+        }
+    }
+
+    /// Creates a file without directory entry, like `O_TMPFILE`.
+    ///
+    /// The file is initialized as by [`create()`](VfsNodeOps::create) in
+    /// this directory, and is freed when its last open handle is closed
+    /// unless it is linked with [`link_into()`](VfsNodeOps::link_into).
+    ///
+    /// # Returns
+    ///
+    /// Returns the new file on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::OperationNotPermitted`] if the directory is
+    /// immutable, [`VfsError::WouldBlock`] if the filesystem is frozen, or,
+    /// with the `enforce-perms` feature, [`VfsError::PermissionDenied`] if
+    /// the current task may not write the directory.
+    fn create_tmpfile(&self) -> VfsResult<VfsNodeRef> {
+        let ctx = self.fs.caller();
+        if let Some(ctx) = &ctx {
+            ctx.check(&VfsNodeOps::get_attr(self)?, Access::WRITE | Access::EXEC)?;
+        }
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().check_changeable()?;
+        let node: VfsNodeRef = Arc::new(FileNode::new_tmpfile(self.fs.clone()));
+        let res = init_attrs(
+            &node,
+            VfsNodeType::File,
+            ctx.as_ref(),
+            self.fs.umask(),
+            self.default_attrs(),
+        );
+        trace::record(TraceOp::Create, self.ino, "", res.map(|_| node))
+    }
+
+    /// Renames or moves the node at `src_path` to `dst_path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The path of the node to move
+    /// * `dst_path` - The new path of the node
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was moved, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::rename_node`], or
+    /// [`VfsError::NotFound`] if a parent directory does not exist.
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        log::debug!("rename at ramfs: {src_path} -> {dst_path}");
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let (src_dir, src_name) = this.clone().split_parent(None, src_path)?;
+        let (dst_dir, dst_name) = this.split_parent(None, dst_path)?;
+        let res = src_dir.rename_node(src_name, &dst_dir, dst_name);
+        trace::record(TraceOp::Rename, src_dir.ino, src_name, res)
+    }
+
+    /// Renames or moves the node at `src_path` to `dst_path`, with the
+    /// options of `flags`.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The path of the node to move
+    /// * `dst_path` - The new path of the node
+    /// * `flags` - The options of the rename
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::rename_node_flags`], or
+    /// [`VfsError::NotFound`] if a parent directory does not exist.
+    fn rename_flags(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult {
+        log::debug!("rename at ramfs: {src_path} -> {dst_path} ({flags:?})");
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let (src_dir, src_name) = this.clone().split_parent(None, src_path)?;
+        let (dst_dir, dst_name) = this.split_parent(None, dst_path)?;
+        let res = src_dir.rename_node_flags(src_name, &dst_dir, dst_name, flags);
+        trace::record(TraceOp::Rename, src_dir.ino, src_name, res)
+    }
+
+    /// Removes a node at the given path.
+    ///
+    /// This method recursively removes nodes along the path.
+    /// Directories must be empty to be removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the node to remove
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if removal succeeds, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// With the `enforce-perms` feature, returns the errors of
+    /// [`remove_ctx()`](VfsNodeOps::remove_ctx) called with the context of
+    /// the current task.
+    fn remove(&self, path: &str) -> VfsResult {
+        match self.fs.caller() {
+            Some(ctx) => VfsNodeOps::remove_ctx(self, Some(&ctx), path),
+            None => self.remove_unchecked(path),
         }
     }
 
-    axfs_vfs::impl_vfs_dir_default! {}
+    /// Removes a node, checking that the caller of `ctx` may write the
+    /// parent directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context of the caller, or `None` to skip the checks
+    /// * `path` - The path of the node to remove
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`remove()`](VfsNodeOps::remove), or
+    /// [`VfsError::PermissionDenied`] if a directory may not be searched,
+    /// or the parent directory may not be written.
+    fn remove_ctx(&self, ctx: Option<&VfsContext>, path: &str) -> VfsResult {
+        let Some(ctx) = ctx else {
+            return self.remove_unchecked(path);
+        };
+        log::debug!("remove at ramfs: {path}");
+        let this = self.this.upgrade().ok_or(VfsError::NotFound)?;
+        let (dir, name) = this.split_parent(Some(ctx), path)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::InvalidInput); // remove '.' or '..
+        }
+        ctx.check(&VfsNodeOps::get_attr(&*dir)?, Access::WRITE | Access::EXEC)?;
+        trace::record(TraceOp::Remove, dir.ino, name, dir.remove_node(name))
+    }
 }
 
-/// Splits a path into the first component and the rest.
+axfs_vfs::impl_vfs_dir_node!(DirNode);
+
+/// Locks the entries of `node` if it is a directory, which must be empty
+/// to be removed.
 ///
-/// This helper function is used for path resolution.
+/// # Returns
 ///
-/// # Arguments
+/// The guard keeping the directory empty until it is removed, or `None` if
+/// the node is not a directory.
 ///
-/// * `path` - The path to split
+/// # Errors
 ///
-/// # Returns
+/// Returns [`VfsError::DirectoryNotEmpty`] if the node is a non-empty
+/// directory.
+fn lock_empty(
+    node: &dyn VfsNodeOps,
+) -> VfsResult<Option<RwLockWriteGuard<'_, BTreeMap<String, VfsNodeRef>>>> {
+    match node.as_any().downcast_ref::<DirNode>() {
+        Some(dir) => {
+            let entries = dir.children.write();
+            if !entries.is_empty() {
+                return Err(VfsError::DirectoryNotEmpty);
+            }
+            Ok(Some(entries))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Sets the initial owner and permissions of the new node `node`.
 ///
-/// A tuple containing the first path component and an optional remainder.
-fn split_path(path: &str) -> (&str, Option<&str>) {
-    let trimmed_path = path.trim_start_matches('/');
-    trimmed_path.find('/').map_or((trimmed_path, None), |n| {
-        (&trimmed_path[..n], Some(&trimmed_path[n + 1..]))
-    })
+/// The node is owned by the caller of `ctx`, with its umask applied to the
+/// built-in permissions. Without a context, `umask` is applied, if any. The
+/// default attributes of the parent directory, if set, take precedence.
+fn init_attrs(
+    node: &VfsNodeRef,
+    ty: VfsNodeType,
+    ctx: Option<&VfsContext>,
+    umask: Option<Umask>,
+    defaults: Option<DefaultAttrs>,
+) -> VfsResult {
+    if let Some(ctx) = ctx {
+        let perm = ctx.apply_umask(node.get_attr()?.perm());
+        let cred = ctx.cred();
+        node.set_attr(&SetAttr::new().mode(perm).uid(cred.uid).gid(cred.gid))?;
+    } else if let Some(umask) = umask {
+        let perm = node.get_attr()?.perm().apply_umask(umask);
+        node.set_attr(&SetAttr::new().mode(perm))?;
+    }
+    if let Some(defaults) = defaults {
+        node.set_attr(&defaults.to_set_attr(ty))?;
+    }
+    Ok(())
+}
+
+/// The position of a [`DirNodeStream`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StreamPos {
+    /// Before `.`.
+    Start,
+    /// After `.`.
+    Dot,
+    /// After `..`.
+    DotDot,
+    /// After the child with this name.
+    After(String),
+}
+
+impl StreamPos {
+    /// Encodes the position as a cookie.
+    fn to_cookie(&self) -> DirCookie {
+        match self {
+            Self::Start => DirCookie::start(),
+            Self::Dot => DirCookie::from_bytes([0]),
+            Self::DotDot => DirCookie::from_bytes([1]),
+            Self::After(name) => DirCookie::from_bytes([&[2], name.as_bytes()].concat()),
+        }
+    }
+
+    /// Decodes a position encoded by [`to_cookie()`](Self::to_cookie).
+    fn from_cookie(cookie: &DirCookie) -> VfsResult<Self> {
+        match cookie.as_bytes() {
+            [] => Ok(Self::Start),
+            [0] => Ok(Self::Dot),
+            [1] => Ok(Self::DotDot),
+            [2, name @ ..] => core::str::from_utf8(name)
+                .map(|name| Self::After(name.into()))
+                .map_err(|_| VfsError::InvalidInput),
+            _ => Err(VfsError::InvalidInput),
+        }
+    }
+}
+
+/// A stream over the entries of a [`DirNode`], keyed by name.
+struct DirNodeStream {
+    dir: Arc<DirNode>,
+    pos: StreamPos,
+}
+
+impl DirStream for DirNodeStream {
+    fn next_entry(&mut self) -> VfsResult<Option<VfsDirEntry>> {
+        let (pos, entry) = match &self.pos {
+            StreamPos::Start => (
+                StreamPos::Dot,
+                VfsDirEntry::new(".", VfsNodeType::Dir).with_ino(self.dir.ino),
+            ),
+            StreamPos::Dot => (
+                StreamPos::DotDot,
+                VfsDirEntry::new("..", VfsNodeType::Dir).with_ino(self.dir.parent_ino()),
+            ),
+            StreamPos::DotDot | StreamPos::After(_) => {
+                let children = self.dir.children.read();
+                let next = match &self.pos {
+                    StreamPos::After(last) => children
+                        .range::<str, _>((Bound::Excluded(last.as_str()), Bound::Unbounded))
+                        .next(),
+                    _ => children.iter().next(),
+                };
+                let Some((name, node)) = next else {
+                    return Ok(None);
+                };
+                let attr = node.get_attr()?;
+                (
+                    StreamPos::After(name.clone()),
+                    VfsDirEntry::new(name, attr.file_type()).with_ino(attr.ino()),
+                )
+            }
+        };
+        self.pos = pos;
+        Ok(Some(entry))
+    }
+
+    fn cookie(&self) -> DirCookie {
+        self.pos.to_cookie()
+    }
+
+    fn seek(&mut self, cookie: &DirCookie) -> VfsResult {
+        self.pos = StreamPos::from_cookie(cookie)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    #[test]
-    fn test_split_path() {
-        assert_eq!(split_path("foo/bar"), ("foo", Some("bar")));
-        assert_eq!(split_path("foo"), ("foo", None));
-        assert_eq!(split_path("/foo/bar"), ("foo", Some("bar")));
-        assert_eq!(split_path("///foo/bar"), ("foo", Some("bar")));
-        assert_eq!(split_path(""), ("", None));
-        assert_eq!(split_path("/"), ("", None));
-        assert_eq!(split_path("///"), ("", None));
-    }
+    use axfs_vfs::clock::ManualClock;
+    use core::time::Duration;
 
     #[test]
     fn test_dir_node_new() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.get_entries().is_empty());
         assert!(!dir.exist("test"));
     }
 
     #[test]
     fn test_dir_node_exist() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(!dir.exist("test"));
         assert!(!dir.exist("foo"));
     }
 
     #[test]
     fn test_dir_node_create_file() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("test.txt", VfsNodeType::File).is_ok());
         assert!(dir.exist("test.txt"));
     }
 
     #[test]
     fn test_dir_node_create_dir() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("testdir", VfsNodeType::Dir).is_ok());
         assert!(dir.exist("testdir"));
     }
 
     #[test]
     fn test_dir_node_create_duplicate() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("test", VfsNodeType::File).is_ok());
         assert_eq!(
             dir.create_node("test", VfsNodeType::File).err(),
@@ -362,7 +1817,7 @@ mod tests {
 
     #[test]
     fn test_dir_node_create_unsupported() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert_eq!(
             dir.create_node("test", VfsNodeType::SymLink).err(),
             Some(VfsError::Unsupported)
@@ -371,23 +1826,64 @@ mod tests {
 
     #[test]
     fn test_dir_node_remove_file() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("test.txt", VfsNodeType::File).is_ok());
         assert!(dir.remove_node("test.txt").is_ok());
         assert!(!dir.exist("test.txt"));
     }
 
+    #[test]
+    fn test_dir_node_rename() {
+        let dir = DirNode::new(None, Default::default());
+        dir.create_node("a", VfsNodeType::Dir).unwrap();
+        dir.create("a/b", VfsNodeType::Dir).unwrap();
+        dir.create("f", VfsNodeType::File).unwrap();
+        dir.create("g", VfsNodeType::File).unwrap();
+        let f = dir.clone().lookup("f").unwrap();
+
+        // replace a file in the same directory
+        assert!(dir.rename("f", "g").is_ok());
+        assert!(!dir.exist("f"));
+        assert!(Arc::ptr_eq(&dir.clone().lookup("g").unwrap(), &f));
+        // move into a subdirectory
+        assert!(dir.rename("g", "a/b/h").is_ok());
+        assert!(Arc::ptr_eq(&dir.clone().lookup("a/b/h").unwrap(), &f));
+        assert_eq!(dir.rename("a/b/h", "a"), Err(VfsError::IsADirectory));
+        dir.create("d", VfsNodeType::Dir).unwrap();
+        assert_eq!(dir.rename("d", "a/b/h"), Err(VfsError::NotADirectory));
+        assert_eq!(dir.rename("a", "a/b/c"), Err(VfsError::InvalidInput));
+        assert_eq!(dir.rename("a/b", "a"), Err(VfsError::DirectoryNotEmpty));
+        assert_eq!(dir.rename("missing", "x"), Err(VfsError::NotFound));
+        assert_eq!(dir.rename("a/b/h", ".."), Err(VfsError::InvalidInput));
+
+        // a moved directory gets a new parent
+        assert!(dir.rename("a/b", "b").is_ok());
+        let b = dir.clone().lookup("b").unwrap();
+        let parent = b.parent().unwrap();
+        assert!(core::ptr::addr_eq(Arc::as_ptr(&parent), Arc::as_ptr(&dir)));
+        assert!(Arc::ptr_eq(&b.lookup("../b/h").unwrap(), &f));
+    }
+
     #[test]
     fn test_dir_node_remove_empty_dir() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("testdir", VfsNodeType::Dir).is_ok());
+        let testdir = dir.clone().lookup("testdir").unwrap();
         assert!(dir.remove_node("testdir").is_ok());
         assert!(!dir.exist("testdir"));
+        // nothing can be added to the removed directory anymore
+        assert_eq!(
+            testdir.create("f", VfsNodeType::File),
+            Err(VfsError::NotFound)
+        );
+        dir.create_node("f", VfsNodeType::File).unwrap();
+        let testdir = testdir.downcast::<DirNode>().ok().unwrap();
+        assert_eq!(dir.rename_node("f", &testdir, "f"), Err(VfsError::NotFound));
     }
 
     #[test]
     fn test_dir_node_remove_not_empty_dir() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("testdir", VfsNodeType::Dir).is_ok());
         let subdir = dir.clone().lookup("testdir").unwrap();
         assert!(subdir.create("nested.txt", VfsNodeType::File).is_ok());
@@ -399,7 +1895,7 @@ mod tests {
 
     #[test]
     fn test_dir_node_remove_not_found() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert_eq!(
             dir.remove_node("nonexistent").err(),
             Some(VfsError::NotFound)
@@ -408,7 +1904,7 @@ mod tests {
 
     #[test]
     fn test_dir_node_get_entries() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("f1", VfsNodeType::File).is_ok());
         assert!(dir.create_node("f2", VfsNodeType::File).is_ok());
         assert!(dir.create_node("d1", VfsNodeType::Dir).is_ok());
@@ -420,16 +1916,71 @@ mod tests {
         assert!(entries.contains(&"d1".to_string()));
     }
 
+    #[test]
+    fn test_dir_node_stream() {
+        let dir = DirNode::new(None, Default::default());
+        for name in ["a", "b", "c", "d"] {
+            dir.create(name, VfsNodeType::File).unwrap();
+        }
+        let mut stream = dir.clone().open_dir().unwrap();
+        let mut next = || {
+            stream
+                .next_entry()
+                .unwrap()
+                .map(|e| String::from_utf8(e.name_as_bytes().to_vec()).unwrap())
+        };
+        assert_eq!(next().as_deref(), Some("."));
+        assert_eq!(next().as_deref(), Some(".."));
+        assert_eq!(next().as_deref(), Some("a"));
+        assert_eq!(next().as_deref(), Some("b"));
+
+        // entries before the position do not shift the stream
+        dir.remove("a").unwrap();
+        dir.create("aa", VfsNodeType::File).unwrap();
+        // removing the last returned entry is fine too
+        dir.remove("b").unwrap();
+        dir.create("e", VfsNodeType::Dir).unwrap();
+        assert_eq!(next().as_deref(), Some("c"));
+        assert_eq!(next().as_deref(), Some("d"));
+        assert_eq!(next().as_deref(), Some("e"));
+        assert!(next().is_none());
+    }
+
+    #[test]
+    fn test_dir_node_stream_seek() {
+        let dir = DirNode::new(None, Default::default());
+        dir.create("x", VfsNodeType::File).unwrap();
+        dir.create("y", VfsNodeType::Dir).unwrap();
+        let mut stream = dir.clone().open_dir().unwrap();
+        let mut cookies = alloc::vec![stream.cookie()];
+        while stream.next_entry().unwrap().is_some() {
+            cookies.push(stream.cookie());
+        }
+        assert_eq!(cookies.len(), 5);
+
+        let mut other = dir.clone().open_dir().unwrap();
+        other.seek(&cookies[3]).unwrap();
+        let entry = other.next_entry().unwrap().unwrap();
+        assert_eq!(entry.name_as_bytes(), b"y");
+        assert_eq!(entry.entry_type(), VfsNodeType::Dir);
+        other.seek(&cookies[1]).unwrap();
+        assert_eq!(other.next_entry().unwrap().unwrap().name_as_bytes(), b"..");
+        assert_eq!(
+            other.seek(&DirCookie::from_bytes([3])),
+            Err(VfsError::InvalidInput)
+        );
+    }
+
     #[test]
     fn test_dir_node_lookup_current() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         let current = dir.clone().lookup(".").unwrap();
         assert!(current.get_attr().unwrap().is_dir());
     }
 
     #[test]
     fn test_dir_node_lookup_file() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("test.txt", VfsNodeType::File).is_ok());
         let file = dir.lookup("test.txt").unwrap();
         assert!(file.get_attr().unwrap().is_file());
@@ -437,27 +1988,94 @@ mod tests {
 
     #[test]
     fn test_dir_node_lookup_not_found() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert_eq!(dir.lookup("nonexistent").err(), Some(VfsError::NotFound));
     }
 
     #[test]
     fn test_dir_node_parent_none() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.parent().is_none());
     }
 
     #[test]
     fn test_dir_node_get_attr() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         let attr = dir.get_attr().unwrap();
         assert!(attr.is_dir());
         assert_eq!(attr.size(), 4096);
     }
 
+    #[test]
+    fn test_dir_node_times() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(5)));
+        let fs = Arc::new(FsState::default());
+        fs.set_clock(Some(clock.clone()));
+        let dir = DirNode::new(None, fs);
+        assert_eq!(dir.get_attr().unwrap().ctime(), Duration::from_secs(5));
+
+        clock.set(Duration::from_secs(6));
+        dir.create_node("f", VfsNodeType::File).unwrap();
+        assert_eq!(dir.get_attr().unwrap().mtime(), Duration::from_secs(6));
+        let file = dir.clone().lookup("f").unwrap();
+        assert_eq!(file.get_attr().unwrap().mtime(), Duration::from_secs(6));
+
+        clock.set(Duration::from_secs(7));
+        dir.remove_node("f").unwrap();
+        let attr = dir.get_attr().unwrap();
+        assert_eq!(attr.mtime(), Duration::from_secs(7));
+        assert_eq!(attr.atime(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_dir_node_set_attr() {
+        let dir = DirNode::new(None, Arc::new(FsState::default()));
+        assert_eq!(dir.get_attr().unwrap().perm().mode(), 0o755);
+        let mode = VfsNodePerm::from_bits_truncate(0o700);
+        dir.set_attr(&SetAttr::new().mode(mode)).unwrap();
+        assert_eq!(dir.get_attr().unwrap().perm().mode(), 0o700);
+        assert_eq!(
+            dir.set_attr(&SetAttr::new().mode(mode).size(0)),
+            Err(VfsError::IsADirectory)
+        );
+    }
+
+    #[test]
+    fn test_dir_node_read_dir_opts() {
+        use axfs_vfs::DirOrder;
+
+        let dir = DirNode::new(None, Default::default());
+        for name in ["log.2", "log.1", "app.conf", "log.d"] {
+            let ty = if name == "log.d" {
+                VfsNodeType::Dir
+            } else {
+                VfsNodeType::File
+            };
+            dir.create_node(name, ty).unwrap();
+        }
+        let read = |start_idx, opts: &ReadDirOptions| {
+            let mut entries: Vec<VfsDirEntry> = (0..3).map(|_| VfsDirEntry::default()).collect();
+            let n = dir.read_dir_opts(start_idx, &mut entries, opts).unwrap();
+            entries[..n]
+                .iter()
+                .map(|e| String::from_utf8(e.name_as_bytes().to_vec()).unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let opts = ReadDirOptions::new().prefix("log.");
+        assert_eq!(read(0, &opts), ["log.1", "log.2", "log.d"]);
+        let opts = opts.file_type(VfsNodeType::File);
+        assert_eq!(read(1, &opts), ["log.2"]);
+        let opts = ReadDirOptions::new().order(DirOrder::Name);
+        assert_eq!(read(0, &opts), [".", "..", "app.conf"]);
+        assert_eq!(read(3, &opts), ["log.1", "log.2", "log.d"]);
+        let opts = ReadDirOptions::new().file_type(VfsNodeType::Dir);
+        assert_eq!(read(0, &opts), [".", "..", "log.d"]);
+    }
+
     #[test]
     fn test_dir_node_create_with_path() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         // Create intermediate directory first
         assert!(dir.create("subdir", VfsNodeType::Dir).is_ok());
         assert!(dir.create("subdir/nested", VfsNodeType::Dir).is_ok());
@@ -469,7 +2087,7 @@ mod tests {
 
     #[test]
     fn test_dir_node_read_dir() {
-        let dir = DirNode::new(None);
+        let dir = DirNode::new(None, Default::default());
         assert!(dir.create_node("f1", VfsNodeType::File).is_ok());
         assert!(dir.create_node("f2", VfsNodeType::Dir).is_ok());
 
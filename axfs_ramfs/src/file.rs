@@ -1,6 +1,28 @@
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
 use alloc::vec::Vec;
-use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeAttr, VfsNodeOps, VfsResult};
-use spin::RwLock;
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::notify::WatchMask;
+use axfs_vfs::page::PageFrame;
+use axfs_vfs::trace::{self, TraceOp};
+use axfs_vfs::{
+    FallocateMode, IoSlice, IoSliceMut, OpenFlags, SeekHint, SetAttr, VfsError, VfsFileRef,
+    VfsNodeAttr, VfsNodeFlags, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeRefExt, VfsPage,
+    VfsResult,
+};
+use spin::{Mutex, RwLock};
+
+use crate::data::{FileData, MAX_FILE_SIZE, PAGE_SIZE};
+use crate::dir::DirNode;
+use crate::fsck::{FsckIssue, FsckReport};
+use crate::meta::NodeMeta;
+use crate::persist::{add_dirty_range, clip_dirty_ranges};
+use crate::state::FsState;
+use crate::swap::SwapArea;
 
 /// The file node in RAM filesystem.
 ///
@@ -9,32 +31,516 @@ use spin::RwLock;
 ///
 /// # Fields
 ///
-/// - `content` - The file content stored as a byte vector
+/// - `fs` - The state shared with the other nodes of the filesystem
+/// - `ino` - The inode number of the file
+/// - `data` - The file content stored as a sparse map of pages
+/// - `dirty` - The ranges modified since the last synchronization, or `None`
+///   if the file is clean
+/// - `meta` - The timestamps of the file
+/// - `nlink` - The number of directory entries referring to the file
+/// - `entries` - The directory entries referring to the file, as
+///   `(directory, name)` pairs. The first one gives the path of the file
+///   for the persistence backend.
+/// - `unlinked` - Whether the file has been removed from all its directories
+/// - `linkable` - Whether the file is a temporary file that was never
+///   linked, which may be linked while it has no directory entry
+/// - `opens` - The open handles of the file
 pub struct FileNode {
-    content: RwLock<Vec<u8>>,
+    fs: Arc<FsState>,
+    ino: u64,
+    data: RwLock<FileData>,
+    dirty: Mutex<Option<Vec<Range<u64>>>>,
+    meta: Mutex<NodeMeta>,
+    nlink: AtomicU64,
+    entries: Mutex<Vec<(Weak<DirNode>, String)>>,
+    unlinked: AtomicBool,
+    linkable: AtomicBool,
+    opens: OpenState,
 }
 
 impl FileNode {
     /// Creates a new empty file node.
     ///
+    /// # Arguments
+    ///
+    /// * `fs` - The state of the filesystem the file belongs to
+    ///
     /// # Returns
     ///
     /// A new file node with empty content.
-    pub(super) const fn new() -> Self {
+    pub(super) fn new(fs: Arc<FsState>) -> Self {
+        let meta = NodeMeta::new(fs.now(), VfsNodePerm::default_file());
         Self {
-            content: RwLock::new(Vec::new()),
+            ino: fs.alloc_ino(),
+            fs,
+            data: RwLock::new(FileData::default()),
+            dirty: Mutex::new(None),
+            meta: Mutex::new(meta),
+            nlink: AtomicU64::new(1),
+            entries: Mutex::new(Vec::new()),
+            unlinked: AtomicBool::new(false),
+            linkable: AtomicBool::new(false),
+            opens: OpenState::new(),
+        }
+    }
+
+    /// Creates a new empty file node without directory entry, like
+    /// `O_TMPFILE`.
+    ///
+    /// The file cannot be looked up by inode number until it is linked, and
+    /// its content is freed when its last open handle is closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `fs` - The state of the filesystem the file belongs to
+    pub(super) fn new_tmpfile(fs: Arc<FsState>) -> Self {
+        let file = Self::new(fs);
+        file.nlink.store(0, Ordering::Release);
+        file.unlinked.store(true, Ordering::Release);
+        file.linkable.store(true, Ordering::Release);
+        file
+    }
+
+    /// Returns the inode number of the file.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the number of pages of the file held in memory.
+    pub(crate) fn resident_pages(&self) -> usize {
+        self.data.read().resident_pages()
+    }
+
+    /// Returns whether the file belongs to the filesystem with state `fs`.
+    pub(crate) fn belongs_to(&self, fs: &Arc<FsState>) -> bool {
+        Arc::ptr_eq(&self.fs, fs)
+    }
+
+    /// Records a new directory entry referring to the file.
+    ///
+    /// A temporary file linked for the first time can be looked up by inode
+    /// number from then on, as `node`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if the file has been removed from all
+    /// its directories and is not a temporary file that was never linked.
+    pub(crate) fn link(&self, node: &VfsNodeRef) -> VfsResult {
+        if self.unlinked.load(Ordering::Acquire) {
+            if !self.linkable.swap(false, Ordering::AcqRel) {
+                return Err(VfsError::NotFound);
+            }
+            self.unlinked.store(false, Ordering::Release);
+            self.fs.register_ino(self.ino, node);
+        }
+        self.nlink.fetch_add(1, Ordering::AcqRel);
+        self.meta.lock().touch_change(self.fs.now());
+        Ok(())
+    }
+
+    /// Records the removal of a directory entry referring to the file.
+    ///
+    /// Once the file is removed from all its directories, it cannot be
+    /// looked up by inode number anymore, and its storage is freed when its
+    /// last open handle is closed.
+    pub(crate) fn unlink(&self) {
+        if self.nlink.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.unlinked.store(true, Ordering::Release);
+            self.fs.unregister_ino(self.ino);
+        } else {
+            self.meta.lock().touch_change(self.fs.now());
+        }
+    }
+
+    /// Records that the entry `name` of `dir` refers to the file.
+    pub(crate) fn add_entry(&self, dir: &DirNode, name: &str) {
+        self.entries.lock().push((dir.weak(), name.into()));
+    }
+
+    /// Forgets the entry `name` of `dir`.
+    ///
+    /// If it gave the path of the file, the whole content is marked dirty,
+    /// to be pushed to the persistence backend under the next path.
+    pub(crate) fn remove_entry(&self, dir: &DirNode, name: &str) {
+        let mut entries = self.entries.lock();
+        let Some(idx) = entries
+            .iter()
+            .position(|e| core::ptr::eq(e.0.as_ptr(), dir) && e.1 == name)
+        else {
+            return;
+        };
+        entries.remove(idx);
+        if idx == 0 && !entries.is_empty() {
+            drop(entries);
+            let size = self.data.read().size();
+            add_dirty_range(self.dirty.lock().get_or_insert_with(Vec::new), 0..size);
+        }
+    }
+
+    /// Records the move of the entry `name` of `dir` to `new_name` in
+    /// `new_dir`.
+    pub(crate) fn move_entry(&self, dir: &DirNode, name: &str, new_dir: &DirNode, new_name: &str) {
+        let mut entries = self.entries.lock();
+        if let Some(entry) = entries
+            .iter_mut()
+            .find(|e| core::ptr::eq(e.0.as_ptr(), dir) && e.1 == name)
+        {
+            *entry = (new_dir.weak(), new_name.into());
+        }
+    }
+
+    /// Returns the absolute path of the file, from its first directory
+    /// entry.
+    ///
+    /// # Returns
+    ///
+    /// The path, or `None` if the file is not reachable from the root
+    /// directory.
+    pub(crate) fn path(&self) -> Option<String> {
+        let (dir, name) = self.entries.lock().first().cloned()?;
+        let mut path = dir.upgrade()?.path()?;
+        path.push('/');
+        path.push_str(&name);
+        Some(path)
+    }
+
+    /// Records a change of the content: marks the filesystem dirty, updates
+    /// the modification time and notifies the watches of the file.
+    fn content_changed(&self) {
+        self.fs.mark_dirty();
+        self.meta.lock().touch_modify(self.fs.now());
+        self.fs.notify(self.ino, WatchMask::MODIFY, None, false);
+    }
+
+    /// Truncates the content, for [`VfsNodeOps::truncate`].
+    fn truncate_data(&self, size: u64) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().check_rewritable()?;
+        self.data.write().truncate(size, &self.fs)?;
+        let mut dirty = self.dirty.lock();
+        clip_dirty_ranges(dirty.get_or_insert_with(Vec::new), size);
+        drop(dirty);
+        self.content_changed();
+        Ok(())
+    }
+
+    /// Reads the content, for [`VfsNodeOps::read_at`].
+    fn read_data(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let data = self.data.read();
+        let read = if data.is_resident(offset, buf.len()) {
+            data.read(offset, buf, &self.fs)?
+        } else {
+            drop(data);
+            let mut data = self.data.write();
+            data.fault_in(offset, buf.len(), &self.fs)?;
+            data.read(offset, buf, &self.fs)?
+        };
+        if self.fs.updates_atime() {
+            self.meta
+                .lock()
+                .touch_access(self.fs.now(), self.fs.atime_policy());
+        }
+        Ok(read)
+    }
+
+    /// Returns a read-only copy of the page at `offset`, for
+    /// [`VfsNodeOps::get_page`] while the filesystem cannot be modified.
+    ///
+    /// Swapped pages are read without being faulted in, and holes are not
+    /// allocated.
+    fn page_copy(&self, offset: u64) -> VfsResult<VfsPage> {
+        let data = self.data.read();
+        if !offset.is_multiple_of(PAGE_SIZE as u64) || offset >= data.size() {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut frame = PageFrame::new();
+        data.read(offset, frame.get_mut(), &self.fs)?;
+        Ok(VfsPage::ReadOnlyFrame(Arc::new(frame)))
+    }
+
+    /// Writes the content, for [`VfsNodeOps::write_at`].
+    fn write_data(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let _mutation = self.fs.check_mutable()?;
+        let mut data = self.data.write();
+        self.meta.lock().check_write(offset, data.size())?;
+        let written = data.write(offset, buf, &self.fs)?;
+        drop(data);
+        let range = offset..offset + written as u64;
+        add_dirty_range(self.dirty.lock().get_or_insert_with(Vec::new), range);
+        self.content_changed();
+        Ok(written)
+    }
+
+    /// Copies the content into another file of RAM filesystem, for
+    /// [`VfsNodeOps::copy_file_range`].
+    ///
+    /// The contents are locked in the order of the addresses of the nodes,
+    /// so that concurrent copies in opposite directions do not deadlock.
+    fn copy_data(
+        &self,
+        src_off: u64,
+        dst: &FileNode,
+        dst_off: u64,
+        len: usize,
+    ) -> VfsResult<usize> {
+        let _mutation = dst.fs.check_mutable()?;
+        let (src_data, mut dst_data) = if (self as *const Self) < (dst as *const Self) {
+            let src_data = self.data.read();
+            (src_data, dst.data.write())
+        } else {
+            let dst_data = dst.data.write();
+            (self.data.read(), dst_data)
+        };
+        dst.meta.lock().check_write(dst_off, dst_data.size())?;
+        let copied = dst_data.copy_from(dst_off, &src_data, src_off, len, &dst.fs, &self.fs)?;
+        drop(dst_data);
+        drop(src_data);
+        if copied > 0 {
+            let range = dst_off..dst_off + copied as u64;
+            add_dirty_range(dst.dirty.lock().get_or_insert_with(Vec::new), range);
+            dst.content_changed();
+        }
+        if self.fs.updates_atime() {
+            self.meta
+                .lock()
+                .touch_access(self.fs.now(), self.fs.atime_policy());
+        }
+        Ok(copied)
+    }
+
+    /// Reads the content, for [`VfsNodeOps::read_vectored_at`].
+    fn read_vectored_data(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        let len = IoSliceMut::total_len(bufs);
+        let data = self.data.read();
+        let data = if data.is_resident(offset, len) {
+            data
+        } else {
+            drop(data);
+            let mut data = self.data.write();
+            data.fault_in(offset, len, &self.fs)?;
+            data.downgrade()
+        };
+        let mut read = 0;
+        for buf in bufs {
+            let n = data.read(offset + read as u64, buf, &self.fs)?;
+            read += n;
+            if n < buf.len() {
+                break;
+            }
+        }
+        drop(data);
+        if self.fs.updates_atime() {
+            self.meta
+                .lock()
+                .touch_access(self.fs.now(), self.fs.atime_policy());
+        }
+        Ok(read)
+    }
+
+    /// Writes the content, for [`VfsNodeOps::write_vectored_at`].
+    fn write_vectored_data(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        let _mutation = self.fs.check_mutable()?;
+        let mut data = self.data.write();
+        self.meta.lock().check_write(offset, data.size())?;
+        let mut written = 0;
+        for buf in bufs {
+            match data.write(offset + written as u64, buf, &self.fs) {
+                Ok(n) if n == buf.len() => written += n,
+                // the filesystem is full
+                Ok(n) => {
+                    written += n;
+                    break;
+                }
+                Err(VfsError::StorageFull) if written > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        drop(data);
+        let range = offset..offset + written as u64;
+        add_dirty_range(self.dirty.lock().get_or_insert_with(Vec::new), range);
+        self.content_changed();
+        Ok(written)
+    }
+
+    /// Pushes the dirty ranges of this file to the persistence backend.
+    ///
+    /// Does nothing if the file is clean or no backend is attached. The
+    /// ranges stay dirty if the backend fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path of this file in the filesystem
+    pub(crate) fn sync_to(&self, path: &str) -> VfsResult {
+        let Some(backend) = self.fs.backend() else {
+            return Ok(());
+        };
+        let data = self.data.read();
+        let mut dirty = self.dirty.lock();
+        if let Some(ranges) = dirty.as_ref() {
+            let mut chunks = Vec::with_capacity(ranges.len());
+            for r in ranges {
+                let mut buf = vec![0; (r.end - r.start) as usize];
+                data.read(r.start, &mut buf, &self.fs)?;
+                chunks.push((r.start, buf));
+            }
+            let chunks: Vec<_> = chunks.iter().map(|(off, buf)| (*off, &buf[..])).collect();
+            backend.persist(path, data.size(), &chunks)?;
+            *dirty = None;
+        }
+        Ok(())
+    }
+
+    /// Moves the pages of this file not accessed since the previous pass to
+    /// `swap`.
+    ///
+    /// # Arguments
+    ///
+    /// * `swap` - The swap area of the filesystem
+    /// * `budget` - The maximum number of pages to evict, decreased by the
+    ///   number of evicted pages
+    pub(crate) fn evict(&self, swap: &SwapArea, budget: &mut usize) -> VfsResult {
+        self.data.write().evict(swap, budget, &self.fs)
+    }
+
+    /// Checks the consistency of this file, see
+    /// [`RamFileSystem::fsck()`](crate::RamFileSystem::fsck).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path the file was found at
+    /// * `links` - The number of directory entries referring to the file
+    /// * `repair` - Whether to repair the anomalies found
+    /// * `report` - The report to add the anomalies to
+    pub(crate) fn fsck(&self, path: &str, links: u64, repair: bool, report: &mut FsckReport) {
+        let recorded = self.nlink.load(Ordering::Acquire);
+        if recorded != links {
+            report.issues.push(FsckIssue::LinkCount {
+                path: path.into(),
+                recorded,
+                actual: links,
+            });
+            if repair {
+                self.nlink.store(links, Ordering::Release);
+            }
+        }
+        if self.unlinked.load(Ordering::Acquire) {
+            report
+                .issues
+                .push(FsckIssue::UnlinkedReachable { path: path.into() });
+            if repair {
+                self.unlinked.store(false, Ordering::Release);
+            }
+        }
+        if self.data.read().has_data_beyond_size() {
+            report
+                .issues
+                .push(FsckIssue::DataBeyondSize { path: path.into() });
+            if repair {
+                self.data.write().trim(&self.fs);
+            }
         }
     }
 }
 
-impl VfsNodeOps for FileNode {
+impl axfs_vfs::VfsFileNodeOps for FileNode {
+    /// Returns the state counting the open handles of the file.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Opens the file, truncating it if `flags` contains
+    /// [`OpenFlags::TRUNC`] and [`OpenFlags::WRITE`].
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The access mode and options of the open
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(None)` on success, or
+    /// [`VfsError::PermissionDenied`](axfs_vfs::VfsError::PermissionDenied)
+    /// if the file is opened for writing and the filesystem is read-only, or
+    /// [`VfsError::OperationNotPermitted`](axfs_vfs::VfsError::OperationNotPermitted)
+    /// if the file is opened for writing and is immutable, or append-only
+    /// and not opened in append mode, or the errors of
+    /// [`truncate()`](Self::truncate).
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        if !flags.is_writable() {
+            return Ok(None);
+        }
+        if self.fs.is_read_only() {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.meta
+            .lock()
+            .check_open_write(flags.contains(OpenFlags::APPEND))?;
+        if flags.contains(OpenFlags::TRUNC) {
+            VfsNodeOps::truncate(self, 0)?;
+        }
+        Ok(None)
+    }
+
     /// Returns the attributes of this file.
     ///
     /// # Returns
     ///
-    /// Returns file attributes with current size.
+    /// Returns file attributes with current size, link count and timestamps.
     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        Ok(VfsNodeAttr::new_file(self.content.read().len() as _, 0))
+        let attr = VfsNodeAttr::new_file(self.data.read().size(), 0)
+            .with_ino(self.ino)
+            .with_nlink(self.nlink.load(Ordering::Acquire));
+        Ok(self.meta.lock().fill_attr(attr))
+    }
+
+    /// Returns the generation of the filesystem.
+    fn generation(&self) -> u32 {
+        self.fs.generation()
+    }
+
+    /// Changes the permissions, size or times of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `attr` - The attributes to change
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the errors of
+    /// [`truncate()`](VfsNodeOps::truncate) if the size is changed, or
+    /// [`VfsError::WouldBlock`](axfs_vfs::VfsError::WouldBlock) if the
+    /// filesystem is frozen, or
+    /// [`VfsError::OperationNotPermitted`](axfs_vfs::VfsError::OperationNotPermitted)
+    /// if the file is immutable, or append-only and the size is changed.
+    fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().check_changeable()?;
+        if let Some(size) = attr.get_size() {
+            VfsNodeOps::truncate(self, size)?;
+        }
+        self.meta.lock().apply(attr, self.fs.now());
+        Ok(())
+    }
+
+    /// Returns the protection flags of the file.
+    fn get_flags(&self) -> VfsResult<VfsNodeFlags> {
+        Ok(self.meta.lock().flags)
+    }
+
+    /// Replaces the protection flags of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The new flags of the file
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or
+    /// [`VfsError::WouldBlock`](axfs_vfs::VfsError::WouldBlock) if the
+    /// filesystem is frozen.
+    fn set_flags(&self, flags: VfsNodeFlags) -> VfsResult {
+        let _mutation = self.fs.check_mutable()?;
+        self.meta.lock().set_flags(flags, self.fs.now());
+        Ok(())
     }
 
     /// Truncates or extends the file to the specified size.
@@ -48,17 +554,82 @@ impl VfsNodeOps for FileNode {
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` on success.
+    /// Returns `Ok(())` on success, or
+    /// [`VfsError::InvalidInput`](axfs_vfs::VfsError::InvalidInput) if
+    /// `size` is beyond the maximum file size, `i64::MAX`, or
+    /// [`VfsError::WouldBlock`](axfs_vfs::VfsError::WouldBlock) if the
+    /// filesystem is frozen, or
+    /// [`VfsError::OperationNotPermitted`](axfs_vfs::VfsError::OperationNotPermitted)
+    /// if the file is immutable or append-only.
     fn truncate(&self, size: u64) -> VfsResult {
-        let mut content = self.content.write();
-        if size < content.len() as u64 {
-            content.truncate(size as _);
+        trace::record(TraceOp::Truncate, self.ino, "", self.truncate_data(size))
+    }
+
+    /// Allocates or deallocates the pages of a range of the file.
+    ///
+    /// Allocating reserves the pages of the range up front, so that writes
+    /// to it do not allocate memory; with [`FallocateMode::KEEP_SIZE`], the
+    /// pages beyond the end of the file are not reserved. Punching a hole
+    /// frees the pages of the range.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file of the range
+    /// * `len` - The length of the range in bytes
+    /// * `mode` - The operation to perform
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or
+    /// [`VfsError::InvalidInput`](axfs_vfs::VfsError::InvalidInput) if `len`
+    /// is `0`, the range ends beyond the maximum file size, or a hole is
+    /// punched without [`FallocateMode::KEEP_SIZE`], or
+    /// [`VfsError::WouldBlock`](axfs_vfs::VfsError::WouldBlock) if the
+    /// filesystem is frozen, or
+    /// [`VfsError::OperationNotPermitted`](axfs_vfs::VfsError::OperationNotPermitted)
+    /// if the file is immutable, or append-only and a hole is punched.
+    fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        let punch = mode.contains(FallocateMode::PUNCH_HOLE);
+        if len == 0
+            || offset
+                .checked_add(len)
+                .is_none_or(|end| end > MAX_FILE_SIZE)
+            || (punch && !mode.contains(FallocateMode::KEEP_SIZE))
+        {
+            return Err(VfsError::InvalidInput);
+        }
+        let _mutation = self.fs.check_mutable()?;
+        let mut data = self.data.write();
+        let old_size = data.size();
+        let changed = if punch {
+            self.meta.lock().check_rewritable()?;
+            data.punch_hole(offset, len, &self.fs)?
         } else {
-            content.resize(size as _, 0);
+            self.meta.lock().check_changeable()?;
+            data.allocate(
+                offset,
+                len,
+                mode.contains(FallocateMode::KEEP_SIZE),
+                &self.fs,
+            )?;
+            old_size..data.size()
+        };
+        drop(data);
+        if !changed.is_empty() {
+            add_dirty_range(self.dirty.lock().get_or_insert_with(Vec::new), changed);
+            self.content_changed();
         }
         Ok(())
     }
 
+    /// Finds the next region of data or the next hole of the file.
+    ///
+    /// Unallocated pages are holes, see
+    /// [`fallocate()`](VfsNodeOps::fallocate) to punch them.
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        Ok(self.data.read().seek_hint(offset, hint))
+    }
+
     /// Reads data from the file at the given offset.
     ///
     /// # Arguments
@@ -70,12 +641,7 @@ impl VfsNodeOps for FileNode {
     ///
     /// Returns the number of bytes actually read.
     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
-        let content = self.content.read();
-        let start = content.len().min(offset as usize);
-        let end = content.len().min(offset as usize + buf.len());
-        let src = &content[start..end];
-        buf[..src.len()].copy_from_slice(src);
-        Ok(src.len())
+        trace::record(TraceOp::Read, self.ino, "", self.read_data(offset, buf))
     }
 
     /// Writes data to the file at the given offset.
@@ -89,28 +655,255 @@ impl VfsNodeOps for FileNode {
     ///
     /// # Returns
     ///
-    /// Returns the number of bytes written.
+    /// Returns the number of bytes written, fewer than `buf.len()` if the
+    /// size limit of the filesystem is reached, or
+    /// [`VfsError::StorageFull`](axfs_vfs::VfsError::StorageFull) if it is
+    /// reached before any byte is written, or
+    /// [`VfsError::InvalidInput`](axfs_vfs::VfsError::InvalidInput) if the
+    /// file would grow beyond the maximum file size, `i64::MAX`, or
+    /// [`VfsError::WouldBlock`](axfs_vfs::VfsError::WouldBlock) if the
+    /// filesystem is frozen, or
+    /// [`VfsError::OperationNotPermitted`](axfs_vfs::VfsError::OperationNotPermitted)
+    /// if the file is immutable, or append-only and `offset` is before its
+    /// end.
     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
-        let offset = offset as usize;
-        let mut content = self.content.write();
-        if offset + buf.len() > content.len() {
-            content.resize(offset + buf.len(), 0);
+        trace::record(TraceOp::Write, self.ino, "", self.write_data(offset, buf))
+    }
+
+    /// Reads data from the file at the given offset into several buffers.
+    ///
+    /// The content is locked and faulted in once for all the buffers, which
+    /// are filled directly from the pages.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The byte offset to start reading from
+    /// * `bufs` - The buffers to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the total number of bytes read.
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        trace::record(
+            TraceOp::Read,
+            self.ino,
+            "",
+            self.read_vectored_data(offset, bufs),
+        )
+    }
+
+    /// Writes data to the file at the given offset from several buffers.
+    ///
+    /// The buffers are written under a single lock of the content, so
+    /// concurrent readers see either none or all of them, as for
+    /// `pwritev()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The byte offset to start writing at
+    /// * `bufs` - The buffers containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the total number of bytes written, or the errors of
+    /// [`write_at()`](Self::write_at).
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        trace::record(
+            TraceOp::Write,
+            self.ino,
+            "",
+            self.write_vectored_data(offset, bufs),
+        )
+    }
+
+    /// Copies a range of the file into another file.
+    ///
+    /// If `dst` is another file of RAM filesystem, the data is copied
+    /// directly between the pages of the two files, and holes are
+    /// preserved. Otherwise, it is copied through a buffer with
+    /// [`copy_range()`](axfs_vfs::copy::copy_range).
+    ///
+    /// # Arguments
+    ///
+    /// * `src_off` - The offset in this file of the range to copy
+    /// * `dst` - The file to copy into
+    /// * `dst_off` - The offset in `dst` to copy the range at
+    /// * `len` - The length of the range in bytes
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes copied, fewer than `len` if the end of
+    /// this file or the size limit of the filesystem is reached, or the
+    /// errors of [`write_at()`](Self::write_at) on `dst`.
+    fn copy_file_range(
+        &self,
+        src_off: u64,
+        dst: &dyn VfsNodeOps,
+        dst_off: u64,
+        len: usize,
+    ) -> VfsResult<usize> {
+        match dst.as_any().downcast_ref::<FileNode>() {
+            Some(dst) if !core::ptr::eq(self, dst) => self.copy_data(src_off, dst, dst_off, len),
+            _ => axfs_vfs::copy::copy_range(self, src_off, dst, dst_off, len),
+        }
+    }
+
+    /// Adds an entry referring to this file in `dir`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to add the entry in
+    /// * `name` - The path of the new entry, relative to `dir`
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the entry was added, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`DirNode::link_node`], or
+    /// [`VfsError::NotADirectory`] if `dir` is not a directory, or
+    /// [`VfsError::CrossesDevices`] if it is not a directory of RAM
+    /// filesystem.
+    fn link_into(self: Arc<Self>, dir: &VfsNodeRef, name: &str) -> VfsResult {
+        let parent = match dir.clone().downcast::<DirNode>() {
+            Ok(parent) => parent,
+            Err(_) if dir.get_attr()?.is_dir() => return Err(VfsError::CrossesDevices),
+            Err(_) => return Err(VfsError::NotADirectory),
+        };
+        let (parent, name) = parent.split_parent(None, name)?;
+        if name.is_empty() || name == "." || name == ".." {
+            return Err(VfsError::AlreadyExists);
+        }
+        let res = parent.link_node(name, self);
+        trace::record(TraceOp::Link, parent.ino(), name, res)
+    }
+
+    /// Brings the swapped-out pages of a range of the file back in memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file of the range
+    /// * `len` - The length of the range in bytes, or `0` for the rest of
+    ///   the file
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the error of the swap device.
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        let mut data = self.data.write();
+        let size = data.size();
+        let end = match len {
+            0 => size,
+            len => offset.saturating_add(len).min(size),
+        };
+        if end > offset {
+            data.fault_in(offset, (end - offset) as usize, &self.fs)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the page at `offset` of the file, to map in memory.
+    ///
+    /// The page is the storage of the file itself, so writes through the
+    /// mapping are seen by reads of the file, and the page is not swapped
+    /// out while mapped. It is marked dirty for the persistence backend when
+    /// it is returned: later writes through the mapping are persisted if the
+    /// page is requested again, like when a mapping faults again after
+    /// `msync()`.
+    ///
+    /// While the filesystem is read-only or frozen, the page is a private
+    /// copy of the content instead, which must be mapped read-only, so that
+    /// the file cannot be modified through the mapping.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset of the page, a multiple of
+    ///   [`PAGE_SIZE`](axfs_vfs::page::PAGE_SIZE)
+    ///
+    /// # Returns
+    ///
+    /// Returns a [`VfsPage::Frame`], or a [`VfsPage::ReadOnlyFrame`] if the
+    /// filesystem cannot be modified, on success, or
+    /// [`VfsError::InvalidInput`](axfs_vfs::VfsError::InvalidInput) if
+    /// `offset` is not aligned or is beyond the end of the file.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        let Ok(_mutation) = self.fs.check_mutable() else {
+            return self.page_copy(offset);
+        };
+        let mut data = self.data.write();
+        let frame = data.get_page(offset, &self.fs)?;
+        let end = (offset + PAGE_SIZE as u64).min(data.size());
+        drop(data);
+        add_dirty_range(self.dirty.lock().get_or_insert_with(Vec::new), offset..end);
+        self.fs.mark_dirty();
+        Ok(VfsPage::Frame(frame))
+    }
+
+    /// Synchronizes the file to the persistence backend.
+    ///
+    /// Does nothing if the file is no longer reachable from the root
+    /// directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the error reported by the backend.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if no backend is attached to the
+    /// filesystem, as a file only held in memory cannot be synchronized.
+    fn fsync(&self) -> VfsResult {
+        if self.fs.backend().is_none() {
+            return Err(VfsError::InvalidInput);
+        }
+        match self.path() {
+            Some(path) => self.sync_to(&path),
+            None => Ok(()),
         }
-        let dst = &mut content[offset..offset + buf.len()];
-        dst.copy_from_slice(&buf[..dst.len()]);
-        Ok(buf.len())
     }
 
-    impl_vfs_non_dir_default! {}
+    /// Synchronizes the data of the file to the persistence backend.
+    ///
+    /// The backend only receives the data and the size of files, so this is
+    /// the same as [`fsync()`](VfsNodeOps::fsync).
+    fn fsync_data(&self) -> VfsResult {
+        VfsNodeOps::fsync(self)
+    }
+
+    /// Frees the content of the file if it has been removed from its
+    /// directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())`.
+    fn on_last_release(&self) -> VfsResult {
+        if self.unlinked.load(Ordering::Acquire) {
+            self.data.write().clear(&self.fs);
+            *self.dirty.lock() = None;
+        }
+        Ok(())
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(FileNode);
+
+impl Drop for FileNode {
+    fn drop(&mut self) {
+        self.fs.unregister_ino(self.ino);
+        self.data.get_mut().clear(&self.fs);
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axfs_vfs::clock::ManualClock;
+    use core::time::Duration;
 
     #[test]
     fn test_file_node_new() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let attr = file.get_attr().unwrap();
         assert!(attr.is_file());
         assert_eq!(attr.size(), 0);
@@ -118,7 +911,7 @@ mod tests {
 
     #[test]
     fn test_file_node_write_at() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let data = b"Hello, World!";
         let written = file.write_at(0, data).unwrap();
         assert_eq!(written, data.len());
@@ -127,7 +920,7 @@ mod tests {
 
     #[test]
     fn test_file_node_write_at_offset() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let data = b"World!";
         file.write_at(0, b"Hello, ").unwrap();
         let written = file.write_at(7, data).unwrap();
@@ -141,7 +934,7 @@ mod tests {
 
     #[test]
     fn test_file_node_read_at_empty() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let mut buf = [0; 100];
         let read = file.read_at(0, &mut buf).unwrap();
         assert_eq!(read, 0);
@@ -149,7 +942,7 @@ mod tests {
 
     #[test]
     fn test_file_node_read_at() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let data = b"Hello, World!";
         file.write_at(0, data).unwrap();
 
@@ -161,7 +954,7 @@ mod tests {
 
     #[test]
     fn test_file_node_read_at_partial() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let data = b"Hello, World!";
         file.write_at(0, data).unwrap();
 
@@ -173,7 +966,7 @@ mod tests {
 
     #[test]
     fn test_file_node_read_at_offset() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let data = b"Hello, World!";
         file.write_at(0, data).unwrap();
 
@@ -185,7 +978,7 @@ mod tests {
 
     #[test]
     fn test_file_node_truncate_shrink() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         file.write_at(0, b"Hello, World!").unwrap();
         assert_eq!(file.get_attr().unwrap().size(), 13);
 
@@ -200,7 +993,7 @@ mod tests {
 
     #[test]
     fn test_file_node_truncate_grow() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         file.write_at(0, b"Hello").unwrap();
         assert_eq!(file.get_attr().unwrap().size(), 5);
 
@@ -214,9 +1007,29 @@ mod tests {
         assert_eq!(&buf[5..10], [0, 0, 0, 0, 0]);
     }
 
+    #[test]
+    fn test_file_node_max_size() {
+        let file = FileNode::new(Default::default());
+        assert_eq!(file.truncate(u64::MAX), Err(VfsError::InvalidInput));
+        assert_eq!(
+            file.set_attr(&SetAttr::new().size(MAX_FILE_SIZE + 1)),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(file.read_at(u64::MAX - 4, &mut [0; 8]), Ok(0));
+        assert_eq!(
+            file.write_at(u64::MAX - 1, b"abcd"),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            file.fallocate(MAX_FILE_SIZE, 1, FallocateMode::empty()),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(file.get_attr().unwrap().size(), 0);
+    }
+
     #[test]
     fn test_file_node_truncate_zero() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         file.write_at(0, b"Hello, World!").unwrap();
         assert_eq!(file.get_attr().unwrap().size(), 13);
 
@@ -230,7 +1043,7 @@ mod tests {
 
     #[test]
     fn test_file_node_write_extends() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         file.write_at(0, b"Hello").unwrap();
         file.write_at(10, b"World").unwrap();
 
@@ -246,7 +1059,7 @@ mod tests {
 
     #[test]
     fn test_file_node_get_attr() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         let attr = file.get_attr().unwrap();
         assert!(attr.is_file());
         assert!(!attr.is_dir());
@@ -256,15 +1069,247 @@ mod tests {
 
     #[test]
     fn test_file_node_get_attr_after_write() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
         file.write_at(0, b"Hello").unwrap();
         let attr = file.get_attr().unwrap();
         assert_eq!(attr.size(), 5);
     }
 
+    #[test]
+    fn test_file_node_times() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(10)));
+        let fs = Arc::new(FsState::default());
+        fs.set_clock(Some(clock.clone()));
+        let file = FileNode::new(fs);
+        let attr = file.get_attr().unwrap();
+        assert_eq!(attr.mtime(), Duration::from_secs(10));
+
+        clock.set(Duration::from_secs(20));
+        file.write_at(0, b"Hello").unwrap();
+        clock.set(Duration::from_secs(30));
+        file.read_at(0, &mut [0; 5]).unwrap();
+        let attr = file.get_attr().unwrap();
+        assert_eq!(attr.atime(), Duration::from_secs(30));
+        assert_eq!(attr.mtime(), Duration::from_secs(20));
+        assert_eq!(attr.ctime(), Duration::from_secs(20));
+
+        clock.set(Duration::from_secs(40));
+        file.truncate(0).unwrap();
+        assert_eq!(file.get_attr().unwrap().mtime(), Duration::from_secs(40));
+    }
+
+    #[test]
+    fn test_file_node_set_attr() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(10)));
+        let fs = Arc::new(FsState::default());
+        fs.set_clock(Some(clock.clone()));
+        let file = FileNode::new(fs.clone());
+        file.write_at(0, b"Hello").unwrap();
+        assert_eq!(file.get_attr().unwrap().perm().mode(), 0o666);
+
+        clock.set(Duration::from_secs(20));
+        let attr = SetAttr::new()
+            .mode(VfsNodePerm::from_bits_truncate(0o600))
+            .size(2)
+            .mtime(Duration::from_secs(1));
+        file.set_attr(&attr).unwrap();
+        let attr = file.get_attr().unwrap();
+        assert_eq!(attr.perm().mode(), 0o600);
+        assert_eq!(attr.size(), 2);
+        assert_eq!(attr.mtime(), Duration::from_secs(1));
+        assert_eq!(attr.ctime(), Duration::from_secs(20));
+
+        fs.set_frozen(true);
+        assert_eq!(
+            file.set_attr(&SetAttr::new()),
+            Err(axfs_vfs::VfsError::WouldBlock)
+        );
+    }
+
+    #[test]
+    fn test_file_node_last_release() {
+        let file = FileNode::new(Default::default());
+        file.write_at(0, b"Hello").unwrap();
+        file.on_last_release().unwrap();
+        assert_eq!(file.get_attr().unwrap().size(), 5);
+
+        file.unlink();
+        file.on_last_release().unwrap();
+        assert_eq!(file.get_attr().unwrap().size(), 0);
+        assert_eq!(file.data.read().resident_pages(), 0);
+    }
+
+    #[test]
+    fn test_file_node_secure_wipe_truncate() {
+        let fs = Arc::new(FsState::default());
+        fs.set_secure_wipe(true);
+        let file = FileNode::new(fs);
+        file.write_at(0, b"top secret").unwrap();
+        file.truncate(3).unwrap();
+
+        // the page no longer holds the truncated secret
+        let data = file.data.read();
+        assert_eq!(&data.resident_page(0).unwrap()[..10], b"top\0\0\0\0\0\0\0");
+    }
+
+    #[test]
+    fn test_file_node_secure_wipe_sparse() {
+        let fs = Arc::new(FsState::default());
+        fs.set_secure_wipe(true);
+        let file = FileNode::new(fs.clone());
+        file.write_at(0, b"secret").unwrap();
+        file.write_at(10_000, b"more").unwrap();
+        let mut buf = [0; 10_004];
+        assert_eq!(file.read_at(0, &mut buf).unwrap(), 10_004);
+        assert_eq!(&buf[..6], b"secret");
+        assert_eq!(&buf[10_000..], b"more");
+        file.truncate(0).unwrap();
+        assert_eq!(file.get_attr().unwrap().size(), 0);
+
+        // both pages were zeroized before being freed
+        let released = fs.take_released();
+        assert_eq!(released.len(), 2);
+        assert!(released.iter().flatten().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_file_node_secure_wipe_mapped() {
+        let fs = Arc::new(FsState::default());
+        fs.set_secure_wipe(true);
+        let file = FileNode::new(fs.clone());
+        file.write_at(0, b"secret").unwrap();
+        let Ok(VfsPage::Frame(mapping)) = file.get_page(0) else {
+            panic!("expected a page frame");
+        };
+        file.unlink();
+        file.on_last_release().unwrap();
+
+        // the page is freed with the mapping, and zeroized first
+        assert!(fs.take_released().is_empty());
+        drop(mapping);
+        let released = fs.take_released();
+        assert_eq!(released.len(), 1);
+        assert!(released[0].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_file_node_no_secure_wipe() {
+        let fs = Arc::new(FsState::default());
+        let file = FileNode::new(fs.clone());
+        file.write_at(0, b"secret").unwrap();
+        file.truncate(0).unwrap();
+
+        // without secure wipe the freed page still holds the data
+        let released = fs.take_released();
+        assert_eq!(released.len(), 1);
+        assert_eq!(&released[0][..6], b"secret");
+    }
+
+    #[test]
+    fn test_file_node_fallocate() {
+        let fs = Arc::new(FsState::default());
+        let file = FileNode::new(fs);
+        let page = PAGE_SIZE as u64;
+        assert_eq!(
+            file.fallocate(0, 0, FallocateMode::empty()),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            file.fallocate(0, 1, FallocateMode::PUNCH_HOLE),
+            Err(VfsError::InvalidInput)
+        );
+
+        file.fallocate(0, page * 2, FallocateMode::empty()).unwrap();
+        assert_eq!(file.get_attr().unwrap().size(), page * 2);
+        assert_eq!(file.resident_pages(), 2);
+        file.write_at(0, &[7; PAGE_SIZE * 2]).unwrap();
+        assert_eq!(file.resident_pages(), 2);
+
+        let punch = FallocateMode::PUNCH_HOLE | FallocateMode::KEEP_SIZE;
+        file.fallocate(page, page, punch).unwrap();
+        assert_eq!(file.resident_pages(), 1);
+        assert_eq!(file.get_attr().unwrap().size(), page * 2);
+        let mut buf = [1; 4];
+        assert_eq!(file.read_at(page, &mut buf), Ok(4));
+        assert_eq!(buf, [0; 4]);
+
+        file.set_flags(VfsNodeFlags::APPEND).unwrap();
+        assert_eq!(
+            file.fallocate(0, 1, punch),
+            Err(VfsError::OperationNotPermitted)
+        );
+        assert!(file.fallocate(page * 2, 1, FallocateMode::empty()).is_ok());
+    }
+
+    #[test]
+    fn test_file_node_copy_file_range() {
+        let fs = Arc::new(FsState::default());
+        let src = FileNode::new(fs.clone());
+        let page = PAGE_SIZE as u64;
+        src.write_at(10, b"head").unwrap();
+        src.write_at(3 * page - 2, b"tail").unwrap();
+        src.truncate(5 * page).unwrap();
+
+        // unaligned copies go from page to page, skipping the holes
+        let dst = FileNode::new(fs.clone());
+        assert_eq!(
+            src.copy_file_range(8, &dst, 1, 6 * PAGE_SIZE),
+            Ok(5 * PAGE_SIZE - 8)
+        );
+        assert_eq!(dst.get_attr().unwrap().size(), 5 * page - 7);
+        assert_eq!(dst.resident_pages(), 4);
+        let mut buf = [0; 6];
+        dst.read_at(1, &mut buf).unwrap();
+        assert_eq!(&buf, b"\0\0head");
+        dst.read_at(3 * page - 9, &mut buf[..4]).unwrap();
+        assert_eq!(&buf[..4], b"tail");
+
+        // holes copied over data clear it
+        let data = FileNode::new(fs.clone());
+        data.write_at(0, &[0xff; PAGE_SIZE]).unwrap();
+        assert_eq!(src.copy_file_range(page, &data, 0, 16), Ok(16));
+        data.read_at(0, &mut buf).unwrap();
+        assert_eq!(buf, [0; 6]);
+
+        assert_eq!(src.copy_file_range(5 * page, &dst, 0, 1), Ok(0));
+        assert_eq!(
+            src.copy_file_range(0, &src, 4, 8),
+            Err(VfsError::InvalidInput)
+        );
+        dst.set_flags(VfsNodeFlags::IMMUTABLE).unwrap();
+        assert_eq!(
+            src.copy_file_range(0, &dst, 0, 1),
+            Err(VfsError::OperationNotPermitted)
+        );
+    }
+
+    #[test]
+    fn test_file_node_vectored_io() {
+        let file = FileNode::new(Default::default());
+        let bufs = [
+            IoSlice::new(b"Hello, "),
+            IoSlice::new(&[]),
+            IoSlice::new(b"World!"),
+        ];
+        assert_eq!(file.write_vectored_at(PAGE_SIZE as u64 - 3, &bufs), Ok(13));
+        assert_eq!(file.get_attr().unwrap().size(), PAGE_SIZE as u64 + 10);
+
+        let (mut a, mut b, mut c) = ([0; 4], [0; 6], [1; 8]);
+        let mut bufs = [
+            IoSliceMut::new(&mut a),
+            IoSliceMut::new(&mut b),
+            IoSliceMut::new(&mut c),
+        ];
+        assert_eq!(
+            file.read_vectored_at(PAGE_SIZE as u64 - 3, &mut bufs),
+            Ok(13)
+        );
+        assert_eq!((&a, &b, &c[..3]), (b"Hell", b"o, Wor", &b"ld!"[..]));
+    }
+
     #[test]
     fn test_file_node_operations_combined() {
-        let file = FileNode::new();
+        let file = FileNode::new(Default::default());
 
         // Write data
         file.write_at(0, b"Hello, ").unwrap();
@@ -0,0 +1,164 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::dir::DirNode;
+use crate::file::FileNode;
+
+/// An anomaly found by [`RamFileSystem::fsck()`](crate::RamFileSystem::fsck).
+///
+/// Each anomaly carries the absolute path of the node it was found on. For
+/// files with several links, this is the first path found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsckIssue {
+    /// The parent pointer of a directory does not refer to the directory
+    /// containing it, or refers to a directory that has been dropped.
+    WrongParent {
+        /// The path of the directory
+        path: String,
+    },
+    /// A file holds data beyond its size.
+    DataBeyondSize {
+        /// The path of the file
+        path: String,
+    },
+    /// The link count of a file differs from the number of directory
+    /// entries referring to it.
+    LinkCount {
+        /// The path of the file
+        path: String,
+        /// The link count recorded in the file
+        recorded: u64,
+        /// The number of directory entries found
+        actual: u64,
+    },
+    /// A file reachable from the root is marked as removed from all its
+    /// directories, so its storage would be freed on its last close.
+    UnlinkedReachable {
+        /// The path of the file
+        path: String,
+    },
+}
+
+/// The result of a consistency check of a RAM filesystem.
+///
+/// # Fields
+///
+/// - `nodes` - The number of nodes checked, counting files with several links
+///   once
+/// - `issues` - The anomalies found
+/// - `repaired` - Whether the anomalies have been repaired
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct FsckReport {
+    pub nodes: usize,
+    pub issues: Vec<FsckIssue>,
+    pub repaired: bool,
+}
+
+impl FsckReport {
+    /// Returns whether no anomaly was found.
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// The files met while walking the tree, with the first path they were found
+/// at and the number of entries referring to them.
+pub(crate) type FileLinks = BTreeMap<*const FileNode, (Arc<FileNode>, String, u64)>;
+
+/// Checks the tree rooted at `root`.
+///
+/// # Arguments
+///
+/// * `root` - The root directory of the filesystem
+/// * `repair` - Whether to repair the anomalies found
+///
+/// # Returns
+///
+/// Returns the report of the check.
+pub(crate) fn check(root: &Arc<DirNode>, repair: bool) -> FsckReport {
+    let mut report = FsckReport {
+        nodes: 1,
+        repaired: repair,
+        ..Default::default()
+    };
+    let mut files = FileLinks::new();
+    root.fsck_tree("", repair, &mut report, &mut files);
+    report.nodes += files.len();
+    for (file, path, links) in files.into_values() {
+        file.fsck(&path, links, repair, &mut report);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RamFileSystem;
+    use axfs_vfs::{VfsNodeOps, VfsNodeRef, VfsNodeRefExt, VfsNodeType, VfsOps};
+
+    #[test]
+    fn test_fsck_clean() {
+        let fs = RamFileSystem::new();
+        let root = fs.root_dir();
+        root.create("a", VfsNodeType::Dir).unwrap();
+        root.create("a/b", VfsNodeType::Dir).unwrap();
+        root.create("a/b/f", VfsNodeType::File).unwrap();
+        root.link("a/b/f", "g").unwrap();
+        root.create_symlink("l", "a").unwrap();
+        let file = root.clone().lookup("g").unwrap();
+        file.write_at(5000, b"data").unwrap();
+        file.truncate(4100).unwrap();
+
+        let report = fs.fsck(false);
+        assert!(report.is_clean(), "{report:?}");
+        assert_eq!(report.nodes, 5);
+        assert!(!report.repaired);
+    }
+
+    #[test]
+    fn test_fsck_link_count() {
+        let fs = RamFileSystem::new();
+        let root = fs.root_dir();
+        root.create("f", VfsNodeType::File).unwrap();
+        let node = root.clone().lookup("f").unwrap();
+        let file = node.clone().downcast::<FileNode>().unwrap();
+        file.link(&node).unwrap();
+
+        let issue = FsckIssue::LinkCount {
+            path: "/f".into(),
+            recorded: 2,
+            actual: 1,
+        };
+        assert_eq!(fs.fsck(false).issues, core::slice::from_ref(&issue));
+        // checking without repairing changes nothing
+        assert_eq!(fs.fsck(false).issues, core::slice::from_ref(&issue));
+        assert_eq!(fs.fsck(true).issues, [issue]);
+        assert!(fs.fsck(false).is_clean());
+        assert_eq!(file.get_attr().unwrap().nlink(), 1);
+    }
+
+    #[test]
+    fn test_fsck_wrong_parent() {
+        let fs = RamFileSystem::new();
+        let root = fs.root_dir();
+        root.create("a", VfsNodeType::Dir).unwrap();
+        root.create("b", VfsNodeType::Dir).unwrap();
+        let a = root.clone().lookup_as::<DirNode>("a").unwrap();
+        let b: VfsNodeRef = root.clone().lookup("b").unwrap();
+        a.set_parent(Some(&b));
+
+        let report = fs.fsck(true);
+        assert_eq!(
+            report.issues,
+            [FsckIssue::WrongParent { path: "/a".into() }]
+        );
+        assert!(report.repaired);
+        assert!(fs.fsck(false).is_clean());
+        assert!(core::ptr::addr_eq(
+            Arc::as_ptr(&a.parent().unwrap()),
+            Arc::as_ptr(&fs.root_dir_node())
+        ));
+    }
+}
@@ -10,11 +10,25 @@
 //! - [`RamFileSystem`] - The main filesystem structure implementing filesystem operations
 //! - [`DirNode`] - Directory node implementing directory operations
 //! - [`FileNode`] - File node implementing file operations
+//! - [`SymlinkNode`] - Symbolic link node
+//! - [`PersistenceBackend`] - Optional write-through target for file contents
+//!
+//! With the `test-util` feature, the [`test_util`] module builds populated
+//! filesystems for tests, and compares them with expected trees.
+//!
+//! With the `enforce-perms` feature, lookups, creations and removals are
+//! checked against the [`VfsContext`](axfs_vfs::VfsContext) of the current
+//! task, given by the provider installed with
+//! [`set_context_provider`](axfs_vfs::set_context_provider), and new nodes
+//! are owned by the task.
+//!
+//! File contents are stored in pages that can be evicted to a swap device
+//! (see [`RamFileSystem::set_swap_device`]) on memory-constrained systems.
 //!
 //! # Features
 //!
 //! - Full support for file and directory operations
-//! - Hierarchical directory structure
+//! - Hierarchical directory structure, with atomic renames
 //! - In-memory storage with fast access
 //! - Compatible with the axfs_vfs interface
 //!
@@ -24,19 +38,47 @@
 
 extern crate alloc;
 
+mod data;
+mod defaults;
+mod device;
 mod dir;
 mod file;
+mod fsck;
+mod meta;
+mod persist;
+mod state;
+mod swap;
+mod symlink;
+
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
 
 #[cfg(test)]
 mod tests;
 
+pub use self::defaults::DefaultAttrs;
+pub use self::device::DeviceNode;
 pub use self::dir::DirNode;
 pub use self::file::FileNode;
+pub use self::fsck::{FsckIssue, FsckReport};
+pub use self::persist::PersistenceBackend;
+pub use self::symlink::SymlinkNode;
 
+use alloc::collections::BTreeSet;
 use alloc::sync::Arc;
-use axfs_vfs::{VfsNodeRef, VfsOps, VfsResult};
+use axfs_vfs::names::NamePolicy;
+use axfs_vfs::notify::{WatchId, WatchMask, WatchSink};
+use axfs_vfs::writeback::{FlushStatus, PollFlush, WritebackScheduler, WritebackTarget};
+use axfs_vfs::{
+    AtimePolicy, BlockDeviceOps, DeviceResolver, FileSystemInfo, MountOptions, ReadDirPolicy,
+    Umask, VfsCapabilities, VfsClock, VfsError, VfsFeatures, VfsNodeRef, VfsOps, VfsResult,
+};
+use core::time::Duration;
 use spin::once::Once;
 
+use self::state::FsState;
+use self::swap::SwapArea;
+
 /// A RAM filesystem that implements VFS operations.
 ///
 /// This is an in-memory filesystem that stores all data in RAM.
@@ -46,9 +88,11 @@ use spin::once::Once;
 ///
 /// - `parent` - The parent filesystem mount point
 /// - `root` - The root directory of the RAM filesystem
+/// - `state` - The state shared by all nodes of the filesystem
 pub struct RamFileSystem {
     parent: Once<VfsNodeRef>,
     root: Arc<DirNode>,
+    state: Arc<FsState>,
 }
 
 impl RamFileSystem {
@@ -58,9 +102,37 @@ impl RamFileSystem {
     ///
     /// A new `RamFileSystem` with an empty root directory.
     pub fn new() -> Self {
+        Self::with_state(FsState::default())
+    }
+
+    /// Create a new RAM filesystem instance with its own clock.
+    ///
+    /// All timestamps of the filesystem, including those of the root
+    /// directory, are taken from `clock` instead of the global clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The time source of the filesystem
+    ///
+    /// # Returns
+    ///
+    /// A new `RamFileSystem` with an empty root directory.
+    pub fn with_clock(clock: Arc<dyn VfsClock>) -> Self {
+        let state = FsState::default();
+        state.set_clock(Some(clock));
+        Self::with_state(state)
+    }
+
+    /// Creates the root directory of a new filesystem sharing `state`.
+    fn with_state(state: FsState) -> Self {
+        let state = Arc::new(state);
+        let root = DirNode::new(None, state.clone());
+        state.set_root(&root);
+        state.register_ino(root.ino(), &(root.clone() as VfsNodeRef));
         Self {
             parent: Once::new(),
-            root: DirNode::new(None),
+            root,
+            state,
         }
     }
 
@@ -72,6 +144,313 @@ impl RamFileSystem {
     pub fn root_dir_node(&self) -> Arc<DirNode> {
         self.root.clone()
     }
+
+    /// Sets the time source of the filesystem.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The new clock, or `None` to use the global clock
+    pub fn set_clock(&self, clock: Option<Arc<dyn VfsClock>>) {
+        self.state.set_clock(clock);
+    }
+
+    /// Sets the rules that the names of the nodes follow.
+    ///
+    /// The policy applies to the nodes created from now on, and to the
+    /// lookups of all nodes, so existing names that do not follow it may
+    /// become unreachable. It is meant to be set right after the creation of
+    /// the filesystem.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The validation and normalization rules of names
+    pub fn set_name_policy(&self, policy: NamePolicy) {
+        self.state.set_name_policy(policy);
+    }
+
+    /// Returns the rules that the names of the nodes follow.
+    pub fn name_policy(&self) -> NamePolicy {
+        self.state.name_policy()
+    }
+
+    /// Sets when the streams opened with
+    /// [`open_dir()`](axfs_vfs::VfsNodeOps::open_dir) read the entries of their
+    /// directory.
+    ///
+    /// With [`ReadDirPolicy::Snapshot`], a stream copies the entries at its
+    /// first read, so that a directory changed while it is listed is listed
+    /// consistently. The policy applies to the streams opened from now on.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy, [`ReadDirPolicy::Live`] by default
+    pub fn set_read_dir_policy(&self, policy: ReadDirPolicy) {
+        self.state.set_read_dir_policy(policy);
+    }
+
+    /// Returns when the directory streams read their entries.
+    pub fn read_dir_policy(&self) -> ReadDirPolicy {
+        self.state.read_dir_policy()
+    }
+
+    /// Sets when reads update the access times of the nodes.
+    ///
+    /// With [`AtimePolicy::Relative`] most reads leave the metadata of the
+    /// node untouched, and with [`AtimePolicy::Never`] they do not even
+    /// lock it.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - The policy, [`AtimePolicy::Strict`] by default
+    pub fn set_atime_policy(&self, policy: AtimePolicy) {
+        self.state.set_atime_policy(policy);
+    }
+
+    /// Returns when reads update the access times of the nodes.
+    pub fn atime_policy(&self) -> AtimePolicy {
+        self.state.atime_policy()
+    }
+
+    /// Sets the umask applied to the nodes created without a context.
+    ///
+    /// The nodes created with a [`VfsContext`](axfs_vfs::VfsContext), or
+    /// with the context of the current task under the `enforce-perms`
+    /// feature, get the umask of their context instead. Without a umask,
+    /// such nodes keep their built-in permissions, `0o666` for files and
+    /// `0o755` for directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `umask` - The umask, or `None` to keep the built-in permissions
+    pub fn set_umask(&self, umask: Option<Umask>) {
+        self.state.set_umask(umask);
+    }
+
+    /// Returns the umask applied to the nodes created without a context.
+    pub fn umask(&self) -> Option<Umask> {
+        self.state.umask()
+    }
+
+    /// Limits the size of the file data stored in the filesystem, as the
+    /// `size=` mount option does.
+    ///
+    /// Writes, extending fallocates and faults of memory mappings needing
+    /// more pages then fail with [`VfsError::StorageFull`], and writes that
+    /// reach the limit midway are short. Holes take no space. Data already
+    /// stored beyond a lowered limit is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The maximum size in bytes, rounded up to whole pages of
+    ///   4096 bytes, or `None` (or `Some(0)`) for no limit
+    pub fn set_size_limit(&self, bytes: Option<u64>) {
+        let pages = bytes.map(|bytes| bytes.div_ceil(data::PAGE_SIZE as u64));
+        self.state.set_page_limit(pages.filter(|&pages| pages != 0));
+    }
+
+    /// Returns the limit of the size of the file data, in bytes.
+    pub fn size_limit(&self) -> Option<u64> {
+        self.state
+            .page_limit()
+            .map(|pages| pages * data::PAGE_SIZE as u64)
+    }
+
+    /// Enables or disables the secure wipe of file contents.
+    ///
+    /// When enabled, file data is overwritten with zeros before its memory is
+    /// freed: when a file is truncated, when a removed file is finally
+    /// dropped, and for pages still mapped in memory, when the last mapping
+    /// is dropped. This keeps secrets stored in the filesystem from
+    /// lingering in freed heap memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to zeroize freed file data
+    pub fn set_secure_wipe(&self, enabled: bool) {
+        self.state.set_secure_wipe(enabled);
+    }
+
+    /// Attaches a swap device to the filesystem.
+    ///
+    /// Cold file pages can then be moved to the device with
+    /// [`evict_cold_pages()`](Self::evict_cold_pages), and are faulted back
+    /// in memory when accessed. The whole device is used as swap area.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - The swap device, or `None` to detach the current one
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the page size (4096 bytes) is
+    /// not a multiple of the block size of the device, or
+    /// [`VfsError::ResourceBusy`] if pages are still stored in the current
+    /// swap device.
+    pub fn set_swap_device(&self, dev: Option<Arc<dyn BlockDeviceOps>>) -> VfsResult {
+        let swap = dev.map(SwapArea::new).transpose()?.map(Arc::new);
+        self.state.set_swap(swap)
+    }
+
+    /// Moves cold file pages to the swap device.
+    ///
+    /// A page is cold if it has not been accessed since the previous call.
+    /// Calling this periodically thus evicts the pages unused for a period.
+    /// Does nothing if no swap device is attached.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_pages` - The maximum number of pages to evict
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of evicted pages, which is smaller than
+    /// `max_pages` if there are not enough cold pages or the swap device is
+    /// full.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of the swap device.
+    pub fn evict_cold_pages(&self, max_pages: usize) -> VfsResult<usize> {
+        let Some(swap) = self.state.swap() else {
+            return Ok(0);
+        };
+        let mut budget = max_pages;
+        // files with several links are visited once per link, but must only
+        // be aged once per call
+        let mut visited = BTreeSet::new();
+        let res = self.root.visit_files(&mut |file| {
+            if visited.insert(file as *const FileNode) {
+                file.evict(&swap, &mut budget)?;
+            }
+            Ok(())
+        });
+        match res {
+            Ok(()) | Err(VfsError::StorageFull) => Ok(max_pages - budget),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Returns the number of pages stored in the swap device.
+    pub fn swapped_pages(&self) -> usize {
+        self.state.swap().map_or(0, |swap| swap.used_slots())
+    }
+
+    /// Sets the resolver of the device nodes of the filesystem.
+    ///
+    /// Device nodes created with `mknod` forward their operations to the
+    /// driver `resolver` returns for their device number, such as those
+    /// registered in an `axfs_devfs` device registry. This lets a ramfs-based
+    /// `/dev` work like devtmpfs.
+    ///
+    /// # Arguments
+    ///
+    /// * `resolver` - The resolver to use, or `None` to make all device nodes
+    ///   fail with [`VfsError::NoSuchDevice`]
+    pub fn set_device_resolver(&self, resolver: Option<Arc<dyn DeviceResolver>>) {
+        self.state.set_device_resolver(resolver);
+    }
+
+    /// Attaches a persistence backend to the filesystem.
+    ///
+    /// From now on, `fsync` on a file and [`sync()`](Self::sync) push the
+    /// data written since the last synchronization to `backend`. Data written
+    /// before the backend was attached is pushed by the next synchronization.
+    /// Removals and renames are reported to `backend` as they happen.
+    ///
+    /// # Arguments
+    ///
+    /// * `backend` - The backend to attach, or `None` to detach the current one
+    pub fn set_persistence_backend(&self, backend: Option<Arc<dyn PersistenceBackend>>) {
+        let attached = backend.is_some();
+        self.state.set_backend(backend);
+        if attached {
+            // data written before may be waiting for the backend
+            self.state.mark_dirty();
+        }
+    }
+
+    /// Registers the filesystem with a writeback scheduler.
+    ///
+    /// Writes and truncations then mark the filesystem dirty in `scheduler`,
+    /// which flushes it with [`sync()`](Self::sync) once the data is old
+    /// enough. The filesystem is unregistered from the previous scheduler.
+    ///
+    /// # Arguments
+    ///
+    /// * `scheduler` - The scheduler to register with, or `None` to only
+    ///   unregister
+    pub fn set_writeback_scheduler(self: &Arc<Self>, scheduler: Option<Arc<WritebackScheduler>>) {
+        let writeback = scheduler.map(|scheduler| {
+            let target: Arc<dyn WritebackTarget> = self.clone();
+            let id = scheduler.register(&target);
+            (scheduler, id)
+        });
+        if let Some((old, id)) = self.state.set_writeback(writeback) {
+            old.unregister(id);
+        }
+    }
+
+    /// Checks the consistency of the filesystem, and optionally repairs the
+    /// anomalies found.
+    ///
+    /// Walks the whole tree and verifies that:
+    ///
+    /// - the parent pointer of each directory refers to the directory
+    ///   containing it;
+    /// - the link count of each file matches the number of directory entries
+    ///   referring to it, and no reachable file is marked as removed;
+    /// - no file holds data beyond its size.
+    ///
+    /// The check is not atomic: it should run while the filesystem is idle,
+    /// for instance frozen, or it may report transient anomalies.
+    ///
+    /// # Arguments
+    ///
+    /// * `repair` - Whether to repair the anomalies found. Nothing is
+    ///   repaired while the filesystem is frozen or read-only.
+    ///
+    /// # Returns
+    ///
+    /// Returns the report of the check.
+    pub fn fsck(&self, repair: bool) -> FsckReport {
+        let mutation = repair.then(|| self.state.check_mutable().ok()).flatten();
+        fsck::check(&self.root, mutation.is_some())
+    }
+
+    /// Synchronizes all files of the filesystem to the persistence backend.
+    ///
+    /// Does nothing if no backend is attached. The filesystem is clean
+    /// afterwards, unless the synchronization fails.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the first error reported by the backend.
+    pub fn sync(&self) -> VfsResult {
+        let since = self.state.take_dirty();
+        if self.state.backend().is_none() {
+            return Ok(());
+        }
+        self.root.sync_tree().inspect_err(|_| {
+            if let Some(since) = since {
+                self.state.restore_dirty(since);
+            }
+        })
+    }
+
+    /// Returns since when the filesystem holds data not yet synchronized
+    /// to the persistence backend.
+    ///
+    /// # Returns
+    ///
+    /// Returns the time of the first change since the last
+    /// [`sync()`](Self::sync), or `None` if the filesystem is clean.
+    pub fn dirty_since(&self) -> Option<Duration> {
+        self.state.dirty_since()
+    }
 }
 
 impl VfsOps for RamFileSystem {
@@ -79,15 +458,24 @@ impl VfsOps for RamFileSystem {
     ///
     /// This method sets up the parent reference for the root directory.
     ///
+    /// The options are applied as by [`remount()`](VfsOps::remount).
+    ///
     /// # Arguments
     ///
     /// * `_path` - The mount path (not used in RAM filesystem)
     /// * `mount_point` - The mount point directory node
+    /// * `options` - The options of the mount
     ///
     /// # Returns
     ///
     /// Returns `Ok(())` on success.
-    fn mount(&self, _path: &str, mount_point: VfsNodeRef) -> VfsResult {
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] for an option other than `size=`,
+    /// or an invalid size.
+    fn mount(&self, _path: &str, mount_point: VfsNodeRef, options: &MountOptions) -> VfsResult {
+        self.remount(options)?;
         if let Some(parent) = mount_point.parent() {
             self.root.set_parent(Some(self.parent.call_once(|| parent)));
         } else {
@@ -96,6 +484,242 @@ impl VfsOps for RamFileSystem {
         Ok(())
     }
 
+    /// Changes the options of the RAM filesystem.
+    ///
+    /// A read-only filesystem fails writes, truncation, opening files for
+    /// writing, creation, removal and changes of attributes with
+    /// [`VfsError::PermissionDenied`], and reads do not update access
+    /// times. An initramfs can thus be sealed once booted, and unsealed by
+    /// remounting it without `ro`.
+    ///
+    /// The `size=` option limits the size of the file data, see
+    /// [`set_size_limit()`](Self::set_size_limit), with `size=0` meaning no
+    /// limit as for Linux `tmpfs`. The limit is kept if the option is not
+    /// given, and so is the access time policy, see
+    /// [`set_atime_policy()`](Self::set_atime_policy).
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The new options
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, leaving the filesystem unchanged on
+    /// error.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] for an option other than `size=`,
+    /// or an invalid size.
+    fn remount(&self, options: &MountOptions) -> VfsResult {
+        options.check_known(&["size"])?;
+        if let Some(size) = options.get_size("size")? {
+            self.set_size_limit(Some(size));
+        }
+        if let Some(policy) = options.atime_policy() {
+            self.state.set_atime_policy(policy);
+        }
+        self.state.set_read_only(options.is_read_only());
+        Ok(())
+    }
+
+    /// Returns the attributes of the RAM filesystem.
+    ///
+    /// Without a size limit, a RAM filesystem has no fixed capacity, so the
+    /// blocks reported are the pages of file data held in memory, and none
+    /// are free. Pages moved to the swap device are not counted. With a
+    /// limit, the blocks reported are the limit, and the free ones the pages
+    /// not allocated yet, in memory or swapped. The inodes reported are the
+    /// live nodes.
+    ///
+    /// # Returns
+    ///
+    /// Returns the memory usage of the filesystem, in pages of 4096 bytes.
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        // files with several links are visited once per link
+        let mut visited = BTreeSet::new();
+        let mut pages = 0;
+        self.root.visit_files(&mut |file| {
+            if visited.insert(file as *const FileNode) {
+                pages += file.resident_pages() as u64;
+            }
+            Ok(())
+        })?;
+        let (blocks, free) = match self.state.page_limit() {
+            Some(max) => (max, max.saturating_sub(self.state.allocated_pages())),
+            None => (pages, 0),
+        };
+        let files = self.state.inode_count() as u64;
+        Ok(FileSystemInfo::new(
+            self.fs_magic(),
+            data::PAGE_SIZE as u64,
+            axfs_vfs::limits::MAX_NAME_LEN as u64,
+        )
+        .with_blocks(blocks, free)
+        .with_files(files, 0))
+    }
+
+    /// Freezes the RAM filesystem.
+    ///
+    /// Writes, truncation, creation and removal fail with
+    /// [`VfsError::WouldBlock`] until [`thaw()`](VfsOps::thaw) is called.
+    /// Reads are still served, without updating access times. The
+    /// mutations already in progress are waited for, so the filesystem is
+    /// consistent once this returns.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or [`VfsError::ResourceBusy`] if the
+    /// filesystem is already frozen.
+    fn freeze(&self) -> VfsResult {
+        if !self.state.set_frozen(true) {
+            return Err(VfsError::ResourceBusy);
+        }
+        Ok(())
+    }
+
+    /// Thaws the RAM filesystem.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or [`VfsError::InvalidInput`] if the
+    /// filesystem is not frozen.
+    fn thaw(&self) -> VfsResult {
+        if !self.state.set_frozen(false) {
+            return Err(VfsError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Shuts the RAM filesystem down.
+    ///
+    /// All files are synchronized to the persistence backend, then the
+    /// filesystem is frozen so that later writes fail instead of being lost,
+    /// the open handles of its nodes are revoked, and the persistence
+    /// backend, writeback scheduler and device resolver are detached.
+    /// The teardown happens even if the synchronization fails.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the first error reported by the
+    /// backend.
+    fn shutdown(&self) -> VfsResult {
+        let synced = self.sync();
+        self.state.set_frozen(true);
+        for node in self.state.live_nodes() {
+            axfs_vfs::handle::revoke(node.as_ref());
+        }
+        if let Some((scheduler, id)) = self.state.set_writeback(None) {
+            scheduler.unregister(id);
+        }
+        self.state.set_backend(None);
+        self.state.set_device_resolver(None);
+        self.state.watches().clear();
+        synced
+    }
+
+    /// Returns the capabilities of the RAM filesystem.
+    ///
+    /// # Returns
+    ///
+    /// Case-sensitive names of up to [`MAX_NAME_LEN`] bytes, symbolic links,
+    /// hard links, lookups by inode number and watches, and
+    /// [`VfsFeatures::READ_ONLY`] while remounted read-only.
+    ///
+    /// [`MAX_NAME_LEN`]: axfs_vfs::limits::MAX_NAME_LEN
+    fn capabilities(&self) -> VfsCapabilities {
+        let mut features = VfsFeatures::CASE_SENSITIVE
+            | VfsFeatures::SYMLINK
+            | VfsFeatures::HARDLINK
+            | VfsFeatures::OPEN_BY_INO
+            | VfsFeatures::WATCH;
+        features.set(VfsFeatures::READ_ONLY, self.state.is_read_only());
+        VfsCapabilities::new(features, axfs_vfs::limits::MAX_NAME_LEN)
+    }
+
+    /// Returns the name of the filesystem type.
+    ///
+    /// # Returns
+    ///
+    /// Returns `"ramfs"`.
+    fn fs_type(&self) -> &str {
+        "ramfs"
+    }
+
+    /// Returns the magic number of the filesystem type.
+    ///
+    /// # Returns
+    ///
+    /// Returns `RAMFS_MAGIC` (`0x8584_58f6`), as the Linux ramfs.
+    fn fs_magic(&self) -> u64 {
+        0x8584_58f6
+    }
+
+    /// Returns the node with inode number `ino`.
+    ///
+    /// The root directory has number 1. Nodes are numbered in creation
+    /// order, and numbers are never reused.
+    ///
+    /// # Arguments
+    ///
+    /// * `ino` - The inode number of the node
+    ///
+    /// # Returns
+    ///
+    /// Returns a reference to the node on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if no node has number `ino`, or it has
+    /// been removed from all its directories.
+    fn open_by_ino(&self, ino: u64) -> VfsResult<VfsNodeRef> {
+        self.state.node_by_ino(ino).ok_or(VfsError::NotFound)
+    }
+
+    /// Watches the changes of the node at `path`.
+    ///
+    /// Directories report the creation ([`WatchMask::CREATE`]), removal
+    /// ([`WatchMask::DELETE`]) and renaming ([`WatchMask::MOVED_FROM`] and
+    /// [`WatchMask::MOVED_TO`]) of their entries. Files report the writes,
+    /// truncations and allocations changing their content
+    /// ([`WatchMask::MODIFY`]). Events are delivered synchronously, after
+    /// the change is done.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the node to watch
+    /// * `mask` - The changes to report
+    /// * `sink` - The receiver of the events
+    ///
+    /// # Returns
+    ///
+    /// Returns the identifier of the watch on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`VfsNodeOps::lookup`](axfs_vfs::VfsNodeOps::lookup).
+    fn subscribe(
+        &self,
+        path: &str,
+        mask: WatchMask,
+        sink: Arc<dyn WatchSink>,
+    ) -> VfsResult<WatchId> {
+        let ino = self.root_dir().lookup(path)?.get_attr()?.ino();
+        Ok(self.state.watches().add(ino, mask, sink))
+    }
+
+    /// Removes a watch.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if there is no watch `id`.
+    fn unsubscribe(&self, id: WatchId) -> VfsResult {
+        if !self.state.watches().remove(id) {
+            return Err(VfsError::InvalidInput);
+        }
+        Ok(())
+    }
+
     /// Returns the root directory of the RAM filesystem.
     ///
     /// # Returns
@@ -106,6 +730,45 @@ impl VfsOps for RamFileSystem {
     }
 }
 
+impl WritebackTarget for RamFileSystem {
+    /// Synchronizes all files to the persistence backend.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the first error reported by the backend.
+    fn writeback(&self) -> VfsResult {
+        self.sync()
+    }
+}
+
+impl PollFlush for RamFileSystem {
+    /// Synchronizes the filesystem with [`sync()`](Self::sync) if it has
+    /// been dirty for at least `older_than`.
+    ///
+    /// Ages are measured with the clock of the filesystem. This is an
+    /// alternative to [`set_writeback_scheduler()`](Self::set_writeback_scheduler)
+    /// for kernels polling each filesystem directly.
+    ///
+    /// # Returns
+    ///
+    /// Returns a [`FlushStatus`] counting the filesystem as one target on
+    /// success, or the first error reported by the backend.
+    fn poll_flush(&self, older_than: Duration) -> VfsResult<FlushStatus> {
+        let due = |since: Duration| since.saturating_add(older_than);
+        let flushed = match self.state.dirty_since() {
+            Some(since) if due(since) <= self.state.now() => {
+                self.sync()?;
+                1
+            }
+            _ => 0,
+        };
+        Ok(FlushStatus {
+            flushed,
+            next_due: self.state.dirty_since().map(due),
+        })
+    }
+}
+
 impl Default for RamFileSystem {
     /// Creates a default RAM filesystem instance.
     ///
@@ -0,0 +1,250 @@
+use core::time::Duration;
+
+use axfs_vfs::{
+    AtimePolicy, SetAttr, VfsError, VfsNodeAttr, VfsNodeFlags, VfsNodeOps, VfsNodePerm, VfsResult,
+};
+
+/// Metadata kept by every node of a RAM filesystem.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NodeMeta {
+    /// Time of last access.
+    pub atime: Duration,
+    /// Time of last modification of the content.
+    pub mtime: Duration,
+    /// Time of last status change.
+    pub ctime: Duration,
+    /// Permissions of the node.
+    pub mode: VfsNodePerm,
+    /// User ID of the owner.
+    pub uid: u32,
+    /// Group ID of the owner.
+    pub gid: u32,
+    /// Protection flags.
+    pub flags: VfsNodeFlags,
+}
+
+impl NodeMeta {
+    /// Creates the metadata of a node created at `now` with permissions
+    /// `mode`, owned by the superuser.
+    pub const fn new(now: Duration, mode: VfsNodePerm) -> Self {
+        Self {
+            atime: now,
+            mtime: now,
+            ctime: now,
+            mode,
+            uid: 0,
+            gid: 0,
+            flags: VfsNodeFlags::empty(),
+        }
+    }
+
+    /// Records an access of the content, if `policy` updates the access
+    /// time.
+    pub fn touch_access(&mut self, now: Duration, policy: AtimePolicy) {
+        if policy.needs_update(self.atime, self.mtime, self.ctime, now) {
+            self.atime = now;
+        }
+    }
+
+    /// Records a modification of the content, which is also a status change.
+    pub fn touch_modify(&mut self, now: Duration) {
+        self.mtime = now;
+        self.ctime = now;
+    }
+
+    /// Records a change of the metadata only.
+    pub fn touch_change(&mut self, now: Duration) {
+        self.ctime = now;
+    }
+
+    /// Applies the permissions, owner and times of `attr`, which is a status
+    /// change at `now`.
+    pub fn apply(&mut self, attr: &SetAttr, now: Duration) {
+        if let Some(mode) = attr.get_mode() {
+            self.mode = mode;
+        }
+        if let Some(uid) = attr.get_uid() {
+            self.uid = uid;
+        }
+        if let Some(gid) = attr.get_gid() {
+            self.gid = gid;
+        }
+        if let Some(atime) = attr.get_atime() {
+            self.atime = atime;
+        }
+        if let Some(mtime) = attr.get_mtime() {
+            self.mtime = mtime;
+        }
+        self.ctime = now;
+    }
+
+    /// Replaces the protection flags, which is a status change at `now`.
+    pub fn set_flags(&mut self, flags: VfsNodeFlags, now: Duration) {
+        self.flags = flags;
+        self.ctime = now;
+    }
+
+    /// Checks that the node can be changed.
+    ///
+    /// Returns [`VfsError::OperationNotPermitted`] if the node is immutable.
+    pub fn check_changeable(&self) -> VfsResult {
+        if self.flags.contains(VfsNodeFlags::IMMUTABLE) {
+            return Err(VfsError::OperationNotPermitted);
+        }
+        Ok(())
+    }
+
+    /// Checks that the existing content of the node can be changed.
+    ///
+    /// Returns [`VfsError::OperationNotPermitted`] if the node is immutable
+    /// or append-only.
+    pub fn check_rewritable(&self) -> VfsResult {
+        if self
+            .flags
+            .intersects(VfsNodeFlags::IMMUTABLE | VfsNodeFlags::APPEND)
+        {
+            return Err(VfsError::OperationNotPermitted);
+        }
+        Ok(())
+    }
+
+    /// Checks that the file can be opened for writing, in append mode if
+    /// `append` is set.
+    ///
+    /// Returns [`VfsError::OperationNotPermitted`] if the file is immutable,
+    /// or append-only and `append` is not set.
+    pub fn check_open_write(&self, append: bool) -> VfsResult {
+        self.check_changeable()?;
+        if self.flags.contains(VfsNodeFlags::APPEND) && !append {
+            return Err(VfsError::OperationNotPermitted);
+        }
+        Ok(())
+    }
+
+    /// Checks that a file of `size` bytes can be written at `offset`.
+    ///
+    /// Returns [`VfsError::OperationNotPermitted`] if the file is immutable,
+    /// or append-only and `offset` is before its end.
+    pub fn check_write(&self, offset: u64, size: u64) -> VfsResult {
+        self.check_changeable()?;
+        if self.flags.contains(VfsNodeFlags::APPEND) && offset < size {
+            return Err(VfsError::OperationNotPermitted);
+        }
+        Ok(())
+    }
+
+    /// Fills the metadata into the attributes `attr`.
+    pub fn fill_attr(&self, attr: VfsNodeAttr) -> VfsNodeAttr {
+        let mut attr = attr
+            .with_times(self.atime, self.mtime, self.ctime)
+            .with_owner(self.uid, self.gid);
+        attr.set_perm(self.mode);
+        attr
+    }
+}
+
+/// Checks that `node` can be removed from a directory or linked to.
+///
+/// Returns [`VfsError::OperationNotPermitted`] if the node is immutable or
+/// append-only, or [`VfsError::ResourceBusy`] if it is a mount point. Other
+/// nodes without flags can always be removed.
+pub(crate) fn check_unlinkable(node: &dyn VfsNodeOps) -> VfsResult {
+    if node.is_mount_point() {
+        return Err(VfsError::ResourceBusy);
+    }
+    let flags = node.get_flags().unwrap_or_default();
+    if flags.intersects(VfsNodeFlags::IMMUTABLE | VfsNodeFlags::APPEND) {
+        return Err(VfsError::OperationNotPermitted);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_meta_touch() {
+        let secs = Duration::from_secs;
+        let mut meta = NodeMeta::new(secs(1), VfsNodePerm::default_file());
+        meta.touch_access(secs(2), AtimePolicy::Strict);
+        assert_eq!(
+            (meta.atime, meta.mtime, meta.ctime),
+            (secs(2), secs(1), secs(1))
+        );
+        meta.touch_modify(secs(3));
+        assert_eq!(
+            (meta.atime, meta.mtime, meta.ctime),
+            (secs(2), secs(3), secs(3))
+        );
+
+        meta.touch_change(secs(4));
+        assert_eq!(
+            (meta.atime, meta.mtime, meta.ctime),
+            (secs(2), secs(3), secs(4))
+        );
+
+        let attr = meta.fill_attr(VfsNodeAttr::new_file(0, 0));
+        assert_eq!(attr.atime(), secs(2));
+        assert_eq!(attr.mtime(), secs(3));
+    }
+
+    #[test]
+    fn test_node_meta_apply() {
+        let secs = Duration::from_secs;
+        let mut meta = NodeMeta::new(secs(1), VfsNodePerm::default_file());
+        meta.apply(
+            &SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o600)),
+            secs(2),
+        );
+        assert_eq!(meta.mode.mode(), 0o600);
+        assert_eq!(
+            (meta.atime, meta.mtime, meta.ctime),
+            (secs(1), secs(1), secs(2))
+        );
+
+        meta.apply(&SetAttr::new().atime(secs(5)).mtime(secs(6)), secs(3));
+        assert_eq!(
+            (meta.atime, meta.mtime, meta.ctime),
+            (secs(5), secs(6), secs(3))
+        );
+        meta.apply(&SetAttr::new().uid(1000), secs(4));
+        assert_eq!((meta.uid, meta.gid, meta.ctime), (1000, 0, secs(4)));
+        meta.apply(&SetAttr::new().gid(100), secs(4));
+        let attr = meta.fill_attr(VfsNodeAttr::new_file(0, 0));
+        assert_eq!(attr.perm().mode(), 0o600);
+        assert_eq!((attr.uid(), attr.gid()), (1000, 100));
+    }
+
+    #[test]
+    fn test_node_meta_flags() {
+        let secs = Duration::from_secs;
+        let mut meta = NodeMeta::new(secs(1), VfsNodePerm::default_file());
+        assert!(meta.check_changeable().is_ok());
+        assert!(meta.check_rewritable().is_ok());
+        assert!(meta.check_write(0, 10).is_ok());
+
+        meta.set_flags(VfsNodeFlags::APPEND, secs(2));
+        assert_eq!(meta.ctime, secs(2));
+        assert!(meta.check_changeable().is_ok());
+        assert_eq!(
+            meta.check_rewritable(),
+            Err(VfsError::OperationNotPermitted)
+        );
+        assert_eq!(
+            meta.check_write(9, 10),
+            Err(VfsError::OperationNotPermitted)
+        );
+        assert!(meta.check_write(10, 10).is_ok());
+
+        meta.set_flags(VfsNodeFlags::IMMUTABLE, secs(3));
+        assert_eq!(
+            meta.check_changeable(),
+            Err(VfsError::OperationNotPermitted)
+        );
+        assert_eq!(
+            meta.check_write(10, 10),
+            Err(VfsError::OperationNotPermitted)
+        );
+    }
+}
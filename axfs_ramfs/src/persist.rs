@@ -0,0 +1,124 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use axfs_vfs::{RenameFlags, VfsResult};
+
+/// A write-through target for the contents of a RAM filesystem.
+///
+/// When a backend is attached with
+/// [`RamFileSystem::set_persistence_backend`](crate::RamFileSystem::set_persistence_backend),
+/// every `fsync` on a file and every [`RamFileSystem::sync`](crate::RamFileSystem::sync)
+/// pushes the ranges written since the last synchronization to it. This
+/// gives a cheap way to make a tmpfs durable (e.g. on flash) without a full
+/// on-disk filesystem.
+///
+/// Removals and renames are reported as they happen, before the filesystem
+/// changes. A file with several hard links is persisted under one of its
+/// paths only, so the backend may be told about paths it has never
+/// received, which it should ignore.
+pub trait PersistenceBackend: Send + Sync {
+    /// Persists the dirty ranges of the file at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path of the file inside the filesystem
+    /// * `size` - The current size of the file in bytes
+    /// * `dirty` - The `(offset, data)` pairs modified since the last call,
+    ///   sorted by offset and non-overlapping. It can be empty if only the
+    ///   size of the file has changed.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the data was persisted. On error, the ranges stay
+    /// dirty and will be pushed again by the next synchronization.
+    fn persist(&self, path: &str, size: u64, dirty: &[(u64, &[u8])]) -> VfsResult;
+
+    /// Removes the node at `path`, which is a file or an empty directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The absolute path of the node inside the filesystem
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the removal was persisted. On error, the node is
+    /// not removed from the filesystem.
+    fn remove(&self, path: &str) -> VfsResult;
+
+    /// Moves the node at `src_path`, and its subtree if it is a directory,
+    /// to `dst_path`.
+    ///
+    /// The node at `dst_path`, if any, is replaced, or with
+    /// [`RenameFlags::EXCHANGE`] moved to `src_path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The absolute path of the node inside the filesystem
+    /// * `dst_path` - The new absolute path of the node
+    /// * `flags` - The options of the rename
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the rename was persisted. On error, the node is
+    /// not moved in the filesystem.
+    fn rename(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult;
+}
+
+/// Inserts `range` into the sorted, non-overlapping list `ranges`, merging it
+/// with the ranges it overlaps or touches.
+pub(crate) fn add_dirty_range(ranges: &mut Vec<Range<u64>>, mut range: Range<u64>) {
+    if range.is_empty() {
+        return;
+    }
+    let first = ranges.partition_point(|r| r.end < range.start);
+    let last = ranges.partition_point(|r| r.start <= range.end);
+    if first < last {
+        range.start = range.start.min(ranges[first].start);
+        range.end = range.end.max(ranges[last - 1].end);
+    }
+    ranges.splice(first..last, [range]);
+}
+
+/// Drops the parts of `ranges` that are beyond `size`.
+pub(crate) fn clip_dirty_ranges(ranges: &mut Vec<Range<u64>>, size: u64) {
+    ranges.retain_mut(|r| {
+        r.end = r.end.min(size);
+        !r.is_empty()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_dirty_range_merge() {
+        let mut ranges = Vec::new();
+        add_dirty_range(&mut ranges, 10..20);
+        add_dirty_range(&mut ranges, 30..40);
+        assert_eq!(ranges, [10..20, 30..40]);
+
+        add_dirty_range(&mut ranges, 0..5);
+        assert_eq!(ranges, [0..5, 10..20, 30..40]);
+
+        add_dirty_range(&mut ranges, 20..30);
+        assert_eq!(ranges, [0..5, 10..40]);
+
+        add_dirty_range(&mut ranges, 3..50);
+        assert_eq!(ranges.len(), 1);
+        assert_eq!(ranges[0], 0..50);
+
+        add_dirty_range(&mut ranges, 7..7);
+        add_dirty_range(&mut ranges, 60..70);
+        assert_eq!(ranges, [0..50, 60..70]);
+    }
+
+    #[test]
+    fn test_clip_dirty_ranges() {
+        let mut ranges = vec![0..5, 10..20, 30..40];
+        clip_dirty_ranges(&mut ranges, 15);
+        assert_eq!(ranges, [0..5, 10..15]);
+        clip_dirty_ranges(&mut ranges, 0);
+        assert!(ranges.is_empty());
+    }
+}
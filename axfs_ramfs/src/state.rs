@@ -0,0 +1,425 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use core::time::Duration;
+
+use axfs_vfs::names::NamePolicy;
+use axfs_vfs::notify::{WatchEvent, WatchList, WatchMask};
+use axfs_vfs::writeback::{WritebackId, WritebackScheduler};
+use axfs_vfs::{
+    AtimePolicy, DeviceResolver, ReadDirPolicy, Umask, VfsClock, VfsContext, VfsError, VfsNodeOps,
+    VfsNodeRef, VfsResult,
+};
+use spin::{Mutex, MutexGuard, Once, RwLock, RwLockReadGuard};
+
+use crate::swap::SwapArea;
+use crate::{DirNode, PersistenceBackend};
+
+/// The generation number of the last filesystem created.
+static LAST_GENERATION: AtomicU32 = AtomicU32::new(0);
+
+/// The generation number of a filesystem, different for every filesystem
+/// created since boot.
+///
+/// Inode numbers restart from 1 in every filesystem, so a file handle
+/// exported by a filesystem is told apart from the nodes of the filesystems
+/// created later at the same place.
+struct Generation(u32);
+
+impl Default for Generation {
+    fn default() -> Self {
+        Self(LAST_GENERATION.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+}
+
+/// State shared by all nodes of a RAM filesystem.
+#[derive(Default)]
+pub(crate) struct FsState {
+    root: Once<Weak<DirNode>>,
+    backend: RwLock<Option<Arc<dyn PersistenceBackend>>>,
+    clock: RwLock<Option<Arc<dyn VfsClock>>>,
+    secure_wipe: AtomicBool,
+    frozen: AtomicBool,
+    mutations: RwLock<()>,
+    read_only: AtomicBool,
+    atime: RwLock<AtimePolicy>,
+    swap: RwLock<Option<Arc<SwapArea>>>,
+    writeback: RwLock<Option<(Arc<WritebackScheduler>, WritebackId)>>,
+    generation: Generation,
+    last_ino: AtomicU64,
+    inodes: RwLock<BTreeMap<u64, Weak<dyn VfsNodeOps>>>,
+    devices: RwLock<Option<Arc<dyn DeviceResolver>>>,
+    names: RwLock<NamePolicy>,
+    read_dir_policy: RwLock<ReadDirPolicy>,
+    umask: RwLock<Option<Umask>>,
+    dirty_since: Mutex<Option<Duration>>,
+    renames: Mutex<()>,
+    watches: WatchList,
+    /// The number of pages of file data, shared with the release hooks of
+    /// the pages freed while still mapped.
+    pages: Arc<AtomicU64>,
+    max_pages: AtomicU64,
+    /// The contents of the freed pages, for tests.
+    #[cfg(test)]
+    released: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl FsState {
+    /// Records the root directory of the filesystem.
+    pub fn set_root(&self, root: &Arc<DirNode>) {
+        self.root.call_once(|| Arc::downgrade(root));
+    }
+
+    /// Returns the root directory of the filesystem, if it is still alive.
+    pub fn root(&self) -> Option<Arc<DirNode>> {
+        self.root.get().and_then(Weak::upgrade)
+    }
+
+    /// Returns the attached persistence backend.
+    pub fn backend(&self) -> Option<Arc<dyn PersistenceBackend>> {
+        self.backend.read().clone()
+    }
+
+    /// Attaches or detaches the persistence backend.
+    pub fn set_backend(&self, backend: Option<Arc<dyn PersistenceBackend>>) {
+        *self.backend.write() = backend;
+    }
+
+    /// Returns the current time, from the filesystem clock if one is set, or
+    /// from the global clock otherwise.
+    pub fn now(&self) -> Duration {
+        match self.clock.read().as_ref() {
+            Some(clock) => clock.now(),
+            None => axfs_vfs::clock::now(),
+        }
+    }
+
+    /// Sets or clears the filesystem clock.
+    pub fn set_clock(&self, clock: Option<Arc<dyn VfsClock>>) {
+        *self.clock.write() = clock;
+    }
+
+    /// Returns the rules that the names of new nodes follow.
+    pub fn name_policy(&self) -> NamePolicy {
+        *self.names.read()
+    }
+
+    /// Sets the rules that the names of new nodes follow.
+    pub fn set_name_policy(&self, policy: NamePolicy) {
+        *self.names.write() = policy;
+    }
+
+    /// Returns when the directory streams read their entries.
+    pub fn read_dir_policy(&self) -> ReadDirPolicy {
+        *self.read_dir_policy.read()
+    }
+
+    /// Sets when the directory streams read their entries.
+    pub fn set_read_dir_policy(&self, policy: ReadDirPolicy) {
+        *self.read_dir_policy.write() = policy;
+    }
+
+    /// Returns the umask applied to the nodes created without a context.
+    pub fn umask(&self) -> Option<Umask> {
+        *self.umask.read()
+    }
+
+    /// Sets the umask applied to the nodes created without a context.
+    pub fn set_umask(&self, umask: Option<Umask>) {
+        *self.umask.write() = umask;
+    }
+
+    /// Returns the watches of the filesystem.
+    pub fn watches(&self) -> &WatchList {
+        &self.watches
+    }
+
+    /// Reports a change of the node `ino` to its watches.
+    ///
+    /// Must be called without any lock of the node held.
+    pub fn notify(&self, ino: u64, mask: WatchMask, name: Option<&str>, is_dir: bool) {
+        let event = WatchEvent {
+            mask,
+            name,
+            cookie: 0,
+            is_dir,
+        };
+        self.watches.notify(ino, &event);
+    }
+
+    /// Returns whether file contents are zeroized before being freed.
+    pub fn secure_wipe(&self) -> bool {
+        self.secure_wipe.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of pages of file data, in memory or swapped.
+    pub fn allocated_pages(&self) -> u64 {
+        self.pages.load(Ordering::Relaxed)
+    }
+
+    /// Returns the maximum number of pages of file data, if limited.
+    pub fn page_limit(&self) -> Option<u64> {
+        match self.max_pages.load(Ordering::Relaxed) {
+            0 => None,
+            max => Some(max),
+        }
+    }
+
+    /// Limits the number of pages of file data, or removes the limit.
+    ///
+    /// Pages already allocated are kept even beyond the limit. Pages removed
+    /// from a file while still mapped in memory count until the last
+    /// mapping drops them.
+    pub fn set_page_limit(&self, max_pages: Option<u64>) {
+        self.max_pages
+            .store(max_pages.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Accounts for a new page of file data.
+    ///
+    /// Returns [`VfsError::StorageFull`] if the page limit is reached.
+    pub fn charge_page(&self) -> VfsResult {
+        let max = self.page_limit().unwrap_or(u64::MAX);
+        self.pages
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |pages| {
+                (pages < max).then_some(pages + 1)
+            })
+            .map(drop)
+            .map_err(|_| VfsError::StorageFull)
+    }
+
+    /// Accounts for a freed page of file data.
+    pub fn uncharge_page(&self) {
+        self.pages.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Returns a function accounting for a freed page of file data, to run
+    /// once the last mapping of the page drops it.
+    pub fn deferred_uncharge(&self) -> impl FnOnce() + Send + 'static {
+        let pages = self.pages.clone();
+        move || {
+            pages.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Records the contents of a page being freed, for tests.
+    #[cfg(test)]
+    pub fn record_released(&self, data: &[u8]) {
+        self.released.lock().push(data.to_vec());
+    }
+
+    /// Returns the list recording the contents of the freed pages, for
+    /// tests.
+    #[cfg(test)]
+    pub fn released(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
+        self.released.clone()
+    }
+
+    /// Returns the contents of the pages freed since the previous call, for
+    /// tests.
+    #[cfg(test)]
+    pub fn take_released(&self) -> Vec<Vec<u8>> {
+        core::mem::take(&mut *self.released.lock())
+    }
+
+    /// Enables or disables zeroizing file contents before freeing them.
+    pub fn set_secure_wipe(&self, enabled: bool) {
+        self.secure_wipe.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the filesystem is frozen.
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Acquire)
+    }
+
+    /// Freezes or thaws the filesystem.
+    ///
+    /// When freezing, waits for the mutations that passed
+    /// [`check_mutable()`](Self::check_mutable) before to end. It must not
+    /// be called during a mutation, which would never end.
+    ///
+    /// Returns `false` if the filesystem was already in the requested state.
+    pub fn set_frozen(&self, frozen: bool) -> bool {
+        let changed = self.frozen.swap(frozen, Ordering::AcqRel) != frozen;
+        if frozen {
+            drop(self.mutations.write());
+        }
+        changed
+    }
+
+    /// Returns whether the filesystem is read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Acquire)
+    }
+
+    /// Makes the filesystem read-only or writable again.
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Release);
+    }
+
+    /// Returns when reads update access times.
+    pub fn atime_policy(&self) -> AtimePolicy {
+        *self.atime.read()
+    }
+
+    /// Sets when reads update access times.
+    pub fn set_atime_policy(&self, policy: AtimePolicy) {
+        *self.atime.write() = policy;
+    }
+
+    /// Returns whether reads may update access times, which they do not
+    /// while the filesystem is frozen or read-only, or with
+    /// [`AtimePolicy::Never`].
+    pub fn updates_atime(&self) -> bool {
+        !self.is_frozen() && !self.is_read_only() && self.atime_policy() != AtimePolicy::Never
+    }
+
+    /// Returns the context of the caller to check the operations called
+    /// without one against.
+    ///
+    /// This is the context of the current task with the `enforce-perms`
+    /// feature, and `None` otherwise.
+    pub fn caller(&self) -> Option<VfsContext> {
+        #[cfg(feature = "enforce-perms")]
+        return axfs_vfs::current_context();
+        #[cfg(not(feature = "enforce-perms"))]
+        return None;
+    }
+
+    /// Checks that the filesystem can be mutated, and starts a mutation.
+    ///
+    /// The filesystem is not frozen until the returned guard is dropped, so
+    /// the guard must be held until the mutation is done.
+    ///
+    /// Returns [`VfsError::PermissionDenied`] if the filesystem is
+    /// read-only, or [`VfsError::WouldBlock`] if it is frozen.
+    pub fn check_mutable(&self) -> VfsResult<RwLockReadGuard<'_, ()>> {
+        let mutation = self.mutations.read();
+        if self.is_read_only() {
+            return Err(VfsError::PermissionDenied);
+        }
+        if self.is_frozen() {
+            return Err(VfsError::WouldBlock);
+        }
+        Ok(mutation)
+    }
+
+    /// Serializes the renames between directories of the filesystem, like
+    /// `s_vfs_rename_mutex` on Linux.
+    ///
+    /// The parents of the directories do not change while the returned
+    /// guard is held, so a rename can check that it does not move a
+    /// directory into one of its descendants.
+    pub fn lock_renames(&self) -> MutexGuard<'_, ()> {
+        self.renames.lock()
+    }
+
+    /// Returns the swap area, if one is attached.
+    pub fn swap(&self) -> Option<Arc<SwapArea>> {
+        self.swap.read().clone()
+    }
+
+    /// Attaches or detaches the swap area.
+    ///
+    /// Returns [`VfsError::ResourceBusy`] if pages are still stored in the
+    /// current swap area.
+    pub fn set_swap(&self, swap: Option<Arc<SwapArea>>) -> VfsResult {
+        let mut cur = self.swap.write();
+        if cur.as_ref().is_some_and(|cur| cur.used_slots() != 0) {
+            return Err(VfsError::ResourceBusy);
+        }
+        *cur = swap;
+        Ok(())
+    }
+
+    /// Sets or clears the writeback scheduler the filesystem is registered
+    /// with, returning the previous one.
+    pub fn set_writeback(
+        &self,
+        writeback: Option<(Arc<WritebackScheduler>, WritebackId)>,
+    ) -> Option<(Arc<WritebackScheduler>, WritebackId)> {
+        core::mem::replace(&mut *self.writeback.write(), writeback)
+    }
+
+    /// Records that file data is dirty, and reports it to the writeback
+    /// scheduler, if any.
+    pub fn mark_dirty(&self) {
+        let now = self.now();
+        self.dirty_since.lock().get_or_insert(now);
+        if let Some((scheduler, id)) = self.writeback.read().as_ref() {
+            scheduler.mark_dirty(*id);
+        }
+    }
+
+    /// Returns since when file data is dirty, or `None` if the filesystem
+    /// was synchronized since the last change.
+    pub fn dirty_since(&self) -> Option<Duration> {
+        *self.dirty_since.lock()
+    }
+
+    /// Marks the filesystem clean, returning since when it was dirty.
+    pub fn take_dirty(&self) -> Option<Duration> {
+        self.dirty_since.lock().take()
+    }
+
+    /// Marks the filesystem dirty again since `since`, after a failed
+    /// synchronization.
+    pub fn restore_dirty(&self, since: Duration) {
+        let mut dirty_since = self.dirty_since.lock();
+        *dirty_since = Some(dirty_since.map_or(since, |cur| cur.min(since)));
+    }
+
+    /// Returns the generation number of all the nodes of the filesystem.
+    ///
+    /// Inode numbers are never reused within a filesystem, so the number
+    /// only tells apart the filesystems created since boot.
+    pub fn generation(&self) -> u32 {
+        self.generation.0
+    }
+
+    /// Allocates a new inode number, starting from 1 for the root directory.
+    pub fn alloc_ino(&self) -> u64 {
+        self.last_ino.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    /// Makes `node` reachable by its inode number `ino`.
+    pub fn register_ino(&self, ino: u64, node: &VfsNodeRef) {
+        self.inodes.write().insert(ino, Arc::downgrade(node));
+    }
+
+    /// Makes the node with inode number `ino` unreachable by number.
+    pub fn unregister_ino(&self, ino: u64) {
+        self.inodes.write().remove(&ino);
+    }
+
+    /// Returns the number of nodes reachable by inode number.
+    pub fn inode_count(&self) -> usize {
+        self.inodes.read().len()
+    }
+
+    /// Returns the node with inode number `ino`, if it is registered and
+    /// still alive.
+    pub fn node_by_ino(&self, ino: u64) -> Option<VfsNodeRef> {
+        self.inodes.read().get(&ino)?.upgrade()
+    }
+
+    /// Returns the nodes reachable by inode number that are still alive.
+    pub fn live_nodes(&self) -> Vec<VfsNodeRef> {
+        self.inodes
+            .read()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    /// Returns the resolver of device numbers, if one is set.
+    pub fn device_resolver(&self) -> Option<Arc<dyn DeviceResolver>> {
+        self.devices.read().clone()
+    }
+
+    /// Sets or clears the resolver of device numbers.
+    pub fn set_device_resolver(&self, resolver: Option<Arc<dyn DeviceResolver>>) {
+        *self.devices.write() = resolver;
+    }
+}
@@ -0,0 +1,154 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{BlockDeviceOps, VfsError, VfsResult};
+use spin::Mutex;
+
+use crate::data::PAGE_SIZE;
+
+/// A swap area on a block device, divided into page-sized slots.
+pub(crate) struct SwapArea {
+    dev: Arc<dyn BlockDeviceOps>,
+    blocks_per_slot: u64,
+    /// Whether each slot is in use.
+    used: Mutex<Vec<bool>>,
+}
+
+impl SwapArea {
+    /// Creates a swap area covering the whole device `dev`.
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the page size is not a multiple
+    /// of the block size of the device.
+    pub fn new(dev: Arc<dyn BlockDeviceOps>) -> VfsResult<Self> {
+        let block_size = dev.block_size();
+        if block_size == 0 || !PAGE_SIZE.is_multiple_of(block_size) {
+            return Err(VfsError::InvalidInput);
+        }
+        let blocks_per_slot = (PAGE_SIZE / block_size) as u64;
+        let num_slots = (dev.num_blocks() / blocks_per_slot) as usize;
+        Ok(Self {
+            dev,
+            blocks_per_slot,
+            used: Mutex::new(vec![false; num_slots]),
+        })
+    }
+
+    /// Returns the number of slots in use.
+    pub fn used_slots(&self) -> usize {
+        self.used.lock().iter().filter(|&&used| used).count()
+    }
+
+    /// Writes a page to a free slot.
+    ///
+    /// Returns the slot, or [`VfsError::StorageFull`] if all slots are used.
+    pub fn swap_out(&self, page: &[u8]) -> VfsResult<u64> {
+        let slot = {
+            let mut used = self.used.lock();
+            let slot = used
+                .iter()
+                .position(|&used| !used)
+                .ok_or(VfsError::StorageFull)?;
+            used[slot] = true;
+            slot as u64
+        };
+        if let Err(e) = self.dev.write_block(slot * self.blocks_per_slot, page) {
+            self.used.lock()[slot as usize] = false;
+            return Err(e);
+        }
+        Ok(slot)
+    }
+
+    /// Reads the page stored in `slot`.
+    pub fn read(&self, slot: u64, page: &mut [u8]) -> VfsResult {
+        self.dev.read_block(slot * self.blocks_per_slot, page)
+    }
+
+    /// Frees `slot`, zeroizing it first if `wipe` is set.
+    pub fn free(&self, slot: u64, wipe: bool) {
+        if wipe {
+            let zeros = [0; PAGE_SIZE];
+            if let Err(e) = self.dev.write_block(slot * self.blocks_per_slot, &zeros) {
+                log::warn!("failed to wipe swap slot {slot}: {e:?}");
+            }
+        }
+        self.used.lock()[slot as usize] = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A RAM-backed block device with 512-byte blocks.
+    struct RamDisk(Mutex<Vec<u8>>);
+
+    impl BlockDeviceOps for RamDisk {
+        fn block_size(&self) -> usize {
+            512
+        }
+
+        fn num_blocks(&self) -> u64 {
+            self.0.lock().len() as u64 / 512
+        }
+
+        fn read_block(&self, block_id: u64, buf: &mut [u8]) -> VfsResult {
+            let start = block_id as usize * 512;
+            buf.copy_from_slice(&self.0.lock()[start..start + buf.len()]);
+            Ok(())
+        }
+
+        fn write_block(&self, block_id: u64, buf: &[u8]) -> VfsResult {
+            let start = block_id as usize * 512;
+            self.0.lock()[start..start + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_swap_area_slots() {
+        let disk = Arc::new(RamDisk(Mutex::new(vec![0; PAGE_SIZE * 2 + 512])));
+        let swap = SwapArea::new(disk.clone()).unwrap();
+        let page = [7; PAGE_SIZE];
+        assert_eq!(swap.swap_out(&page), Ok(0));
+        assert_eq!(swap.swap_out(&page), Ok(1));
+        assert_eq!(swap.swap_out(&page), Err(VfsError::StorageFull));
+        assert_eq!(swap.used_slots(), 2);
+
+        let mut buf = [0; PAGE_SIZE];
+        swap.read(1, &mut buf).unwrap();
+        assert_eq!(buf, page);
+
+        swap.free(0, true);
+        assert!(disk.0.lock()[..PAGE_SIZE].iter().all(|&b| b == 0));
+        assert_eq!(swap.swap_out(&page), Ok(0));
+    }
+
+    #[test]
+    fn test_swap_area_bad_block_size() {
+        struct OddDisk;
+
+        impl BlockDeviceOps for OddDisk {
+            fn block_size(&self) -> usize {
+                1000
+            }
+
+            fn num_blocks(&self) -> u64 {
+                16
+            }
+
+            fn read_block(&self, _block_id: u64, _buf: &mut [u8]) -> VfsResult {
+                Ok(())
+            }
+
+            fn write_block(&self, _block_id: u64, _buf: &[u8]) -> VfsResult {
+                Ok(())
+            }
+        }
+
+        assert!(matches!(
+            SwapArea::new(Arc::new(OddDisk)),
+            Err(VfsError::InvalidInput)
+        ));
+    }
+}
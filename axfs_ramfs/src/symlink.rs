@@ -0,0 +1,147 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+
+use axfs_vfs::handle::OpenState;
+use axfs_vfs::{VfsNodeAttr, VfsNodePerm, VfsNodeType, VfsResult};
+use spin::Mutex;
+
+use crate::meta::NodeMeta;
+use crate::state::FsState;
+
+/// The symbolic link node in RAM filesystem.
+///
+/// The target is stored as given and never resolved by the filesystem:
+/// path resolution is left to the caller, which reads it with
+/// [`read_link()`](axfs_vfs::VfsNodeOps::read_link).
+///
+/// # Fields
+///
+/// - `fs` - The state shared with the other nodes of the filesystem
+/// - `ino` - The inode number of the link
+/// - `target` - The path the link points to
+/// - `meta` - The timestamps of the link
+/// - `opens` - The open handles of the link
+pub struct SymlinkNode {
+    fs: Arc<FsState>,
+    ino: u64,
+    target: String,
+    meta: Mutex<NodeMeta>,
+    opens: OpenState,
+}
+
+impl SymlinkNode {
+    /// Creates a new symbolic link node.
+    ///
+    /// # Arguments
+    ///
+    /// * `fs` - The state of the filesystem the link belongs to
+    /// * `target` - The path the link points to
+    ///
+    /// # Returns
+    ///
+    /// A new symbolic link node.
+    pub(super) fn new(fs: Arc<FsState>, target: &str) -> Self {
+        let meta = NodeMeta::new(fs.now(), VfsNodePerm::from_bits_truncate(0o777));
+        Self {
+            ino: fs.alloc_ino(),
+            fs,
+            target: target.into(),
+            meta: Mutex::new(meta),
+            opens: OpenState::new(),
+        }
+    }
+
+    /// Returns the inode number of the link.
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the path the link points to.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+}
+
+impl Drop for SymlinkNode {
+    fn drop(&mut self) {
+        self.fs.unregister_ino(self.ino);
+    }
+}
+
+impl axfs_vfs::VfsFileNodeOps for SymlinkNode {
+    /// Returns the state counting the open handles of the link.
+    fn open_state(&self) -> Option<&OpenState> {
+        Some(&self.opens)
+    }
+
+    /// Returns the attributes of the link.
+    ///
+    /// # Returns
+    ///
+    /// Returns the attributes of a symbolic link whose size is the length of
+    /// its target. Links are always accessible (`0o777`), permissions are
+    /// checked on their target.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = VfsNodeAttr::builder()
+            .ty(VfsNodeType::SymLink)
+            .perm(VfsNodePerm::from_bits_truncate(0o777))
+            .size(self.target.len() as u64)
+            .ino(self.ino)
+            .build();
+        Ok(self.meta.lock().fill_attr(attr))
+    }
+
+    /// Returns the generation of the filesystem.
+    fn generation(&self) -> u32 {
+        self.fs.generation()
+    }
+
+    /// Reads the target of the link into `buf`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The buffer to read the target into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes read, the target is truncated if `buf` is
+    /// too small.
+    fn read_link(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        let len = self.target.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.target.as_bytes()[..len]);
+        if self.fs.updates_atime() {
+            self.meta
+                .lock()
+                .touch_access(self.fs.now(), self.fs.atime_policy());
+        }
+        Ok(len)
+    }
+}
+
+axfs_vfs::impl_vfs_file_node!(SymlinkNode);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axfs_vfs::VfsNodeOps;
+
+    #[test]
+    fn test_symlink_node() {
+        let link = SymlinkNode::new(Arc::new(FsState::default()), "../target");
+        assert_eq!(link.target(), "../target");
+        let attr = link.get_attr().unwrap();
+        assert_eq!(attr.file_type(), VfsNodeType::SymLink);
+        assert_eq!(attr.size(), 9);
+
+        let mut buf = [0; 16];
+        assert_eq!(link.read_link(&mut buf), Ok(9));
+        assert_eq!(&buf[..9], b"../target");
+        let mut short = [0; 4];
+        assert_eq!(link.read_link(&mut short), Ok(4));
+        assert_eq!(&short, b"../t");
+        assert_eq!(
+            link.read_at(0, &mut buf),
+            Err(axfs_vfs::VfsError::InvalidInput)
+        );
+    }
+}
@@ -0,0 +1,312 @@
+//! Helpers for tests of code using RAM filesystems.
+//!
+//! This module requires the `test-util` feature. A [`Fixture`] describes a
+//! tree of directories, files and symbolic links, which is built as a new
+//! [`RamFileSystem`], or compared with the tree of any filesystem by
+//! [`assert_tree_eq!`](crate::assert_tree_eq).
+//!
+//! # Examples
+//!
+//! ```
+//! use axfs_ramfs::assert_tree_eq;
+//! use axfs_ramfs::test_util::fixture;
+//! use axfs_vfs::{VfsNodeType, VfsOps};
+//!
+//! let fs = fixture()
+//!     .file("etc/passwd", b"root:x:0:0::/root:/bin/sh\n")
+//!     .dir("var/log")
+//!     .build_ramfs();
+//! fs.root_dir().create("var/log/boot.log", VfsNodeType::File).unwrap();
+//!
+//! let expected = fixture()
+//!     .file("etc/passwd", b"root:x:0:0::/root:/bin/sh\n")
+//!     .file("var/log/boot.log", b"");
+//! assert_tree_eq!(fs, expected);
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use axfs_vfs::{VfsDirEntry, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+
+use crate::RamFileSystem;
+
+/// A node of a [`Fixture`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum TreeEntry {
+    /// A directory.
+    Dir,
+    /// A regular file, with its content.
+    File(Vec<u8>),
+    /// A symbolic link, with its target.
+    Symlink(String),
+    /// A node of another type, such as a device, compared by type only.
+    Other(VfsNodeType),
+}
+
+/// Shows the content of files as escaped text, to keep failed assertions
+/// readable.
+impl fmt::Debug for TreeEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dir => f.write_str("Dir"),
+            Self::File(content) => write!(f, "File(b\"{}\")", content.escape_ascii()),
+            Self::Symlink(target) => write!(f, "Symlink({target:?})"),
+            Self::Other(ty) => write!(f, "Other({ty:?})"),
+        }
+    }
+}
+
+/// A tree of nodes, keyed by their path relative to the root directory.
+///
+/// The parent directories of the nodes are part of the tree, whether they
+/// are added explicitly or not.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fixture {
+    entries: BTreeMap<String, TreeEntry>,
+}
+
+/// Creates an empty [`Fixture`].
+pub fn fixture() -> Fixture {
+    Fixture::default()
+}
+
+impl Fixture {
+    /// Adds a directory, and its parents.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the directory, relative to the root
+    pub fn dir(self, path: &str) -> Self {
+        self.with(path, TreeEntry::Dir)
+    }
+
+    /// Adds a regular file, and its parent directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, relative to the root
+    /// * `content` - The content of the file
+    pub fn file(self, path: &str, content: impl AsRef<[u8]>) -> Self {
+        self.with(path, TreeEntry::File(content.as_ref().to_vec()))
+    }
+
+    /// Adds a symbolic link, and its parent directories.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the link, relative to the root
+    /// * `target` - The target of the link
+    pub fn symlink(self, path: &str, target: &str) -> Self {
+        self.with(path, TreeEntry::Symlink(target.to_string()))
+    }
+
+    /// Adds the node `entry` at `path`, replacing any node there.
+    fn with(mut self, path: &str, entry: TreeEntry) -> Self {
+        let path: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+        assert!(!path.is_empty(), "a fixture node needs a name");
+        for end in 1..path.len() {
+            self.entries
+                .entry(path[..end].join("/"))
+                .or_insert(TreeEntry::Dir);
+        }
+        self.entries.insert(path.join("/"), entry);
+        self
+    }
+
+    /// Returns the nodes of the tree, keyed by path.
+    pub fn entries(&self) -> &BTreeMap<String, TreeEntry> {
+        &self.entries
+    }
+
+    /// Creates the nodes of the tree in the directory `root`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the node operations, such as
+    /// [`VfsError::AlreadyExists`](axfs_vfs::VfsError::AlreadyExists) if a
+    /// node exists already.
+    pub fn populate(&self, root: &VfsNodeRef) -> VfsResult {
+        // parents sort before their children
+        for (path, entry) in &self.entries {
+            match entry {
+                TreeEntry::Dir => root.create(path, VfsNodeType::Dir)?,
+                TreeEntry::File(content) => {
+                    root.create(path, VfsNodeType::File)?;
+                    let file = root.clone().lookup(path)?;
+                    let written = file.write_at(0, content)?;
+                    assert_eq!(written, content.len(), "short write to {path}");
+                }
+                TreeEntry::Symlink(target) => root.create_symlink(path, target)?,
+                TreeEntry::Other(ty) => root.create(path, *ty)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds a new RAM filesystem holding the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a node cannot be created, such as for
+    /// [`TreeEntry::Other`] nodes.
+    pub fn build_ramfs(&self) -> RamFileSystem {
+        let fs = RamFileSystem::new();
+        if let Err(e) = self.populate(&fs.root_dir()) {
+            panic!("cannot build the fixture: {e:?}");
+        }
+        fs
+    }
+
+    /// Reads the tree of the directory `root`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the tree, without `root` itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the node operations.
+    pub fn from_dir(root: &VfsNodeRef) -> VfsResult<Self> {
+        let mut tree = Self::default();
+        tree.read_dir(root, "")?;
+        Ok(tree)
+    }
+
+    /// Reads the tree of the root directory of `fs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the node operations.
+    pub fn from_fs(fs: &dyn VfsOps) -> VfsResult<Self> {
+        Self::from_dir(&fs.root_dir())
+    }
+
+    /// Adds the nodes of the directory `dir`, whose path is `prefix`.
+    fn read_dir(&mut self, dir: &VfsNodeRef, prefix: &str) -> VfsResult {
+        let mut dirents: [VfsDirEntry; 16] = core::array::from_fn(|_| VfsDirEntry::default());
+        let mut start = 0;
+        loop {
+            let n = dir.read_dir(start, &mut dirents)?;
+            if n == 0 {
+                return Ok(());
+            }
+            start += n;
+            for dirent in &dirents[..n] {
+                let Ok(name) = core::str::from_utf8(dirent.name_as_bytes()) else {
+                    continue;
+                };
+                if name == "." || name == ".." {
+                    continue;
+                }
+                let path = match prefix {
+                    "" => name.to_string(),
+                    _ => alloc::format!("{prefix}/{name}"),
+                };
+                let node = dir.clone().lookup(name)?;
+                let entry = read_entry(&node)?;
+                if entry == TreeEntry::Dir {
+                    self.read_dir(&node, &path)?;
+                }
+                self.entries.insert(path, entry);
+            }
+        }
+    }
+}
+
+/// Reads a node as a [`TreeEntry`], without the children of directories.
+fn read_entry(node: &VfsNodeRef) -> VfsResult<TreeEntry> {
+    let attr = node.get_attr()?;
+    Ok(match attr.file_type() {
+        VfsNodeType::Dir => TreeEntry::Dir,
+        VfsNodeType::File => {
+            let mut content = vec![0; attr.size() as usize];
+            let mut read = 0;
+            while read < content.len() {
+                match node.read_at(read as u64, &mut content[read..])? {
+                    0 => break,
+                    n => read += n,
+                }
+            }
+            content.truncate(read);
+            TreeEntry::File(content)
+        }
+        VfsNodeType::SymLink => {
+            let mut target = vec![0; axfs_vfs::limits::MAX_PATH];
+            let len = node.read_link(&mut target)?;
+            TreeEntry::Symlink(String::from_utf8_lossy(&target[..len]).into_owned())
+        }
+        ty => TreeEntry::Other(ty),
+    })
+}
+
+/// Asserts that the tree of a filesystem equals a [`Fixture`].
+///
+/// The first argument is a filesystem implementing
+/// [`VfsOps`](axfs_vfs::VfsOps), the second the expected
+/// [`Fixture`](crate::test_util::Fixture). On failure, the panic message
+/// shows both trees, like [`assert_eq!`].
+///
+/// Requires the `test-util` feature.
+#[macro_export]
+macro_rules! assert_tree_eq {
+    ($fs:expr, $expected:expr $(,)?) => {
+        match $crate::test_util::Fixture::from_fs(&$fs) {
+            Ok(actual) => assert_eq!(actual, $expected, "the trees differ"),
+            Err(e) => panic!("cannot read the tree: {e:?}"),
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axfs_vfs::VfsError;
+
+    #[test]
+    fn test_fixture_parents() {
+        let tree = fixture().file("/a/b/c", "x").dir("a/d/").symlink("l", "a");
+        let paths: Vec<_> = tree.entries().keys().map(String::as_str).collect();
+        assert_eq!(paths, ["a", "a/b", "a/b/c", "a/d", "l"]);
+        assert_eq!(tree.entries()["a/b"], TreeEntry::Dir);
+        // replacing a node keeps its parents
+        let tree = tree.file("a/b", "y");
+        assert_eq!(tree.entries()["a/b"], TreeEntry::File(b"y".to_vec()));
+        assert_eq!(
+            alloc::format!("{:?}", tree.entries()["a/b"]),
+            "File(b\"y\")"
+        );
+    }
+
+    #[test]
+    fn test_build_and_read_back() {
+        let tree = fixture()
+            .file("etc/passwd", b"root:x:0:0\n")
+            .file("big", [7; 10000])
+            .dir("var/log")
+            .symlink("bin", "usr/bin");
+        let fs = tree.build_ramfs();
+        assert_tree_eq!(fs, tree);
+
+        fs.root_dir().remove("var/log").unwrap();
+        let actual = Fixture::from_fs(&fs).unwrap();
+        assert_ne!(actual, tree);
+        assert_eq!(actual, tree.clone().dir("var").with_removed("var/log"));
+
+        assert_eq!(tree.populate(&fs.root_dir()), Err(VfsError::AlreadyExists));
+    }
+
+    impl Fixture {
+        fn with_removed(mut self, path: &str) -> Self {
+            self.entries.remove(path);
+            self
+        }
+    }
+}
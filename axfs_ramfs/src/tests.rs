@@ -1,6 +1,6 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use axfs_vfs::{VfsError, VfsNodeType, VfsResult};
+use axfs_vfs::{RenameFlags, VfsError, VfsNodeType, VfsResult};
 
 use crate::*;
 
@@ -134,3 +134,413 @@ fn test_ramfs() {
     assert_eq!(root.remove("./foo"), Ok(()));
     assert!(ramfs.root_dir_node().get_entries().is_empty());
 }
+
+/// The arguments of a [`PersistenceBackend::persist`] call.
+type PersistCall = (String, u64, Vec<(u64, Vec<u8>)>);
+
+/// A persistence backend recording every call it receives.
+///
+/// The removals and renames are recorded in `moves`, as the source path and
+/// the destination path if any.
+#[derive(Default)]
+struct RecordingBackend {
+    calls: std::sync::Mutex<Vec<PersistCall>>,
+    moves: std::sync::Mutex<Vec<(String, Option<String>)>>,
+}
+
+impl PersistenceBackend for RecordingBackend {
+    fn persist(&self, path: &str, size: u64, dirty: &[(u64, &[u8])]) -> VfsResult {
+        let dirty = dirty.iter().map(|(off, data)| (*off, data.to_vec()));
+        self.calls
+            .lock()
+            .unwrap()
+            .push((path.into(), size, dirty.collect()));
+        Ok(())
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.moves.lock().unwrap().push((path.into(), None));
+        Ok(())
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str, _flags: RenameFlags) -> VfsResult {
+        self.moves
+            .lock()
+            .unwrap()
+            .push((src_path.into(), Some(dst_path.into())));
+        Ok(())
+    }
+}
+
+#[test]
+fn test_persistence_backend() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("foo", VfsNodeType::Dir).unwrap();
+    root.create("foo/f1", VfsNodeType::File).unwrap();
+    root.create("f2", VfsNodeType::File).unwrap();
+
+    let f1 = root.clone().lookup("foo/f1").unwrap();
+    f1.write_at(0, b"hello").unwrap();
+    // no backend attached yet: fsync is unsupported and the data stays dirty
+    assert_eq!(f1.fsync(), Err(VfsError::InvalidInput));
+
+    let backend = Arc::new(RecordingBackend::default());
+    ramfs.set_persistence_backend(Some(backend.clone()));
+    f1.write_at(10, b"world").unwrap();
+    f1.fsync().unwrap();
+    assert_eq!(
+        backend.calls.lock().unwrap().pop(),
+        Some((
+            "/foo/f1".into(),
+            15,
+            vec![(0, b"hello".to_vec()), (10, b"world".to_vec())]
+        ))
+    );
+
+    // fsync_data pushes the data and the size too
+    f1.write_at(0, b"H").unwrap();
+    f1.fsync_data().unwrap();
+    assert_eq!(
+        backend.calls.lock().unwrap().pop(),
+        Some(("/foo/f1".into(), 15, vec![(0, b"H".to_vec())]))
+    );
+
+    // clean files are not pushed again
+    f1.fsync().unwrap();
+    f1.fsync_data().unwrap();
+    ramfs.sync().unwrap();
+    assert!(backend.calls.lock().unwrap().is_empty());
+
+    // a size change alone is reported with no dirty ranges
+    f1.truncate(12).unwrap();
+    let f2 = root.lookup("f2").unwrap();
+    f2.write_at(0, b"f2").unwrap();
+    ramfs.sync().unwrap();
+    let mut calls = backend.calls.lock().unwrap().clone();
+    calls.sort();
+    assert_eq!(
+        calls,
+        [
+            ("/f2".into(), 2, vec![(0, b"f2".to_vec())]),
+            ("/foo/f1".into(), 12, vec![]),
+        ]
+    );
+}
+
+#[test]
+fn test_persistence_backend_moves() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    let backend = Arc::new(RecordingBackend::default());
+    ramfs.set_persistence_backend(Some(backend.clone()));
+    root.create("a", VfsNodeType::Dir).unwrap();
+    root.create("b", VfsNodeType::Dir).unwrap();
+    root.create("a/f", VfsNodeType::File).unwrap();
+    let f = root.clone().lookup("a/f").unwrap();
+    f.write_at(0, b"data").unwrap();
+
+    // files are persisted under their new path once their directory moved
+    root.rename("a", "b/c").unwrap();
+    f.fsync().unwrap();
+    assert_eq!(
+        backend.calls.lock().unwrap().pop(),
+        Some(("/b/c/f".into(), 4, vec![(0, b"data".to_vec())]))
+    );
+
+    root.rename("b/c/f", "g").unwrap();
+    root.create("b/c/h", VfsNodeType::File).unwrap();
+    root.rename_flags("g", "b/c/h", RenameFlags::EXCHANGE)
+        .unwrap();
+    root.remove("g").unwrap();
+    root.remove("b/c").unwrap_err();
+    root.remove("b/c/f").unwrap_err();
+    assert_eq!(
+        *backend.moves.lock().unwrap(),
+        [
+            ("/a".into(), Some("/b/c".into())),
+            ("/b/c/f".into(), Some("/g".into())),
+            ("/g".into(), Some("/b/c/h".into())),
+            ("/g".into(), None),
+        ]
+    );
+
+    // removing the link a file is persisted under pushes it whole to the
+    // next one
+    root.link("b/c/h", "l").unwrap();
+    f.fsync().unwrap();
+    root.remove("b/c/h").unwrap();
+    f.fsync().unwrap();
+    assert_eq!(
+        backend.calls.lock().unwrap().pop(),
+        Some(("/l".into(), 4, vec![(0, b"data".to_vec())]))
+    );
+    assert_eq!(
+        backend.moves.lock().unwrap().pop(),
+        Some(("/b/c/h".into(), None))
+    );
+}
+
+#[test]
+fn test_unlinked_file_freed_on_last_close() {
+    use axfs_vfs::handle::VfsFileHandle;
+
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("tmp", VfsNodeType::File).unwrap();
+    let node = root.clone().lookup("tmp").unwrap();
+    let h1 = VfsFileHandle::open(node.clone()).unwrap();
+    let h2 = VfsFileHandle::open(node.clone()).unwrap();
+    h1.node().write_at(0, b"scratch").unwrap();
+
+    // the data stays readable through open handles after unlink
+    root.remove("tmp").unwrap();
+    h1.close().unwrap();
+    let mut buf = [0; 7];
+    assert_eq!(h2.node().read_at(0, &mut buf), Ok(7));
+    assert_eq!(&buf, b"scratch");
+
+    drop(h2);
+    assert_eq!(node.get_attr().unwrap().size(), 0);
+}
+
+#[test]
+fn test_unlinked_file_kept_open_through_wrappers() {
+    use axfs_vfs::handle::{open_count, VfsFileHandle};
+    use axfs_vfs::metrics::MeteredFs;
+    use axfs_vfs::VfsOps;
+
+    let fs = MeteredFs::new(RamFileSystem::new());
+    let root = fs.root_dir();
+    root.create("tmp", VfsNodeType::File).unwrap();
+    // every lookup returns a new wrapper of the same file
+    let h1 = VfsFileHandle::open(root.clone().lookup("tmp").unwrap()).unwrap();
+    let h2 = VfsFileHandle::open(root.clone().lookup("tmp").unwrap()).unwrap();
+    assert_eq!(h1.open_count(), 2);
+    h1.write(b"scratch").unwrap();
+
+    root.remove("tmp").unwrap();
+    h1.close().unwrap();
+    let mut buf = [0; 7];
+    assert_eq!(h2.read(&mut buf), Ok(7));
+    assert_eq!(&buf, b"scratch");
+
+    let node = h2.node().clone();
+    drop(h2);
+    assert_eq!(open_count(node.as_ref()), 0);
+    assert_eq!(node.get_attr().unwrap().size(), 0);
+}
+
+/// A RAM-backed block device with 512-byte blocks.
+struct RamDisk(Mutex<Vec<u8>>);
+
+impl axfs_vfs::BlockDeviceOps for RamDisk {
+    fn block_size(&self) -> usize {
+        512
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.0.lock().unwrap().len() as u64 / 512
+    }
+
+    fn read_block(&self, block_id: u64, buf: &mut [u8]) -> VfsResult {
+        let start = block_id as usize * 512;
+        buf.copy_from_slice(&self.0.lock().unwrap()[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn write_block(&self, block_id: u64, buf: &[u8]) -> VfsResult {
+        let start = block_id as usize * 512;
+        self.0.lock().unwrap()[start..start + buf.len()].copy_from_slice(buf);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_swap_cold_pages() {
+    let ramfs = RamFileSystem::new();
+    // no swap device: nothing is evicted
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(0));
+
+    let disk = Arc::new(RamDisk(Mutex::new(vec![0; 4096 * 4])));
+    ramfs.set_swap_device(Some(disk)).unwrap();
+    let root = ramfs.root_dir();
+    root.create("big", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("big").unwrap();
+    let content: Vec<u8> = (0..4096 * 3).map(|i| (i % 251) as u8).collect();
+    file.write_at(0, &content).unwrap();
+
+    // the pages were just written, the first pass only ages them
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(0));
+    assert_eq!(ramfs.evict_cold_pages(2), Ok(2));
+    assert_eq!(ramfs.swapped_pages(), 2);
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(1));
+    assert_eq!(ramfs.swapped_pages(), 3);
+
+    // the swap device cannot be detached while in use
+    assert_eq!(ramfs.set_swap_device(None), Err(VfsError::ResourceBusy));
+
+    // reading faults the pages back in
+    let mut buf = vec![0; content.len()];
+    assert_eq!(file.read_at(0, &mut buf), Ok(content.len()));
+    assert_eq!(buf, content);
+    assert_eq!(ramfs.swapped_pages(), 0);
+
+    // removing a file frees its slots
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(0));
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(3));
+    drop(file);
+    root.remove("big").unwrap();
+    assert_eq!(ramfs.swapped_pages(), 0);
+    ramfs.set_swap_device(None).unwrap();
+}
+
+#[test]
+fn test_readahead_faults_in() {
+    let ramfs = RamFileSystem::new();
+    let disk = Arc::new(RamDisk(Mutex::new(vec![0; 4096 * 4])));
+    ramfs.set_swap_device(Some(disk)).unwrap();
+    let root = ramfs.root_dir();
+    root.create("big", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("big").unwrap();
+    file.write_at(0, &[7; 4096 * 3]).unwrap();
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(0));
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(3));
+
+    // only the pages of the range are prefetched
+    file.readahead(4096, 10).unwrap();
+    assert_eq!(ramfs.swapped_pages(), 2);
+    // ranges past the end are ignored
+    file.readahead(4096 * 8, 0).unwrap();
+    assert_eq!(ramfs.swapped_pages(), 2);
+    file.readahead(0, 0).unwrap();
+    assert_eq!(ramfs.swapped_pages(), 0);
+}
+
+#[test]
+fn test_mapped_pages_stay_resident() {
+    let ramfs = RamFileSystem::new();
+    let disk = Arc::new(RamDisk(Mutex::new(vec![0; 4096 * 4])));
+    ramfs.set_swap_device(Some(disk)).unwrap();
+    let root = ramfs.root_dir();
+    root.create("big", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("big").unwrap();
+    file.write_at(0, &[7; 4096 * 2]).unwrap();
+    let page = file.get_page(4096).unwrap();
+
+    // the mapped page is skipped, without being aged
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(0));
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(1));
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(0));
+    drop(page);
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(0));
+    assert_eq!(ramfs.evict_cold_pages(16), Ok(1));
+    assert_eq!(ramfs.swapped_pages(), 2);
+}
+
+#[test]
+fn test_writeback_scheduler() {
+    use axfs_vfs::writeback::WritebackScheduler;
+    use core::time::Duration;
+
+    let ramfs = Arc::new(RamFileSystem::new());
+    let scheduler = Arc::new(WritebackScheduler::new());
+    ramfs.set_writeback_scheduler(Some(scheduler.clone()));
+    let backend = Arc::new(RecordingBackend::default());
+    ramfs.set_persistence_backend(Some(backend.clone()));
+    assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(1));
+
+    // writes mark the filesystem dirty, and the scheduler flushes them
+    let root = ramfs.root_dir();
+    root.create("f", VfsNodeType::File).unwrap();
+    root.lookup("f").unwrap().write_at(0, b"data").unwrap();
+    assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(1));
+    assert_eq!(
+        backend.calls.lock().unwrap().pop(),
+        Some(("/f".into(), 4, vec![(0, b"data".to_vec())]))
+    );
+    assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(0));
+
+    ramfs.set_writeback_scheduler(None);
+    ramfs.sync().unwrap();
+    assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(0));
+}
+
+#[test]
+fn test_poll_flush() {
+    use axfs_vfs::clock::ManualClock;
+    use axfs_vfs::writeback::{FlushStatus, PollFlush};
+    use core::time::Duration;
+
+    let clock = Arc::new(ManualClock::new(Duration::from_secs(10)));
+    let ramfs = RamFileSystem::with_clock(clock.clone());
+    let backend = Arc::new(RecordingBackend::default());
+    ramfs.set_persistence_backend(Some(backend.clone()));
+    ramfs.sync().unwrap();
+    let expire = Duration::from_secs(5);
+    assert_eq!(ramfs.poll_flush(expire), Ok(FlushStatus::default()));
+
+    let root = ramfs.root_dir();
+    root.create("f", VfsNodeType::File).unwrap();
+    let file = root.lookup("f").unwrap();
+    file.write_at(0, b"data").unwrap();
+    assert_eq!(ramfs.dirty_since(), Some(Duration::from_secs(10)));
+
+    // too young to be flushed
+    clock.advance(Duration::from_secs(3));
+    file.write_at(4, b"more").unwrap();
+    assert_eq!(
+        ramfs.poll_flush(expire),
+        Ok(FlushStatus {
+            flushed: 0,
+            next_due: Some(Duration::from_secs(15)),
+        })
+    );
+    assert!(backend.calls.lock().unwrap().is_empty());
+
+    clock.advance(Duration::from_secs(2));
+    assert_eq!(
+        ramfs.poll_flush(expire),
+        Ok(FlushStatus {
+            flushed: 1,
+            next_due: None,
+        })
+    );
+    assert_eq!(backend.calls.lock().unwrap().len(), 1);
+    assert_eq!(ramfs.dirty_since(), None);
+
+    // an explicit sync cleans the filesystem too
+    file.write_at(0, b"x").unwrap();
+    ramfs.sync().unwrap();
+    assert_eq!(ramfs.poll_flush(Duration::ZERO), Ok(FlushStatus::default()));
+}
+
+#[test]
+fn test_shutdown() {
+    use axfs_vfs::handle::VfsFileHandle;
+    use axfs_vfs::writeback::WritebackScheduler;
+    use core::time::Duration;
+
+    let ramfs = Arc::new(RamFileSystem::new());
+    let scheduler = Arc::new(WritebackScheduler::new());
+    ramfs.set_writeback_scheduler(Some(scheduler.clone()));
+    let backend = Arc::new(RecordingBackend::default());
+    ramfs.set_persistence_backend(Some(backend.clone()));
+    let root = ramfs.root_dir();
+    root.create("f", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("f").unwrap();
+    let handle = VfsFileHandle::open(file.clone()).unwrap();
+    handle.write(b"data").unwrap();
+
+    ramfs.shutdown().unwrap();
+    // the data is flushed before the teardown
+    assert_eq!(
+        backend.calls.lock().unwrap().pop(),
+        Some(("/f".into(), 4, vec![(0, b"data".to_vec())]))
+    );
+    assert_eq!(handle.write(b"more"), Err(VfsError::NoSuchDevice));
+    assert_eq!(file.write_at(4, b"more"), Err(VfsError::WouldBlock));
+    // the scheduler no longer knows the filesystem
+    assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(0));
+    drop(handle);
+}
@@ -3,8 +3,21 @@
 //! This module contains functional tests that verify the core functionality
 //! of the axfs_ramfs crate using the actual implementation.
 
-use axfs_ramfs::{DirNode, RamFileSystem};
-use axfs_vfs::{VfsDirEntry, VfsNodeType, VfsOps};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "test-util")]
+use axfs_ramfs::assert_tree_eq;
+#[cfg(feature = "test-util")]
+use axfs_ramfs::test_util::fixture;
+use axfs_ramfs::{DefaultAttrs, DirNode, FileNode, RamFileSystem, SymlinkNode};
+use axfs_vfs::clock::ManualClock;
+use axfs_vfs::export::ExportHandle;
+use axfs_vfs::{
+    AtimePolicy, AttrMask, Credentials, DeviceId, MountOptions, OpenFlags, OpenOptions,
+    RenameFlags, SetAttr, Umask, VfsClock, VfsContext, VfsDirEntry, VfsError, VfsFeatures,
+    VfsNodeFlags, VfsNodeOps, VfsNodePerm, VfsNodeRefExt, VfsNodeType, VfsOps, VfsPage,
+};
 
 // ============== Filesystem Operations Tests ==============
 
@@ -12,14 +25,14 @@ use axfs_vfs::{VfsDirEntry, VfsNodeType, VfsOps};
 fn test_ramfs_new() {
     let fs = RamFileSystem::new();
     let root = fs.root_dir();
-    assert!(root.open().is_ok());
+    assert!(root.open(OpenFlags::READ).is_ok());
 }
 
 #[test]
 fn test_ramfs_default() {
     let fs = RamFileSystem::default();
     let root = fs.root_dir();
-    assert!(root.open().is_ok());
+    assert!(root.open(OpenFlags::READ).is_ok());
 }
 
 #[test]
@@ -41,6 +54,125 @@ fn test_ramfs_root_dir_node() {
     assert!(entries.is_empty());
 }
 
+#[test]
+fn test_ramfs_capabilities() {
+    let fs = RamFileSystem::new();
+    let caps = fs.capabilities();
+    assert!(caps.supports(VfsFeatures::CASE_SENSITIVE));
+    assert!(caps.supports(VfsFeatures::SYMLINK));
+    assert!(caps.supports(VfsFeatures::HARDLINK));
+    assert!(!caps.supports(VfsFeatures::READ_ONLY));
+    assert_eq!(caps.max_name_len(), axfs_vfs::limits::MAX_NAME_LEN);
+}
+
+#[test]
+fn test_ramfs_statfs() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    let info = fs.statfs().unwrap();
+    assert_eq!(info.fs_type(), fs.fs_magic());
+    assert_eq!(info.block_size(), 4096);
+    assert_eq!(info.name_len(), axfs_vfs::limits::MAX_NAME_LEN as u64);
+    assert_eq!((info.blocks(), info.blocks_free()), (0, 0));
+    assert_eq!(info.files(), 1);
+
+    root.create("a", VfsNodeType::File).unwrap();
+    root.link("a", "b").unwrap();
+    let file = root.clone().lookup("a").unwrap();
+    file.write_at(4096 + 1, b"x").unwrap();
+    let info = fs.statfs().unwrap();
+    assert_eq!(info.blocks(), 1);
+    assert_eq!(info.files(), 2);
+
+    file.truncate(0).unwrap();
+    assert_eq!(fs.statfs().unwrap().blocks(), 0);
+}
+
+#[test]
+fn test_ramfs_fs_type() {
+    let fs = RamFileSystem::new();
+    assert_eq!(fs.fs_type(), "ramfs");
+    assert_eq!(fs.fs_magic(), 0x8584_58f6);
+}
+
+#[test]
+fn test_ramfs_with_clock() {
+    let clock = Arc::new(ManualClock::new(Duration::from_secs(1_000)));
+    let fs = RamFileSystem::with_clock(clock.clone());
+    let root = fs.root_dir();
+    assert_eq!(root.get_attr().unwrap().ctime(), Duration::from_secs(1_000));
+
+    clock.advance(Duration::from_secs(1));
+    root.create("file.txt", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("file.txt").unwrap();
+    file.write_at(0, b"data").unwrap();
+    assert_eq!(file.get_attr().unwrap().mtime(), Duration::from_secs(1_001));
+    assert_eq!(root.get_attr().unwrap().mtime(), Duration::from_secs(1_001));
+
+    fs.set_clock(Some(Arc::new(ManualClock::new(Duration::from_secs(5)))));
+    file.truncate(0).unwrap();
+    assert_eq!(file.get_attr().unwrap().mtime(), Duration::from_secs(5));
+}
+
+#[test]
+fn test_ramfs_freeze_thaw() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("file.txt", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("file.txt").unwrap();
+    file.write_at(0, b"snapshot").unwrap();
+
+    fs.freeze().unwrap();
+    assert_eq!(fs.freeze(), Err(VfsError::ResourceBusy));
+    assert_eq!(file.write_at(0, b"x"), Err(VfsError::WouldBlock));
+    assert_eq!(file.truncate(0), Err(VfsError::WouldBlock));
+    assert_eq!(
+        root.create("new.txt", VfsNodeType::File),
+        Err(VfsError::WouldBlock)
+    );
+    assert_eq!(root.remove("file.txt"), Err(VfsError::WouldBlock));
+
+    // reads are still served
+    let mut buf = [0; 8];
+    assert_eq!(file.read_at(0, &mut buf), Ok(8));
+    assert_eq!(&buf, b"snapshot");
+
+    fs.thaw().unwrap();
+    assert_eq!(fs.thaw(), Err(VfsError::InvalidInput));
+    assert_eq!(file.write_at(0, b"S"), Ok(1));
+    root.remove("file.txt").unwrap();
+}
+
+#[test]
+fn test_ramfs_freeze_waits_for_writers() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("file.txt", VfsNodeType::File).unwrap();
+    let file = root.lookup("file.txt").unwrap();
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| loop {
+                let size = file.get_attr().unwrap().size();
+                match file.write_at(size, &[1; 64]) {
+                    Ok(_) => {}
+                    Err(VfsError::WouldBlock) => break,
+                    Err(e) => panic!("{e:?}"),
+                }
+            });
+        }
+        while file.get_attr().unwrap().size() < 4096 {
+            std::thread::yield_now();
+        }
+        fs.freeze().unwrap();
+        // the writes in progress ended before `freeze()` returned
+        let size = file.get_attr().unwrap().size();
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(file.get_attr().unwrap().size(), size);
+    });
+    fs.thaw().unwrap();
+}
+
 // ============== Directory Operations Tests ==============
 
 #[test]
@@ -64,6 +196,36 @@ fn test_directory_create_existing() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_directory_create_exclusive() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create_exclusive("dir", VfsNodeType::Dir).unwrap();
+    root.create_exclusive("dir/file", VfsNodeType::File)
+        .unwrap();
+    for path in ["dir", "dir/file", "dir/", ".", "dir/..", ""] {
+        assert_eq!(
+            root.create_exclusive(path, VfsNodeType::File),
+            Err(VfsError::AlreadyExists),
+            "{path}"
+        );
+    }
+    // unlike `create()`, which succeeds on the existing directories
+    assert_eq!(root.create("dir/.", VfsNodeType::Dir), Ok(()));
+
+    // a single one of concurrent creators succeeds
+    let created = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| s.spawn(|| root.create_exclusive("dir/lock", VfsNodeType::File)))
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|h| h.join().unwrap().ok())
+            .count()
+    });
+    assert_eq!(created, 1);
+}
+
 #[test]
 fn test_directory_remove() {
     let fs = RamFileSystem::new();
@@ -194,6 +356,412 @@ fn test_directory_exist() {
     assert!(!root_node.exist("nonexistent.txt"));
 }
 
+#[test]
+fn test_name_length_limits() {
+    use axfs_vfs::limits::{MAX_NAME_LEN, MAX_PATH};
+
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    let longest = "n".repeat(MAX_NAME_LEN);
+    root.create(&longest, VfsNodeType::File).unwrap();
+    assert!(root.clone().lookup(&longest).is_ok());
+    // the whole name is read back
+    let mut dirents: [VfsDirEntry; 4] = std::array::from_fn(|_| VfsDirEntry::default());
+    assert_eq!(root.read_dir(0, &mut dirents), Ok(3));
+    assert_eq!(dirents[2].name(), longest);
+
+    let too_long = "n".repeat(MAX_NAME_LEN + 1);
+    assert_eq!(
+        root.create(&too_long, VfsNodeType::File),
+        Err(VfsError::NameTooLong)
+    );
+    assert_eq!(
+        root.clone().lookup(&too_long).err(),
+        Some(VfsError::NameTooLong)
+    );
+    let deep = "a/".repeat(MAX_PATH / 2 + 1);
+    assert_eq!(root.lookup(&deep).err(), Some(VfsError::NameTooLong));
+    assert_eq!(fs.capabilities().max_name_len(), MAX_NAME_LEN);
+}
+
+// ============== Symbolic Link Tests ==============
+
+#[test]
+fn test_symlink_create_and_read() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("proc", VfsNodeType::Dir).unwrap();
+    root.create("proc/self", VfsNodeType::Dir).unwrap();
+    root.create_symlink("proc/self/exe", "/bin/busybox")
+        .unwrap();
+
+    let link = root.clone().lookup("proc/self/exe").unwrap();
+    let attr = link.get_attr().unwrap();
+    assert!(attr.file_type().is_symlink());
+    assert_eq!(attr.size(), 12);
+    let mut buf = [0; 64];
+    let n = link.read_link(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"/bin/busybox");
+
+    // the link shows up in listings, and can be removed
+    let dir = root.clone().lookup("proc/self").unwrap();
+    let mut entries = [
+        VfsDirEntry::default(),
+        VfsDirEntry::default(),
+        VfsDirEntry::default(),
+    ];
+    assert_eq!(dir.read_dir(0, &mut entries), Ok(3));
+    assert_eq!(entries[2].name_as_bytes(), b"exe");
+    assert_eq!(entries[2].entry_type(), VfsNodeType::SymLink);
+    root.remove("proc/self/exe").unwrap();
+    assert!(root.lookup("proc/self/exe").is_err());
+}
+
+#[test]
+fn test_symlink_errors() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("file", VfsNodeType::File).unwrap();
+    assert_eq!(
+        root.create_symlink("file", "target"),
+        Err(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.create_symlink("missing/link", "target"),
+        Err(VfsError::NotFound)
+    );
+
+    // only links have a target
+    let file = root.clone().lookup("file").unwrap();
+    let mut buf = [0; 8];
+    assert_eq!(file.read_link(&mut buf), Err(VfsError::InvalidInput));
+    assert_eq!(root.read_link(&mut buf), Err(VfsError::InvalidInput));
+    assert_eq!(
+        file.create_symlink("link", "target"),
+        Err(VfsError::NotADirectory)
+    );
+}
+
+// ============== Hard Link Tests ==============
+
+#[test]
+fn test_hard_link_shares_content() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("a", VfsNodeType::File).unwrap();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.link("a", "dir/b").unwrap();
+
+    let a = root.clone().lookup("a").unwrap();
+    let b = root.clone().lookup("dir/b").unwrap();
+    assert!(Arc::ptr_eq(&a, &b));
+    assert_eq!(a.get_attr().unwrap().nlink(), 2);
+    a.write_at(0, b"shared").unwrap();
+    let mut buf = [0; 6];
+    assert_eq!(b.read_at(0, &mut buf), Ok(6));
+    assert_eq!(&buf, b"shared");
+
+    // the content survives the removal of one of the links
+    root.remove("a").unwrap();
+    assert_eq!(b.get_attr().unwrap().nlink(), 1);
+    assert_eq!(b.read_at(0, &mut buf), Ok(6));
+    root.remove("dir/b").unwrap();
+    assert_eq!(b.get_attr().unwrap().nlink(), 0);
+}
+
+#[test]
+fn test_hard_link_errors() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("file", VfsNodeType::File).unwrap();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    assert_eq!(root.link("file", "dir"), Err(VfsError::AlreadyExists));
+    assert_eq!(root.link("missing", "new"), Err(VfsError::NotFound));
+    assert_eq!(root.link("file", "nodir/new"), Err(VfsError::NotFound));
+    assert_eq!(root.link("file", "file/new"), Err(VfsError::NotADirectory));
+    assert_eq!(
+        root.link("dir", "dir2"),
+        Err(VfsError::OperationNotPermitted)
+    );
+
+    // other filesystems cannot be linked into this one
+    let other = RamFileSystem::new();
+    other.root_dir().create("f", VfsNodeType::File).unwrap();
+    let foreign = other.root_dir().lookup("f").unwrap();
+    assert_eq!(
+        fs.root_dir_node().link_node("f", foreign),
+        Err(VfsError::CrossesDevices)
+    );
+}
+
+#[test]
+fn test_directory_link_count() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    assert_eq!(root.get_attr().unwrap().nlink(), 2);
+    root.create("d1", VfsNodeType::Dir).unwrap();
+    root.create("d2", VfsNodeType::Dir).unwrap();
+    root.create("f", VfsNodeType::File).unwrap();
+    assert_eq!(root.get_attr().unwrap().nlink(), 4);
+}
+
+// ============== Attribute Tests ==============
+
+#[test]
+fn test_chmod_chown() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/file", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("dir/file").unwrap();
+    let attr = file.get_attr().unwrap();
+    assert_eq!((attr.uid(), attr.gid()), (0, 0));
+
+    file.set_attr(&SetAttr::new().uid(1000).gid(100)).unwrap();
+    file.set_attr(&SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o640)))
+        .unwrap();
+    let attr = file.get_attr().unwrap();
+    assert_eq!((attr.uid(), attr.gid()), (1000, 100));
+    assert_eq!(attr.perm().mode(), 0o640);
+
+    // the owner is per node
+    let dir = root.clone().lookup("dir").unwrap();
+    dir.set_attr(&SetAttr::new().gid(50)).unwrap();
+    let attr = dir.get_attr().unwrap();
+    assert_eq!((attr.uid(), attr.gid()), (0, 50));
+    assert_eq!(root.get_attr().unwrap().gid(), 0);
+}
+
+#[test]
+fn test_default_attrs_inherited() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("spool", VfsNodeType::Dir).unwrap();
+    let spool = root.clone().lookup_as::<DirNode>("spool").unwrap();
+    let defaults = DefaultAttrs {
+        file_mode: Some(VfsNodePerm::from_bits_truncate(0o640)),
+        dir_mode: Some(VfsNodePerm::from_bits_truncate(0o770)),
+        uid: None,
+        gid: Some(12),
+    };
+    spool.set_default_attrs(Some(defaults));
+
+    root.create("spool/mail", VfsNodeType::Dir).unwrap();
+    root.create("spool/mail/msg", VfsNodeType::File).unwrap();
+    root.create("other", VfsNodeType::File).unwrap();
+
+    let attr = root
+        .clone()
+        .lookup("spool/mail")
+        .unwrap()
+        .get_attr()
+        .unwrap();
+    assert_eq!((attr.perm().mode(), attr.uid(), attr.gid()), (0o770, 0, 12));
+    // the template is inherited by subdirectories
+    let attr = root
+        .clone()
+        .lookup("spool/mail/msg")
+        .unwrap()
+        .get_attr()
+        .unwrap();
+    assert_eq!((attr.perm().mode(), attr.gid()), (0o640, 12));
+    // and only applies below the directory
+    let attr = root.clone().lookup("other").unwrap().get_attr().unwrap();
+    assert_eq!((attr.perm().mode(), attr.gid()), (0o666, 0));
+
+    spool.set_default_attrs(None);
+    root.create("spool/plain", VfsNodeType::File).unwrap();
+    let attr = root
+        .clone()
+        .lookup("spool/plain")
+        .unwrap()
+        .get_attr()
+        .unwrap();
+    assert_eq!((attr.perm().mode(), attr.gid()), (0o666, 0));
+}
+
+#[test]
+fn test_immutable_file() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("config", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("config").unwrap();
+    file.write_at(0, b"key=value").unwrap();
+    file.set_flags(VfsNodeFlags::IMMUTABLE).unwrap();
+    assert_eq!(file.get_flags().unwrap(), VfsNodeFlags::IMMUTABLE);
+
+    let eperm = Some(VfsError::OperationNotPermitted);
+    assert_eq!(file.write_at(9, b"x").err(), eperm);
+    assert_eq!(file.truncate(0).err(), eperm);
+    assert_eq!(file.set_attr(&SetAttr::new().uid(1)).err(), eperm);
+    assert_eq!(root.remove("config").err(), eperm);
+    assert_eq!(root.link("config", "alias").err(), eperm);
+
+    // reading is still allowed, and clearing the flag lifts the protection
+    let mut buf = [0; 9];
+    assert_eq!(file.read_at(0, &mut buf).unwrap(), 9);
+    file.set_flags(VfsNodeFlags::empty()).unwrap();
+    file.truncate(0).unwrap();
+    root.remove("config").unwrap();
+}
+
+#[test]
+fn test_append_only_file() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("log", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("log").unwrap();
+    file.write_at(0, b"boot\n").unwrap();
+    file.set_flags(VfsNodeFlags::APPEND).unwrap();
+
+    let eperm = Some(VfsError::OperationNotPermitted);
+    assert_eq!(file.write_at(0, b"BOOT").err(), eperm);
+    assert_eq!(file.write_at(5, b"ok\n").unwrap(), 3);
+    assert_eq!(file.get_attr().unwrap().size(), 8);
+    assert_eq!(file.truncate(0).err(), eperm);
+    assert_eq!(root.remove("log").err(), eperm);
+
+    // the attributes can still be changed
+    file.set_attr(&SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o600)))
+        .unwrap();
+}
+
+#[test]
+fn test_open_flags() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("log", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("log").unwrap();
+    file.write_at(0, b"boot\n").unwrap();
+
+    // O_TRUNC only applies to writable opens
+    file.open(OpenFlags::READ | OpenFlags::TRUNC).unwrap();
+    assert_eq!(file.get_attr().unwrap().size(), 5);
+    file.open(OpenFlags::WRITE | OpenFlags::TRUNC).unwrap();
+    assert_eq!(file.get_attr().unwrap().size(), 0);
+
+    // append-only files must be opened in append mode to be written
+    file.set_flags(VfsNodeFlags::APPEND).unwrap();
+    let eperm = Some(VfsError::OperationNotPermitted);
+    assert_eq!(file.open(OpenFlags::WRITE).err(), eperm);
+    assert!(file.open(OpenFlags::WRITE | OpenFlags::APPEND).is_ok());
+    assert_eq!(
+        file.open(OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::TRUNC)
+            .err(),
+        eperm
+    );
+    file.set_flags(VfsNodeFlags::IMMUTABLE).unwrap();
+    assert_eq!(file.open(OpenFlags::WRITE | OpenFlags::APPEND).err(), eperm);
+    assert!(file.open(OpenFlags::READ).is_ok());
+
+    assert_eq!(
+        root.open(OpenFlags::WRITE).err(),
+        Some(VfsError::IsADirectory)
+    );
+}
+
+#[test]
+fn test_protected_directory() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("etc", VfsNodeType::Dir).unwrap();
+    root.create("etc/passwd", VfsNodeType::File).unwrap();
+    let etc = root.clone().lookup("etc").unwrap();
+    let eperm = Some(VfsError::OperationNotPermitted);
+
+    etc.set_flags(VfsNodeFlags::APPEND).unwrap();
+    etc.create("group", VfsNodeType::File).unwrap();
+    assert_eq!(etc.remove("group").err(), eperm);
+    assert_eq!(root.remove("etc").err(), eperm);
+
+    etc.set_flags(VfsNodeFlags::IMMUTABLE).unwrap();
+    assert_eq!(etc.create("shadow", VfsNodeType::File).err(), eperm);
+    assert_eq!(etc.create_symlink("link", "passwd").err(), eperm);
+    assert_eq!(etc.remove("passwd").err(), eperm);
+
+    // the files of an immutable directory can still be written
+    let passwd = etc.clone().lookup("passwd").unwrap();
+    passwd.write_at(0, b"root").unwrap();
+}
+
+// ============== Open By Inode Tests ==============
+
+#[test]
+fn test_open_by_ino() {
+    let fs = RamFileSystem::new();
+    assert!(fs.capabilities().supports(VfsFeatures::OPEN_BY_INO));
+    let root = fs.root_dir();
+    assert!(Arc::ptr_eq(&fs.open_by_ino(1).unwrap(), &root));
+
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/file", VfsNodeType::File).unwrap();
+    root.create_symlink("link", "dir").unwrap();
+    let dir = root.clone().lookup_as::<DirNode>("dir").unwrap();
+    let file = root.clone().lookup_as::<FileNode>("dir/file").unwrap();
+    let link = root.clone().lookup_as::<SymlinkNode>("link").unwrap();
+    assert_eq!((dir.ino(), file.ino(), link.ino()), (2, 3, 4));
+
+    let node = fs.open_by_ino(file.ino()).unwrap();
+    assert!(Arc::ptr_eq(
+        &node,
+        &root.clone().lookup("dir/file").unwrap()
+    ));
+    assert_eq!(
+        fs.open_by_ino(link.ino())
+            .unwrap()
+            .get_attr()
+            .unwrap()
+            .file_type(),
+        VfsNodeType::SymLink
+    );
+    assert_eq!(fs.open_by_ino(5).err(), Some(VfsError::NotFound));
+}
+
+#[test]
+fn test_open_by_ino_removed() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("a", VfsNodeType::File).unwrap();
+    root.create("d", VfsNodeType::Dir).unwrap();
+    root.link("a", "b").unwrap();
+    let ino = root.clone().lookup_as::<FileNode>("a").unwrap().ino();
+    let dir_ino = root.clone().lookup_as::<DirNode>("d").unwrap().ino();
+
+    // the file stays reachable while one link remains, even if open
+    let open = root.clone().lookup("a").unwrap();
+    root.remove("a").unwrap();
+    assert!(fs.open_by_ino(ino).is_ok());
+    root.remove("b").unwrap();
+    assert_eq!(fs.open_by_ino(ino).err(), Some(VfsError::NotFound));
+    drop(open);
+
+    root.remove("d").unwrap();
+    assert_eq!(fs.open_by_ino(dir_ino).err(), Some(VfsError::NotFound));
+
+    // numbers are not reused
+    root.create("c", VfsNodeType::File).unwrap();
+    assert_eq!(root.clone().lookup_as::<FileNode>("c").unwrap().ino(), 4);
+}
+
+#[test]
+fn test_ino_in_attr_and_dir_entries() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.create("dir/file", VfsNodeType::File).unwrap();
+    let dir = root.clone().lookup("dir").unwrap();
+    let file = root.clone().lookup("dir/file").unwrap();
+    let dir_ino = dir.get_attr().unwrap().ino();
+    assert_eq!(root.get_attr().unwrap().ino(), 1);
+    assert_eq!(dir_ino, 2);
+
+    let mut entries: [VfsDirEntry; 3] = core::array::from_fn(|_| VfsDirEntry::default());
+    assert_eq!(dir.read_dir(0, &mut entries).unwrap(), 3);
+    assert_eq!(entries[0].ino(), dir_ino);
+    assert_eq!(entries[1].ino(), 1);
+    assert_eq!(entries[2].ino(), file.get_attr().unwrap().ino());
+}
+
 // ============== File Operations Tests ==============
 
 #[test]
@@ -331,6 +899,37 @@ fn test_file_truncate_shrink() {
     assert_eq!(&buf[..5], b"Hello");
 }
 
+#[test]
+fn test_file_get_page() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("mapped", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("mapped").unwrap();
+    file.write_at(0, b"hello").unwrap();
+    file.truncate(4096 + 10).unwrap();
+
+    let Ok(VfsPage::Frame(frame)) = file.get_page(0) else {
+        panic!("expected a page frame");
+    };
+    // the mapping shares the storage of the file
+    // SAFETY: the file is not accessed concurrently.
+    unsafe {
+        let mut buf = [0; 5];
+        frame.read(0, &mut buf);
+        assert_eq!(&buf, b"hello");
+        frame.write(0, b"HE");
+    }
+    let mut buf = [0; 5];
+    file.read_at(0, &mut buf).unwrap();
+    assert_eq!(&buf, b"HEllo");
+
+    // holes are allocated when mapped
+    assert!(matches!(file.get_page(4096), Ok(VfsPage::Frame(_))));
+    assert_eq!(file.get_page(1).err(), Some(VfsError::InvalidInput));
+    assert_eq!(file.get_page(8192).err(), Some(VfsError::InvalidInput));
+    assert_eq!(root.get_page(0).err(), Some(VfsError::NoSuchDevice));
+}
+
 // ============== File Remove Tests ==============
 
 #[test]
@@ -362,10 +961,125 @@ fn test_ramfs_mount() {
     let fs = RamFileSystem::new();
 
     let root = fs.root_dir();
-    let result = fs.mount("/", root);
+    let result = fs.mount("/", root, &MountOptions::new());
     assert!(result.is_ok());
 }
 
+#[test]
+fn test_ramfs_mount_size_option() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    let bad = MountOptions::parse("size=1m,huge=always").unwrap();
+    assert_eq!(
+        fs.mount("/", root.clone(), &bad),
+        Err(VfsError::InvalidInput)
+    );
+    let bad = MountOptions::parse("size=lots").unwrap();
+    assert_eq!(
+        fs.mount("/", root.clone(), &bad),
+        Err(VfsError::InvalidInput)
+    );
+    assert_eq!(fs.size_limit(), None);
+
+    let opts = MountOptions::parse("size=10000").unwrap();
+    fs.mount("/", root.clone(), &opts).unwrap();
+    // rounded up to whole pages
+    assert_eq!(fs.size_limit(), Some(3 * 4096));
+    let info = fs.statfs().unwrap();
+    assert_eq!((info.blocks(), info.blocks_free()), (3, 3));
+
+    root.create("a", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("a").unwrap();
+    assert_eq!(file.write_at(0, &[1; 5 * 4096]), Ok(3 * 4096));
+    assert_eq!(file.write_at(3 * 4096, b"x"), Err(VfsError::StorageFull));
+    assert_eq!(fs.statfs().unwrap().blocks_free(), 0);
+
+    root.remove("a").unwrap();
+    drop(file);
+    assert_eq!(fs.statfs().unwrap().blocks_free(), 3);
+    fs.mount("/", root, &MountOptions::parse("size=0").unwrap())
+        .unwrap();
+    assert_eq!(fs.size_limit(), None);
+}
+
+#[test]
+#[cfg(feature = "test-util")]
+fn test_ramfs_remount_read_only() {
+    let tree = fixture().file("etc/rc", "boot");
+    let fs = tree.build_ramfs();
+    let root = fs.root_dir();
+    let rc = root.clone().lookup("etc/rc").unwrap();
+
+    fs.remount(&MountOptions::parse("ro").unwrap()).unwrap();
+    assert!(fs.capabilities().supports(VfsFeatures::READ_ONLY));
+    let denied = Err(VfsError::PermissionDenied);
+    assert_eq!(rc.write_at(0, b"x"), Err(VfsError::PermissionDenied));
+    assert_eq!(rc.truncate(0), denied);
+    assert_eq!(
+        rc.open(OpenFlags::WRITE).err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        rc.set_attr(&SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o600))),
+        denied
+    );
+    assert_eq!(root.create("etc/new", VfsNodeType::File), denied);
+    assert_eq!(root.create_symlink("link", "etc/rc"), denied);
+    assert_eq!(root.rename("etc/rc", "etc/rc2"), denied);
+    assert_eq!(root.remove("etc/rc"), denied);
+    assert_tree_eq!(fs, tree);
+
+    // reads are still served, without updating access times
+    let atime = rc.get_attr().unwrap().atime();
+    let mut buf = [0; 4];
+    assert_eq!(rc.read_at(0, &mut buf), Ok(4));
+    assert_eq!(&buf, b"boot");
+    assert!(rc.open(OpenFlags::READ).is_ok());
+    assert_eq!(rc.get_attr().unwrap().atime(), atime);
+
+    // unknown options are rejected without changing the state
+    let bad = MountOptions::parse("rw,huge").unwrap();
+    assert_eq!(fs.remount(&bad), Err(VfsError::InvalidInput));
+    assert_eq!(root.remove("etc/rc"), denied);
+
+    fs.remount(&MountOptions::new()).unwrap();
+    assert!(!fs.capabilities().supports(VfsFeatures::READ_ONLY));
+    root.remove("etc/rc").unwrap();
+}
+
+#[test]
+fn test_ramfs_get_page_read_only() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("init", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("init").unwrap();
+    file.write_at(0, b"boot").unwrap();
+    file.truncate(4096 + 10).unwrap();
+    assert_eq!(fs.statfs().unwrap().blocks(), 1);
+
+    fs.remount(&MountOptions::parse("ro").unwrap()).unwrap();
+    let Ok(VfsPage::ReadOnlyFrame(copy)) = file.get_page(0) else {
+        panic!("expected a read-only page frame");
+    };
+    // SAFETY: the copy is private to the test.
+    unsafe { copy.write(0, b"BO") };
+    let mut buf = [0; 4];
+    file.read_at(0, &mut buf).unwrap();
+    assert_eq!(&buf, b"boot");
+    // holes are not allocated
+    assert!(matches!(file.get_page(4096), Ok(VfsPage::ReadOnlyFrame(_))));
+    assert_eq!(fs.statfs().unwrap().blocks(), 1);
+    assert_eq!(file.get_page(1).err(), Some(VfsError::InvalidInput));
+    fs.remount(&MountOptions::new()).unwrap();
+
+    fs.freeze().unwrap();
+    assert!(matches!(file.get_page(4096), Ok(VfsPage::ReadOnlyFrame(_))));
+    assert_eq!(fs.statfs().unwrap().blocks(), 1);
+    fs.thaw().unwrap();
+    assert!(matches!(file.get_page(4096), Ok(VfsPage::Frame(_))));
+    assert_eq!(fs.statfs().unwrap().blocks(), 2);
+}
+
 #[test]
 fn test_ramfs_umount() {
     let fs = RamFileSystem::new();
@@ -390,6 +1104,8 @@ fn test_nested_directories() {
 
     let file = root.lookup("a/b/c/file.txt").unwrap();
     assert!(file.get_attr().unwrap().is_file());
+    #[cfg(feature = "test-util")]
+    assert_tree_eq!(fs, fixture().file("a/b/c/file.txt", ""));
 }
 
 #[test]
@@ -398,17 +1114,17 @@ fn test_multiple_files_in_directory() {
     let root = fs.root_dir();
 
     root.create("dir", VfsNodeType::Dir).unwrap();
-    let dir = root.lookup("dir").unwrap();
+    let dir_node = root.lookup_as::<DirNode>("dir").unwrap();
 
     for i in 0..10 {
-        dir.create(&format!("file{}.txt", i), VfsNodeType::File)
+        dir_node
+            .create(&format!("file{}.txt", i), VfsNodeType::File)
             .unwrap();
     }
 
     let root_node = fs.root_dir_node();
     assert!(root_node.exist("dir"));
 
-    let dir_node = dir.as_any().downcast_ref::<DirNode>().unwrap();
     assert_eq!(dir_node.get_entries().len(), 10);
 }
 
@@ -426,3 +1142,1175 @@ fn test_mixed_entries_in_directory() {
     let entries = root_node.get_entries();
     assert_eq!(entries.len(), 4);
 }
+
+#[test]
+fn test_file_window() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("fw.bin", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("fw.bin").unwrap();
+    file.write_at(0, &[0xab; 8192]).unwrap();
+    file.write_at(4096, b"image").unwrap();
+
+    let window = axfs_vfs::FileWindow::new(file.clone()).with_range(4096, Some(5));
+    let mut buf = [0; 16];
+    assert_eq!(window.read_at(0, &mut buf), Ok(5));
+    assert_eq!(&buf[..5], b"image");
+    assert_eq!(window.get_attr().unwrap().size(), 5);
+    assert_eq!(window.write_at(0, b"x"), Err(VfsError::ReadOnlyFilesystem));
+    assert_eq!(
+        window.open(OpenFlags::WRITE).err(),
+        Some(VfsError::ReadOnlyFilesystem)
+    );
+    // the page would expose the bytes after the window
+    assert_eq!(window.get_page(0).err(), Some(VfsError::InvalidInput));
+    let head = axfs_vfs::FileWindow::new(file.clone()).with_range(0, Some(10));
+    assert_eq!(head.get_page(0).err(), Some(VfsError::InvalidInput));
+
+    // pages of the file are shared read-only with the window
+    let window = axfs_vfs::FileWindow::new(file.clone()).with_range(4096, Some(4096));
+    let (Ok(VfsPage::ReadOnlyFrame(a)), Ok(VfsPage::Frame(b))) =
+        (window.get_page(0), file.get_page(4096))
+    else {
+        panic!("expected page frames");
+    };
+    assert!(Arc::ptr_eq(&a, &b));
+    let tail = axfs_vfs::FileWindow::new(file.clone()).with_range(4096, None);
+    assert!(matches!(tail.get_page(0), Ok(VfsPage::ReadOnlyFrame(_))));
+    assert_eq!(file.get_attr().unwrap().size(), 8192);
+}
+
+#[test]
+fn test_slice_partitions() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("disk.img", VfsNodeType::File).unwrap();
+    let disk = root.clone().lookup("disk.img").unwrap();
+    disk.truncate(1024).unwrap();
+
+    let p1 = axfs_vfs::SliceNode::new(disk.clone(), 0, Some(512));
+    let p2 = axfs_vfs::SliceNode::new(disk.clone(), 512, Some(512));
+    assert_eq!(p1.write_at(510, b"boot"), Ok(2));
+    assert_eq!(p2.write_at(0, b"data"), Ok(4));
+    assert_eq!(p2.write_at(512, b"x"), Err(VfsError::StorageFull));
+
+    let mut buf = [0; 6];
+    assert_eq!(disk.read_at(510, &mut buf), Ok(6));
+    assert_eq!(&buf, b"bodata");
+    assert_eq!(p2.get_attr().unwrap().size(), 512);
+    assert_eq!(disk.get_attr().unwrap().size(), 1024);
+
+    // truncating a partition leaves the next one alone
+    assert_eq!(p1.truncate(0), Err(VfsError::InvalidInput));
+    p1.open(OpenFlags::WRITE | OpenFlags::TRUNC).unwrap();
+    assert_eq!(p2.read_at(0, &mut buf), Ok(6));
+    assert_eq!(&buf[..4], b"data");
+    assert_eq!(disk.get_attr().unwrap().size(), 1024);
+}
+
+#[test]
+fn test_replace_file() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("etc", VfsNodeType::Dir).unwrap();
+    let etc = root.clone().lookup("etc").unwrap();
+
+    axfs_vfs::replace::replace_file(&etc, "hosts", b"127.0.0.1 localhost\n").unwrap();
+    let old = etc.clone().lookup("hosts").unwrap();
+    axfs_vfs::replace::replace_file(&etc, "hosts", b"10.0.0.1 arceos\n").unwrap();
+
+    let new = etc.clone().lookup("hosts").unwrap();
+    assert!(!Arc::ptr_eq(&old, &new));
+    let mut buf = [0; 32];
+    let n = new.read_at(0, &mut buf).unwrap();
+    assert_eq!(&buf[..n], b"10.0.0.1 arceos\n");
+    // the old file stays readable through existing references
+    let n = old.read_at(0, &mut buf).unwrap();
+    assert_eq!(&buf[..n], b"127.0.0.1 localhost\n");
+
+    let etc_dir = root.lookup_as::<DirNode>("etc").unwrap();
+    assert_eq!(etc_dir.get_entries(), ["hosts"]);
+}
+
+#[test]
+fn test_fallocate_through_handle() {
+    use axfs_vfs::handle::VfsFileHandle;
+    use axfs_vfs::FallocateMode;
+
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("log", VfsNodeType::File).unwrap();
+    let log = root.clone().lookup("log").unwrap();
+
+    let reader = VfsFileHandle::open_with(log.clone(), OpenFlags::READ).unwrap();
+    assert_eq!(
+        reader.fallocate(0, 4096, FallocateMode::empty()),
+        Err(VfsError::BadFileDescriptor)
+    );
+    let writer = VfsFileHandle::open(log.clone()).unwrap();
+    writer.fallocate(0, 8192, FallocateMode::empty()).unwrap();
+    assert_eq!(log.get_attr().unwrap().size(), 8192);
+    writer.write_at(0, b"entry").unwrap();
+    writer
+        .fallocate(
+            0,
+            8192,
+            FallocateMode::PUNCH_HOLE | FallocateMode::KEEP_SIZE,
+        )
+        .unwrap();
+    let mut buf = [1; 5];
+    assert_eq!(reader.read_at(0, &mut buf), Ok(5));
+    assert_eq!(buf, [0; 5]);
+    assert_eq!(log.get_attr().unwrap().size(), 8192);
+
+    assert_eq!(
+        root.fallocate(0, 1, FallocateMode::empty()),
+        Err(VfsError::IsADirectory)
+    );
+}
+
+#[test]
+fn test_seek_data_and_hole() {
+    use axfs_vfs::handle::VfsFileHandle;
+    use axfs_vfs::SeekHint;
+
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("sparse", VfsNodeType::File).unwrap();
+    root.create("copy", VfsNodeType::File).unwrap();
+    let sparse = root.clone().lookup("sparse").unwrap();
+    let copy = root.clone().lookup("copy").unwrap();
+    sparse.truncate(1 << 30).unwrap();
+    sparse.write_at(1 << 20, b"data").unwrap();
+
+    let handle = VfsFileHandle::open(sparse.clone()).unwrap();
+    assert_eq!(handle.seek_hint(0, SeekHint::Data), Ok(Some(1 << 20)));
+    assert_eq!(handle.offset(), 1 << 20);
+    assert_eq!(
+        handle.seek_hint(1 << 20, SeekHint::Hole),
+        Ok(Some((1 << 20) + 4096))
+    );
+    assert_eq!(handle.seek_hint(1 << 21, SeekHint::Data), Ok(None));
+    assert_eq!(handle.offset(), (1 << 20) + 4096);
+
+    // the gigabyte of holes is skipped
+    let size = axfs_vfs::copy::copy_sparse(sparse.as_ref(), copy.as_ref()).unwrap();
+    assert_eq!(size, 1 << 30);
+    assert_eq!(copy.seek_hint(0, SeekHint::Data), Ok(Some(1 << 20)));
+    let mut buf = [0; 4];
+    copy.read_at(1 << 20, &mut buf).unwrap();
+    assert_eq!(&buf, b"data");
+}
+
+#[test]
+fn test_dir_stream_under_concurrent_changes() {
+    let ramfs = RamFileSystem::new();
+    let root = ramfs.root_dir();
+    root.create("d", VfsNodeType::Dir).unwrap();
+    for i in 0..20 {
+        root.create(&format!("d/f{i:02}"), VfsNodeType::File)
+            .unwrap();
+    }
+    let dir = root.clone().lookup("d").unwrap();
+
+    // delete every entry right after it is returned, which would make an
+    // index-based reader skip half of them
+    let mut stream = dir.clone().open_dir().unwrap();
+    let mut seen = Vec::new();
+    while let Some(entry) = stream.next_entry().unwrap() {
+        let name = String::from_utf8(entry.name_as_bytes().to_vec()).unwrap();
+        if name != "." && name != ".." {
+            dir.remove(&name).unwrap();
+        }
+        seen.push(name);
+    }
+    assert_eq!(seen.len(), 22);
+    assert_eq!(seen[2], "f00");
+    assert_eq!(seen[21], "f19");
+
+    let parent = root.clone().lookup("d/..").unwrap();
+    assert!(parent.open_dir().is_ok());
+    root.create("g", VfsNodeType::File).unwrap();
+    let file = root.lookup("g").unwrap();
+    assert_eq!(file.open_dir().err(), Some(VfsError::NotADirectory));
+}
+
+#[test]
+fn test_dir_stream_snapshot_policy() {
+    use axfs_vfs::ReadDirPolicy;
+
+    let ramfs = RamFileSystem::new();
+    assert_eq!(ramfs.read_dir_policy(), ReadDirPolicy::Live);
+    ramfs.set_read_dir_policy(ReadDirPolicy::Snapshot);
+    let root = ramfs.root_dir();
+    root.create("d", VfsNodeType::Dir).unwrap();
+    for name in ["a", "c", "e"] {
+        root.create(&format!("d/{name}"), VfsNodeType::File)
+            .unwrap();
+    }
+    let dir = root.lookup("d").unwrap();
+
+    // entries added or removed during the listing do not change it
+    let mut stream = dir.clone().open_dir().unwrap();
+    let mut seen = Vec::new();
+    while let Some(entry) = stream.next_entry().unwrap() {
+        if entry.name() == "a" {
+            dir.remove("c").unwrap();
+            dir.create("b", VfsNodeType::File).unwrap();
+            dir.create("f", VfsNodeType::File).unwrap();
+        }
+        seen.push(entry.name().to_string());
+    }
+    assert_eq!(seen, [".", "..", "a", "c", "e"]);
+
+    // rewinding lists the current entries
+    stream.seek(&axfs_vfs::DirCookie::start()).unwrap();
+    assert_eq!(
+        std::iter::from_fn(|| stream.next_entry().unwrap()).count(),
+        6
+    );
+}
+
+#[test]
+fn test_name_policy() {
+    use axfs_vfs::names::{NamePolicy, Normalization};
+    use axfs_vfs::DirEntries;
+
+    let ramfs = RamFileSystem::new();
+    let policy = NamePolicy::new()
+        .normalization(Normalization::ToNfc)
+        .reject_control(true);
+    ramfs.set_name_policy(policy);
+    assert_eq!(ramfs.name_policy(), policy);
+    let root = ramfs.root_dir();
+
+    // both encodings of "café" name the same entry
+    root.create("cafe\u{301}", VfsNodeType::Dir).unwrap();
+    assert_eq!(
+        root.create("caf\u{e9}", VfsNodeType::Dir),
+        Err(VfsError::AlreadyExists)
+    );
+    root.create("caf\u{e9}/menu", VfsNodeType::File).unwrap();
+    let menu = root.clone().lookup("cafe\u{301}/menu").unwrap();
+    menu.write_at(0, b"tea").unwrap();
+    let dir = root.clone().lookup("caf\u{e9}").unwrap();
+    let names: Vec<_> = DirEntries::new(dir.as_ref())
+        .map(|e| e.unwrap().name_as_bytes().to_vec())
+        .collect();
+    assert_eq!(names, [&b"."[..], b"..", b"menu"]);
+    let root_names: Vec<_> = DirEntries::new(root.as_ref())
+        .map(|e| e.unwrap().name_as_bytes().to_vec())
+        .collect();
+    assert_eq!(root_names[2], "caf\u{e9}".as_bytes());
+
+    // renames and removals find the entry under either encoding
+    root.rename("cafe\u{301}/menu", "cafe\u{301}/me\u{301}nu")
+        .unwrap();
+    assert!(root.clone().lookup("caf\u{e9}/m\u{e9}nu").is_ok());
+    root.remove("caf\u{e9}/me\u{301}nu").unwrap();
+    root.remove("cafe\u{301}").unwrap();
+
+    for name in ["new\nline", "bell\u{7}"] {
+        assert_eq!(
+            root.create(name, VfsNodeType::File),
+            Err(VfsError::IllegalBytes)
+        );
+    }
+    assert_eq!(
+        root.create_symlink("tab\tlink", "/"),
+        Err(VfsError::IllegalBytes)
+    );
+
+    // a stricter policy rejects the names that are not normalized
+    ramfs.set_name_policy(NamePolicy::new().normalization(Normalization::RequireNfc));
+    assert_eq!(
+        root.create("cafe\u{301}", VfsNodeType::File),
+        Err(VfsError::IllegalBytes)
+    );
+    root.create("caf\u{e9}", VfsNodeType::File).unwrap();
+}
+
+#[test]
+fn test_watch_events() {
+    use axfs_vfs::notify::{WatchEvent, WatchId, WatchMask, WatchSink};
+    use std::sync::Mutex;
+
+    /// The events received, as `(watch, mask, name, cookie, is_dir)`.
+    type Received = (WatchId, WatchMask, Option<String>, u32, bool);
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<Received>>);
+
+    impl Recorder {
+        fn take(&self) -> Vec<Received> {
+            std::mem::take(&mut *self.0.lock().unwrap())
+        }
+    }
+
+    impl WatchSink for Recorder {
+        fn event(&self, id: WatchId, event: &WatchEvent) {
+            let name = event.name.map(String::from);
+            let received = (id, event.mask, name, event.cookie, event.is_dir);
+            self.0.lock().unwrap().push(received);
+        }
+    }
+
+    let ramfs = RamFileSystem::new();
+    assert!(ramfs.capabilities().supports(VfsFeatures::WATCH));
+    let root = ramfs.root_dir();
+    root.create("src", VfsNodeType::Dir).unwrap();
+    root.create("dst", VfsNodeType::Dir).unwrap();
+
+    let sink = Arc::new(Recorder::default());
+    let all = WatchMask::all();
+    let src = ramfs.subscribe("src", all, sink.clone()).unwrap();
+    let dst = ramfs.subscribe("/dst", all, sink.clone()).unwrap();
+    assert_eq!(
+        ramfs.subscribe("nowhere", all, sink.clone()).err(),
+        Some(VfsError::NotFound)
+    );
+
+    root.create("src/f", VfsNodeType::File).unwrap();
+    root.create("src/d", VfsNodeType::Dir).unwrap();
+    root.create("elsewhere", VfsNodeType::File).unwrap();
+    assert_eq!(
+        sink.take(),
+        [
+            (src, WatchMask::CREATE, Some("f".into()), 0, false),
+            (src, WatchMask::CREATE, Some("d".into()), 0, true),
+        ]
+    );
+
+    // a file watch follows the file across renames
+    let file = ramfs
+        .subscribe("src/f", WatchMask::MODIFY, sink.clone())
+        .unwrap();
+    root.rename("src/f", "dst/g").unwrap();
+    let events = sink.take();
+    assert_eq!(events.len(), 2);
+    let cookie = events[0].3;
+    assert_ne!(cookie, 0);
+    assert_eq!(
+        events,
+        [
+            (src, WatchMask::MOVED_FROM, Some("f".into()), cookie, false),
+            (dst, WatchMask::MOVED_TO, Some("g".into()), cookie, false),
+        ]
+    );
+    let g = root.clone().lookup("dst/g").unwrap();
+    g.write_at(0, b"hello").unwrap();
+    g.truncate(2).unwrap();
+    assert_eq!(
+        sink.take(),
+        [
+            (file, WatchMask::MODIFY, None, 0, false),
+            (file, WatchMask::MODIFY, None, 0, false),
+        ]
+    );
+
+    root.remove("src/d").unwrap();
+    root.remove("dst/g").unwrap();
+    assert_eq!(
+        sink.take(),
+        [
+            (src, WatchMask::DELETE, Some("d".into()), 0, true),
+            (dst, WatchMask::DELETE, Some("g".into()), 0, false),
+        ]
+    );
+
+    // masks filter the events, and removed watches report nothing
+    ramfs.unsubscribe(src).unwrap();
+    assert_eq!(ramfs.unsubscribe(src), Err(VfsError::InvalidInput));
+    let creates = ramfs
+        .subscribe("dst", WatchMask::CREATE, sink.clone())
+        .unwrap();
+    ramfs.unsubscribe(dst).unwrap();
+    root.create("src/x", VfsNodeType::File).unwrap();
+    root.create("dst/y", VfsNodeType::File).unwrap();
+    root.remove("dst/y").unwrap();
+    assert_eq!(
+        sink.take(),
+        [(creates, WatchMask::CREATE, Some("y".into()), 0, false)]
+    );
+}
+
+#[test]
+fn test_operation_trace() {
+    use axfs_vfs::trace::{self, hash_path, TraceOp, TraceRing};
+
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    let ring = Arc::new(TraceRing::new(1024));
+    trace::set_global_ring(Some(ring.clone()));
+    root.create("trace_dir", VfsNodeType::Dir).unwrap();
+    root.create("trace_dir/trace_file", VfsNodeType::File)
+        .unwrap();
+    let file = root.clone().lookup("trace_dir/trace_file").unwrap();
+    file.write_at(0, b"data").unwrap();
+    assert!(root.clone().lookup("trace_dir/trace_missing").is_err());
+    root.rename("trace_dir/trace_file", "trace_dir/trace_moved")
+        .unwrap();
+    assert_eq!(root.remove("trace_dir"), Err(VfsError::DirectoryNotEmpty));
+    trace::set_global_ring(None);
+
+    // other tests run concurrently, so only the records of this one are
+    // checked
+    let names = ["trace_dir", "trace_file", "trace_missing", "trace_moved"].map(hash_path);
+    let file_ino = file.get_attr().unwrap().ino();
+    let ops: Vec<_> = ring
+        .records()
+        .into_iter()
+        .filter(|r| names.contains(&r.path_hash) || r.ino == file_ino && r.path_hash == 0)
+        .map(|r| (r.op, r.result))
+        .collect();
+    assert_eq!(
+        ops,
+        [
+            (TraceOp::Create, Ok(())),
+            (TraceOp::Create, Ok(())),
+            (TraceOp::Lookup, Ok(())),
+            (TraceOp::Write, Ok(())),
+            (TraceOp::Lookup, Err(VfsError::NotFound)),
+            // the parent directories of both paths
+            (TraceOp::Lookup, Ok(())),
+            (TraceOp::Lookup, Ok(())),
+            (TraceOp::Rename, Ok(())),
+            (TraceOp::Remove, Err(VfsError::DirectoryNotEmpty)),
+        ]
+    );
+    let mut dump = String::new();
+    ring.dump(&mut dump).unwrap();
+    assert!(dump.starts_with("vfs trace: "));
+}
+
+#[test]
+fn test_context_permissions() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    let alice = VfsContext::new(Credentials::new(1000, 1000), 0o077);
+    let bob = VfsContext::new(Credentials::new(1001, 1001), 0o022);
+
+    // the root directory is owned by root with mode 0o755
+    assert_eq!(
+        root.create_ctx(Some(&alice), "a", VfsNodeType::Dir),
+        Err(VfsError::PermissionDenied)
+    );
+    root.create_ctx(None, "home", VfsNodeType::Dir).unwrap();
+    let home = root.clone().lookup("home").unwrap();
+    home.set_attr(&SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o777)))
+        .unwrap();
+
+    // new nodes belong to the caller, with its umask applied
+    root.create_ctx(Some(&alice), "home/alice", VfsNodeType::Dir)
+        .unwrap();
+    root.create_ctx(Some(&alice), "/home/alice/notes", VfsNodeType::File)
+        .unwrap();
+    let dir = root
+        .clone()
+        .lookup("home/alice")
+        .unwrap()
+        .get_attr()
+        .unwrap();
+    assert_eq!(
+        (dir.uid(), dir.gid(), dir.perm().mode()),
+        (1000, 1000, 0o700)
+    );
+    let notes = root
+        .clone()
+        .lookup_ctx(Some(&alice), "home/alice/notes")
+        .unwrap()
+        .get_attr()
+        .unwrap();
+    assert_eq!((notes.uid(), notes.perm().mode()), (1000, 0o600));
+
+    // others may not search, write or remove in the directory of alice
+    assert_eq!(
+        root.clone()
+            .lookup_ctx(Some(&bob), "home/alice/notes")
+            .err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.create_ctx(Some(&bob), "home/alice/x", VfsNodeType::File),
+        Err(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.remove_ctx(Some(&bob), "home/alice/notes"),
+        Err(VfsError::PermissionDenied)
+    );
+    assert!(root.clone().lookup_ctx(None, "home/alice/notes").is_ok());
+
+    root.remove_ctx(Some(&alice), "home/alice/notes").unwrap();
+    assert_eq!(
+        root.clone().lookup("home/alice/notes").err(),
+        Some(VfsError::NotFound)
+    );
+    root.remove_ctx(Some(&VfsContext::root()), "home/alice")
+        .unwrap();
+}
+
+/// Gives the context set by each test thread.
+struct ThreadContext;
+
+thread_local! {
+    static CONTEXT: std::cell::RefCell<Option<VfsContext>> = const { std::cell::RefCell::new(None) };
+}
+
+impl axfs_vfs::ContextProvider for ThreadContext {
+    fn current(&self) -> Option<VfsContext> {
+        CONTEXT.with(|ctx| ctx.borrow().clone())
+    }
+}
+
+fn set_task_context(ctx: Option<VfsContext>) {
+    axfs_vfs::set_context_provider(Some(Arc::new(ThreadContext)));
+    CONTEXT.with(|cur| *cur.borrow_mut() = ctx);
+}
+
+#[test]
+#[cfg(feature = "enforce-perms")]
+fn test_task_context() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("tmp", VfsNodeType::Dir).unwrap();
+    let tmp = root.clone().lookup("tmp").unwrap();
+    tmp.set_attr(&SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o777)))
+        .unwrap();
+
+    // operations without context are checked against the current task
+    set_task_context(Some(VfsContext::new(Credentials::new(1000, 1000), 0o077)));
+    assert_eq!(
+        root.create("etc", VfsNodeType::Dir),
+        Err(VfsError::PermissionDenied)
+    );
+    root.create("tmp/private", VfsNodeType::Dir).unwrap();
+    root.create("tmp/private/key", VfsNodeType::File).unwrap();
+    let key = root.clone().lookup("tmp/private/key").unwrap();
+    let attr = key.get_attr().unwrap();
+    assert_eq!((attr.uid(), attr.perm().mode()), (1000, 0o600));
+
+    set_task_context(Some(VfsContext::new(Credentials::new(1001, 1001), 0o022)));
+    assert_eq!(
+        root.clone().lookup("tmp/private/key").err(),
+        Some(VfsError::PermissionDenied)
+    );
+    assert_eq!(
+        root.remove("tmp/private/key"),
+        Err(VfsError::PermissionDenied)
+    );
+
+    // capabilities bypass the permission bits
+    let cred = Credentials::new(1001, 1001).with_caps(axfs_vfs::Capabilities::DAC_READ_SEARCH);
+    set_task_context(Some(VfsContext::new(cred, 0o022)));
+    assert!(root.clone().lookup("tmp/private/key").is_ok());
+    assert_eq!(
+        root.remove("tmp/private/key"),
+        Err(VfsError::PermissionDenied)
+    );
+
+    // tasks without context, such as the kernel, are not checked
+    set_task_context(None);
+    root.remove("tmp/private/key").unwrap();
+    root.remove("tmp/private").unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "enforce-perms"))]
+fn test_task_context_ignored() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+
+    // without the `enforce-perms` feature, the current task is not checked
+    set_task_context(Some(VfsContext::new(Credentials::new(1000, 1000), 0o077)));
+    root.create("etc", VfsNodeType::Dir).unwrap();
+    root.create("etc/key", VfsNodeType::File).unwrap();
+    let key = root.clone().lookup("etc/key").unwrap();
+    assert_eq!(key.get_attr().unwrap().uid(), 0);
+    key.set_attr(&SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o600)))
+        .unwrap();
+
+    set_task_context(Some(VfsContext::new(Credentials::new(1001, 1001), 0o022)));
+    assert!(root.clone().lookup("etc/key").is_ok());
+    root.remove("etc/key").unwrap();
+
+    set_task_context(None);
+    root.remove("etc").unwrap();
+}
+
+#[test]
+fn test_path_canonicalize() {
+    use axfs_vfs::path::canonicalize;
+
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("usr", VfsNodeType::Dir).unwrap();
+    root.create("usr/lib", VfsNodeType::Dir).unwrap();
+    root.create("usr/lib/libc.so", VfsNodeType::File).unwrap();
+    root.create_symlink("lib", "usr/lib").unwrap();
+    root.create_symlink("usr/abs", "/usr/lib/libc.so").unwrap();
+    root.create_symlink("loop", "loop").unwrap();
+
+    assert_eq!(canonicalize(&root, "/").unwrap(), "/");
+    assert_eq!(
+        canonicalize(&root, "lib/./libc.so").unwrap(),
+        "/usr/lib/libc.so"
+    );
+    // `..` applies to the target of the link, unlike with `normalize`
+    assert_eq!(canonicalize(&root, "/lib/..").unwrap(), "/usr");
+    assert_eq!(axfs_vfs::path::normalize("/lib/.."), "/");
+    assert_eq!(canonicalize(&root, "usr/abs").unwrap(), "/usr/lib/libc.so");
+    assert_eq!(canonicalize(&root, "/../usr//lib/").unwrap(), "/usr/lib");
+
+    assert_eq!(canonicalize(&root, "usr/nope"), Err(VfsError::NotFound));
+    assert_eq!(
+        canonicalize(&root, "usr/abs/x"),
+        Err(VfsError::NotADirectory)
+    );
+    assert_eq!(canonicalize(&root, "loop"), Err(VfsError::FilesystemLoop));
+}
+
+#[test]
+fn test_policy_resolves_symlinks() {
+    use axfs_vfs::policy::{check_policy, check_resolved, AccessOp, WritePrefixPolicy};
+    use axfs_vfs::Credentials;
+
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("etc", VfsNodeType::Dir).unwrap();
+    root.create("etc/passwd", VfsNodeType::File).unwrap();
+    root.create("tmp", VfsNodeType::Dir).unwrap();
+    root.create_symlink("tmp/link", "/etc").unwrap();
+
+    let policy = WritePrefixPolicy::new(["/tmp"]);
+    let cred = Credentials::new(1000, 1000);
+    let check = |path, op| check_resolved(Some(&policy), &root, path, op, &cred);
+
+    // the lexical check is fooled by the link, not the resolved one
+    assert_eq!(
+        check_policy(Some(&policy), "/tmp/link/passwd", AccessOp::Write, &cred),
+        Ok(())
+    );
+    let denied = Err(VfsError::PermissionDenied);
+    assert_eq!(check("/tmp/link/passwd", AccessOp::Write), denied);
+    assert_eq!(check("/tmp/link/passwd", AccessOp::SetAttr), denied);
+    assert_eq!(check("/tmp/link/new", AccessOp::Create), denied);
+    assert_eq!(check("tmp/link/passwd", AccessOp::Remove), denied);
+    assert_eq!(check("/tmp/link/passwd", AccessOp::Read), Ok(()));
+
+    // the entry operations apply to the link itself
+    assert_eq!(check("/tmp/link", AccessOp::Remove), Ok(()));
+    assert_eq!(check("/tmp/link", AccessOp::Rename), Ok(()));
+    assert_eq!(check("/tmp/new", AccessOp::Create), Ok(()));
+    assert_eq!(check("/tmp/link", AccessOp::Write), denied);
+    assert_eq!(
+        check("/tmp/nope/new", AccessOp::Create),
+        Err(VfsError::NotFound)
+    );
+    assert_eq!(
+        check_resolved(None, &root, "/nope", AccessOp::Write, &cred),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_tree_digest() {
+    use axfs_vfs::digest::tree_digest;
+
+    let build = |names: &[&str]| {
+        let fs = RamFileSystem::new();
+        let root = fs.root_dir();
+        for name in names {
+            let (name, ty) = match name.strip_suffix('/') {
+                Some(dir) => (dir, VfsNodeType::Dir),
+                None => (*name, VfsNodeType::File),
+            };
+            root.create(name, ty).unwrap();
+            if ty == VfsNodeType::File {
+                let file = root.clone().lookup(name).unwrap();
+                file.write_at(0, name.as_bytes()).unwrap();
+            }
+        }
+        root.create_symlink("link", "a/b").unwrap();
+        fs
+    };
+
+    let fs1 = build(&["a/", "a/b", "c", "d/"]);
+    let fs2 = build(&["d/", "c", "a/", "a/b"]);
+    let digest = tree_digest(&fs1.root_dir()).unwrap();
+    assert_eq!(digest, tree_digest(&fs2.root_dir()).unwrap());
+    assert_eq!(digest.to_string().len(), 16);
+
+    // contents, names, types and link targets are all covered
+    let file = fs2.root_dir().lookup("c").unwrap();
+    file.write_at(0, b"C").unwrap();
+    assert_ne!(digest, tree_digest(&fs2.root_dir()).unwrap());
+    file.write_at(0, b"c").unwrap();
+    assert_eq!(digest, tree_digest(&fs2.root_dir()).unwrap());
+
+    let fs3 = build(&["a/", "a/b", "c", "e/"]);
+    assert_ne!(digest, tree_digest(&fs3.root_dir()).unwrap());
+    let fs4 = build(&["a/", "a/b", "c", "d"]);
+    assert_ne!(digest, tree_digest(&fs4.root_dir()).unwrap());
+    let fs5 = build(&["a/", "a/b", "c", "d/"]);
+    fs5.root_dir().remove("link").unwrap();
+    fs5.root_dir().create_symlink("link", "a/c").unwrap();
+    assert_ne!(digest, tree_digest(&fs5.root_dir()).unwrap());
+
+    // a subtree has its own digest, independent of its location
+    let a1 = tree_digest(&fs1.root_dir().lookup("a").unwrap()).unwrap();
+    let a3 = tree_digest(&fs3.root_dir().lookup("a").unwrap()).unwrap();
+    assert_eq!(a1, a3);
+}
+
+#[test]
+fn test_rename_flags() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    let read = |path: &str| {
+        let mut buf = [0; 8];
+        let n = root
+            .clone()
+            .lookup(path)
+            .unwrap()
+            .read_at(0, &mut buf)
+            .unwrap();
+        buf[..n].to_vec()
+    };
+    root.create("a", VfsNodeType::File).unwrap();
+    root.create("b", VfsNodeType::File).unwrap();
+    root.create("d", VfsNodeType::Dir).unwrap();
+    root.create("d/sub", VfsNodeType::Dir).unwrap();
+    root.clone().lookup("a").unwrap().write_at(0, b"A").unwrap();
+    root.clone().lookup("b").unwrap().write_at(0, b"B").unwrap();
+
+    assert_eq!(
+        root.rename_flags("a", "b", RenameFlags::NOREPLACE),
+        Err(VfsError::AlreadyExists)
+    );
+    assert_eq!(
+        root.rename_flags("a", "b", RenameFlags::all()),
+        Err(VfsError::InvalidInput)
+    );
+    root.rename_flags("a", "c", RenameFlags::NOREPLACE).unwrap();
+    assert_eq!(read("c"), b"A");
+
+    // exchange swaps the nodes, even of different types
+    root.rename_flags("b", "c", RenameFlags::EXCHANGE).unwrap();
+    assert_eq!((read("b"), read("c")), (b"A".to_vec(), b"B".to_vec()));
+    root.rename_flags("c", "d/sub", RenameFlags::EXCHANGE)
+        .unwrap();
+    assert!(root
+        .clone()
+        .lookup("c")
+        .unwrap()
+        .get_attr()
+        .unwrap()
+        .is_dir());
+    assert_eq!(read("d/sub"), b"B");
+    let c = root.clone().lookup("c").unwrap();
+    assert_eq!(
+        c.parent().unwrap().get_attr().unwrap().ino(),
+        root.get_attr().unwrap().ino()
+    );
+    assert_eq!(
+        root.rename_flags("b", "missing", RenameFlags::EXCHANGE),
+        Err(VfsError::NotFound)
+    );
+
+    // a directory cannot be exchanged with one of its descendants
+    root.create("c/inner", VfsNodeType::File).unwrap();
+    assert_eq!(
+        root.rename_flags("c", "c/inner", RenameFlags::EXCHANGE),
+        Err(VfsError::InvalidInput)
+    );
+    assert_eq!(
+        root.rename_flags("c/inner", "c", RenameFlags::EXCHANGE),
+        Err(VfsError::InvalidInput)
+    );
+    assert_eq!(
+        root.rename_flags("b", "c", RenameFlags::empty()),
+        Err(VfsError::IsADirectory)
+    );
+}
+
+#[test]
+fn test_tmpfile() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    let dir = root.clone().lookup("dir").unwrap();
+
+    // the file has no name until it is linked
+    let tmp = dir.create_tmpfile().unwrap();
+    tmp.write_at(0, b"draft").unwrap();
+    let attr = tmp.get_attr().unwrap();
+    assert_eq!(attr.nlink(), 0);
+    assert_eq!(fs.open_by_ino(attr.ino()).err(), Some(VfsError::NotFound));
+    let entries = root.clone().lookup_as::<DirNode>("dir").unwrap();
+    assert!(entries.get_entries().is_empty());
+
+    tmp.clone().link_into(&dir, "final").unwrap();
+    let file = root.clone().lookup("dir/final").unwrap();
+    assert!(Arc::ptr_eq(&file, &tmp));
+    assert_eq!(tmp.get_attr().unwrap().nlink(), 1);
+    assert!(fs.open_by_ino(attr.ino()).is_ok());
+    assert_eq!(
+        tmp.clone().link_into(&dir, "final").err(),
+        Some(VfsError::AlreadyExists)
+    );
+    tmp.clone().link_into(&root, "dir/copy").unwrap();
+    assert_eq!(tmp.get_attr().unwrap().nlink(), 2);
+
+    // files that lost all their names cannot be linked again
+    root.remove("dir/final").unwrap();
+    root.remove("dir/copy").unwrap();
+    assert_eq!(
+        tmp.clone().link_into(&dir, "again").err(),
+        Some(VfsError::NotFound)
+    );
+    assert_eq!(
+        tmp.clone().link_into(&tmp, "x").err(),
+        Some(VfsError::NotADirectory)
+    );
+    let other = RamFileSystem::new();
+    assert_eq!(
+        dir.create_tmpfile()
+            .unwrap()
+            .link_into(&other.root_dir(), "x")
+            .err(),
+        Some(VfsError::CrossesDevices)
+    );
+
+    // the content of files never linked is freed with them
+    drop((tmp, file));
+    fs.set_size_limit(Some(4 * 4096));
+    let tmp = dir.create_tmpfile().unwrap();
+    tmp.write_at(0, &[1; 8192]).unwrap();
+    assert_eq!(fs.statfs().unwrap().blocks_free(), 2);
+    drop(tmp);
+    assert_eq!(fs.statfs().unwrap().blocks_free(), 4);
+
+    root.create("file", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("file").unwrap();
+    assert_eq!(file.create_tmpfile().err(), Some(VfsError::NotADirectory));
+}
+
+#[test]
+fn test_lookup_step() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("mnt", VfsNodeType::Dir).unwrap();
+    root.create("mnt/hidden", VfsNodeType::File).unwrap();
+    let mnt = root.clone().lookup("mnt").unwrap();
+
+    let usb = RamFileSystem::new();
+    usb.root_dir().create("notes", VfsNodeType::Dir).unwrap();
+    usb.root_dir().create("notes/a", VfsNodeType::File).unwrap();
+    assert_eq!(root.clone().lookup_step("..").unwrap().1, 0);
+    usb.mount("/mnt", mnt.clone(), &MountOptions::new())
+        .unwrap();
+    mnt.set_mount_point(true).unwrap();
+    assert!(mnt.is_mount_point());
+
+    // the walk stops at the mount point, which the mount manager enters
+    let (node, consumed) = root.clone().lookup_step("./mnt//notes/a").unwrap();
+    assert!(Arc::ptr_eq(&node, &mnt));
+    assert_eq!(consumed, 2);
+    let (node, consumed) = usb.root_dir().lookup_step("notes/a").unwrap();
+    assert_eq!(consumed, 2);
+    assert!(node.get_attr().unwrap().is_file());
+
+    // `..` leaves the mounted filesystem for the parent of the mount point
+    let (node, consumed) = usb.root_dir().lookup_step("../mnt/x").unwrap();
+    assert!(Arc::ptr_eq(&node, &mnt));
+    assert_eq!(consumed, 2);
+    let (node, consumed) = root.clone().lookup_step("/").unwrap();
+    assert!(Arc::ptr_eq(&node, &root));
+    assert_eq!(consumed, 1);
+    assert_eq!(
+        root.clone().lookup_step("missing/mnt").err(),
+        Some(VfsError::NotFound)
+    );
+
+    // mount points are busy
+    assert_eq!(root.remove("mnt/hidden"), Ok(()));
+    assert_eq!(root.remove("mnt"), Err(VfsError::ResourceBusy));
+    assert_eq!(root.rename("mnt", "media"), Err(VfsError::ResourceBusy));
+    mnt.set_mount_point(false).unwrap();
+    assert_eq!(root.remove("mnt"), Ok(()));
+}
+
+#[test]
+fn test_open_options() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("etc", VfsNodeType::Dir).unwrap();
+
+    assert_eq!(
+        OpenOptions::new().read(true).open(&root, "etc/motd").err(),
+        Some(VfsError::NotFound)
+    );
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .open(&root, "etc/motd")
+        .unwrap();
+    file.write(b"hello world").unwrap();
+    drop(file);
+
+    let log = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&root, "etc/motd")
+        .unwrap();
+    log.write(b"!").unwrap();
+    drop(log);
+    let mut buf = [0; 16];
+    let file = OpenOptions::new()
+        .read(true)
+        .open(&root, "etc/motd")
+        .unwrap();
+    assert_eq!(file.read(&mut buf), Ok(12));
+    assert_eq!(&buf[..12], b"hello world!");
+    drop(file);
+
+    OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(&root, "etc/motd")
+        .unwrap();
+    assert_eq!(
+        root.clone()
+            .lookup("etc/motd")
+            .unwrap()
+            .get_attr()
+            .unwrap()
+            .size(),
+        0
+    );
+
+    let mut exclusive = OpenOptions::new();
+    exclusive.write(true).create_new(true);
+    assert_eq!(
+        exclusive.open(&root, "etc/motd").err(),
+        Some(VfsError::AlreadyExists)
+    );
+    assert!(exclusive.open(&root, "etc/issue").is_ok());
+    assert_eq!(
+        OpenOptions::new()
+            .read(true)
+            .create(true)
+            .open(&root, "etc/x")
+            .err(),
+        Some(VfsError::InvalidInput)
+    );
+}
+
+#[test]
+fn test_export_handles() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("exports", VfsNodeType::Dir).unwrap();
+    root.create("exports/data", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("exports/data").unwrap();
+    file.write_at(0, b"payload").unwrap();
+
+    let fh = fs.encode_fh(&file).unwrap();
+    assert_eq!(fh.ino(), file.get_attr().unwrap().ino());
+    assert_eq!(fh.generation(), file.generation());
+    let bytes = fh.encode();
+    drop(file);
+
+    // the handle follows renames, without keeping a path
+    root.rename("exports/data", "moved").unwrap();
+    let node = fs
+        .open_by_handle(&ExportHandle::decode(&bytes).unwrap())
+        .unwrap();
+    let mut buf = [0; 7];
+    node.read_at(0, &mut buf).unwrap();
+    assert_eq!(&buf, b"payload");
+    drop(node);
+
+    // a filesystem created later does not resolve the handles of this one
+    let other = RamFileSystem::new();
+    other.root_dir().create("a", VfsNodeType::Dir).unwrap();
+    other.root_dir().create("b", VfsNodeType::File).unwrap();
+    assert_ne!(other.root_dir().generation(), root.generation());
+    assert!(other.open_by_ino(fh.ino()).is_ok());
+    assert_eq!(other.open_by_handle(&fh).err(), Some(VfsError::NotFound));
+
+    root.remove("moved").unwrap();
+    assert_eq!(fs.open_by_handle(&fh).err(), Some(VfsError::NotFound));
+}
+
+#[test]
+fn test_get_attr_ext() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("a", VfsNodeType::Dir).unwrap();
+    root.create("b", VfsNodeType::Dir).unwrap();
+    root.create("f", VfsNodeType::File).unwrap();
+
+    let ext = root.get_attr_ext(AttrMask::INO | AttrMask::MTIME).unwrap();
+    assert!(ext.attr().is_dir());
+    assert!(!ext.mask().contains(AttrMask::NLINK));
+    assert_eq!(ext.nlink(), None);
+    assert_eq!(ext.ino(), Some(root.get_attr().unwrap().ino()));
+    assert_eq!(ext.mtime(), Some(root.get_attr().unwrap().mtime()));
+
+    let ext = root.get_attr_ext(AttrMask::NLINK).unwrap();
+    assert_eq!(ext.nlink(), Some(4));
+
+    // the default implementation returns everything
+    let file = root.clone().lookup("f").unwrap();
+    file.write_at(0, b"abc").unwrap();
+    let ext = file.get_attr_ext(AttrMask::SIZE).unwrap();
+    assert_eq!(ext.mask(), AttrMask::BASIC_STATS);
+    assert_eq!(ext.size(), Some(3));
+    assert_eq!(ext.nlink(), Some(1));
+}
+
+#[test]
+fn test_whiteout() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    root.create("upper", VfsNodeType::Dir).unwrap();
+    root.create("upper/deleted", VfsNodeType::Whiteout).unwrap();
+    root.create("upper/kept", VfsNodeType::File).unwrap();
+
+    let node = root.clone().lookup("upper/deleted").unwrap();
+    let attr = node.get_attr().unwrap();
+    assert!(attr.file_type().is_whiteout());
+    assert_eq!(attr.file_type().as_char(), 'w');
+    assert_eq!(
+        node.open(OpenFlags::READ).err(),
+        Some(VfsError::NoSuchDevice)
+    );
+
+    let upper = root.clone().lookup("upper").unwrap();
+    let mut dirents: Vec<_> = (0..4).map(|_| VfsDirEntry::default()).collect();
+    let n = upper.read_dir(0, &mut dirents).unwrap();
+    let types: Vec<_> = dirents[..n]
+        .iter()
+        .map(|e| (e.name().to_string(), e.entry_type()))
+        .collect();
+    assert!(types.contains(&("deleted".to_string(), VfsNodeType::Whiteout)));
+    assert!(types.contains(&("kept".to_string(), VfsNodeType::File)));
+
+    // linux_dirent64 records carry DT_WHT
+    let mut buf = [0u8; 256];
+    let (len, _) = upper.read_dir_buf(0, &mut buf).unwrap();
+    let mut pos = 0;
+    let mut found = false;
+    while pos < len {
+        let reclen = u16::from_le_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+        if buf[pos + 19..pos + reclen].starts_with(b"deleted\0") {
+            assert_eq!(buf[pos + 18], 14);
+            found = true;
+        }
+        pos += reclen;
+    }
+    assert!(found);
+
+    root.remove("upper/deleted").unwrap();
+    assert_eq!(
+        root.clone().lookup("upper/deleted").err(),
+        Some(VfsError::NotFound)
+    );
+}
+
+#[test]
+fn test_conformance() {
+    axfs_vfs_testsuite::run(&RamFileSystem::new());
+}
+
+#[test]
+fn test_umask() {
+    let fs = RamFileSystem::new();
+    let root = fs.root_dir();
+    assert_eq!(fs.umask(), None);
+    root.create("plain", VfsNodeType::File).unwrap();
+    let perm = |path: &str| {
+        root.clone()
+            .lookup(path)
+            .unwrap()
+            .get_attr()
+            .unwrap()
+            .perm()
+    };
+    assert_eq!(perm("plain").mode(), 0o666);
+
+    fs.set_umask(Some(Umask::new(0o027)));
+    assert_eq!(fs.umask(), Some(Umask::new(0o027)));
+    root.create("file", VfsNodeType::File).unwrap();
+    root.create("dir", VfsNodeType::Dir).unwrap();
+    root.mknod("dev", VfsNodeType::CharDevice, DeviceId::new(1, 3))
+        .unwrap();
+    assert_eq!(perm("file").mode(), 0o640);
+    assert_eq!(perm("dir").mode(), 0o750);
+    assert_eq!(perm("dev").mode(), 0o640);
+    let tmp = root.create_tmpfile().unwrap();
+    assert_eq!(tmp.get_attr().unwrap().perm().mode(), 0o640);
+
+    // the umask of a context takes precedence
+    let ctx = VfsContext::new(Credentials::root(), 0o077);
+    root.create_ctx(Some(&ctx), "private", VfsNodeType::File)
+        .unwrap();
+    assert_eq!(perm("private").mode(), 0o600);
+
+    // existing nodes keep their permissions
+    fs.set_umask(None);
+    assert_eq!(perm("file").mode(), 0o640);
+    root.create("again", VfsNodeType::File).unwrap();
+    assert_eq!(perm("again").mode(), 0o666);
+}
+
+#[test]
+fn test_atime_policy() {
+    let secs = Duration::from_secs;
+    let clock = Arc::new(ManualClock::new(secs(1_000)));
+    let fs = RamFileSystem::with_clock(clock.clone());
+    let root = fs.root_dir();
+    root.create("file.txt", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("file.txt").unwrap();
+    file.write_at(0, b"data").unwrap();
+    let atime = || file.get_attr().unwrap().atime();
+    let mut buf = [0; 4];
+
+    // every read updates the access time by default
+    assert_eq!(fs.atime_policy(), AtimePolicy::Strict);
+    clock.advance(secs(1));
+    file.read_at(0, &mut buf).unwrap();
+    assert_eq!(atime(), secs(1_001));
+
+    // remounting without a policy keeps it
+    fs.remount(&MountOptions::parse("size=1m").unwrap())
+        .unwrap();
+    assert_eq!(fs.atime_policy(), AtimePolicy::Strict);
+
+    // only the first read after a change updates it with relatime
+    fs.remount(&MountOptions::parse("relatime").unwrap())
+        .unwrap();
+    assert_eq!(fs.atime_policy(), AtimePolicy::Relative);
+    clock.advance(secs(1));
+    file.read_at(0, &mut buf).unwrap();
+    assert_eq!(atime(), secs(1_001));
+    clock.advance(secs(1));
+    file.write_at(0, b"more").unwrap();
+    clock.advance(secs(1));
+    file.read_at(0, &mut buf).unwrap();
+    assert_eq!(atime(), secs(1_004));
+    clock.advance(secs(1));
+    file.read_at(0, &mut buf).unwrap();
+    assert_eq!(atime(), secs(1_004));
+    clock.advance(AtimePolicy::RELATIVE_PERIOD);
+    file.read_at(0, &mut buf).unwrap();
+    assert_eq!(atime(), clock.now());
+
+    // reads never update it with noatime
+    fs.set_atime_policy(AtimePolicy::Never);
+    clock.advance(secs(1));
+    file.write_at(0, b"last").unwrap();
+    file.read_at(0, &mut buf).unwrap();
+    assert!(atime() < clock.now());
+}
@@ -4,8 +4,13 @@
 //! of axfs_ramfs in a simulated operating system environment.
 //! These tests verify the behavior of RAM filesystem in real-world scenarios.
 
+use std::sync::Arc;
+
+use axfs_devfs::{DeviceFileSystem, NullDev, ZeroDev};
 use axfs_ramfs::{DirNode, RamFileSystem};
-use axfs_vfs::{VfsDirEntry, VfsNodeType, VfsOps};
+use axfs_vfs::{
+    DeviceId, MountOptions, OpenFlags, VfsDirEntry, VfsError, VfsNodeRefExt, VfsNodeType, VfsOps,
+};
 
 // ============== System-Level Integration Tests ==============
 
@@ -18,7 +23,7 @@ fn test_system_complete_filesystem_lifecycle() {
 
     // Mount
     let root = fs.root_dir();
-    fs.mount("/", root.clone()).unwrap();
+    fs.mount("/", root.clone(), &MountOptions::new()).unwrap();
 
     // Create directory structure
     root.create("home", VfsNodeType::Dir).unwrap();
@@ -506,7 +511,7 @@ fn test_system_directory_listing_consistency() {
     let root_node = fs.root_dir_node();
     assert!(root_node.exist("test"));
 
-    let test_node = test_dir.as_any().downcast_ref::<DirNode>().unwrap();
+    let test_node = test_dir.downcast_ref::<DirNode>().unwrap();
     let entries1 = test_node.get_entries();
     assert_eq!(entries1.len(), 3);
 
@@ -601,3 +606,129 @@ fn test_system_attributes_consistency() {
     let dir_attr2 = dir.get_attr().unwrap();
     assert_eq!(dir_attr2.size(), dir_attr1.size());
 }
+
+#[test]
+fn test_system_ramfs_dev_directory() {
+    // A ramfs-based /dev populated by an archive extractor, dispatching to
+    // the drivers registered in devfs, like devtmpfs
+    let devfs = DeviceFileSystem::new();
+    devfs
+        .add_device("null", DeviceId::new(1, 3), Arc::new(NullDev))
+        .unwrap();
+    devfs
+        .add_device("zero", DeviceId::new(1, 5), Arc::new(ZeroDev))
+        .unwrap();
+
+    let fs = RamFileSystem::new();
+    fs.set_device_resolver(Some(devfs.registry()));
+    let root = fs.root_dir();
+    root.create("dev", VfsNodeType::Dir).unwrap();
+    root.mknod("dev/null", VfsNodeType::CharDevice, DeviceId::new(1, 3))
+        .unwrap();
+    root.mknod("dev/zero", VfsNodeType::CharDevice, DeviceId::new(1, 5))
+        .unwrap();
+    root.mknod("dev/sda", VfsNodeType::BlockDevice, DeviceId::new(8, 0))
+        .unwrap();
+    assert_eq!(
+        root.mknod("dev/fifo", VfsNodeType::Fifo, DeviceId::new(0, 0)),
+        Err(VfsError::InvalidInput)
+    );
+    assert_eq!(
+        root.mknod("dev/null", VfsNodeType::CharDevice, DeviceId::new(1, 3)),
+        Err(VfsError::AlreadyExists)
+    );
+
+    let zero = root.clone().lookup("dev/zero").unwrap();
+    assert_eq!(
+        zero.get_attr().unwrap().file_type(),
+        VfsNodeType::CharDevice
+    );
+    zero.open(OpenFlags::READ).unwrap();
+    let mut buf = [1; 16];
+    assert_eq!(zero.read_at(0, &mut buf), Ok(16));
+    assert_eq!(buf, [0; 16]);
+
+    let null = root.clone().lookup("dev/null").unwrap();
+    assert_eq!(null.write_at(0, b"discarded"), Ok(9));
+    assert_eq!(null.read_at(0, &mut buf), Ok(0));
+
+    // no driver for the disk
+    let sda = root.clone().lookup("dev/sda").unwrap();
+    assert_eq!(
+        sda.open(OpenFlags::READ).err(),
+        Some(VfsError::NoSuchDevice)
+    );
+
+    // device nodes are listed with their type
+    let mut dirents = [const { VfsDirEntry::default() }; 8];
+    let n = root
+        .clone()
+        .lookup("dev")
+        .unwrap()
+        .read_dir(0, &mut dirents)
+        .unwrap();
+    let sda = dirents[..n]
+        .iter()
+        .find(|e| e.name_as_bytes() == b"sda")
+        .unwrap();
+    assert_eq!(sda.entry_type(), VfsNodeType::BlockDevice);
+    root.remove("dev/sda").unwrap();
+}
+
+#[test]
+fn test_system_cwd_across_mounts() {
+    use axfs_vfs::cwd::{Cwd, MountTable};
+    use axfs_vfs::VfsNodeRef;
+
+    struct Mounts(Vec<(&'static str, RamFileSystem)>);
+
+    impl MountTable for Mounts {
+        fn root(&self) -> VfsNodeRef {
+            self.0[0].1.root_dir()
+        }
+
+        fn mounted_at(&self, path: &str) -> Option<VfsNodeRef> {
+            let (_, fs) = self.0[1..].iter().rev().find(|(at, _)| *at == path)?;
+            Some(fs.root_dir())
+        }
+    }
+
+    let rootfs = RamFileSystem::new();
+    let root = rootfs.root_dir();
+    root.create("mnt", VfsNodeType::Dir).unwrap();
+    root.create("mnt/usb", VfsNodeType::Dir).unwrap();
+    root.create("mnt/notes", VfsNodeType::File).unwrap();
+    let usb = RamFileSystem::new();
+    usb.root_dir().create("photos", VfsNodeType::Dir).unwrap();
+    usb.root_dir()
+        .create("photos/cat.jpg", VfsNodeType::File)
+        .unwrap();
+    let mounts = Mounts(vec![("/", rootfs), ("/mnt/usb", usb)]);
+
+    let cwd = Cwd::root(&mounts).chdir(&mounts, "mnt/usb/photos").unwrap();
+    assert_eq!(cwd.path(), "/mnt/usb/photos");
+    let (cat, path) = cwd.lookup(&mounts, "./cat.jpg").unwrap();
+    assert_eq!(path, "/mnt/usb/photos/cat.jpg");
+    assert!(cat.get_attr().unwrap().is_file());
+
+    // `..` leaves the mounted filesystem at its root
+    let (usb_root, path) = cwd.lookup(&mounts, "..").unwrap();
+    assert_eq!(path, "/mnt/usb");
+    assert!(Arc::ptr_eq(&usb_root, &mounts.0[1].1.root_dir()));
+    let up = cwd.chdir(&mounts, "../..").unwrap();
+    assert_eq!(up.path(), "/mnt");
+    assert!(up.lookup(&mounts, "notes").is_ok());
+    assert_eq!(cwd.lookup(&mounts, "../../notes").unwrap().1, "/mnt/notes");
+    assert_eq!(cwd.chdir(&mounts, "../../../../..").unwrap().path(), "/");
+
+    assert_eq!(
+        cwd.chdir(&mounts, "cat.jpg").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        cwd.lookup(&mounts, "../missing").err(),
+        Some(VfsError::NotFound)
+    );
+    // absolute paths ignore the working directory
+    assert_eq!(cwd.chdir(&mounts, "/mnt/usb").unwrap().path(), "/mnt/usb");
+}
@@ -0,0 +1,354 @@
+//! Multi-threaded stress tests for axfs_ramfs
+//!
+//! Several RAM filesystems are mounted over subdirectories of a device
+//! filesystem, and many threads run randomized workloads on them (creating,
+//! writing, truncating, reading, deleting and moving nodes). Each thread
+//! checks its private directory against a model of what it did; all threads
+//! also race on a shared directory, where only consistency is checked, and
+//! move directories across each other in a shared tree, where no directory
+//! may get lost.
+//!
+//! `test_stress_smoke` runs a short fixed workload and is part of the normal
+//! test suite. `test_stress_soak` runs for `AXFS_STRESS_SECS` seconds
+//! (default 60) and is ignored by default, run it for release validation
+//! with:
+//!
+//! ```text
+//! AXFS_STRESS_SECS=600 cargo test -p axfs_ramfs --test stress_test -- --ignored
+//! ```
+
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use axfs_devfs::DeviceFileSystem;
+use axfs_ramfs::RamFileSystem;
+use axfs_vfs::{DirEntries, MountOptions, RenameFlags, VfsError, VfsNodeRef, VfsNodeType, VfsOps};
+
+const NUM_MOUNTS: usize = 3;
+const THREADS_PER_MOUNT: usize = 4;
+const NAMES_PER_DIR: u64 = 8;
+/// The names of the directories moved across each other.
+const MOVED_NAMES: [&str; 3] = ["a", "b", "c"];
+/// The number of directories moved across each other above which no more
+/// are created, as moves may nest them arbitrarily deep.
+const MAX_MOVED_DIRS: usize = 64;
+
+/// A xorshift pseudo-random generator, seeded per thread so that failures
+/// can be reproduced.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+/// When a workload stops.
+#[derive(Clone, Copy)]
+enum Budget {
+    Ops(usize),
+    Until(Instant),
+}
+
+impl Budget {
+    fn exhausted(&self, done: usize) -> bool {
+        match *self {
+            Budget::Ops(n) => done >= n,
+            Budget::Until(deadline) => Instant::now() >= deadline,
+        }
+    }
+}
+
+/// Reads the whole content of a file node.
+fn read_all(node: &VfsNodeRef) -> Vec<u8> {
+    let size = node.get_attr().unwrap().size() as usize;
+    let mut buf = vec![0; size];
+    assert_eq!(node.read_at(0, &mut buf).unwrap(), size);
+    buf
+}
+
+/// Returns the names of the entries of `dir`, without `.` and `..`.
+fn entry_names(dir: &VfsNodeRef) -> Vec<String> {
+    DirEntries::new(dir.as_ref())
+        .skip(2)
+        .map(|e| String::from_utf8(e.unwrap().name_as_bytes().to_vec()).unwrap())
+        .collect()
+}
+
+/// The tree where directories are moved across each other.
+struct MovedTree {
+    root: VfsNodeRef,
+    /// The number of directories created minus the number removed. The
+    /// directories are moved without replacing the destination, so this is
+    /// the number of directories in the tree.
+    dirs: AtomicUsize,
+}
+
+/// Returns a random path of one to three directories of [`MOVED_NAMES`].
+fn moved_path(rng: &mut Rng) -> String {
+    let depth = rng.below(3) + 1;
+    let names: Vec<_> = (0..depth)
+        .map(|_| MOVED_NAMES[rng.below(MOVED_NAMES.len() as u64) as usize])
+        .collect();
+    names.join("/")
+}
+
+/// Returns the number of directories in the subtree of `dir`, checking
+/// that each of them has the directory it was found in as parent.
+fn count_dirs(dir: &VfsNodeRef) -> usize {
+    entry_names(dir)
+        .into_iter()
+        .map(|name| {
+            let child = dir.clone().lookup(&name).unwrap();
+            assert!(Arc::ptr_eq(&child.parent().unwrap(), dir));
+            count_dirs(&child) + 1
+        })
+        .sum()
+}
+
+/// Runs a randomized workload on `dir` (private to this thread), `shared`
+/// and `moves` (shared with other threads), checking the private directory
+/// against a model of its expected content.
+fn worker(dir: VfsNodeRef, shared: VfsNodeRef, moves: Arc<MovedTree>, seed: u64, budget: Budget) {
+    let mut rng = Rng(seed | 1);
+    let mut model: BTreeMap<String, Option<Vec<u8>>> = BTreeMap::new();
+    let mut done = 0;
+    while !budget.exhausted(done) {
+        done += 1;
+        let name = format!("n{}", rng.below(NAMES_PER_DIR));
+        match rng.below(9) {
+            0 => {
+                let ty = if rng.below(4) == 0 {
+                    VfsNodeType::Dir
+                } else {
+                    VfsNodeType::File
+                };
+                let res = dir.create(&name, ty);
+                match model.entry(name) {
+                    Entry::Occupied(_) => assert_eq!(res, Err(VfsError::AlreadyExists)),
+                    Entry::Vacant(entry) => {
+                        res.unwrap();
+                        entry.insert((ty == VfsNodeType::File).then(Vec::new));
+                    }
+                }
+            }
+            1 => {
+                if let Some(Some(content)) = model.get_mut(&name) {
+                    let offset = rng.below(64) as usize;
+                    let data = vec![rng.next() as u8; rng.below(32) as usize + 1];
+                    let node = dir.clone().lookup(&name).unwrap();
+                    assert_eq!(node.write_at(offset as u64, &data), Ok(data.len()));
+                    if content.len() < offset + data.len() {
+                        content.resize(offset + data.len(), 0);
+                    }
+                    content[offset..offset + data.len()].copy_from_slice(&data);
+                }
+            }
+            2 => {
+                if let Some(Some(content)) = model.get_mut(&name) {
+                    let size = rng.below(48) as usize;
+                    let node = dir.clone().lookup(&name).unwrap();
+                    node.truncate(size as u64).unwrap();
+                    content.resize(size, 0);
+                }
+            }
+            3 => match model.get(&name) {
+                Some(Some(content)) => {
+                    let node = dir.clone().lookup(&name).unwrap();
+                    assert_eq!(&read_all(&node), content);
+                }
+                Some(None) => {
+                    let node = dir.clone().lookup(&name).unwrap();
+                    assert!(node.get_attr().unwrap().is_dir());
+                }
+                None => assert_eq!(dir.clone().lookup(&name).err(), Some(VfsError::NotFound)),
+            },
+            4 => {
+                let res = dir.remove(&name);
+                if model.remove(&name).is_some() {
+                    res.unwrap();
+                } else {
+                    assert_eq!(res, Err(VfsError::NotFound));
+                }
+            }
+            5..=7 => {
+                // move directories across each other, racing with the other
+                // threads: a move into a descendant must be refused
+                let path = moved_path(&mut rng);
+                let res = match rng.below(4) {
+                    0 if moves.dirs.load(Ordering::Relaxed) < MAX_MOVED_DIRS => {
+                        let res = moves.root.create(&path, VfsNodeType::Dir);
+                        res.map(|()| {
+                            moves.dirs.fetch_add(1, Ordering::Relaxed);
+                        })
+                    }
+                    1 => {
+                        let res = moves.root.remove(&path);
+                        res.map(|()| {
+                            moves.dirs.fetch_sub(1, Ordering::Relaxed);
+                        })
+                    }
+                    _ => {
+                        let dst = moved_path(&mut rng);
+                        moves.root.rename_flags(&path, &dst, RenameFlags::NOREPLACE)
+                    }
+                };
+                match res {
+                    Ok(())
+                    | Err(VfsError::AlreadyExists)
+                    | Err(VfsError::NotFound)
+                    | Err(VfsError::InvalidInput)
+                    | Err(VfsError::DirectoryNotEmpty) => {}
+                    Err(e) => panic!("unexpected error in moved directories: {e:?}"),
+                }
+            }
+            _ => {
+                // race with the other threads: any outcome is fine as long
+                // as the errors are the expected ones
+                let res = match rng.below(3) {
+                    0 => shared.create(&name, VfsNodeType::File),
+                    1 => shared.remove(&name),
+                    _ => shared
+                        .clone()
+                        .lookup(&name)
+                        .and_then(|node| node.write_at(rng.below(16), b"shared"))
+                        .map(|_| ()),
+                };
+                match res {
+                    Ok(()) | Err(VfsError::AlreadyExists) | Err(VfsError::NotFound) => {}
+                    Err(e) => panic!("unexpected error in shared directory: {e:?}"),
+                }
+            }
+        }
+    }
+
+    // final invariant: the directory holds exactly what the model says
+    let names = entry_names(&dir);
+    assert_eq!(names, model.keys().cloned().collect::<Vec<_>>());
+    for (name, content) in model {
+        let node = dir.clone().lookup(&name).unwrap();
+        match content {
+            Some(content) => assert_eq!(read_all(&node), content),
+            None => assert!(node.get_attr().unwrap().is_dir()),
+        }
+    }
+}
+
+/// Moves the directory `p/a` of `root` into `q/b` and back, while `q/b` is
+/// moved into `p/a` and back by another thread with `reverse` set, so that
+/// each move races with one that would close a cycle.
+fn cross_mover(root: VfsNodeRef, reverse: bool, budget: Budget) {
+    let (src, dst) = if reverse {
+        ("q/b", "p/a/b")
+    } else {
+        ("p/a", "q/b/a")
+    };
+    let mut done = 0;
+    while !budget.exhausted(done) {
+        done += 1;
+        for (src, dst) in [(src, dst), (dst, src)] {
+            match root.rename(src, dst) {
+                Ok(()) | Err(VfsError::NotFound) | Err(VfsError::InvalidInput) => {}
+                Err(e) => panic!("unexpected error in crossed moves: {e:?}"),
+            }
+        }
+    }
+}
+
+/// Mounts RAM filesystems over a device filesystem and runs the workload on
+/// all of them with `budget`.
+fn run_stress(budget: Budget) {
+    let devfs = DeviceFileSystem::new();
+    let mounts: Vec<_> = (0..NUM_MOUNTS)
+        .map(|i| {
+            let name: &'static str = format!("mnt{i}").leak();
+            let mount_point = devfs.mkdir(name);
+            let ramfs = Arc::new(RamFileSystem::new());
+            ramfs
+                .mount(name, mount_point, &MountOptions::new())
+                .unwrap();
+            ramfs
+        })
+        .collect();
+
+    let mut handles = Vec::new();
+    let mut trees = Vec::new();
+    for (i, ramfs) in mounts.iter().enumerate() {
+        let root = ramfs.root_dir();
+        root.create("shared", VfsNodeType::Dir).unwrap();
+        root.create("moves", VfsNodeType::Dir).unwrap();
+        for path in ["cross", "cross/p", "cross/q", "cross/p/a", "cross/q/b"] {
+            root.create(path, VfsNodeType::Dir).unwrap();
+        }
+        let cross = root.clone().lookup("cross").unwrap();
+        for reverse in [false, true] {
+            let cross = cross.clone();
+            handles.push(thread::spawn(move || cross_mover(cross, reverse, budget)));
+        }
+        let shared = root.clone().lookup("shared").unwrap();
+        let moves = Arc::new(MovedTree {
+            root: root.clone().lookup("moves").unwrap(),
+            dirs: AtomicUsize::new(0),
+        });
+        trees.push(moves.clone());
+        for t in 0..THREADS_PER_MOUNT {
+            let name = format!("t{t}");
+            root.create(&name, VfsNodeType::Dir).unwrap();
+            let dir = root.clone().lookup(&name).unwrap();
+            let shared = shared.clone();
+            let moves = moves.clone();
+            let seed = ((i * THREADS_PER_MOUNT + t) as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+            handles.push(thread::spawn(move || {
+                worker(dir, shared, moves, seed, budget)
+            }));
+        }
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // the shared directories are still consistent, no moved directory was
+    // detached from the tree, and the mounts are attached under the root of
+    // the devfs
+    for (ramfs, moves) in mounts.iter().zip(trees) {
+        let root = ramfs.root_dir();
+        assert_eq!(count_dirs(&moves.root), moves.dirs.load(Ordering::Relaxed));
+        let cross = root.clone().lookup("cross").unwrap();
+        assert_eq!(count_dirs(&cross), 4);
+        let shared = root.clone().lookup("shared").unwrap();
+        for name in entry_names(&shared) {
+            let node = shared.clone().lookup(&name).unwrap();
+            assert_eq!(
+                read_all(&node).len() as u64,
+                node.get_attr().unwrap().size()
+            );
+        }
+        let parent = root.parent().unwrap();
+        assert!(Arc::ptr_eq(&parent, &devfs.root_dir()));
+    }
+}
+
+#[test]
+fn test_stress_smoke() {
+    run_stress(Budget::Ops(2_000));
+}
+
+#[test]
+#[ignore = "long-running soak test, run with --ignored"]
+fn test_stress_soak() {
+    let secs = std::env::var("AXFS_STRESS_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(60);
+    run_stress(Budget::Until(Instant::now() + Duration::from_secs(secs)));
+}
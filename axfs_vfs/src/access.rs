@@ -0,0 +1,167 @@
+//! Unix permission checks.
+//!
+//! [`check_access`] decides whether [`Credentials`] may read, write or
+//! execute a node, from the owner, group and permission bits of its
+//! [`VfsNodeAttr`], as Linux does for `access(2)` and `open(2)`, including
+//! the [`Capabilities`] that bypass them. Access control lists are not
+//! supported.
+
+pub use crate::cred::{Capabilities, Credentials};
+use crate::{VfsError, VfsNodeAttr, VfsNodePerm, VfsResult};
+
+bitflags::bitflags! {
+    /// The kinds of access checked by [`check_access`].
+    ///
+    /// The values are those of the `R_OK`, `W_OK` and `X_OK` constants of
+    /// `access(2)`, and of the permission bits of each class.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Access: u8 {
+        /// Execute a file, or search a directory (`X_OK`).
+        const EXEC = 0o1;
+        /// Write to the node (`W_OK`).
+        const WRITE = 0o2;
+        /// Read the node (`R_OK`).
+        const READ = 0o4;
+    }
+}
+
+/// Checks that `cred` may access the node of `attr` as `access` requests.
+///
+/// The permission bits of the owner apply if `cred` is the owner of the
+/// node, else those of the group if it is in the group of the node, else
+/// those of the others. With [`Capabilities::DAC_OVERRIDE`], as the
+/// superuser has, `cred` may also read and write any node, and execute
+/// directories and the files with an execute bit in any class. With
+/// [`Capabilities::DAC_READ_SEARCH`], it may read any node and search any
+/// directory.
+///
+/// # Arguments
+///
+/// * `attr` - The attributes of the node
+/// * `cred` - The identity of the caller
+/// * `access` - The requested access, all of which must be granted
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the access is granted.
+///
+/// # Errors
+///
+/// Returns [`VfsError::PermissionDenied`] if a requested access is not
+/// granted.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::access::{check_access, Access, Credentials};
+/// use axfs_vfs::{VfsError, VfsNodeAttr, VfsNodePerm, VfsNodeType};
+///
+/// let perm = VfsNodePerm::from_bits_truncate(0o640);
+/// let attr = VfsNodeAttr::new(perm, VfsNodeType::File, 0, 0).with_owner(1000, 100);
+/// let user = Credentials::new(1000, 1000);
+/// let staff = Credentials::new(1001, 100);
+/// assert_eq!(check_access(&attr, &user, Access::READ | Access::WRITE), Ok(()));
+/// assert_eq!(check_access(&attr, &staff, Access::READ), Ok(()));
+/// assert_eq!(
+///     check_access(&attr, &staff, Access::WRITE),
+///     Err(VfsError::PermissionDenied)
+/// );
+/// ```
+pub fn check_access(attr: &VfsNodeAttr, cred: &Credentials, access: Access) -> VfsResult {
+    let granted = granted_access(attr, cred);
+    match granted.contains(access) {
+        true => Ok(()),
+        false => Err(VfsError::PermissionDenied),
+    }
+}
+
+/// Returns all the access `cred` has to the node of `attr`.
+///
+/// # Arguments
+///
+/// * `attr` - The attributes of the node
+/// * `cred` - The identity of the caller
+pub fn granted_access(attr: &VfsNodeAttr, cred: &Credentials) -> Access {
+    let bits = attr.perm().bits();
+    let class = if cred.uid == attr.uid() {
+        bits >> 6
+    } else if cred.in_group(attr.gid()) {
+        bits >> 3
+    } else {
+        bits
+    };
+    let mut access = Access::from_bits_truncate((class & 0o7) as u8);
+    if cred.has_caps(Capabilities::DAC_OVERRIDE) {
+        let any_exec = VfsNodePerm::OWNER_EXEC | VfsNodePerm::GROUP_EXEC | VfsNodePerm::OTHER_EXEC;
+        access |= Access::READ | Access::WRITE;
+        if attr.is_dir() || attr.perm().intersects(any_exec) {
+            access |= Access::EXEC;
+        }
+    }
+    if cred.has_caps(Capabilities::DAC_READ_SEARCH) {
+        access |= Access::READ;
+        if attr.is_dir() {
+            access |= Access::EXEC;
+        }
+    }
+    access
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VfsNodeType;
+    use alloc::vec;
+
+    fn attr(mode: u16, ty: VfsNodeType) -> VfsNodeAttr {
+        VfsNodeAttr::new(VfsNodePerm::from_bits_truncate(mode), ty, 0, 0).with_owner(10, 20)
+    }
+
+    #[test]
+    fn test_classes() {
+        let file = attr(0o754, VfsNodeType::File);
+        let owner = Credentials::new(10, 99);
+        let member = Credentials::new(11, 99).with_groups(vec![20]);
+        let other = Credentials::new(12, 99);
+        assert_eq!(granted_access(&file, &owner), Access::all());
+        assert_eq!(granted_access(&file, &member), Access::READ | Access::EXEC);
+        assert_eq!(granted_access(&file, &other), Access::READ);
+        assert_eq!(check_access(&file, &other, Access::empty()), Ok(()));
+        assert_eq!(
+            check_access(&file, &other, Access::READ | Access::EXEC),
+            Err(VfsError::PermissionDenied)
+        );
+
+        // the owner class applies even if it grants less than the others
+        let file = attr(0o047, VfsNodeType::File);
+        assert_eq!(granted_access(&file, &owner), Access::empty());
+        assert_eq!(granted_access(&file, &member), Access::READ);
+    }
+
+    #[test]
+    fn test_root() {
+        let root = Credentials::root();
+        let file = attr(0o000, VfsNodeType::File);
+        assert_eq!(granted_access(&file, &root), Access::READ | Access::WRITE);
+        let script = attr(0o001, VfsNodeType::File);
+        assert_eq!(check_access(&script, &root, Access::EXEC), Ok(()));
+        let dir = attr(0o000, VfsNodeType::Dir);
+        assert_eq!(check_access(&dir, &root, Access::all()), Ok(()));
+    }
+
+    #[test]
+    fn test_caps() {
+        let file = attr(0o000, VfsNodeType::File);
+        let dir = attr(0o000, VfsNodeType::Dir);
+        let root = Credentials::root().with_caps(Capabilities::empty());
+        assert_eq!(granted_access(&file, &root), Access::empty());
+
+        let backup = Credentials::new(12, 99).with_caps(Capabilities::DAC_READ_SEARCH);
+        assert_eq!(granted_access(&file, &backup), Access::READ);
+        assert_eq!(granted_access(&dir, &backup), Access::READ | Access::EXEC);
+
+        let admin = Credentials::new(12, 99).with_caps(Capabilities::DAC_OVERRIDE);
+        assert_eq!(granted_access(&file, &admin), Access::READ | Access::WRITE);
+        assert_eq!(granted_access(&dir, &admin), Access::all());
+    }
+}
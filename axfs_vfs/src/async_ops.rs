@@ -0,0 +1,320 @@
+//! Asynchronous filesystem interfaces.
+//!
+//! This module is only available with the `async` feature. It provides
+//! [`AsyncVfsOps`] and [`AsyncVfsNodeOps`], the asynchronous counterparts of
+//! [`VfsOps`] and [`VfsNodeOps`], so that async executors can await
+//! filesystem I/O (such as block device requests) without blocking their
+//! worker threads.
+//!
+//! The methods return boxed [`VfsFuture`]s, so that the traits can be used
+//! as trait objects, like their synchronous counterparts.
+//!
+//! Synchronous filesystems are used through the [`SyncNode`] and [`SyncFs`]
+//! adapters, whose futures complete on their first poll. They are wrappers
+//! rather than blanket implementations, so that calling a method on a
+//! synchronous node is never ambiguous between the two traits.
+//!
+//! # Examples
+//!
+//! ```
+//! use axfs_vfs::async_ops::{AsyncVfsNodeOps, SyncNode};
+//! # use axfs_vfs::{VfsNodeOps, VfsResult};
+//! # struct Hello;
+//! # impl VfsNodeOps for Hello {
+//! #     fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+//! #         buf[..5].copy_from_slice(b"hello");
+//! #         Ok(5)
+//! #     }
+//! # }
+//!
+//! async fn read_greeting(node: &dyn AsyncVfsNodeOps) -> [u8; 5] {
+//!     let mut buf = [0; 5];
+//!     node.read_at(0, &mut buf).await.unwrap();
+//!     buf
+//! }
+//!
+//! let node = SyncNode::new(std::sync::Arc::new(Hello));
+//! let _future = read_greeting(&node);
+//! ```
+//!
+//! [`VfsOps`]: crate::VfsOps
+//! [`VfsNodeOps`]: crate::VfsNodeOps
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+
+use crate::{
+    FileSystemInfo, VfsCapabilities, VfsDirEntry, VfsNodeAttr, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsResult,
+};
+
+/// A boxed future resolving to a [`VfsResult`].
+pub type VfsFuture<'a, T> = Pin<Box<dyn Future<Output = VfsResult<T>> + Send + 'a>>;
+
+/// A wrapper of [`Arc<dyn AsyncVfsNodeOps>`].
+pub type AsyncVfsNodeRef = Arc<dyn AsyncVfsNodeOps>;
+
+/// Returns a future that is immediately ready with `res`.
+fn ready<'a, T: Send + 'a>(res: VfsResult<T>) -> VfsFuture<'a, T> {
+    Box::pin(core::future::ready(res))
+}
+
+/// Asynchronous operations on a filesystem, see [`VfsOps`].
+pub trait AsyncVfsOps: Send + Sync {
+    /// Get the root directory of the filesystem.
+    fn root_dir(&self) -> AsyncVfsNodeRef;
+
+    /// Get the attributes of the filesystem, see [`VfsOps::statfs`].
+    fn statfs(&self) -> VfsFuture<'_, FileSystemInfo>;
+
+    /// Flush the filesystem before it is unmounted, see [`VfsOps::umount`].
+    fn umount(&self) -> VfsFuture<'_, ()>;
+
+    /// Get the optional features supported by the filesystem, see
+    /// [`VfsOps::capabilities`].
+    fn capabilities(&self) -> VfsCapabilities;
+}
+
+/// Asynchronous operations on a node, see
+/// [`VfsNodeOps`](crate::VfsNodeOps).
+///
+/// Only the operations that may wait for storage are provided. Each method
+/// behaves like the synchronous method of the same name.
+pub trait AsyncVfsNodeOps: Send + Sync {
+    /// Get the attributes of the node.
+    fn get_attr(&self) -> VfsFuture<'_, VfsNodeAttr>;
+
+    /// Read data from the file at the given offset.
+    fn read_at<'a>(&'a self, offset: u64, buf: &'a mut [u8]) -> VfsFuture<'a, usize>;
+
+    /// Write data to the file at the given offset.
+    fn write_at<'a>(&'a self, offset: u64, buf: &'a [u8]) -> VfsFuture<'a, usize>;
+
+    /// Flush the file, synchronize the data to disk.
+    fn fsync(&self) -> VfsFuture<'_, ()>;
+
+    /// Flush the data of the file, skipping the metadata that is not needed
+    /// to read it back.
+    fn fsync_data(&self) -> VfsFuture<'_, ()> {
+        self.fsync()
+    }
+
+    /// Truncate the file to the given size.
+    fn truncate(&self, size: u64) -> VfsFuture<'_, ()>;
+
+    /// Lookup the node with the given path in the directory.
+    fn lookup<'a>(self: Arc<Self>, path: &'a str) -> VfsFuture<'a, AsyncVfsNodeRef>;
+
+    /// Read directory entries into `dirents`, starting from `start_idx`.
+    fn read_dir<'a>(
+        &'a self,
+        start_idx: usize,
+        dirents: &'a mut [VfsDirEntry],
+    ) -> VfsFuture<'a, usize>;
+
+    /// Create a new node with the given path in the directory.
+    fn create<'a>(&'a self, path: &'a str, ty: VfsNodeType) -> VfsFuture<'a, ()>;
+
+    /// Remove the node with the given path in the directory.
+    fn remove<'a>(&'a self, path: &'a str) -> VfsFuture<'a, ()>;
+}
+
+/// An adapter running the operations of a synchronous node in
+/// [`AsyncVfsNodeOps`].
+///
+/// The operations run on the first poll of their futures, so they still
+/// block the executor while they run.
+#[derive(Clone)]
+pub struct SyncNode(VfsNodeRef);
+
+impl SyncNode {
+    /// Wraps the synchronous node `node`.
+    pub fn new(node: VfsNodeRef) -> Self {
+        Self(node)
+    }
+
+    /// Wraps the synchronous node `node` into an [`AsyncVfsNodeRef`].
+    pub fn wrap(node: VfsNodeRef) -> AsyncVfsNodeRef {
+        Arc::new(Self(node))
+    }
+
+    /// Returns the synchronous node.
+    pub fn inner(&self) -> &VfsNodeRef {
+        &self.0
+    }
+}
+
+impl AsyncVfsNodeOps for SyncNode {
+    fn get_attr(&self) -> VfsFuture<'_, VfsNodeAttr> {
+        ready(self.0.get_attr())
+    }
+
+    fn read_at<'a>(&'a self, offset: u64, buf: &'a mut [u8]) -> VfsFuture<'a, usize> {
+        Box::pin(async move { self.0.read_at(offset, buf) })
+    }
+
+    fn write_at<'a>(&'a self, offset: u64, buf: &'a [u8]) -> VfsFuture<'a, usize> {
+        Box::pin(async move { self.0.write_at(offset, buf) })
+    }
+
+    fn fsync(&self) -> VfsFuture<'_, ()> {
+        Box::pin(async move { self.0.fsync() })
+    }
+
+    fn fsync_data(&self) -> VfsFuture<'_, ()> {
+        Box::pin(async move { self.0.fsync_data() })
+    }
+
+    fn truncate(&self, size: u64) -> VfsFuture<'_, ()> {
+        Box::pin(async move { self.0.truncate(size) })
+    }
+
+    fn lookup<'a>(self: Arc<Self>, path: &'a str) -> VfsFuture<'a, AsyncVfsNodeRef> {
+        Box::pin(async move { self.0.clone().lookup(path).map(Self::wrap) })
+    }
+
+    fn read_dir<'a>(
+        &'a self,
+        start_idx: usize,
+        dirents: &'a mut [VfsDirEntry],
+    ) -> VfsFuture<'a, usize> {
+        Box::pin(async move { self.0.read_dir(start_idx, dirents) })
+    }
+
+    fn create<'a>(&'a self, path: &'a str, ty: VfsNodeType) -> VfsFuture<'a, ()> {
+        Box::pin(async move { self.0.create(path, ty) })
+    }
+
+    fn remove<'a>(&'a self, path: &'a str) -> VfsFuture<'a, ()> {
+        Box::pin(async move { self.0.remove(path) })
+    }
+}
+
+/// An adapter running the operations of a synchronous filesystem in
+/// [`AsyncVfsOps`].
+#[derive(Clone)]
+pub struct SyncFs(Arc<dyn VfsOps>);
+
+impl SyncFs {
+    /// Wraps the synchronous filesystem `fs`.
+    pub fn new(fs: Arc<dyn VfsOps>) -> Self {
+        Self(fs)
+    }
+
+    /// Returns the synchronous filesystem.
+    pub fn inner(&self) -> &Arc<dyn VfsOps> {
+        &self.0
+    }
+}
+
+impl AsyncVfsOps for SyncFs {
+    fn root_dir(&self) -> AsyncVfsNodeRef {
+        SyncNode::wrap(self.0.root_dir())
+    }
+
+    fn statfs(&self) -> VfsFuture<'_, FileSystemInfo> {
+        Box::pin(async move { self.0.statfs() })
+    }
+
+    fn umount(&self) -> VfsFuture<'_, ()> {
+        Box::pin(async move { self.0.umount() })
+    }
+
+    fn capabilities(&self) -> VfsCapabilities {
+        self.0.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VfsError, VfsNodeOps};
+    use core::task::{Context, Poll, Waker};
+    use spin::Mutex;
+
+    /// Polls `future` once, as every future of the adapters is ready on its
+    /// first poll.
+    fn poll_once<T>(future: VfsFuture<'_, T>) -> VfsResult<T> {
+        let mut future = future;
+        match future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        {
+            Poll::Ready(res) => res,
+            Poll::Pending => panic!("future not ready"),
+        }
+    }
+
+    struct MemFile(Mutex<alloc::vec::Vec<u8>>);
+
+    impl VfsNodeOps for MemFile {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_file(self.0.lock().len() as u64, 0))
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            let data = self.0.lock();
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            Ok(n)
+        }
+
+        fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            let mut data = self.0.lock();
+            let end = offset as usize + buf.len();
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            data[offset as usize..end].copy_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            match path {
+                "" | "." => Ok(self),
+                _ => Err(VfsError::NotFound),
+            }
+        }
+    }
+
+    #[test]
+    fn test_sync_node() {
+        let node = SyncNode::wrap(Arc::new(MemFile(Mutex::new(alloc::vec::Vec::new()))));
+        assert_eq!(poll_once(node.write_at(2, b"abc")), Ok(3));
+        assert_eq!(poll_once(node.get_attr()).unwrap().size(), 5);
+        let mut buf = [1; 5];
+        assert_eq!(poll_once(node.read_at(0, &mut buf)), Ok(5));
+        assert_eq!(&buf, b"\0\0abc");
+
+        let same = poll_once(node.clone().lookup(".")).unwrap();
+        assert_eq!(poll_once(same.get_attr()).unwrap().size(), 5);
+        assert_eq!(
+            poll_once(node.clone().lookup("x")).err(),
+            Some(VfsError::NotFound)
+        );
+        assert_eq!(poll_once(node.fsync()), Err(VfsError::InvalidInput));
+        assert_eq!(poll_once(node.fsync_data()), Err(VfsError::InvalidInput));
+    }
+
+    #[test]
+    fn test_sync_fs() {
+        struct Fs(VfsNodeRef);
+
+        impl VfsOps for Fs {
+            fn root_dir(&self) -> VfsNodeRef {
+                self.0.clone()
+            }
+        }
+
+        let file: VfsNodeRef = Arc::new(MemFile(Mutex::new(b"data".to_vec())));
+        let fs = SyncFs::new(Arc::new(Fs(file)));
+        let root = fs.root_dir();
+        assert_eq!(poll_once(root.get_attr()).unwrap().size(), 4);
+        assert_eq!(poll_once(fs.statfs()).err(), Some(VfsError::Unsupported));
+        assert_eq!(poll_once(fs.umount()), Ok(()));
+        assert_eq!(fs.capabilities(), VfsCapabilities::default());
+    }
+}
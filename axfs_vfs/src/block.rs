@@ -0,0 +1,494 @@
+//! Block device interface.
+//!
+//! Filesystems can access a [`BlockDeviceOps`] directly, or through a
+//! [`RequestQueue`], which merges the requests on adjacent blocks into one
+//! call to the device.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{VfsError, VfsResult};
+
+/// Operations of a block device, such as a disk or a swap partition.
+///
+/// Data is transferred in whole blocks of [`block_size()`](Self::block_size)
+/// bytes. A buffer longer than one block accesses consecutive blocks.
+pub trait BlockDeviceOps: Send + Sync {
+    /// Returns the size of a block in bytes.
+    fn block_size(&self) -> usize;
+
+    /// Returns the number of blocks of the device.
+    fn num_blocks(&self) -> u64;
+
+    /// Reads blocks starting at `block_id` into `buf`.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_id` - The index of the first block to read
+    /// * `buf` - The buffer to read into, whose length is a multiple of the
+    ///   block size
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    fn read_block(&self, block_id: u64, buf: &mut [u8]) -> VfsResult;
+
+    /// Writes `buf` to the blocks starting at `block_id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_id` - The index of the first block to write
+    /// * `buf` - The data to write, whose length is a multiple of the block
+    ///   size
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    fn write_block(&self, block_id: u64, buf: &[u8]) -> VfsResult;
+
+    /// Flushes the data written to the device to its persistent storage.
+    ///
+    /// The default implementation does nothing.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    fn flush(&self) -> VfsResult {
+        Ok(())
+    }
+}
+
+/// The identifier of a request submitted to a [`RequestQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestId(u64);
+
+/// The direction of a block request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOp {
+    /// Read blocks from the device.
+    Read,
+    /// Write blocks to the device.
+    Write,
+}
+
+/// A request completed by a [`RequestQueue`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockCompletion {
+    /// The identifier returned when the request was submitted.
+    pub id: RequestId,
+    /// The direction of the request.
+    pub op: BlockOp,
+    /// The index of the first block of the request.
+    pub block_id: u64,
+    /// The result of the device call serving the request.
+    pub result: VfsResult,
+    /// The blocks read, or the data written.
+    pub data: Vec<u8>,
+}
+
+/// A pending request.
+struct Request {
+    id: RequestId,
+    op: BlockOp,
+    block_id: u64,
+    data: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the range of blocks of the request.
+    fn blocks(&self, block_size: usize) -> Range<u64> {
+        let count = (self.data.len() / block_size) as u64;
+        self.block_id..self.block_id + count
+    }
+}
+
+/// A queue of block requests, submitted and completed in batches.
+///
+/// Requests are only sent to the device by [`dispatch()`](Self::dispatch).
+/// Consecutive requests of the same direction are then sorted by block, and
+/// those on adjacent blocks are served by one call to the device, up to
+/// [`max_merge_blocks()`](Self::max_merge_blocks) blocks. Reads and writes
+/// are never reordered with each other, nor overlapping writes, so a read
+/// always sees the writes submitted before it.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use axfs_vfs::block::{BlockDeviceOps, RequestQueue};
+/// use axfs_vfs::VfsResult;
+///
+/// struct Disk(Mutex<Vec<u8>>);
+///
+/// impl BlockDeviceOps for Disk {
+///     fn block_size(&self) -> usize {
+///         512
+///     }
+///     fn num_blocks(&self) -> u64 {
+///         8
+///     }
+///     fn read_block(&self, block_id: u64, buf: &mut [u8]) -> VfsResult {
+///         let start = block_id as usize * 512;
+///         buf.copy_from_slice(&self.0.lock().unwrap()[start..start + buf.len()]);
+///         Ok(())
+///     }
+///     fn write_block(&self, block_id: u64, buf: &[u8]) -> VfsResult {
+///         let start = block_id as usize * 512;
+///         self.0.lock().unwrap()[start..start + buf.len()].copy_from_slice(buf);
+///         Ok(())
+///     }
+/// }
+///
+/// let mut queue = RequestQueue::new(Arc::new(Disk(Mutex::new(vec![0; 4096]))));
+/// queue.submit_write(1, vec![1; 512]).unwrap();
+/// queue.submit_write(0, vec![2; 512]).unwrap();
+/// let read = queue.submit_read(0, 2).unwrap();
+/// // one call for the two writes, and one for the read
+/// assert_eq!(queue.dispatch(), 2);
+///
+/// let done = queue.complete();
+/// let read = done.iter().find(|c| c.id == read).unwrap();
+/// assert_eq!(read.result, Ok(()));
+/// assert_eq!((read.data[0], read.data[512]), (2, 1));
+/// ```
+pub struct RequestQueue {
+    dev: Arc<dyn BlockDeviceOps>,
+    max_merge_blocks: usize,
+    next_id: u64,
+    pending: Vec<Request>,
+    completed: VecDeque<BlockCompletion>,
+}
+
+impl RequestQueue {
+    /// The default maximum number of blocks served by one device call.
+    pub const DEFAULT_MAX_MERGE_BLOCKS: usize = 128;
+
+    /// Creates an empty queue for the device `dev`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - The block device serving the requests
+    pub fn new(dev: Arc<dyn BlockDeviceOps>) -> Self {
+        Self {
+            dev,
+            max_merge_blocks: Self::DEFAULT_MAX_MERGE_BLOCKS,
+            next_id: 0,
+            pending: Vec::new(),
+            completed: VecDeque::new(),
+        }
+    }
+
+    /// Sets the maximum number of blocks served by one device call.
+    ///
+    /// A request longer than the limit is still served by one call, but is
+    /// not merged with others.
+    ///
+    /// # Arguments
+    ///
+    /// * `blocks` - The maximum number of blocks, at least 1
+    pub fn with_max_merge_blocks(mut self, blocks: usize) -> Self {
+        self.max_merge_blocks = blocks.max(1);
+        self
+    }
+
+    /// Returns the maximum number of blocks served by one device call.
+    pub fn max_merge_blocks(&self) -> usize {
+        self.max_merge_blocks
+    }
+
+    /// Returns the device serving the requests.
+    pub fn device(&self) -> &Arc<dyn BlockDeviceOps> {
+        &self.dev
+    }
+
+    /// Returns the number of requests not yet dispatched.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Queues a read of `count` blocks starting at `block_id`.
+    ///
+    /// # Returns
+    ///
+    /// The identifier of the request, found in its [`BlockCompletion`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `count` is 0.
+    pub fn submit_read(&mut self, block_id: u64, count: usize) -> VfsResult<RequestId> {
+        if count == 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let data = vec![0; count * self.dev.block_size()];
+        Ok(self.push(BlockOp::Read, block_id, data))
+    }
+
+    /// Queues a write of `data` to the blocks starting at `block_id`.
+    ///
+    /// # Returns
+    ///
+    /// The identifier of the request, found in its [`BlockCompletion`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `data` is empty, or its length
+    /// is not a multiple of the block size.
+    pub fn submit_write(&mut self, block_id: u64, data: Vec<u8>) -> VfsResult<RequestId> {
+        if data.is_empty() || !data.len().is_multiple_of(self.dev.block_size()) {
+            return Err(VfsError::InvalidInput);
+        }
+        Ok(self.push(BlockOp::Write, block_id, data))
+    }
+
+    fn push(&mut self, op: BlockOp, block_id: u64, data: Vec<u8>) -> RequestId {
+        let id = RequestId(self.next_id);
+        self.next_id += 1;
+        self.pending.push(Request {
+            id,
+            op,
+            block_id,
+            data,
+        });
+        id
+    }
+
+    /// Sends the pending requests to the device.
+    ///
+    /// The results are then available from [`complete()`](Self::complete).
+    /// A failed device call fails all the requests it serves.
+    ///
+    /// # Returns
+    ///
+    /// The number of calls made to the device.
+    pub fn dispatch(&mut self) -> usize {
+        let block_size = self.dev.block_size();
+        let mut pending = core::mem::take(&mut self.pending);
+        let mut calls = 0;
+        let mut start = 0;
+        while start < pending.len() {
+            let op = pending[start].op;
+            let end = pending[start..]
+                .iter()
+                .position(|req| req.op != op)
+                .map_or(pending.len(), |n| start + n);
+            let run = &mut pending[start..end];
+            if op == BlockOp::Read || !has_overlap(run, block_size) {
+                run.sort_by_key(|req| req.block_id);
+            }
+            calls += self.serve_run(run, block_size);
+            start = end;
+        }
+        calls
+    }
+
+    /// Serves a run of requests of the same direction, merging the
+    /// adjacent ones.
+    fn serve_run(&mut self, run: &mut [Request], block_size: usize) -> usize {
+        let mut calls = 0;
+        let mut start = 0;
+        while start < run.len() {
+            let mut blocks = run[start].blocks(block_size);
+            let mut end = start + 1;
+            while end < run.len() {
+                let next = run[end].blocks(block_size);
+                let len = (next.end - blocks.start) as usize;
+                if next.start != blocks.end || len > self.max_merge_blocks {
+                    break;
+                }
+                blocks.end = next.end;
+                end += 1;
+            }
+            self.serve(&mut run[start..end]);
+            calls += 1;
+            start = end;
+        }
+        calls
+    }
+
+    /// Serves adjacent requests with one device call.
+    fn serve(&mut self, reqs: &mut [Request]) {
+        let (block_id, op) = (reqs[0].block_id, reqs[0].op);
+        let result = match (&mut *reqs, op) {
+            ([req], BlockOp::Read) => self.dev.read_block(block_id, &mut req.data),
+            ([req], BlockOp::Write) => self.dev.write_block(block_id, &req.data),
+            (reqs, BlockOp::Read) => {
+                let len = reqs.iter().map(|req| req.data.len()).sum();
+                let mut buf = vec![0; len];
+                let result = self.dev.read_block(block_id, &mut buf);
+                let mut chunks = buf.as_slice();
+                for req in reqs.iter_mut() {
+                    let (chunk, rest) = chunks.split_at(req.data.len());
+                    req.data.copy_from_slice(chunk);
+                    chunks = rest;
+                }
+                result
+            }
+            (reqs, BlockOp::Write) => {
+                let buf: Vec<u8> = reqs
+                    .iter()
+                    .flat_map(|req| req.data.iter().copied())
+                    .collect();
+                self.dev.write_block(block_id, &buf)
+            }
+        };
+        for req in reqs.iter_mut() {
+            self.completed.push_back(BlockCompletion {
+                id: req.id,
+                op: req.op,
+                block_id: req.block_id,
+                result,
+                data: core::mem::take(&mut req.data),
+            });
+        }
+    }
+
+    /// Returns the completed requests, in the order they were served.
+    pub fn complete(&mut self) -> Vec<BlockCompletion> {
+        self.completed.drain(..).collect()
+    }
+
+    /// Sends the pending requests to the device, then flushes it.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the error of the flush.
+    pub fn flush(&mut self) -> VfsResult {
+        self.dispatch();
+        self.dev.flush()
+    }
+}
+
+/// Returns whether two requests of `reqs` access a common block.
+fn has_overlap(reqs: &[Request], block_size: usize) -> bool {
+    let mut ranges: Vec<Range<u64>> = reqs.iter().map(|req| req.blocks(block_size)).collect();
+    ranges.sort_by_key(|range| range.start);
+    ranges.windows(2).any(|w| w[1].start < w[0].end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use spin::Mutex;
+
+    const BS: usize = 4;
+
+    struct Disk {
+        data: Mutex<Vec<u8>>,
+        calls: AtomicUsize,
+    }
+
+    impl Disk {
+        fn new() -> Arc<Self> {
+            Arc::new(Self {
+                data: Mutex::new(vec![0; 16 * BS]),
+                calls: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    impl BlockDeviceOps for Disk {
+        fn block_size(&self) -> usize {
+            BS
+        }
+
+        fn num_blocks(&self) -> u64 {
+            16
+        }
+
+        fn read_block(&self, block_id: u64, buf: &mut [u8]) -> VfsResult {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let start = block_id as usize * BS;
+            let data = self.data.lock();
+            let src = data
+                .get(start..start + buf.len())
+                .ok_or(VfsError::InvalidInput)?;
+            buf.copy_from_slice(src);
+            Ok(())
+        }
+
+        fn write_block(&self, block_id: u64, buf: &[u8]) -> VfsResult {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            let start = block_id as usize * BS;
+            let mut data = self.data.lock();
+            let dst = data
+                .get_mut(start..start + buf.len())
+                .ok_or(VfsError::InvalidInput)?;
+            dst.copy_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_merge_adjacent() {
+        let disk = Disk::new();
+        let mut queue = RequestQueue::new(disk.clone());
+        for block in [3, 1, 2, 0, 8] {
+            queue.submit_write(block, vec![block as u8; BS]).unwrap();
+        }
+        assert_eq!(queue.pending(), 5);
+        assert_eq!(queue.dispatch(), 2);
+        assert_eq!(disk.calls.load(Ordering::Relaxed), 2);
+        assert_eq!(queue.pending(), 0);
+        let done = queue.complete();
+        assert_eq!(done.len(), 5);
+        assert!(done.iter().all(|c| c.result.is_ok()));
+        assert_eq!(
+            disk.data.lock()[..4 * BS],
+            [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3]
+        );
+
+        let a = queue.submit_read(1, 2).unwrap();
+        let b = queue.submit_read(0, 1).unwrap();
+        assert_eq!(queue.dispatch(), 1);
+        let done = queue.complete();
+        let data = |id| &done.iter().find(|c| c.id == id).unwrap().data;
+        assert_eq!(data(a), &[1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(data(b), &[0; BS]);
+        assert!(queue.complete().is_empty());
+    }
+
+    #[test]
+    fn test_ordering() {
+        let disk = Disk::new();
+        let mut queue = RequestQueue::new(disk.clone());
+        queue.submit_write(0, vec![1; 2 * BS]).unwrap();
+        queue.submit_write(1, vec![2; BS]).unwrap();
+        let read = queue.submit_read(0, 2).unwrap();
+        queue.submit_write(0, vec![3; BS]).unwrap();
+        // the overlapping writes and the read keep their order
+        assert_eq!(queue.dispatch(), 4);
+        let done = queue.complete();
+        let read = done.iter().find(|c| c.id == read).unwrap();
+        assert_eq!(read.op, BlockOp::Read);
+        assert_eq!(read.data, [1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(disk.data.lock()[..2 * BS], [3, 3, 3, 3, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_limits_and_errors() {
+        let disk = Disk::new();
+        let mut queue = RequestQueue::new(disk.clone()).with_max_merge_blocks(2);
+        assert_eq!(queue.submit_read(0, 0), Err(VfsError::InvalidInput));
+        assert_eq!(
+            queue.submit_write(0, vec![0; 3]),
+            Err(VfsError::InvalidInput)
+        );
+        for block in 0..5 {
+            queue.submit_read(block, 1).unwrap();
+        }
+        assert_eq!(queue.dispatch(), 3);
+        queue.complete();
+
+        // a failed call fails all the requests it serves
+        queue.submit_read(15, 1).unwrap();
+        queue.submit_read(16, 1).unwrap();
+        assert_eq!(queue.dispatch(), 1);
+        let done = queue.complete();
+        assert!(done.iter().all(|c| c.result == Err(VfsError::InvalidInput)));
+        assert_eq!(queue.flush(), Ok(()));
+    }
+}
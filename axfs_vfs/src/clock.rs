@@ -0,0 +1,124 @@
+//! Time sources for node timestamps.
+//!
+//! Timestamps are represented as a [`Duration`] since the Unix epoch. All
+//! filesystems read the current time from a [`VfsClock`]: either one supplied
+//! per filesystem instance, or the global clock installed with
+//! [`set_global_clock`]. The kernel can wire its RTC in, while tests can use
+//! a [`ManualClock`] to get deterministic timestamps.
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use spin::RwLock;
+
+/// A source of the current time.
+pub trait VfsClock: Send + Sync {
+    /// Returns the current time, as a duration since the Unix epoch.
+    fn now(&self) -> Duration;
+}
+
+/// A clock that only changes when told to.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::clock::ManualClock;
+/// use axfs_vfs::VfsClock;
+/// use core::time::Duration;
+///
+/// let clock = ManualClock::new(Duration::from_secs(100));
+/// clock.advance(Duration::from_secs(5));
+/// assert_eq!(clock.now(), Duration::from_secs(105));
+/// ```
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    nanos: AtomicU64,
+}
+
+impl ManualClock {
+    /// Creates a new clock stopped at `now`.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The initial time, since the Unix epoch
+    pub const fn new(now: Duration) -> Self {
+        Self {
+            nanos: AtomicU64::new(now.as_nanos() as u64),
+        }
+    }
+
+    /// Sets the current time.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The new time, since the Unix epoch
+    pub fn set(&self, now: Duration) {
+        self.nanos.store(now.as_nanos() as u64, Ordering::SeqCst);
+    }
+
+    /// Moves the current time forward.
+    ///
+    /// # Arguments
+    ///
+    /// * `delta` - The amount of time to add
+    pub fn advance(&self, delta: Duration) {
+        self.nanos
+            .fetch_add(delta.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl VfsClock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
+
+static GLOBAL_CLOCK: RwLock<Option<Arc<dyn VfsClock>>> = RwLock::new(None);
+
+/// Installs the global clock, used by filesystems without their own clock.
+///
+/// # Arguments
+///
+/// * `clock` - The new global clock, or `None` to reset it
+pub fn set_global_clock(clock: Option<Arc<dyn VfsClock>>) {
+    *GLOBAL_CLOCK.write() = clock;
+}
+
+/// Returns the current time of the global clock.
+///
+/// # Returns
+///
+/// The time since the Unix epoch, or [`Duration::ZERO`] if no global clock
+/// is installed.
+pub fn now() -> Duration {
+    GLOBAL_CLOCK
+        .read()
+        .as_ref()
+        .map_or(Duration::ZERO, |clock| clock.now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::default();
+        assert_eq!(clock.now(), Duration::ZERO);
+        clock.set(Duration::from_secs(10));
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now(), Duration::from_millis(11_500));
+    }
+
+    #[test]
+    fn test_global_clock() {
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(42)));
+        set_global_clock(Some(clock.clone()));
+        assert_eq!(now(), Duration::from_secs(42));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(now(), Duration::from_secs(43));
+        set_global_clock(None);
+        assert_eq!(now(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,176 @@
+use alloc::sync::Arc;
+use spin::RwLock;
+
+use crate::access::{check_access, Access};
+use crate::{Credentials, Umask, VfsNodeAttr, VfsNodePerm, VfsResult};
+
+/// The context of the caller of a filesystem operation.
+///
+/// It is passed to [`lookup_ctx()`](crate::VfsNodeOps::lookup_ctx),
+/// [`create_ctx()`](crate::VfsNodeOps::create_ctx) and
+/// [`remove_ctx()`](crate::VfsNodeOps::remove_ctx), so that filesystems can
+/// enforce permissions and set the owner and mode of new nodes. Filesystems
+/// that do not use it keep the behavior of the methods without context.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{Credentials, VfsContext, VfsNodePerm};
+///
+/// let ctx = VfsContext::new(Credentials::new(1000, 100), 0o027);
+/// let perm = ctx.apply_umask(VfsNodePerm::from_bits_truncate(0o666));
+/// assert_eq!(perm.mode(), 0o640);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VfsContext {
+    cred: Credentials,
+    umask: Umask,
+}
+
+impl VfsContext {
+    /// The default file mode creation mask.
+    pub const DEFAULT_UMASK: u16 = Umask::DEFAULT.bits();
+
+    /// Creates a new context.
+    ///
+    /// # Arguments
+    ///
+    /// * `cred` - The identity of the caller
+    /// * `umask` - The permission bits to clear on the nodes created by the
+    ///   caller; only the low 9 bits are used
+    pub const fn new(cred: Credentials, umask: u16) -> Self {
+        Self {
+            cred,
+            umask: Umask::new(umask),
+        }
+    }
+
+    /// Returns the context of the superuser, with the default umask.
+    pub const fn root() -> Self {
+        Self::new(Credentials::root(), Self::DEFAULT_UMASK)
+    }
+
+    /// Returns the identity of the caller.
+    pub const fn cred(&self) -> &Credentials {
+        &self.cred
+    }
+
+    /// Returns the file mode creation mask of the caller.
+    pub const fn umask(&self) -> u16 {
+        self.umask.bits()
+    }
+
+    /// Returns the file mode creation mask of the caller, as a [`Umask`].
+    pub const fn umask_mask(&self) -> Umask {
+        self.umask
+    }
+
+    /// Returns `perm` without the bits of the umask, see
+    /// [`VfsNodePerm::apply_umask`].
+    ///
+    /// # Arguments
+    ///
+    /// * `perm` - The permissions requested for a new node
+    pub const fn apply_umask(&self, perm: VfsNodePerm) -> VfsNodePerm {
+        perm.apply_umask(self.umask)
+    }
+
+    /// Checks that the caller may access the node of `attr` as `access`
+    /// requests, see [`check_access`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::PermissionDenied`](crate::VfsError::PermissionDenied)
+    /// if a requested access is not granted.
+    pub fn check(&self, attr: &VfsNodeAttr, access: Access) -> VfsResult {
+        check_access(attr, &self.cred, access)
+    }
+}
+
+impl Default for VfsContext {
+    /// Returns the context of the superuser, see [`VfsContext::root`].
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+/// The source of the context of the task calling a filesystem operation.
+///
+/// The kernel implements it to return the credentials and umask of the
+/// current task, and installs it with [`set_context_provider`]. With their
+/// enforcement feature enabled, filesystems then check the permissions of
+/// the operations called without an explicit context.
+pub trait ContextProvider: Send + Sync {
+    /// Returns the context of the current task.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the caller is not a task subject to permission
+    /// checks, such as the kernel itself.
+    fn current(&self) -> Option<VfsContext>;
+}
+
+static CONTEXT_PROVIDER: RwLock<Option<Arc<dyn ContextProvider>>> = RwLock::new(None);
+
+/// Installs the provider of the context of the current task.
+///
+/// # Arguments
+///
+/// * `provider` - The new provider, or `None` to reset it
+pub fn set_context_provider(provider: Option<Arc<dyn ContextProvider>>) {
+    *CONTEXT_PROVIDER.write() = provider;
+}
+
+/// Returns the context of the current task.
+///
+/// # Returns
+///
+/// Returns the context given by the installed [`ContextProvider`], or
+/// `None` if none is installed or the caller has no context.
+pub fn current_context() -> Option<VfsContext> {
+    CONTEXT_PROVIDER
+        .read()
+        .as_ref()
+        .and_then(|provider| provider.current())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VfsError, VfsNodeType};
+
+    #[test]
+    fn test_context() {
+        let ctx = VfsContext::new(Credentials::new(1, 1), 0o7077);
+        assert_eq!(ctx.umask(), 0o077);
+        assert_eq!(ctx.umask_mask(), Umask::new(0o077));
+        let perm = VfsNodePerm::from_bits_truncate(0o755);
+        assert_eq!(ctx.apply_umask(perm).mode(), 0o700);
+
+        let attr = VfsNodeAttr::new(perm, VfsNodeType::Dir, 0, 0).with_owner(0, 0);
+        assert_eq!(ctx.check(&attr, Access::READ | Access::EXEC), Ok(()));
+        assert_eq!(
+            ctx.check(&attr, Access::WRITE),
+            Err(VfsError::PermissionDenied)
+        );
+        assert_eq!(VfsContext::default().check(&attr, Access::WRITE), Ok(()));
+    }
+
+    struct Fixed(VfsContext);
+
+    impl ContextProvider for Fixed {
+        fn current(&self) -> Option<VfsContext> {
+            Some(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_context_provider() {
+        assert_eq!(current_context(), None);
+        let ctx = VfsContext::new(Credentials::new(1, 1), 0o077);
+        set_context_provider(Some(Arc::new(Fixed(ctx.clone()))));
+        assert_eq!(current_context(), Some(ctx));
+        set_context_provider(None);
+        assert_eq!(current_context(), None);
+    }
+}
@@ -0,0 +1,317 @@
+//! Utilities for copying file contents between nodes.
+
+use alloc::vec;
+
+use crate::{SeekHint, VfsError, VfsNodeOps, VfsResult};
+
+/// The granularity at which [`copy_sparse`] looks for holes.
+///
+/// Chunks of this size that contain only zeros are not written to the
+/// destination, leaving a hole in their place.
+pub const SPARSE_CHUNK_SIZE: usize = 4096;
+
+/// Copies the whole content of `src` into `dst`, preserving holes.
+///
+/// The destination is truncated to zero first. The holes reported by
+/// [`VfsNodeOps::seek_hint`] are skipped without being read, then every
+/// chunk of [`SPARSE_CHUNK_SIZE`] bytes of the data regions that contains
+/// data is written at the same offset. All-zero chunks are skipped too, and
+/// the destination is finally extended to the source size, so that trailing
+/// and intermediate holes are not materialized as zeros.
+///
+/// # Arguments
+///
+/// * `src` - The file to copy from
+/// * `dst` - The file to copy to
+///
+/// # Returns
+///
+/// Returns the logical size of the copied file on success, or an error
+/// otherwise.
+///
+/// # Errors
+///
+/// Returns any error reported by the underlying node operations, or
+/// [`VfsError::WriteZero`] if `dst` stops accepting data.
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::{VfsNodeOps, VfsResult};
+/// # fn example(src: &dyn VfsNodeOps, dst: &dyn VfsNodeOps) -> VfsResult {
+/// let size = axfs_vfs::copy::copy_sparse(src, dst)?;
+/// assert_eq!(dst.get_attr()?.size(), size);
+/// # Ok(())
+/// # }
+/// ```
+pub fn copy_sparse(src: &dyn VfsNodeOps, dst: &dyn VfsNodeOps) -> VfsResult<u64> {
+    let size = src.get_attr()?.size();
+    dst.truncate(0)?;
+
+    let mut buf = vec![0; SPARSE_CHUNK_SIZE];
+    let mut offset = 0;
+    let mut end = 0;
+    while offset < size {
+        if offset >= end {
+            let Some(data) = src.seek_hint(offset, SeekHint::Data)? else {
+                offset = size; // only a hole is left
+                break;
+            };
+            offset = data.min(size);
+            end = src
+                .seek_hint(offset, SeekHint::Hole)?
+                .map_or(size, |hole| hole.min(size));
+            if offset >= end {
+                break; // `src` was shrunk while copying
+            }
+        }
+        let len = (end - offset).min(SPARSE_CHUNK_SIZE as u64) as usize;
+        let n = src.read_at(offset, &mut buf[..len])?;
+        if n == 0 {
+            break; // `src` was shrunk while copying
+        }
+        if buf[..n].iter().any(|&b| b != 0) {
+            write_all_at(dst, offset, &buf[..n])?;
+        }
+        offset += n as u64;
+    }
+
+    dst.truncate(offset)?;
+    Ok(offset)
+}
+
+/// The size of the buffer through which [`copy_range`] copies data.
+pub const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Copies `len` bytes of `src` at `src_off` into `dst` at `dst_off`,
+/// through a buffer.
+///
+/// This is the default implementation of
+/// [`VfsNodeOps::copy_file_range`], which filesystems can use as a
+/// fallback when their fast path does not apply.
+///
+/// # Arguments
+///
+/// * `src` - The file to copy from
+/// * `src_off` - The offset in `src` of the range to copy
+/// * `dst` - The file to copy into, which may be `src`
+/// * `dst_off` - The offset in `dst` to copy the range at
+/// * `len` - The length of the range in bytes
+///
+/// # Returns
+///
+/// Returns the number of bytes copied, fewer than `len` if the end of
+/// `src` is reached.
+///
+/// # Errors
+///
+/// Returns [`VfsError::InvalidInput`] if `dst` is `src` and the ranges
+/// overlap, or [`VfsError::WriteZero`] if `dst` stops accepting data.
+/// Returns any error reported by the underlying node operations otherwise;
+/// the bytes copied before the error are lost.
+pub fn copy_range<S: VfsNodeOps + ?Sized>(
+    src: &S,
+    src_off: u64,
+    dst: &dyn VfsNodeOps,
+    dst_off: u64,
+    len: usize,
+) -> VfsResult<usize> {
+    if is_same_node(src, dst) && ranges_overlap(src_off, dst_off, len) {
+        return Err(VfsError::InvalidInput);
+    }
+    let mut buf = vec![0; len.min(COPY_CHUNK_SIZE)];
+    let mut copied = 0;
+    while copied < len {
+        let n = (len - copied).min(buf.len());
+        let n = src.read_at(src_off + copied as u64, &mut buf[..n])?;
+        if n == 0 {
+            break;
+        }
+        write_all_at(dst, dst_off + copied as u64, &buf[..n])?;
+        copied += n;
+    }
+    Ok(copied)
+}
+
+/// Returns whether `a` and `b` are the same node.
+pub(crate) fn is_same_node<S: VfsNodeOps + ?Sized>(a: &S, b: &dyn VfsNodeOps) -> bool {
+    core::ptr::addr_eq(a as *const S, b as *const dyn VfsNodeOps)
+}
+
+/// Returns whether the ranges of `len` bytes at `a` and `b` overlap.
+pub(crate) fn ranges_overlap(a: u64, b: u64, len: usize) -> bool {
+    len > 0 && a.abs_diff(b) < len as u64
+}
+
+/// Writes the whole `buf` to `node` at `offset`, retrying on short writes.
+pub(crate) fn write_all_at(node: &dyn VfsNodeOps, mut offset: u64, mut buf: &[u8]) -> VfsResult {
+    while !buf.is_empty() {
+        match node.write_at(offset, buf)? {
+            0 => return Err(VfsError::WriteZero),
+            n => {
+                offset += n as u64;
+                buf = &buf[n..];
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::VfsNodeAttr;
+    use alloc::vec::Vec;
+    use std::sync::Mutex;
+
+    /// A file that records every write it receives.
+    #[derive(Default)]
+    struct RecordingFile {
+        content: Mutex<Vec<u8>>,
+        writes: Mutex<Vec<(u64, usize)>>,
+    }
+
+    impl RecordingFile {
+        fn content(&self) -> Vec<u8> {
+            self.content.lock().unwrap().clone()
+        }
+    }
+
+    impl VfsNodeOps for RecordingFile {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_file(
+                self.content.lock().unwrap().len() as _,
+                0,
+            ))
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            let content = self.content.lock().unwrap();
+            let start = content.len().min(offset as usize);
+            let end = content.len().min(offset as usize + buf.len());
+            buf[..end - start].copy_from_slice(&content[start..end]);
+            Ok(end - start)
+        }
+
+        fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            let mut content = self.content.lock().unwrap();
+            let end = offset as usize + buf.len();
+            if end > content.len() {
+                content.resize(end, 0);
+            }
+            content[offset as usize..end].copy_from_slice(buf);
+            self.writes.lock().unwrap().push((offset, buf.len()));
+            Ok(buf.len())
+        }
+
+        fn truncate(&self, size: u64) -> VfsResult {
+            self.content.lock().unwrap().resize(size as _, 0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_copy_sparse_skips_holes() {
+        let chunk = SPARSE_CHUNK_SIZE as u64;
+        let src = RecordingFile::default();
+        src.write_at(0, b"head").unwrap();
+        src.write_at(3 * chunk, b"tail").unwrap();
+        src.truncate(5 * chunk).unwrap();
+
+        let dst = RecordingFile::default();
+        assert_eq!(copy_sparse(&src, &dst).unwrap(), 5 * chunk);
+        assert_eq!(dst.content(), src.content());
+        assert_eq!(
+            *dst.writes.lock().unwrap(),
+            [(0, SPARSE_CHUNK_SIZE), (3 * chunk, SPARSE_CHUNK_SIZE)]
+        );
+    }
+
+    #[test]
+    fn test_copy_sparse_overwrites_dst() {
+        let src = RecordingFile::default();
+        src.truncate(100).unwrap();
+
+        let dst = RecordingFile::default();
+        dst.write_at(0, &[0xff; 200]).unwrap();
+        dst.writes.lock().unwrap().clear();
+
+        assert_eq!(copy_sparse(&src, &dst).unwrap(), 100);
+        assert_eq!(dst.content(), [0; 100]);
+        assert!(dst.writes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_copy_range() {
+        let src = RecordingFile::default();
+        src.write_at(0, b"0123456789").unwrap();
+        let dst = RecordingFile::default();
+        assert_eq!(src.copy_file_range(2, &dst, 4, 5).unwrap(), 5);
+        assert_eq!(dst.content(), b"\x00\x00\x00\x0023456");
+        assert_eq!(src.copy_file_range(8, &dst, 0, 10).unwrap(), 2);
+        assert_eq!(src.copy_file_range(20, &dst, 0, 10).unwrap(), 0);
+
+        // copies within a file must not overlap
+        assert_eq!(src.copy_file_range(0, &src, 10, 10).unwrap(), 10);
+        assert_eq!(src.content(), b"01234567890123456789");
+        assert_eq!(
+            src.copy_file_range(0, &src, 5, 10).err(),
+            Some(VfsError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_copy_sparse_empty() {
+        let src = RecordingFile::default();
+        let dst = RecordingFile::default();
+        assert_eq!(copy_sparse(&src, &dst).unwrap(), 0);
+        assert!(dst.content().is_empty());
+    }
+
+    /// A file of `size` bytes with a single data chunk, that fails reads
+    /// of its holes.
+    struct HoleyFile {
+        size: u64,
+        data: u64,
+    }
+
+    impl VfsNodeOps for HoleyFile {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_file(self.size, 0))
+        }
+
+        fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+            let chunk = self.data..self.data + SPARSE_CHUNK_SIZE as u64;
+            Ok(match hint {
+                _ if offset >= self.size => None,
+                SeekHint::Data if offset < chunk.end => Some(offset.max(chunk.start)),
+                SeekHint::Data => None,
+                SeekHint::Hole if chunk.contains(&offset) => Some(chunk.end),
+                SeekHint::Hole => Some(offset),
+            })
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            assert!(offset >= self.data && offset < self.data + SPARSE_CHUNK_SIZE as u64);
+            buf.fill(1);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_copy_sparse_seeks_over_holes() {
+        let chunk = SPARSE_CHUNK_SIZE as u64;
+        let src = HoleyFile {
+            size: 1 << 22,
+            data: chunk * 3,
+        };
+        let dst = RecordingFile::default();
+        assert_eq!(copy_sparse(&src, &dst).unwrap(), 1 << 22);
+        assert_eq!(
+            *dst.writes.lock().unwrap(),
+            [(chunk * 3, SPARSE_CHUNK_SIZE)]
+        );
+    }
+}
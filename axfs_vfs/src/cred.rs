@@ -0,0 +1,162 @@
+use alloc::vec::Vec;
+
+bitflags::bitflags! {
+    /// The privileges that bypass the permission checks, like Linux
+    /// capabilities.
+    ///
+    /// The values are the bits of the Linux capability numbers, so that
+    /// they can be copied from a capability set.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Capabilities: u64 {
+        /// Change the owner and group of any node (`CAP_CHOWN`).
+        const CHOWN = 1 << 0;
+        /// Read, write and execute any node (`CAP_DAC_OVERRIDE`).
+        const DAC_OVERRIDE = 1 << 1;
+        /// Read any node and search any directory (`CAP_DAC_READ_SEARCH`).
+        const DAC_READ_SEARCH = 1 << 2;
+        /// Act as the owner of any node (`CAP_FOWNER`).
+        const FOWNER = 1 << 3;
+        /// Keep the set-user-ID and set-group-ID bits (`CAP_FSETID`).
+        const FSETID = 1 << 4;
+        /// Create device nodes (`CAP_MKNOD`).
+        const MKNOD = 1 << 27;
+    }
+}
+
+/// The identity of the caller of a filesystem operation.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::Credentials;
+///
+/// let cred = Credentials::new(1000, 100).with_groups(vec![10, 20]);
+/// assert!(cred.in_group(100));
+/// assert!(cred.in_group(20));
+/// assert!(!cred.is_root());
+/// assert!(cred.caps().is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    /// Effective user ID.
+    pub uid: u32,
+    /// Effective group ID.
+    pub gid: u32,
+    /// Supplementary group IDs.
+    pub groups: Vec<u32>,
+    /// Effective capabilities.
+    pub caps: Capabilities,
+}
+
+impl Credentials {
+    /// Creates new credentials without supplementary groups.
+    ///
+    /// The superuser gets all the capabilities, and the other users none,
+    /// as after `execve(2)` without file capabilities.
+    ///
+    /// # Arguments
+    ///
+    /// * `uid` - The effective user ID
+    /// * `gid` - The effective group ID
+    pub const fn new(uid: u32, gid: u32) -> Self {
+        Self {
+            uid,
+            gid,
+            groups: Vec::new(),
+            caps: if uid == 0 {
+                Capabilities::all()
+            } else {
+                Capabilities::empty()
+            },
+        }
+    }
+
+    /// Returns the credentials of the superuser (uid and gid 0).
+    pub const fn root() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// Returns the credentials with the given supplementary groups.
+    ///
+    /// # Arguments
+    ///
+    /// * `groups` - The supplementary group IDs
+    pub fn with_groups(mut self, groups: Vec<u32>) -> Self {
+        self.groups = groups;
+        self
+    }
+
+    /// Returns the credentials with the given capabilities.
+    ///
+    /// # Arguments
+    ///
+    /// * `caps` - The effective capabilities
+    pub const fn with_caps(mut self, caps: Capabilities) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    /// Returns the effective capabilities.
+    pub const fn caps(&self) -> Capabilities {
+        self.caps
+    }
+
+    /// Returns whether all the capabilities of `caps` are effective.
+    ///
+    /// # Arguments
+    ///
+    /// * `caps` - The capabilities to check
+    pub const fn has_caps(&self, caps: Capabilities) -> bool {
+        self.caps.contains(caps)
+    }
+
+    /// Returns whether these are the credentials of the superuser.
+    ///
+    /// Permission checks rely on [`caps()`](Self::caps) rather than on
+    /// the user ID.
+    pub const fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    /// Returns whether `gid` is the effective group or one of the
+    /// supplementary groups.
+    ///
+    /// # Arguments
+    ///
+    /// * `gid` - The group ID to check
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+}
+
+impl Default for Credentials {
+    /// Returns the credentials of the superuser, see [`Credentials::root`].
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_credentials() {
+        assert!(Credentials::root().is_root());
+        assert_eq!(Credentials::default(), Credentials::root());
+
+        let cred = Credentials::new(1000, 1000).with_groups(vec![4, 27]);
+        assert!(!cred.is_root());
+        assert!(cred.in_group(1000));
+        assert!(cred.in_group(27));
+        assert!(!cred.in_group(0));
+        assert!(!cred.has_caps(Capabilities::CHOWN));
+
+        let root = Credentials::root();
+        assert!(root.has_caps(Capabilities::DAC_OVERRIDE | Capabilities::FOWNER));
+        let jailed = root.with_caps(Capabilities::DAC_READ_SEARCH);
+        assert!(jailed.is_root());
+        assert!(!jailed.has_caps(Capabilities::DAC_OVERRIDE));
+    }
+}
@@ -0,0 +1,205 @@
+//! Current working directories.
+//!
+//! A [`Cwd`] is the directory a task resolves relative paths against: a
+//! reference to the directory node, which keeps working if the directory is
+//! moved, like a file descriptor opened for `fchdir()`, and its absolute
+//! path, for `getcwd()`.
+//!
+//! Paths are resolved one component at a time through a [`MountTable`], so
+//! that walking into a mount point enters the mounted filesystem, and `..`
+//! at the root of a mounted filesystem leaves it for the parent of the
+//! mount point. Symbolic links are not followed.
+
+use alloc::string::String;
+
+use crate::path::normalize;
+use crate::{VfsError, VfsNodeRef, VfsResult};
+
+/// The mounts of a namespace, as seen by path resolution.
+///
+/// This is implemented by the mount manager of the kernel.
+pub trait MountTable {
+    /// Returns the root directory of the namespace.
+    fn root(&self) -> VfsNodeRef;
+
+    /// Returns the root directory of the filesystem mounted at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A canonical absolute path, such as `/mnt/usb`
+    ///
+    /// # Returns
+    ///
+    /// Returns the root directory of the filesystem mounted last at `path`,
+    /// or `None` if nothing is mounted there.
+    fn mounted_at(&self, path: &str) -> Option<VfsNodeRef>;
+}
+
+/// A current working directory.
+///
+/// The working directory of a task starts at [`Cwd::root`], and is changed
+/// with [`chdir()`](Cwd::chdir), or with [`Cwd::new`] for `fchdir()`.
+#[derive(Clone)]
+pub struct Cwd {
+    dir: VfsNodeRef,
+    path: String,
+}
+
+impl Cwd {
+    /// Creates a working directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory node
+    /// * `path` - The absolute path of `dir`, normalized
+    ///
+    /// # Returns
+    ///
+    /// Returns the working directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `path` is not absolute, or
+    /// [`VfsError::NotADirectory`] if `dir` is not a directory.
+    pub fn new(dir: VfsNodeRef, path: &str) -> VfsResult<Self> {
+        if !path.starts_with('/') {
+            return Err(VfsError::InvalidInput);
+        }
+        if !dir.get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        Ok(Self {
+            dir,
+            path: normalize(path),
+        })
+    }
+
+    /// Creates a working directory at the root of the namespace.
+    pub fn root(mounts: &dyn MountTable) -> Self {
+        Self {
+            dir: mounts.root(),
+            path: String::from("/"),
+        }
+    }
+
+    /// Returns the directory node.
+    pub fn dir(&self) -> &VfsNodeRef {
+        &self.dir
+    }
+
+    /// Returns the absolute path of the directory, as when it was entered.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the absolute form of `path`, relative to this directory.
+    ///
+    /// The path is normalized, and `..` is resolved lexically: in `/a/d`,
+    /// `../b/./c` is `/a/b/c`.
+    pub fn absolute(&self, path: &str) -> String {
+        if path.starts_with('/') {
+            normalize(path)
+        } else {
+            normalize(&alloc::format!("{}/{path}", self.path))
+        }
+    }
+
+    /// Looks up the node at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mounts` - The mounts of the namespace
+    /// * `path` - An absolute path, or a path relative to this directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the node and its canonical absolute path.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`VfsNodeOps::lookup`](crate::VfsNodeOps::lookup),
+    /// such as [`VfsError::NotFound`].
+    pub fn lookup(&self, mounts: &dyn MountTable, path: &str) -> VfsResult<(VfsNodeRef, String)> {
+        let (mut node, mut abs) = match path.starts_with('/') {
+            true => (mounts.root(), String::from("/")),
+            false => (self.dir.clone(), self.path.clone()),
+        };
+        for name in path.split('/') {
+            match name {
+                "" | "." => {}
+                ".." => {
+                    if abs == "/" {
+                        continue;
+                    }
+                    let parent = &abs[..abs.rfind('/').unwrap_or(0).max(1)];
+                    node = match node.parent() {
+                        // the root of a mounted filesystem has no parent
+                        Some(dir) if mounts.mounted_at(&abs).is_none() => dir,
+                        _ => Self::root(mounts).lookup(mounts, parent)?.0,
+                    };
+                    abs.truncate(parent.len());
+                }
+                _ => {
+                    if !abs.ends_with('/') {
+                        abs.push('/');
+                    }
+                    abs.push_str(name);
+                    node = match mounts.mounted_at(&abs) {
+                        Some(root) => root,
+                        None => node.lookup(name)?,
+                    };
+                }
+            }
+        }
+        Ok((node, abs))
+    }
+
+    /// Changes to the directory at `path`, like `chdir()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `mounts` - The mounts of the namespace
+    /// * `path` - An absolute path, or a path relative to this directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the new working directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`lookup()`](Self::lookup), or
+    /// [`VfsError::NotADirectory`] if the node is not a directory.
+    pub fn chdir(&self, mounts: &dyn MountTable, path: &str) -> VfsResult<Self> {
+        let (dir, path) = self.lookup(mounts, path)?;
+        Self::new(dir, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeType};
+    use alloc::sync::Arc;
+
+    struct Node(VfsNodeType);
+
+    impl VfsNodeOps for Node {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new(VfsNodePerm::default_dir(), self.0, 0, 0))
+        }
+    }
+
+    #[test]
+    fn test_cwd_new() {
+        let dir: VfsNodeRef = Arc::new(Node(VfsNodeType::Dir));
+        let file: VfsNodeRef = Arc::new(Node(VfsNodeType::File));
+        let cwd = Cwd::new(dir.clone(), "/a//b/../c/").unwrap();
+        assert_eq!(cwd.path(), "/a/c");
+        assert_eq!(cwd.absolute("../d/./e"), "/a/d/e");
+        assert_eq!(cwd.absolute("../../../x"), "/x");
+        assert_eq!(cwd.absolute("/y/"), "/y");
+        assert_eq!(cwd.absolute(""), "/a/c");
+        assert_eq!(Cwd::new(dir, "a").err(), Some(VfsError::InvalidInput));
+        assert_eq!(Cwd::new(file, "/a").err(), Some(VfsError::NotADirectory));
+    }
+}
@@ -0,0 +1,534 @@
+//! Caching of lookups for slow filesystems.
+//!
+//! Each lookup of a path walks it one directory at a time, which is costly
+//! on a filesystem whose directories are read from a device or a network.
+//! A [`DcacheDir`] wraps a directory and keeps the nodes found by its
+//! lookups, by name, so that walking the same path again is only a few
+//! map searches. The nodes it returns are wrapped too, with their own
+//! cache, so that wrapping the root directory caches the whole tree.
+//!
+//! The entries of a directory are invalidated by the operations that
+//! create, remove or rename names in it through the wrapper. All the
+//! wrappers of a directory in the same tree share its entries, so a change
+//! made through any of them, such as one reached through a path with `..`,
+//! is seen by the others. Changes made
+//! by other means, such as directly on the wrapped nodes or by another
+//! client of a network filesystem, are not seen: they must be reported
+//! with [`invalidate()`](DcacheDir::invalidate) or
+//! [`invalidate_all()`](DcacheDir::invalidate_all).
+//!
+//! Only the paths made of plain names are cached. The paths with `.` or
+//! `..` components, and the lookups checking the permissions of a
+//! [`VfsContext`], are forwarded to the wrapped directory every time.
+//!
+//! # Examples
+//!
+//! ```
+//! # use axfs_vfs::{VfsNodeRef, VfsResult};
+//! use std::sync::Arc;
+//! use axfs_vfs::dcache::DcacheDir;
+//! use axfs_vfs::VfsNodeOps;
+//!
+//! # fn example(root: VfsNodeRef) -> VfsResult {
+//! let root = Arc::new(DcacheDir::new(root));
+//! // the first lookup walks the wrapped directories
+//! let libc = root.clone().lookup("usr/lib/libc.so.6")?;
+//! // the next ones only search the caches
+//! let libm = root.clone().lookup("usr/lib/libm.so.6")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+
+use spin::Mutex;
+
+use crate::path::{Component, Path};
+use crate::{
+    impl_vfs_forward, DeviceId, RenameFlags, VfsContext, VfsNodeOps, VfsNodeRef, VfsNodeType,
+    VfsResult,
+};
+
+/// The number of entries cached per directory by [`DcacheDir::new`].
+pub const DEFAULT_CAPACITY: usize = 64;
+
+/// The number of directories above which [`Cache::dirs`] is first pruned.
+const MIN_PRUNE: usize = 64;
+
+/// The cached entries of a directory, shared by all its wrappers.
+#[derive(Default)]
+struct Entries {
+    map: BTreeMap<String, Arc<DcacheDir>>,
+    /// Incremented by each invalidation, so that the result of a lookup
+    /// started before it is not cached.
+    generation: u64,
+}
+
+/// The state shared by the wrappers of a tree.
+struct Cache {
+    /// The entries of the wrapped directories, keyed by the address of the
+    /// directories. A key refers to the same directory while its entries
+    /// are alive, since their wrappers keep the directory alive.
+    dirs: Mutex<DirMap>,
+    /// The number of entries cached per directory.
+    capacity: usize,
+}
+
+/// The map of [`Cache::dirs`].
+struct DirMap {
+    map: BTreeMap<usize, Weak<Mutex<Entries>>>,
+    /// The size of the map at which the dead entries are removed.
+    prune_at: usize,
+}
+
+impl Cache {
+    /// Creates the state of a tree caching up to `capacity` entries per
+    /// directory.
+    fn new(capacity: usize) -> Self {
+        Self {
+            dirs: Mutex::new(DirMap {
+                map: BTreeMap::new(),
+                prune_at: MIN_PRUNE,
+            }),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Returns the entries of the directory `dir`, shared with its other
+    /// wrappers.
+    fn entries<N: ?Sized>(&self, dir: &Arc<N>) -> Arc<Mutex<Entries>> {
+        let key = Arc::as_ptr(dir) as *const () as usize;
+        let mut dirs = self.dirs.lock();
+        if let Some(entries) = dirs.map.get(&key).and_then(Weak::upgrade) {
+            return entries;
+        }
+        if dirs.map.len() >= dirs.prune_at {
+            dirs.map.retain(|_, entries| entries.strong_count() > 0);
+            dirs.prune_at = (dirs.map.len() * 2).max(MIN_PRUNE);
+        }
+        let entries = Arc::new(Mutex::new(Entries::default()));
+        dirs.map.insert(key, Arc::downgrade(&entries));
+        entries
+    }
+}
+
+/// A node caching the results of its lookups.
+///
+/// See the [module documentation](self) for when the cache is used and
+/// invalidated. The operations on the node itself are forwarded to the
+/// wrapped node as is.
+pub struct DcacheDir<N: ?Sized = dyn VfsNodeOps> {
+    inner: Arc<N>,
+    entries: Arc<Mutex<Entries>>,
+    cache: Arc<Cache>,
+}
+
+/// Returns whether `path` is only made of names, and can be cached.
+fn is_plain(path: &str) -> bool {
+    path.split('/').all(|name| !matches!(name, "" | "." | ".."))
+}
+
+impl<N: VfsNodeOps + ?Sized> DcacheDir<N> {
+    /// Wraps the node `inner`, caching up to [`DEFAULT_CAPACITY`] entries
+    /// per directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The directory to wrap, usually the root of a filesystem
+    pub fn new(inner: Arc<N>) -> Self {
+        Self::with_capacity(inner, DEFAULT_CAPACITY)
+    }
+
+    /// Wraps the node `inner`, caching up to `capacity` entries per
+    /// directory.
+    ///
+    /// A directory whose cache is full forgets all its entries before
+    /// caching a new one.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The directory to wrap, usually the root of a filesystem
+    /// * `capacity` - The number of entries cached in each directory, at
+    ///   least 1
+    pub fn with_capacity(inner: Arc<N>, capacity: usize) -> Self {
+        let cache = Arc::new(Cache::new(capacity));
+        Self {
+            entries: cache.entries(&inner),
+            inner,
+            cache,
+        }
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &Arc<N> {
+        &self.inner
+    }
+
+    /// Returns the number of entries cached in this directory.
+    pub fn cached_entries(&self) -> usize {
+        self.entries.lock().map.len()
+    }
+
+    /// Forgets the cached node of `path`, after it was changed without
+    /// going through the wrapper.
+    ///
+    /// The entries of the directories on the way are kept. A path with a
+    /// `..` component may refer to any directory, so it invalidates all the
+    /// entries of this directory instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the changed entry, relative to this directory
+    pub fn invalidate(&self, path: &str) {
+        if Path::new(path)
+            .components()
+            .any(|c| c == Component::ParentDir)
+        {
+            self.invalidate_all();
+            return;
+        }
+        let (name, rest) = Path::new(path).split_first();
+        let rest = rest.map(|rest| rest.as_str().trim_start_matches('/'));
+        match (name, rest) {
+            ("" | ".", Some(rest)) if !rest.is_empty() => self.invalidate(rest),
+            ("" | ".", _) => {}
+            (name, Some(rest)) if !rest.is_empty() => {
+                let child = self.entries.lock().map.get(name).cloned();
+                if let Some(child) = child {
+                    child.invalidate(rest);
+                }
+            }
+            (name, _) => {
+                let mut entries = self.entries.lock();
+                entries.generation += 1;
+                entries.map.remove(name);
+            }
+        }
+    }
+
+    /// Forgets all the entries cached in this directory.
+    pub fn invalidate_all(&self) {
+        let mut entries = self.entries.lock();
+        entries.generation += 1;
+        entries.map.clear();
+    }
+
+    /// Wraps a node returned by the wrapped node, sharing the entries of
+    /// its other wrappers in the tree.
+    fn wrap(&self, node: VfsNodeRef) -> Arc<DcacheDir> {
+        Arc::new(DcacheDir {
+            entries: self.cache.entries(&node),
+            inner: node,
+            cache: self.cache.clone(),
+        })
+    }
+
+    /// Returns the entry `name` of this directory, from the cache if
+    /// possible.
+    fn child(&self, name: &str) -> VfsResult<Arc<DcacheDir>> {
+        let generation = {
+            let entries = self.entries.lock();
+            if let Some(child) = entries.map.get(name) {
+                return Ok(child.clone());
+            }
+            entries.generation
+        };
+        let child = self.wrap(self.inner.clone().lookup(name)?);
+        let mut entries = self.entries.lock();
+        if entries.generation == generation {
+            if entries.map.len() >= self.cache.capacity {
+                entries.map.clear();
+            }
+            entries.map.insert(String::from(name), child.clone());
+        }
+        Ok(child)
+    }
+
+    /// Invalidates the entry `path` after an operation changed it, and
+    /// returns the result of the operation.
+    fn change(&self, path: &str, result: VfsResult) -> VfsResult {
+        self.invalidate(path);
+        result
+    }
+}
+
+impl<N: VfsNodeOps + ?Sized + 'static> VfsNodeOps for DcacheDir<N> {
+    fn parent(&self) -> Option<VfsNodeRef> {
+        // not cached: the directory may have been moved since
+        let parent = self.inner.parent()?;
+        Some(self.wrap(parent))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        if !is_plain(path) {
+            let node = self.inner.clone().lookup(path)?;
+            return Ok(self.wrap(node));
+        }
+        match path.split_once('/') {
+            Some((name, rest)) => self.child(name)?.lookup(rest),
+            None => self.child(path).map(|child| child as VfsNodeRef),
+        }
+    }
+
+    fn lookup_step(self: Arc<Self>, path: &str) -> VfsResult<(VfsNodeRef, usize)> {
+        if !is_plain(path) {
+            let (node, consumed) = self.inner.clone().lookup_step(path)?;
+            return Ok((self.wrap(node), consumed));
+        }
+        // walks the cached entries, stopping at the first mount point
+        crate::path::lookup_step(self, path)
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.change(path, self.inner.create(path, ty))
+    }
+
+    fn create_exclusive(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.change(path, self.inner.create_exclusive(path, ty))
+    }
+
+    fn lookup_ctx(self: Arc<Self>, ctx: Option<&VfsContext>, path: &str) -> VfsResult<VfsNodeRef> {
+        if ctx.is_none() {
+            return self.lookup(path);
+        }
+        // the permissions of each directory on the way must be checked
+        let node = self.inner.clone().lookup_ctx(ctx, path)?;
+        Ok(self.wrap(node))
+    }
+
+    fn create_ctx(&self, ctx: Option<&VfsContext>, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.change(path, self.inner.create_ctx(ctx, path, ty))
+    }
+
+    fn mknod(&self, path: &str, ty: VfsNodeType, dev: DeviceId) -> VfsResult {
+        self.change(path, self.inner.mknod(path, ty, dev))
+    }
+
+    fn create_symlink(&self, path: &str, target: &str) -> VfsResult {
+        self.change(path, self.inner.create_symlink(path, target))
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.change(path, self.inner.remove(path))
+    }
+
+    fn remove_ctx(&self, ctx: Option<&VfsContext>, path: &str) -> VfsResult {
+        self.change(path, self.inner.remove_ctx(ctx, path))
+    }
+
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.change(dst_path, self.inner.link(src_path, dst_path))
+    }
+
+    fn create_tmpfile(&self) -> VfsResult<VfsNodeRef> {
+        let node = self.inner.create_tmpfile()?;
+        Ok(self.wrap(node))
+    }
+
+    fn link_into(self: Arc<Self>, dir: &VfsNodeRef, name: &str) -> VfsResult {
+        // the inner node only knows the inner directories
+        let Some(dir) = dir.as_any().downcast_ref::<DcacheDir>() else {
+            return self.inner.clone().link_into(dir, name);
+        };
+        dir.change(name, self.inner.clone().link_into(&dir.inner, name))
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        let result = self.change(src_path, self.inner.rename(src_path, dst_path));
+        self.change(dst_path, result)
+    }
+
+    fn rename_flags(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult {
+        let result = self.inner.rename_flags(src_path, dst_path, flags);
+        self.change(dst_path, self.change(src_path, result))
+    }
+
+    impl_vfs_forward! {
+        inner;
+        except parent, lookup, lookup_step, create, create_exclusive, lookup_ctx, create_ctx, mknod,
+        create_symlink, remove, remove_ctx, link, create_tmpfile, link_into, rename, rename_flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{impl_vfs_dir_default, impl_vfs_non_dir_default, VfsError};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A directory of files and directories, counting its lookups.
+    struct Dir {
+        children: Mutex<BTreeMap<String, VfsNodeRef>>,
+        lookups: Arc<AtomicUsize>,
+    }
+
+    impl Dir {
+        fn new(lookups: &Arc<AtomicUsize>) -> Arc<Self> {
+            Arc::new(Self {
+                children: Mutex::new(BTreeMap::new()),
+                lookups: lookups.clone(),
+            })
+        }
+    }
+
+    impl VfsNodeOps for Dir {
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            self.lookups.fetch_add(1, Ordering::Relaxed);
+            let (name, rest) = Path::new(path).split_first();
+            if name == "." {
+                return match rest {
+                    Some(rest) => self.lookup(rest.as_str()),
+                    None => Ok(self),
+                };
+            }
+            let child = self.children.lock().get(name).cloned();
+            match (child, rest) {
+                (Some(child), Some(rest)) => child.lookup(rest.as_str()),
+                (Some(child), None) => Ok(child),
+                (None, _) => Err(VfsError::NotFound),
+            }
+        }
+
+        fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+            let node: VfsNodeRef = match ty {
+                VfsNodeType::Dir => Dir::new(&self.lookups),
+                _ => Arc::new(File),
+            };
+            self.children.lock().insert(String::from(path), node);
+            Ok(())
+        }
+
+        fn remove(&self, path: &str) -> VfsResult {
+            let (name, rest) = Path::new(path).split_first();
+            let mut children = self.children.lock();
+            match rest {
+                Some(rest) => match children.get(name) {
+                    Some(child) => child.remove(rest.as_str()),
+                    None => Err(VfsError::NotFound),
+                },
+                None => children.remove(name).map(drop).ok_or(VfsError::NotFound),
+            }
+        }
+
+        impl_vfs_dir_default! {}
+    }
+
+    struct File;
+
+    impl VfsNodeOps for File {
+        impl_vfs_non_dir_default! {}
+    }
+
+    #[test]
+    fn test_cached_lookup() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let inner = Dir::new(&lookups);
+        let root = Arc::new(DcacheDir::new(inner.clone()));
+        root.create("a", VfsNodeType::Dir).unwrap();
+        let a = root.clone().lookup("a").unwrap();
+        a.create("f", VfsNodeType::File).unwrap();
+
+        let f = root.clone().lookup("a/f").unwrap();
+        assert!(f.as_any().is::<DcacheDir>());
+        let count = lookups.load(Ordering::Relaxed);
+        root.clone().lookup("a/f").unwrap();
+        root.clone().lookup("a").unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), count);
+        assert_eq!(root.cached_entries(), 1);
+
+        // neither the paths with `.` nor the missing entries are cached
+        root.clone().lookup("./a").unwrap();
+        assert!(lookups.load(Ordering::Relaxed) > count);
+        let count = lookups.load(Ordering::Relaxed);
+        for _ in 0..2 {
+            let err = root.clone().lookup("a/g").err();
+            assert_eq!(err, Some(VfsError::NotFound));
+        }
+        assert_eq!(lookups.load(Ordering::Relaxed), count + 2);
+    }
+
+    #[test]
+    fn test_cached_lookup_step() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let root = Arc::new(DcacheDir::new(Dir::new(&lookups)));
+        root.create("a", VfsNodeType::Dir).unwrap();
+        root.clone()
+            .lookup("a")
+            .unwrap()
+            .create("f", VfsNodeType::File)
+            .unwrap();
+        root.clone().lookup("a/f").unwrap();
+
+        let count = lookups.load(Ordering::Relaxed);
+        let (f, consumed) = root.clone().lookup_step("a/f").unwrap();
+        assert_eq!(consumed, 2);
+        assert!(f.as_any().is::<DcacheDir>());
+        assert_eq!(lookups.load(Ordering::Relaxed), count);
+    }
+
+    #[test]
+    fn test_invalidation() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let inner = Dir::new(&lookups);
+        let root = Arc::new(DcacheDir::new(inner.clone()));
+        root.create("a", VfsNodeType::Dir).unwrap();
+        root.clone()
+            .lookup("a")
+            .unwrap()
+            .create("f", VfsNodeType::File)
+            .unwrap();
+        root.clone().lookup("a/f").unwrap();
+
+        // removed through the wrapper
+        root.remove("a/f").unwrap();
+        assert_eq!(root.clone().lookup("a/f").err(), Some(VfsError::NotFound));
+        assert_eq!(root.cached_entries(), 1);
+
+        // removed behind the wrapper
+        inner.remove("a").unwrap();
+        assert!(root.clone().lookup("a").is_ok());
+        root.invalidate("a");
+        assert_eq!(root.clone().lookup("a").err(), Some(VfsError::NotFound));
+        assert_eq!(root.cached_entries(), 0);
+
+        root.create("b", VfsNodeType::File).unwrap();
+        root.clone().lookup("b").unwrap();
+        root.invalidate("x/../b");
+        assert_eq!(root.cached_entries(), 0);
+    }
+
+    #[test]
+    fn test_shared_entries() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let root = Arc::new(DcacheDir::with_capacity(Dir::new(&lookups), 1));
+        root.create("a", VfsNodeType::Dir).unwrap();
+        root.create("b", VfsNodeType::Dir).unwrap();
+        let a = root.clone().lookup("a").unwrap();
+        a.create("f", VfsNodeType::File).unwrap();
+        a.create("g", VfsNodeType::File).unwrap();
+        root.clone().lookup("a/f").unwrap();
+
+        // removed through another wrapper of the same directory
+        root.clone().lookup("./a").unwrap().remove("f").unwrap();
+        assert_eq!(root.clone().lookup("a/f").err(), Some(VfsError::NotFound));
+
+        // the wrapper created after the cache of the root was reset
+        a.clone().lookup("g").unwrap();
+        root.clone().lookup("b").unwrap();
+        root.clone().lookup("a").unwrap().remove("g").unwrap();
+        assert_eq!(a.lookup("g").err(), Some(VfsError::NotFound));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let root = Arc::new(DcacheDir::with_capacity(Dir::new(&lookups), 2));
+        for name in ["a", "b", "c"] {
+            root.create(name, VfsNodeType::File).unwrap();
+            root.clone().lookup(name).unwrap();
+        }
+        assert_eq!(root.cached_entries(), 1);
+    }
+}
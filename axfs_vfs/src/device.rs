@@ -0,0 +1,101 @@
+//! Device numbers and the resolution of device nodes to drivers.
+//!
+//! Device nodes created in a regular filesystem (for instance by `mknod`
+//! or an archive extractor) only record a type and a [`DeviceId`]. The
+//! filesystem forwards their operations to the driver node returned by a
+//! [`DeviceResolver`], typically the device registry of `axfs_devfs`.
+
+use crate::{VfsNodeRef, VfsNodeType};
+
+/// A device number, made of a major and a minor number.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::DeviceId;
+///
+/// // /dev/null
+/// let dev = DeviceId::new(1, 3);
+/// assert_eq!(dev.to_raw(), 0x103);
+/// assert_eq!(DeviceId::from_raw(0x103), dev);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceId {
+    major: u32,
+    minor: u32,
+}
+
+impl DeviceId {
+    /// Creates a device number.
+    ///
+    /// # Arguments
+    ///
+    /// * `major` - The major number, identifying the driver
+    /// * `minor` - The minor number, identifying the device of the driver
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Returns the major number.
+    pub const fn major(&self) -> u32 {
+        self.major
+    }
+
+    /// Returns the minor number.
+    pub const fn minor(&self) -> u32 {
+        self.minor
+    }
+
+    /// Encodes the device number as a Linux `dev_t`, as `makedev()` does.
+    pub const fn to_raw(&self) -> u64 {
+        let (major, minor) = (self.major as u64, self.minor as u64);
+        ((major & 0xffff_f000) << 32)
+            | ((major & 0xfff) << 8)
+            | ((minor & 0xffff_ff00) << 12)
+            | (minor & 0xff)
+    }
+
+    /// Decodes a Linux `dev_t`, as `major()` and `minor()` do.
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The encoded device number
+    pub const fn from_raw(raw: u64) -> Self {
+        let major = ((raw >> 32) & 0xffff_f000) | ((raw >> 8) & 0xfff);
+        let minor = ((raw >> 12) & 0xffff_ff00) | (raw & 0xff);
+        Self::new(major as u32, minor as u32)
+    }
+}
+
+/// Maps device numbers to the nodes implementing the devices.
+pub trait DeviceResolver: Send + Sync {
+    /// Returns the node implementing a device.
+    ///
+    /// # Arguments
+    ///
+    /// * `ty` - The type of the device, [`VfsNodeType::CharDevice`] or
+    ///   [`VfsNodeType::BlockDevice`]
+    /// * `dev` - The device number
+    ///
+    /// # Returns
+    ///
+    /// Returns the node of the device, or `None` if no such device exists.
+    fn resolve(&self, ty: VfsNodeType, dev: DeviceId) -> Option<VfsNodeRef>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_id_raw() {
+        let dev = DeviceId::new(8, 1);
+        assert_eq!((dev.major(), dev.minor()), (8, 1));
+        assert_eq!(dev.to_raw(), 0x801);
+
+        // large numbers use the extended encoding
+        let dev = DeviceId::new(0x12345, 0x6789a);
+        assert_eq!(dev.to_raw(), 0x0001_2000_6783_459a);
+        assert_eq!(DeviceId::from_raw(dev.to_raw()), dev);
+    }
+}
@@ -0,0 +1,127 @@
+//! Digests of whole filesystem trees.
+//!
+//! [`tree_digest`] hashes the names, types and contents of a subtree into a
+//! Merkle digest: the digest of a directory covers the digests of its
+//! children, sorted by name. Two trees with the same digest are identical,
+//! up to hash collisions, regardless of the order in which they were built.
+//! Metadata such as permissions, owners, times and inode numbers is not
+//! covered.
+//!
+//! The hash is the 64-bit FNV-1a, which is fast but not cryptographic: the
+//! digest detects accidental differences, not tampering.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::limits::MAX_PATH;
+use crate::{DirEntries, VfsError, VfsNodeRef, VfsNodeType, VfsResult};
+
+/// The digest of a node and of all the nodes below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TreeDigest(u64);
+
+impl TreeDigest {
+    /// Returns the digest as an integer.
+    pub const fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for TreeDigest {
+    /// Writes the digest as 16 hexadecimal digits.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// A streaming 64-bit FNV-1a hasher.
+struct Fnv64(u64);
+
+impl Fnv64 {
+    const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100_0000_01b3);
+        }
+    }
+
+    /// Writes `bytes` preceded by their length, so that consecutive fields
+    /// cannot be confused.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    fn finish(&self) -> TreeDigest {
+        TreeDigest(self.0)
+    }
+}
+
+/// Computes the digest of the subtree of `node`.
+///
+/// Files are read to their end and symbolic links are not followed, but
+/// hashed by target. Devices, FIFOs and sockets are only hashed by type, as
+/// reading them may not end or have side effects.
+///
+/// # Arguments
+///
+/// * `node` - The root of the subtree, of any type
+///
+/// # Returns
+///
+/// Returns the digest of the subtree.
+///
+/// # Errors
+///
+/// Returns the errors of reading the directories, files and symbolic
+/// links, or [`VfsError::InvalidData`] if a symbolic link target is not
+/// UTF-8.
+pub fn tree_digest(node: &VfsNodeRef) -> VfsResult<TreeDigest> {
+    let ty = node.get_attr()?.file_type();
+    let mut hasher = Fnv64::new();
+    hasher.write(&[ty as u8]);
+    match ty {
+        VfsNodeType::Dir => {
+            let mut names = Vec::new();
+            for entry in DirEntries::new(&**node) {
+                let entry = entry?;
+                match entry.name() {
+                    "." | ".." => {}
+                    name => names.push(String::from(name)),
+                }
+            }
+            names.sort_unstable();
+            for name in names {
+                let child = node.clone().lookup(&name)?;
+                hasher.write_field(name.as_bytes());
+                hasher.write(&tree_digest(&child)?.0.to_le_bytes());
+            }
+        }
+        VfsNodeType::File => {
+            let mut buf = [0; 4096];
+            let mut offset = 0;
+            loop {
+                let n = node.read_at(offset, &mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                hasher.write(&buf[..n]);
+                offset += n as u64;
+            }
+            hasher.write(&offset.to_le_bytes());
+        }
+        VfsNodeType::SymLink => {
+            let mut buf = [0; MAX_PATH];
+            let len = node.read_link(&mut buf)?;
+            let target = core::str::from_utf8(&buf[..len]).map_err(|_| VfsError::InvalidData)?;
+            hasher.write_field(target.as_bytes());
+        }
+        _ => {}
+    }
+    Ok(hasher.finish())
+}
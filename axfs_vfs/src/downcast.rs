@@ -0,0 +1,166 @@
+use alloc::sync::Arc;
+
+use crate::{VfsError, VfsNodeOps, VfsNodeRef, VfsResult};
+
+/// Typed access to the concrete node behind a [`VfsNodeRef`].
+///
+/// These helpers are built on [`VfsNodeOps::as_any`] and
+/// [`VfsNodeOps::as_any_arc`], and replace the
+/// `node.as_any().downcast_ref::<T>()` pattern with a typed [`Arc`] or a
+/// clear error.
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::{VfsNodeOps, VfsNodeRef, VfsNodeRefExt, VfsResult};
+/// # fn example<DirNode: VfsNodeOps + 'static>(root: VfsNodeRef) -> VfsResult {
+/// let dir = root.lookup_as::<DirNode>("tmp")?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait VfsNodeRefExt {
+    /// Returns a reference to the concrete node, if it is of type `T`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(&T)` if the node is a `T`, or `None` otherwise.
+    fn downcast_ref<T: VfsNodeOps + 'static>(&self) -> Option<&T>;
+
+    /// Converts the node into an [`Arc`] of its concrete type.
+    ///
+    /// # Returns
+    ///
+    /// Returns the node as an `Arc<T>` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::Unsupported`] if the node does not support
+    /// [`VfsNodeOps::as_any_arc`], or [`VfsError::InvalidInput`] if it is
+    /// not a `T`.
+    fn downcast<T: VfsNodeOps + 'static>(self) -> VfsResult<Arc<T>>;
+
+    /// Looks up the node at `path` and converts it into an [`Arc`] of its
+    /// concrete type.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the node, relative to this directory
+    ///
+    /// # Returns
+    ///
+    /// Returns the node as an `Arc<T>` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`VfsNodeOps::lookup`], or those of
+    /// [`downcast()`](Self::downcast).
+    fn lookup_as<T: VfsNodeOps + 'static>(self, path: &str) -> VfsResult<Arc<T>>;
+}
+
+impl VfsNodeRefExt for VfsNodeRef {
+    fn downcast_ref<T: VfsNodeOps + 'static>(&self) -> Option<&T> {
+        let any = self.as_any();
+        // `as_any()` may forward to an inner object, which is not the node
+        // held by the `Arc`
+        if !core::ptr::addr_eq(any, Arc::as_ptr(self)) {
+            return None;
+        }
+        any.downcast_ref::<T>()
+    }
+
+    fn downcast<T: VfsNodeOps + 'static>(self) -> VfsResult<Arc<T>> {
+        self.as_any_arc()
+            .ok_or(VfsError::Unsupported)?
+            .downcast::<T>()
+            .map_err(|_| VfsError::InvalidInput)
+    }
+
+    fn lookup_as<T: VfsNodeOps + 'static>(self, path: &str) -> VfsResult<Arc<T>> {
+        self.lookup(path)?.downcast()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{impl_vfs_dir_default, impl_vfs_non_dir_default, VfsNodeAttr};
+
+    struct Dir;
+    struct File;
+    /// Forwards `as_any()` to a shared `File`.
+    struct Proxy(Arc<File>);
+
+    impl VfsNodeOps for Dir {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_dir(0, 0))
+        }
+
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            match path {
+                "file" => Ok(Arc::new(File)),
+                "." | "" => Ok(self),
+                _ => Err(VfsError::NotFound),
+            }
+        }
+
+        impl_vfs_dir_default! {}
+    }
+
+    impl VfsNodeOps for File {
+        impl_vfs_non_dir_default! {}
+    }
+
+    impl VfsNodeOps for Proxy {
+        fn as_any(&self) -> &dyn core::any::Any {
+            self.0.as_any()
+        }
+    }
+
+    #[test]
+    fn test_downcast() {
+        let dir: VfsNodeRef = Arc::new(Dir);
+        assert!(dir.downcast_ref::<Dir>().is_some());
+        assert!(dir.downcast_ref::<File>().is_none());
+        assert_eq!(
+            dir.clone().downcast::<File>().err(),
+            Some(VfsError::InvalidInput)
+        );
+        let typed: Arc<Dir> = dir.clone().downcast().unwrap();
+        assert_eq!(Arc::strong_count(&typed), 2);
+    }
+
+    #[test]
+    fn test_as_any_arc() {
+        let file: VfsNodeRef = Arc::new(File);
+        let any = file.clone().as_any_arc().unwrap();
+        assert!(any.clone().downcast::<Dir>().is_err());
+        let typed: Arc<File> = any.downcast().unwrap();
+        assert_eq!(Arc::strong_count(&typed), 2);
+        drop(file);
+        assert_eq!(Arc::strong_count(&typed), 1);
+    }
+
+    #[test]
+    fn test_downcast_forwarded_as_any() {
+        let proxy: VfsNodeRef = Arc::new(Proxy(Arc::new(File)));
+        assert!(proxy.as_any().is::<File>());
+        assert!(proxy.clone().as_any_arc().is_none());
+        assert!(proxy.downcast_ref::<File>().is_none());
+        assert_eq!(proxy.downcast::<File>().err(), Some(VfsError::Unsupported));
+    }
+
+    #[test]
+    fn test_lookup_as() {
+        let dir: VfsNodeRef = Arc::new(Dir);
+        assert!(dir.clone().lookup_as::<File>("file").is_ok());
+        assert!(dir.clone().lookup_as::<Dir>(".").is_ok());
+        assert_eq!(
+            dir.clone().lookup_as::<Dir>("file").err(),
+            Some(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            dir.lookup_as::<File>("missing").err(),
+            Some(VfsError::NotFound)
+        );
+    }
+}
@@ -0,0 +1,328 @@
+//! Registry of disk-backed filesystem drivers.
+//!
+//! A [`FilesystemDriver`] recognizes its on-disk format with
+//! [`probe()`](FilesystemDriver::probe), and creates a filesystem on a
+//! block device with [`mount()`](FilesystemDriver::mount). The drivers are
+//! registered in a [`DriverRegistry`] under their name, which the mount
+//! manager uses to mount a device by filesystem type, or to detect the type
+//! as `mount -t auto` does.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::RwLock;
+
+use crate::{BlockDeviceOps, MountOptions, VfsError, VfsOps, VfsResult};
+
+/// The filesystem type that makes [`DriverRegistry::mount`] probe the
+/// device.
+pub const AUTO: &str = "auto";
+
+/// A filesystem implementation stored on a block device.
+pub trait FilesystemDriver: Send + Sync {
+    /// Returns the name of the filesystem type, such as `"ext4"`.
+    fn name(&self) -> &str;
+
+    /// Returns whether `dev` holds a filesystem of this type.
+    ///
+    /// This usually checks a magic number in the superblock, and must not
+    /// modify the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - The block device to inspect
+    fn probe(&self, dev: &Arc<dyn BlockDeviceOps>) -> bool;
+
+    /// Creates a filesystem from the content of `dev`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - The block device holding the filesystem
+    /// * `options` - The options of the mount
+    ///
+    /// # Returns
+    ///
+    /// Returns the filesystem, ready to be mounted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the device does not hold a valid filesystem of
+    /// this type, or an option is not supported.
+    fn mount(
+        &self,
+        dev: Arc<dyn BlockDeviceOps>,
+        options: &MountOptions,
+    ) -> VfsResult<Arc<dyn VfsOps>>;
+}
+
+/// The registered filesystem drivers, like `/proc/filesystems`.
+///
+/// Devices are probed in registration order, so drivers with a stricter
+/// probe should be registered first.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_vfs::block::BlockDeviceOps;
+/// use axfs_vfs::driver::{DriverRegistry, FilesystemDriver};
+/// use axfs_vfs::{MountOptions, VfsError, VfsOps, VfsResult};
+///
+/// struct NoFs;
+///
+/// impl FilesystemDriver for NoFs {
+///     fn name(&self) -> &str {
+///         "nofs"
+///     }
+///     fn probe(&self, _dev: &Arc<dyn BlockDeviceOps>) -> bool {
+///         false
+///     }
+///     fn mount(
+///         &self,
+///         _dev: Arc<dyn BlockDeviceOps>,
+///         _options: &MountOptions,
+///     ) -> VfsResult<Arc<dyn VfsOps>> {
+///         Err(VfsError::InvalidData)
+///     }
+/// }
+///
+/// let registry = DriverRegistry::new();
+/// registry.register(Arc::new(NoFs)).unwrap();
+/// assert_eq!(registry.names(), ["nofs"]);
+/// assert_eq!(
+///     registry.register(Arc::new(NoFs)).err(),
+///     Some(VfsError::AlreadyExists)
+/// );
+/// ```
+#[derive(Default)]
+pub struct DriverRegistry {
+    drivers: RwLock<Vec<Arc<dyn FilesystemDriver>>>,
+}
+
+impl DriverRegistry {
+    /// Creates an empty registry.
+    pub const fn new() -> Self {
+        Self {
+            drivers: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Registers a driver under its name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::AlreadyExists`] if a driver with the same name is
+    /// registered, or [`VfsError::InvalidInput`] if the name is empty or
+    /// [`AUTO`].
+    pub fn register(&self, driver: Arc<dyn FilesystemDriver>) -> VfsResult {
+        let name = driver.name();
+        if name.is_empty() || name == AUTO {
+            return Err(VfsError::InvalidInput);
+        }
+        let mut drivers = self.drivers.write();
+        if drivers.iter().any(|d| d.name() == name) {
+            return Err(VfsError::AlreadyExists);
+        }
+        drivers.push(driver);
+        Ok(())
+    }
+
+    /// Unregisters the driver named `name`.
+    ///
+    /// The filesystems it mounted are not affected.
+    ///
+    /// # Returns
+    ///
+    /// Returns the driver that was registered.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NotFound`] if no driver has this name.
+    pub fn unregister(&self, name: &str) -> VfsResult<Arc<dyn FilesystemDriver>> {
+        let mut drivers = self.drivers.write();
+        let pos = drivers
+            .iter()
+            .position(|d| d.name() == name)
+            .ok_or(VfsError::NotFound)?;
+        Ok(drivers.remove(pos))
+    }
+
+    /// Returns the driver named `name`, if registered.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn FilesystemDriver>> {
+        self.drivers
+            .read()
+            .iter()
+            .find(|d| d.name() == name)
+            .cloned()
+    }
+
+    /// Returns the names of the registered drivers, in registration order.
+    pub fn names(&self) -> Vec<String> {
+        self.drivers
+            .read()
+            .iter()
+            .map(|d| d.name().into())
+            .collect()
+    }
+
+    /// Returns the first driver recognizing the filesystem on `dev`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - The block device to inspect
+    pub fn probe(&self, dev: &Arc<dyn BlockDeviceOps>) -> Option<Arc<dyn FilesystemDriver>> {
+        self.drivers.read().iter().find(|d| d.probe(dev)).cloned()
+    }
+
+    /// Creates the filesystem of type `fs_type` stored on `dev`.
+    ///
+    /// The registry is not locked while the driver mounts the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `fs_type` - The name of a driver, or [`AUTO`] to use the first
+    ///   driver recognizing the device
+    /// * `dev` - The block device holding the filesystem
+    /// * `options` - The options of the mount
+    ///
+    /// # Returns
+    ///
+    /// Returns the filesystem, ready to be mounted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NoSuchDevice`] if no driver has this name, like
+    /// `ENODEV`, or [`VfsError::InvalidInput`] if `fs_type` is [`AUTO`] and
+    /// no driver recognizes the device. Returns the errors of
+    /// [`FilesystemDriver::mount`] otherwise.
+    pub fn mount(
+        &self,
+        fs_type: &str,
+        dev: Arc<dyn BlockDeviceOps>,
+        options: &MountOptions,
+    ) -> VfsResult<Arc<dyn VfsOps>> {
+        let driver = match fs_type {
+            AUTO => self.probe(&dev).ok_or(VfsError::InvalidInput)?,
+            _ => self.get(fs_type).ok_or(VfsError::NoSuchDevice)?,
+        };
+        driver.mount(dev, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VfsNodeRef;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Disk(u8);
+
+    impl BlockDeviceOps for Disk {
+        fn block_size(&self) -> usize {
+            1
+        }
+
+        fn num_blocks(&self) -> u64 {
+            1
+        }
+
+        fn read_block(&self, _block_id: u64, buf: &mut [u8]) -> VfsResult {
+            buf.fill(self.0);
+            Ok(())
+        }
+
+        fn write_block(&self, _block_id: u64, _buf: &[u8]) -> VfsResult {
+            Err(VfsError::PermissionDenied)
+        }
+    }
+
+    struct Fs;
+
+    impl VfsOps for Fs {
+        fn root_dir(&self) -> VfsNodeRef {
+            unimplemented!()
+        }
+    }
+
+    /// Recognizes the disks whose first byte is `magic`.
+    struct Driver {
+        name: &'static str,
+        magic: u8,
+        mounts: AtomicUsize,
+    }
+
+    impl Driver {
+        fn new(name: &'static str, magic: u8) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                magic,
+                mounts: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    impl FilesystemDriver for Driver {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn probe(&self, dev: &Arc<dyn BlockDeviceOps>) -> bool {
+            let mut magic = [0];
+            dev.read_block(0, &mut magic).is_ok() && magic[0] == self.magic
+        }
+
+        fn mount(
+            &self,
+            dev: Arc<dyn BlockDeviceOps>,
+            options: &MountOptions,
+        ) -> VfsResult<Arc<dyn VfsOps>> {
+            options.check_known(&[])?;
+            if !self.probe(&dev) {
+                return Err(VfsError::InvalidData);
+            }
+            self.mounts.fetch_add(1, Ordering::Relaxed);
+            Ok(Arc::new(Fs))
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = DriverRegistry::new();
+        let a = Driver::new("a", 1);
+        let b = Driver::new("b", 2);
+        registry.register(a.clone()).unwrap();
+        registry.register(b.clone()).unwrap();
+        assert_eq!(
+            registry.register(Driver::new(AUTO, 3)).err(),
+            Some(VfsError::InvalidInput)
+        );
+        assert_eq!(registry.names(), ["a", "b"]);
+        assert!(registry.get("b").is_some());
+
+        let opts = MountOptions::new();
+        let disk: Arc<dyn BlockDeviceOps> = Arc::new(Disk(2));
+        assert!(registry.mount(AUTO, disk.clone(), &opts).is_ok());
+        assert_eq!(b.mounts.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            registry.mount("a", disk.clone(), &opts).err(),
+            Some(VfsError::InvalidData)
+        );
+        assert_eq!(
+            registry.mount("c", disk.clone(), &opts).err(),
+            Some(VfsError::NoSuchDevice)
+        );
+        let unknown: Arc<dyn BlockDeviceOps> = Arc::new(Disk(9));
+        assert_eq!(
+            registry.mount(AUTO, unknown, &opts).err(),
+            Some(VfsError::InvalidInput)
+        );
+        assert_eq!(a.mounts.load(Ordering::Relaxed), 0);
+
+        assert_eq!(registry.unregister("b").unwrap().name(), "b");
+        assert_eq!(registry.unregister("b").err(), Some(VfsError::NotFound));
+        assert_eq!(
+            registry.mount(AUTO, disk, &opts).err(),
+            Some(VfsError::InvalidInput)
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Mapping between [`VfsError`] and Linux errno values.
+//!
+//! Syscall layers translate filesystem errors with [`to_errno()`] and
+//! [`from_errno()`], so that all of them report identical errno values for
+//! the same failure. The mapping is defined once by [`ERRNO_TABLE`].
+//!
+//! Each errno maps back to a single *canonical* error: errors that share an
+//! errno with an earlier entry of the table (such as
+//! [`VfsError::InvalidData`] and [`VfsError::InvalidInput`], both `EINVAL`)
+//! do not round-trip. Errnos without an entry (such as `EMLINK`, as
+//! [`VfsError`] has no "too many links" variant) are rejected by
+//! [`from_errno()`].
+//!
+//! Errors are constructed with the context of the failure by [`vfs_err!`]
+//! and [`vfs_bail!`], which log the formatted message at debug level.
+//! Syscalls then return the value of [`to_syscall_ret()`].
+
+use axerrno::LinuxError;
+
+use crate::{VfsError, VfsResult};
+
+/// Constructs an [`Err(VfsError)`](VfsError), logging the context of the
+/// failure.
+///
+/// Unlike `ax_err!`, the message is a format string with its arguments, and
+/// it is logged at debug level, as most filesystem errors (such as a missing
+/// file) are expected by the caller.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{vfs_err, VfsError, VfsResult};
+///
+/// let name = "foo";
+/// let res: VfsResult = vfs_err!(NotFound, "lookup of {name:?}");
+/// assert_eq!(res, Err(VfsError::NotFound));
+/// ```
+#[macro_export]
+macro_rules! vfs_err {
+    ($err:ident) => {{
+        $crate::__priv::debug!("[VfsError::{:?}]", $crate::VfsError::$err);
+        Err($crate::VfsError::$err)
+    }};
+    ($err:ident, $($arg:tt)+) => {{
+        $crate::__priv::debug!(
+            "[VfsError::{:?}] {}",
+            $crate::VfsError::$err,
+            format_args!($($arg)+)
+        );
+        Err($crate::VfsError::$err)
+    }};
+}
+
+/// Returns an [`Err(VfsError)`](VfsError) from the current function,
+/// logging the context of the failure as [`vfs_err!`] does.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{vfs_bail, VfsError, VfsResult};
+///
+/// fn check_name(name: &str) -> VfsResult {
+///     if name.is_empty() {
+///         vfs_bail!(InvalidInput, "empty name");
+///     }
+///     Ok(())
+/// }
+/// assert_eq!(check_name(""), Err(VfsError::InvalidInput));
+/// ```
+#[macro_export]
+macro_rules! vfs_bail {
+    ($($t:tt)+) => {
+        return $crate::vfs_err!($($t)+)
+    };
+}
+
+/// The mapping from [`VfsError`] to Linux errno values.
+///
+/// Every error appears exactly once. When several errors map to the same
+/// errno, the first one is the canonical error returned by
+/// [`from_errno()`].
+pub const ERRNO_TABLE: &[(VfsError, LinuxError)] = &[
+    (VfsError::AddrInUse, LinuxError::EADDRINUSE),
+    (VfsError::AlreadyConnected, LinuxError::EISCONN),
+    (VfsError::AlreadyExists, LinuxError::EEXIST),
+    (VfsError::ArgumentListTooLong, LinuxError::E2BIG),
+    (VfsError::BadAddress, LinuxError::EFAULT),
+    (VfsError::BadState, LinuxError::EFAULT),
+    (VfsError::BadFileDescriptor, LinuxError::EBADF),
+    (VfsError::BrokenPipe, LinuxError::EPIPE),
+    (VfsError::ConnectionRefused, LinuxError::ECONNREFUSED),
+    (VfsError::ConnectionReset, LinuxError::ECONNRESET),
+    (VfsError::CrossesDevices, LinuxError::EXDEV),
+    (VfsError::DirectoryNotEmpty, LinuxError::ENOTEMPTY),
+    (VfsError::FilesystemLoop, LinuxError::ELOOP),
+    (VfsError::IllegalBytes, LinuxError::EILSEQ),
+    (VfsError::InProgress, LinuxError::EINPROGRESS),
+    (VfsError::Interrupted, LinuxError::EINTR),
+    (VfsError::InvalidExecutable, LinuxError::ENOEXEC),
+    (VfsError::InvalidInput, LinuxError::EINVAL),
+    (VfsError::InvalidData, LinuxError::EINVAL),
+    (VfsError::Io, LinuxError::EIO),
+    (VfsError::UnexpectedEof, LinuxError::EIO),
+    (VfsError::WriteZero, LinuxError::EIO),
+    (VfsError::IsADirectory, LinuxError::EISDIR),
+    (VfsError::NameTooLong, LinuxError::ENAMETOOLONG),
+    (VfsError::NoMemory, LinuxError::ENOMEM),
+    (VfsError::NoSuchDevice, LinuxError::ENODEV),
+    (VfsError::NoSuchProcess, LinuxError::ESRCH),
+    (VfsError::NotADirectory, LinuxError::ENOTDIR),
+    (VfsError::NotASocket, LinuxError::ENOTSOCK),
+    (VfsError::NotATty, LinuxError::ENOTTY),
+    (VfsError::NotConnected, LinuxError::ENOTCONN),
+    (VfsError::NotFound, LinuxError::ENOENT),
+    (VfsError::OperationNotPermitted, LinuxError::EPERM),
+    (VfsError::OperationNotSupported, LinuxError::EOPNOTSUPP),
+    (VfsError::OutOfRange, LinuxError::ERANGE),
+    (VfsError::PermissionDenied, LinuxError::EACCES),
+    (VfsError::ReadOnlyFilesystem, LinuxError::EROFS),
+    (VfsError::ResourceBusy, LinuxError::EBUSY),
+    (VfsError::StorageFull, LinuxError::ENOSPC),
+    (VfsError::TimedOut, LinuxError::ETIMEDOUT),
+    (VfsError::TooManyOpenFiles, LinuxError::EMFILE),
+    (VfsError::Unsupported, LinuxError::ENOSYS),
+    (VfsError::WouldBlock, LinuxError::EAGAIN),
+];
+
+/// Converts a [`VfsError`] to a [`LinuxError`].
+///
+/// # Arguments
+///
+/// * `err` - The error to convert
+///
+/// # Returns
+///
+/// Returns the Linux error of `err` according to [`ERRNO_TABLE`].
+pub fn to_linux_error(err: VfsError) -> LinuxError {
+    ERRNO_TABLE
+        .iter()
+        .find(|(e, _)| *e == err)
+        .map_or(LinuxError::EIO, |&(_, linux)| linux)
+}
+
+/// Converts a [`VfsError`] to a positive Linux errno value.
+///
+/// Syscalls usually return the negated value.
+///
+/// # Arguments
+///
+/// * `err` - The error to convert
+///
+/// # Returns
+///
+/// Returns the errno of `err` according to [`ERRNO_TABLE`].
+pub fn to_errno(err: VfsError) -> i32 {
+    to_linux_error(err).code()
+}
+
+/// Converts a positive Linux errno value to its canonical [`VfsError`].
+///
+/// # Arguments
+///
+/// * `errno` - The errno value to convert
+///
+/// # Returns
+///
+/// Returns the first error of [`ERRNO_TABLE`] mapping to `errno`, or `None`
+/// if no error maps to it.
+pub fn from_errno(errno: i32) -> Option<VfsError> {
+    ERRNO_TABLE
+        .iter()
+        .find(|(_, linux)| linux.code() == errno)
+        .map(|&(err, _)| err)
+}
+
+/// Converts the result of a syscall to its return value.
+///
+/// # Arguments
+///
+/// * `res` - The result of the syscall
+///
+/// # Returns
+///
+/// Returns the value of `Ok`, or the negated errno of the error according
+/// to [`ERRNO_TABLE`].
+pub fn to_syscall_ret(res: VfsResult<usize>) -> isize {
+    match res {
+        Ok(value) => value as isize,
+        Err(err) => -(to_errno(err) as isize),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every error, as axerrno numbers them from 1.
+    fn all_errors() -> impl Iterator<Item = VfsError> {
+        (1..).map_while(|code| VfsError::try_from(code).ok())
+    }
+
+    #[test]
+    fn test_table_covers_all_errors() {
+        for err in all_errors() {
+            let count = ERRNO_TABLE.iter().filter(|(e, _)| *e == err).count();
+            assert_eq!(count, 1, "{err:?} must appear once in the table");
+        }
+        assert_eq!(all_errors().count(), ERRNO_TABLE.len());
+    }
+
+    #[test]
+    fn test_matches_axerrno() {
+        for &(err, linux) in ERRNO_TABLE {
+            assert_eq!(LinuxError::from(err), linux, "{err:?}");
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for err in all_errors() {
+            let canonical = from_errno(to_errno(err)).unwrap();
+            assert_eq!(to_errno(canonical), to_errno(err));
+        }
+        assert_eq!(to_errno(VfsError::NameTooLong), 36);
+        assert_eq!(from_errno(36), Some(VfsError::NameTooLong));
+        assert_eq!(to_errno(VfsError::CrossesDevices), 18);
+        assert_eq!(from_errno(18), Some(VfsError::CrossesDevices));
+        assert_eq!(from_errno(5), Some(VfsError::Io));
+        assert_eq!(from_errno(22), Some(VfsError::InvalidInput));
+        // EMLINK has no error
+        assert_eq!(from_errno(31), None);
+        assert_eq!(from_errno(0), None);
+        assert_eq!(from_errno(-2), None);
+    }
+
+    #[test]
+    fn test_macros() {
+        fn lookup(name: &str) -> VfsResult<usize> {
+            if name.is_empty() {
+                vfs_bail!(InvalidInput);
+            }
+            if name.len() > 3 {
+                vfs_bail!(NameTooLong, "{} bytes", name.len());
+            }
+            vfs_err!(NotFound, "lookup of {name:?}")
+        }
+        assert_eq!(lookup(""), Err(VfsError::InvalidInput));
+        assert_eq!(lookup("long"), Err(VfsError::NameTooLong));
+        assert_eq!(lookup("a"), Err(VfsError::NotFound));
+        assert_eq!(to_syscall_ret(lookup("a")), -2);
+        assert_eq!(to_syscall_ret(Ok(42)), 42);
+    }
+}
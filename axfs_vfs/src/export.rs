@@ -0,0 +1,99 @@
+//! Exporting nodes by file handle, like `name_to_handle_at()`.
+//!
+//! An NFS-style server gives its clients an opaque [`ExportHandle`] for
+//! each node instead of a path, which stays valid when the node is renamed
+//! and does not keep the node alive. The handle is made of the inode number
+//! of the node and its generation number (see
+//! [`VfsNodeOps::generation`](crate::VfsNodeOps::generation)), which tells
+//! apart the successive nodes having the same inode number, so a handle of
+//! a removed node is never resolved to a new one.
+//!
+//! Handles are created by [`VfsOps::encode_fh`](crate::VfsOps::encode_fh)
+//! and resolved back to nodes by
+//! [`VfsOps::open_by_handle`](crate::VfsOps::open_by_handle).
+
+use crate::{VfsError, VfsResult};
+
+/// The length of an encoded [`ExportHandle`], in bytes.
+pub const EXPORT_HANDLE_LEN: usize = 12;
+
+/// A handle identifying a node of a filesystem across its renames.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::export::ExportHandle;
+///
+/// let fh = ExportHandle::new(42, 7);
+/// let bytes = fh.encode();
+/// assert_eq!(ExportHandle::decode(&bytes), Ok(fh));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExportHandle {
+    ino: u64,
+    generation: u32,
+}
+
+impl ExportHandle {
+    /// Creates the handle of the node with inode number `ino` and
+    /// generation number `generation`.
+    pub const fn new(ino: u64, generation: u32) -> Self {
+        Self { ino, generation }
+    }
+
+    /// Returns the inode number of the node.
+    pub const fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns the generation number of the node.
+    pub const fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Encodes the handle into the opaque bytes given to clients.
+    ///
+    /// The inode number comes first, then the generation number, both in
+    /// little endian.
+    pub fn encode(&self) -> [u8; EXPORT_HANDLE_LEN] {
+        let mut bytes = [0; EXPORT_HANDLE_LEN];
+        bytes[..8].copy_from_slice(&self.ino.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.generation.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a handle encoded with [`encode()`](Self::encode).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidData`] if `bytes` is not
+    /// [`EXPORT_HANDLE_LEN`] bytes long.
+    pub fn decode(bytes: &[u8]) -> VfsResult<Self> {
+        let bytes: &[u8; EXPORT_HANDLE_LEN] =
+            bytes.try_into().map_err(|_| VfsError::InvalidData)?;
+        let (ino, generation) = bytes.split_at(8);
+        Ok(Self {
+            ino: u64::from_le_bytes(ino.try_into().unwrap()),
+            generation: u32::from_le_bytes(generation.try_into().unwrap()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_handle() {
+        let fh = ExportHandle::new(0x0102_0304_0506_0708, 0x0a0b_0c0d);
+        let bytes = fh.encode();
+        assert_eq!(bytes[0], 0x08);
+        assert_eq!(bytes[8], 0x0d);
+        assert_eq!(ExportHandle::decode(&bytes), Ok(fh));
+        assert_eq!(
+            ExportHandle::decode(&bytes[..11]),
+            Err(VfsError::InvalidData)
+        );
+        assert_eq!(ExportHandle::decode(&[0; 13]), Err(VfsError::InvalidData));
+    }
+}
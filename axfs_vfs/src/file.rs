@@ -0,0 +1,105 @@
+use alloc::sync::Arc;
+
+use crate::{VfsError, VfsResult};
+
+/// A wrapper of [`Arc<dyn VfsFileOps>`].
+pub type VfsFileRef = Arc<dyn VfsFileOps>;
+
+/// Operations on a single open of a node.
+///
+/// A node returns a file from [`VfsNodeOps::open`](crate::VfsNodeOps::open)
+/// when each open needs its own state, such as the master side of a
+/// pseudo-terminal, which creates a new terminal pair for each opener. The
+/// file offset and the open flags are kept by the
+/// [`VfsFileHandle`](crate::handle::VfsFileHandle), which dispatches the
+/// data operations to the file instead of the node. The other operations,
+/// such as [`get_attr`](crate::VfsNodeOps::get_attr), still go to the node.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use axfs_vfs::handle::VfsFileHandle;
+/// use axfs_vfs::{OpenFlags, VfsFileOps, VfsFileRef, VfsNodeOps, VfsResult};
+///
+/// /// A device telling each opener its number.
+/// #[derive(Default)]
+/// struct Ticket(AtomicUsize);
+///
+/// struct TicketFile(u8);
+///
+/// impl VfsFileOps for TicketFile {
+///     fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+///         buf[0] = self.0;
+///         Ok(1)
+///     }
+/// }
+///
+/// impl VfsNodeOps for Ticket {
+///     fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+///         let number = self.0.fetch_add(1, Ordering::Relaxed) as u8;
+///         Ok(Some(Arc::new(TicketFile(number))))
+///     }
+/// }
+///
+/// let node = Arc::new(Ticket::default());
+/// let first = VfsFileHandle::open(node.clone()).unwrap();
+/// let second = VfsFileHandle::open(node).unwrap();
+/// let mut buf = [0];
+/// second.read(&mut buf).unwrap();
+/// assert_eq!(buf, [1]);
+/// first.read(&mut buf).unwrap();
+/// assert_eq!(buf, [0]);
+/// ```
+pub trait VfsFileOps: Send + Sync {
+    /// Reads data from the file at the given offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start reading from
+    /// * `buf` - The buffer to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually read on success, or an error
+    /// otherwise. The default implementation returns
+    /// [`VfsError::InvalidInput`].
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidInput)
+    }
+
+    /// Writes data to the file at the given offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start writing to
+    /// * `buf` - The buffer containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually written on success, or an error
+    /// otherwise. The default implementation returns
+    /// [`VfsError::InvalidInput`].
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidInput)
+    }
+
+    /// Sends a device-specific control command to the file.
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of the command. The default implementation
+    /// returns [`VfsError::NotATty`].
+    fn ioctl(&self, _cmd: u32, _arg: usize) -> VfsResult<usize> {
+        Err(VfsError::NotATty)
+    }
+
+    /// Closes the file, when the last duplicate of its handle is closed.
+    ///
+    /// It is called before [`VfsNodeOps::release`](crate::VfsNodeOps::release)
+    /// on the node. The default implementation does nothing.
+    fn release(&self) -> VfsResult {
+        Ok(())
+    }
+}
@@ -0,0 +1,1679 @@
+//! Open file handles.
+//!
+//! A [`VfsFileHandle`] represents one successful [`open()`] of a node, like
+//! an open file description in POSIX. Duplicating a handle with
+//! [`clone()`](Clone::clone) does not open the node again: all duplicates
+//! share the same open, which is closed when the last of them is dropped.
+//!
+//! Each open has a file offset, used by [`read()`](VfsFileHandle::read),
+//! [`write()`](VfsFileHandle::write) and [`seek()`](VfsFileHandle::seek):
+//!
+//! - Duplicates share the offset, like file descriptors duplicated with
+//!   `dup()`: reading from one advances the others. Reads and writes keep
+//!   the offset locked, so concurrent duplicates never use the same
+//!   offset, except a read that may wait for data, of a node other than a
+//!   regular file: the duplicates can still write or seek meanwhile, and
+//!   the read only advances the offset if it was not moved.
+//! - [`reopen()`](VfsFileHandle::reopen) opens the node again, with an
+//!   independent offset, like a second `open()` of the same path.
+//!
+//! In append mode (see [`set_append()`](VfsFileHandle::set_append)), every
+//! write first moves the offset to the end of the file, so writes through
+//! any handle in append mode never overwrite data, even if other handles
+//! extend the file in between.
+//!
+//! Reads wait for data on nodes that can block, such as terminals, unless
+//! the handle is in nonblocking mode (see
+//! [`set_nonblocking()`](VfsFileHandle::set_nonblocking)), like
+//! `O_NONBLOCK`: they go through [`VfsNodeOps::read_hinted`], with the
+//! [`ReadHint`] of the mode.
+//!
+//! The handle layer guarantees balanced calls to the node:
+//!
+//! - [`release()`] is called once for every successful [`open()`], when the
+//!   last duplicate of the handle is closed.
+//! - [`on_last_release()`] is called after [`release()`] when no handle to
+//!   the node is open anymore. The handles are counted in the
+//!   [`OpenState`] of the node (see [`VfsNodeOps::open_state`]), shared
+//!   with the wrappers of the node, so the hook runs once even if the node
+//!   was opened through several wrappers.
+//!
+//! Access patterns can be announced with
+//! [`advise()`](VfsFileHandle::advise), like `posix_fadvise()`.
+//!
+//! A handle can be switched to direct I/O with
+//! [`set_direct()`](VfsFileHandle::set_direct), like `O_DIRECT`: its reads
+//! and writes then go through [`VfsNodeOps::read_direct_at`] and
+//! [`VfsNodeOps::write_direct_at`], bypassing any caching layer, and must be
+//! aligned to the [`io_alignment()`](VfsNodeOps::io_alignment) of the node.
+//!
+//! If [`open()`] returns a per-open [`VfsFileOps`](crate::VfsFileOps), the
+//! reads, writes and control commands of the handle go to it instead of the
+//! node.
+//!
+//! The handles of a node can be revoked with [`revoke()`], such as when
+//! its device is unplugged: their operations then fail with
+//! [`VfsError::NoSuchDevice`], and so do new opens of the node until all
+//! its handles are closed.
+//!
+//! [`open()`]: VfsNodeOps::open
+//! [`release()`]: VfsNodeOps::release
+//! [`on_last_release()`]: VfsNodeOps::on_last_release
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use spin::Mutex;
+
+use crate::{
+    FallocateMode, IoSlice, IoSliceMut, OpenFlags, ReadHint, SeekHint, VfsError, VfsFileRef,
+    VfsNodeOps, VfsNodeRef, VfsResult,
+};
+
+/// The expected access pattern of a range of a file, as given to
+/// [`VfsFileHandle::advise`].
+///
+/// The variants match the `POSIX_FADV_*` advice values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileAdvice {
+    /// No particular access pattern.
+    Normal,
+    /// The range will be read sequentially.
+    Sequential,
+    /// The range will be read in random order.
+    Random,
+    /// The range will be read soon, so it should be prefetched.
+    WillNeed,
+    /// The range will not be read soon.
+    DontNeed,
+    /// The range will be read only once.
+    NoReuse,
+}
+
+/// The position to move the offset of a handle to, as given to
+/// [`VfsFileHandle::seek`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// The given number of bytes from the start of the file.
+    Start(u64),
+    /// The end of the file plus the given number of bytes.
+    End(i64),
+    /// The current offset plus the given number of bytes.
+    Current(i64),
+}
+
+/// The default alignment required for the offset, length and buffer address
+/// of direct I/O, in bytes, see [`VfsNodeOps::io_alignment`].
+///
+/// It matches the unit of [`VfsNodeAttr::blocks`](crate::VfsNodeAttr::blocks).
+pub const DIRECT_IO_ALIGN: usize = 512;
+
+/// The open state of a node, tracked by the handle layer.
+///
+/// A node owning one returns it from [`VfsNodeOps::open_state`], so that
+/// the handle layer can count its open handles, run its
+/// [`on_last_release()`](VfsNodeOps::on_last_release) hook when the last
+/// one is closed, and revoke them with [`revoke()`].
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_vfs::handle::{open_count, OpenState, VfsFileHandle};
+/// use axfs_vfs::{VfsNodeOps, VfsNodeRef};
+///
+/// #[derive(Default)]
+/// struct Pipe {
+///     opens: OpenState,
+/// }
+///
+/// impl VfsNodeOps for Pipe {
+///     fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+///         Some((&self.opens, self))
+///     }
+/// }
+///
+/// let pipe: VfsNodeRef = Arc::new(Pipe::default());
+/// let handle = VfsFileHandle::open(pipe.clone()).unwrap();
+/// assert_eq!(open_count(pipe.as_ref()), 1);
+/// drop(handle);
+/// assert_eq!(open_count(pipe.as_ref()), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct OpenState {
+    counts: Mutex<OpenCounts>,
+    /// Whether the open handles are revoked. It is only set while a handle
+    /// is open or being opened.
+    revoked: AtomicBool,
+}
+
+/// The counts of an [`OpenState`].
+#[derive(Debug, Default)]
+struct OpenCounts {
+    /// The number of open handles, duplicates not counted separately.
+    open: usize,
+    /// The number of handles being opened.
+    pending: usize,
+    /// Whether the last handle was closed while others were being opened,
+    /// so the last-release hook is due if they all fail.
+    deferred: bool,
+}
+
+impl OpenState {
+    /// Creates the state of a node that is not open.
+    pub const fn new() -> Self {
+        Self {
+            counts: Mutex::new(OpenCounts {
+                open: 0,
+                pending: 0,
+                deferred: false,
+            }),
+            revoked: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the number of open handles of the node.
+    pub fn count(&self) -> usize {
+        self.counts.lock().open
+    }
+
+    /// Returns whether the open handles of the node are revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.revoked.load(Ordering::Acquire)
+    }
+
+    /// Counts a handle being opened, unless the open handles are revoked.
+    fn begin_open(&self) -> VfsResult {
+        let mut counts = self.counts.lock();
+        if self.is_revoked() {
+            return Err(VfsError::NoSuchDevice);
+        }
+        counts.pending += 1;
+        Ok(())
+    }
+
+    /// Counts the end of an open, and returns whether the last-release hook
+    /// is due, which is only the case if the open failed.
+    fn end_open(&self, opened: bool) -> bool {
+        let mut counts = self.counts.lock();
+        counts.pending -= 1;
+        if opened {
+            counts.open += 1;
+            counts.deferred = false;
+            return false;
+        }
+        self.settle(&mut counts)
+    }
+
+    /// Counts a closed handle, and returns whether the last-release hook is
+    /// due.
+    fn release(&self) -> bool {
+        let mut counts = self.counts.lock();
+        debug_assert!(counts.open > 0, "unbalanced release");
+        counts.open = counts.open.saturating_sub(1);
+        if counts.open == 0 {
+            counts.deferred = true;
+        }
+        self.settle(&mut counts)
+    }
+
+    /// Returns whether the last-release hook is due, and clears the
+    /// revocation once no handle is open or being opened.
+    fn settle(&self, counts: &mut OpenCounts) -> bool {
+        if counts.open > 0 || counts.pending > 0 {
+            return false;
+        }
+        self.revoked.store(false, Ordering::Release);
+        core::mem::take(&mut counts.deferred)
+    }
+
+    /// Revokes the open handles, and returns their number.
+    fn revoke(&self) -> usize {
+        let counts = self.counts.lock();
+        if counts.open > 0 || counts.pending > 0 {
+            self.revoked.store(true, Ordering::Release);
+        }
+        counts.open
+    }
+}
+
+/// Runs the last-release hook of the owner of the open state of `node` if
+/// `due` returns `true` for the state.
+fn settle(node: &dyn VfsNodeOps, due: impl FnOnce(&OpenState) -> bool) -> VfsResult {
+    match node.open_state() {
+        Some((state, owner)) if due(state) => owner.on_last_release(),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the number of open handles of `node`.
+///
+/// Duplicates of a handle are not counted separately, and neither are the
+/// wrappers of a node, see [`VfsNodeOps::open_state`].
+///
+/// # Arguments
+///
+/// * `node` - The node to query
+///
+/// # Returns
+///
+/// The number of opens of `node` that are not released yet, or `0` if the
+/// node does not track its opens.
+pub fn open_count(node: &dyn VfsNodeOps) -> usize {
+    node.open_state().map_or(0, |(state, _)| state.count())
+}
+
+/// Revokes the open handles of a node.
+///
+/// The operations on the handles fail with [`VfsError::NoSuchDevice`] from
+/// now on, and so do new opens of the node until all its handles are
+/// closed. The node is still released normally when the handles are closed.
+///
+/// # Arguments
+///
+/// * `node` - The node whose handles to revoke
+///
+/// # Returns
+///
+/// The number of open handles revoked, duplicates not counted separately.
+/// Nothing is revoked if the node does not track its opens.
+pub fn revoke(node: &dyn VfsNodeOps) -> usize {
+    node.open_state().map_or(0, |(state, _)| state.revoke())
+}
+
+/// Returns whether the open handles of `node` are revoked.
+pub fn is_revoked(node: &dyn VfsNodeOps) -> bool {
+    node.open_state()
+        .is_some_and(|(state, _)| state.is_revoked())
+}
+
+/// A counter of the opens of a node, for nodes implementing their own
+/// open policy.
+///
+/// The handle layer calls [`release()`](VfsNodeOps::release) once for every
+/// successful [`open()`](VfsNodeOps::open), so a node can count its opens
+/// by calling [`open()`](Self::open) and [`release()`](Self::release) of
+/// the counter from them. An open with [`OpenFlags::EXCL`] is exclusive,
+/// like `O_EXCL` on a Linux block device: it fails if the node is open, and
+/// the other opens fail until it is released.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::handle::OpenCounter;
+/// use axfs_vfs::{OpenFlags, VfsError};
+///
+/// let counter = OpenCounter::new();
+/// counter.open(OpenFlags::READ | OpenFlags::EXCL).unwrap();
+/// assert_eq!(counter.open(OpenFlags::READ), Err(VfsError::ResourceBusy));
+/// counter.release();
+/// assert_eq!(counter.count(), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct OpenCounter {
+    /// The number of opens, and whether the open is exclusive.
+    state: Mutex<(usize, bool)>,
+}
+
+impl OpenCounter {
+    /// Creates a counter of a node that is not open.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new((0, false)),
+        }
+    }
+
+    /// Counts an open of the node with `flags`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::ResourceBusy`] if the node is open exclusively,
+    /// or if `flags` contains [`OpenFlags::EXCL`] and the node is open.
+    pub fn open(&self, flags: OpenFlags) -> VfsResult {
+        let mut state = self.state.lock();
+        let exclusive = flags.contains(OpenFlags::EXCL);
+        if state.1 || (exclusive && state.0 > 0) {
+            return Err(VfsError::ResourceBusy);
+        }
+        *state = (state.0 + 1, exclusive);
+        Ok(())
+    }
+
+    /// Counts a release of the node.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if this was the last open of the node.
+    pub fn release(&self) -> bool {
+        let mut state = self.state.lock();
+        debug_assert!(state.0 > 0, "unbalanced release");
+        state.0 = state.0.saturating_sub(1);
+        if state.0 == 0 {
+            state.1 = false;
+        }
+        state.0 == 0
+    }
+
+    /// Returns the number of opens not released yet.
+    pub fn count(&self) -> usize {
+        self.state.lock().0
+    }
+
+    /// Returns whether the node is open exclusively.
+    pub fn is_exclusive(&self) -> bool {
+        self.state.lock().1
+    }
+}
+
+/// The state shared by the duplicates of a handle.
+struct HandleInner {
+    node: VfsNodeRef,
+    /// The flags the node was opened with.
+    flags: OpenFlags,
+    /// The per-open file returned by the node, receiving the data
+    /// operations.
+    file: Option<VfsFileRef>,
+    direct: AtomicBool,
+    append: AtomicBool,
+    nonblock: AtomicBool,
+    /// Whether the node is a regular file, whose reads never wait for
+    /// data.
+    regular: bool,
+    /// The file offset. It is locked for the whole duration of the reads
+    /// and writes using it, so that concurrent duplicates never use the
+    /// same offset. Reads that may wait for data only lock it to read it
+    /// and then to advance it.
+    offset: Mutex<u64>,
+    released: bool,
+}
+
+impl HandleInner {
+    /// Releases the node, and runs its last-release hook if this was its last
+    /// open handle.
+    fn release(&mut self) -> VfsResult {
+        self.released = true;
+        let res = match &self.file {
+            Some(file) => file.release().and(self.node.release()),
+            None => self.node.release(),
+        };
+        res.and(settle(self.node.as_ref(), OpenState::release))
+    }
+}
+
+impl Drop for HandleInner {
+    fn drop(&mut self) {
+        if !self.released {
+            if let Err(e) = self.release() {
+                log::warn!("failed to release node on close: {e:?}");
+            }
+        }
+    }
+}
+
+/// An open handle of a VFS node.
+///
+/// See the [module-level documentation](self) for the lifecycle guarantees.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::handle::{open_count, VfsFileHandle};
+/// # use axfs_vfs::handle::OpenState;
+/// # use axfs_vfs::{VfsNodeOps, VfsNodeRef};
+/// # #[derive(Default)]
+/// # struct Dummy(OpenState);
+/// # impl VfsNodeOps for Dummy {
+/// #     fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+/// #         Some((&self.0, self))
+/// #     }
+/// # }
+/// # let node: VfsNodeRef = std::sync::Arc::new(Dummy::default());
+///
+/// let handle = VfsFileHandle::open(node.clone()).unwrap();
+/// let dup = handle.clone();
+/// assert_eq!(open_count(node.as_ref()), 1);
+/// drop(handle);
+/// drop(dup); // releases the node
+/// assert_eq!(open_count(node.as_ref()), 0);
+/// ```
+#[derive(Clone)]
+pub struct VfsFileHandle {
+    inner: Arc<HandleInner>,
+}
+
+impl VfsFileHandle {
+    /// Opens `node` for reading and writing and returns a new handle of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - The node to open
+    ///
+    /// # Returns
+    ///
+    /// The new handle, or the error returned by [`VfsNodeOps::open`].
+    pub fn open(node: VfsNodeRef) -> VfsResult<Self> {
+        Self::open_with(node, OpenFlags::READ | OpenFlags::WRITE)
+    }
+
+    /// Opens `node` with the given flags and returns a new handle of it.
+    ///
+    /// Reads and writes through the handle are only allowed if `flags`
+    /// contains [`OpenFlags::READ`] and [`OpenFlags::WRITE`] respectively,
+    /// [`OpenFlags::APPEND`] enables the append mode, and
+    /// [`OpenFlags::NONBLOCK`] the nonblocking mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - The node to open
+    /// * `flags` - The access mode and options of the open, passed to
+    ///   [`VfsNodeOps::open`]
+    ///
+    /// # Returns
+    ///
+    /// The new handle, or the error returned by [`VfsNodeOps::open`].
+    pub fn open_with(node: VfsNodeRef, flags: OpenFlags) -> VfsResult<Self> {
+        // the handle is counted before the node is opened, so that it is
+        // either refused or seen by a concurrent revoke
+        if let Some((state, _)) = node.open_state() {
+            state.begin_open()?;
+        }
+        let res = node.open(flags);
+        if let Err(e) = settle(node.as_ref(), |state| state.end_open(res.is_ok())) {
+            log::warn!("failed to run last-release hook after failed open: {e:?}");
+        }
+        let file = res?;
+        let regular = node.get_attr().is_ok_and(|attr| attr.is_file());
+        Ok(Self {
+            inner: Arc::new(HandleInner {
+                regular,
+                node,
+                flags,
+                file,
+                direct: AtomicBool::new(false),
+                append: AtomicBool::new(flags.contains(OpenFlags::APPEND)),
+                nonblock: AtomicBool::new(flags.contains(OpenFlags::NONBLOCK)),
+                offset: Mutex::new(0),
+                released: false,
+            }),
+        })
+    }
+
+    /// Opens the node of this handle again.
+    ///
+    /// Unlike [`clone()`](Clone::clone), the new handle is a separate open
+    /// of the node, with its own offset starting at `0`. It inherits the
+    /// open flags, except [`OpenFlags::TRUNC`] and [`OpenFlags::EXCL`], and
+    /// the direct I/O, append and nonblocking modes of this handle.
+    ///
+    /// # Returns
+    ///
+    /// The new handle, or the error returned by [`VfsNodeOps::open`].
+    pub fn reopen(&self) -> VfsResult<Self> {
+        let flags = self.flags() - OpenFlags::TRUNC - OpenFlags::EXCL;
+        let handle = Self::open_with(self.inner.node.clone(), flags)?;
+        handle.set_direct(self.is_direct())?;
+        handle.set_append(self.is_append());
+        handle.set_nonblocking(self.is_nonblocking());
+        Ok(handle)
+    }
+
+    /// Returns the per-open file of this handle, if the node returned one
+    /// from [`VfsNodeOps::open`].
+    pub fn file(&self) -> Option<&VfsFileRef> {
+        self.inner.file.as_ref()
+    }
+
+    /// Returns the node this handle refers to.
+    pub fn node(&self) -> &VfsNodeRef {
+        &self.inner.node
+    }
+
+    /// Returns the flags the node of this handle was opened with.
+    pub fn flags(&self) -> OpenFlags {
+        self.inner.flags
+    }
+
+    /// Returns whether this handle was revoked with [`revoke()`].
+    pub fn is_revoked(&self) -> bool {
+        is_revoked(self.inner.node.as_ref())
+    }
+
+    /// Checks that the handle is not revoked.
+    fn check_revoked(&self) -> VfsResult {
+        if self.is_revoked() {
+            return Err(VfsError::NoSuchDevice);
+        }
+        Ok(())
+    }
+
+    /// Checks that the handle is not revoked and was opened with the access
+    /// mode `mode`.
+    fn check_access(&self, mode: OpenFlags) -> VfsResult {
+        self.check_revoked()?;
+        if !self.inner.flags.contains(mode) {
+            return Err(VfsError::BadFileDescriptor);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of open handles of the node of this handle,
+    /// including this one.
+    pub fn open_count(&self) -> usize {
+        open_count(self.inner.node.as_ref())
+    }
+
+    /// Returns whether direct I/O is enabled on this handle.
+    pub fn is_direct(&self) -> bool {
+        self.inner.direct.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables direct I/O on this handle and its duplicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `direct` - Whether reads and writes should bypass caches
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if direct I/O is enabled on a node
+    /// that does not support it, see [`VfsNodeOps::supports_direct_io`], as
+    /// `open()` does with `O_DIRECT`.
+    pub fn set_direct(&self, direct: bool) -> VfsResult {
+        if direct && !self.inner.node.supports_direct_io() {
+            return Err(VfsError::InvalidInput);
+        }
+        self.inner.direct.store(direct, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Checks that a direct I/O request is aligned to the
+    /// [`io_alignment()`](VfsNodeOps::io_alignment) of the node.
+    fn check_direct_align(&self, offset: u64, addr: *const u8, len: usize) -> VfsResult {
+        let align = self.inner.node.io_alignment() as u64;
+        if ![offset, len as u64, addr as u64]
+            .iter()
+            .all(|v| v.is_multiple_of(align))
+        {
+            return Err(VfsError::InvalidInput);
+        }
+        Ok(())
+    }
+
+    /// Returns whether append mode is enabled on this handle.
+    pub fn is_append(&self) -> bool {
+        self.inner.append.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables append mode on this handle and its duplicates,
+    /// like `O_APPEND`.
+    ///
+    /// # Arguments
+    ///
+    /// * `append` - Whether writes should always happen at the end of the
+    ///   file
+    pub fn set_append(&self, append: bool) {
+        self.inner.append.store(append, Ordering::Relaxed);
+    }
+
+    /// Returns whether the reads of this handle fail with
+    /// [`VfsError::WouldBlock`] instead of waiting for data.
+    pub fn is_nonblocking(&self) -> bool {
+        self.inner.nonblock.load(Ordering::Relaxed)
+    }
+
+    /// Enables or disables the nonblocking mode of this handle and its
+    /// duplicates, like `O_NONBLOCK`.
+    ///
+    /// # Arguments
+    ///
+    /// * `nonblock` - Whether reads should fail with
+    ///   [`VfsError::WouldBlock`] instead of waiting for data, see
+    ///   [`VfsNodeOps::read_hinted`]
+    pub fn set_nonblocking(&self, nonblock: bool) {
+        self.inner.nonblock.store(nonblock, Ordering::Relaxed);
+    }
+
+    /// Returns the current offset of this handle, shared with its
+    /// duplicates.
+    pub fn offset(&self) -> u64 {
+        *self.inner.offset.lock()
+    }
+
+    /// Moves the offset of this handle and its duplicates.
+    ///
+    /// # Arguments
+    ///
+    /// * `pos` - The new position
+    ///
+    /// # Returns
+    ///
+    /// Returns the new offset on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the new offset would be
+    /// negative or overflow, or the error of [`VfsNodeOps::get_attr`] when
+    /// seeking relative to the end of the file.
+    pub fn seek(&self, pos: SeekFrom) -> VfsResult<u64> {
+        let mut offset = self.inner.offset.lock();
+        let new = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => self.inner.node.get_attr()?.size().checked_add_signed(delta),
+            SeekFrom::Current(delta) => offset.checked_add_signed(delta),
+        };
+        *offset = new.ok_or(VfsError::InvalidInput)?;
+        Ok(*offset)
+    }
+
+    /// Moves the offset of this handle to the next region of data or the
+    /// next hole at or after `offset`, like `lseek()` with `SEEK_DATA` or
+    /// `SEEK_HOLE`.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset to search from
+    /// * `hint` - The kind of region to search for
+    ///
+    /// # Returns
+    ///
+    /// Returns the new offset, or `None` if there is no such region, in
+    /// which case the offset is left unchanged.
+    pub fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        self.check_revoked()?;
+        let mut pos = self.inner.offset.lock();
+        let found = self.inner.node.seek_hint(offset, hint)?;
+        if let Some(found) = found {
+            *pos = found;
+        }
+        Ok(found)
+    }
+
+    /// Reads data from the node at the offset of this handle, and advances
+    /// the offset by the number of bytes read.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The buffer to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually read on success, or an error
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`read_at()`](Self::read_at).
+    pub fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        self.read_at_offset(|offset| self.read_at(offset, buf))
+    }
+
+    /// Returns whether a read of this handle may wait for data.
+    ///
+    /// Reads of regular files never wait, and neither do the reads of the
+    /// node in nonblocking mode. Per-open files and direct I/O do not get
+    /// the [`ReadHint`], so they may wait in any mode.
+    fn read_may_block(&self) -> bool {
+        !self.inner.regular
+            && (self.inner.file.is_some() || self.is_direct() || !self.is_nonblocking())
+    }
+
+    /// Runs `read` at the offset of this handle, and advances the offset by
+    /// the number of bytes it read.
+    ///
+    /// A read that may wait for data does not keep the offset locked
+    /// meanwhile, to let the duplicates write or seek. It then only
+    /// advances the offset if it was not moved in between.
+    fn read_at_offset(&self, read: impl FnOnce(u64) -> VfsResult<usize>) -> VfsResult<usize> {
+        if !self.read_may_block() {
+            let mut offset = self.inner.offset.lock();
+            let n = read(*offset)?;
+            *offset += n as u64;
+            return Ok(n);
+        }
+        let start = self.offset();
+        let n = read(start)?;
+        let mut offset = self.inner.offset.lock();
+        if *offset == start {
+            *offset += n as u64;
+        }
+        Ok(n)
+    }
+
+    /// Writes data to the node at the offset of this handle, and advances
+    /// the offset by the number of bytes written.
+    ///
+    /// In append mode, the offset is moved to the end of the file first.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The buffer containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually written on success, or an error
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`write_at()`](Self::write_at), and in append
+    /// mode those of [`VfsNodeOps::get_attr`].
+    pub fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+        let mut offset = self.inner.offset.lock();
+        if self.is_append() {
+            *offset = self.inner.node.get_attr()?.size();
+        }
+        let written = self.write_at(*offset, buf)?;
+        *offset += written as u64;
+        Ok(written)
+    }
+
+    /// Reads data from the node at the offset of this handle into several
+    /// buffers, and advances the offset by the number of bytes read.
+    ///
+    /// # Arguments
+    ///
+    /// * `bufs` - The buffers to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the total number of bytes read on success, or an error
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::BadFileDescriptor`] if the handle was not opened
+    /// for reading, or [`VfsError::NoSuchDevice`] if it was revoked.
+    ///
+    /// Returns [`VfsError::InvalidInput`] if direct I/O is enabled and a
+    /// buffer is not aligned to the
+    /// [`io_alignment()`](VfsNodeOps::io_alignment) of the node.
+    pub fn read_vectored(&self, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        self.check_access(OpenFlags::READ)?;
+        self.read_at_offset(|offset| {
+            if self.inner.file.is_none() && !self.is_direct() {
+                if self.inner.regular {
+                    return self.inner.node.read_vectored_at(offset, bufs);
+                }
+                return self.read_vectored_hinted(offset, bufs);
+            }
+            if self.inner.file.is_none() {
+                let mut pos = offset;
+                for buf in bufs.iter() {
+                    self.check_direct_align(pos, buf.as_ptr(), buf.len())?;
+                    pos += buf.len() as u64;
+                }
+            }
+            let mut total = 0;
+            for buf in bufs {
+                let n = self.dispatch_read(offset + total as u64, buf)?;
+                total += n;
+                if n < buf.len() {
+                    break;
+                }
+            }
+            Ok(total)
+        })
+    }
+
+    /// Reads a node that is not a regular file into several buffers, with
+    /// the [`ReadHint`] of this handle.
+    ///
+    /// Only the first read may wait for data: once some data was read, the
+    /// next buffers only get the data that is already available.
+    fn read_vectored_hinted(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        let mut total = 0;
+        for buf in bufs {
+            let hint = match total == 0 && !self.is_nonblocking() {
+                true => ReadHint::Blocking,
+                false => ReadHint::NonBlocking,
+            };
+            match self
+                .inner
+                .node
+                .read_hinted(offset + total as u64, buf, hint)
+            {
+                Ok(n) => {
+                    total += n;
+                    if n < buf.len() {
+                        break;
+                    }
+                }
+                Err(VfsError::WouldBlock) if total > 0 => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total)
+    }
+
+    /// Writes data to the node at the offset of this handle from several
+    /// buffers, and advances the offset by the number of bytes written.
+    ///
+    /// In append mode, the offset is moved to the end of the file first.
+    ///
+    /// # Arguments
+    ///
+    /// * `bufs` - The buffers containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the total number of bytes written on success, or an error
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::BadFileDescriptor`] if the handle was not opened
+    /// for writing, or [`VfsError::NoSuchDevice`] if it was revoked.
+    ///
+    /// Returns [`VfsError::InvalidInput`] if direct I/O is enabled and a
+    /// buffer is not aligned to the
+    /// [`io_alignment()`](VfsNodeOps::io_alignment) of the node, and in
+    /// append mode the errors of [`VfsNodeOps::get_attr`].
+    pub fn write_vectored(&self, bufs: &[IoSlice]) -> VfsResult<usize> {
+        self.check_access(OpenFlags::WRITE)?;
+        let mut offset = self.inner.offset.lock();
+        if self.is_append() {
+            *offset = self.inner.node.get_attr()?.size();
+        }
+        let written = if self.inner.file.is_none() && !self.is_direct() {
+            self.inner.node.write_vectored_at(*offset, bufs)?
+        } else {
+            if self.inner.file.is_none() {
+                let mut pos = *offset;
+                for buf in bufs {
+                    self.check_direct_align(pos, buf.as_ptr(), buf.len())?;
+                    pos += buf.len() as u64;
+                }
+            }
+            let mut total = 0;
+            for buf in bufs {
+                let n = self.dispatch_write(*offset + total as u64, buf)?;
+                total += n;
+                if n < buf.len() {
+                    break;
+                }
+            }
+            total
+        };
+        *offset += written as u64;
+        Ok(written)
+    }
+
+    /// Announces how a range of the node will be accessed.
+    ///
+    /// This is advisory, like `posix_fadvise()`:
+    /// [`FileAdvice::WillNeed`] prefetches the range with
+    /// [`VfsNodeOps::readahead`], and the other advice values are accepted
+    /// without effect.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file of the range
+    /// * `len` - The length of the range in bytes, or `0` for the rest of
+    ///   the file
+    /// * `advice` - The expected access pattern
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the error of
+    /// [`VfsNodeOps::readahead`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::NoSuchDevice`] if the handle was revoked.
+    pub fn advise(&self, offset: u64, len: u64, advice: FileAdvice) -> VfsResult {
+        self.check_revoked()?;
+        match advice {
+            FileAdvice::WillNeed => self.inner.node.readahead(offset, len),
+            FileAdvice::Normal
+            | FileAdvice::Sequential
+            | FileAdvice::Random
+            | FileAdvice::DontNeed
+            | FileAdvice::NoReuse => Ok(()),
+        }
+    }
+
+    /// Allocates or deallocates the storage of a range of the node, like
+    /// `fallocate()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file of the range
+    /// * `len` - The length of the range in bytes
+    /// * `mode` - The operation to perform
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of [`VfsNodeOps::fallocate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::BadFileDescriptor`] if the handle was not opened
+    /// for writing, or [`VfsError::NoSuchDevice`] if it was revoked.
+    pub fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        self.check_access(OpenFlags::WRITE)?;
+        self.inner.node.fallocate(offset, len, mode)
+    }
+
+    /// Reads data from the node at the given offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start reading from
+    /// * `buf` - The buffer to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually read on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::BadFileDescriptor`] if the handle was not opened
+    /// for reading, or [`VfsError::NoSuchDevice`] if it was revoked.
+    ///
+    /// Returns [`VfsError::InvalidInput`] if direct I/O is enabled and the
+    /// request is not aligned to the
+    /// [`io_alignment()`](VfsNodeOps::io_alignment) of the node.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.check_access(OpenFlags::READ)?;
+        self.dispatch_read(offset, buf)
+    }
+
+    /// Reads from the per-open file if there is one, or else from the node,
+    /// bypassing caches in direct I/O mode.
+    fn dispatch_read(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        match &self.inner.file {
+            Some(file) => file.read_at(offset, buf),
+            None if self.is_direct() => {
+                self.check_direct_align(offset, buf.as_ptr(), buf.len())?;
+                self.inner.node.read_direct_at(offset, buf)
+            }
+            None => {
+                let hint = match self.is_nonblocking() {
+                    true => ReadHint::NonBlocking,
+                    false => ReadHint::Blocking,
+                };
+                self.inner.node.read_hinted(offset, buf, hint)
+            }
+        }
+    }
+
+    /// Writes data to the node at the given offset.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start writing to
+    /// * `buf` - The buffer containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually written on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::BadFileDescriptor`] if the handle was not opened
+    /// for writing, or [`VfsError::NoSuchDevice`] if it was revoked.
+    ///
+    /// Returns [`VfsError::InvalidInput`] if direct I/O is enabled and the
+    /// request is not aligned to the
+    /// [`io_alignment()`](VfsNodeOps::io_alignment) of the node.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.check_access(OpenFlags::WRITE)?;
+        self.dispatch_write(offset, buf)
+    }
+
+    /// Writes to the per-open file if there is one, or else to the node,
+    /// bypassing caches in direct I/O mode.
+    fn dispatch_write(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        match &self.inner.file {
+            Some(file) => file.write_at(offset, buf),
+            None if self.is_direct() => {
+                self.check_direct_align(offset, buf.as_ptr(), buf.len())?;
+                self.inner.node.write_direct_at(offset, buf)
+            }
+            None => self.inner.node.write_at(offset, buf),
+        }
+    }
+
+    /// Sends a device-specific control command to the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The command number
+    /// * `arg` - The argument of the command
+    ///
+    /// # Returns
+    ///
+    /// Returns the result of [`VfsFileOps::ioctl`](crate::VfsFileOps::ioctl)
+    /// on the per-open file if there is one, or else of
+    /// [`VfsNodeOps::ioctl`], or
+    /// [`VfsError::NoSuchDevice`] if the handle was revoked.
+    pub fn ioctl(&self, cmd: u32, arg: usize) -> VfsResult<usize> {
+        self.check_revoked()?;
+        match &self.inner.file {
+            Some(file) => file.ioctl(cmd, arg),
+            None => self.inner.node.ioctl(cmd, arg),
+        }
+    }
+
+    /// Closes this handle.
+    ///
+    /// This is the same as dropping the handle, except that the errors of
+    /// [`VfsNodeOps::release`] and [`VfsNodeOps::on_last_release`] are
+    /// reported to the caller instead of being logged.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was released successfully, or if other
+    /// duplicates of this handle are still open.
+    pub fn close(self) -> VfsResult {
+        match Arc::into_inner(self.inner) {
+            Some(mut inner) => inner.release(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+
+    #[derive(Default)]
+    struct CountingNode {
+        opens: AtomicUsize,
+        releases: AtomicUsize,
+        last_releases: AtomicUsize,
+        fail_open: AtomicBool,
+        state: OpenState,
+    }
+
+    impl VfsNodeOps for CountingNode {
+        fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+            Some((&self.state, self))
+        }
+
+        fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+            if self.fail_open.load(Ordering::SeqCst) {
+                return Err(VfsError::PermissionDenied);
+            }
+            self.opens.fetch_add(1, Ordering::SeqCst);
+            Ok(None)
+        }
+
+        fn release(&self) -> VfsResult {
+            self.releases.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn on_last_release(&self) -> VfsResult {
+            self.last_releases.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// A node recording the ranges to prefetch.
+    #[derive(Default)]
+    struct PrefetchNode(Mutex<alloc::vec::Vec<(u64, u64)>>);
+
+    impl VfsNodeOps for PrefetchNode {
+        fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+            self.0.lock().push((offset, len));
+            Ok(())
+        }
+    }
+
+    /// A cached file whose direct path reports a different result.
+    struct CachedNode;
+
+    impl VfsNodeOps for CachedNode {
+        fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+            Ok(1)
+        }
+
+        fn read_direct_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            Ok(buf.len())
+        }
+    }
+
+    /// A file on a device with 4 KiB logical blocks.
+    struct BlockNode;
+
+    impl VfsNodeOps for BlockNode {
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            Ok(buf.len())
+        }
+
+        fn io_alignment(&self) -> usize {
+            4096
+        }
+    }
+
+    /// A file that cannot bypass its cache.
+    struct UncachedNode;
+
+    impl VfsNodeOps for UncachedNode {
+        fn supports_direct_io(&self) -> bool {
+            false
+        }
+    }
+
+    /// A device whose data arrives after a first read found none.
+    #[derive(Default)]
+    struct SlowNode(AtomicBool);
+
+    impl VfsNodeOps for SlowNode {
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            match self.0.swap(true, Ordering::SeqCst) {
+                true => Ok(buf.len()),
+                false => Err(VfsError::WouldBlock),
+            }
+        }
+    }
+
+    /// A growable in-memory file.
+    #[derive(Default)]
+    struct MemNode(Mutex<alloc::vec::Vec<u8>>, OpenState);
+
+    impl VfsNodeOps for MemNode {
+        fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+            Some((&self.1, self))
+        }
+
+        fn get_attr(&self) -> VfsResult<crate::VfsNodeAttr> {
+            Ok(crate::VfsNodeAttr::new_file(self.0.lock().len() as u64, 0))
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            let data = self.0.lock();
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            Ok(n)
+        }
+
+        fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            let mut data = self.0.lock();
+            let end = offset as usize + buf.len();
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            data[offset as usize..end].copy_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    /// A wrapper created anew on every lookup of its node.
+    struct Wrapper(Arc<CountingNode>);
+
+    impl VfsNodeOps for Wrapper {
+        fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+            self.0.open_state()
+        }
+    }
+
+    #[repr(align(512))]
+    struct AlignedBuf([u8; 1024]);
+
+    fn counts(node: &CountingNode) -> (usize, usize, usize) {
+        (
+            node.opens.load(Ordering::SeqCst),
+            node.releases.load(Ordering::SeqCst),
+            node.last_releases.load(Ordering::SeqCst),
+        )
+    }
+
+    #[test]
+    fn test_dup_shares_open() {
+        let node = Arc::new(CountingNode::default());
+        let handle = VfsFileHandle::open(node.clone()).unwrap();
+        let dup = handle.clone();
+        assert_eq!(dup.open_count(), 1);
+
+        drop(handle);
+        assert_eq!(counts(&node), (1, 0, 0));
+        dup.close().unwrap();
+        assert_eq!(counts(&node), (1, 1, 1));
+        assert_eq!(open_count(node.as_ref()), 0);
+    }
+
+    #[test]
+    fn test_last_release_after_all_opens() {
+        let node = Arc::new(CountingNode::default());
+        let h1 = VfsFileHandle::open(node.clone()).unwrap();
+        let h2 = VfsFileHandle::open(node.clone()).unwrap();
+        assert_eq!(h1.open_count(), 2);
+
+        h1.close().unwrap();
+        assert_eq!(counts(&node), (2, 1, 0));
+        assert_eq!(h2.open_count(), 1);
+        drop(h2);
+        assert_eq!(counts(&node), (2, 2, 1));
+    }
+
+    #[test]
+    fn test_open_through_wrappers() {
+        let node = Arc::new(CountingNode::default());
+        let h1 = VfsFileHandle::open(Arc::new(Wrapper(node.clone()))).unwrap();
+        let h2 = VfsFileHandle::open(Arc::new(Wrapper(node.clone()))).unwrap();
+        let h3 = VfsFileHandle::open(node.clone()).unwrap();
+        assert_eq!(open_count(node.as_ref()), 3);
+        assert_eq!(revoke(h1.node().as_ref()), 3);
+        assert_eq!(h3.read(&mut [0; 4]), Err(VfsError::NoSuchDevice));
+
+        // the hook of the node runs once, when its last handle is closed
+        drop(h1);
+        h3.close().unwrap();
+        assert_eq!(node.last_releases.load(Ordering::SeqCst), 0);
+        h2.close().unwrap();
+        assert_eq!(node.last_releases.load(Ordering::SeqCst), 1);
+        assert!(!is_revoked(node.as_ref()));
+    }
+
+    #[test]
+    fn test_failed_open() {
+        let node = Arc::new(CountingNode::default());
+        node.fail_open.store(true, Ordering::SeqCst);
+        assert!(VfsFileHandle::open(node.clone()).is_err());
+        assert_eq!(open_count(node.as_ref()), 0);
+        assert_eq!(counts(&node), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_open_counter() {
+        let counter = OpenCounter::new();
+        counter.open(OpenFlags::READ).unwrap();
+        counter.open(OpenFlags::WRITE).unwrap();
+        assert_eq!(counter.count(), 2);
+        assert_eq!(
+            counter.open(OpenFlags::READ | OpenFlags::EXCL),
+            Err(VfsError::ResourceBusy)
+        );
+        assert!(!counter.release());
+        assert!(counter.release());
+
+        counter.open(OpenFlags::WRITE | OpenFlags::EXCL).unwrap();
+        assert!(counter.is_exclusive());
+        assert_eq!(counter.open(OpenFlags::READ), Err(VfsError::ResourceBusy));
+        assert!(counter.release());
+        assert!(!counter.is_exclusive());
+        counter.open(OpenFlags::READ).unwrap();
+    }
+
+    #[test]
+    fn test_direct_io() {
+        let handle = VfsFileHandle::open(Arc::new(CachedNode)).unwrap();
+        let mut buf = AlignedBuf([0; 1024]);
+        assert!(!handle.is_direct());
+        assert_eq!(handle.read_at(3, &mut buf.0[1..4]), Ok(1));
+
+        let dup = handle.clone();
+        dup.set_direct(true).unwrap();
+        assert!(handle.is_direct());
+        assert_eq!(handle.read_at(512, &mut buf.0), Ok(1024));
+        assert_eq!(handle.read_at(512, &mut buf.0[..512]), Ok(512));
+        assert_eq!(
+            handle.read_at(3, &mut buf.0[..512]),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            handle.read_at(0, &mut buf.0[..100]),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            handle.read_at(0, &mut buf.0[1..513]),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(handle.write_at(0, &buf.0[1..]), Err(VfsError::InvalidInput));
+    }
+
+    #[test]
+    fn test_direct_io_constraints() {
+        #[repr(align(4096))]
+        struct PageBuf([u8; 8192]);
+
+        let handle = VfsFileHandle::open(Arc::new(BlockNode)).unwrap();
+        handle.set_direct(true).unwrap();
+        let mut buf = PageBuf([0; 8192]);
+        assert_eq!(handle.read_at(4096, &mut buf.0[..4096]), Ok(4096));
+        assert_eq!(
+            handle.read_at(512, &mut buf.0[..4096]),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            handle.read_at(0, &mut buf.0[512..4608]),
+            Err(VfsError::InvalidInput)
+        );
+
+        let handle = VfsFileHandle::open(Arc::new(UncachedNode)).unwrap();
+        assert_eq!(handle.set_direct(true), Err(VfsError::InvalidInput));
+        assert!(!handle.is_direct());
+        assert_eq!(handle.set_direct(false), Ok(()));
+    }
+
+    #[test]
+    fn test_nonblocking_mode() {
+        let node = Arc::new(SlowNode::default());
+        let flags = OpenFlags::READ | OpenFlags::NONBLOCK;
+        let handle = VfsFileHandle::open_with(node.clone(), flags).unwrap();
+        assert!(handle.is_nonblocking());
+        assert!(handle.reopen().unwrap().is_nonblocking());
+        let mut buf = [0; 4];
+        assert_eq!(handle.read_at(0, &mut buf), Err(VfsError::WouldBlock));
+        assert_eq!(handle.read_at(0, &mut buf), Ok(4));
+
+        // a blocking read waits for the data
+        node.0.store(false, Ordering::SeqCst);
+        handle.set_nonblocking(false);
+        assert_eq!(handle.read_at(0, &mut buf), Ok(4));
+    }
+
+    /// A terminal whose reads wait for a write.
+    #[derive(Default)]
+    struct TtyNode {
+        input: AtomicBool,
+        wakers: crate::poll::WakerSet,
+    }
+
+    impl VfsNodeOps for TtyNode {
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            match self.input.swap(false, Ordering::AcqRel) {
+                true => Ok(buf.len()),
+                false => Err(VfsError::WouldBlock),
+            }
+        }
+
+        fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            self.input.store(true, Ordering::Release);
+            self.wakers.wake(crate::PollEvents::IN);
+            Ok(buf.len())
+        }
+
+        fn poll(&self) -> VfsResult<crate::PollEvents> {
+            let mut events = crate::PollEvents::OUT;
+            events.set(crate::PollEvents::IN, self.input.load(Ordering::Acquire));
+            Ok(events)
+        }
+
+        fn register_waker(
+            &self,
+            events: crate::PollEvents,
+            waker: &core::task::Waker,
+        ) -> VfsResult {
+            self.wakers.register(events, waker);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_blocked_read_does_not_lock_offset() {
+        extern crate std;
+
+        let flags = OpenFlags::READ | OpenFlags::WRITE;
+        let handle = VfsFileHandle::open_with(Arc::new(TtyNode::default()), flags).unwrap();
+        let dup = handle.clone();
+        let reader = std::thread::spawn(move || {
+            let mut buf = [0; 4];
+            (handle.read(&mut buf), handle.read(&mut buf))
+        });
+        for _ in 0..2 {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            // the duplicate is usable while the read waits
+            dup.seek(SeekFrom::Current(0)).unwrap();
+            assert_eq!(dup.write(b"x"), Ok(1));
+        }
+        assert_eq!(reader.join().unwrap(), (Ok(4), Ok(4)));
+        // the offset was moved by the writes during the reads
+        assert_eq!(dup.offset(), 2);
+    }
+
+    #[test]
+    fn test_vectored_read_hint() {
+        extern crate std;
+
+        let flags = OpenFlags::READ | OpenFlags::WRITE | OpenFlags::NONBLOCK;
+        let handle = VfsFileHandle::open_with(Arc::new(TtyNode::default()), flags).unwrap();
+        let (mut a, mut b) = ([0; 4], [0; 4]);
+        let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        assert_eq!(handle.read_vectored(&mut bufs), Err(VfsError::WouldBlock));
+        // the second buffer does not wait for more data
+        handle.write(b"x").unwrap();
+        assert_eq!(handle.read_vectored(&mut bufs), Ok(4));
+
+        handle.set_nonblocking(false);
+        let dup = handle.clone();
+        let reader = std::thread::spawn(move || {
+            let (mut a, mut b) = ([0; 4], [0; 4]);
+            dup.read_vectored(&mut [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)])
+        });
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        handle.write(b"x").unwrap();
+        assert_eq!(reader.join().unwrap(), Ok(4));
+    }
+
+    #[test]
+    fn test_concurrent_dup_reads() {
+        extern crate std;
+
+        let data: alloc::vec::Vec<u8> = (0..=255).collect();
+        let node = Arc::new(MemNode(Mutex::new(data), OpenState::new()));
+        let handle = VfsFileHandle::open(node).unwrap();
+        let readers: alloc::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let dup = handle.clone();
+                std::thread::spawn(move || {
+                    let mut seen = alloc::vec::Vec::new();
+                    let mut buf = [0; 1];
+                    while dup.read(&mut buf) == Ok(1) {
+                        seen.push(buf[0]);
+                    }
+                    seen
+                })
+            })
+            .collect();
+        let mut seen: alloc::vec::Vec<u8> = readers
+            .into_iter()
+            .flat_map(|reader| reader.join().unwrap())
+            .collect();
+        seen.sort_unstable();
+        // every byte is read exactly once
+        assert_eq!(seen, (0..=255).collect::<alloc::vec::Vec<u8>>());
+        assert_eq!(handle.offset(), 256);
+    }
+
+    #[test]
+    fn test_dup_shares_offset() {
+        let node = Arc::new(MemNode::default());
+        let handle = VfsFileHandle::open(node.clone()).unwrap();
+        let dup = handle.clone();
+        assert_eq!(handle.write(b"hello"), Ok(5));
+        assert_eq!(dup.offset(), 5);
+        assert_eq!(dup.write(b" world"), Ok(6));
+        assert_eq!(node.0.lock().as_slice(), b"hello world");
+
+        let mut buf = [0; 5];
+        assert_eq!(dup.seek(SeekFrom::Start(0)), Ok(0));
+        assert_eq!(handle.read(&mut buf), Ok(5));
+        assert_eq!(dup.read(&mut buf[..1]), Ok(1));
+        assert_eq!(&buf[..1], b" ");
+        assert_eq!(handle.offset(), 6);
+    }
+
+    #[test]
+    fn test_reopen_has_own_offset() {
+        let node = Arc::new(MemNode::default());
+        let handle = VfsFileHandle::open(node.clone()).unwrap();
+        handle.write(b"abcdef").unwrap();
+        let other = handle.reopen().unwrap();
+        assert_eq!(handle.open_count(), 2);
+        assert_eq!(other.offset(), 0);
+
+        assert_eq!(other.write(b"XY"), Ok(2));
+        assert_eq!(handle.offset(), 6);
+        assert_eq!(node.0.lock().as_slice(), b"XYcdef");
+        drop(other);
+        assert_eq!(handle.open_count(), 1);
+    }
+
+    #[test]
+    fn test_seek() {
+        let node = Arc::new(MemNode::default());
+        let handle = VfsFileHandle::open(node).unwrap();
+        handle.write(b"0123456789").unwrap();
+        assert_eq!(handle.seek(SeekFrom::End(-4)), Ok(6));
+        assert_eq!(handle.seek(SeekFrom::Current(-6)), Ok(0));
+        assert_eq!(
+            handle.seek(SeekFrom::Current(-1)),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(handle.offset(), 0);
+        assert_eq!(handle.seek(SeekFrom::End(2)), Ok(12));
+    }
+
+    #[test]
+    fn test_append_mode() {
+        let node = Arc::new(MemNode::default());
+        let log = VfsFileHandle::open(node.clone()).unwrap();
+        log.set_append(true);
+        let dup = log.clone();
+        let other = log.reopen().unwrap();
+        assert!(other.is_append());
+        let plain = VfsFileHandle::open(node.clone()).unwrap();
+
+        log.write(b"one\n").unwrap();
+        // the other open still writes at the end, despite its own offset
+        other.write(b"two\n").unwrap();
+        assert_eq!(other.offset(), 8);
+        // seeking back does not make appends overwrite data
+        dup.seek(SeekFrom::Start(0)).unwrap();
+        dup.write(b"three\n").unwrap();
+        assert_eq!(log.offset(), 14);
+        // a handle without append mode writes at its own offset
+        plain.write(b"ONE").unwrap();
+        assert_eq!(node.0.lock().as_slice(), b"ONE\ntwo\nthree\n");
+    }
+
+    #[test]
+    fn test_vectored_io() {
+        let node = Arc::new(MemNode::default());
+        let handle = VfsFileHandle::open(node.clone()).unwrap();
+        let bufs = [IoSlice::new(b"head:"), IoSlice::new(b"body")];
+        assert_eq!(handle.write_vectored(&bufs), Ok(9));
+        assert_eq!(handle.offset(), 9);
+
+        handle.seek(SeekFrom::Start(2)).unwrap();
+        let (mut a, mut b) = ([0; 3], [0; 8]);
+        let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        // the default implementation stops at the end of the file
+        assert_eq!(handle.read_vectored(&mut bufs), Ok(7));
+        assert_eq!((&a, &b[..4]), (b"ad:", &b"body"[..]));
+        assert_eq!(handle.offset(), 9);
+    }
+
+    #[test]
+    fn test_open_flags() {
+        let node = Arc::new(MemNode::default());
+        let reader = VfsFileHandle::open_with(node.clone(), OpenFlags::READ).unwrap();
+        assert_eq!(reader.write(b"x"), Err(VfsError::BadFileDescriptor));
+        assert_eq!(reader.read(&mut [0; 4]), Ok(0));
+
+        let flags = OpenFlags::WRITE | OpenFlags::APPEND | OpenFlags::TRUNC;
+        let writer = VfsFileHandle::open_with(node.clone(), flags).unwrap();
+        assert!(writer.is_append());
+        assert_eq!(writer.read(&mut [0; 4]), Err(VfsError::BadFileDescriptor));
+        writer.write(b"data").unwrap();
+        // reopening does not truncate again
+        assert_eq!(
+            writer.reopen().unwrap().flags(),
+            OpenFlags::WRITE | OpenFlags::APPEND
+        );
+        assert_eq!(reader.read(&mut [0; 4]), Ok(4));
+    }
+
+    #[test]
+    fn test_revoke() {
+        let node = Arc::new(MemNode::default());
+        assert_eq!(revoke(node.as_ref()), 0);
+        let handle = VfsFileHandle::open(node.clone()).unwrap();
+        handle.write(b"data").unwrap();
+        let other = handle.reopen().unwrap();
+        assert_eq!(revoke(node.as_ref()), 2);
+
+        assert!(handle.is_revoked());
+        assert_eq!(handle.write(b"x"), Err(VfsError::NoSuchDevice));
+        assert_eq!(other.read_at(0, &mut [0; 4]), Err(VfsError::NoSuchDevice));
+        assert_eq!(handle.ioctl(0, 0), Err(VfsError::NoSuchDevice));
+        assert_eq!(
+            handle.advise(0, 0, FileAdvice::WillNeed),
+            Err(VfsError::NoSuchDevice)
+        );
+        assert_eq!(
+            VfsFileHandle::open(node.clone()).err(),
+            Some(VfsError::NoSuchDevice)
+        );
+
+        // the node can be opened again once the revoked handles are closed
+        drop(handle);
+        other.close().unwrap();
+        assert!(!is_revoked(node.as_ref()));
+        let handle = VfsFileHandle::open(node).unwrap();
+        assert_eq!(handle.read(&mut [0; 4]), Ok(4));
+    }
+
+    #[test]
+    fn test_per_open_file() {
+        use crate::VfsFileOps;
+
+        /// A file recording its writes and release in its node.
+        struct LogFile(Arc<LogNode>, u8);
+
+        #[derive(Default)]
+        struct LogNode(Mutex<alloc::vec::Vec<u8>>);
+
+        impl VfsFileOps for LogFile {
+            fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+                self.0 .0.lock().push(self.1);
+                Ok(buf.len())
+            }
+
+            fn ioctl(&self, _cmd: u32, _arg: usize) -> VfsResult<usize> {
+                Ok(self.1 as usize)
+            }
+
+            fn release(&self) -> VfsResult {
+                self.0 .0.lock().push(0);
+                Ok(())
+            }
+        }
+
+        impl VfsNodeOps for Arc<LogNode> {
+            fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+                let id = self.0.lock().len() as u8 + 1;
+                Ok(Some(Arc::new(LogFile(self.clone(), id))))
+            }
+        }
+
+        let node = Arc::new(LogNode::default());
+        let first = VfsFileHandle::open(Arc::new(node.clone())).unwrap();
+        first.write(b"a").unwrap();
+        let second = first.reopen().unwrap();
+        assert!(second.file().is_some());
+        second.write(b"b").unwrap();
+        assert_eq!((first.ioctl(0, 0), second.ioctl(0, 0)), (Ok(1), Ok(2)));
+        drop(first);
+        assert_eq!(node.0.lock().as_slice(), [1, 2, 0]);
+    }
+
+    #[test]
+    fn test_advise() {
+        let node = Arc::new(PrefetchNode::default());
+        let handle = VfsFileHandle::open(node.clone()).unwrap();
+        handle.advise(0, 0, FileAdvice::Sequential).unwrap();
+        handle.advise(4096, 8192, FileAdvice::WillNeed).unwrap();
+        handle.advise(0, 4096, FileAdvice::DontNeed).unwrap();
+        assert_eq!(node.0.lock().as_slice(), &[(4096, 8192)]);
+
+        // the default implementation accepts the advice
+        let handle = VfsFileHandle::open(Arc::new(CachedNode)).unwrap();
+        assert_eq!(handle.advise(0, 0, FileAdvice::WillNeed), Ok(()));
+    }
+}
@@ -0,0 +1,138 @@
+use core::ops::{Deref, DerefMut};
+
+use crate::VfsResult;
+
+/// A buffer to write from, for vectored I/O.
+///
+/// This is the `no_std` counterpart of `std::io::IoSlice`, used by
+/// [`VfsNodeOps::write_vectored_at`](crate::VfsNodeOps::write_vectored_at).
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::IoSlice;
+///
+/// let bufs = [IoSlice::new(b"hello, "), IoSlice::new(b"world")];
+/// assert_eq!(IoSlice::total_len(&bufs), 12);
+/// assert_eq!(&*bufs[1], b"world");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct IoSlice<'a>(&'a [u8]);
+
+/// A buffer to read into, for vectored I/O.
+///
+/// This is the `no_std` counterpart of `std::io::IoSliceMut`, used by
+/// [`VfsNodeOps::read_vectored_at`](crate::VfsNodeOps::read_vectored_at).
+#[derive(Debug)]
+pub struct IoSliceMut<'a>(&'a mut [u8]);
+
+impl<'a> IoSlice<'a> {
+    /// Wraps the buffer `buf`.
+    pub const fn new(buf: &'a [u8]) -> Self {
+        Self(buf)
+    }
+
+    /// Returns the total length of the buffers `bufs`.
+    pub fn total_len(bufs: &[Self]) -> usize {
+        bufs.iter().map(|buf| buf.len()).sum()
+    }
+}
+
+impl<'a> IoSliceMut<'a> {
+    /// Wraps the buffer `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self(buf)
+    }
+
+    /// Returns the total length of the buffers `bufs`.
+    pub fn total_len(bufs: &[Self]) -> usize {
+        bufs.iter().map(|buf| buf.len()).sum()
+    }
+}
+
+impl Deref for IoSlice<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl Deref for IoSliceMut<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.0
+    }
+}
+
+impl DerefMut for IoSliceMut<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.0
+    }
+}
+
+/// Reads into `bufs` in order with `read`, stopping at the first short read.
+///
+/// An error is only returned if nothing was read, otherwise the bytes read
+/// so far are.
+pub(crate) fn read_vectored_with(
+    offset: u64,
+    bufs: &mut [IoSliceMut],
+    mut read: impl FnMut(u64, &mut [u8]) -> VfsResult<usize>,
+) -> VfsResult<usize> {
+    let mut total = 0;
+    for buf in bufs {
+        match read(offset + total as u64, buf) {
+            Ok(n) => {
+                total += n;
+                if n < buf.len() {
+                    break;
+                }
+            }
+            Err(err) if total == 0 => return Err(err),
+            Err(_) => break,
+        }
+    }
+    Ok(total)
+}
+
+/// Writes `bufs` in order with `write`, stopping at the first short write.
+///
+/// An error is only returned if nothing was written, otherwise the bytes
+/// written so far are.
+pub(crate) fn write_vectored_with(
+    offset: u64,
+    bufs: &[IoSlice],
+    mut write: impl FnMut(u64, &[u8]) -> VfsResult<usize>,
+) -> VfsResult<usize> {
+    let mut total = 0;
+    for buf in bufs {
+        match write(offset + total as u64, buf) {
+            Ok(n) => {
+                total += n;
+                if n < buf.len() {
+                    break;
+                }
+            }
+            Err(err) if total == 0 => return Err(err),
+            Err(_) => break,
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_slice_mut() {
+        let (mut a, mut b) = ([0; 2], [0; 3]);
+        let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        assert_eq!(IoSliceMut::total_len(&bufs), 5);
+        bufs[1].copy_from_slice(b"abc");
+        assert_eq!(&b, b"abc");
+        assert_eq!(IoSlice::total_len(&[]), 0);
+    }
+}
@@ -1,17 +1,23 @@
 //! Virtual filesystem interfaces used by [ArceOS](https://github.com/arceos-org/arceos).
 //!
-//! A filesystem is a set of files and directories (symbol links are not
-//! supported currently), collectively referred to as **nodes**, which are
-//! conceptually similar to [inodes] in Linux. A file system needs to implement
-//! the [`VfsOps`] trait, its files and directories need to implement the
-//! [`VfsNodeOps`] trait.
+//! A filesystem is a set of files, directories and symbolic links,
+//! collectively referred to as **nodes**, which are conceptually similar to
+//! [inodes] in Linux. A file system needs to implement the [`VfsOps`] trait,
+//! its nodes need to implement the [`VfsNodeOps`] trait.
 //!
 //! The [`VfsOps`] trait provides the following operations on a filesystem:
 //!
-//! - [`mount()`](VfsOps::mount): Do something when the filesystem is mounted.
+//! - [`mount()`](VfsOps::mount): Do something when the filesystem is mounted, with the given [`MountOptions`].
+//! - [`remount()`](VfsOps::remount): Change the options of the mounted filesystem, such as making it read-only.
 //! - [`umount()`](VfsOps::umount): Do something when the filesystem is unmounted.
 //! - [`format()`](VfsOps::format): Format the filesystem.
 //! - [`statfs()`](VfsOps::statfs): Get the attributes of the filesystem.
+//! - [`freeze()`](VfsOps::freeze) / [`thaw()`](VfsOps::thaw): Reject or accept mutations again.
+//! - [`capabilities()`](VfsOps::capabilities): Get the optional features supported by the filesystem.
+//! - [`fs_type()`](VfsOps::fs_type) / [`fs_magic()`](VfsOps::fs_magic): Get the name and id of the filesystem type.
+//! - [`open_by_ino()`](VfsOps::open_by_ino): Get a node by its inode number.
+//! - [`encode_fh()`](VfsOps::encode_fh) / [`open_by_handle()`](VfsOps::open_by_handle): Export a node by file handle, see [`export`].
+//! - [`subscribe()`](VfsOps::subscribe) / [`unsubscribe()`](VfsOps::unsubscribe): Watch the changes of a node, see [`notify`].
 //! - [`root_dir()`](VfsOps::root_dir): Get root directory of the filesystem.
 //!
 //! The [`VfsNodeOps`] trait provides the following operations on a file or a
@@ -21,16 +27,105 @@
 //! | --- | --- | --- |
 //! | [`open()`](VfsNodeOps::open) | Do something when the node is opened | both |
 //! | [`release()`](VfsNodeOps::release) | Do something when the node is closed | both |
+//! | [`on_last_release()`](VfsNodeOps::on_last_release) | Do something when the last open handle is closed | both |
+//! | [`open_state()`](VfsNodeOps::open_state) | Get the state counting the open handles of the node | both |
 //! | [`get_attr()`](VfsNodeOps::get_attr) | Get the attributes of the node | both |
+//! | [`get_attr_ext()`](VfsNodeOps::get_attr_ext) | Get the requested attributes of the node, like `statx()` | both |
+//! | [`set_attr()`](VfsNodeOps::set_attr) | Change the permissions, owner, size or times of the node | both |
+//! | [`generation()`](VfsNodeOps::generation) | Get the generation number of the node | both |
+//! | [`get_flags()`](VfsNodeOps::get_flags) / [`set_flags()`](VfsNodeOps::set_flags) | Get or change the immutable and append-only flags | both |
 //! | [`read_at()`](VfsNodeOps::read_at) | Read data from the file | file |
 //! | [`write_at()`](VfsNodeOps::write_at) | Write data to the file | file |
+//! | [`read_direct_at()`](VfsNodeOps::read_direct_at) | Read data from the file, bypassing caches | file |
+//! | [`write_direct_at()`](VfsNodeOps::write_direct_at) | Write data to the file, bypassing caches | file |
+//! | [`io_alignment()`](VfsNodeOps::io_alignment) / [`supports_direct_io()`](VfsNodeOps::supports_direct_io) | Get the direct I/O constraints of the file | file |
 //! | [`fsync()`](VfsNodeOps::fsync) | Synchronize the file data to disk | file |
+//! | [`readahead()`](VfsNodeOps::readahead) | Prefetch a range of the file | file |
 //! | [`truncate()`](VfsNodeOps::truncate) | Truncate the file | file |
+//! | [`copy_file_range()`](VfsNodeOps::copy_file_range) | Copy a range of the file into another file | file |
+//! | [`ioctl()`](VfsNodeOps::ioctl) | Send a device-specific control command | file |
+//! | [`poll()`](VfsNodeOps::poll) / [`register_waker()`](VfsNodeOps::register_waker) | Get or wait for the readiness of the node, see [`poll`] | file |
+//! | [`read_hinted()`](VfsNodeOps::read_hinted) | Read data from the file, waiting for it or not | file |
+//! | [`get_page()`](VfsNodeOps::get_page) | Get a page of the file to map in memory | file |
+//! | [`read_link()`](VfsNodeOps::read_link) | Read the target of the symbolic link | symlink |
 //! | [`parent()`](VfsNodeOps::parent) | Get the parent directory | directory |
 //! | [`lookup()`](VfsNodeOps::lookup) | Lookup the node with the given path | directory |
+//! | [`lookup_step()`](VfsNodeOps::lookup_step) | Lookup the given path until a mount point | directory |
+//! | [`is_mount_point()`](VfsNodeOps::is_mount_point) / [`set_mount_point()`](VfsNodeOps::set_mount_point) | Get or set whether a filesystem is mounted on the node | directory |
 //! | [`create()`](VfsNodeOps::create) | Create a new node with the given path | directory |
+//! | [`create_exclusive()`](VfsNodeOps::create_exclusive) | Create a new node, failing if it exists | directory |
+//! | [`mknod()`](VfsNodeOps::mknod) | Create a device node | directory |
+//! | [`create_symlink()`](VfsNodeOps::create_symlink) | Create a symbolic link with the given path | directory |
 //! | [`remove()`](VfsNodeOps::remove) | Remove the node with the given path | directory |
+//! | [`link()`](VfsNodeOps::link) | Create a hard link to an existing node | directory |
+//! | [`create_tmpfile()`](VfsNodeOps::create_tmpfile) | Create a file without a name | directory |
+//! | [`link_into()`](VfsNodeOps::link_into) | Give a name to the node in a directory | file |
 //! | [`read_dir()`](VfsNodeOps::read_dir) | Read directory entries | directory |
+//! | [`read_dir_opts()`](VfsNodeOps::read_dir_opts) | Read filtered and sorted directory entries | directory |
+//! | [`read_dir_buf()`](VfsNodeOps::read_dir_buf) | Serialize directory entries into a byte buffer | directory |
+//! | [`open_dir()`](VfsNodeOps::open_dir) | Open a cursor over the directory entries | directory |
+//!
+//! Nodes that are only files or only directories implement the smaller
+//! [`VfsFileNodeOps`] or [`VfsDirNodeOps`] traits instead, as the nodes of
+//! `axfs_ramfs` and `axfs_devfs` do, and derive [`VfsNodeOps`] with
+//! [`impl_vfs_file_node!`] or [`impl_vfs_dir_node!`]. The derived
+//! operations of the other kind fail with the right error. Foreign types,
+//! for which the macros cannot implement [`VfsNodeOps`], are wrapped in a
+//! [`FileNodeAdapter`] or a [`DirNodeAdapter`] instead. A node wrapping
+//! another one, such as a [`TracedNode`](trace::TracedNode), forwards the
+//! operations it does not hook with [`impl_vfs_forward!`].
+//!
+//! The concrete node behind a [`VfsNodeRef`] can be recovered with the
+//! [`VfsNodeRefExt`] helpers, such as
+//! [`lookup_as()`](VfsNodeRefExt::lookup_as).
+//!
+//! Periodic flushing of buffered writes is driven through the [`writeback`]
+//! module.
+//!
+//! Memory mappings of nodes are described in the [`page`] module.
+//!
+//! A [`SliceNode`] exposes a byte range of a file as a file, and a
+//! [`FileWindow`] exposes a file, or a byte range of it, read-only.
+//!
+//! With the `async` feature, the `async_ops` module provides asynchronous
+//! counterparts of the filesystem traits.
+//!
+//! With the `serde` feature, [`VfsNodeAttr`], [`VfsNodePerm`],
+//! [`VfsNodeType`], [`VfsDirEntry`] and [`FileSystemInfo`] implement
+//! `Serialize` and `Deserialize`, for dump tools and remote filesystems.
+//!
+//! Path and name length limits are defined in the [`limits`] module, and
+//! the validation and normalization of names in the [`names`] module.
+//!
+//! Errors are translated to Linux errno values by the [`errno`] module, and
+//! constructed with the context of the failure by [`vfs_err!`].
+//!
+//! Node timestamps are read from a [`VfsClock`], see the [`clock`] module.
+//! Path-based access control for sandboxing is provided by the [`policy`]
+//! module, and Unix permission checks by the [`access`] module. Directories
+//! can enforce them with the [`VfsContext`] of the caller, passed to the
+//! `*_ctx` variants of their operations, or obtained from the
+//! [`ContextProvider`] of the current task.
+//!
+//! Files are opened by path with the [`OpenOptions`] builder, and replaced
+//! atomically with the [`replace`] module.
+//!
+//! Relative paths are resolved across mounts against the current working
+//! directories of the [`cwd`] module. Repeated walks of
+//! the same paths over a slow filesystem are cached by the [`dcache`]
+//! module.
+//!
+//! Disk-backed filesystems register a driver in the [`driver`] module, to
+//! be mounted by type or detected from the content of a [`block`] device.
+//! Backends that can stall are given per-operation deadlines by the
+//! [`timeout`] module.
+//!
+//! The last operations of the filesystems can be kept for postmortem
+//! debugging with the [`trace`] module, which also times the operations on
+//! a tree wrapped in a [`TracedNode`](trace::TracedNode), and whole trees
+//! compared by digest with the [`digest`] module. The I/O statistics of a
+//! filesystem are counted by wrapping it in a
+//! [`MeteredFs`](metrics::MeteredFs), see the [`metrics`] module.
 //!
 //! [inodes]: https://en.wikipedia.org/wiki/Inode
 
@@ -38,15 +133,82 @@
 
 extern crate alloc;
 
+mod context;
+mod cred;
+mod downcast;
+mod file;
+mod iovec;
 mod macros;
+mod node;
+mod options;
+mod readdir;
+mod setattr;
+mod slice;
+mod statx;
 mod structs;
+mod window;
 
+pub mod access;
+#[cfg(feature = "async")]
+pub mod async_ops;
+pub mod block;
+pub mod clock;
+pub mod copy;
+pub mod cwd;
+pub mod dcache;
+pub mod device;
+pub mod digest;
+pub mod driver;
+pub mod errno;
+pub mod export;
+pub mod handle;
+pub mod limits;
+pub mod metrics;
+pub mod mount;
+pub mod names;
+pub mod notify;
+pub mod page;
 pub mod path;
+pub mod policy;
+pub mod poll;
+pub mod replace;
+pub mod timeout;
+pub mod trace;
+pub mod writeback;
 
+use alloc::boxed::Box;
 use alloc::sync::Arc;
 use axerrno::{ax_err, AxError, AxResult};
+use core::task::Waker;
 
-pub use self::structs::{FileSystemInfo, VfsDirEntry, VfsNodeAttr, VfsNodePerm, VfsNodeType};
+use self::export::ExportHandle;
+use self::notify::{WatchId, WatchMask, WatchSink};
+
+pub use self::block::BlockDeviceOps;
+pub use self::clock::VfsClock;
+pub use self::context::{current_context, set_context_provider, ContextProvider, VfsContext};
+pub use self::cred::{Capabilities, Credentials};
+pub use self::device::{DeviceId, DeviceResolver};
+pub use self::downcast::VfsNodeRefExt;
+pub use self::file::{VfsFileOps, VfsFileRef};
+pub use self::iovec::{IoSlice, IoSliceMut};
+pub use self::mount::{AtimePolicy, MountOptions};
+pub use self::node::{DirNodeAdapter, FileNodeAdapter, VfsDirNodeOps, VfsFileNodeOps};
+pub use self::options::OpenOptions;
+pub use self::page::VfsPage;
+pub use self::poll::{PollEvents, ReadHint};
+pub use self::readdir::{
+    DirCookie, DirEntries, DirOrder, DirStream, ReadDirOptions, ReadDirPolicy, SnapshotDirStream,
+};
+pub use self::setattr::SetAttr;
+pub use self::slice::SliceNode;
+pub use self::statx::{AttrMask, VfsNodeAttrExt};
+pub use self::structs::{
+    FallocateMode, FileSystemInfo, OpenFlags, RenameFlags, SeekHint, Umask, VfsCapabilities,
+    VfsDirEntry, VfsFeatures, VfsNodeAttr, VfsNodeAttrBuilder, VfsNodeFlags, VfsNodePerm,
+    VfsNodeType,
+};
+pub use self::window::FileWindow;
 
 /// A wrapper of [`Arc<dyn VfsNodeOps>`].
 ///
@@ -68,6 +230,28 @@ pub type VfsError = AxError;
 /// * `T` - The type of the success value. Defaults to `()`.
 pub type VfsResult<T = ()> = AxResult<T>;
 
+/// Returns the error of a file operation that `node` does not implement:
+/// [`VfsError::IsADirectory`] if it is a directory, `err` otherwise.
+fn file_op_default<T, N: VfsNodeOps + ?Sized>(node: &N, err: VfsError) -> VfsResult<T> {
+    match node.get_attr() {
+        Ok(attr) if attr.is_dir() => Err(VfsError::IsADirectory),
+        _ => Err(err),
+    }
+}
+
+/// Returns the error of a directory operation that `node` does not
+/// implement: [`VfsError::NotADirectory`] if it is not a directory, `err`
+/// otherwise.
+///
+/// Nodes whose type is unknown, as they do not implement
+/// [`get_attr()`](VfsNodeOps::get_attr), get `err`.
+fn dir_op_default<T, N: VfsNodeOps + ?Sized>(node: &N, err: VfsError) -> VfsResult<T> {
+    match node.get_attr() {
+        Ok(attr) if !attr.is_dir() => Err(VfsError::NotADirectory),
+        _ => Err(err),
+    }
+}
+
 /// Filesystem operations.
 ///
 /// This trait defines the operations that a filesystem must implement.
@@ -77,20 +261,52 @@ pub trait VfsOps: Send + Sync {
     /// Do something when the filesystem is mounted.
     ///
     /// This method is called when the filesystem is mounted at a specific path.
-    /// The default implementation does nothing.
+    /// The default implementation does nothing, and ignores the
+    /// filesystem-specific options.
     ///
     /// # Arguments
     ///
     /// * `_path` - The path where the filesystem is being mounted
     /// * `_mount_point` - A reference to the mount point directory node
+    /// * `_options` - The options of the mount, see [`MountOptions`]
     ///
     /// # Returns
     ///
     /// Returns `Ok(())` if the mount operation succeeds, or an error otherwise.
-    fn mount(&self, _path: &str, _mount_point: VfsNodeRef) -> VfsResult {
+    ///
+    /// # Errors
+    ///
+    /// Filesystems return [`AxError::InvalidInput`] for filesystem-specific
+    /// options they do not know or whose value is invalid.
+    fn mount(&self, _path: &str, _mount_point: VfsNodeRef, _options: &MountOptions) -> VfsResult {
         Ok(())
     }
 
+    /// Change the options of the mounted filesystem, like `mount -o remount`.
+    ///
+    /// Filesystems supporting it become read-only when
+    /// [`MountOptions::is_read_only`] is set, and then fail every mutating
+    /// operation of their nodes with [`AxError::PermissionDenied`], or
+    /// writable again otherwise. The default implementation returns
+    /// [`AxError::Unsupported`].
+    ///
+    /// # Arguments
+    ///
+    /// * `_options` - The new options of the mount
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the options are applied, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::Unsupported`] if the filesystem cannot be
+    /// remounted, or [`AxError::InvalidInput`] for filesystem-specific
+    /// options it does not know or whose value is invalid.
+    fn remount(&self, _options: &MountOptions) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
     /// Do something when the filesystem is unmounted.
     ///
     /// This method is called when the filesystem is unmounted.
@@ -103,6 +319,23 @@ pub trait VfsOps: Send + Sync {
         Ok(())
     }
 
+    /// Shut the filesystem down for system poweroff.
+    ///
+    /// Unlike [`umount()`](Self::umount), which may be followed by another
+    /// mount, this is the last call on the filesystem: it flushes buffered
+    /// data to persistent storage, revokes the open handles of its nodes
+    /// (see [`handle::revoke`]) and drops its background state, such as its
+    /// registration with a [`writeback`] scheduler. A mount manager calls it
+    /// on every mounted filesystem, children first, when powering off.
+    /// The default implementation does nothing.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or the first error met while flushing.
+    fn shutdown(&self) -> VfsResult {
+        Ok(())
+    }
+
     /// Format the filesystem.
     ///
     /// This method formats the filesystem, erasing all existing data.
@@ -137,310 +370,1524 @@ pub trait VfsOps: Send + Sync {
         ax_err!(Unsupported)
     }
 
-    /// Get the root directory of the filesystem.
+    /// Freeze the filesystem.
     ///
-    /// This method returns a reference to the root directory node of the filesystem.
-    /// This is the only required method that must be implemented.
+    /// While frozen, the filesystem rejects new mutations (writes, truncation,
+    /// creation and removal of nodes) but still serves reads, so that a
+    /// consistent backup, snapshot or export of a live filesystem can be
+    /// taken. The default implementation returns [`AxError::Unsupported`].
     ///
     /// # Returns
     ///
-    /// Returns a [`VfsNodeRef`] to the root directory.
-    fn root_dir(&self) -> VfsNodeRef;
-}
+    /// Returns `Ok(())` if the filesystem is now frozen, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::Unsupported`] if the filesystem cannot be frozen,
+    /// or [`AxError::ResourceBusy`] if it is already frozen.
+    fn freeze(&self) -> VfsResult {
+        ax_err!(Unsupported)
+    }
 
-/// Node (file/directory) operations.
-///
-/// This trait defines the operations that can be performed on a VFS node,
-/// which can be either a file or a directory. All operations are safe by
-/// default and can be called concurrently from multiple threads.
-///
-/// # File Operations
-///
-/// The following methods are specific to files:
-/// - [`read_at`](Self::read_at) - Read data from a file
-/// - [`write_at`](Self::write_at) - Write data to a file
-/// - [`fsync`](Self::fsync) - Synchronize file data to disk
-/// - [`truncate`](Self::truncate) - Truncate a file
-///
-/// # Directory Operations
-///
-/// The following methods are specific to directories:
-/// - [`parent`](Self::parent) - Get the parent directory
-/// - [`lookup`](Self::lookup) - Look up a node by path
-/// - [`create`](Self::create) - Create a new node
-/// - [`remove`](Self::remove) - Remove a node
-/// - [`read_dir`](Self::read_dir) - Read directory entries
-/// - [`rename`](Self::rename) - Rename or move a node
-pub trait VfsNodeOps: Send + Sync {
-    /// Do something when the node is opened.
+    /// Thaw the filesystem frozen by [`freeze()`](Self::freeze).
     ///
-    /// This method is called when a node is opened for access.
-    /// The default implementation does nothing.
+    /// The default implementation returns [`AxError::Unsupported`].
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if the open operation succeeds, or an error otherwise.
-    fn open(&self) -> VfsResult {
-        Ok(())
+    /// Returns `Ok(())` if the filesystem accepts mutations again, or an
+    /// error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::Unsupported`] if the filesystem cannot be frozen,
+    /// or [`AxError::InvalidInput`] if it is not frozen.
+    fn thaw(&self) -> VfsResult {
+        ax_err!(Unsupported)
     }
 
-    /// Do something when the node is closed.
+    /// Get the optional features supported by the filesystem.
     ///
-    /// This method is called when a node is closed after use.
-    /// The default implementation does nothing.
+    /// Generic code can use this method to adapt its behavior to the
+    /// filesystem, instead of probing it with operations that may have side
+    /// effects. The default implementation returns
+    /// [`VfsCapabilities::default()`].
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if the release operation succeeds, or an error otherwise.
-    fn release(&self) -> VfsResult {
-        Ok(())
+    /// Returns a [`VfsCapabilities`] describing the filesystem.
+    fn capabilities(&self) -> VfsCapabilities {
+        VfsCapabilities::default()
     }
 
-    /// Get the attributes of the node.
+    /// Get the name of the filesystem type.
     ///
-    /// This method retrieves metadata about the node, including its type,
-    /// size, permissions, etc.
-    /// The default implementation returns [`AxError::Unsupported`].
+    /// The name identifies the implementation (such as `"ramfs"`), as shown
+    /// in `/proc/mounts` and mount diagnostics. The default implementation
+    /// returns `"unknown"`.
     ///
     /// # Returns
     ///
-    /// Returns a [`VfsNodeAttr`] containing the node's attributes on success,
-    /// or an error otherwise.
+    /// Returns the filesystem type name.
+    fn fs_type(&self) -> &str {
+        "unknown"
+    }
+
+    /// Get the numeric identifier of the filesystem type.
     ///
-    /// # Errors
+    /// This is the magic number reported in the `f_type` field of `statfs`,
+    /// using the Linux value when the filesystem has one. The default
+    /// implementation returns `0`.
     ///
-    /// Returns [`AxError::Unsupported`] if the node does not support this operation.
-    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
-        ax_err!(Unsupported)
+    /// # Returns
+    ///
+    /// Returns the filesystem type magic number.
+    fn fs_magic(&self) -> u64 {
+        0
     }
 
-    // file operations:
-
-    /// Read data from the file at the given offset.
+    /// Get the node with inode number `ino`.
     ///
-    /// This method reads up to `buf.len()` bytes from the file starting at
-    /// `offset`. The actual number of bytes read is returned.
-    /// The default implementation returns [`AxError::InvalidInput`].
+    /// This allows NFS-style file handles, and re-validating cached entries
+    /// without walking their path again. Filesystems supporting it advertise
+    /// [`VfsFeatures::OPEN_BY_INO`]. The default implementation returns
+    /// [`AxError::Unsupported`].
     ///
     /// # Arguments
     ///
-    /// * `offset` - The offset in the file to start reading from
-    /// * `buf` - The buffer to read data into
+    /// * `ino` - The inode number of the node
     ///
     /// # Returns
     ///
-    /// Returns the number of bytes actually read on success, or an error otherwise.
+    /// Returns a [`VfsNodeRef`] to the node on success, or an error otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::InvalidInput`] if called on a non-file node.
-    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
-        ax_err!(InvalidInput)
+    /// Returns [`AxError::Unsupported`] if the filesystem does not support
+    /// lookups by inode number, or [`AxError::NotFound`] if no node has
+    /// number `ino`, or it has been removed.
+    fn open_by_ino(&self, _ino: u64) -> VfsResult<VfsNodeRef> {
+        ax_err!(Unsupported)
     }
 
-    /// Write data to the file at the given offset.
+    /// Get a handle identifying `node` across renames, like
+    /// `name_to_handle_at()`.
     ///
-    /// This method writes up to `buf.len()` bytes to the file starting at
-    /// `offset`. The actual number of bytes written is returned.
-    /// The default implementation returns [`AxError::InvalidInput`].
+    /// The handle is made of the inode number and the
+    /// [`generation`](VfsNodeOps::generation) of the node, see [`export`].
+    /// The default implementation builds it from the attributes of the node
+    /// if the filesystem advertises [`VfsFeatures::OPEN_BY_INO`], and
+    /// returns [`AxError::Unsupported`] otherwise.
     ///
     /// # Arguments
     ///
-    /// * `offset` - The offset in the file to start writing to
-    /// * `buf` - The buffer containing the data to write
+    /// * `node` - A node of this filesystem
     ///
     /// # Returns
     ///
-    /// Returns the number of bytes actually written on success, or an error otherwise.
+    /// Returns the handle of the node on success, or an error otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::InvalidInput`] if called on a non-file node.
-    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
-        ax_err!(InvalidInput)
+    /// Returns [`AxError::Unsupported`] if the filesystem cannot resolve
+    /// handles, or the errors of [`VfsNodeOps::get_attr`].
+    fn encode_fh(&self, node: &VfsNodeRef) -> VfsResult<ExportHandle> {
+        if !self.capabilities().supports(VfsFeatures::OPEN_BY_INO) {
+            return ax_err!(Unsupported);
+        }
+        Ok(ExportHandle::new(node.get_attr()?.ino(), node.generation()))
     }
 
-    /// Flush the file, synchronize the data to disk.
+    /// Get the node identified by a handle from
+    /// [`encode_fh()`](Self::encode_fh), like `open_by_handle_at()`.
     ///
-    /// This method ensures that all data written to the file is persisted
-    /// to the underlying storage device.
-    /// The default implementation returns [`AxError::InvalidInput`].
+    /// The default implementation gets the node with
+    /// [`open_by_ino()`](Self::open_by_ino), and checks its generation
+    /// number.
+    ///
+    /// # Arguments
+    ///
+    /// * `fh` - The handle of the node
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if synchronization succeeds, or an error otherwise.
+    /// Returns a reference to the node on success, or an error otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::InvalidInput`] if called on a non-file node.
-    fn fsync(&self) -> VfsResult {
-        ax_err!(InvalidInput)
+    /// Returns [`AxError::NotFound`] if the node is gone, including when its
+    /// inode number was reused by another node (the `ESTALE` case), or the
+    /// errors of [`open_by_ino()`](Self::open_by_ino).
+    fn open_by_handle(&self, fh: &ExportHandle) -> VfsResult<VfsNodeRef> {
+        let node = self.open_by_ino(fh.ino())?;
+        if node.generation() != fh.generation() {
+            return ax_err!(NotFound);
+        }
+        Ok(node)
     }
 
-    /// Truncate the file to the given size.
+    /// Watch the changes of the node at `path`, like `inotify_add_watch()`.
     ///
-    /// If `size` is larger than the current file size, the file is extended
-    /// with zeros. If `size` is smaller, the file is truncated.
-    /// The default implementation returns [`AxError::InvalidInput`].
+    /// On a directory, the watch reports the creation, removal and renaming
+    /// of its entries. On a file, it reports the changes of its content.
+    /// Filesystems supporting it advertise [`VfsFeatures::WATCH`]. The
+    /// default implementation returns [`AxError::Unsupported`].
     ///
     /// # Arguments
     ///
-    /// * `size` - The new size of the file in bytes
+    /// * `path` - The path of the node to watch, from the root of the
+    ///   filesystem
+    /// * `mask` - The changes to report
+    /// * `sink` - The receiver of the events
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if truncation succeeds, or an error otherwise.
+    /// Returns the identifier of the watch on success, or an error
+    /// otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::InvalidInput`] if called on a non-file node.
-    fn truncate(&self, _size: u64) -> VfsResult {
-        ax_err!(InvalidInput)
+    /// Returns [`AxError::Unsupported`] if the filesystem does not support
+    /// watches, or the errors of [`VfsNodeOps::lookup`].
+    fn subscribe(
+        &self,
+        _path: &str,
+        _mask: WatchMask,
+        _sink: Arc<dyn WatchSink>,
+    ) -> VfsResult<WatchId> {
+        ax_err!(Unsupported)
     }
 
-    // directory operations:
-
-    /// Get the parent directory of this directory.
+    /// Remove a watch added with [`subscribe()`](Self::subscribe).
     ///
-    /// This method returns a reference to the parent directory of the current node.
-    /// The default implementation returns `None`.
+    /// The default implementation returns [`AxError::Unsupported`].
     ///
     /// # Returns
     ///
-    /// Returns `Some(VfsNodeRef)` if the node has a parent directory,
-    /// or `None` if the node has no parent (e.g., it's a file or root directory).
-    fn parent(&self) -> Option<VfsNodeRef> {
-        None
+    /// Returns `Ok(())` if the watch was removed, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::Unsupported`] if the filesystem does not support
+    /// watches, or [`AxError::InvalidInput`] if there is no watch `id`.
+    fn unsubscribe(&self, _id: WatchId) -> VfsResult {
+        ax_err!(Unsupported)
     }
 
-    /// Lookup the node with given `path` in the directory.
+    /// Get the root directory of the filesystem.
     ///
-    /// This method searches for a node with the specified relative path within
-    /// the directory. If found, it returns a reference to the node.
-    /// The default implementation returns [`AxError::Unsupported`].
+    /// This method returns a reference to the root directory node of the filesystem.
+    /// This is the only required method that must be implemented.
+    ///
+    /// # Returns
+    ///
+    /// Returns a [`VfsNodeRef`] to the root directory.
+    fn root_dir(&self) -> VfsNodeRef;
+}
+
+/// Node (file/directory) operations.
+///
+/// This trait defines the operations that can be performed on a VFS node,
+/// which can be either a file or a directory. All operations are safe by
+/// default and can be called concurrently from multiple threads.
+///
+/// # File and Directory Operations
+///
+/// Most operations only apply to either files or directories, and the other
+/// kind of node rejects them, such as with [`AxError::NotADirectory`] or
+/// [`AxError::IsADirectory`]. [`VfsFileNodeOps`] and [`VfsDirNodeOps`] hold
+/// the operations of each kind, and are the simpler traits to implement:
+/// [`impl_vfs_file_node!`] and [`impl_vfs_dir_node!`] derive this trait
+/// from them, with the operations of the other kind rejected.
+pub trait VfsNodeOps: Send + Sync {
+    /// Do something when the node is opened.
+    ///
+    /// This method is called when a node is opened for access, so the
+    /// filesystem can enforce the access mode and apply the options of
+    /// `flags`, such as [`OpenFlags::TRUNC`]. Nodes needing a separate state
+    /// for each open return it as a [`VfsFileOps`], which then receives the
+    /// data operations of the open. The default implementation does
+    /// nothing.
     ///
     /// # Arguments
     ///
-    /// * `path` - The relative path to look up (e.g., "subdir/file.txt")
+    /// * `flags` - The access mode and options of the open
     ///
     /// # Returns
     ///
-    /// Returns a [`VfsNodeRef`] to the found node, or an error if not found.
+    /// Returns `Ok(None)` if the operations of the open go to the node, the
+    /// file of the open if they go to a per-open file, or an error if the
+    /// open fails.
+    fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        Ok(None)
+    }
+
+    /// Do something when the node is closed.
     ///
-    /// # Errors
+    /// This method is called once for every successful [`open()`](Self::open),
+    /// on the last close of the opened handle (duplicates of a handle share
+    /// the same open, see [`handle`]). The calls are thus balanced, and
+    /// nodes can count their opens with a [`handle::OpenCounter`]. The
+    /// default implementation does nothing.
     ///
-    /// Returns [`AxError::Unsupported`] if the directory does not support lookup,
-    /// or [`AxError::NotFound`] if the path does not exist.
-    fn lookup(self: Arc<Self>, _path: &str) -> VfsResult<VfsNodeRef> {
-        ax_err!(Unsupported)
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the release operation succeeds, or an error otherwise.
+    fn release(&self) -> VfsResult {
+        Ok(())
     }
 
-    /// Create a new node with the given `path` in the directory.
+    /// Do something when the last open handle of the node is closed.
     ///
-    /// This method creates a new file or directory with the specified path.
-    /// If the node already exists, the method returns `Ok(())`.
-    /// The default implementation returns [`AxError::Unsupported`].
+    /// This method is called after [`release()`](Self::release) when the
+    /// node has no open handle anymore. It is the place to implement
+    /// lifecycle behaviors depending on all handles being closed, such as
+    /// signaling EOF to the readers of a FIFO, hanging up a pty, or freeing
+    /// the storage of an unlinked file. The default implementation does
+    /// nothing.
     ///
-    /// # Arguments
+    /// It is only called on nodes tracking their opens, see
+    /// [`open_state()`](Self::open_state), and only on the node owning the
+    /// state, never on its wrappers.
     ///
-    /// * `path` - The path for the new node
-    /// * `ty` - The type of node to create (file or directory)
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    fn on_last_release(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Returns the state in which the handle layer counts the open handles
+    /// of the node, and the node owning it.
+    ///
+    /// A node returning its own [`OpenState`](handle::OpenState) gets its
+    /// [`on_last_release()`](Self::on_last_release) hook called when its
+    /// last handle is closed, and its handles can be revoked with
+    /// [`handle::revoke`]. A node wrapping another one, such as a tracing or
+    /// caching layer, returns the state of the wrapped node instead, so that
+    /// the handles opened through any of its wrappers are counted together
+    /// and the hook runs on the wrapped node.
+    ///
+    /// The default implementation returns `None`: the opens of the node are
+    /// not tracked.
+    fn open_state(&self) -> Option<(&handle::OpenState, &dyn VfsNodeOps)> {
+        None
+    }
+
+    /// Get the attributes of the node.
+    ///
+    /// This method retrieves metadata about the node, including its type,
+    /// size, permissions, etc.
+    /// The default implementation returns [`AxError::Unsupported`].
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if the node was created or already exists,
+    /// Returns a [`VfsNodeAttr`] containing the node's attributes on success,
     /// or an error otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::Unsupported`] if the directory does not support creation.
-    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+    /// Returns [`AxError::Unsupported`] if the node does not support this operation.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
         ax_err!(Unsupported)
     }
 
-    /// Remove the node with the given `path` in the directory.
+    /// Get the attributes of the node selected by `mask`, like `statx()`.
     ///
-    /// This method removes a file or directory at the specified path.
-    /// The default implementation returns [`AxError::Unsupported`].
+    /// Filesystems may skip computing the fields not in `mask`, such as a
+    /// link count that requires scanning a directory, or times that must be
+    /// fetched from a remote server. They may also return more fields than
+    /// requested, the returned [`VfsNodeAttrExt::mask`] tells which are
+    /// valid. The default implementation returns all the fields of
+    /// [`get_attr()`](Self::get_attr).
     ///
     /// # Arguments
     ///
-    /// * `path` - The path of the node to remove
+    /// * `mask` - The fields the caller needs
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if the node was removed successfully, or an error otherwise.
+    /// Returns the attributes of the node on success, or an error
+    /// otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::Unsupported`] if the directory does not support removal.
-    fn remove(&self, _path: &str) -> VfsResult {
-        ax_err!(Unsupported)
+    /// Returns the errors of [`get_attr()`](Self::get_attr).
+    fn get_attr_ext(&self, _mask: AttrMask) -> VfsResult<VfsNodeAttrExt> {
+        self.get_attr().map(VfsNodeAttrExt::from)
     }
 
-    /// Read directory entries into `dirents`, starting from `start_idx`.
+    /// Change the attributes of the node.
     ///
-    /// This method reads directory entries (files and subdirectories) into
-    /// the provided buffer, starting from the specified index. This allows
-    /// for pagination of directory contents.
-    /// The default implementation returns [`AxError::Unsupported`].
+    /// This method implements `chmod`, `chown`, `truncate` and `utimensat`:
+    /// only the attributes set in `attr` are changed, and the change time of
+    /// the node is updated. Permission checks (such as only letting the
+    /// superuser change the owner) are left to the caller. The default
+    /// implementation returns [`AxError::Unsupported`].
     ///
     /// # Arguments
     ///
-    /// * `start_idx` - The starting index for reading entries (for pagination)
-    /// * `dirents` - A mutable slice to store the directory entries
+    /// * `attr` - The attributes to change
     ///
     /// # Returns
     ///
-    /// Returns the number of entries read on success, or an error otherwise.
+    /// Returns `Ok(())` if the attributes were changed, or an error otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::Unsupported`] if called on a non-directory node.
-    fn read_dir(&self, _start_idx: usize, _dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+    /// Returns [`AxError::Unsupported`] if the node does not support this
+    /// operation, or [`AxError::IsADirectory`] if the size of a directory
+    /// is changed.
+    fn set_attr(&self, _attr: &SetAttr) -> VfsResult {
         ax_err!(Unsupported)
     }
 
-    /// Renames or moves existing file or directory.
+    /// Get the generation number of the node.
     ///
-    /// This method renames or moves a node from `src_path` to `dst_path`.
-    /// The operation can be within the same directory or across directories.
-    /// The default implementation returns [`AxError::Unsupported`].
+    /// Together with the inode number, it identifies the node for the
+    /// lifetime of the filesystem: filesystems reusing inode numbers give
+    /// a different generation to each node having the same number, so that
+    /// the stale [`export`] handles of a removed node are not resolved to
+    /// its successor. The default implementation returns `0`, which is
+    /// correct for filesystems that never reuse inode numbers.
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `src_path` - The source path of the node to rename/move
-    /// * `dst_path` - The destination path for the node
+    /// Returns the generation number of the node.
+    fn generation(&self) -> u32 {
+        0
+    }
+
+    /// Get the protection flags of the node.
+    ///
+    /// The default implementation returns [`AxError::Unsupported`].
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if the rename/move operation succeeds, or an error otherwise.
+    /// Returns the [`VfsNodeFlags`] of the node on success, or an error
+    /// otherwise.
     ///
     /// # Errors
     ///
-    /// Returns [`AxError::Unsupported`] if the directory does not support renaming.
-    fn rename(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+    /// Returns [`AxError::Unsupported`] if the node does not support this
+    /// operation.
+    fn get_flags(&self) -> VfsResult<VfsNodeFlags> {
         ax_err!(Unsupported)
     }
 
-    /// Convert `&self` to [`&dyn Any`][1] that can use
-    /// [`Any::downcast_ref`][2].
+    /// Change the protection flags of the node.
     ///
-    /// This method enables downcasting to the concrete type that implements
-    /// this trait. This is useful for accessing implementation-specific methods.
-    /// The default implementation returns `unimplemented!()` and must be
-    /// implemented by all concrete types.
+    /// This method implements `chattr`. The flags are replaced by `flags`,
+    /// which is allowed even if the node is immutable, so that the flag can
+    /// be cleared. Permission checks (only the superuser may change these
+    /// flags) are left to the caller. The default implementation returns
+    /// [`AxError::Unsupported`].
+    ///
+    /// # Arguments
+    ///
+    /// * `flags` - The new flags of the node
     ///
     /// # Returns
     ///
-    /// Returns a reference to the node as `&dyn Any`.
+    /// Returns `Ok(())` if the flags were changed, or an error otherwise.
     ///
-    /// [1]: core::any::Any
-    /// [2]: core::any::Any#method.downcast_ref
-    fn as_any(&self) -> &dyn core::any::Any {
-        unimplemented!()
-    }
-}
+    /// # Errors
+    ///
+    /// Returns [`AxError::Unsupported`] if the node does not support this
+    /// operation.
+    fn set_flags(&self, _flags: VfsNodeFlags) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    // file operations:
+
+    /// Read data from the file at the given offset.
+    ///
+    /// This method reads up to `buf.len()` bytes from the file starting at
+    /// `offset`. The actual number of bytes read is returned.
+    /// The default implementation returns [`AxError::IsADirectory`] if the node
+    /// is a directory, and [`AxError::InvalidInput`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start reading from
+    /// * `buf` - The buffer to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually read on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::IsADirectory`] if called on a directory.
+    /// Returns [`AxError::InvalidInput`] if called on another non-file node.
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        file_op_default(self, VfsError::InvalidInput)
+    }
+
+    /// Write data to the file at the given offset.
+    ///
+    /// This method writes up to `buf.len()` bytes to the file starting at
+    /// `offset`. The actual number of bytes written is returned.
+    /// The default implementation returns [`AxError::IsADirectory`] if the node
+    /// is a directory, and [`AxError::InvalidInput`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start writing to
+    /// * `buf` - The buffer containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually written on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::IsADirectory`] if called on a directory.
+    /// Returns [`AxError::InvalidInput`] if called on another non-file node.
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        file_op_default(self, VfsError::InvalidInput)
+    }
+
+    /// Read data from the file at the given offset into several buffers.
+    ///
+    /// This is the scatter read of `preadv()`: the buffers are filled in
+    /// order, as if they were one contiguous buffer. The default
+    /// implementation calls [`read_at()`](Self::read_at) for each buffer,
+    /// and stops at the first short read.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start reading from
+    /// * `bufs` - The buffers to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the total number of bytes read on success, or an error if
+    /// nothing could be read.
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        iovec::read_vectored_with(offset, bufs, |off, buf| self.read_at(off, buf))
+    }
+
+    /// Write data to the file at the given offset from several buffers.
+    ///
+    /// This is the gather write of `pwritev()`: the buffers are written in
+    /// order, as if they were one contiguous buffer. The default
+    /// implementation calls [`write_at()`](Self::write_at) for each buffer,
+    /// and stops at the first short write.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start writing to
+    /// * `bufs` - The buffers containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the total number of bytes written on success, or an error if
+    /// nothing could be written.
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        iovec::write_vectored_with(offset, bufs, |off, buf| self.write_at(off, buf))
+    }
+
+    /// Read data from the file at the given offset, bypassing any cache.
+    ///
+    /// This is the read path of handles opened for direct I/O (see
+    /// [`VfsFileHandle::set_direct`](handle::VfsFileHandle::set_direct)),
+    /// whose offset and buffer are aligned to
+    /// [`DIRECT_IO_ALIGN`](handle::DIRECT_IO_ALIGN). Caching layers wrapping
+    /// another node must override it to forward the request directly to the
+    /// backing node. The default implementation calls
+    /// [`read_at()`](Self::read_at).
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start reading from
+    /// * `buf` - The buffer to read data into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually read on success, or an error otherwise.
+    fn read_direct_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.read_at(offset, buf)
+    }
+
+    /// Write data to the file at the given offset, bypassing any cache.
+    ///
+    /// This is the write path of handles opened for direct I/O, see
+    /// [`read_direct_at()`](Self::read_direct_at). The default implementation
+    /// calls [`write_at()`](Self::write_at).
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start writing to
+    /// * `buf` - The buffer containing the data to write
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually written on success, or an error otherwise.
+    fn write_direct_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.write_at(offset, buf)
+    }
+
+    /// Returns the alignment required for direct I/O on the file, in bytes.
+    ///
+    /// The offset, length and buffer address of the requests of handles
+    /// opened for direct I/O must be multiples of it, which
+    /// [`VfsFileHandle`](handle::VfsFileHandle) checks before calling
+    /// [`read_direct_at()`](Self::read_direct_at) and
+    /// [`write_direct_at()`](Self::write_direct_at). Filesystems backed by a
+    /// block device return its logical block size. The default
+    /// implementation returns [`DIRECT_IO_ALIGN`](handle::DIRECT_IO_ALIGN).
+    ///
+    /// # Returns
+    ///
+    /// The alignment, a power of two.
+    fn io_alignment(&self) -> usize {
+        handle::DIRECT_IO_ALIGN
+    }
+
+    /// Returns whether the file can be opened for direct I/O, like
+    /// `O_DIRECT`.
+    ///
+    /// Handles of files that do not support it fail to switch to direct
+    /// I/O, see [`VfsFileHandle::set_direct`](handle::VfsFileHandle::set_direct).
+    /// The default implementation returns `true`, the direct requests going
+    /// to [`read_at()`](Self::read_at) and [`write_at()`](Self::write_at)
+    /// unless [`read_direct_at()`](Self::read_direct_at) and
+    /// [`write_direct_at()`](Self::write_direct_at) are overridden.
+    fn supports_direct_io(&self) -> bool {
+        true
+    }
+
+    /// Finds the next region of data or the next hole of a sparse file.
+    ///
+    /// Holes are ranges that were never written, or were deallocated, and
+    /// read as zeros; copy utilities can skip them instead of reading the
+    /// zeros. A filesystem may report a hole as data, but not the reverse.
+    /// The default implementation reports the whole file as data.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset to search from
+    /// * `hint` - The kind of region to search for
+    ///
+    /// # Returns
+    ///
+    /// Returns the offset of the first byte at or after `offset` in a region
+    /// of the given kind, or `None` if `offset` is at or past the end of
+    /// the file, or there is no data after it (`ENXIO` for `lseek()`).
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        let size = self.get_attr()?.size();
+        if offset >= size {
+            return Ok(None);
+        }
+        Ok(Some(match hint {
+            SeekHint::Data => offset,
+            SeekHint::Hole => size,
+        }))
+    }
+
+    /// Prefetch a range of the file.
+    ///
+    /// This is an advisory operation, issued when the data is expected to be
+    /// read soon (such as by `readahead()` or `posix_fadvise()` with
+    /// `POSIX_FADV_WILLNEED`): caching layers and block-backed filesystems
+    /// can start loading the range, so that later reads do not wait for the
+    /// storage. Errors are not reported for ranges that cannot be
+    /// prefetched. The default implementation does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file of the range
+    /// * `len` - The length of the range in bytes, or `0` for the rest of
+    ///   the file
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the request was accepted, or an error otherwise.
+    fn readahead(&self, _offset: u64, _len: u64) -> VfsResult {
+        Ok(())
+    }
+
+    /// Flush the file, synchronize the data to disk.
+    ///
+    /// This method ensures that all data written to the file is persisted
+    /// to the underlying storage device.
+    /// The default implementation returns [`AxError::IsADirectory`] if the node
+    /// is a directory, and [`AxError::InvalidInput`] otherwise.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if synchronization succeeds, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::IsADirectory`] if called on a directory.
+    /// Returns [`AxError::InvalidInput`] if called on another non-file node.
+    fn fsync(&self) -> VfsResult {
+        file_op_default(self, VfsError::InvalidInput)
+    }
+
+    /// Flush the data of the file, like `fdatasync()`.
+    ///
+    /// This works like [`fsync()`](Self::fsync), except that metadata
+    /// which is not needed to read the data back, such as timestamps, may
+    /// be left unsynchronized. Block-backed filesystems can skip their
+    /// metadata flushes; the size of the file is still persisted.
+    /// The default implementation calls [`fsync()`](Self::fsync).
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if synchronization succeeds, or an error otherwise.
+    fn fsync_data(&self) -> VfsResult {
+        self.fsync()
+    }
+
+    /// Truncate the file to the given size.
+    ///
+    /// If `size` is larger than the current file size, the file is extended
+    /// with zeros. If `size` is smaller, the file is truncated.
+    /// The default implementation returns [`AxError::IsADirectory`] if the node
+    /// is a directory, and [`AxError::InvalidInput`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The new size of the file in bytes
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if truncation succeeds, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::IsADirectory`] if called on a directory.
+    /// Returns [`AxError::InvalidInput`] if called on another non-file node.
+    fn truncate(&self, _size: u64) -> VfsResult {
+        file_op_default(self, VfsError::InvalidInput)
+    }
+
+    /// Allocates or deallocates the storage of a range of the file.
+    ///
+    /// Allocating reserves the storage of the range up front, so that later
+    /// writes to it cannot fail for lack of space. With
+    /// [`FallocateMode::PUNCH_HOLE`], the storage of the range is released
+    /// instead and the range reads back as zeros.
+    /// The default implementation returns [`AxError::IsADirectory`] if the node
+    /// is a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file of the range
+    /// * `len` - The length of the range in bytes, must not be `0`
+    /// * `mode` - The operation to perform
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::IsADirectory`] if called on a directory.
+    /// Returns [`AxError::InvalidInput`] if `len` is `0`, or if
+    /// [`FallocateMode::PUNCH_HOLE`] is given without
+    /// [`FallocateMode::KEEP_SIZE`].
+    /// Returns [`AxError::Unsupported`] if the node does not support the
+    /// operation.
+    fn fallocate(&self, _offset: u64, _len: u64, _mode: FallocateMode) -> VfsResult {
+        file_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Copies a range of this file into another file, like
+    /// `copy_file_range()`.
+    ///
+    /// The default implementation reads and writes the range through a
+    /// buffer, see [`copy::copy_range`]. Filesystems can override it to copy
+    /// the data without the buffer, or to share it between the files.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_off` - The offset in this file of the range to copy
+    /// * `dst` - The file to copy into, which may be this file
+    /// * `dst_off` - The offset in `dst` to copy the range at
+    /// * `len` - The length of the range in bytes
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes copied, fewer than `len` if the end of
+    /// this file is reached, or `0` if `src_off` is at or past its end.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::InvalidInput`] if `dst` is this file and the
+    /// ranges overlap, or the errors of [`read_at()`](Self::read_at) and
+    /// [`write_at()`](Self::write_at).
+    fn copy_file_range(
+        &self,
+        src_off: u64,
+        dst: &dyn VfsNodeOps,
+        dst_off: u64,
+        len: usize,
+    ) -> VfsResult<usize> {
+        copy::copy_range(self, src_off, dst, dst_off, len)
+    }
+
+    /// Send a device-specific control command to the node.
+    ///
+    /// This method implements `ioctl()`, mostly for device nodes (such as
+    /// `TIOCGWINSZ` on a console). The meaning of `arg` depends on `cmd`: it
+    /// is an integer or the address of a buffer in the caller's address
+    /// space, which the node must access with the appropriate care. The
+    /// default implementation returns [`AxError::NotATty`], the `ENOTTY`
+    /// reported by Linux for unknown commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - The command number
+    /// * `arg` - The argument of the command
+    ///
+    /// # Returns
+    ///
+    /// Returns the command-specific result on success, or an error
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotATty`] if the node does not support `cmd`.
+    fn ioctl(&self, _cmd: u32, _arg: usize) -> VfsResult<usize> {
+        ax_err!(NotATty)
+    }
+
+    /// Get the readiness of the node, like `poll()`.
+    ///
+    /// Nodes whose operations can fail with [`AxError::WouldBlock`] report
+    /// whether they can currently be read or written without blocking. The
+    /// default implementation reports the node always ready for both, as
+    /// regular files are.
+    ///
+    /// # Returns
+    ///
+    /// Returns the events currently set, see [`PollEvents`].
+    fn poll(&self) -> VfsResult<PollEvents> {
+        Ok(PollEvents::IN | PollEvents::OUT)
+    }
+
+    /// Register a waker to be woken when the readiness of the node changes.
+    ///
+    /// The waker is woken once, when one of `events`, or an error or hang
+    /// up, is set, and must be registered again to wait again. Nodes that
+    /// can block keep their wakers in a [`WakerSet`](poll::WakerSet). The
+    /// default implementation wakes the waker right away, since the
+    /// readiness of nodes that never block does not change.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The events waited for
+    /// * `waker` - The waker to wake
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the waker is registered, or an error otherwise.
+    fn register_waker(&self, _events: PollEvents, waker: &Waker) -> VfsResult {
+        waker.wake_by_ref();
+        Ok(())
+    }
+
+    /// Read data from the file at the given offset, waiting for it unless
+    /// `hint` is [`ReadHint::NonBlocking`](poll::ReadHint::NonBlocking).
+    ///
+    /// Nodes whose data arrives over time, such as terminals and pipes,
+    /// fail [`read_at()`](Self::read_at) with [`AxError::WouldBlock`] while
+    /// there is none, for callers that poll them. Blocking callers, such as
+    /// [`VfsFileHandle`](handle::VfsFileHandle) without
+    /// [`OpenFlags::NONBLOCK`], read with [`ReadHint::Blocking`] instead.
+    /// The default implementation calls [`read_at()`](Self::read_at), and
+    /// for a blocking read waits with [`poll::block_on`] until the node
+    /// becomes readable, as long as it would block. Nodes that can wait
+    /// more efficiently override it.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the file to start reading from
+    /// * `buf` - The buffer to read data into
+    /// * `hint` - Whether the read may wait
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes actually read on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::WouldBlock`] if `hint` is
+    /// [`ReadHint::NonBlocking`](poll::ReadHint::NonBlocking) and no data is
+    /// available, and the errors of [`read_at()`](Self::read_at),
+    /// [`poll()`](Self::poll) and [`register_waker()`](Self::register_waker).
+    fn read_hinted(&self, offset: u64, buf: &mut [u8], hint: ReadHint) -> VfsResult<usize> {
+        loop {
+            match self.read_at(offset, buf) {
+                Err(AxError::WouldBlock) if hint == ReadHint::Blocking => {
+                    poll::block_on(poll::ready(self, PollEvents::IN))?;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Get a page of the file to map in memory.
+    ///
+    /// This method implements `mmap()`: the kernel maps each page of the
+    /// mapped range with the [`VfsPage`] returned for its offset, see the
+    /// [`page`] module. The default implementation returns
+    /// [`AxError::NoSuchDevice`], the `ENODEV` reported by Linux for files
+    /// that cannot be mapped.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset of the page in the file, a multiple of
+    ///   [`PAGE_SIZE`](page::PAGE_SIZE)
+    ///
+    /// # Returns
+    ///
+    /// Returns the page on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NoSuchDevice`] if the node cannot be mapped, or
+    /// [`AxError::InvalidInput`] if `offset` is not aligned.
+    fn get_page(&self, _offset: u64) -> VfsResult<VfsPage> {
+        ax_err!(NoSuchDevice)
+    }
+
+    /// Read the target of a symbolic link into `buf`.
+    ///
+    /// The target is not NUL-terminated, and is truncated if `buf` is too
+    /// small. The default implementation returns [`AxError::InvalidInput`],
+    /// as the node is not a symbolic link.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The buffer to read the target into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes read on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::InvalidInput`] if the node is not a symbolic link.
+    fn read_link(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        ax_err!(InvalidInput)
+    }
+
+    /// Get whether a filesystem is mounted on this node.
+    ///
+    /// The flag is set by the mount manager with
+    /// [`set_mount_point()`](Self::set_mount_point), so that path
+    /// resolution can detect mount points without looking paths up in the
+    /// mount table, see [`lookup_step()`](Self::lookup_step). The default
+    /// implementation returns `false`.
+    fn is_mount_point(&self) -> bool {
+        false
+    }
+
+    /// Set or clear the flag telling that a filesystem is mounted on this
+    /// node.
+    ///
+    /// Filesystems supporting it refuse to remove a mount point with
+    /// [`AxError::ResourceBusy`]. The default implementation returns
+    /// [`AxError::Unsupported`].
+    ///
+    /// # Arguments
+    ///
+    /// * `mounted` - Whether a filesystem is mounted on the node
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::Unsupported`] if the node cannot be a mount point.
+    fn set_mount_point(&self, _mounted: bool) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    // directory operations:
+
+    /// Get the parent directory of this directory.
+    ///
+    /// This method returns a reference to the parent directory of the current node.
+    /// The default implementation returns `None`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(VfsNodeRef)` if the node has a parent directory,
+    /// or `None` if the node has no parent (e.g., it's a file or root directory).
+    fn parent(&self) -> Option<VfsNodeRef> {
+        None
+    }
+
+    /// Lookup the node with given `path` in the directory.
+    ///
+    /// This method searches for a node with the specified relative path within
+    /// the directory. If found, it returns a reference to the node.
+    /// The default implementation returns [`AxError::NotADirectory`] if the
+    /// node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The relative path to look up (e.g., "subdir/file.txt")
+    ///
+    /// # Returns
+    ///
+    /// Returns a [`VfsNodeRef`] to the found node, or an error if not found.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support lookup,
+    /// or [`AxError::NotFound`] if the path does not exist.
+    fn lookup(self: Arc<Self>, _path: &str) -> VfsResult<VfsNodeRef> {
+        dir_op_default(&*self, VfsError::Unsupported)
+    }
+
+    /// Lookup the given `path` until it reaches a mount point.
+    ///
+    /// The [`components`](path::Path::components) of `path` are resolved
+    /// one by one, and the walk stops after entering a node that
+    /// [`is_mount_point()`](Self::is_mount_point), or before a `..` at a
+    /// node without [`parent()`](Self::parent). A mount manager thus
+    /// resolves a path across filesystems by calling this method on the
+    /// root of the mounted filesystem, with the components that are left.
+    /// The default implementation calls [`path::lookup_step`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The relative path to lookup
+    ///
+    /// # Returns
+    ///
+    /// Returns the node reached and the number of components consumed, all
+    /// of them if no mount point was crossed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`lookup()`](Self::lookup).
+    fn lookup_step(self: Arc<Self>, path: &str) -> VfsResult<(VfsNodeRef, usize)> {
+        path::lookup_step(self, path)
+    }
+
+    /// Create a new node with the given `path` in the directory.
+    ///
+    /// This method creates a new file or directory with the specified path.
+    /// If the node already exists, the method returns `Ok(())`.
+    /// The default implementation returns [`AxError::NotADirectory`] if the
+    /// node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path for the new node
+    /// * `ty` - The type of node to create (file or directory)
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was created or already exists,
+    /// or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support creation.
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Create a new node with the given `path` in the directory, failing if
+    /// it already exists.
+    ///
+    /// This is the creation of `open()` with `O_CREAT | O_EXCL`: the check
+    /// and the creation are atomic, so that a single caller creates the
+    /// node. The default implementation returns [`AxError::NotADirectory`] if
+    /// the node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path for the new node
+    /// * `ty` - The type of node to create (file or directory)
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::AlreadyExists`] if a node exists at `path`,
+    /// including when it ends with `.` or `..`, or
+    /// [`AxError::Unsupported`] if the directory does not support it.
+    fn create_exclusive(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Lookup the node with given `path` in the directory, on behalf of the
+    /// caller of `ctx`.
+    ///
+    /// Filesystems enforcing permissions check that the caller may search
+    /// every directory walked. The default implementation ignores `ctx` and
+    /// calls [`lookup()`](Self::lookup).
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context of the caller, or `None` to skip the checks
+    /// * `path` - The relative path to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns a [`VfsNodeRef`] to the found node, or an error if not found.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`lookup()`](Self::lookup), or
+    /// [`AxError::PermissionDenied`] if a directory may not be searched.
+    fn lookup_ctx(self: Arc<Self>, _ctx: Option<&VfsContext>, path: &str) -> VfsResult<VfsNodeRef> {
+        self.lookup(path)
+    }
+
+    /// Create a new node with the given `path` in the directory, on behalf
+    /// of the caller of `ctx`.
+    ///
+    /// Filesystems enforcing permissions check that the caller may write
+    /// the parent directory, and give the new node to the caller, with the
+    /// umask applied. The default implementation ignores `ctx` and calls
+    /// [`create()`](Self::create).
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context of the caller, or `None` to skip the checks
+    /// * `path` - The path for the new node
+    /// * `ty` - The type of node to create (file or directory)
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was created or already exists,
+    /// or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`create()`](Self::create), or
+    /// [`AxError::PermissionDenied`] if the parent directory may not be
+    /// written.
+    fn create_ctx(&self, _ctx: Option<&VfsContext>, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.create(path, ty)
+    }
+
+    /// Create a device node at `path`.
+    ///
+    /// Unlike the nodes of a device filesystem, such a node only records the
+    /// device number, and its operations are forwarded to the device
+    /// registered under that number, if any. The default implementation returns
+    /// [`AxError::NotADirectory`] if the node is not a directory, and
+    /// [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the new node
+    /// * `ty` - The type of the node, [`VfsNodeType::CharDevice`] or
+    ///   [`VfsNodeType::BlockDevice`]
+    /// * `dev` - The device number
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support
+    /// device nodes, [`AxError::InvalidInput`] if `ty` is not a device type,
+    /// or [`AxError::AlreadyExists`] if `path` exists.
+    fn mknod(&self, _path: &str, _ty: VfsNodeType, _dev: DeviceId) -> VfsResult {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Create a symbolic link at `path` in the directory, pointing to
+    /// `target`.
+    ///
+    /// The target is stored as is: it is neither resolved nor checked for
+    /// existence. The default implementation returns [`AxError::NotADirectory`]
+    /// if the node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the new link
+    /// * `target` - The path the link points to
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the link was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support
+    /// symbolic links, or [`AxError::AlreadyExists`] if `path` exists.
+    fn create_symlink(&self, _path: &str, _target: &str) -> VfsResult {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Remove the node with the given `path` in the directory.
+    ///
+    /// This method removes a file or directory at the specified path.
+    /// The default implementation returns [`AxError::NotADirectory`] if the
+    /// node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the node to remove
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was removed successfully, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support removal.
+    fn remove(&self, _path: &str) -> VfsResult {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Remove the node with the given `path` in the directory, on behalf of
+    /// the caller of `ctx`.
+    ///
+    /// Filesystems enforcing permissions check that the caller may write
+    /// the parent directory. The default implementation ignores `ctx` and
+    /// calls [`remove()`](Self::remove).
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context of the caller, or `None` to skip the checks
+    /// * `path` - The path of the node to remove
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the node was removed successfully, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`remove()`](Self::remove), or
+    /// [`AxError::PermissionDenied`] if the parent directory may not be
+    /// written.
+    fn remove_ctx(&self, _ctx: Option<&VfsContext>, path: &str) -> VfsResult {
+        self.remove(path)
+    }
+
+    /// Read directory entries into `dirents`, starting from `start_idx`.
+    ///
+    /// This method reads directory entries (files and subdirectories) into
+    /// the provided buffer, starting from the specified index. This allows
+    /// for pagination of directory contents.
+    /// The default implementation returns [`AxError::NotADirectory`] if the
+    /// node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_idx` - The starting index for reading entries (for pagination)
+    /// * `dirents` - A mutable slice to store the directory entries
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of entries read on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support
+    /// reading its entries.
+    fn read_dir(&self, _start_idx: usize, _dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Read the directory entries passing the filters of `opts`, in the
+    /// order it selects.
+    ///
+    /// This works like [`read_dir()`](Self::read_dir), except that
+    /// `start_idx` counts the matching entries only. Filesystems able to
+    /// filter or sort natively should override it, so large directories
+    /// need not be transferred entirely. The default implementation reads
+    /// the entries with [`read_dir()`](Self::read_dir) and filters them in
+    /// memory, up to the last requested one in the native order, or all of
+    /// them to sort by name.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_idx` - The index of the first matching entry to read
+    /// * `dirents` - A mutable slice to store the directory entries
+    /// * `opts` - The filters and order of the entries
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of entries read on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`read_dir()`](Self::read_dir).
+    fn read_dir_opts(
+        &self,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+        opts: &ReadDirOptions,
+    ) -> VfsResult<usize> {
+        readdir::read_dir_opts_fallback(self, start_idx, dirents, opts)
+    }
+
+    /// Serialize as many directory entries as fit into `buf`, starting from
+    /// `cursor`.
+    ///
+    /// Entries are written as Linux `struct linux_dirent64` records, so that
+    /// a `getdents64` syscall is served by a single call with the user
+    /// buffer. Pass `0` to start from the first entry, then the returned
+    /// cursor to continue. The `d_off` field of each record is the cursor
+    /// following it. The default implementation reads the entries with
+    /// [`read_dir()`](Self::read_dir), using their position as `d_ino` if
+    /// their inode number is unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `cursor` - The position of the first entry to serialize
+    /// * `buf` - The buffer to serialize the entries into
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes written and the cursor of the next entry
+    /// on success. Zero bytes are written at the end of the directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::InvalidInput`] if `buf` is too small to hold the
+    /// next entry, or the errors of [`read_dir()`](Self::read_dir).
+    fn read_dir_buf(&self, cursor: u64, buf: &mut [u8]) -> VfsResult<(usize, u64)> {
+        readdir::read_dir_buf_fallback(self, cursor, buf)
+    }
+
+    /// Open a stream over the directory entries.
+    ///
+    /// The position of the returned [`DirStream`] is an opaque
+    /// [`DirCookie`], which is not disturbed by entries being added or
+    /// removed between calls, unlike the index of
+    /// [`read_dir()`](Self::read_dir). The default implementation reads the
+    /// entries with [`read_dir()`](Self::read_dir), using their index as
+    /// the cookie, so filesystems able to do better should override it.
+    ///
+    /// # Returns
+    ///
+    /// Returns a stream starting at the first entry on success, or an error
+    /// otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node
+    /// implemented with [`impl_vfs_non_dir_default!`].
+    fn open_dir(self: Arc<Self>) -> VfsResult<Box<dyn DirStream>>
+    where
+        Self: 'static,
+    {
+        Ok(Box::new(readdir::IndexDirStream::new(self)))
+    }
+
+    /// Create a hard link at `dst_path` to the existing node at `src_path`.
+    ///
+    /// Both paths are relative to this directory. After the call, both
+    /// entries refer to the same node, whose link count is incremented.
+    /// The default implementation returns [`AxError::NotADirectory`] if the
+    /// node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The path of the existing node
+    /// * `dst_path` - The path of the new entry
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the link was created, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support hard
+    /// links, [`AxError::AlreadyExists`] if `dst_path` exists, or
+    /// [`AxError::OperationNotPermitted`] if the node cannot be linked (such
+    /// as a directory).
+    fn link(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Create a regular file that has no name, like `O_TMPFILE`.
+    ///
+    /// The file belongs to the filesystem of this directory, but cannot be
+    /// looked up, and is freed when its last reference is dropped, unless
+    /// it is given a name with [`link_into()`](Self::link_into) before.
+    /// Temporary files thus never appear in the directory while being
+    /// written, nor leak a name if the writer fails.
+    /// The default implementation returns [`AxError::NotADirectory`] if the
+    /// node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Returns
+    ///
+    /// Returns the new file on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node,
+    /// or [`AxError::Unsupported`] if the filesystem does not support it.
+    fn create_tmpfile(&self) -> VfsResult<VfsNodeRef> {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Add an entry referring to this node in `dir`, like `linkat()` with
+    /// `AT_EMPTY_PATH`.
+    ///
+    /// This gives a name to a file created with
+    /// [`create_tmpfile()`](Self::create_tmpfile), or adds a hard link to a
+    /// file that has names already. A file that has lost all its names
+    /// cannot be linked again, except a temporary file that was never
+    /// linked. The default implementation returns [`AxError::Unsupported`].
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to add the entry in
+    /// * `name` - The path of the new entry, relative to `dir`
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the entry was added, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::AlreadyExists`] if `name` exists,
+    /// [`AxError::NotFound`] if the file has lost all its names,
+    /// [`AxError::CrossesDevices`] if `dir` belongs to another filesystem,
+    /// or [`AxError::OperationNotPermitted`] if the node cannot be linked.
+    fn link_into(self: Arc<Self>, _dir: &VfsNodeRef, _name: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Renames or moves existing file or directory.
+    ///
+    /// This method renames or moves a node from `src_path` to `dst_path`.
+    /// The operation can be within the same directory or across directories.
+    /// The default implementation returns [`AxError::NotADirectory`] if the
+    /// node is not a directory, and [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The source path of the node to rename/move
+    /// * `dst_path` - The destination path for the node
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the rename/move operation succeeds, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AxError::NotADirectory`] if called on a non-directory node.
+    /// Returns [`AxError::Unsupported`] if the directory does not support renaming.
+    fn rename(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        dir_op_default(self, VfsError::Unsupported)
+    }
+
+    /// Renames or moves a node, with the options of `flags`.
+    ///
+    /// This is the `renameat2()` counterpart of
+    /// [`rename()`](Self::rename): [`RenameFlags::NOREPLACE`] keeps an
+    /// existing destination, and [`RenameFlags::EXCHANGE`] swaps the two
+    /// nodes. Both are atomic. The default implementation calls
+    /// [`rename()`](Self::rename) without flags, and returns
+    /// [`AxError::Unsupported`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `src_path` - The source path of the node to rename/move
+    /// * `dst_path` - The destination path for the node
+    /// * `flags` - The options of the rename
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the rename/move operation succeeds, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`rename()`](Self::rename), or:
+    /// - [`AxError::InvalidInput`] if both flags are set.
+    /// - [`AxError::AlreadyExists`] if `flags` contains
+    ///   [`RenameFlags::NOREPLACE`] and `dst_path` exists.
+    /// - [`AxError::NotFound`] if `flags` contains
+    ///   [`RenameFlags::EXCHANGE`] and `dst_path` does not exist.
+    /// - [`AxError::Unsupported`] if the directory does not support `flags`.
+    fn rename_flags(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult {
+        if flags.contains(RenameFlags::NOREPLACE | RenameFlags::EXCHANGE) {
+            return ax_err!(InvalidInput);
+        }
+        if !flags.is_empty() {
+            return ax_err!(Unsupported);
+        }
+        self.rename(src_path, dst_path)
+    }
+
+    /// Convert `&self` to [`&dyn Any`][1] that can use
+    /// [`Any::downcast_ref`][2].
+    ///
+    /// This method enables downcasting to the concrete type that implements
+    /// this trait. This is useful for accessing implementation-specific methods,
+    /// prefer the typed helpers of [`VfsNodeRefExt`] over calling it directly.
+    /// The default implementation returns `unimplemented!()` and must be
+    /// implemented by all concrete types.
+    ///
+    /// # Returns
+    ///
+    /// Returns a reference to the node as `&dyn Any`.
+    ///
+    /// [1]: core::any::Any
+    /// [2]: core::any::Any#method.downcast_ref
+    fn as_any(&self) -> &dyn core::any::Any {
+        unimplemented!()
+    }
+
+    /// Convert `Arc<Self>` to [`Arc<dyn Any>`][1] that can use
+    /// [`Arc::downcast`][2], keeping the ownership of the node.
+    ///
+    /// Unlike [`as_any()`](Self::as_any), the result can be turned back into
+    /// an `Arc` of the concrete type, which outlives the borrow of the node.
+    /// [`VfsNodeRefExt::downcast`] is usually more convenient. The
+    /// `impl_vfs_*_default!` macros implement it for concrete types; the
+    /// default implementation returns `None`, so the nodes that do not
+    /// support it cannot be downcast, instead of panicking, and
+    /// [`VfsNodeRefExt::downcast`] fails with [`AxError::Unsupported`].
+    ///
+    /// Nodes that do not use the macros opt in by returning themselves:
+    ///
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use axfs_vfs::VfsNodeOps;
+    /// struct MyNode;
+    ///
+    /// impl VfsNodeOps for MyNode {
+    ///     fn as_any_arc(self: Arc<Self>) -> Option<Arc<dyn core::any::Any + Send + Sync>> {
+    ///         Some(self)
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// Returns the node as `Arc<dyn Any + Send + Sync>`, or `None` if the
+    /// node does not support owning downcasts.
+    ///
+    /// [1]: core::any::Any
+    /// [2]: alloc::sync::Arc#method.downcast
+    fn as_any_arc(self: Arc<Self>) -> Option<Arc<dyn core::any::Any + Send + Sync>> {
+        None
+    }
+}
 
 #[doc(hidden)]
 pub mod __priv {
@@ -450,6 +1897,10 @@ pub mod __priv {
     //! the declarative macros in this crate, but are not part of the
     //! public API.
 
+    pub use alloc::boxed::Box;
     pub use alloc::sync::Arc;
     pub use axerrno::ax_err;
+    pub use log::debug;
+
+    pub use crate::readdir::{read_dir_buf_fallback, read_dir_opts_fallback, IndexDirStream};
 }
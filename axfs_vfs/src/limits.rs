@@ -0,0 +1,114 @@
+//! Limits on path and name lengths.
+//!
+//! The limits are chosen at build time. By default they match Linux, so that
+//! applications see the usual values. The `small-limits` feature lowers them
+//! for memory-constrained profiles: directory entries ([`VfsDirEntry`]) then
+//! take 4 times less memory.
+//!
+//! | Constant | Default | `small-limits` |
+//! | --- | --- | --- |
+//! | [`MAX_NAME_LEN`] | 255 | 63 |
+//! | [`MAX_PATH`] | 4096 | 1024 |
+//! | [`MAX_SYMLINK_DEPTH`] | 40 | 8 |
+//!
+//! [`VfsDirEntry`]: crate::VfsDirEntry
+
+use crate::{VfsError, VfsResult};
+
+/// The maximum length of a path component (a file name), in bytes.
+pub const MAX_NAME_LEN: usize = if cfg!(feature = "small-limits") {
+    63
+} else {
+    255
+};
+
+/// The maximum length of a path, in bytes.
+pub const MAX_PATH: usize = if cfg!(feature = "small-limits") {
+    1024
+} else {
+    4096
+};
+
+/// The maximum number of symbolic links followed while resolving a path.
+pub const MAX_SYMLINK_DEPTH: usize = if cfg!(feature = "small-limits") {
+    8
+} else {
+    40
+};
+
+/// Checks that a file name fits in [`MAX_NAME_LEN`].
+///
+/// # Arguments
+///
+/// * `name` - The file name to check
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the name is short enough.
+///
+/// # Errors
+///
+/// Returns [`VfsError::NameTooLong`] if the name is longer than
+/// [`MAX_NAME_LEN`] bytes.
+pub fn check_name(name: &str) -> VfsResult {
+    if name.len() > MAX_NAME_LEN {
+        return Err(VfsError::NameTooLong);
+    }
+    Ok(())
+}
+
+/// Checks that a path fits in [`MAX_PATH`], and each of its components in
+/// [`MAX_NAME_LEN`].
+///
+/// # Arguments
+///
+/// * `path` - The path to check
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the path and its components are short enough.
+///
+/// # Errors
+///
+/// Returns [`VfsError::NameTooLong`] if the path is longer than [`MAX_PATH`]
+/// bytes, or one of its components longer than [`MAX_NAME_LEN`] bytes.
+pub fn check_path(path: &str) -> VfsResult {
+    if path.len() > MAX_PATH {
+        return Err(VfsError::NameTooLong);
+    }
+    path.split('/').try_for_each(check_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+
+    #[test]
+    fn test_check_name() {
+        assert_eq!(check_name(""), Ok(()));
+        assert_eq!(check_name(&"a".repeat(MAX_NAME_LEN)), Ok(()));
+        assert_eq!(
+            check_name(&"a".repeat(MAX_NAME_LEN + 1)),
+            Err(VfsError::NameTooLong)
+        );
+    }
+
+    #[test]
+    fn test_check_path() {
+        let name = "a".repeat(MAX_NAME_LEN);
+        assert_eq!(check_path(&alloc::format!("/{name}/{name}/")), Ok(()));
+        assert_eq!(
+            check_path(&alloc::format!("/{name}b/x")),
+            Err(VfsError::NameTooLong)
+        );
+
+        let mut path = String::new();
+        while path.len() + 2 <= MAX_PATH {
+            path.push_str("/a");
+        }
+        assert_eq!(check_path(&path), Ok(()));
+        path.push_str("/a");
+        assert_eq!(check_path(&path), Err(VfsError::NameTooLong));
+    }
+}
@@ -4,7 +4,14 @@
 /// This macro provides default implementations of file operations that return
 /// `AxError::IsADirectory` errors. It should be used when implementing
 /// `VfsNodeOps` for a directory node, as directories do not support file
-/// operations like `read_at`, `write_at`, `fsync`, and `truncate`.
+/// operations like `read_at`, `write_at`, `fsync`, `fsync_data`,
+/// `truncate` and `fallocate`.
+/// `read_link` returns `AxError::InvalidInput`, as a directory is not a
+/// symbolic link.
+///
+/// The defaults of [`VfsNodeOps`] already return these errors for a node
+/// whose [`get_attr()`](crate::VfsNodeOps::get_attr) reports a directory;
+/// the macro also covers directories that do not implement it.
 ///
 /// [`VfsNodeOps`]: crate::VfsNodeOps
 #[macro_export]
@@ -22,14 +29,38 @@ macro_rules! impl_vfs_dir_default {
             $crate::__priv::ax_err!(IsADirectory)
         }
 
+        fn fsync_data(&self) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(IsADirectory)
+        }
+
         fn truncate(&self, _size: u64) -> $crate::VfsResult {
             $crate::__priv::ax_err!(IsADirectory)
         }
 
+        fn fallocate(
+            &self,
+            _offset: u64,
+            _len: u64,
+            _mode: $crate::FallocateMode,
+        ) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(IsADirectory)
+        }
+
+        fn read_link(&self, _buf: &mut [u8]) -> $crate::VfsResult<usize> {
+            $crate::__priv::ax_err!(InvalidInput)
+        }
+
         #[inline]
         fn as_any(&self) -> &dyn core::any::Any {
             self
         }
+
+        #[inline]
+        fn as_any_arc(
+            self: $crate::__priv::Arc<Self>,
+        ) -> ::core::option::Option<$crate::__priv::Arc<dyn core::any::Any + Send + Sync>> {
+            ::core::option::Option::Some(self)
+        }
     };
 }
 
@@ -39,7 +70,14 @@ macro_rules! impl_vfs_dir_default {
 /// This macro provides default implementations of directory operations that return
 /// `AxError::NotADirectory` errors. It should be used when implementing
 /// `VfsNodeOps` for a non-directory node (e.g., a file or device), as these nodes
-/// do not support directory operations like `lookup`, `create`, `remove`, and `read_dir`.
+/// do not support directory operations like `lookup`, `create`,
+/// `create_exclusive`, `mknod`,
+/// `create_symlink`, `link`, `create_tmpfile`, `remove`, `read_dir`,
+/// `open_dir` and `rename`.
+///
+/// The defaults of [`VfsNodeOps`] already return these errors for a node
+/// whose [`get_attr()`](crate::VfsNodeOps::get_attr) reports its type; the
+/// macro also covers nodes that do not implement it.
 ///
 /// [`VfsNodeOps`]: crate::VfsNodeOps
 #[macro_export]
@@ -56,6 +94,31 @@ macro_rules! impl_vfs_non_dir_default {
             $crate::__priv::ax_err!(NotADirectory)
         }
 
+        fn create_exclusive(&self, _path: &str, _ty: $crate::VfsNodeType) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
+        fn mknod(
+            &self,
+            _path: &str,
+            _ty: $crate::VfsNodeType,
+            _dev: $crate::DeviceId,
+        ) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
+        fn create_symlink(&self, _path: &str, _target: &str) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
+        fn link(&self, _src_path: &str, _dst_path: &str) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
+        fn create_tmpfile(&self) -> $crate::VfsResult<$crate::VfsNodeRef> {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
         fn remove(&self, _path: &str) -> $crate::VfsResult {
             $crate::__priv::ax_err!(NotADirectory)
         }
@@ -68,9 +131,997 @@ macro_rules! impl_vfs_non_dir_default {
             $crate::__priv::ax_err!(NotADirectory)
         }
 
+        fn open_dir(
+            self: $crate::__priv::Arc<Self>,
+        ) -> $crate::VfsResult<$crate::__priv::Box<dyn $crate::DirStream>> {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
+        fn rename(&self, _src_path: &str, _dst_path: &str) -> $crate::VfsResult {
+            $crate::__priv::ax_err!(NotADirectory)
+        }
+
+        #[inline]
+        fn as_any(&self) -> &dyn core::any::Any {
+            self
+        }
+
+        #[inline]
+        fn as_any_arc(
+            self: $crate::__priv::Arc<Self>,
+        ) -> ::core::option::Option<$crate::__priv::Arc<dyn core::any::Any + Send + Sync>> {
+            ::core::option::Option::Some(self)
+        }
+    };
+}
+
+/// When implement [`VfsNodeOps`] on a node wrapping another one, forward the
+/// operations to the wrapped node.
+///
+/// The field `$inner` of the wrapper holds the wrapped node, as an [`Arc`]
+/// of a type implementing [`VfsNodeOps`], such as a [`VfsNodeRef`]. All the
+/// operations are forwarded to it, except the ones listed after `except`,
+/// which the wrapper implements itself, and
+/// [`on_last_release()`](crate::VfsNodeOps::on_last_release), which is only
+/// called on the wrapped node. `as_any()` and `as_any_arc()` return the
+/// wrapper.
+///
+/// The nodes returned by the forwarded operations, such as the result of
+/// `lookup()`, are not wrapped: a wrapper covering the nodes it returns
+/// lists `parent`, `lookup`, `lookup_step`, `lookup_ctx` and
+/// `create_tmpfile` and wraps them itself.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use axfs_vfs::{impl_vfs_forward, VfsNodeOps, VfsNodeRef, VfsResult};
+///
+/// /// Counts the reads of a node.
+/// struct Counted {
+///     inner: VfsNodeRef,
+///     reads: AtomicUsize,
+/// }
+///
+/// impl VfsNodeOps for Counted {
+///     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+///         self.reads.fetch_add(1, Ordering::Relaxed);
+///         self.inner.read_at(offset, buf)
+///     }
+///
+///     impl_vfs_forward! { inner; except read_at }
+/// }
+/// ```
+///
+/// [`VfsNodeOps`]: crate::VfsNodeOps
+/// [`VfsNodeRef`]: crate::VfsNodeRef
+/// [`Arc`]: alloc::sync::Arc
+#[macro_export]
+macro_rules! impl_vfs_forward {
+    ($inner:ident $(; except $($skip:ident),+ $(,)?)?) => {
+        $crate::__forward_node_ops! {
+            $inner [$($($skip)+)?]
+            open release open_state get_attr set_attr get_attr_ext generation get_flags
+            set_flags read_at write_at read_vectored_at write_vectored_at read_direct_at
+            write_direct_at io_alignment supports_direct_io seek_hint readahead fsync
+            fsync_data truncate fallocate copy_file_range ioctl poll register_waker
+            read_hinted get_page read_link is_mount_point set_mount_point parent lookup
+            lookup_step create create_exclusive lookup_ctx create_ctx mknod create_symlink
+            remove remove_ctx read_dir read_dir_opts read_dir_buf open_dir link
+            create_tmpfile link_into rename rename_flags
+        }
+
         #[inline]
         fn as_any(&self) -> &dyn core::any::Any {
             self
         }
+
+        #[inline]
+        fn as_any_arc(
+            self: $crate::__priv::Arc<Self>,
+        ) -> ::core::option::Option<$crate::__priv::Arc<dyn core::any::Any + Send + Sync>> {
+            ::core::option::Option::Some(self)
+        }
+    };
+}
+
+/// Forwards each of the operations `$op` of [`VfsNodeOps`] to the field
+/// `$inner`, unless it is in the `[]` list of operations to skip.
+///
+/// [`VfsNodeOps`]: crate::VfsNodeOps
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __forward_node_ops {
+    ($inner:ident $skip:tt $($op:ident)*) => {
+        $($crate::__forward_node_op! { $op $inner $skip })*
+    };
+}
+
+/// Forwards the operation `$op` to the field `$inner`, unless it is in the
+/// `[]` list of operations to skip.
+///
+/// Each operation has a rule matching it at the head of the list, where it
+/// is skipped, and a rule for the empty list, where it is forwarded. The
+/// last rule drops the head of the list when it is another operation.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __forward_node_op {
+    (open $f:ident [open $($r:ident)*]) => {};
+    (open $f:ident []) => {
+        fn open(
+            &self,
+            flags: $crate::OpenFlags,
+        ) -> $crate::VfsResult<::core::option::Option<$crate::VfsFileRef>> {
+            self.$f.open(flags)
+        }
+    };
+    (release $f:ident [release $($r:ident)*]) => {};
+    (release $f:ident []) => {
+        fn release(&self) -> $crate::VfsResult {
+            self.$f.release()
+        }
+    };
+    (open_state $f:ident [open_state $($r:ident)*]) => {};
+    (open_state $f:ident []) => {
+        fn open_state(
+            &self,
+        ) -> ::core::option::Option<(&$crate::handle::OpenState, &dyn $crate::VfsNodeOps)> {
+            self.$f.open_state()
+        }
+    };
+    (get_attr $f:ident [get_attr $($r:ident)*]) => {};
+    (get_attr $f:ident []) => {
+        fn get_attr(&self) -> $crate::VfsResult<$crate::VfsNodeAttr> {
+            self.$f.get_attr()
+        }
+    };
+    (set_attr $f:ident [set_attr $($r:ident)*]) => {};
+    (set_attr $f:ident []) => {
+        fn set_attr(&self, attr: &$crate::SetAttr) -> $crate::VfsResult {
+            self.$f.set_attr(attr)
+        }
+    };
+    (get_attr_ext $f:ident [get_attr_ext $($r:ident)*]) => {};
+    (get_attr_ext $f:ident []) => {
+        fn get_attr_ext(
+            &self,
+            mask: $crate::AttrMask,
+        ) -> $crate::VfsResult<$crate::VfsNodeAttrExt> {
+            self.$f.get_attr_ext(mask)
+        }
+    };
+    (generation $f:ident [generation $($r:ident)*]) => {};
+    (generation $f:ident []) => {
+        fn generation(&self) -> u32 {
+            self.$f.generation()
+        }
+    };
+    (get_flags $f:ident [get_flags $($r:ident)*]) => {};
+    (get_flags $f:ident []) => {
+        fn get_flags(&self) -> $crate::VfsResult<$crate::VfsNodeFlags> {
+            self.$f.get_flags()
+        }
+    };
+    (set_flags $f:ident [set_flags $($r:ident)*]) => {};
+    (set_flags $f:ident []) => {
+        fn set_flags(&self, flags: $crate::VfsNodeFlags) -> $crate::VfsResult {
+            self.$f.set_flags(flags)
+        }
+    };
+    (read_at $f:ident [read_at $($r:ident)*]) => {};
+    (read_at $f:ident []) => {
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> $crate::VfsResult<usize> {
+            self.$f.read_at(offset, buf)
+        }
+    };
+    (write_at $f:ident [write_at $($r:ident)*]) => {};
+    (write_at $f:ident []) => {
+        fn write_at(&self, offset: u64, buf: &[u8]) -> $crate::VfsResult<usize> {
+            self.$f.write_at(offset, buf)
+        }
+    };
+    (read_vectored_at $f:ident [read_vectored_at $($r:ident)*]) => {};
+    (read_vectored_at $f:ident []) => {
+        fn read_vectored_at(
+            &self,
+            offset: u64,
+            bufs: &mut [$crate::IoSliceMut],
+        ) -> $crate::VfsResult<usize> {
+            self.$f.read_vectored_at(offset, bufs)
+        }
+    };
+    (write_vectored_at $f:ident [write_vectored_at $($r:ident)*]) => {};
+    (write_vectored_at $f:ident []) => {
+        fn write_vectored_at(
+            &self,
+            offset: u64,
+            bufs: &[$crate::IoSlice],
+        ) -> $crate::VfsResult<usize> {
+            self.$f.write_vectored_at(offset, bufs)
+        }
+    };
+    (read_direct_at $f:ident [read_direct_at $($r:ident)*]) => {};
+    (read_direct_at $f:ident []) => {
+        fn read_direct_at(&self, offset: u64, buf: &mut [u8]) -> $crate::VfsResult<usize> {
+            self.$f.read_direct_at(offset, buf)
+        }
+    };
+    (write_direct_at $f:ident [write_direct_at $($r:ident)*]) => {};
+    (write_direct_at $f:ident []) => {
+        fn write_direct_at(&self, offset: u64, buf: &[u8]) -> $crate::VfsResult<usize> {
+            self.$f.write_direct_at(offset, buf)
+        }
+    };
+    (io_alignment $f:ident [io_alignment $($r:ident)*]) => {};
+    (io_alignment $f:ident []) => {
+        fn io_alignment(&self) -> usize {
+            self.$f.io_alignment()
+        }
+    };
+    (supports_direct_io $f:ident [supports_direct_io $($r:ident)*]) => {};
+    (supports_direct_io $f:ident []) => {
+        fn supports_direct_io(&self) -> bool {
+            self.$f.supports_direct_io()
+        }
+    };
+    (seek_hint $f:ident [seek_hint $($r:ident)*]) => {};
+    (seek_hint $f:ident []) => {
+        fn seek_hint(
+            &self,
+            offset: u64,
+            hint: $crate::SeekHint,
+        ) -> $crate::VfsResult<::core::option::Option<u64>> {
+            self.$f.seek_hint(offset, hint)
+        }
+    };
+    (readahead $f:ident [readahead $($r:ident)*]) => {};
+    (readahead $f:ident []) => {
+        fn readahead(&self, offset: u64, len: u64) -> $crate::VfsResult {
+            self.$f.readahead(offset, len)
+        }
+    };
+    (fsync $f:ident [fsync $($r:ident)*]) => {};
+    (fsync $f:ident []) => {
+        fn fsync(&self) -> $crate::VfsResult {
+            self.$f.fsync()
+        }
+    };
+    (fsync_data $f:ident [fsync_data $($r:ident)*]) => {};
+    (fsync_data $f:ident []) => {
+        fn fsync_data(&self) -> $crate::VfsResult {
+            self.$f.fsync_data()
+        }
+    };
+    (truncate $f:ident [truncate $($r:ident)*]) => {};
+    (truncate $f:ident []) => {
+        fn truncate(&self, size: u64) -> $crate::VfsResult {
+            self.$f.truncate(size)
+        }
+    };
+    (fallocate $f:ident [fallocate $($r:ident)*]) => {};
+    (fallocate $f:ident []) => {
+        fn fallocate(
+            &self,
+            offset: u64,
+            len: u64,
+            mode: $crate::FallocateMode,
+        ) -> $crate::VfsResult {
+            self.$f.fallocate(offset, len, mode)
+        }
+    };
+    (copy_file_range $f:ident [copy_file_range $($r:ident)*]) => {};
+    (copy_file_range $f:ident []) => {
+        fn copy_file_range(
+            &self,
+            src_off: u64,
+            dst: &dyn $crate::VfsNodeOps,
+            dst_off: u64,
+            len: usize,
+        ) -> $crate::VfsResult<usize> {
+            self.$f.copy_file_range(src_off, dst, dst_off, len)
+        }
+    };
+    (ioctl $f:ident [ioctl $($r:ident)*]) => {};
+    (ioctl $f:ident []) => {
+        fn ioctl(&self, cmd: u32, arg: usize) -> $crate::VfsResult<usize> {
+            self.$f.ioctl(cmd, arg)
+        }
+    };
+    (poll $f:ident [poll $($r:ident)*]) => {};
+    (poll $f:ident []) => {
+        fn poll(&self) -> $crate::VfsResult<$crate::PollEvents> {
+            self.$f.poll()
+        }
+    };
+    (register_waker $f:ident [register_waker $($r:ident)*]) => {};
+    (register_waker $f:ident []) => {
+        fn register_waker(
+            &self,
+            events: $crate::PollEvents,
+            waker: &::core::task::Waker,
+        ) -> $crate::VfsResult {
+            self.$f.register_waker(events, waker)
+        }
+    };
+    (read_hinted $f:ident [read_hinted $($r:ident)*]) => {};
+    (read_hinted $f:ident []) => {
+        fn read_hinted(
+            &self,
+            offset: u64,
+            buf: &mut [u8],
+            hint: $crate::ReadHint,
+        ) -> $crate::VfsResult<usize> {
+            self.$f.read_hinted(offset, buf, hint)
+        }
+    };
+    (get_page $f:ident [get_page $($r:ident)*]) => {};
+    (get_page $f:ident []) => {
+        fn get_page(&self, offset: u64) -> $crate::VfsResult<$crate::VfsPage> {
+            self.$f.get_page(offset)
+        }
+    };
+    (read_link $f:ident [read_link $($r:ident)*]) => {};
+    (read_link $f:ident []) => {
+        fn read_link(&self, buf: &mut [u8]) -> $crate::VfsResult<usize> {
+            self.$f.read_link(buf)
+        }
+    };
+    (is_mount_point $f:ident [is_mount_point $($r:ident)*]) => {};
+    (is_mount_point $f:ident []) => {
+        fn is_mount_point(&self) -> bool {
+            self.$f.is_mount_point()
+        }
+    };
+    (set_mount_point $f:ident [set_mount_point $($r:ident)*]) => {};
+    (set_mount_point $f:ident []) => {
+        fn set_mount_point(&self, mounted: bool) -> $crate::VfsResult {
+            self.$f.set_mount_point(mounted)
+        }
+    };
+    (parent $f:ident [parent $($r:ident)*]) => {};
+    (parent $f:ident []) => {
+        fn parent(&self) -> ::core::option::Option<$crate::VfsNodeRef> {
+            self.$f.parent()
+        }
+    };
+    (lookup $f:ident [lookup $($r:ident)*]) => {};
+    (lookup $f:ident []) => {
+        fn lookup(
+            self: $crate::__priv::Arc<Self>,
+            path: &str,
+        ) -> $crate::VfsResult<$crate::VfsNodeRef> {
+            self.$f.clone().lookup(path)
+        }
+    };
+    (lookup_step $f:ident [lookup_step $($r:ident)*]) => {};
+    (lookup_step $f:ident []) => {
+        fn lookup_step(
+            self: $crate::__priv::Arc<Self>,
+            path: &str,
+        ) -> $crate::VfsResult<($crate::VfsNodeRef, usize)> {
+            self.$f.clone().lookup_step(path)
+        }
+    };
+    (create $f:ident [create $($r:ident)*]) => {};
+    (create $f:ident []) => {
+        fn create(&self, path: &str, ty: $crate::VfsNodeType) -> $crate::VfsResult {
+            self.$f.create(path, ty)
+        }
+    };
+    (create_exclusive $f:ident [create_exclusive $($r:ident)*]) => {};
+    (create_exclusive $f:ident []) => {
+        fn create_exclusive(&self, path: &str, ty: $crate::VfsNodeType) -> $crate::VfsResult {
+            self.$f.create_exclusive(path, ty)
+        }
+    };
+    (lookup_ctx $f:ident [lookup_ctx $($r:ident)*]) => {};
+    (lookup_ctx $f:ident []) => {
+        fn lookup_ctx(
+            self: $crate::__priv::Arc<Self>,
+            ctx: ::core::option::Option<&$crate::VfsContext>,
+            path: &str,
+        ) -> $crate::VfsResult<$crate::VfsNodeRef> {
+            self.$f.clone().lookup_ctx(ctx, path)
+        }
+    };
+    (create_ctx $f:ident [create_ctx $($r:ident)*]) => {};
+    (create_ctx $f:ident []) => {
+        fn create_ctx(
+            &self,
+            ctx: ::core::option::Option<&$crate::VfsContext>,
+            path: &str,
+            ty: $crate::VfsNodeType,
+        ) -> $crate::VfsResult {
+            self.$f.create_ctx(ctx, path, ty)
+        }
+    };
+    (mknod $f:ident [mknod $($r:ident)*]) => {};
+    (mknod $f:ident []) => {
+        fn mknod(
+            &self,
+            path: &str,
+            ty: $crate::VfsNodeType,
+            dev: $crate::DeviceId,
+        ) -> $crate::VfsResult {
+            self.$f.mknod(path, ty, dev)
+        }
+    };
+    (create_symlink $f:ident [create_symlink $($r:ident)*]) => {};
+    (create_symlink $f:ident []) => {
+        fn create_symlink(&self, path: &str, target: &str) -> $crate::VfsResult {
+            self.$f.create_symlink(path, target)
+        }
+    };
+    (remove $f:ident [remove $($r:ident)*]) => {};
+    (remove $f:ident []) => {
+        fn remove(&self, path: &str) -> $crate::VfsResult {
+            self.$f.remove(path)
+        }
+    };
+    (remove_ctx $f:ident [remove_ctx $($r:ident)*]) => {};
+    (remove_ctx $f:ident []) => {
+        fn remove_ctx(
+            &self,
+            ctx: ::core::option::Option<&$crate::VfsContext>,
+            path: &str,
+        ) -> $crate::VfsResult {
+            self.$f.remove_ctx(ctx, path)
+        }
+    };
+    (read_dir $f:ident [read_dir $($r:ident)*]) => {};
+    (read_dir $f:ident []) => {
+        fn read_dir(
+            &self,
+            start_idx: usize,
+            dirents: &mut [$crate::VfsDirEntry],
+        ) -> $crate::VfsResult<usize> {
+            self.$f.read_dir(start_idx, dirents)
+        }
+    };
+    (read_dir_opts $f:ident [read_dir_opts $($r:ident)*]) => {};
+    (read_dir_opts $f:ident []) => {
+        fn read_dir_opts(
+            &self,
+            start_idx: usize,
+            dirents: &mut [$crate::VfsDirEntry],
+            opts: &$crate::ReadDirOptions,
+        ) -> $crate::VfsResult<usize> {
+            self.$f.read_dir_opts(start_idx, dirents, opts)
+        }
+    };
+    (read_dir_buf $f:ident [read_dir_buf $($r:ident)*]) => {};
+    (read_dir_buf $f:ident []) => {
+        fn read_dir_buf(&self, cursor: u64, buf: &mut [u8]) -> $crate::VfsResult<(usize, u64)> {
+            self.$f.read_dir_buf(cursor, buf)
+        }
+    };
+    (open_dir $f:ident [open_dir $($r:ident)*]) => {};
+    (open_dir $f:ident []) => {
+        fn open_dir(
+            self: $crate::__priv::Arc<Self>,
+        ) -> $crate::VfsResult<$crate::__priv::Box<dyn $crate::DirStream>> {
+            self.$f.clone().open_dir()
+        }
+    };
+    (link $f:ident [link $($r:ident)*]) => {};
+    (link $f:ident []) => {
+        fn link(&self, src_path: &str, dst_path: &str) -> $crate::VfsResult {
+            self.$f.link(src_path, dst_path)
+        }
+    };
+    (create_tmpfile $f:ident [create_tmpfile $($r:ident)*]) => {};
+    (create_tmpfile $f:ident []) => {
+        fn create_tmpfile(&self) -> $crate::VfsResult<$crate::VfsNodeRef> {
+            self.$f.create_tmpfile()
+        }
+    };
+    (link_into $f:ident [link_into $($r:ident)*]) => {};
+    (link_into $f:ident []) => {
+        fn link_into(
+            self: $crate::__priv::Arc<Self>,
+            dir: &$crate::VfsNodeRef,
+            name: &str,
+        ) -> $crate::VfsResult {
+            self.$f.clone().link_into(dir, name)
+        }
+    };
+    (rename $f:ident [rename $($r:ident)*]) => {};
+    (rename $f:ident []) => {
+        fn rename(&self, src_path: &str, dst_path: &str) -> $crate::VfsResult {
+            self.$f.rename(src_path, dst_path)
+        }
+    };
+    (rename_flags $f:ident [rename_flags $($r:ident)*]) => {};
+    (rename_flags $f:ident []) => {
+        fn rename_flags(
+            &self,
+            src_path: &str,
+            dst_path: &str,
+            flags: $crate::RenameFlags,
+        ) -> $crate::VfsResult {
+            self.$f.rename_flags(src_path, dst_path, flags)
+        }
+    };
+    ($op:ident $f:ident [$skip:ident $($r:ident)*]) => {
+        $crate::__forward_node_op! { $op $f [$($r)*] }
+    };
+}
+
+/// Implements [`VfsNodeOps`] for a type implementing [`VfsFileNodeOps`].
+///
+/// The directory operations return [`AxError::NotADirectory`], as with
+/// [`impl_vfs_non_dir_default!`], and the other operations are forwarded to
+/// [`VfsFileNodeOps`]. Unlike wrapping the node in a
+/// [`FileNodeAdapter`](crate::FileNodeAdapter), the node stays the type a
+/// [`VfsNodeRef`](crate::VfsNodeRef) downcasts to.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{VfsFileNodeOps, VfsNodeAttr, VfsResult};
+///
+/// struct Empty;
+///
+/// impl VfsFileNodeOps for Empty {
+///     fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+///         Ok(VfsNodeAttr::new_file(0, 0))
+///     }
+/// }
+///
+/// axfs_vfs::impl_vfs_file_node!(Empty);
+/// ```
+///
+/// [`VfsNodeOps`]: crate::VfsNodeOps
+/// [`VfsFileNodeOps`]: crate::VfsFileNodeOps
+/// [`AxError::NotADirectory`]: crate::VfsError::NotADirectory
+#[macro_export]
+macro_rules! impl_vfs_file_node {
+    ($ty:ty) => {
+        impl $crate::VfsNodeOps for $ty {
+            $crate::__forward_file_node_ops!($ty, self => self, self.clone());
+            $crate::impl_vfs_non_dir_default! {}
+        }
+    };
+}
+
+/// Implements [`VfsNodeOps`] for a type implementing [`VfsDirNodeOps`].
+///
+/// The file operations return [`AxError::IsADirectory`], as with
+/// [`impl_vfs_dir_default!`], and the other operations are forwarded to
+/// [`VfsDirNodeOps`]. Unlike wrapping the node in a
+/// [`DirNodeAdapter`](crate::DirNodeAdapter), the node stays the type a
+/// [`VfsNodeRef`](crate::VfsNodeRef) downcasts to.
+///
+/// [`VfsNodeOps`]: crate::VfsNodeOps
+/// [`VfsDirNodeOps`]: crate::VfsDirNodeOps
+/// [`AxError::IsADirectory`]: crate::VfsError::IsADirectory
+#[macro_export]
+macro_rules! impl_vfs_dir_node {
+    ($ty:ty) => {
+        impl $crate::VfsNodeOps for $ty {
+            $crate::__forward_dir_node_ops!($ty, self => self, self.clone());
+            $crate::impl_vfs_dir_default! {}
+        }
+    };
+}
+
+/// Forwards the operations of [`VfsNodeOps`] that are not directory
+/// operations to the [`VfsFileNodeOps`] of `$ty`.
+///
+/// `$this` is the `self` of the generated methods, `$node` the `&$ty` to
+/// forward to, and `$arc` the `Arc<$ty>` for the methods taking an [`Arc`].
+/// The passed `self` keeps the three in the same hygiene context.
+///
+/// [`VfsNodeOps`]: crate::VfsNodeOps
+/// [`VfsFileNodeOps`]: crate::VfsFileNodeOps
+/// [`Arc`]: alloc::sync::Arc
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __forward_file_node_ops {
+    ($ty:ty, $this:ident => $node:expr, $arc:expr) => {
+        fn open(
+            &$this,
+            flags: $crate::OpenFlags,
+        ) -> $crate::VfsResult<::core::option::Option<$crate::VfsFileRef>> {
+            <$ty as $crate::VfsFileNodeOps>::open($node, flags)
+        }
+
+        fn release(&$this) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::release($node)
+        }
+
+        fn on_last_release(&$this) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::on_last_release($node)
+        }
+
+        fn open_state(
+            &$this,
+        ) -> ::core::option::Option<(&$crate::handle::OpenState, &dyn $crate::VfsNodeOps)> {
+            ::core::option::Option::Some((
+                <$ty as $crate::VfsFileNodeOps>::open_state($node)?,
+                $this,
+            ))
+        }
+
+        fn get_attr(&$this) -> $crate::VfsResult<$crate::VfsNodeAttr> {
+            <$ty as $crate::VfsFileNodeOps>::get_attr($node)
+        }
+
+        fn get_attr_ext(
+            &$this,
+            mask: $crate::AttrMask,
+        ) -> $crate::VfsResult<$crate::VfsNodeAttrExt> {
+            <$ty as $crate::VfsFileNodeOps>::get_attr_ext($node, mask)
+        }
+
+        fn set_attr(&$this, attr: &$crate::SetAttr) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::set_attr($node, attr)
+        }
+
+        fn generation(&$this) -> u32 {
+            <$ty as $crate::VfsFileNodeOps>::generation($node)
+        }
+
+        fn get_flags(&$this) -> $crate::VfsResult<$crate::VfsNodeFlags> {
+            <$ty as $crate::VfsFileNodeOps>::get_flags($node)
+        }
+
+        fn set_flags(&$this, flags: $crate::VfsNodeFlags) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::set_flags($node, flags)
+        }
+
+        fn read_at(&$this, offset: u64, buf: &mut [u8]) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::read_at($node, offset, buf)
+        }
+
+        fn write_at(&$this, offset: u64, buf: &[u8]) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::write_at($node, offset, buf)
+        }
+
+        fn read_vectored_at(
+            &$this,
+            offset: u64,
+            bufs: &mut [$crate::IoSliceMut],
+        ) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::read_vectored_at($node, offset, bufs)
+        }
+
+        fn write_vectored_at(
+            &$this,
+            offset: u64,
+            bufs: &[$crate::IoSlice],
+        ) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::write_vectored_at($node, offset, bufs)
+        }
+
+        fn read_direct_at(&$this, offset: u64, buf: &mut [u8]) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::read_direct_at($node, offset, buf)
+        }
+
+        fn write_direct_at(&$this, offset: u64, buf: &[u8]) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::write_direct_at($node, offset, buf)
+        }
+
+        fn io_alignment(&$this) -> usize {
+            <$ty as $crate::VfsFileNodeOps>::io_alignment($node)
+        }
+
+        fn supports_direct_io(&$this) -> bool {
+            <$ty as $crate::VfsFileNodeOps>::supports_direct_io($node)
+        }
+
+        fn seek_hint(
+            &$this,
+            offset: u64,
+            hint: $crate::SeekHint,
+        ) -> $crate::VfsResult<::core::option::Option<u64>> {
+            <$ty as $crate::VfsFileNodeOps>::seek_hint($node, offset, hint)
+        }
+
+        fn readahead(&$this, offset: u64, len: u64) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::readahead($node, offset, len)
+        }
+
+        fn fsync(&$this) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::fsync($node)
+        }
+
+        fn fsync_data(&$this) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::fsync_data($node)
+        }
+
+        fn truncate(&$this, size: u64) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::truncate($node, size)
+        }
+
+        fn fallocate(
+            &$this,
+            offset: u64,
+            len: u64,
+            mode: $crate::FallocateMode,
+        ) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::fallocate($node, offset, len, mode)
+        }
+
+        fn copy_file_range(
+            &$this,
+            src_off: u64,
+            dst: &dyn $crate::VfsNodeOps,
+            dst_off: u64,
+            len: usize,
+        ) -> $crate::VfsResult<usize> {
+            match <$ty as $crate::VfsFileNodeOps>::copy_file_range(
+                $node, src_off, dst, dst_off, len,
+            ) {
+                ::core::result::Result::Err($crate::VfsError::Unsupported) => {
+                    $crate::copy::copy_range($this, src_off, dst, dst_off, len)
+                }
+                res => res,
+            }
+        }
+
+        fn ioctl(&$this, cmd: u32, arg: usize) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::ioctl($node, cmd, arg)
+        }
+
+        fn poll(&$this) -> $crate::VfsResult<$crate::PollEvents> {
+            <$ty as $crate::VfsFileNodeOps>::poll($node)
+        }
+
+        fn register_waker(
+            &$this,
+            events: $crate::PollEvents,
+            waker: &::core::task::Waker,
+        ) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::register_waker($node, events, waker)
+        }
+
+        fn read_hinted(
+            &$this,
+            offset: u64,
+            buf: &mut [u8],
+            hint: $crate::ReadHint,
+        ) -> $crate::VfsResult<usize> {
+            loop {
+                match <$ty as $crate::VfsFileNodeOps>::read_hinted($node, offset, buf, hint) {
+                    ::core::result::Result::Err($crate::VfsError::WouldBlock)
+                        if hint == $crate::ReadHint::Blocking =>
+                    {
+                        $crate::poll::block_on($crate::poll::ready(
+                            $this,
+                            $crate::PollEvents::IN,
+                        ))?;
+                    }
+                    res => return res,
+                }
+            }
+        }
+
+        fn get_page(&$this, offset: u64) -> $crate::VfsResult<$crate::VfsPage> {
+            <$ty as $crate::VfsFileNodeOps>::get_page($node, offset)
+        }
+
+        fn read_link(&$this, buf: &mut [u8]) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsFileNodeOps>::read_link($node, buf)
+        }
+
+        fn link_into(
+            $this: $crate::__priv::Arc<Self>,
+            dir: &$crate::VfsNodeRef,
+            name: &str,
+        ) -> $crate::VfsResult {
+            <$ty as $crate::VfsFileNodeOps>::link_into($arc, dir, name)
+        }
+    };
+}
+
+/// Forwards the operations of [`VfsNodeOps`] that are not file operations
+/// to the [`VfsDirNodeOps`] of `$ty`, see `__forward_file_node_ops!`.
+///
+/// [`VfsNodeOps`]: crate::VfsNodeOps
+/// [`VfsDirNodeOps`]: crate::VfsDirNodeOps
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __forward_dir_node_ops {
+    ($ty:ty, $this:ident => $node:expr, $arc:expr) => {
+        fn open(
+            &$this,
+            flags: $crate::OpenFlags,
+        ) -> $crate::VfsResult<::core::option::Option<$crate::VfsFileRef>> {
+            <$ty as $crate::VfsDirNodeOps>::open($node, flags)
+        }
+
+        fn release(&$this) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::release($node)
+        }
+
+        fn on_last_release(&$this) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::on_last_release($node)
+        }
+
+        fn open_state(
+            &$this,
+        ) -> ::core::option::Option<(&$crate::handle::OpenState, &dyn $crate::VfsNodeOps)> {
+            ::core::option::Option::Some((
+                <$ty as $crate::VfsDirNodeOps>::open_state($node)?,
+                $this,
+            ))
+        }
+
+        fn get_attr(&$this) -> $crate::VfsResult<$crate::VfsNodeAttr> {
+            <$ty as $crate::VfsDirNodeOps>::get_attr($node)
+        }
+
+        fn get_attr_ext(
+            &$this,
+            mask: $crate::AttrMask,
+        ) -> $crate::VfsResult<$crate::VfsNodeAttrExt> {
+            <$ty as $crate::VfsDirNodeOps>::get_attr_ext($node, mask)
+        }
+
+        fn set_attr(&$this, attr: &$crate::SetAttr) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::set_attr($node, attr)
+        }
+
+        fn generation(&$this) -> u32 {
+            <$ty as $crate::VfsDirNodeOps>::generation($node)
+        }
+
+        fn get_flags(&$this) -> $crate::VfsResult<$crate::VfsNodeFlags> {
+            <$ty as $crate::VfsDirNodeOps>::get_flags($node)
+        }
+
+        fn set_flags(&$this, flags: $crate::VfsNodeFlags) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::set_flags($node, flags)
+        }
+
+        fn is_mount_point(&$this) -> bool {
+            <$ty as $crate::VfsDirNodeOps>::is_mount_point($node)
+        }
+
+        fn set_mount_point(&$this, mounted: bool) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::set_mount_point($node, mounted)
+        }
+
+        fn parent(&$this) -> ::core::option::Option<$crate::VfsNodeRef> {
+            <$ty as $crate::VfsDirNodeOps>::parent($node)
+        }
+
+        fn lookup(
+            $this: $crate::__priv::Arc<Self>,
+            path: &str,
+        ) -> $crate::VfsResult<$crate::VfsNodeRef> {
+            <$ty as $crate::VfsDirNodeOps>::lookup($arc, path)
+        }
+
+        fn lookup_step(
+            $this: $crate::__priv::Arc<Self>,
+            path: &str,
+        ) -> $crate::VfsResult<($crate::VfsNodeRef, usize)> {
+            match <$ty as $crate::VfsDirNodeOps>::lookup_step($arc, path) {
+                ::core::result::Result::Err($crate::VfsError::Unsupported) => {
+                    $crate::path::lookup_step($this, path)
+                }
+                res => res,
+            }
+        }
+
+        fn lookup_ctx(
+            $this: $crate::__priv::Arc<Self>,
+            ctx: ::core::option::Option<&$crate::VfsContext>,
+            path: &str,
+        ) -> $crate::VfsResult<$crate::VfsNodeRef> {
+            <$ty as $crate::VfsDirNodeOps>::lookup_ctx($arc, ctx, path)
+        }
+
+        fn create(&$this, path: &str, ty: $crate::VfsNodeType) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::create($node, path, ty)
+        }
+
+        fn create_exclusive(&$this, path: &str, ty: $crate::VfsNodeType) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::create_exclusive($node, path, ty)
+        }
+
+        fn create_ctx(
+            &$this,
+            ctx: ::core::option::Option<&$crate::VfsContext>,
+            path: &str,
+            ty: $crate::VfsNodeType,
+        ) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::create_ctx($node, ctx, path, ty)
+        }
+
+        fn mknod(
+            &$this,
+            path: &str,
+            ty: $crate::VfsNodeType,
+            dev: $crate::DeviceId,
+        ) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::mknod($node, path, ty, dev)
+        }
+
+        fn create_symlink(&$this, path: &str, target: &str) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::create_symlink($node, path, target)
+        }
+
+        fn remove(&$this, path: &str) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::remove($node, path)
+        }
+
+        fn remove_ctx(
+            &$this,
+            ctx: ::core::option::Option<&$crate::VfsContext>,
+            path: &str,
+        ) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::remove_ctx($node, ctx, path)
+        }
+
+        fn read_dir(
+            &$this,
+            start_idx: usize,
+            dirents: &mut [$crate::VfsDirEntry],
+        ) -> $crate::VfsResult<usize> {
+            <$ty as $crate::VfsDirNodeOps>::read_dir($node, start_idx, dirents)
+        }
+
+        fn read_dir_opts(
+            &$this,
+            start_idx: usize,
+            dirents: &mut [$crate::VfsDirEntry],
+            opts: &$crate::ReadDirOptions,
+        ) -> $crate::VfsResult<usize> {
+            match <$ty as $crate::VfsDirNodeOps>::read_dir_opts($node, start_idx, dirents, opts) {
+                ::core::result::Result::Err($crate::VfsError::Unsupported) => {
+                    $crate::__priv::read_dir_opts_fallback($this, start_idx, dirents, opts)
+                }
+                res => res,
+            }
+        }
+
+        fn read_dir_buf(&$this, cursor: u64, buf: &mut [u8]) -> $crate::VfsResult<(usize, u64)> {
+            match <$ty as $crate::VfsDirNodeOps>::read_dir_buf($node, cursor, buf) {
+                ::core::result::Result::Err($crate::VfsError::Unsupported) => {
+                    $crate::__priv::read_dir_buf_fallback($this, cursor, buf)
+                }
+                res => res,
+            }
+        }
+
+        fn open_dir(
+            $this: $crate::__priv::Arc<Self>,
+        ) -> $crate::VfsResult<$crate::__priv::Box<dyn $crate::DirStream>>
+        where
+            Self: 'static,
+        {
+            match <$ty as $crate::VfsDirNodeOps>::open_dir($arc) {
+                ::core::result::Result::Err($crate::VfsError::Unsupported) => {
+                    ::core::result::Result::Ok($crate::__priv::Box::new(
+                        $crate::__priv::IndexDirStream::new($this),
+                    ))
+                }
+                res => res,
+            }
+        }
+
+        fn link(&$this, src_path: &str, dst_path: &str) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::link($node, src_path, dst_path)
+        }
+
+        fn create_tmpfile(&$this) -> $crate::VfsResult<$crate::VfsNodeRef> {
+            <$ty as $crate::VfsDirNodeOps>::create_tmpfile($node)
+        }
+
+        fn rename(&$this, src_path: &str, dst_path: &str) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::rename($node, src_path, dst_path)
+        }
+
+        fn rename_flags(
+            &$this,
+            src_path: &str,
+            dst_path: &str,
+            flags: $crate::RenameFlags,
+        ) -> $crate::VfsResult {
+            <$ty as $crate::VfsDirNodeOps>::rename_flags($node, src_path, dst_path, flags)
+        }
     };
 }
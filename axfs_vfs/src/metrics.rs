@@ -0,0 +1,645 @@
+//! I/O statistics of filesystems.
+//!
+//! A [`MeteredFs`] wraps a filesystem and counts the lookups, reads and
+//! writes done on its nodes, the bytes transferred and the failed
+//! operations, in an [`FsMetrics`]. The kernel can then expose the
+//! statistics of each mount, such as in a `/proc` file, without any support
+//! from the filesystems.
+//!
+//! The counters are updated with relaxed atomic operations: they are
+//! consistent one by one, but a [`snapshot()`](FsMetrics::snapshot) taken
+//! during I/O may mix counters from before and after an operation.
+//!
+//! The per-open files and the directory streams of the nodes are returned
+//! as is, and their operations are not counted.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use axfs_vfs::metrics::MeteredFs;
+//! use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeOps, VfsNodeRef, VfsOps, VfsResult};
+//!
+//! struct Zero;
+//!
+//! impl VfsNodeOps for Zero {
+//!     fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+//!         buf.fill(0);
+//!         Ok(buf.len())
+//!     }
+//!     impl_vfs_non_dir_default! {}
+//! }
+//!
+//! struct ZeroFs;
+//!
+//! impl VfsOps for ZeroFs {
+//!     fn root_dir(&self) -> VfsNodeRef {
+//!         Arc::new(Zero)
+//!     }
+//! }
+//!
+//! let fs = MeteredFs::new(ZeroFs);
+//! let root = fs.root_dir();
+//! root.read_at(0, &mut [1; 512]).unwrap();
+//! assert!(root.clone().lookup("a").is_err());
+//!
+//! let stats = fs.metrics().snapshot();
+//! assert_eq!((stats.reads, stats.bytes_read), (1, 512));
+//! assert_eq!((stats.lookups, stats.errors), (1, 1));
+//! ```
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::fmt;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::Waker;
+
+use crate::export::ExportHandle;
+use crate::notify::{WatchId, WatchMask, WatchSink};
+use crate::{
+    impl_vfs_forward, AttrMask, DeviceId, DirStream, FallocateMode, FileSystemInfo, IoSlice,
+    IoSliceMut, MountOptions, OpenFlags, PollEvents, ReadDirOptions, ReadHint, RenameFlags,
+    SeekHint, SetAttr, VfsCapabilities, VfsContext, VfsDirEntry, VfsFileRef, VfsNodeAttr,
+    VfsNodeAttrExt, VfsNodeFlags, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsPage, VfsResult,
+};
+
+/// The I/O counters of a filesystem.
+#[derive(Debug, Default)]
+pub struct FsMetrics {
+    lookups: AtomicU64,
+    reads: AtomicU64,
+    writes: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// The values of the counters of an [`FsMetrics`] at some point.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// The number of lookups, successful or not.
+    pub lookups: u64,
+    /// The number of reads, successful or not.
+    pub reads: u64,
+    /// The number of writes, successful or not.
+    pub writes: u64,
+    /// The number of bytes read.
+    pub bytes_read: u64,
+    /// The number of bytes written.
+    pub bytes_written: u64,
+    /// The number of operations that failed, of any kind.
+    pub errors: u64,
+}
+
+/// Writes the counters one per line, such as `reads 12`.
+impl fmt::Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lookups {}", self.lookups)?;
+        writeln!(f, "reads {}", self.reads)?;
+        writeln!(f, "writes {}", self.writes)?;
+        writeln!(f, "bytes_read {}", self.bytes_read)?;
+        writeln!(f, "bytes_written {}", self.bytes_written)?;
+        writeln!(f, "errors {}", self.errors)
+    }
+}
+
+impl FsMetrics {
+    /// Creates counters at zero.
+    pub const fn new() -> Self {
+        Self {
+            lookups: AtomicU64::new(0),
+            reads: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the current values of the counters.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            lookups: self.lookups.load(Ordering::Relaxed),
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets all the counters back to zero.
+    pub fn reset(&self) {
+        for counter in [
+            &self.lookups,
+            &self.reads,
+            &self.writes,
+            &self.bytes_read,
+            &self.bytes_written,
+            &self.errors,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts the failure of an operation.
+    fn check<T>(&self, result: VfsResult<T>) -> VfsResult<T> {
+        if result.is_err() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    /// Counts a lookup.
+    fn lookup<T>(&self, result: VfsResult<T>) -> VfsResult<T> {
+        self.lookups.fetch_add(1, Ordering::Relaxed);
+        self.check(result)
+    }
+
+    /// Counts a read, and the bytes read.
+    fn read(&self, result: VfsResult<usize>) -> VfsResult<usize> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        if let Ok(n) = &result {
+            self.bytes_read.fetch_add(*n as u64, Ordering::Relaxed);
+        }
+        self.check(result)
+    }
+
+    /// Counts a write, and the bytes written.
+    fn write(&self, result: VfsResult<usize>) -> VfsResult<usize> {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        if let Ok(n) = &result {
+            self.bytes_written.fetch_add(*n as u64, Ordering::Relaxed);
+        }
+        self.check(result)
+    }
+}
+
+/// A filesystem whose operations are counted in an [`FsMetrics`].
+///
+/// The nodes returned by the filesystem are wrapped in [`MeteredNode`]s,
+/// counting in the same metrics.
+pub struct MeteredFs<F: VfsOps> {
+    inner: F,
+    metrics: Arc<FsMetrics>,
+}
+
+impl<F: VfsOps> MeteredFs<F> {
+    /// Wraps the filesystem `inner`, with counters at zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The filesystem to wrap
+    pub fn new(inner: F) -> Self {
+        Self::with_metrics(inner, Arc::new(FsMetrics::new()))
+    }
+
+    /// Wraps the filesystem `inner`, counting in existing metrics.
+    ///
+    /// This allows to keep the statistics of a mount point across remounts,
+    /// or to sum up those of several filesystems.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The filesystem to wrap
+    /// * `metrics` - The counters to update
+    pub fn with_metrics(inner: F, metrics: Arc<FsMetrics>) -> Self {
+        Self { inner, metrics }
+    }
+
+    /// Returns the wrapped filesystem.
+    pub fn inner(&self) -> &F {
+        &self.inner
+    }
+
+    /// Returns the counters of the filesystem.
+    pub fn metrics(&self) -> &Arc<FsMetrics> {
+        &self.metrics
+    }
+
+    /// Wraps a node of the filesystem.
+    fn wrap(&self, node: VfsNodeRef) -> VfsNodeRef {
+        MeteredNode::wrap(node, &self.metrics)
+    }
+}
+
+impl<F: VfsOps> VfsOps for MeteredFs<F> {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef, options: &MountOptions) -> VfsResult {
+        self.metrics
+            .check(self.inner.mount(path, mount_point, options))
+    }
+
+    fn remount(&self, options: &MountOptions) -> VfsResult {
+        self.metrics.check(self.inner.remount(options))
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.metrics.check(self.inner.umount())
+    }
+
+    fn shutdown(&self) -> VfsResult {
+        self.metrics.check(self.inner.shutdown())
+    }
+
+    fn format(&self) -> VfsResult {
+        self.metrics.check(self.inner.format())
+    }
+
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        self.metrics.check(self.inner.statfs())
+    }
+
+    fn freeze(&self) -> VfsResult {
+        self.metrics.check(self.inner.freeze())
+    }
+
+    fn thaw(&self) -> VfsResult {
+        self.metrics.check(self.inner.thaw())
+    }
+
+    fn capabilities(&self) -> VfsCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn fs_type(&self) -> &str {
+        self.inner.fs_type()
+    }
+
+    fn fs_magic(&self) -> u64 {
+        self.inner.fs_magic()
+    }
+
+    fn open_by_ino(&self, ino: u64) -> VfsResult<VfsNodeRef> {
+        let node = self.metrics.lookup(self.inner.open_by_ino(ino))?;
+        Ok(self.wrap(node))
+    }
+
+    fn encode_fh(&self, node: &VfsNodeRef) -> VfsResult<ExportHandle> {
+        // the inner filesystem only knows the inner nodes
+        let node = match node.as_any().downcast_ref::<MeteredNode>() {
+            Some(node) => node.inner.clone(),
+            None => node.clone(),
+        };
+        self.metrics.check(self.inner.encode_fh(&node))
+    }
+
+    fn open_by_handle(&self, fh: &ExportHandle) -> VfsResult<VfsNodeRef> {
+        let node = self.metrics.lookup(self.inner.open_by_handle(fh))?;
+        Ok(self.wrap(node))
+    }
+
+    fn subscribe(
+        &self,
+        path: &str,
+        mask: WatchMask,
+        sink: Arc<dyn WatchSink>,
+    ) -> VfsResult<WatchId> {
+        self.metrics.check(self.inner.subscribe(path, mask, sink))
+    }
+
+    fn unsubscribe(&self, id: WatchId) -> VfsResult {
+        self.metrics.check(self.inner.unsubscribe(id))
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.wrap(self.inner.root_dir())
+    }
+}
+
+/// A node of a [`MeteredFs`], whose operations are counted.
+///
+/// The nodes it returns, from a lookup or as its parent, are wrapped too.
+pub struct MeteredNode {
+    inner: VfsNodeRef,
+    metrics: Arc<FsMetrics>,
+}
+
+impl MeteredNode {
+    fn wrap(inner: VfsNodeRef, metrics: &Arc<FsMetrics>) -> VfsNodeRef {
+        Arc::new(Self {
+            inner,
+            metrics: metrics.clone(),
+        })
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &VfsNodeRef {
+        &self.inner
+    }
+
+    /// Returns the counters the node updates.
+    pub fn metrics(&self) -> &Arc<FsMetrics> {
+        &self.metrics
+    }
+}
+
+impl VfsNodeOps for MeteredNode {
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        self.metrics.check(self.inner.open(flags))
+    }
+
+    fn release(&self) -> VfsResult {
+        self.metrics.check(self.inner.release())
+    }
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.metrics.check(self.inner.get_attr())
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+        self.metrics.check(self.inner.set_attr(attr))
+    }
+
+    fn get_attr_ext(&self, mask: AttrMask) -> VfsResult<VfsNodeAttrExt> {
+        self.metrics.check(self.inner.get_attr_ext(mask))
+    }
+
+    fn get_flags(&self) -> VfsResult<VfsNodeFlags> {
+        self.metrics.check(self.inner.get_flags())
+    }
+
+    fn set_flags(&self, flags: VfsNodeFlags) -> VfsResult {
+        self.metrics.check(self.inner.set_flags(flags))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.metrics.read(self.inner.read_at(offset, buf))
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.metrics.write(self.inner.write_at(offset, buf))
+    }
+
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        self.metrics.read(self.inner.read_vectored_at(offset, bufs))
+    }
+
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        self.metrics
+            .write(self.inner.write_vectored_at(offset, bufs))
+    }
+
+    fn read_direct_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.metrics.read(self.inner.read_direct_at(offset, buf))
+    }
+
+    fn write_direct_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.metrics.write(self.inner.write_direct_at(offset, buf))
+    }
+
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        self.metrics.check(self.inner.seek_hint(offset, hint))
+    }
+
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        self.metrics.check(self.inner.readahead(offset, len))
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.metrics.check(self.inner.fsync())
+    }
+
+    fn fsync_data(&self) -> VfsResult {
+        self.metrics.check(self.inner.fsync_data())
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.metrics.check(self.inner.truncate(size))
+    }
+
+    fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        self.metrics.check(self.inner.fallocate(offset, len, mode))
+    }
+
+    fn copy_file_range(
+        &self,
+        src_off: u64,
+        dst: &dyn VfsNodeOps,
+        dst_off: u64,
+        len: usize,
+    ) -> VfsResult<usize> {
+        self.metrics
+            .check(self.inner.copy_file_range(src_off, dst, dst_off, len))
+    }
+
+    fn ioctl(&self, cmd: u32, arg: usize) -> VfsResult<usize> {
+        self.metrics.check(self.inner.ioctl(cmd, arg))
+    }
+
+    fn poll(&self) -> VfsResult<PollEvents> {
+        self.metrics.check(self.inner.poll())
+    }
+
+    fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+        self.metrics.check(self.inner.register_waker(events, waker))
+    }
+
+    fn read_hinted(&self, offset: u64, buf: &mut [u8], hint: ReadHint) -> VfsResult<usize> {
+        self.metrics.read(self.inner.read_hinted(offset, buf, hint))
+    }
+
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        self.metrics.check(self.inner.get_page(offset))
+    }
+
+    fn read_link(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        self.metrics.check(self.inner.read_link(buf))
+    }
+
+    fn set_mount_point(&self, mounted: bool) -> VfsResult {
+        self.metrics.check(self.inner.set_mount_point(mounted))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        let parent = self.inner.parent()?;
+        Some(Self::wrap(parent, &self.metrics))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let node = self.metrics.lookup(self.inner.clone().lookup(path))?;
+        Ok(Self::wrap(node, &self.metrics))
+    }
+
+    fn lookup_step(self: Arc<Self>, path: &str) -> VfsResult<(VfsNodeRef, usize)> {
+        let (node, consumed) = self.metrics.lookup(self.inner.clone().lookup_step(path))?;
+        Ok((Self::wrap(node, &self.metrics), consumed))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.metrics.check(self.inner.create(path, ty))
+    }
+
+    fn create_exclusive(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.metrics.check(self.inner.create_exclusive(path, ty))
+    }
+
+    fn lookup_ctx(self: Arc<Self>, ctx: Option<&VfsContext>, path: &str) -> VfsResult<VfsNodeRef> {
+        let node = self
+            .metrics
+            .lookup(self.inner.clone().lookup_ctx(ctx, path))?;
+        Ok(Self::wrap(node, &self.metrics))
+    }
+
+    fn create_ctx(&self, ctx: Option<&VfsContext>, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.metrics.check(self.inner.create_ctx(ctx, path, ty))
+    }
+
+    fn mknod(&self, path: &str, ty: VfsNodeType, dev: DeviceId) -> VfsResult {
+        self.metrics.check(self.inner.mknod(path, ty, dev))
+    }
+
+    fn create_symlink(&self, path: &str, target: &str) -> VfsResult {
+        self.metrics.check(self.inner.create_symlink(path, target))
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.metrics.check(self.inner.remove(path))
+    }
+
+    fn remove_ctx(&self, ctx: Option<&VfsContext>, path: &str) -> VfsResult {
+        self.metrics.check(self.inner.remove_ctx(ctx, path))
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        self.metrics.check(self.inner.read_dir(start_idx, dirents))
+    }
+
+    fn read_dir_opts(
+        &self,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+        opts: &ReadDirOptions,
+    ) -> VfsResult<usize> {
+        self.metrics
+            .check(self.inner.read_dir_opts(start_idx, dirents, opts))
+    }
+
+    fn read_dir_buf(&self, cursor: u64, buf: &mut [u8]) -> VfsResult<(usize, u64)> {
+        self.metrics.check(self.inner.read_dir_buf(cursor, buf))
+    }
+
+    fn open_dir(self: Arc<Self>) -> VfsResult<Box<dyn DirStream>> {
+        self.metrics.check(self.inner.clone().open_dir())
+    }
+
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.metrics.check(self.inner.link(src_path, dst_path))
+    }
+
+    fn create_tmpfile(&self) -> VfsResult<VfsNodeRef> {
+        let node = self.metrics.check(self.inner.create_tmpfile())?;
+        Ok(Self::wrap(node, &self.metrics))
+    }
+
+    fn link_into(self: Arc<Self>, dir: &VfsNodeRef, name: &str) -> VfsResult {
+        // the inner node only knows the inner directories
+        let dir = match dir.as_any().downcast_ref::<MeteredNode>() {
+            Some(dir) => dir.inner.clone(),
+            None => dir.clone(),
+        };
+        self.metrics.check(self.inner.clone().link_into(&dir, name))
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.metrics.check(self.inner.rename(src_path, dst_path))
+    }
+
+    fn rename_flags(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult {
+        self.metrics
+            .check(self.inner.rename_flags(src_path, dst_path, flags))
+    }
+
+    impl_vfs_forward! {
+        inner;
+        except open, release, get_attr, set_attr, get_attr_ext, get_flags, set_flags, read_at,
+        write_at, read_vectored_at, write_vectored_at, read_direct_at, write_direct_at, seek_hint,
+        readahead, fsync, fsync_data, truncate, fallocate, copy_file_range, ioctl, poll,
+        register_waker, read_hinted, get_page, read_link, set_mount_point, parent, lookup,
+        lookup_step, create, create_exclusive, lookup_ctx, create_ctx, mknod, create_symlink,
+        remove, remove_ctx, read_dir, read_dir_opts, read_dir_buf, open_dir, link, create_tmpfile,
+        link_into, rename, rename_flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{impl_vfs_dir_default, impl_vfs_non_dir_default, VfsError};
+    use alloc::string::ToString;
+
+    /// A directory whose entries are files of 4 bytes, refusing writes.
+    struct Dir;
+
+    impl VfsNodeOps for Dir {
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            match path {
+                "f" => Ok(Arc::new(File)),
+                _ => Err(VfsError::NotFound),
+            }
+        }
+
+        impl_vfs_dir_default! {}
+    }
+
+    struct File;
+
+    impl VfsNodeOps for File {
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            Ok(buf.len().min(4))
+        }
+
+        fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+            Err(VfsError::PermissionDenied)
+        }
+
+        impl_vfs_non_dir_default! {}
+    }
+
+    struct Fs;
+
+    impl VfsOps for Fs {
+        fn root_dir(&self) -> VfsNodeRef {
+            Arc::new(Dir)
+        }
+    }
+
+    #[test]
+    fn test_metered_fs() {
+        let fs = MeteredFs::new(Fs);
+        let root = fs.root_dir();
+        assert!(root.as_any().is::<MeteredNode>());
+        let file = root.clone().lookup("f").unwrap();
+        assert!(file.as_any().is::<MeteredNode>());
+        assert_eq!(root.clone().lookup("g").err(), Some(VfsError::NotFound));
+        assert_eq!(file.read_at(0, &mut [0; 16]), Ok(4));
+        assert_eq!(file.read_at(4, &mut [0; 2]), Ok(2));
+        assert!(file.write_at(0, b"abc").is_err());
+
+        let stats = fs.metrics().snapshot();
+        assert_eq!(
+            stats,
+            MetricsSnapshot {
+                lookups: 2,
+                reads: 2,
+                writes: 1,
+                bytes_read: 6,
+                bytes_written: 0,
+                errors: 2,
+            }
+        );
+        assert!(stats.to_string().contains("bytes_read 6\n"));
+
+        fs.metrics().reset();
+        assert_eq!(fs.metrics().snapshot(), MetricsSnapshot::default());
+    }
+
+    #[test]
+    fn test_shared_metrics() {
+        let metrics = Arc::new(FsMetrics::new());
+        let a = MeteredFs::with_metrics(Fs, metrics.clone());
+        let b = MeteredFs::with_metrics(Fs, metrics.clone());
+        a.root_dir().lookup("f").unwrap();
+        b.root_dir().lookup("f").unwrap();
+        assert_eq!(metrics.snapshot().lookups, 2);
+    }
+}
@@ -0,0 +1,383 @@
+//! Options of a mount.
+//!
+//! [`MountOptions`] is passed to [`VfsOps::mount`] and
+//! [`VfsOps::remount`]. The generic options, read-only and noexec, are
+//! enforced by the layer that resolves paths through the mount, and
+//! read-only also by the filesystems that support it; the access time
+//! policy, an [`AtimePolicy`], is applied by the filesystems that record
+//! access times; the
+//! filesystem-specific options, such as `size=` for a RAM filesystem, are
+//! interpreted by the filesystem, which rejects those it does not know.
+//!
+//! [`VfsOps::mount`]: crate::VfsOps::mount
+//! [`VfsOps::remount`]: crate::VfsOps::remount
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+use crate::{VfsError, VfsNodePerm, VfsResult};
+
+/// When reads update the access time of a node, like the `strictatime`,
+/// `relatime` and `noatime` mount options.
+///
+/// Updating the access time on every read costs a write to the metadata of
+/// the node, so most mounts only update it when it tells something new.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::AtimePolicy;
+/// use core::time::Duration;
+///
+/// let secs = Duration::from_secs;
+/// // read again after the last read, the file being unchanged since
+/// assert!(!AtimePolicy::Relative.needs_update(secs(20), secs(10), secs(10), secs(30)));
+/// // read after a modification
+/// assert!(AtimePolicy::Relative.needs_update(secs(20), secs(25), secs(25), secs(30)));
+/// assert!(AtimePolicy::Strict.needs_update(secs(20), secs(10), secs(10), secs(30)));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AtimePolicy {
+    /// Every read updates the access time (`strictatime`).
+    #[default]
+    Strict,
+    /// A read updates the access time if it is not later than the
+    /// modification or status change time, or if it is a day old
+    /// (`relatime`), so that it still tells whether a file was read since
+    /// it was last changed.
+    Relative,
+    /// Reads never update the access time (`noatime`).
+    Never,
+}
+
+impl AtimePolicy {
+    /// How old an access time is updated by a read with
+    /// [`AtimePolicy::Relative`].
+    pub const RELATIVE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// Returns whether a read at `now` updates the access time of a node.
+    ///
+    /// # Arguments
+    ///
+    /// * `atime` - The access time of the node
+    /// * `mtime` - The modification time of the node
+    /// * `ctime` - The status change time of the node
+    /// * `now` - The time of the read
+    pub fn needs_update(
+        &self,
+        atime: Duration,
+        mtime: Duration,
+        ctime: Duration,
+        now: Duration,
+    ) -> bool {
+        match self {
+            Self::Strict => true,
+            Self::Relative => {
+                atime <= mtime
+                    || atime <= ctime
+                    || now.saturating_sub(atime) >= Self::RELATIVE_PERIOD
+            }
+            Self::Never => false,
+        }
+    }
+
+    /// Returns the name of the mount option selecting the policy.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "strictatime",
+            Self::Relative => "relatime",
+            Self::Never => "noatime",
+        }
+    }
+}
+
+/// The options of a mount, like the `-o` argument of `mount(8)`.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::MountOptions;
+///
+/// let opts = MountOptions::parse("ro,size=16m,mode=0755").unwrap();
+/// assert!(opts.is_read_only());
+/// assert_eq!(opts.get_size("size"), Ok(Some(16 << 20)));
+/// assert_eq!(opts.get_mode("mode").unwrap().unwrap().bits(), 0o755);
+/// assert_eq!(opts.to_string(), "ro,size=16m,mode=0755");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MountOptions {
+    read_only: bool,
+    no_exec: bool,
+    atime: Option<AtimePolicy>,
+    options: Vec<(String, String)>,
+}
+
+impl MountOptions {
+    /// Creates the options of a writable mount, with no filesystem-specific
+    /// option.
+    pub const fn new() -> Self {
+        Self {
+            read_only: false,
+            no_exec: false,
+            atime: None,
+            options: Vec::new(),
+        }
+    }
+
+    /// Parses a comma-separated list of options.
+    ///
+    /// `ro`, `rw`, `noexec`, `exec`, `strictatime`, `relatime` and
+    /// `noatime` set the generic options, the later ones taking precedence.
+    /// The others are filesystem-specific, either `key` or `key=value`.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options, such as `"ro,size=1m"`
+    ///
+    /// # Returns
+    ///
+    /// Returns the parsed options.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if an option has an empty key.
+    pub fn parse(opts: &str) -> VfsResult<Self> {
+        let mut parsed = Self::new();
+        for opt in opts.split(',').filter(|opt| !opt.is_empty()) {
+            parsed = match opt {
+                "ro" => parsed.read_only(true),
+                "rw" => parsed.read_only(false),
+                "noexec" => parsed.no_exec(true),
+                "exec" => parsed.no_exec(false),
+                "strictatime" => parsed.atime(AtimePolicy::Strict),
+                "relatime" => parsed.atime(AtimePolicy::Relative),
+                "noatime" => parsed.atime(AtimePolicy::Never),
+                _ => {
+                    let (key, value) = opt.split_once('=').unwrap_or((opt, ""));
+                    if key.is_empty() {
+                        return Err(VfsError::InvalidInput);
+                    }
+                    parsed.option(key, value)
+                }
+            };
+        }
+        Ok(parsed)
+    }
+
+    /// Sets whether the mount is read-only.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Sets whether executing files of the mount is forbidden.
+    pub fn no_exec(mut self, no_exec: bool) -> Self {
+        self.no_exec = no_exec;
+        self
+    }
+
+    /// Sets when reads update the access times of the nodes of the mount.
+    pub fn atime(mut self, policy: AtimePolicy) -> Self {
+        self.atime = Some(policy);
+        self
+    }
+
+    /// Sets a filesystem-specific option, replacing its previous value.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The name of the option
+    /// * `value` - The value of the option, empty for a flag
+    pub fn option(mut self, key: &str, value: &str) -> Self {
+        match self.options.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.options.push((key.to_string(), value.to_string())),
+        }
+        self
+    }
+
+    /// Returns whether the mount is read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Returns whether executing files of the mount is forbidden.
+    pub fn is_no_exec(&self) -> bool {
+        self.no_exec
+    }
+
+    /// Returns when reads update the access times of the nodes of the
+    /// mount, or `None` to keep the policy of the filesystem.
+    pub fn atime_policy(&self) -> Option<AtimePolicy> {
+        self.atime
+    }
+
+    /// Returns the value of a filesystem-specific option, empty for a
+    /// flag, or `None` if it is not set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the filesystem-specific options, in the order they were set.
+    pub fn options(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.options.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Checks that all filesystem-specific options are known.
+    ///
+    /// # Arguments
+    ///
+    /// * `known` - The keys of the options supported by the filesystem
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if an option is not in `known`, as
+    /// Linux does for unknown mount options.
+    pub fn check_known(&self, known: &[&str]) -> VfsResult {
+        match self.options().all(|(key, _)| known.contains(&key)) {
+            true => Ok(()),
+            false => Err(VfsError::InvalidInput),
+        }
+    }
+
+    /// Returns the value of a size option, such as `size=16m`.
+    ///
+    /// The value is a number of bytes, with an optional `k`, `m` or `g`
+    /// suffix (in either case) for KiB, MiB or GiB.
+    ///
+    /// # Returns
+    ///
+    /// Returns the size in bytes, or `None` if the option is not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the value is not a valid size.
+    pub fn get_size(&self, key: &str) -> VfsResult<Option<u64>> {
+        let Some(value) = self.get(key) else {
+            return Ok(None);
+        };
+        let (digits, shift) = match value.as_bytes().last() {
+            Some(b'k' | b'K') => (&value[..value.len() - 1], 10),
+            Some(b'm' | b'M') => (&value[..value.len() - 1], 20),
+            Some(b'g' | b'G') => (&value[..value.len() - 1], 30),
+            _ => (value, 0),
+        };
+        let size: u64 = digits.parse().map_err(|_| VfsError::InvalidInput)?;
+        size.checked_mul(1 << shift)
+            .map(Some)
+            .ok_or(VfsError::InvalidInput)
+    }
+
+    /// Returns the value of a permission option, such as `mode=0755`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the permission, given in octal, or `None` if the option is
+    /// not set.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the value is not an octal
+    /// number of at most `0o777`.
+    pub fn get_mode(&self, key: &str) -> VfsResult<Option<VfsNodePerm>> {
+        let Some(value) = self.get(key) else {
+            return Ok(None);
+        };
+        match u16::from_str_radix(value, 8) {
+            Ok(mode) if mode <= 0o777 => Ok(Some(VfsNodePerm::from_bits_truncate(mode))),
+            _ => Err(VfsError::InvalidInput),
+        }
+    }
+}
+
+/// Formats the options like `/proc/mounts`, such as `ro,noexec,size=1m`.
+///
+/// Only the generic options that differ from the defaults are written, and
+/// the access time policy if it is set.
+impl fmt::Display for MountOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        let atime = self.atime.map(|policy| policy.as_str());
+        for flag in [(self.read_only, "ro"), (self.no_exec, "noexec")]
+            .into_iter()
+            .filter_map(|(set, name)| set.then_some(name))
+            .chain(atime)
+        {
+            write!(f, "{sep}{flag}")?;
+            sep = ",";
+        }
+        for (key, value) in self.options() {
+            match value {
+                "" => write!(f, "{sep}{key}")?,
+                _ => write!(f, "{sep}{key}={value}")?,
+            }
+            sep = ",";
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let opts = MountOptions::parse("ro,noexec,,size=1k,huge,rw,size=2k").unwrap();
+        assert!(!opts.is_read_only());
+        assert!(opts.is_no_exec());
+        assert_eq!(opts.get("huge"), Some(""));
+        assert_eq!(opts.get("size"), Some("2k"));
+        assert_eq!(opts.get("mode"), None);
+        assert_eq!(opts.to_string(), "noexec,size=2k,huge");
+        assert_eq!(MountOptions::parse("=1"), Err(VfsError::InvalidInput));
+        assert_eq!(MountOptions::parse(""), Ok(MountOptions::new()));
+    }
+
+    #[test]
+    fn test_atime_policy() {
+        assert_eq!(MountOptions::new().atime_policy(), None);
+        let opts = MountOptions::parse("noatime,ro,relatime").unwrap();
+        assert_eq!(opts.atime_policy(), Some(AtimePolicy::Relative));
+        assert_eq!(opts.to_string(), "ro,relatime");
+        let opts = MountOptions::parse("strictatime,noatime,size=1k").unwrap();
+        assert_eq!(opts.atime_policy(), Some(AtimePolicy::Never));
+        assert_eq!(opts.get("noatime"), None);
+        assert_eq!(opts.to_string(), "noatime,size=1k");
+
+        let secs = Duration::from_secs;
+        let day = AtimePolicy::RELATIVE_PERIOD;
+        let relative = AtimePolicy::Relative;
+        assert!(relative.needs_update(secs(10), secs(10), secs(5), secs(20)));
+        assert!(relative.needs_update(secs(10), secs(5), secs(12), secs(20)));
+        assert!(!relative.needs_update(secs(10), secs(5), secs(5), secs(20)));
+        assert!(relative.needs_update(secs(10), secs(5), secs(5), secs(10) + day));
+        assert!(!AtimePolicy::Never.needs_update(secs(0), secs(5), secs(5), day));
+        assert_eq!(AtimePolicy::default(), AtimePolicy::Strict);
+    }
+
+    #[test]
+    fn test_typed_values() {
+        let opts = MountOptions::new()
+            .option("a", "4096")
+            .option("b", "3G")
+            .option("c", "1x")
+            .option("d", "99999999999g")
+            .option("mode", "1777");
+        assert_eq!(opts.get_size("a"), Ok(Some(4096)));
+        assert_eq!(opts.get_size("b"), Ok(Some(3 << 30)));
+        assert_eq!(opts.get_size("c"), Err(VfsError::InvalidInput));
+        assert_eq!(opts.get_size("d"), Err(VfsError::InvalidInput));
+        assert_eq!(opts.get_size("e"), Ok(None));
+        assert_eq!(opts.get_mode("mode").err(), Some(VfsError::InvalidInput));
+        assert_eq!(opts.get_mode("a").err(), Some(VfsError::InvalidInput));
+
+        assert_eq!(opts.check_known(&["a", "b", "c", "d", "mode"]), Ok(()));
+        assert_eq!(opts.check_known(&["a"]), Err(VfsError::InvalidInput));
+    }
+}
@@ -0,0 +1,225 @@
+//! Validation and normalization of node names.
+//!
+//! Unicode allows several encodings of visually identical names, such as
+//! `é` precomposed (U+00E9) or followed by a combining accent (U+0065
+//! U+0301). A filesystem shared with host tooling, for example through an
+//! exported tar archive, should not accumulate such duplicates. A
+//! [`NamePolicy`] rejects or normalizes them, along with names that such
+//! tools handle poorly, before they are stored.
+//!
+//! The default policy accepts every name unchanged. Normalization to NFC
+//! requires the `nfc` feature.
+
+use alloc::borrow::Cow;
+
+use crate::{VfsError, VfsResult};
+
+/// How a [`NamePolicy`] handles the Unicode normalization of names.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Normalization {
+    /// Names are stored and looked up as given.
+    #[default]
+    Preserve,
+    /// Names are converted to Normalization Form C when created and looked
+    /// up, so that all encodings of a name refer to the same entry.
+    #[cfg(feature = "nfc")]
+    ToNfc,
+    /// Names that are not in Normalization Form C are rejected.
+    #[cfg(feature = "nfc")]
+    RequireNfc,
+}
+
+/// The rules that the names of the nodes of a filesystem follow.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::names::NamePolicy;
+/// use axfs_vfs::VfsError;
+///
+/// let policy = NamePolicy::new().reject_control(true);
+/// assert_eq!(policy.check_bytes(b"notes.txt"), Ok("notes.txt"));
+/// assert_eq!(policy.check_bytes(b"\xff"), Err(VfsError::IllegalBytes));
+/// assert_eq!(policy.apply("a\nb").err(), Some(VfsError::IllegalBytes));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NamePolicy {
+    normalization: Normalization,
+    reject_control: bool,
+}
+
+impl NamePolicy {
+    /// Creates a policy accepting every name unchanged.
+    pub const fn new() -> Self {
+        Self {
+            normalization: Normalization::Preserve,
+            reject_control: false,
+        }
+    }
+
+    /// Sets how the Unicode normalization of names is handled.
+    pub const fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Sets whether names containing control characters (U+0000 to U+001F
+    /// and U+007F to U+009F), such as newlines, are rejected.
+    pub const fn reject_control(mut self, reject: bool) -> Self {
+        self.reject_control = reject;
+        self
+    }
+
+    /// Returns how the Unicode normalization of names is handled.
+    pub const fn get_normalization(&self) -> Normalization {
+        self.normalization
+    }
+
+    /// Returns whether names containing control characters are rejected.
+    pub const fn rejects_control(&self) -> bool {
+        self.reject_control
+    }
+
+    /// Checks a name received as bytes, such as from a syscall.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to check
+    ///
+    /// # Returns
+    ///
+    /// Returns the name as a string if it is valid UTF-8 and satisfies
+    /// [`check()`](Self::check).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::IllegalBytes`] if the name is not valid UTF-8,
+    /// or the errors of [`check()`](Self::check).
+    pub fn check_bytes<'a>(&self, name: &'a [u8]) -> VfsResult<&'a str> {
+        let name = core::str::from_utf8(name).map_err(|_| VfsError::IllegalBytes)?;
+        self.check(name)?;
+        Ok(name)
+    }
+
+    /// Checks that a name satisfies the policy, without normalizing it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to check
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the name is accepted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::IllegalBytes`] if the name contains a control
+    /// character that the policy rejects, or is not in NFC while
+    /// [`Normalization::RequireNfc`] is set.
+    pub fn check(&self, name: &str) -> VfsResult {
+        if self.reject_control && name.chars().any(char::is_control) {
+            return Err(VfsError::IllegalBytes);
+        }
+        #[cfg(feature = "nfc")]
+        if self.normalization == Normalization::RequireNfc && !is_nfc(name) {
+            return Err(VfsError::IllegalBytes);
+        }
+        Ok(())
+    }
+
+    /// Checks a name and normalizes it, to create a node with it.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the new node
+    ///
+    /// # Returns
+    ///
+    /// Returns the name to store, which is borrowed from `name` unless it
+    /// had to be normalized.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`check()`](Self::check).
+    pub fn apply<'a>(&self, name: &'a str) -> VfsResult<Cow<'a, str>> {
+        self.check(name)?;
+        Ok(self.lookup_key(name))
+    }
+
+    /// Normalizes a name to look a node up with it.
+    ///
+    /// Names are not checked, since a rejected name cannot have been
+    /// created and is simply not found.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to look up
+    ///
+    /// # Returns
+    ///
+    /// Returns the name the node would be stored with.
+    pub fn lookup_key<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self.normalization {
+            #[cfg(feature = "nfc")]
+            Normalization::ToNfc if !is_nfc(name) => {
+                use unicode_normalization::UnicodeNormalization;
+                Cow::Owned(name.nfc().collect())
+            }
+            _ => Cow::Borrowed(name),
+        }
+    }
+}
+
+/// Returns whether `name` is in Normalization Form C.
+#[cfg(feature = "nfc")]
+fn is_nfc(name: &str) -> bool {
+    use unicode_normalization::{is_nfc_quick, IsNormalized};
+    match is_nfc_quick(name.chars()) {
+        IsNormalized::Yes => true,
+        IsNormalized::No => false,
+        IsNormalized::Maybe => unicode_normalization::is_nfc(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_policy() {
+        let policy = NamePolicy::default();
+        assert_eq!(policy, NamePolicy::new());
+        assert_eq!(policy.check("a\tb"), Ok(()));
+        assert!(matches!(
+            policy.apply("e\u{301}"),
+            Ok(Cow::Borrowed("e\u{301}"))
+        ));
+        assert_eq!(policy.check_bytes(b"ok"), Ok("ok"));
+        assert_eq!(policy.check_bytes(b"\xc3"), Err(VfsError::IllegalBytes));
+    }
+
+    #[test]
+    fn test_reject_control() {
+        let policy = NamePolicy::new().reject_control(true);
+        assert!(policy.rejects_control());
+        for name in ["a\nb", "\0", "tab\t", "del\u{7f}", "c1\u{85}"] {
+            assert_eq!(policy.check(name), Err(VfsError::IllegalBytes), "{name:?}");
+        }
+        assert_eq!(policy.check("caf\u{e9} \u{1f600}"), Ok(()));
+    }
+
+    #[cfg(feature = "nfc")]
+    #[test]
+    fn test_nfc() {
+        let policy = NamePolicy::new().normalization(Normalization::ToNfc);
+        assert_eq!(policy.apply("caf\u{e9}").unwrap(), "caf\u{e9}");
+        assert!(matches!(policy.apply("caf\u{e9}"), Ok(Cow::Borrowed(_))));
+        assert_eq!(policy.apply("cafe\u{301}").unwrap(), "caf\u{e9}");
+        assert_eq!(policy.lookup_key("cafe\u{301}"), "caf\u{e9}");
+
+        let policy = policy.normalization(Normalization::RequireNfc);
+        assert_eq!(policy.check("caf\u{e9}"), Ok(()));
+        assert_eq!(policy.check("cafe\u{301}"), Err(VfsError::IllegalBytes));
+        assert_eq!(policy.lookup_key("cafe\u{301}"), "cafe\u{301}");
+    }
+}
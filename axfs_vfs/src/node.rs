@@ -0,0 +1,669 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use axerrno::ax_err;
+use core::task::Waker;
+
+use crate::handle::{self, OpenState};
+use crate::{
+    iovec, AttrMask, DeviceId, DirStream, FallocateMode, IoSlice, IoSliceMut, OpenFlags,
+    PollEvents, ReadDirOptions, ReadHint, RenameFlags, SeekHint, SetAttr, VfsContext, VfsDirEntry,
+    VfsFileRef, VfsNodeAttr, VfsNodeAttrExt, VfsNodeFlags, VfsNodeOps, VfsNodeRef, VfsNodeType,
+    VfsPage, VfsResult,
+};
+
+/// Operations of a node that is not a directory, such as a regular file or
+/// a device.
+///
+/// Unlike [`VfsNodeOps`], this trait has no directory operations to stub
+/// out. A node implementing it derives [`VfsNodeOps`] with
+/// [`impl_vfs_file_node!`](crate::impl_vfs_file_node), or is wrapped in a
+/// [`FileNodeAdapter`]: the directory operations of the derived
+/// [`VfsNodeOps`] return [`AxError::NotADirectory`], and the other ones are
+/// forwarded to this trait. The defaults are those of [`VfsNodeOps`], except
+/// where noted.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_vfs::{VfsError, VfsFileNodeOps, VfsNodeOps, VfsNodeRef, VfsResult};
+///
+/// struct Hello;
+///
+/// impl VfsFileNodeOps for Hello {
+///     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+///         let data = b"hello".get(offset as usize..).unwrap_or_default();
+///         let len = data.len().min(buf.len());
+///         buf[..len].copy_from_slice(&data[..len]);
+///         Ok(len)
+///     }
+/// }
+///
+/// axfs_vfs::impl_vfs_file_node!(Hello);
+///
+/// let node: VfsNodeRef = Arc::new(Hello);
+/// let mut buf = [0; 8];
+/// assert_eq!(node.read_at(0, &mut buf), Ok(5));
+/// assert_eq!(node.create("x", axfs_vfs::VfsNodeType::File), Err(VfsError::NotADirectory));
+/// ```
+///
+/// [`AxError::NotADirectory`]: crate::VfsError::NotADirectory
+pub trait VfsFileNodeOps: Send + Sync {
+    /// Do something when the node is opened, see [`VfsNodeOps::open`].
+    fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        Ok(None)
+    }
+
+    /// Do something when the node is closed, see [`VfsNodeOps::release`].
+    fn release(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Do something when the last open handle of the node is closed, see
+    /// [`VfsNodeOps::on_last_release`].
+    fn on_last_release(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Returns the state counting the open handles of the node, see
+    /// [`VfsNodeOps::open_state`]. The node is the owner of the state.
+    fn open_state(&self) -> Option<&OpenState> {
+        None
+    }
+
+    /// Get the attributes of the node, see [`VfsNodeOps::get_attr`].
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        ax_err!(Unsupported)
+    }
+
+    /// Get the attributes of the node selected by `mask`, see
+    /// [`VfsNodeOps::get_attr_ext`].
+    fn get_attr_ext(&self, _mask: AttrMask) -> VfsResult<VfsNodeAttrExt> {
+        self.get_attr().map(VfsNodeAttrExt::from)
+    }
+
+    /// Change the attributes of the node, see [`VfsNodeOps::set_attr`].
+    fn set_attr(&self, _attr: &SetAttr) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Get the generation number of the node, see
+    /// [`VfsNodeOps::generation`].
+    fn generation(&self) -> u32 {
+        0
+    }
+
+    /// Get the flags of the node, see [`VfsNodeOps::get_flags`].
+    fn get_flags(&self) -> VfsResult<VfsNodeFlags> {
+        ax_err!(Unsupported)
+    }
+
+    /// Change the flags of the node, see [`VfsNodeOps::set_flags`].
+    fn set_flags(&self, _flags: VfsNodeFlags) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Read data from the file at the given offset, see
+    /// [`VfsNodeOps::read_at`].
+    fn read_at(&self, _offset: u64, _buf: &mut [u8]) -> VfsResult<usize> {
+        ax_err!(InvalidInput)
+    }
+
+    /// Write data to the file at the given offset, see
+    /// [`VfsNodeOps::write_at`].
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        ax_err!(InvalidInput)
+    }
+
+    /// Read data from the file at the given offset into several buffers,
+    /// see [`VfsNodeOps::read_vectored_at`].
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        iovec::read_vectored_with(offset, bufs, |off, buf| self.read_at(off, buf))
+    }
+
+    /// Write data to the file at the given offset from several buffers, see
+    /// [`VfsNodeOps::write_vectored_at`].
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        iovec::write_vectored_with(offset, bufs, |off, buf| self.write_at(off, buf))
+    }
+
+    /// Read data from the file at the given offset, bypassing any cache,
+    /// see [`VfsNodeOps::read_direct_at`].
+    fn read_direct_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.read_at(offset, buf)
+    }
+
+    /// Write data to the file at the given offset, bypassing any cache, see
+    /// [`VfsNodeOps::write_direct_at`].
+    fn write_direct_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.write_at(offset, buf)
+    }
+
+    /// Returns the alignment required for direct I/O on the file, see
+    /// [`VfsNodeOps::io_alignment`].
+    fn io_alignment(&self) -> usize {
+        handle::DIRECT_IO_ALIGN
+    }
+
+    /// Returns whether the file can be opened for direct I/O, see
+    /// [`VfsNodeOps::supports_direct_io`].
+    fn supports_direct_io(&self) -> bool {
+        true
+    }
+
+    /// Finds the next region of data or the next hole of a sparse file, see
+    /// [`VfsNodeOps::seek_hint`].
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        let size = self.get_attr()?.size();
+        if offset >= size {
+            return Ok(None);
+        }
+        Ok(Some(match hint {
+            SeekHint::Data => offset,
+            SeekHint::Hole => size,
+        }))
+    }
+
+    /// Prefetch a range of the file, see [`VfsNodeOps::readahead`].
+    fn readahead(&self, _offset: u64, _len: u64) -> VfsResult {
+        Ok(())
+    }
+
+    /// Synchronize the file data and metadata to disk, see
+    /// [`VfsNodeOps::fsync`].
+    fn fsync(&self) -> VfsResult {
+        ax_err!(InvalidInput)
+    }
+
+    /// Synchronize the file data to disk, see [`VfsNodeOps::fsync_data`].
+    /// The default implementation calls [`fsync()`](Self::fsync).
+    fn fsync_data(&self) -> VfsResult {
+        self.fsync()
+    }
+
+    /// Truncate the file to the given size, see [`VfsNodeOps::truncate`].
+    fn truncate(&self, _size: u64) -> VfsResult {
+        ax_err!(InvalidInput)
+    }
+
+    /// Allocate or deallocate a range of the file, see
+    /// [`VfsNodeOps::fallocate`].
+    fn fallocate(&self, _offset: u64, _len: u64, _mode: FallocateMode) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Copy a range of the file into `dst`, see
+    /// [`VfsNodeOps::copy_file_range`].
+    ///
+    /// The default implementation returns [`AxError::Unsupported`], for
+    /// which the derived [`VfsNodeOps`] copies the range with
+    /// [`copy::copy_range`](crate::copy::copy_range).
+    ///
+    /// [`AxError::Unsupported`]: crate::VfsError::Unsupported
+    fn copy_file_range(
+        &self,
+        _src_off: u64,
+        _dst: &dyn VfsNodeOps,
+        _dst_off: u64,
+        _len: usize,
+    ) -> VfsResult<usize> {
+        ax_err!(Unsupported)
+    }
+
+    /// Send a device-specific control command, see [`VfsNodeOps::ioctl`].
+    fn ioctl(&self, _cmd: u32, _arg: usize) -> VfsResult<usize> {
+        ax_err!(NotATty)
+    }
+
+    /// Get the readiness of the node, see [`VfsNodeOps::poll`].
+    fn poll(&self) -> VfsResult<PollEvents> {
+        Ok(PollEvents::IN | PollEvents::OUT)
+    }
+
+    /// Register a waker to be woken when the readiness of the node changes,
+    /// see [`VfsNodeOps::register_waker`].
+    fn register_waker(&self, _events: PollEvents, waker: &Waker) -> VfsResult {
+        waker.wake_by_ref();
+        Ok(())
+    }
+
+    /// Read data from the file at the given offset, see
+    /// [`VfsNodeOps::read_hinted`].
+    ///
+    /// The default implementation calls [`read_at()`](Self::read_at). When
+    /// it fails with [`AxError::WouldBlock`] for a blocking read, the
+    /// derived [`VfsNodeOps`] waits for the node to become readable and
+    /// reads again.
+    ///
+    /// [`AxError::WouldBlock`]: crate::VfsError::WouldBlock
+    fn read_hinted(&self, offset: u64, buf: &mut [u8], _hint: ReadHint) -> VfsResult<usize> {
+        self.read_at(offset, buf)
+    }
+
+    /// Get a page of the file to map in memory, see
+    /// [`VfsNodeOps::get_page`].
+    fn get_page(&self, _offset: u64) -> VfsResult<VfsPage> {
+        ax_err!(NoSuchDevice)
+    }
+
+    /// Read the target of a symbolic link, see [`VfsNodeOps::read_link`].
+    fn read_link(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        ax_err!(InvalidInput)
+    }
+
+    /// Link the node into `dir` under `name`, see
+    /// [`VfsNodeOps::link_into`].
+    fn link_into(self: Arc<Self>, _dir: &VfsNodeRef, _name: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+}
+
+/// Operations of a directory node.
+///
+/// Unlike [`VfsNodeOps`], this trait has no file operations to stub out. A
+/// node implementing it derives [`VfsNodeOps`] with
+/// [`impl_vfs_dir_node!`](crate::impl_vfs_dir_node), or is wrapped in a
+/// [`DirNodeAdapter`]: the file operations of the derived [`VfsNodeOps`]
+/// return [`AxError::IsADirectory`], its
+/// [`read_link()`](VfsNodeOps::read_link) returns
+/// [`AxError::InvalidInput`], and the other operations are forwarded to this
+/// trait. The defaults are those of [`VfsNodeOps`], except where noted.
+///
+/// [`AxError::IsADirectory`]: crate::VfsError::IsADirectory
+/// [`AxError::InvalidInput`]: crate::VfsError::InvalidInput
+pub trait VfsDirNodeOps: Send + Sync {
+    /// Do something when the node is opened, see [`VfsNodeOps::open`].
+    fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        Ok(None)
+    }
+
+    /// Do something when the node is closed, see [`VfsNodeOps::release`].
+    fn release(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Do something when the last open handle of the node is closed, see
+    /// [`VfsNodeOps::on_last_release`].
+    fn on_last_release(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Returns the state counting the open handles of the node, see
+    /// [`VfsNodeOps::open_state`]. The node is the owner of the state.
+    fn open_state(&self) -> Option<&OpenState> {
+        None
+    }
+
+    /// Get the attributes of the node, see [`VfsNodeOps::get_attr`].
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        ax_err!(Unsupported)
+    }
+
+    /// Get the attributes of the node selected by `mask`, see
+    /// [`VfsNodeOps::get_attr_ext`].
+    fn get_attr_ext(&self, _mask: AttrMask) -> VfsResult<VfsNodeAttrExt> {
+        self.get_attr().map(VfsNodeAttrExt::from)
+    }
+
+    /// Change the attributes of the node, see [`VfsNodeOps::set_attr`].
+    fn set_attr(&self, _attr: &SetAttr) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Get the generation number of the node, see
+    /// [`VfsNodeOps::generation`].
+    fn generation(&self) -> u32 {
+        0
+    }
+
+    /// Get the flags of the node, see [`VfsNodeOps::get_flags`].
+    fn get_flags(&self) -> VfsResult<VfsNodeFlags> {
+        ax_err!(Unsupported)
+    }
+
+    /// Change the flags of the node, see [`VfsNodeOps::set_flags`].
+    fn set_flags(&self, _flags: VfsNodeFlags) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Returns whether a filesystem is mounted on the directory, see
+    /// [`VfsNodeOps::is_mount_point`].
+    fn is_mount_point(&self) -> bool {
+        false
+    }
+
+    /// Mark or unmark the directory as a mount point, see
+    /// [`VfsNodeOps::set_mount_point`].
+    fn set_mount_point(&self, _mounted: bool) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Get the parent directory, see [`VfsNodeOps::parent`].
+    fn parent(&self) -> Option<VfsNodeRef> {
+        None
+    }
+
+    /// Lookup the node with the given `path`, see [`VfsNodeOps::lookup`].
+    fn lookup(self: Arc<Self>, _path: &str) -> VfsResult<VfsNodeRef> {
+        ax_err!(Unsupported)
+    }
+
+    /// Lookup the components of `path` up to the first mount point, see
+    /// [`VfsNodeOps::lookup_step`].
+    ///
+    /// The default implementation returns [`AxError::Unsupported`], for
+    /// which the derived [`VfsNodeOps`] calls
+    /// [`path::lookup_step`](crate::path::lookup_step).
+    ///
+    /// [`AxError::Unsupported`]: crate::VfsError::Unsupported
+    fn lookup_step(self: Arc<Self>, _path: &str) -> VfsResult<(VfsNodeRef, usize)> {
+        ax_err!(Unsupported)
+    }
+
+    /// Lookup the node with the given `path` on behalf of the caller of
+    /// `ctx`, see [`VfsNodeOps::lookup_ctx`].
+    fn lookup_ctx(self: Arc<Self>, _ctx: Option<&VfsContext>, path: &str) -> VfsResult<VfsNodeRef> {
+        self.lookup(path)
+    }
+
+    /// Create a new node with the given `path`, see [`VfsNodeOps::create`].
+    fn create(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Create a new node with the given `path`, failing if it already
+    /// exists, see [`VfsNodeOps::create_exclusive`].
+    fn create_exclusive(&self, _path: &str, _ty: VfsNodeType) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Create a new node with the given `path` on behalf of the caller of
+    /// `ctx`, see [`VfsNodeOps::create_ctx`].
+    fn create_ctx(&self, _ctx: Option<&VfsContext>, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.create(path, ty)
+    }
+
+    /// Create a device node, see [`VfsNodeOps::mknod`].
+    fn mknod(&self, _path: &str, _ty: VfsNodeType, _dev: DeviceId) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Create a symbolic link, see [`VfsNodeOps::create_symlink`].
+    fn create_symlink(&self, _path: &str, _target: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Remove the node with the given `path`, see [`VfsNodeOps::remove`].
+    fn remove(&self, _path: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Remove the node with the given `path` on behalf of the caller of
+    /// `ctx`, see [`VfsNodeOps::remove_ctx`].
+    fn remove_ctx(&self, _ctx: Option<&VfsContext>, path: &str) -> VfsResult {
+        self.remove(path)
+    }
+
+    /// Read directory entries, see [`VfsNodeOps::read_dir`].
+    fn read_dir(&self, _start_idx: usize, _dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        ax_err!(Unsupported)
+    }
+
+    /// Read the directory entries selected by `opts`, see
+    /// [`VfsNodeOps::read_dir_opts`].
+    ///
+    /// The default implementation returns [`AxError::Unsupported`], for
+    /// which the derived [`VfsNodeOps`] filters and sorts the entries of
+    /// [`read_dir()`](Self::read_dir).
+    ///
+    /// [`AxError::Unsupported`]: crate::VfsError::Unsupported
+    fn read_dir_opts(
+        &self,
+        _start_idx: usize,
+        _dirents: &mut [VfsDirEntry],
+        _opts: &ReadDirOptions,
+    ) -> VfsResult<usize> {
+        ax_err!(Unsupported)
+    }
+
+    /// Read directory entries as Linux `struct linux_dirent64` records, see
+    /// [`VfsNodeOps::read_dir_buf`].
+    ///
+    /// The default implementation returns [`AxError::Unsupported`], for
+    /// which the derived [`VfsNodeOps`] encodes the entries of
+    /// [`read_dir()`](Self::read_dir).
+    ///
+    /// [`AxError::Unsupported`]: crate::VfsError::Unsupported
+    fn read_dir_buf(&self, _cursor: u64, _buf: &mut [u8]) -> VfsResult<(usize, u64)> {
+        ax_err!(Unsupported)
+    }
+
+    /// Open a stream over the entries of the directory, see
+    /// [`VfsNodeOps::open_dir`].
+    ///
+    /// The default implementation returns [`AxError::Unsupported`], for
+    /// which the derived [`VfsNodeOps`] streams the entries of
+    /// [`read_dir()`](Self::read_dir) by index.
+    ///
+    /// [`AxError::Unsupported`]: crate::VfsError::Unsupported
+    fn open_dir(self: Arc<Self>) -> VfsResult<Box<dyn DirStream>> {
+        ax_err!(Unsupported)
+    }
+
+    /// Create a hard link, see [`VfsNodeOps::link`].
+    fn link(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Create an unnamed file in the directory, see
+    /// [`VfsNodeOps::create_tmpfile`].
+    fn create_tmpfile(&self) -> VfsResult<VfsNodeRef> {
+        ax_err!(Unsupported)
+    }
+
+    /// Rename or move a node, see [`VfsNodeOps::rename`].
+    fn rename(&self, _src_path: &str, _dst_path: &str) -> VfsResult {
+        ax_err!(Unsupported)
+    }
+
+    /// Rename or move a node with `flags`, see
+    /// [`VfsNodeOps::rename_flags`].
+    fn rename_flags(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult {
+        if flags.contains(RenameFlags::NOREPLACE | RenameFlags::EXCHANGE) {
+            return ax_err!(InvalidInput);
+        }
+        if !flags.is_empty() {
+            return ax_err!(Unsupported);
+        }
+        self.rename(src_path, dst_path)
+    }
+}
+
+/// Makes a [`VfsFileNodeOps`] a [`VfsNodeOps`], for nodes that cannot use
+/// [`impl_vfs_file_node!`](crate::impl_vfs_file_node), such as the types of
+/// other crates.
+///
+/// The node is kept in an [`Arc`], so that
+/// [`link_into()`](VfsFileNodeOps::link_into) can be forwarded to it. The
+/// adapter is what a [`VfsNodeRef`] downcasts to, the node is then reached
+/// with [`inner()`](Self::inner).
+pub struct FileNodeAdapter<T>(Arc<T>);
+
+impl<T: VfsFileNodeOps> FileNodeAdapter<T> {
+    /// Wraps `node` into an adapter.
+    pub fn new(node: T) -> Self {
+        Self(Arc::new(node))
+    }
+
+    /// Wraps a shared `node` into an adapter.
+    pub const fn from_arc(node: Arc<T>) -> Self {
+        Self(node)
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &Arc<T> {
+        &self.0
+    }
+}
+
+impl<T: VfsFileNodeOps + 'static> VfsNodeOps for FileNodeAdapter<T> {
+    crate::__forward_file_node_ops!(T, self => &*self.0, self.0.clone());
+    crate::impl_vfs_non_dir_default! {}
+}
+
+/// Makes a [`VfsDirNodeOps`] a [`VfsNodeOps`], for nodes that cannot use
+/// [`impl_vfs_dir_node!`](crate::impl_vfs_dir_node), such as the types of
+/// other crates.
+///
+/// The node is kept in an [`Arc`], so that
+/// [`lookup()`](VfsDirNodeOps::lookup) can be forwarded to it. The adapter
+/// is what a [`VfsNodeRef`] downcasts to, the node is then reached with
+/// [`inner()`](Self::inner).
+pub struct DirNodeAdapter<T>(Arc<T>);
+
+impl<T: VfsDirNodeOps> DirNodeAdapter<T> {
+    /// Wraps `node` into an adapter.
+    pub fn new(node: T) -> Self {
+        Self(Arc::new(node))
+    }
+
+    /// Wraps a shared `node` into an adapter.
+    pub const fn from_arc(node: Arc<T>) -> Self {
+        Self(node)
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &Arc<T> {
+        &self.0
+    }
+}
+
+impl<T: VfsDirNodeOps + 'static> VfsNodeOps for DirNodeAdapter<T> {
+    crate::__forward_dir_node_ops!(T, self => &*self.0, self.0.clone());
+    crate::impl_vfs_dir_default! {}
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::sync::Arc;
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::{DirOrder, VfsError, VfsNodePerm, VfsNodeRefExt};
+
+    struct File;
+
+    impl VfsFileNodeOps for File {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_file(3, 0))
+        }
+
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            buf[..3].copy_from_slice(b"abc");
+            Ok(3)
+        }
+    }
+
+    struct Dir;
+
+    impl VfsDirNodeOps for Dir {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new(
+                VfsNodePerm::default_dir(),
+                VfsNodeType::Dir,
+                0,
+                0,
+            ))
+        }
+
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            match path {
+                "file" => Ok(Arc::new(FileNodeAdapter::new(File))),
+                _ => Err(VfsError::NotFound),
+            }
+        }
+
+        fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+            let names = ["b", "a", ".", ".."];
+            let mut count = 0;
+            for (ent, name) in dirents.iter_mut().zip(names.iter().skip(start_idx)) {
+                *ent = VfsDirEntry::new(name, VfsNodeType::File);
+                count += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    struct Counter(spin::Mutex<u32>);
+
+    impl VfsFileNodeOps for Counter {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_file(*self.0.lock() as u64, 0))
+        }
+
+        fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            *self.0.lock() += buf.len() as u32;
+            Ok(buf.len())
+        }
+    }
+
+    crate::impl_vfs_file_node!(Counter);
+
+    #[test]
+    fn test_adapters() {
+        let dir: VfsNodeRef = Arc::new(DirNodeAdapter::new(Dir));
+        assert!(dir.get_attr().unwrap().is_dir());
+        assert_eq!(dir.read_at(0, &mut [0; 4]), Err(VfsError::IsADirectory));
+        assert_eq!(dir.truncate(0), Err(VfsError::IsADirectory));
+        assert_eq!(dir.read_link(&mut [0; 4]), Err(VfsError::InvalidInput));
+        assert_eq!(
+            dir.create("x", VfsNodeType::File),
+            Err(VfsError::Unsupported)
+        );
+        assert_eq!(dir.clone().lookup("x").err(), Some(VfsError::NotFound));
+        assert!(dir.downcast_ref::<DirNodeAdapter<Dir>>().is_some());
+
+        let file = dir.lookup("file").unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(file.read_at(0, &mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"abc");
+        assert_eq!(file.write_at(0, b"x"), Err(VfsError::InvalidInput));
+        assert_eq!(
+            file.create("x", VfsNodeType::File),
+            Err(VfsError::NotADirectory)
+        );
+        assert_eq!(file.read_dir(0, &mut []), Err(VfsError::NotADirectory));
+        assert_eq!(
+            file.clone().lookup("x").err(),
+            Some(VfsError::NotADirectory)
+        );
+        let file = file.downcast::<FileNodeAdapter<File>>().unwrap();
+        assert_eq!(file.inner().get_attr().unwrap().size(), 3);
+    }
+
+    #[test]
+    fn test_adapter_fallbacks() {
+        let dir: VfsNodeRef = Arc::new(DirNodeAdapter::new(Dir));
+        let mut dirents: Vec<_> = (0..4).map(|_| VfsDirEntry::default()).collect();
+        let opts = ReadDirOptions::new().order(DirOrder::Name);
+        assert_eq!(dir.read_dir_opts(0, &mut dirents, &opts), Ok(4));
+        let names: Vec<_> = dirents.iter().map(|e| e.name_as_bytes()).collect();
+        assert_eq!(names, [&b"."[..], b"..", b"a", b"b"]);
+
+        let mut stream = dir.clone().open_dir().unwrap();
+        assert_eq!(stream.next_entry().unwrap().unwrap().name_as_bytes(), b"b");
+
+        let (node, consumed) = dir.lookup_step("file").unwrap();
+        assert_eq!(consumed, 1);
+        assert_eq!(node.get_attr().unwrap().size(), 3);
+
+        let counter: VfsNodeRef = Arc::new(Counter(spin::Mutex::new(0)));
+        assert_eq!(node.copy_file_range(0, &*counter, 0, 3), Ok(3));
+        assert_eq!(counter.get_attr().unwrap().size(), 3);
+        assert_eq!(
+            counter.clone().lookup("x").err(),
+            Some(VfsError::NotADirectory)
+        );
+        assert!(counter.downcast::<Counter>().is_ok());
+    }
+}
@@ -0,0 +1,256 @@
+//! Notification of filesystem changes, like Linux `inotify`.
+//!
+//! A watch is added on a node with [`VfsOps::subscribe`], and reports the
+//! changes matching its [`WatchMask`] to a [`WatchSink`]. Watches follow
+//! the node, not its path: a watched directory reports the changes of its
+//! entries, and a watched file the changes of its content, wherever it is
+//! moved.
+//!
+//! Filesystems keep their watches in a [`WatchList`], which does the
+//! bookkeeping and dispatching of events.
+//!
+//! [`VfsOps::subscribe`]: crate::VfsOps::subscribe
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+
+use spin::RwLock;
+
+bitflags::bitflags! {
+    /// The kinds of changes reported by a watch.
+    ///
+    /// The values are those of the Linux `IN_*` constants, to ease the
+    /// emulation of `inotify`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct WatchMask: u32 {
+        /// The content of a file was changed (`IN_MODIFY`).
+        const MODIFY = 0x2;
+        /// An entry was moved out of the directory (`IN_MOVED_FROM`).
+        const MOVED_FROM = 0x40;
+        /// An entry was moved into the directory (`IN_MOVED_TO`).
+        const MOVED_TO = 0x80;
+        /// An entry was created in the directory (`IN_CREATE`).
+        const CREATE = 0x100;
+        /// An entry was removed from the directory (`IN_DELETE`).
+        const DELETE = 0x200;
+    }
+}
+
+/// The identifier of a watch, unique in its filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WatchId(u64);
+
+impl WatchId {
+    /// Returns the raw value of the identifier, such as to report it as an
+    /// `inotify` watch descriptor.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+/// A change reported to a [`WatchSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchEvent<'a> {
+    /// The kind of the change, a single flag.
+    pub mask: WatchMask,
+    /// The name of the entry concerned, for the changes of the entries of a
+    /// watched directory, or `None` for the changes of the watched node
+    /// itself.
+    pub name: Option<&'a str>,
+    /// A value shared by the [`WatchMask::MOVED_FROM`] and
+    /// [`WatchMask::MOVED_TO`] events of a single rename, or `0`.
+    pub cookie: u32,
+    /// Whether the entry concerned is a directory.
+    pub is_dir: bool,
+}
+
+/// A receiver of the events of watches, such as an `inotify` instance.
+///
+/// Sinks are called without any lock of the filesystem held, so they may
+/// access the filesystem.
+pub trait WatchSink: Send + Sync {
+    /// Called when a change matching the mask of watch `id` happens.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The watch reporting the change
+    /// * `event` - The change
+    fn event(&self, id: WatchId, event: &WatchEvent);
+}
+
+struct Watch {
+    id: WatchId,
+    ino: u64,
+    mask: WatchMask,
+    sink: Arc<dyn WatchSink>,
+}
+
+/// The watches of a filesystem, keyed by inode number.
+///
+/// Notifying an event is cheap when there is no watch, so filesystems can
+/// notify every change unconditionally.
+pub struct WatchList {
+    watches: RwLock<Vec<Watch>>,
+    len: AtomicUsize,
+    next_id: AtomicU64,
+    next_cookie: AtomicU32,
+}
+
+impl WatchList {
+    /// Creates a list with no watches.
+    pub const fn new() -> Self {
+        Self {
+            watches: RwLock::new(Vec::new()),
+            len: AtomicUsize::new(0),
+            next_id: AtomicU64::new(1),
+            next_cookie: AtomicU32::new(1),
+        }
+    }
+
+    /// Adds a watch on the node with inode number `ino`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ino` - The inode number of the watched node
+    /// * `mask` - The changes to report
+    /// * `sink` - The receiver of the events
+    ///
+    /// # Returns
+    ///
+    /// Returns the identifier of the new watch.
+    pub fn add(&self, ino: u64, mask: WatchMask, sink: Arc<dyn WatchSink>) -> WatchId {
+        let id = WatchId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut watches = self.watches.write();
+        watches.push(Watch {
+            id,
+            ino,
+            mask,
+            sink,
+        });
+        self.len.store(watches.len(), Ordering::Release);
+        id
+    }
+
+    /// Removes a watch.
+    ///
+    /// # Returns
+    ///
+    /// Returns whether the watch existed.
+    pub fn remove(&self, id: WatchId) -> bool {
+        let mut watches = self.watches.write();
+        let len = watches.len();
+        watches.retain(|watch| watch.id != id);
+        self.len.store(watches.len(), Ordering::Release);
+        watches.len() != len
+    }
+
+    /// Removes all watches.
+    pub fn clear(&self) {
+        self.watches.write().clear();
+        self.len.store(0, Ordering::Release);
+    }
+
+    /// Returns whether there are no watches.
+    pub fn is_empty(&self) -> bool {
+        self.len.load(Ordering::Acquire) == 0
+    }
+
+    /// Returns a new cookie to pair the events of a rename.
+    pub fn next_cookie(&self) -> u32 {
+        self.next_cookie.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Reports a change of the node with inode number `ino` to the watches
+    /// on it whose mask contains `event.mask`.
+    ///
+    /// The sinks are called after the list is unlocked.
+    ///
+    /// # Arguments
+    ///
+    /// * `ino` - The inode number of the changed node
+    /// * `event` - The change
+    pub fn notify(&self, ino: u64, event: &WatchEvent) {
+        if self.is_empty() {
+            return;
+        }
+        let sinks: Vec<_> = self
+            .watches
+            .read()
+            .iter()
+            .filter(|watch| watch.ino == ino && watch.mask.contains(event.mask))
+            .map(|watch| (watch.id, watch.sink.clone()))
+            .collect();
+        for (id, sink) in sinks {
+            sink.event(id, event);
+        }
+    }
+}
+
+impl Default for WatchList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use alloc::string::{String, ToString};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(WatchId, WatchMask, Option<String>)>>);
+
+    impl WatchSink for Recorder {
+        fn event(&self, id: WatchId, event: &WatchEvent) {
+            let name = event.name.map(ToString::to_string);
+            self.0.lock().unwrap().push((id, event.mask, name));
+        }
+    }
+
+    fn event(mask: WatchMask, name: Option<&str>) -> WatchEvent<'_> {
+        WatchEvent {
+            mask,
+            name,
+            cookie: 0,
+            is_dir: false,
+        }
+    }
+
+    #[test]
+    fn test_watch_list() {
+        let list = WatchList::new();
+        assert!(list.is_empty());
+        // no watches: nothing to do
+        list.notify(1, &event(WatchMask::CREATE, Some("a")));
+
+        let sink = Arc::new(Recorder::default());
+        let dir = list.add(1, WatchMask::CREATE | WatchMask::DELETE, sink.clone());
+        let file = list.add(2, WatchMask::MODIFY, sink.clone());
+        assert_ne!(dir, file);
+        assert!(!list.is_empty());
+
+        list.notify(1, &event(WatchMask::CREATE, Some("a")));
+        list.notify(1, &event(WatchMask::MOVED_TO, Some("b")));
+        list.notify(2, &event(WatchMask::MODIFY, None));
+        list.notify(3, &event(WatchMask::MODIFY, None));
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            [
+                (dir, WatchMask::CREATE, Some("a".into())),
+                (file, WatchMask::MODIFY, None),
+            ]
+        );
+
+        assert!(list.remove(dir));
+        assert!(!list.remove(dir));
+        list.notify(1, &event(WatchMask::DELETE, Some("a")));
+        assert_eq!(sink.0.lock().unwrap().len(), 2);
+        list.clear();
+        assert!(list.is_empty());
+        assert_ne!(list.next_cookie(), list.next_cookie());
+    }
+}
@@ -0,0 +1,255 @@
+use axerrno::ax_err;
+
+use crate::handle::VfsFileHandle;
+use crate::{OpenFlags, VfsError, VfsNodeRef, VfsNodeType, VfsResult};
+
+/// Options to open a file, like [`std::fs::OpenOptions`].
+///
+/// This is the common description of an `open()` for all the filesystems:
+/// the node receives the access mode and options as the [`OpenFlags`] of
+/// [`flags()`](Self::flags) in [`VfsNodeOps::open`], while the creation of
+/// a missing file is done by [`open()`](Self::open) on the directory.
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::{OpenOptions, VfsNodeRef, VfsResult};
+/// # fn example(root: VfsNodeRef) -> VfsResult {
+/// let log = OpenOptions::new()
+///     .append(true)
+///     .create(true)
+///     .open(&root, "var/log/messages")?;
+/// log.write(b"booted\n")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`std::fs::OpenOptions`]: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html
+/// [`VfsNodeOps::open`]: crate::VfsNodeOps::open
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl OpenOptions {
+    /// Creates a blank set of options, with every option disabled.
+    pub const fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+        }
+    }
+
+    /// Converts the flags of a Linux `open()` system call.
+    ///
+    /// Unlike [`OpenFlags::from_linux`], `O_CREAT` is kept, as
+    /// [`create()`](Self::create), and `O_CREAT | O_EXCL` becomes
+    /// [`create_new()`](Self::create_new). Flags without a counterpart are
+    /// ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::OpenOptions;
+    ///
+    /// // O_WRONLY | O_CREAT | O_TRUNC
+    /// let opts = OpenOptions::from_linux(0o1101);
+    /// assert_eq!(opts, *OpenOptions::new().write(true).create(true).truncate(true));
+    /// ```
+    pub fn from_linux(flags: u32) -> Self {
+        let access = OpenFlags::from_linux(flags);
+        let create = flags & 0o100 != 0;
+        Self {
+            read: access.contains(OpenFlags::READ),
+            write: access.contains(OpenFlags::WRITE),
+            append: access.contains(OpenFlags::APPEND),
+            truncate: access.contains(OpenFlags::TRUNC),
+            create,
+            create_new: create && access.contains(OpenFlags::EXCL),
+        }
+    }
+
+    /// Sets the option for read access.
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    /// Sets the option for write access.
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Sets the option for append mode, which implies write access.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Sets the option to truncate the file to zero length, which requires
+    /// write access.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Sets the option to create the file if it does not exist, which
+    /// requires write access.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Sets the option to create the file, failing if it exists, like
+    /// `O_CREAT | O_EXCL`. [`create()`](Self::create) and
+    /// [`truncate()`](Self::truncate) are then ignored.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Returns the flags passed to the node when it is opened.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the options are inconsistent:
+    /// no access mode, or truncation or creation without write access.
+    pub fn flags(&self) -> VfsResult<OpenFlags> {
+        let writable = self.write || self.append;
+        if !self.read && !writable {
+            return ax_err!(InvalidInput);
+        }
+        if !writable && (self.truncate || self.create || self.create_new) {
+            return ax_err!(InvalidInput);
+        }
+        let mut flags = OpenFlags::empty();
+        flags.set(OpenFlags::READ, self.read);
+        flags.set(OpenFlags::WRITE, writable);
+        flags.set(OpenFlags::APPEND, self.append);
+        flags.set(OpenFlags::TRUNC, self.truncate && !self.create_new);
+        flags.set(OpenFlags::EXCL, self.create_new);
+        Ok(flags)
+    }
+
+    /// Opens the file at `path`, relative to `dir`, with these options.
+    ///
+    /// A missing file is created as a regular file if
+    /// [`create()`](Self::create) or [`create_new()`](Self::create_new) is
+    /// set. The latter uses
+    /// [`create_exclusive()`](crate::VfsNodeOps::create_exclusive) when the
+    /// directory supports it, so that only one of concurrent opens
+    /// succeeds.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to resolve `path` in
+    /// * `path` - The path of the file
+    ///
+    /// # Returns
+    ///
+    /// Returns a handle of the open on success, or an error otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`flags()`](Self::flags), of the lookup,
+    /// creation and [`open()`](crate::VfsNodeOps::open) of the file, or
+    /// [`VfsError::AlreadyExists`] if [`create_new()`](Self::create_new) is
+    /// set and the file exists.
+    pub fn open(&self, dir: &VfsNodeRef, path: &str) -> VfsResult<VfsFileHandle> {
+        let flags = self.flags()?;
+        if self.create_new {
+            match dir.create_exclusive(path, VfsNodeType::File) {
+                Err(VfsError::Unsupported) => match dir.clone().lookup(path) {
+                    Ok(_) => return ax_err!(AlreadyExists),
+                    Err(VfsError::NotFound) => dir.create(path, VfsNodeType::File)?,
+                    Err(e) => return Err(e),
+                },
+                res => res?,
+            }
+        }
+        let node = match dir.clone().lookup(path) {
+            Err(VfsError::NotFound) if self.create && !self.create_new => {
+                match dir.create(path, VfsNodeType::File) {
+                    Ok(()) | Err(VfsError::AlreadyExists) => {}
+                    Err(e) => return Err(e),
+                }
+                dir.clone().lookup(path)?
+            }
+            res => res?,
+        };
+        VfsFileHandle::open_with(node, flags)
+    }
+}
+
+impl TryFrom<&OpenOptions> for OpenFlags {
+    type Error = VfsError;
+
+    fn try_from(opts: &OpenOptions) -> VfsResult<Self> {
+        opts.flags()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_options_flags() {
+        assert_eq!(OpenOptions::new().read(true).flags(), Ok(OpenFlags::READ));
+        assert_eq!(
+            OpenOptions::new().append(true).create(true).flags(),
+            Ok(OpenFlags::WRITE | OpenFlags::APPEND)
+        );
+        assert_eq!(
+            OpenOptions::new()
+                .write(true)
+                .truncate(true)
+                .create_new(true)
+                .flags(),
+            Ok(OpenFlags::WRITE | OpenFlags::EXCL)
+        );
+        assert_eq!(
+            OpenFlags::try_from(&*OpenOptions::new().read(true).write(true).truncate(true)),
+            Ok(OpenFlags::READ | OpenFlags::WRITE | OpenFlags::TRUNC)
+        );
+        assert_eq!(OpenOptions::new().flags(), Err(VfsError::InvalidInput));
+        assert_eq!(
+            OpenOptions::new().read(true).truncate(true).flags(),
+            Err(VfsError::InvalidInput)
+        );
+        assert_eq!(
+            OpenOptions::new().read(true).create(true).flags(),
+            Err(VfsError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_open_options_from_linux() {
+        // O_RDWR | O_CREAT | O_EXCL | O_APPEND
+        let opts = OpenOptions::from_linux(0o2302);
+        assert_eq!(
+            opts,
+            *OpenOptions::new()
+                .read(true)
+                .write(true)
+                .append(true)
+                .create(true)
+                .create_new(true)
+        );
+        // O_RDONLY | O_EXCL, without O_CREAT
+        assert_eq!(
+            OpenOptions::from_linux(0o200),
+            *OpenOptions::new().read(true)
+        );
+    }
+}
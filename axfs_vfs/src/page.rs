@@ -0,0 +1,249 @@
+//! Pages of nodes, for memory mappings.
+//!
+//! The kernel maps the contents of a node with
+//! [`VfsNodeOps::get_page`](crate::VfsNodeOps::get_page), which returns one
+//! [`VfsPage`] per [`PAGE_SIZE`] bytes of the node:
+//!
+//! - [`VfsPage::Frame`]: memory owned by the node, such as the pages of a
+//!   RAM file. Mapping it shares the storage of the node, like a
+//!   `MAP_SHARED` mapping of a file: writes through the mapping are seen by
+//!   reads of the node, and the other way around.
+//! - [`VfsPage::Phys`]: a physical page frame, such as device memory.
+//! - [`VfsPage::Zero`]: a page of zeros, for anonymous mappings such as
+//!   those of `/dev/zero`.
+//!
+//! Read-only views of a node return [`VfsPage::ReadOnlyFrame`] and
+//! [`VfsPage::ReadOnlyPhys`] instead, see
+//! [`VfsPage::into_read_only()`]: the kernel must map them without write
+//! access, so that writes through the mapping fault.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::cell::UnsafeCell;
+
+use spin::Mutex;
+
+/// The size of the pages returned by
+/// [`VfsNodeOps::get_page`](crate::VfsNodeOps::get_page), in bytes.
+pub const PAGE_SIZE: usize = 4096;
+
+/// A function run with the bytes of a [`PageFrame`] just before it is freed.
+pub type ReleaseHook = Box<dyn FnOnce(&mut [u8; PAGE_SIZE]) + Send>;
+
+/// A page of memory that can be shared between a node and memory mappings.
+///
+/// The bytes of the page may be changed through a mapping at any time, so
+/// they are only accessible through a raw pointer, or the unsafe
+/// [`read()`](Self::read) and [`write()`](Self::write) methods.
+///
+/// A node removing a page that is still mapped can set a
+/// [release hook](Self::set_release_hook), run when the last mapping drops
+/// the page, for example to zeroize it.
+pub struct PageFrame {
+    data: Box<UnsafeCell<[u8; PAGE_SIZE]>>,
+    release: Mutex<Option<ReleaseHook>>,
+}
+
+// SAFETY: the bytes are only accessed through raw pointers and unsafe
+// methods, whose callers must prevent data races.
+unsafe impl Send for PageFrame {}
+// SAFETY: see above.
+unsafe impl Sync for PageFrame {}
+
+impl PageFrame {
+    /// Creates a new page filled with zeros.
+    ///
+    /// # Returns
+    ///
+    /// A new `PageFrame`.
+    pub fn new() -> Self {
+        Self {
+            data: Box::new(UnsafeCell::new([0; PAGE_SIZE])),
+            release: Mutex::new(None),
+        }
+    }
+
+    /// Sets the function run with the bytes of the page just before it is
+    /// freed, replacing the previous one.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - The function to run
+    pub fn set_release_hook(&self, hook: ReleaseHook) {
+        *self.release.lock() = Some(hook);
+    }
+
+    /// Returns a pointer to the first byte of the page.
+    ///
+    /// The pointer is valid for reads and writes of [`PAGE_SIZE`] bytes as
+    /// long as the page is alive, and its address never changes.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.data.get().cast()
+    }
+
+    /// Returns the bytes of the page, which are not shared while it is
+    /// borrowed mutably.
+    pub fn get_mut(&mut self) -> &mut [u8; PAGE_SIZE] {
+        self.data.get_mut()
+    }
+
+    /// Copies the bytes at `offset` in the page into `buf`.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the page
+    /// * `buf` - The buffer to copy the bytes into
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is not inside the page.
+    ///
+    /// # Safety
+    ///
+    /// The range must not be written concurrently.
+    pub unsafe fn read(&self, offset: usize, buf: &mut [u8]) {
+        assert!(offset <= PAGE_SIZE && buf.len() <= PAGE_SIZE - offset);
+        // SAFETY: the range is inside the page, and is not written
+        // concurrently as guaranteed by the caller.
+        unsafe {
+            core::ptr::copy_nonoverlapping(self.as_ptr().add(offset), buf.as_mut_ptr(), buf.len())
+        };
+    }
+
+    /// Copies `buf` into the page at `offset`.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The offset in the page
+    /// * `buf` - The bytes to copy into the page
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is not inside the page.
+    ///
+    /// # Safety
+    ///
+    /// The range must not be read or written concurrently.
+    pub unsafe fn write(&self, offset: usize, buf: &[u8]) {
+        assert!(offset <= PAGE_SIZE && buf.len() <= PAGE_SIZE - offset);
+        // SAFETY: the range is inside the page, and is not accessed
+        // concurrently as guaranteed by the caller.
+        unsafe {
+            core::ptr::copy_nonoverlapping(buf.as_ptr(), self.as_ptr().add(offset), buf.len())
+        };
+    }
+}
+
+impl Default for PageFrame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PageFrame {
+    fn drop(&mut self) {
+        if let Some(hook) = self.release.get_mut().take() {
+            hook(self.data.get_mut());
+        }
+    }
+}
+
+/// A page of a node to map in memory, see the [module-level
+/// documentation](self).
+#[derive(Clone)]
+pub enum VfsPage {
+    /// Memory owned by the node, shared with the mapping.
+    Frame(Arc<PageFrame>),
+    /// The physical address of a page frame.
+    Phys(usize),
+    /// A page of zeros, private to the mapping.
+    Zero,
+    /// Memory owned by the node, shared with the mapping, which must be
+    /// mapped read-only.
+    ReadOnlyFrame(Arc<PageFrame>),
+    /// The physical address of a page frame, which must be mapped
+    /// read-only.
+    ReadOnlyPhys(usize),
+}
+
+impl VfsPage {
+    /// Returns the page with write access removed.
+    ///
+    /// [`VfsPage::Zero`] is returned as is, since it is private to the
+    /// mapping.
+    pub fn into_read_only(self) -> Self {
+        match self {
+            Self::Frame(frame) => Self::ReadOnlyFrame(frame),
+            Self::Phys(paddr) => Self::ReadOnlyPhys(paddr),
+            page => page,
+        }
+    }
+
+    /// Returns whether the page may be mapped with write access.
+    pub fn is_writable(&self) -> bool {
+        !matches!(self, Self::ReadOnlyFrame(_) | Self::ReadOnlyPhys(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_frame() {
+        let mut frame = PageFrame::new();
+        assert!(frame.get_mut().iter().all(|&b| b == 0));
+        let mut buf = [0; 4];
+        // SAFETY: the frame is not shared.
+        unsafe {
+            frame.write(PAGE_SIZE - 4, b"tail");
+            frame.read(PAGE_SIZE - 4, &mut buf);
+        }
+        assert_eq!(&buf, b"tail");
+        // SAFETY: the frame is alive and not shared.
+        assert_eq!(unsafe { *frame.as_ptr().add(PAGE_SIZE - 1) }, b'l');
+    }
+
+    #[test]
+    fn test_page_frame_release_hook() {
+        let released = Arc::new(Mutex::new(None));
+        let frame = Arc::new(PageFrame::new());
+        // SAFETY: the frame is not shared yet.
+        unsafe { frame.write(0, b"secret") };
+        let mapping = frame.clone();
+        let recorded = released.clone();
+        frame.set_release_hook(Box::new(move |data| {
+            data.fill(0);
+            *recorded.lock() = Some(data.to_vec());
+        }));
+        drop(frame);
+        assert!(released.lock().is_none());
+        drop(mapping);
+        let bytes = released.lock().take().unwrap();
+        assert!(bytes.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_read_only_page() {
+        let frame = Arc::new(PageFrame::new());
+        let page = VfsPage::Frame(frame.clone()).into_read_only();
+        assert!(!page.is_writable());
+        let VfsPage::ReadOnlyFrame(shared) = page.into_read_only() else {
+            panic!("expected a read-only frame");
+        };
+        assert!(Arc::ptr_eq(&frame, &shared));
+        assert!(matches!(
+            VfsPage::Phys(0x1000).into_read_only(),
+            VfsPage::ReadOnlyPhys(0x1000)
+        ));
+        assert!(VfsPage::Zero.into_read_only().is_writable());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_page_frame_out_of_range() {
+        let frame = PageFrame::new();
+        // SAFETY: the frame is not shared.
+        unsafe { frame.write(PAGE_SIZE - 1, b"xy") };
+    }
+}
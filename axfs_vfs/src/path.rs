@@ -1,22 +1,415 @@
 //! Utilities for path manipulation.
+//!
+//! [`Path`] and [`PathBuf`] are the `no_std` counterparts of the types of
+//! `std::path`, for the UTF-8, `/`-separated paths of the VFS. All their
+//! operations are lexical: they never access a filesystem.
+//!
+//! [`normalize`] removes the `.`, `..` and repeated separators of a path
+//! lexically, while [`canonicalize`] resolves a path through the lookups
+//! of the filesystem, following symbolic links, like `realpath(3)`.
+//! [`lookup_step`] resolves a path up to the first mount point it crosses.
 
+use alloc::borrow::{Borrow, ToOwned};
 use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
 
-/// Returns the canonical form of the path with all intermediate components
-/// normalized.
+use crate::limits::{self, MAX_PATH, MAX_SYMLINK_DEPTH};
+use crate::{VfsError, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsResult};
+
+/// A component of a [`Path`], as returned by [`Path::components`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Component<'a> {
+    /// The leading `/` of an absolute path.
+    RootDir,
+    /// A `.` at the start of a relative path.
+    CurDir,
+    /// A `..`.
+    ParentDir,
+    /// A file or directory name.
+    Normal(&'a str),
+}
+
+impl<'a> Component<'a> {
+    /// Returns the component as a string slice.
+    pub fn as_str(&self) -> &'a str {
+        match self {
+            Self::RootDir => "/",
+            Self::CurDir => ".",
+            Self::ParentDir => "..",
+            Self::Normal(name) => name,
+        }
+    }
+}
+
+/// The iterator over the [`Component`]s of a [`Path`].
+///
+/// Repeated separators and the `.` components, except a leading one, are
+/// skipped, as `std::path::Components` does.
+#[derive(Debug, Clone)]
+pub struct Components<'a> {
+    rest: &'a str,
+    front: bool,
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = Component<'a>;
+
+    fn next(&mut self) -> Option<Component<'a>> {
+        if self.front {
+            self.front = false;
+            if self.rest.starts_with('/') {
+                self.rest = self.rest.trim_start_matches('/');
+                return Some(Component::RootDir);
+            }
+            if let Some(rest) = self.rest.strip_prefix('.') {
+                if rest.is_empty() || rest.starts_with('/') {
+                    self.rest = rest.trim_start_matches('/');
+                    return Some(Component::CurDir);
+                }
+            }
+        }
+        loop {
+            let (name, rest) = self.rest.split_once('/').unwrap_or((self.rest, ""));
+            self.rest = rest.trim_start_matches('/');
+            match name {
+                "" => return None,
+                "." => continue,
+                ".." => return Some(Component::ParentDir),
+                _ => return Some(Component::Normal(name)),
+            }
+        }
+    }
+}
+
+/// A borrowed path, the counterpart of `str` for [`PathBuf`].
+///
+/// Paths are compared as strings, so `a//b` and `a/b` are different
+/// paths with the same [`components()`](Self::components).
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::path::{Component, Path};
+///
+/// let path = Path::new("/usr/lib/libc.so.6");
+/// assert!(path.is_absolute());
+/// assert_eq!(path.parent(), Some(Path::new("/usr/lib")));
+/// assert_eq!(path.file_name(), Some("libc.so.6"));
+/// assert_eq!(path.extension(), Some("6"));
+/// assert_eq!(path.components().nth(1), Some(Component::Normal("usr")));
+/// assert_eq!(path.join("../include").as_str(), "/usr/lib/libc.so.6/../include");
+/// ```
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct Path(str);
+
+impl Path {
+    /// Wraps a string slice as a path.
+    pub fn new<S: AsRef<str> + ?Sized>(path: &S) -> &Path {
+        let path: &str = path.as_ref();
+        // SAFETY: `Path` is a `repr(transparent)` wrapper of `str`.
+        unsafe { &*(path as *const str as *const Path) }
+    }
+
+    /// Returns the path as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns whether the path starts with `/`.
+    pub fn is_absolute(&self) -> bool {
+        self.0.starts_with('/')
+    }
+
+    /// Returns whether the path does not start with `/`.
+    pub fn is_relative(&self) -> bool {
+        !self.is_absolute()
+    }
+
+    /// Returns the iterator over the components of the path.
+    pub fn components(&self) -> Components<'_> {
+        Components {
+            rest: &self.0,
+            front: true,
+        }
+    }
+
+    /// Splits the first name from the rest of the path.
+    ///
+    /// This is how directories walk a relative path one component at a
+    /// time. Leading separators are skipped, and the rest is `None` if the
+    /// name is the last component.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::path::Path;
+    ///
+    /// assert_eq!(Path::new("/a/b/c").split_first(), ("a", Some(Path::new("b/c"))));
+    /// assert_eq!(Path::new("a").split_first(), ("a", None));
+    /// ```
+    pub fn split_first(&self) -> (&str, Option<&Path>) {
+        let path = self.0.trim_start_matches('/');
+        match path.split_once('/') {
+            Some((name, rest)) => (name, Some(Path::new(rest))),
+            None => (path, None),
+        }
+    }
+
+    /// Returns the path without its last component.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the path is empty or the root, and an empty path
+    /// for a relative path of one component.
+    pub fn parent(&self) -> Option<&Path> {
+        let path = self.0.trim_end_matches('/');
+        if path.is_empty() {
+            return None;
+        }
+        Some(Path::new(match path.rfind('/') {
+            Some(pos) => match path[..pos].trim_end_matches('/') {
+                "" => "/",
+                parent => parent,
+            },
+            None => "",
+        }))
+    }
+
+    /// Returns the last component of the path, if it is a name.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the path is empty, the root, or ends with `.` or
+    /// `..`.
+    pub fn file_name(&self) -> Option<&str> {
+        let path = self.0.trim_end_matches('/');
+        let name = path.rsplit('/').next().unwrap_or(path);
+        match name {
+            "" | "." | ".." => None,
+            _ => Some(name),
+        }
+    }
+
+    /// Returns the file name without its extension.
+    pub fn file_stem(&self) -> Option<&str> {
+        let name = self.file_name()?;
+        Some(split_extension(name).0)
+    }
+
+    /// Returns the extension of the file name, after its last `.`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `None` if the file name has no `.`, or only a leading one,
+    /// such as `.profile`.
+    pub fn extension(&self) -> Option<&str> {
+        split_extension(self.file_name()?).1
+    }
+
+    /// Returns `path` appended to this path, see [`PathBuf::push`].
+    pub fn join<P: AsRef<Path> + ?Sized>(&self, path: &P) -> PathBuf {
+        let mut buf = self.to_path_buf();
+        buf.push(path);
+        buf
+    }
+
+    /// Returns the normal form of the path, see [`normalize`].
+    pub fn normalize(&self) -> PathBuf {
+        PathBuf(normalize(&self.0))
+    }
+
+    /// Copies the path into a [`PathBuf`].
+    pub fn to_path_buf(&self) -> PathBuf {
+        PathBuf(String::from(&self.0))
+    }
+}
+
+/// Splits `name` into its stem and extension.
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    }
+}
+
+impl Deref for Path {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for Path {
+    fn as_ref(&self) -> &Path {
+        self
+    }
+}
+
+impl AsRef<Path> for str {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<Path> for String {
+    fn as_ref(&self) -> &Path {
+        Path::new(self)
+    }
+}
+
+impl AsRef<str> for Path {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ToOwned for Path {
+    type Owned = PathBuf;
+
+    fn to_owned(&self) -> PathBuf {
+        self.to_path_buf()
+    }
+}
+
+/// An owned, mutable path, the counterpart of `String` for [`Path`].
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::path::PathBuf;
 ///
-/// It won't force convert the path to an absolute form.
+/// let mut path = PathBuf::from("/etc");
+/// path.push("fstab");
+/// assert_eq!(path.as_str(), "/etc/fstab");
+/// assert!(path.pop());
+/// path.push("/boot");
+/// assert_eq!(path.as_str(), "/boot");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PathBuf(String);
+
+impl PathBuf {
+    /// Creates an empty path.
+    pub const fn new() -> Self {
+        Self(String::new())
+    }
+
+    /// Returns the path as a [`Path`].
+    pub fn as_path(&self) -> &Path {
+        Path::new(&self.0)
+    }
+
+    /// Appends `path` to this path, with a separator if needed.
+    ///
+    /// If `path` is absolute, it replaces this path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to append
+    pub fn push<P: AsRef<Path> + ?Sized>(&mut self, path: &P) {
+        let path = path.as_ref();
+        if path.is_absolute() {
+            self.0.clear();
+        } else if !self.0.is_empty() && !self.0.ends_with('/') {
+            self.0.push('/');
+        }
+        self.0.push_str(path.as_str());
+    }
+
+    /// Truncates the path to its [`parent()`](Path::parent).
+    ///
+    /// # Returns
+    ///
+    /// Returns `false`, leaving the path unchanged, if it has no parent.
+    pub fn pop(&mut self) -> bool {
+        match self.parent().map(|parent| parent.len()) {
+            Some(len) => {
+                self.0.truncate(len);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Converts the path into a [`String`].
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for PathBuf {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl Borrow<Path> for PathBuf {
+    fn borrow(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl AsRef<Path> for PathBuf {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl From<String> for PathBuf {
+    fn from(path: String) -> Self {
+        Self(path)
+    }
+}
+
+impl From<&str> for PathBuf {
+    fn from(path: &str) -> Self {
+        Self(String::from(path))
+    }
+}
+
+impl From<PathBuf> for String {
+    fn from(path: PathBuf) -> Self {
+        path.0
+    }
+}
+
+impl fmt::Display for PathBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Returns the normal form of the path, with the `.`, `..` and repeated
+/// separators collapsed.
+///
+/// This is lexical: `a/..` is removed even if `a` is a symbolic link, see
+/// [`canonicalize`] to resolve a path through the filesystem. It won't force
+/// convert the path to an absolute form, and `..` above the start of the
+/// path is dropped.
 ///
 /// # Examples
 ///
 /// ```
-/// use axfs_vfs::path::canonicalize;
+/// use axfs_vfs::path::normalize;
 ///
-/// assert_eq!(canonicalize("/path/./to//foo"), "/path/to/foo");
-/// assert_eq!(canonicalize("/./path/to/../bar.rs"), "/path/bar.rs");
-/// assert_eq!(canonicalize("./foo/./bar"), "foo/bar");
+/// assert_eq!(normalize("/path/./to//foo"), "/path/to/foo");
+/// assert_eq!(normalize("/./path/to/../bar.rs"), "/path/bar.rs");
+/// assert_eq!(normalize("./foo/./bar"), "foo/bar");
 /// ```
-pub fn canonicalize(path: &str) -> String {
+pub fn normalize(path: &str) -> String {
     let mut buf = String::new();
     let is_absolute = path.starts_with('/');
     for part in path.split('/') {
@@ -51,47 +444,255 @@ pub fn canonicalize(path: &str) -> String {
     buf
 }
 
+/// Resolves `path` through the lookups of the filesystem of `root`.
+///
+/// Like `realpath(3)`, every component must exist, symbolic links are
+/// followed, and `..` goes to the parent of the directory actually reached.
+/// Relative paths, and absolute symbolic link targets, start at `root`.
+///
+/// # Arguments
+///
+/// * `root` - The root directory
+/// * `path` - The path to resolve
+///
+/// # Returns
+///
+/// Returns the absolute path of the node, without symbolic links.
+///
+/// # Errors
+///
+/// Returns [`VfsError::NotFound`] if a component does not exist,
+/// [`VfsError::NotADirectory`] if a component other than the last is not a
+/// directory, [`VfsError::FilesystemLoop`] if more than
+/// [`MAX_SYMLINK_DEPTH`] symbolic links are followed, or
+/// [`VfsError::NameTooLong`] if the path exceeds the [`limits`].
+pub fn canonicalize(root: &VfsNodeRef, path: &str) -> VfsResult<String> {
+    limits::check_path(path)?;
+    let mut pending = Vec::new();
+    push_components(&mut pending, path);
+    let mut resolved: Vec<(String, VfsNodeRef)> = Vec::new();
+    let mut links = 0;
+    while let Some(name) = pending.pop() {
+        if name == ".." {
+            resolved.pop();
+            continue;
+        }
+        let dir = resolved.last().map_or(root, |(_, node)| node);
+        if !dir.get_attr()?.is_dir() {
+            return Err(VfsError::NotADirectory);
+        }
+        let node = dir.clone().lookup(&name)?;
+        if node.get_attr()?.file_type() != VfsNodeType::SymLink {
+            resolved.push((name, node));
+            continue;
+        }
+        links += 1;
+        if links > MAX_SYMLINK_DEPTH {
+            return Err(VfsError::FilesystemLoop);
+        }
+        let mut buf = [0; MAX_PATH];
+        let len = node.read_link(&mut buf)?;
+        let target = core::str::from_utf8(&buf[..len]).map_err(|_| VfsError::InvalidData)?;
+        if target.starts_with('/') {
+            resolved.clear();
+        }
+        push_components(&mut pending, target);
+    }
+    if resolved.is_empty() {
+        return Ok(String::from("/"));
+    }
+    let mut path = String::new();
+    for (name, _) in resolved {
+        path.push('/');
+        path.push_str(&name);
+    }
+    Ok(path)
+}
+
+/// Pushes the names and `..` of `path` on `pending`, the first one last.
+fn push_components(pending: &mut Vec<String>, path: &str) {
+    let start = pending.len();
+    for component in Path::new(path).components() {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            _ => pending.push(String::from(component.as_str())),
+        }
+    }
+    pending[start..].reverse();
+}
+
+/// Looks up `path` in `dir` until it reaches a mount point.
+///
+/// This is the default implementation of
+/// [`VfsNodeOps::lookup_step`], which calls
+/// [`lookup()`](VfsNodeOps::lookup) for each [`Component::Normal`], and
+/// [`parent()`](VfsNodeOps::parent) for each [`Component::ParentDir`].
+///
+/// # Arguments
+///
+/// * `dir` - The directory to start from
+/// * `path` - The path to lookup, relative to `dir`; a leading `/` is
+///   resolved as `dir`
+///
+/// # Returns
+///
+/// Returns the node reached and the number of components of
+/// [`Path::components`] consumed.
+///
+/// # Errors
+///
+/// Returns the errors of [`lookup()`](VfsNodeOps::lookup).
+pub fn lookup_step<T: VfsNodeOps + ?Sized>(
+    dir: Arc<T>,
+    path: &str,
+) -> VfsResult<(VfsNodeRef, usize)> {
+    // `None` while at `dir`, which may not be converted to a `VfsNodeRef`
+    let mut node: Option<VfsNodeRef> = None;
+    let mut consumed = 0;
+    for component in Path::new(path).components() {
+        let next = match component {
+            Component::RootDir | Component::CurDir => {
+                consumed += 1;
+                continue;
+            }
+            Component::ParentDir => {
+                let parent = match &node {
+                    Some(node) => node.parent(),
+                    None => dir.parent(),
+                };
+                match parent {
+                    Some(parent) => parent,
+                    None => break,
+                }
+            }
+            Component::Normal(name) => match &node {
+                Some(node) => node.clone().lookup(name)?,
+                None => dir.clone().lookup(name)?,
+            },
+        };
+        consumed += 1;
+        let crossed = next.is_mount_point();
+        node = Some(next);
+        if crossed {
+            break;
+        }
+    }
+    match node {
+        Some(node) => Ok((node, consumed)),
+        None => Ok((dir.lookup(".")?, consumed)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn test_components() {
+        let components = |path| Path::new(path).components().collect::<Vec<_>>();
+        use Component::*;
+        assert_eq!(components(""), []);
+        assert_eq!(components("/"), [RootDir]);
+        assert_eq!(
+            components("//a/./b//../c/"),
+            [RootDir, Normal("a"), Normal("b"), ParentDir, Normal("c")]
+        );
+        assert_eq!(components("./a/."), [CurDir, Normal("a")]);
+        assert_eq!(components(".a/.."), [Normal(".a"), ParentDir]);
+        assert_eq!(ParentDir.as_str(), "..");
+    }
+
+    #[test]
+    fn test_split_first() {
+        let split = |path| {
+            let (name, rest) = Path::new(path).split_first();
+            (name, rest.map(Path::as_str))
+        };
+        assert_eq!(split("foo/bar"), ("foo", Some("bar")));
+        assert_eq!(split("foo"), ("foo", None));
+        assert_eq!(split("/foo/bar"), ("foo", Some("bar")));
+        assert_eq!(split("///foo/bar"), ("foo", Some("bar")));
+        assert_eq!(split("foo//bar/"), ("foo", Some("/bar/")));
+        assert_eq!(split(""), ("", None));
+        assert_eq!(split("/"), ("", None));
+        assert_eq!(split("///"), ("", None));
+    }
+
+    #[test]
+    fn test_parent_and_names() {
+        let parent = |path| Path::new(path).parent().map(Path::as_str);
+        assert_eq!(parent("/a/b"), Some("/a"));
+        assert_eq!(parent("/a//b/"), Some("/a"));
+        assert_eq!(parent("/a"), Some("/"));
+        assert_eq!(parent("a"), Some(""));
+        assert_eq!(parent("/"), None);
+        assert_eq!(parent(""), None);
+
+        let path = Path::new("dir/archive.tar.gz");
+        assert_eq!(path.file_name(), Some("archive.tar.gz"));
+        assert_eq!(path.file_stem(), Some("archive.tar"));
+        assert_eq!(path.extension(), Some("gz"));
+        assert_eq!(Path::new(".profile").extension(), None);
+        assert_eq!(Path::new(".profile").file_stem(), Some(".profile"));
+        assert_eq!(Path::new("a/..").file_name(), None);
+        assert_eq!(Path::new("/").file_name(), None);
+    }
+
+    #[test]
+    fn test_path_buf() {
+        assert_eq!(Path::new("a").join("b").as_str(), "a/b");
+        assert_eq!(Path::new("a/").join("b").as_str(), "a/b");
+        assert_eq!(Path::new("").join("b").as_str(), "b");
+        assert_eq!(Path::new("a").join("/b").as_str(), "/b");
+        assert_eq!(Path::new("/a/./b/..").normalize().as_str(), "/a");
+
+        let mut path = PathBuf::from("/a/b");
+        assert!(path.pop());
+        assert!(path.pop());
+        assert_eq!(path.as_str(), "/");
+        assert!(!path.pop());
+        let owned: PathBuf = Path::new("x").to_owned();
+        assert_eq!(String::from(owned), "x");
+    }
 
     #[test]
-    fn test_path_canonicalize() {
-        assert_eq!(canonicalize(""), "");
-        assert_eq!(canonicalize("///"), "/");
-        assert_eq!(canonicalize("//a//.//b///c//"), "/a/b/c");
-        assert_eq!(canonicalize("/a/../"), "/");
-        assert_eq!(canonicalize("/a/../..///"), "/");
-        assert_eq!(canonicalize("a/../"), "");
-        assert_eq!(canonicalize("a/..//.."), "");
-        assert_eq!(canonicalize("././a"), "a");
-        assert_eq!(canonicalize(".././a"), "a");
-        assert_eq!(canonicalize("/././a"), "/a");
-        assert_eq!(canonicalize("/abc/../abc"), "/abc");
-        assert_eq!(canonicalize("/test"), "/test");
-        assert_eq!(canonicalize("/test/"), "/test");
-        assert_eq!(canonicalize("test/"), "test");
-        assert_eq!(canonicalize("test"), "test");
-        assert_eq!(canonicalize("/test//"), "/test");
-        assert_eq!(canonicalize("/test/foo"), "/test/foo");
-        assert_eq!(canonicalize("/test/foo/"), "/test/foo");
-        assert_eq!(canonicalize("/test/foo/bar"), "/test/foo/bar");
-        assert_eq!(canonicalize("/test/foo/bar//"), "/test/foo/bar");
-        assert_eq!(canonicalize("/test//foo/bar//"), "/test/foo/bar");
-        assert_eq!(canonicalize("/test//./foo/bar//"), "/test/foo/bar");
-        assert_eq!(canonicalize("/test//./.foo/bar//"), "/test/.foo/bar");
-        assert_eq!(canonicalize("/test//./..foo/bar//"), "/test/..foo/bar");
-        assert_eq!(canonicalize("/test//./../foo/bar//"), "/foo/bar");
-        assert_eq!(canonicalize("/test/../foo"), "/foo");
-        assert_eq!(canonicalize("/test/bar/../foo"), "/test/foo");
-        assert_eq!(canonicalize("../foo"), "foo");
-        assert_eq!(canonicalize("../foo/"), "foo");
-        assert_eq!(canonicalize("/../foo"), "/foo");
-        assert_eq!(canonicalize("/../foo/"), "/foo");
-        assert_eq!(canonicalize("/../../foo"), "/foo");
-        assert_eq!(canonicalize("/bleh/../../foo"), "/foo");
-        assert_eq!(canonicalize("/bleh/bar/../../foo"), "/foo");
-        assert_eq!(canonicalize("/bleh/bar/../../foo/.."), "/");
-        assert_eq!(canonicalize("/bleh/bar/../../foo/../meh"), "/meh");
+    fn test_path_normalize() {
+        assert_eq!(normalize(""), "");
+        assert_eq!(normalize("///"), "/");
+        assert_eq!(normalize("//a//.//b///c//"), "/a/b/c");
+        assert_eq!(normalize("/a/../"), "/");
+        assert_eq!(normalize("/a/../..///"), "/");
+        assert_eq!(normalize("a/../"), "");
+        assert_eq!(normalize("a/..//.."), "");
+        assert_eq!(normalize("././a"), "a");
+        assert_eq!(normalize(".././a"), "a");
+        assert_eq!(normalize("/././a"), "/a");
+        assert_eq!(normalize("/abc/../abc"), "/abc");
+        assert_eq!(normalize("/test"), "/test");
+        assert_eq!(normalize("/test/"), "/test");
+        assert_eq!(normalize("test/"), "test");
+        assert_eq!(normalize("test"), "test");
+        assert_eq!(normalize("/test//"), "/test");
+        assert_eq!(normalize("/test/foo"), "/test/foo");
+        assert_eq!(normalize("/test/foo/"), "/test/foo");
+        assert_eq!(normalize("/test/foo/bar"), "/test/foo/bar");
+        assert_eq!(normalize("/test/foo/bar//"), "/test/foo/bar");
+        assert_eq!(normalize("/test//foo/bar//"), "/test/foo/bar");
+        assert_eq!(normalize("/test//./foo/bar//"), "/test/foo/bar");
+        assert_eq!(normalize("/test//./.foo/bar//"), "/test/.foo/bar");
+        assert_eq!(normalize("/test//./..foo/bar//"), "/test/..foo/bar");
+        assert_eq!(normalize("/test//./../foo/bar//"), "/foo/bar");
+        assert_eq!(normalize("/test/../foo"), "/foo");
+        assert_eq!(normalize("/test/bar/../foo"), "/test/foo");
+        assert_eq!(normalize("../foo"), "foo");
+        assert_eq!(normalize("../foo/"), "foo");
+        assert_eq!(normalize("/../foo"), "/foo");
+        assert_eq!(normalize("/../foo/"), "/foo");
+        assert_eq!(normalize("/../../foo"), "/foo");
+        assert_eq!(normalize("/bleh/../../foo"), "/foo");
+        assert_eq!(normalize("/bleh/bar/../../foo"), "/foo");
+        assert_eq!(normalize("/bleh/bar/../../foo/.."), "/");
+        assert_eq!(normalize("/bleh/bar/../../foo/../meh"), "/meh");
     }
 }
@@ -0,0 +1,280 @@
+//! Access control hooks consulted before delegating to a filesystem.
+//!
+//! An [`AccessPolicy`] sees every operation by its path, kind and caller, so
+//! the kernel can sandbox tasks (e.g. deny writes outside `/tmp`) without
+//! modifying each filesystem. The path resolver must call [`check_resolved`]
+//! before delegating an operation to the filesystem: it resolves the
+//! symbolic links of the path first, so that a link such as
+//! `/tmp/link -> /etc` cannot be used to escape a prefix-based policy.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::path::{canonicalize, normalize, Path};
+use crate::{Credentials, VfsError, VfsNodeRef, VfsResult};
+
+/// The kind of an operation checked by an [`AccessPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessOp {
+    /// Resolve the path, or list the directory.
+    Lookup,
+    /// Read the content of the node.
+    Read,
+    /// Write to or truncate the node.
+    Write,
+    /// Create a node at the path.
+    Create,
+    /// Remove the node at the path.
+    Remove,
+    /// Move the node at the path, or move a node to the path.
+    Rename,
+    /// Create a hard link at the path.
+    Link,
+    /// Change the attributes of the node, such as its mode or owner.
+    SetAttr,
+}
+
+impl AccessOp {
+    /// Returns whether the operation modifies the filesystem.
+    pub const fn is_mutation(self) -> bool {
+        !matches!(self, Self::Lookup | Self::Read)
+    }
+
+    /// Returns whether the operation applies to the entry at the path
+    /// rather than to the node it refers to, so that a symbolic link at the
+    /// end of the path is not followed.
+    pub const fn is_entry_op(self) -> bool {
+        matches!(
+            self,
+            Self::Create | Self::Remove | Self::Rename | Self::Link
+        )
+    }
+}
+
+/// A policy deciding whether an operation is allowed.
+pub trait AccessPolicy: Send + Sync {
+    /// Checks whether `cred` may perform `op` on `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The canonical absolute path of the target node
+    /// * `op` - The operation to perform
+    /// * `cred` - The credentials of the caller
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the operation is allowed, or the error to report
+    /// to the caller otherwise (usually [`VfsError::PermissionDenied`]).
+    fn check(&self, path: &str, op: AccessOp, cred: &Credentials) -> VfsResult;
+}
+
+/// Consults `policy` about an operation on a path.
+///
+/// The path is normalized first, so that `.` and `..` components cannot
+/// be used to escape a prefix-based policy. This is lexical: symbolic
+/// links are not resolved, see [`check_resolved`] for that.
+///
+/// # Arguments
+///
+/// * `policy` - The policy to consult, or `None` to allow everything
+/// * `path` - The absolute path of the target node
+/// * `op` - The operation to perform
+/// * `cred` - The credentials of the caller
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the operation is allowed, or the error returned by
+/// the policy.
+pub fn check_policy(
+    policy: Option<&dyn AccessPolicy>,
+    path: &str,
+    op: AccessOp,
+    cred: &Credentials,
+) -> VfsResult {
+    match policy {
+        Some(policy) => policy.check(&normalize(path), op, cred),
+        None => Ok(()),
+    }
+}
+
+/// Resolves `path` from `root` and consults `policy` about an operation on
+/// the node reached.
+///
+/// The symbolic links of the path are followed with [`canonicalize`], and
+/// the policy is consulted about the canonical path. For the operations on
+/// an entry (see [`AccessOp::is_entry_op`]), only the parent directory is
+/// resolved, so that the entry may not exist yet, and a symbolic link at
+/// the end of the path is the node checked. A rename or a link must be
+/// checked for both of its paths.
+///
+/// # Arguments
+///
+/// * `policy` - The policy to consult, or `None` to allow everything
+/// * `root` - The root directory of the caller
+/// * `path` - The path of the target node, relative to `root`
+/// * `op` - The operation to perform
+/// * `cred` - The credentials of the caller
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the operation is allowed, or an error otherwise.
+///
+/// # Errors
+///
+/// Returns the errors of [`canonicalize`], or the error returned by the
+/// policy.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::policy::{check_resolved, AccessOp, WritePrefixPolicy};
+/// use axfs_vfs::{Credentials, VfsNodeRef, VfsResult};
+///
+/// fn write_allowed(root: &VfsNodeRef, path: &str) -> VfsResult {
+///     let policy = WritePrefixPolicy::new(["/tmp"]);
+///     let cred = Credentials::new(1000, 1000);
+///     check_resolved(Some(&policy), root, path, AccessOp::Write, &cred)
+/// }
+/// ```
+pub fn check_resolved(
+    policy: Option<&dyn AccessPolicy>,
+    root: &VfsNodeRef,
+    path: &str,
+    op: AccessOp,
+    cred: &Credentials,
+) -> VfsResult {
+    let Some(policy) = policy else {
+        return Ok(());
+    };
+    let path = Path::new(path);
+    let resolved = match (op.is_entry_op(), path.parent(), path.file_name()) {
+        (true, Some(parent), Some(name)) => {
+            let mut resolved = canonicalize(root, parent.as_str())?;
+            if resolved != "/" {
+                resolved.push('/');
+            }
+            resolved.push_str(name);
+            resolved
+        }
+        _ => canonicalize(root, path.as_str())?,
+    };
+    policy.check(&resolved, op, cred)
+}
+
+/// A policy that only allows mutations under a set of path prefixes.
+///
+/// Non-mutating operations are always allowed. The superuser is not exempt.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::policy::{check_policy, AccessOp, WritePrefixPolicy};
+/// use axfs_vfs::{Credentials, VfsError};
+///
+/// let policy = WritePrefixPolicy::new(["/tmp"]);
+/// let cred = Credentials::new(1000, 1000);
+/// assert!(check_policy(Some(&policy), "/tmp/log", AccessOp::Write, &cred).is_ok());
+/// assert_eq!(
+///     check_policy(Some(&policy), "/tmp/../etc/passwd", AccessOp::Write, &cred),
+///     Err(VfsError::PermissionDenied)
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct WritePrefixPolicy {
+    prefixes: Vec<String>,
+}
+
+impl WritePrefixPolicy {
+    /// Creates a policy allowing mutations under `prefixes` only.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefixes` - The absolute paths of the writable subtrees
+    pub fn new<I, S>(prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self {
+            prefixes: prefixes
+                .into_iter()
+                .map(|p| normalize(p.as_ref()))
+                .collect(),
+        }
+    }
+
+    /// Returns whether `path` is one of the prefixes or below one of them.
+    fn is_writable(&self, path: &str) -> bool {
+        self.prefixes.iter().any(|prefix| {
+            prefix == "/"
+                || path
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        })
+    }
+}
+
+impl AccessPolicy for WritePrefixPolicy {
+    fn check(&self, path: &str, op: AccessOp, _cred: &Credentials) -> VfsResult {
+        if op.is_mutation() && !self.is_writable(path) {
+            return Err(VfsError::PermissionDenied);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_prefix_policy() {
+        let policy = WritePrefixPolicy::new(["/tmp/", "/var/log"]);
+        let cred = Credentials::root();
+        let check = |path, op| check_policy(Some(&policy), path, op, &cred);
+
+        assert_eq!(check("/tmp", AccessOp::Create), Ok(()));
+        assert_eq!(check("/tmp/a/b", AccessOp::Remove), Ok(()));
+        assert_eq!(check("/var/log/x", AccessOp::Write), Ok(()));
+        assert_eq!(check("/etc/passwd", AccessOp::Read), Ok(()));
+        assert_eq!(check("/", AccessOp::Lookup), Ok(()));
+
+        let denied = Err(VfsError::PermissionDenied);
+        assert_eq!(check("/tmpfoo", AccessOp::Create), denied);
+        assert_eq!(check("/etc/passwd", AccessOp::Write), denied);
+        assert_eq!(check("/tmp/../etc", AccessOp::Remove), denied);
+        assert_eq!(check("/etc/passwd", AccessOp::Rename), denied);
+        assert_eq!(check("/etc/hosts", AccessOp::Link), denied);
+        assert_eq!(check("/etc/passwd", AccessOp::SetAttr), denied);
+    }
+
+    #[test]
+    fn test_no_policy() {
+        let cred = Credentials::new(1, 1);
+        assert_eq!(check_policy(None, "/etc", AccessOp::Write, &cred), Ok(()));
+    }
+
+    #[test]
+    fn test_custom_policy() {
+        /// Only root may mutate anything.
+        struct RootOnly;
+
+        impl AccessPolicy for RootOnly {
+            fn check(&self, _path: &str, op: AccessOp, cred: &Credentials) -> VfsResult {
+                if op.is_mutation() && !cred.is_root() {
+                    return Err(VfsError::PermissionDenied);
+                }
+                Ok(())
+            }
+        }
+
+        let user = Credentials::new(1000, 1000);
+        let check = |op, cred| check_policy(Some(&RootOnly), "/a", op, cred);
+        assert_eq!(check(AccessOp::Read, &user), Ok(()));
+        assert_eq!(
+            check(AccessOp::Create, &user),
+            Err(VfsError::PermissionDenied)
+        );
+        assert_eq!(check(AccessOp::Create, &Credentials::root()), Ok(()));
+    }
+}
@@ -0,0 +1,393 @@
+//! Readiness of nodes that can block, for `poll()` and async executors.
+//!
+//! A node reports its current readiness with [`VfsNodeOps::poll`]. Nodes
+//! whose operations can fail with [`VfsError::WouldBlock`], such as
+//! terminals, also accept wakers with [`VfsNodeOps::register_waker`], and
+//! wake them when the readiness changes, so that executors need not poll
+//! them in a loop. Such nodes keep the wakers in a [`WakerSet`].
+//!
+//! The [`ready()`] future combines both to wait for an event.
+//!
+//! Callers that cannot await, such as the `read()` system call on a file
+//! opened without `O_NONBLOCK`, read with [`ReadHint::Blocking`] through
+//! [`VfsNodeOps::read_hinted`], which waits with [`block_on()`] while the
+//! node would block. The kernel makes such waits yield the CPU by installing
+//! a [`Blocker`] with [`set_blocker()`].
+//!
+//! [`VfsNodeOps::poll`]: crate::VfsNodeOps::poll
+//! [`VfsNodeOps::register_waker`]: crate::VfsNodeOps::register_waker
+//! [`VfsNodeOps::read_hinted`]: crate::VfsNodeOps::read_hinted
+//! [`VfsError::WouldBlock`]: crate::VfsError::WouldBlock
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use spin::{Mutex, RwLock};
+
+use crate::{OpenFlags, VfsNodeOps, VfsResult};
+
+bitflags::bitflags! {
+    /// Readiness events of a node.
+    ///
+    /// The values are those of the Linux `POLL*` constants.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct PollEvents: u32 {
+        /// Data can be read without blocking (`POLLIN`).
+        const IN = 0x1;
+        /// Urgent data can be read (`POLLPRI`).
+        const PRI = 0x2;
+        /// Data can be written without blocking (`POLLOUT`).
+        const OUT = 0x4;
+        /// An error condition is pending (`POLLERR`).
+        const ERR = 0x8;
+        /// The other end hung up (`POLLHUP`).
+        const HUP = 0x10;
+    }
+}
+
+/// The wakers waiting for readiness events of a node.
+///
+/// Each waker is woken, then forgotten, by the first
+/// [`wake()`](Self::wake) with an event it waits for, so waiters register
+/// again if they still need to wait.
+#[derive(Default)]
+pub struct WakerSet {
+    wakers: Mutex<Vec<(PollEvents, Waker)>>,
+}
+
+impl WakerSet {
+    /// Creates a set with no wakers.
+    pub const fn new() -> Self {
+        Self {
+            wakers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds a waker waiting for `events`.
+    ///
+    /// A waker already waiting is not added twice, it waits for the union
+    /// of the events instead.
+    pub fn register(&self, events: PollEvents, waker: &Waker) {
+        let mut wakers = self.wakers.lock();
+        match wakers.iter_mut().find(|(_, w)| w.will_wake(waker)) {
+            Some((waiting, _)) => *waiting |= events,
+            None => wakers.push((events, waker.clone())),
+        }
+    }
+
+    /// Wakes the wakers waiting for one of `events`.
+    ///
+    /// [`PollEvents::ERR`] and [`PollEvents::HUP`] wake every waker, as
+    /// they are reported whatever the events waited for.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of wakers woken.
+    pub fn wake(&self, events: PollEvents) -> usize {
+        let always = events.intersects(PollEvents::ERR | PollEvents::HUP);
+        let woken: Vec<_> = {
+            let mut wakers = self.wakers.lock();
+            let (woken, waiting) = core::mem::take(&mut *wakers)
+                .into_iter()
+                .partition(|(waiting, _)| always || waiting.intersects(events));
+            *wakers = waiting;
+            woken
+        };
+        let count = woken.len();
+        for (_, waker) in woken {
+            waker.wake();
+        }
+        count
+    }
+
+    /// Returns the number of wakers waiting.
+    pub fn len(&self) -> usize {
+        self.wakers.lock().len()
+    }
+
+    /// Returns whether no waker is waiting.
+    pub fn is_empty(&self) -> bool {
+        self.wakers.lock().is_empty()
+    }
+}
+
+/// Waits until `node` is ready for one of `events`.
+///
+/// The future completes with the events reported by
+/// [`VfsNodeOps::poll`] once one of `events`, [`PollEvents::ERR`] or
+/// [`PollEvents::HUP`] is set. While waiting, it is woken through
+/// [`VfsNodeOps::register_waker`].
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::poll::{ready, PollEvents};
+/// # use axfs_vfs::{VfsNodeOps, VfsResult};
+/// # async fn example(tty: &dyn VfsNodeOps) -> VfsResult {
+/// ready(tty, PollEvents::IN).await?;
+/// let mut buf = [0; 64];
+/// let n = tty.read_at(0, &mut buf)?;
+/// # let _ = n;
+/// # Ok(())
+/// # }
+/// ```
+pub fn ready<N: VfsNodeOps + ?Sized>(node: &N, events: PollEvents) -> Ready<'_, N> {
+    Ready { node, events }
+}
+
+/// The future returned by [`ready()`].
+pub struct Ready<'a, N: VfsNodeOps + ?Sized> {
+    node: &'a N,
+    events: PollEvents,
+}
+
+impl<N: VfsNodeOps + ?Sized> Ready<'_, N> {
+    /// Returns the current events of the node if they complete the wait.
+    fn check(&self) -> VfsResult<Option<PollEvents>> {
+        let current = self.node.poll()?;
+        let wanted = self.events | PollEvents::ERR | PollEvents::HUP;
+        Ok(current.intersects(wanted).then_some(current))
+    }
+}
+
+impl<N: VfsNodeOps + ?Sized> Future for Ready<'_, N> {
+    type Output = VfsResult<PollEvents>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(events) = self.check()? {
+            return Poll::Ready(Ok(events));
+        }
+        self.node.register_waker(self.events, cx.waker())?;
+        // the node may have become ready before the waker was registered
+        match self.check()? {
+            Some(events) => Poll::Ready(Ok(events)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Whether a read may wait for data, see
+/// [`VfsNodeOps::read_hinted`](crate::VfsNodeOps::read_hinted).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadHint {
+    /// The read waits until data is available, like a read of a file opened
+    /// without `O_NONBLOCK`.
+    #[default]
+    Blocking,
+    /// The read fails with [`VfsError::WouldBlock`](crate::VfsError::WouldBlock)
+    /// instead of waiting, for callers polling the node themselves.
+    NonBlocking,
+}
+
+impl ReadHint {
+    /// Returns the hint of the reads of a node opened with `flags`,
+    /// [`ReadHint::NonBlocking`] with [`OpenFlags::NONBLOCK`].
+    pub const fn from_flags(flags: OpenFlags) -> Self {
+        if flags.contains(OpenFlags::NONBLOCK) {
+            Self::NonBlocking
+        } else {
+            Self::Blocking
+        }
+    }
+}
+
+/// How the current task waits in [`block_on()`].
+///
+/// The kernel implements it with its scheduler and installs it with
+/// [`set_blocker`]. Without one, waits spin.
+pub trait Blocker: Send + Sync {
+    /// Gives the CPU to other tasks while the current task waits to be
+    /// woken.
+    ///
+    /// It may return early, the waiting task then checking again whether
+    /// it was woken.
+    fn yield_now(&self);
+}
+
+static BLOCKER: RwLock<Option<Arc<dyn Blocker>>> = RwLock::new(None);
+
+/// Installs how tasks wait in [`block_on()`].
+///
+/// # Arguments
+///
+/// * `blocker` - The new blocker, or `None` to spin
+pub fn set_blocker(blocker: Option<Arc<dyn Blocker>>) {
+    *BLOCKER.write() = blocker;
+}
+
+/// A waker recording that it was woken.
+#[derive(Default)]
+struct FlagWaker(AtomicBool);
+
+impl Wake for FlagWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// Runs `future` to completion on the current task.
+///
+/// Between two polls, the task waits for the future to wake it, yielding
+/// the CPU through the installed [`Blocker`], if any.
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::poll::{block_on, ready, PollEvents};
+/// # use axfs_vfs::{VfsNodeOps, VfsResult};
+/// # fn example(tty: &dyn VfsNodeOps) -> VfsResult {
+/// block_on(ready(tty, PollEvents::IN))?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let flag = Arc::new(FlagWaker::default());
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        while !flag.0.swap(false, Ordering::Acquire) {
+            match BLOCKER.read().clone() {
+                Some(blocker) => blocker.yield_now(),
+                None => core::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use core::sync::atomic::AtomicUsize;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_waker_set() {
+        let set = WakerSet::new();
+        let reader = Arc::new(CountingWaker::default());
+        let writer = Arc::new(CountingWaker::default());
+        let reader_waker = Waker::from(reader.clone());
+        set.register(PollEvents::IN, &reader_waker);
+        set.register(PollEvents::PRI, &reader_waker);
+        set.register(PollEvents::OUT, &Waker::from(writer.clone()));
+        assert_eq!(set.len(), 2);
+
+        assert_eq!(set.wake(PollEvents::PRI), 1);
+        assert_eq!(reader.0.load(Ordering::Relaxed), 1);
+        // woken wakers are forgotten
+        assert_eq!(set.wake(PollEvents::IN), 0);
+        assert_eq!(set.wake(PollEvents::HUP), 1);
+        assert_eq!(writer.0.load(Ordering::Relaxed), 1);
+        assert!(set.is_empty());
+    }
+
+    /// A pipe end that becomes readable once `data` is set.
+    #[derive(Default)]
+    struct Pipe {
+        data: AtomicBool,
+        wakers: WakerSet,
+    }
+
+    impl VfsNodeOps for Pipe {
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            match self.data.swap(false, Ordering::AcqRel) {
+                true => Ok(buf.len()),
+                false => Err(crate::VfsError::WouldBlock),
+            }
+        }
+
+        fn poll(&self) -> VfsResult<PollEvents> {
+            let mut events = PollEvents::OUT;
+            events.set(PollEvents::IN, self.data.load(Ordering::Acquire));
+            Ok(events)
+        }
+
+        fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+            self.wakers.register(events, waker);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_ready() {
+        let pipe = Pipe::default();
+        let counter = Arc::new(CountingWaker::default());
+        let waker = Waker::from(counter.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut future = core::pin::pin!(ready(&pipe, PollEvents::IN));
+        assert!(future.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(pipe.wakers.len(), 1);
+
+        pipe.data.store(true, Ordering::Release);
+        pipe.wakers.wake(PollEvents::IN);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            future.as_mut().poll(&mut cx),
+            Poll::Ready(Ok(PollEvents::IN | PollEvents::OUT))
+        );
+
+        // writable right away
+        let mut future = core::pin::pin!(ready(&pipe, PollEvents::OUT));
+        assert!(future.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[derive(Default)]
+    struct CountingBlocker(AtomicUsize);
+
+    impl Blocker for CountingBlocker {
+        fn yield_now(&self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            std::thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_read_hinted() {
+        assert_eq!(ReadHint::from_flags(OpenFlags::READ), ReadHint::Blocking);
+        assert_eq!(
+            ReadHint::from_flags(OpenFlags::READ | OpenFlags::NONBLOCK),
+            ReadHint::NonBlocking
+        );
+
+        let pipe = Arc::new(Pipe::default());
+        let mut buf = [0; 4];
+        assert_eq!(
+            pipe.read_hinted(0, &mut buf, ReadHint::NonBlocking),
+            Err(crate::VfsError::WouldBlock)
+        );
+
+        let blocker = Arc::new(CountingBlocker::default());
+        set_blocker(Some(blocker.clone()));
+        let writer = {
+            let pipe = pipe.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                pipe.data.store(true, Ordering::Release);
+                pipe.wakers.wake(PollEvents::IN);
+            })
+        };
+        assert_eq!(pipe.read_hinted(0, &mut buf, ReadHint::Blocking), Ok(4));
+        writer.join().unwrap();
+        set_blocker(None);
+        assert!(blocker.0.load(Ordering::Relaxed) > 0);
+    }
+}
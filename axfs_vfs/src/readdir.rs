@@ -0,0 +1,839 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::{VfsDirEntry, VfsError, VfsNodeOps, VfsNodeType, VfsResult};
+
+/// The order of the entries returned by
+/// [`VfsNodeOps::read_dir_opts`](crate::VfsNodeOps::read_dir_opts).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DirOrder {
+    /// The native order of the filesystem, which is stable between calls but
+    /// otherwise unspecified.
+    #[default]
+    Native,
+    /// `.` and `..` first, then the other entries sorted by the bytes of
+    /// their names.
+    Name,
+}
+
+/// Filtering and ordering options of
+/// [`VfsNodeOps::read_dir_opts`](crate::VfsNodeOps::read_dir_opts).
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{DirOrder, ReadDirOptions, VfsNodeType};
+///
+/// let opts = ReadDirOptions::new()
+///     .prefix("log.")
+///     .file_type(VfsNodeType::File)
+///     .order(DirOrder::Name);
+/// assert!(opts.matches(b"log.1", VfsNodeType::File));
+/// assert!(!opts.matches(b"log.d", VfsNodeType::Dir));
+/// assert!(!opts.matches(b"..", VfsNodeType::Dir));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadDirOptions<'a> {
+    prefix: &'a str,
+    ty: Option<VfsNodeType>,
+    order: DirOrder,
+}
+
+impl<'a> ReadDirOptions<'a> {
+    /// Creates options matching every entry in the native order.
+    pub const fn new() -> Self {
+        Self {
+            prefix: "",
+            ty: None,
+            order: DirOrder::Native,
+        }
+    }
+
+    /// Only matches the entries whose name starts with `prefix`.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The required name prefix
+    pub const fn prefix(mut self, prefix: &'a str) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    /// Only matches the entries of type `ty`.
+    ///
+    /// # Arguments
+    ///
+    /// * `ty` - The required entry type
+    pub const fn file_type(mut self, ty: VfsNodeType) -> Self {
+        self.ty = Some(ty);
+        self
+    }
+
+    /// Selects the order of the returned entries.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The required order
+    pub const fn order(mut self, order: DirOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Returns the required name prefix.
+    pub const fn get_prefix(&self) -> &'a str {
+        self.prefix
+    }
+
+    /// Returns the required entry type, if any.
+    pub const fn get_file_type(&self) -> Option<VfsNodeType> {
+        self.ty
+    }
+
+    /// Returns the required order.
+    pub const fn get_order(&self) -> DirOrder {
+        self.order
+    }
+
+    /// Returns whether an entry passes the filters.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the entry
+    /// * `ty` - The type of the entry
+    pub fn matches(&self, name: &[u8], ty: VfsNodeType) -> bool {
+        name.starts_with(self.prefix.as_bytes()) && self.ty.is_none_or(|t| t == ty)
+    }
+}
+
+/// The number of entries read at once by [`DirEntries`].
+const DIR_BATCH: usize = 16;
+
+/// An iterator over the entries of a directory.
+///
+/// The entries are read with [`VfsNodeOps::read_dir`] a batch at a time,
+/// so callers do not need to manage an entry buffer and a start index.
+/// [`next_entry()`](Self::next_entry) lends the entries without copying
+/// them, while the [`Iterator`] implementation returns copies.
+///
+/// The iteration stops after the first error.
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::{DirEntries, VfsNodeOps, VfsResult};
+/// # fn example(dir: &dyn VfsNodeOps) -> VfsResult {
+/// let mut entries = DirEntries::new(dir);
+/// while let Some(entry) = entries.next_entry() {
+///     let name = entry?.name_as_bytes();
+///     # let _ = name;
+/// }
+///
+/// // or, copying the entries
+/// let names = DirEntries::new(dir)
+///     .map(|entry| entry.map(|e| e.name_as_bytes().to_vec()))
+///     .collect::<VfsResult<Vec<_>>>()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct DirEntries<'a, N: VfsNodeOps + ?Sized = dyn VfsNodeOps> {
+    node: &'a N,
+    batch: Vec<VfsDirEntry>,
+    /// The position in `batch` of the next entry.
+    pos: usize,
+    /// The number of entries in `batch`.
+    len: usize,
+    /// The index in the directory of the next entry.
+    idx: usize,
+    done: bool,
+}
+
+impl<'a, N: VfsNodeOps + ?Sized> DirEntries<'a, N> {
+    /// Creates an iterator over all entries of `node`.
+    pub fn new(node: &'a N) -> Self {
+        Self::with_start(node, 0)
+    }
+
+    /// Creates an iterator over the entries of `node`, starting at the
+    /// entry at index `start_idx`.
+    pub fn with_start(node: &'a N, start_idx: usize) -> Self {
+        Self {
+            node,
+            batch: Vec::new(),
+            pos: 0,
+            len: 0,
+            idx: start_idx,
+            done: false,
+        }
+    }
+
+    /// Returns the index in the directory of the next entry, to resume the
+    /// iteration later with [`with_start()`](Self::with_start).
+    pub fn position(&self) -> usize {
+        self.idx
+    }
+
+    /// Returns the next entry, borrowed from the internal buffer.
+    ///
+    /// # Returns
+    ///
+    /// Returns the next entry, an error of [`VfsNodeOps::read_dir`], or
+    /// `None` at the end of the directory or after an error.
+    pub fn next_entry(&mut self) -> Option<VfsResult<&VfsDirEntry>> {
+        if self.pos == self.len {
+            if self.done {
+                return None;
+            }
+            if self.batch.is_empty() {
+                self.batch.resize(DIR_BATCH, VfsDirEntry::default());
+            }
+            match self.node.read_dir(self.idx, &mut self.batch) {
+                Ok(n) => {
+                    self.pos = 0;
+                    self.len = n.min(DIR_BATCH);
+                    self.done = self.len < DIR_BATCH;
+                    if self.len == 0 {
+                        return None;
+                    }
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.pos += 1;
+        self.idx += 1;
+        Some(Ok(&self.batch[self.pos - 1]))
+    }
+}
+
+impl<N: VfsNodeOps + ?Sized> Iterator for DirEntries<'_, N> {
+    type Item = VfsResult<VfsDirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().map(|entry| entry.cloned())
+    }
+}
+
+/// Returns the key sorting entries in [`DirOrder::Name`] order: `.` and
+/// `..` first, then the other names by bytes.
+fn name_order_key(entry: &VfsDirEntry) -> (u8, &[u8]) {
+    let name = entry.name_as_bytes();
+    let rank = match name {
+        b"." => 0,
+        b".." => 1,
+        _ => 2,
+    };
+    (rank, name)
+}
+
+/// Implements [`VfsNodeOps::read_dir_opts`](crate::VfsNodeOps::read_dir_opts)
+/// on top of [`VfsNodeOps::read_dir`].
+///
+/// In [`DirOrder::Native`] order, reading stops as soon as `dirents` is
+/// full, and without filters it starts at `start_idx` directly. In
+/// [`DirOrder::Name`] order all entries of the directory are read, but only
+/// the requested ones are sorted. Filesystems able to filter or sort
+/// natively should provide their own implementation.
+pub fn read_dir_opts_fallback<N: VfsNodeOps + ?Sized>(
+    node: &N,
+    start_idx: usize,
+    dirents: &mut [VfsDirEntry],
+    opts: &ReadDirOptions,
+) -> VfsResult<usize> {
+    if dirents.is_empty() {
+        return Ok(0);
+    }
+    if opts.order == DirOrder::Native {
+        if opts.prefix.is_empty() && opts.ty.is_none() {
+            return node.read_dir(start_idx, dirents);
+        }
+        let mut entries = DirEntries::new(node);
+        let mut skip = start_idx;
+        let mut count = 0;
+        while let Some(entry) = entries.next_entry() {
+            let entry = entry?;
+            if !opts.matches(entry.name_as_bytes(), entry.entry_type()) {
+                continue;
+            }
+            if skip > 0 {
+                skip -= 1;
+                continue;
+            }
+            dirents[count] = entry.clone();
+            count += 1;
+            if count == dirents.len() {
+                break;
+            }
+        }
+        return Ok(count);
+    }
+
+    let mut entries = Vec::new();
+    for entry in DirEntries::new(node) {
+        let entry = entry?;
+        if opts.matches(entry.name_as_bytes(), entry.entry_type()) {
+            entries.push(entry);
+        }
+    }
+    if start_idx >= entries.len() {
+        return Ok(0);
+    }
+    let end = entries.len().min(start_idx.saturating_add(dirents.len()));
+    let cmp = |a: &VfsDirEntry, b: &VfsDirEntry| name_order_key(a).cmp(&name_order_key(b));
+    // move the first `end` entries in front, then the skipped ones before
+    // them, and sort only the requested window
+    if end < entries.len() {
+        entries.select_nth_unstable_by(end, cmp);
+    }
+    if start_idx > 0 {
+        entries[..end].select_nth_unstable_by(start_idx, cmp);
+    }
+    let window = &mut entries[start_idx..end];
+    window.sort_unstable_by(cmp);
+    let count = window.len();
+    for (dst, src) in dirents.iter_mut().zip(entries.drain(start_idx..end)) {
+        *dst = src;
+    }
+    Ok(count)
+}
+
+/// The size of the fixed part of a Linux `struct linux_dirent64`: `d_ino`
+/// (8 bytes), `d_off` (8 bytes), `d_reclen` (2 bytes) and `d_type` (1 byte).
+const DIRENT64_HEADER_LEN: usize = 19;
+
+/// Returns the size of the `struct linux_dirent64` record of a name of
+/// `name_len` bytes: the header, the name and its terminating NUL, padded to
+/// a multiple of 8 bytes.
+pub(crate) const fn dirent64_reclen(name_len: usize) -> usize {
+    (DIRENT64_HEADER_LEN + name_len + 1).next_multiple_of(8)
+}
+
+/// Implements [`VfsNodeOps::read_dir_buf`](crate::VfsNodeOps::read_dir_buf)
+/// on top of [`VfsNodeOps::read_dir`].
+pub fn read_dir_buf_fallback<N: VfsNodeOps + ?Sized>(
+    node: &N,
+    cursor: u64,
+    buf: &mut [u8],
+) -> VfsResult<(usize, u64)> {
+    let mut entries = DirEntries::with_start(node, cursor as usize);
+    let mut pos = 0;
+    let mut cursor = cursor;
+    while let Some(entry) = entries.next_entry() {
+        let entry = entry?;
+        let name = entry.name_as_bytes();
+        let reclen = dirent64_reclen(name.len());
+        if pos + reclen > buf.len() {
+            if pos == 0 {
+                return Err(VfsError::InvalidInput);
+            }
+            break;
+        }
+        cursor += 1;
+        let rec = &mut buf[pos..pos + reclen];
+        // some programs skip entries with a zero inode number, so report
+        // the (never zero) position if the inode number is unknown
+        let ino = match entry.ino() {
+            0 => cursor,
+            ino => ino,
+        };
+        rec[0..8].copy_from_slice(&ino.to_ne_bytes());
+        rec[8..16].copy_from_slice(&cursor.to_ne_bytes());
+        rec[16..18].copy_from_slice(&(reclen as u16).to_ne_bytes());
+        rec[18] = entry.entry_type() as u8;
+        rec[DIRENT64_HEADER_LEN..DIRENT64_HEADER_LEN + name.len()].copy_from_slice(name);
+        rec[DIRENT64_HEADER_LEN + name.len()..].fill(0);
+        pos += reclen;
+    }
+    Ok((pos, cursor))
+}
+
+/// A position in a [`DirStream`].
+///
+/// Cookies are opaque to users: they are obtained from
+/// [`DirStream::cookie()`] and passed back to [`DirStream::seek()`] of a
+/// stream of the same directory. Each filesystem chooses their encoding, so
+/// that a position stays valid while entries are added and removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DirCookie(Vec<u8>);
+
+impl DirCookie {
+    /// Returns the cookie of the beginning of every directory.
+    pub const fn start() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Creates a cookie from its encoding by a filesystem.
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+
+    /// Returns the encoding of the cookie.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Whether the cookie is the beginning of the directory.
+    pub fn is_start(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A cursor over the entries of a directory, returned by
+/// [`VfsNodeOps::open_dir`](crate::VfsNodeOps::open_dir).
+///
+/// Unlike the index of [`VfsNodeOps::read_dir`], the position of a stream
+/// is not shifted by concurrent insertions and removals: every entry present
+/// during the whole iteration is returned exactly once. Entries added or
+/// removed meanwhile may or may not be returned.
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::{DirStream, VfsNodeRef, VfsResult};
+/// # fn example(dir: VfsNodeRef) -> VfsResult {
+/// let mut stream = dir.open_dir()?;
+/// let first = stream.next_entry()?;
+/// let cookie = stream.cookie();
+/// while let Some(entry) = stream.next_entry()? {
+///     # let _ = entry;
+/// }
+///
+/// // resume after the first entry
+/// stream.seek(&cookie)?;
+/// # let _ = first;
+/// # Ok(())
+/// # }
+/// ```
+pub trait DirStream: Send + Sync {
+    /// Returns the next entry of the directory.
+    ///
+    /// # Returns
+    ///
+    /// Returns the next entry, or `None` at the end of the directory.
+    fn next_entry(&mut self) -> VfsResult<Option<VfsDirEntry>>;
+
+    /// Returns the current position of the stream, from which the entries
+    /// following the last returned one are read.
+    fn cookie(&self) -> DirCookie;
+
+    /// Moves the stream to a position returned by
+    /// [`cookie()`](Self::cookie).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `cookie` was not created by a
+    /// stream of this filesystem.
+    fn seek(&mut self, cookie: &DirCookie) -> VfsResult;
+}
+
+/// Implements [`VfsNodeOps::open_dir`](crate::VfsNodeOps::open_dir) on top
+/// of [`VfsNodeOps::read_dir`].
+///
+/// The cookies are the indices of the entries, so the stream is only as
+/// stable as the indices of the filesystem.
+pub struct IndexDirStream<N: VfsNodeOps + ?Sized> {
+    node: Arc<N>,
+    idx: usize,
+}
+
+impl<N: VfsNodeOps + ?Sized> IndexDirStream<N> {
+    /// Creates a stream over the entries of `node`, from the first one.
+    pub fn new(node: Arc<N>) -> Self {
+        Self { node, idx: 0 }
+    }
+}
+
+impl<N: VfsNodeOps + ?Sized> DirStream for IndexDirStream<N> {
+    fn next_entry(&mut self) -> VfsResult<Option<VfsDirEntry>> {
+        let mut dirents = [const { VfsDirEntry::default() }; 1];
+        if self.node.read_dir(self.idx, &mut dirents)? == 0 {
+            return Ok(None);
+        }
+        self.idx += 1;
+        let [entry] = dirents;
+        Ok(Some(entry))
+    }
+
+    fn cookie(&self) -> DirCookie {
+        match self.idx {
+            0 => DirCookie::start(),
+            idx => DirCookie::from_bytes((idx as u64).to_le_bytes()),
+        }
+    }
+
+    fn seek(&mut self, cookie: &DirCookie) -> VfsResult {
+        self.idx = match cookie.as_bytes() {
+            [] => 0,
+            bytes => {
+                let bytes = bytes.try_into().map_err(|_| VfsError::InvalidInput)?;
+                u64::from_le_bytes(bytes) as usize
+            }
+        };
+        Ok(())
+    }
+}
+
+/// When the entries of a [`DirStream`] are read from its directory, as
+/// selected per filesystem.
+///
+/// POSIX leaves unspecified whether the entries added or removed while a
+/// directory is being read are returned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadDirPolicy {
+    /// The entries are read as the stream advances, so the entries added or
+    /// removed meanwhile may or may not be returned.
+    #[default]
+    Live,
+    /// The entries are copied at the first read of the stream, and after it
+    /// is rewound to the start, so the listing is consistent whatever the
+    /// changes of the directory, at the cost of memory.
+    Snapshot,
+}
+
+/// A [`DirStream`] returning a copy of the entries of another stream, taken
+/// at its first read.
+///
+/// The cookies are the indices of the entries in the copy. Seeking to the
+/// start drops the copy, so that rewinding the directory lists its current
+/// entries.
+pub struct SnapshotDirStream {
+    inner: Box<dyn DirStream>,
+    entries: Option<Vec<VfsDirEntry>>,
+    pos: usize,
+}
+
+impl SnapshotDirStream {
+    /// Wraps the stream `inner`, which must be at the start of the
+    /// directory.
+    pub fn new(inner: Box<dyn DirStream>) -> Self {
+        Self {
+            inner,
+            entries: None,
+            pos: 0,
+        }
+    }
+
+    /// Returns the copied entries, reading them all from the inner stream
+    /// on the first call.
+    fn entries(&mut self) -> VfsResult<&[VfsDirEntry]> {
+        if self.entries.is_none() {
+            let mut entries = Vec::new();
+            while let Some(entry) = self.inner.next_entry()? {
+                entries.push(entry);
+            }
+            self.entries = Some(entries);
+        }
+        Ok(self.entries.as_deref().unwrap_or_default())
+    }
+}
+
+impl DirStream for SnapshotDirStream {
+    fn next_entry(&mut self) -> VfsResult<Option<VfsDirEntry>> {
+        let pos = self.pos;
+        let entry = self.entries()?.get(pos).cloned();
+        if entry.is_some() {
+            self.pos += 1;
+        }
+        Ok(entry)
+    }
+
+    fn cookie(&self) -> DirCookie {
+        match self.pos {
+            0 => DirCookie::start(),
+            pos => DirCookie::from_bytes((pos as u64).to_le_bytes()),
+        }
+    }
+
+    /// Moves the stream in the copy of the entries, or drops the copy if
+    /// `cookie` is the start.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `cookie` is not a position in
+    /// the copy, or the errors of the inner stream.
+    fn seek(&mut self, cookie: &DirCookie) -> VfsResult {
+        if cookie.is_start() {
+            self.inner.seek(cookie)?;
+            self.entries = None;
+            self.pos = 0;
+            return Ok(());
+        }
+        let bytes = cookie.as_bytes().try_into();
+        let pos = u64::from_le_bytes(bytes.map_err(|_| VfsError::InvalidInput)?) as usize;
+        if pos > self.entries()?.len() {
+            return Err(VfsError::InvalidInput);
+        }
+        self.pos = pos;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory listing its entries in reverse order of names.
+    struct ReverseDir;
+
+    const NAMES: [(&str, VfsNodeType); 6] = [
+        ("log.2", VfsNodeType::File),
+        ("log.10", VfsNodeType::File),
+        ("log.1", VfsNodeType::File),
+        ("conf", VfsNodeType::Dir),
+        ("..", VfsNodeType::Dir),
+        (".", VfsNodeType::Dir),
+    ];
+
+    impl VfsNodeOps for ReverseDir {
+        fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+            let mut n = 0;
+            for (ent, (name, ty)) in dirents.iter_mut().zip(NAMES.iter().skip(start_idx)) {
+                *ent = VfsDirEntry::new(name, *ty);
+                n += 1;
+            }
+            Ok(n)
+        }
+    }
+
+    fn names(opts: &ReadDirOptions, start_idx: usize) -> Vec<Vec<u8>> {
+        let mut dirents = [const { VfsDirEntry::default() }; 4];
+        let n = read_dir_opts_fallback(&ReverseDir, start_idx, &mut dirents, opts).unwrap();
+        dirents[..n]
+            .iter()
+            .map(|e| e.name_as_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_prefix_and_type() {
+        let opts = ReadDirOptions::new().prefix("log.");
+        assert_eq!(names(&opts, 0), [&b"log.2"[..], b"log.10", b"log.1"]);
+
+        let opts = ReadDirOptions::new().file_type(VfsNodeType::Dir);
+        assert_eq!(names(&opts, 0), [&b"conf"[..], b"..", b"."]);
+    }
+
+    #[test]
+    fn test_order_by_name() {
+        let opts = ReadDirOptions::new().order(DirOrder::Name);
+        assert_eq!(names(&opts, 0), [&b"."[..], b"..", b"conf", b"log.1"]);
+        assert_eq!(names(&opts, 4), [&b"log.10"[..], b"log.2"]);
+    }
+
+    /// A directory of `len` files named by their index, failing reads from
+    /// index `fail_at`.
+    struct NumberedDir {
+        len: usize,
+        fail_at: usize,
+    }
+
+    impl VfsNodeOps for NumberedDir {
+        fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+            if start_idx >= self.fail_at {
+                return Err(VfsError::Io);
+            }
+            let mut n = 0;
+            for (ent, idx) in dirents.iter_mut().zip(start_idx..self.len) {
+                *ent = VfsDirEntry::new(&alloc::format!("{idx}"), VfsNodeType::File);
+                n += 1;
+            }
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_dir_entries() {
+        let dir = NumberedDir {
+            len: 40,
+            fail_at: usize::MAX,
+        };
+        let names: Vec<Vec<u8>> = DirEntries::new(&dir)
+            .map(|e| e.unwrap().name_as_bytes().to_vec())
+            .collect();
+        assert_eq!(names.len(), 40);
+        assert_eq!(names[39], b"39");
+
+        let mut entries = DirEntries::with_start(&dir, 30);
+        assert_eq!(
+            entries.next_entry().unwrap().unwrap().name_as_bytes(),
+            b"30"
+        );
+        assert_eq!(entries.position(), 31);
+        assert_eq!(entries.count(), 9);
+
+        // a full last batch is followed by an empty read
+        let dir = NumberedDir {
+            len: DIR_BATCH,
+            fail_at: usize::MAX,
+        };
+        assert_eq!(DirEntries::new(&dir).count(), DIR_BATCH);
+    }
+
+    #[test]
+    fn test_dir_entries_error() {
+        let dir = NumberedDir {
+            len: 40,
+            fail_at: DIR_BATCH,
+        };
+        let results: Vec<_> = DirEntries::new(&dir).collect();
+        assert_eq!(results.len(), DIR_BATCH + 1);
+        assert_eq!(results.last().unwrap().as_ref().err(), Some(&VfsError::Io));
+    }
+
+    #[test]
+    fn test_native_order_reads_only_needed_entries() {
+        // reading past the first batch fails, so these reads must stop early
+        let dir = NumberedDir {
+            len: 100,
+            fail_at: DIR_BATCH,
+        };
+        let mut dirents = [const { VfsDirEntry::default() }; 2];
+        let opts = ReadDirOptions::new().prefix("1");
+        assert_eq!(read_dir_opts_fallback(&dir, 1, &mut dirents, &opts), Ok(2));
+        assert_eq!(dirents[0].name_as_bytes(), b"10");
+        assert_eq!(dirents[1].name_as_bytes(), b"11");
+
+        let dir = NumberedDir {
+            len: 100,
+            fail_at: 60,
+        };
+        let opts = ReadDirOptions::new();
+        assert_eq!(read_dir_opts_fallback(&dir, 50, &mut dirents, &opts), Ok(2));
+        assert_eq!(dirents[0].name_as_bytes(), b"50");
+        // sorting needs all entries
+        let opts = ReadDirOptions::new().order(DirOrder::Name);
+        assert_eq!(
+            read_dir_opts_fallback(&dir, 0, &mut dirents, &opts),
+            Err(VfsError::Io)
+        );
+    }
+
+    /// Parses `struct linux_dirent64` records into `(d_off, d_type, name)`.
+    fn parse(buf: &[u8]) -> Vec<(u64, u8, Vec<u8>)> {
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos < buf.len() {
+            let off = u64::from_ne_bytes(buf[pos + 8..pos + 16].try_into().unwrap());
+            let reclen = u16::from_ne_bytes(buf[pos + 16..pos + 18].try_into().unwrap()) as usize;
+            assert_eq!(reclen % 8, 0);
+            let name = &buf[pos + DIRENT64_HEADER_LEN..pos + reclen];
+            let len = name.iter().position(|&b| b == 0).unwrap();
+            entries.push((off, buf[pos + 18], name[..len].to_vec()));
+            pos += reclen;
+        }
+        entries
+    }
+
+    #[test]
+    fn test_read_dir_buf() {
+        assert_eq!(dirent64_reclen(1), 24);
+        assert_eq!(dirent64_reclen(4), 24);
+        assert_eq!(dirent64_reclen(5), 32);
+
+        let mut buf = [0; 256];
+        let (len, cursor) = read_dir_buf_fallback(&ReverseDir, 0, &mut buf).unwrap();
+        assert_eq!(cursor, 6);
+        let entries = parse(&buf[..len]);
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[0], (1, VfsNodeType::File as u8, b"log.2".to_vec()));
+        assert_eq!(entries[3], (4, VfsNodeType::Dir as u8, b"conf".to_vec()));
+        // end of directory
+        assert_eq!(read_dir_buf_fallback(&ReverseDir, 6, &mut buf), Ok((0, 6)));
+    }
+
+    #[test]
+    fn test_read_dir_buf_budget() {
+        // "log.2" and "log.10" take 32 bytes each
+        let mut buf = [0; 70];
+        let (len, cursor) = read_dir_buf_fallback(&ReverseDir, 0, &mut buf).unwrap();
+        assert_eq!((len, cursor), (64, 2));
+        let (len, cursor) = read_dir_buf_fallback(&ReverseDir, cursor, &mut buf).unwrap();
+        assert_eq!((len, cursor), (56, 4));
+        assert_eq!(parse(&buf[..len])[1].2, b"conf");
+
+        let mut tiny = [0; 16];
+        assert_eq!(
+            read_dir_buf_fallback(&ReverseDir, 0, &mut tiny),
+            Err(VfsError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_index_dir_stream() {
+        let mut stream = Arc::new(ReverseDir).open_dir().unwrap();
+        assert!(stream.cookie().is_start());
+        assert_eq!(
+            stream.next_entry().unwrap().unwrap().name_as_bytes(),
+            b"log.2"
+        );
+        assert_eq!(
+            stream.next_entry().unwrap().unwrap().name_as_bytes(),
+            b"log.10"
+        );
+        let cookie = stream.cookie();
+        let rest: Vec<_> = core::iter::from_fn(|| stream.next_entry().unwrap()).collect();
+        assert_eq!(rest.len(), 4);
+        assert!(stream.next_entry().unwrap().is_none());
+
+        stream.seek(&cookie).unwrap();
+        assert_eq!(
+            stream.next_entry().unwrap().unwrap().name_as_bytes(),
+            b"log.1"
+        );
+        stream.seek(&DirCookie::start()).unwrap();
+        assert_eq!(
+            stream.next_entry().unwrap().unwrap().name_as_bytes(),
+            b"log.2"
+        );
+        assert_eq!(
+            stream.seek(&DirCookie::from_bytes([1, 2])),
+            Err(VfsError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_snapshot_dir_stream() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        /// A directory of numbered entries, whose count changes.
+        struct GrowingDir(AtomicUsize);
+
+        impl VfsNodeOps for GrowingDir {
+            fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+                let len = self.0.load(Ordering::Relaxed);
+                let mut n = 0;
+                for (ent, i) in dirents.iter_mut().zip(start_idx..len) {
+                    *ent = VfsDirEntry::new(&alloc::format!("{i}"), VfsNodeType::File);
+                    n += 1;
+                }
+                Ok(n)
+            }
+        }
+
+        let dir = Arc::new(GrowingDir(AtomicUsize::new(3)));
+        let mut stream = SnapshotDirStream::new(dir.clone().open_dir().unwrap());
+        assert_eq!(stream.next_entry().unwrap().unwrap().name(), "0");
+        let cookie = stream.cookie();
+        // changes after the first read are not seen
+        dir.0.store(10, Ordering::Relaxed);
+        let names: Vec<_> = core::iter::from_fn(|| stream.next_entry().unwrap())
+            .map(|e| alloc::string::String::from(e.name()))
+            .collect();
+        assert_eq!(names, ["1", "2"]);
+
+        stream.seek(&cookie).unwrap();
+        assert_eq!(stream.next_entry().unwrap().unwrap().name(), "1");
+        assert_eq!(
+            stream.seek(&DirCookie::from_bytes(9u64.to_le_bytes())),
+            Err(VfsError::InvalidInput)
+        );
+        // rewinding takes a new snapshot
+        stream.seek(&DirCookie::start()).unwrap();
+        assert_eq!(
+            core::iter::from_fn(|| stream.next_entry().unwrap()).count(),
+            10
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Crash-consistent replacement of file contents.
+
+use alloc::format;
+
+use crate::copy::write_all_at;
+use crate::{SetAttr, VfsError, VfsNodeRef, VfsNodeType, VfsResult};
+
+/// The number of temporary names tried by [`replace_file`] before giving up.
+const MAX_TEMP_ATTEMPTS: usize = 16;
+
+/// Replaces the content of the file `name` in `dir` atomically.
+///
+/// The new content is written to a hidden temporary file in the same
+/// directory, synchronized if the filesystem supports it, then renamed over
+/// `name`. Readers see either the old or the new content, never a partial
+/// write, and a crash leaves at most a stale temporary file behind. The
+/// permissions of an existing file are kept.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the file
+/// * `name` - The name of the file, created if it does not exist
+/// * `contents` - The new content of the file
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the file was replaced, or an error otherwise, in
+/// which case the file is left unchanged.
+///
+/// # Errors
+///
+/// Returns the errors of the node operations, such as
+/// [`VfsError::Unsupported`] if `dir` does not support renaming, or
+/// [`VfsError::AlreadyExists`] if no temporary name is free.
+///
+/// # Examples
+///
+/// ```
+/// # use axfs_vfs::{VfsNodeRef, VfsResult};
+/// # fn example(etc: &VfsNodeRef) -> VfsResult {
+/// axfs_vfs::replace::replace_file(etc, "hostname", b"arceos\n")?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn replace_file(dir: &VfsNodeRef, name: &str, contents: &[u8]) -> VfsResult {
+    let perm = match dir.clone().lookup(name) {
+        Ok(node) => Some(node.get_attr()?.perm()),
+        Err(VfsError::NotFound) => None,
+        Err(e) => return Err(e),
+    };
+
+    let mut attempt = 0;
+    let temp = loop {
+        let temp = format!(".{name}.tmp{attempt}");
+        match create_temp(dir, &temp) {
+            Ok(()) => break temp,
+            Err(VfsError::AlreadyExists) if attempt + 1 < MAX_TEMP_ATTEMPTS => attempt += 1,
+            Err(e) => return Err(e),
+        }
+    };
+
+    let result = (|| {
+        let file = dir.clone().lookup(&temp)?;
+        write_all_at(file.as_ref(), 0, contents)?;
+        if let Some(perm) = perm {
+            file.set_attr(&SetAttr::new().mode(perm))?;
+        }
+        match file.fsync() {
+            // a file only held in memory has nothing to synchronize
+            Ok(()) | Err(VfsError::InvalidInput | VfsError::Unsupported) => {}
+            Err(e) => return Err(e),
+        }
+        dir.rename(&temp, name)
+    })();
+    if result.is_err() {
+        dir.remove(&temp).ok();
+    }
+    result
+}
+
+/// Creates the temporary file `temp` in `dir`, failing if it exists.
+///
+/// If `dir` does not support [`create_exclusive()`], the file is created
+/// with [`create()`], which may succeed on a stale temporary file, and is
+/// truncated.
+///
+/// [`create_exclusive()`]: crate::VfsNodeOps::create_exclusive
+/// [`create()`]: crate::VfsNodeOps::create
+fn create_temp(dir: &VfsNodeRef, temp: &str) -> VfsResult {
+    match dir.create_exclusive(temp, VfsNodeType::File) {
+        Err(VfsError::Unsupported) => {
+            dir.create(temp, VfsNodeType::File)?;
+            dir.clone().lookup(temp)?.truncate(0)
+        }
+        res => res,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VfsNodeAttr, VfsNodeOps, VfsNodePerm};
+    use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+    use spin::Mutex;
+
+    struct MemFile {
+        data: Mutex<Vec<u8>>,
+        perm: Mutex<VfsNodePerm>,
+    }
+
+    impl VfsNodeOps for MemFile {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            let size = self.data.lock().len() as u64;
+            Ok(VfsNodeAttr::new(
+                *self.perm.lock(),
+                VfsNodeType::File,
+                size,
+                0,
+            ))
+        }
+
+        fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+            if let Some(mode) = attr.get_mode() {
+                *self.perm.lock() = mode;
+            }
+            Ok(())
+        }
+
+        fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            let mut data = self.data.lock();
+            let len = data.len().max(offset as usize);
+            data.resize(len, 0);
+            let end = data.len().min(offset as usize + buf.len());
+            data.splice(offset as usize..end, buf.iter().copied());
+            Ok(buf.len())
+        }
+
+        fn truncate(&self, size: u64) -> VfsResult {
+            self.data.lock().resize(size as usize, 0);
+            Ok(())
+        }
+
+        fn fsync(&self) -> VfsResult {
+            Ok(())
+        }
+
+        crate::impl_vfs_non_dir_default! {}
+    }
+
+    #[derive(Default)]
+    struct MemDir {
+        files: Mutex<BTreeMap<String, Arc<MemFile>>>,
+        fail_rename: bool,
+        no_exclusive: bool,
+    }
+
+    impl VfsNodeOps for MemDir {
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            let files = self.files.lock();
+            let file = files.get(path).ok_or(VfsError::NotFound)?;
+            Ok(file.clone())
+        }
+
+        fn create(&self, path: &str, _ty: VfsNodeType) -> VfsResult {
+            let mut files = self.files.lock();
+            files.entry(path.into()).or_insert_with(|| {
+                Arc::new(MemFile {
+                    data: Mutex::new(Vec::new()),
+                    perm: Mutex::new(VfsNodePerm::default_file()),
+                })
+            });
+            Ok(())
+        }
+
+        fn create_exclusive(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+            if self.no_exclusive {
+                return Err(VfsError::Unsupported);
+            }
+            if self.files.lock().contains_key(path) {
+                return Err(VfsError::AlreadyExists);
+            }
+            self.create(path, ty)
+        }
+
+        fn remove(&self, path: &str) -> VfsResult {
+            self.files.lock().remove(path).ok_or(VfsError::NotFound)?;
+            Ok(())
+        }
+
+        fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+            if self.fail_rename {
+                return Err(VfsError::Unsupported);
+            }
+            let mut files = self.files.lock();
+            let file = files.remove(src_path).ok_or(VfsError::NotFound)?;
+            files.insert(dst_path.into(), file);
+            Ok(())
+        }
+
+        crate::impl_vfs_dir_default! {}
+    }
+
+    fn content(dir: &VfsNodeRef, name: &str) -> Vec<u8> {
+        let node = dir.clone().lookup(name).unwrap();
+        let file = node.as_any().downcast_ref::<MemFile>().unwrap();
+        let data = file.data.lock().clone();
+        data
+    }
+
+    #[test]
+    fn test_replace_file() {
+        let dir: VfsNodeRef = Arc::new(MemDir::default());
+        replace_file(&dir, "conf", b"v1").unwrap();
+        assert_eq!(content(&dir, "conf"), b"v1");
+
+        let old = dir.clone().lookup("conf").unwrap();
+        old.set_attr(&SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o600)))
+            .unwrap();
+        // a stale temporary file is skipped
+        dir.create(".conf.tmp0", VfsNodeType::File).unwrap();
+        replace_file(&dir, "conf", b"v2").unwrap();
+        assert_eq!(content(&dir, "conf"), b"v2");
+        assert_eq!(
+            old.as_any()
+                .downcast_ref::<MemFile>()
+                .unwrap()
+                .data
+                .lock()
+                .as_slice(),
+            b"v1"
+        );
+        let attr = dir.clone().lookup("conf").unwrap().get_attr().unwrap();
+        assert_eq!(attr.perm().bits(), 0o600);
+    }
+
+    #[test]
+    fn test_replace_file_stale_temp() {
+        let dir: VfsNodeRef = Arc::new(MemDir {
+            no_exclusive: true,
+            ..Default::default()
+        });
+        // without exclusive creation, a stale temporary file is reused, and
+        // its old content must not leak into the file
+        dir.create(".conf.tmp0", VfsNodeType::File).unwrap();
+        let stale = dir.clone().lookup(".conf.tmp0").unwrap();
+        stale.write_at(0, b"stale content").unwrap();
+        replace_file(&dir, "conf", b"v1").unwrap();
+        assert_eq!(content(&dir, "conf"), b"v1");
+    }
+
+    #[test]
+    fn test_replace_file_failure() {
+        let dir: VfsNodeRef = Arc::new(MemDir {
+            fail_rename: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            replace_file(&dir, "conf", b"v1"),
+            Err(VfsError::Unsupported)
+        );
+        let files = dir.as_any().downcast_ref::<MemDir>().unwrap().files.lock();
+        assert!(files.is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use core::time::Duration;
+
+use crate::VfsNodePerm;
+
+/// The attributes changed by
+/// [`VfsNodeOps::set_attr`](crate::VfsNodeOps::set_attr).
+///
+/// Attributes that are not set are left unchanged. The change time of the
+/// node is always updated by the filesystem.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{SetAttr, VfsNodePerm};
+/// use core::time::Duration;
+///
+/// // chmod 600
+/// let attr = SetAttr::new().mode(VfsNodePerm::from_bits_truncate(0o600));
+/// assert_eq!(attr.get_size(), None);
+///
+/// // chown :100
+/// let attr = SetAttr::new().gid(100);
+/// assert_eq!(attr.get_uid(), None);
+///
+/// // truncate and touch
+/// let attr = SetAttr::new().size(0).mtime(Duration::from_secs(1));
+/// assert_eq!(attr.get_size(), Some(0));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SetAttr {
+    mode: Option<VfsNodePerm>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    size: Option<u64>,
+    atime: Option<Duration>,
+    mtime: Option<Duration>,
+}
+
+impl SetAttr {
+    /// Creates a request changing no attribute.
+    pub const fn new() -> Self {
+        Self {
+            mode: None,
+            uid: None,
+            gid: None,
+            size: None,
+            atime: None,
+            mtime: None,
+        }
+    }
+
+    /// Changes the permissions of the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The new permissions
+    pub const fn mode(mut self, mode: VfsNodePerm) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Changes the user owning the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `uid` - The user ID of the new owner
+    pub const fn uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Changes the group owning the node.
+    ///
+    /// # Arguments
+    ///
+    /// * `gid` - The group ID of the new owner
+    pub const fn gid(mut self, gid: u32) -> Self {
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Truncates or extends the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The new size, in bytes
+    pub const fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Changes the time of last access.
+    ///
+    /// # Arguments
+    ///
+    /// * `atime` - The new access time
+    pub const fn atime(mut self, atime: Duration) -> Self {
+        self.atime = Some(atime);
+        self
+    }
+
+    /// Changes the time of last modification.
+    ///
+    /// # Arguments
+    ///
+    /// * `mtime` - The new modification time
+    pub const fn mtime(mut self, mtime: Duration) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
+    /// Returns a copy of the request that leaves the size unchanged.
+    ///
+    /// This lets a wrapper node handle the size change itself and forward
+    /// the other changes.
+    pub const fn without_size(mut self) -> Self {
+        self.size = None;
+        self
+    }
+
+    /// Returns `true` if the request changes no attribute.
+    pub const fn is_empty(&self) -> bool {
+        self.mode.is_none()
+            && self.uid.is_none()
+            && self.gid.is_none()
+            && self.size.is_none()
+            && self.atime.is_none()
+            && self.mtime.is_none()
+    }
+
+    /// Returns the new permissions, if they are changed.
+    pub const fn get_mode(&self) -> Option<VfsNodePerm> {
+        self.mode
+    }
+
+    /// Returns the user ID of the new owner, if it is changed.
+    pub const fn get_uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    /// Returns the group ID of the new owner, if it is changed.
+    pub const fn get_gid(&self) -> Option<u32> {
+        self.gid
+    }
+
+    /// Returns the new size, if it is changed.
+    pub const fn get_size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// Returns the new access time, if it is changed.
+    pub const fn get_atime(&self) -> Option<Duration> {
+        self.atime
+    }
+
+    /// Returns the new modification time, if it is changed.
+    pub const fn get_mtime(&self) -> Option<Duration> {
+        self.mtime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_attr_builder() {
+        let attr = SetAttr::new();
+        assert!(attr.get_mode().is_none());
+        assert_eq!(
+            (attr.get_size(), attr.get_atime(), attr.get_mtime()),
+            (None, None, None)
+        );
+
+        let secs = Duration::from_secs;
+        let attr = SetAttr::new()
+            .mode(VfsNodePerm::from_bits_truncate(0o640))
+            .size(10)
+            .atime(secs(1))
+            .mtime(secs(2));
+        assert_eq!(attr.get_mode().unwrap().mode(), 0o640);
+        assert_eq!(attr.get_size(), Some(10));
+        assert_eq!(attr.get_atime(), Some(secs(1)));
+        assert_eq!(attr.get_mtime(), Some(secs(2)));
+        assert_eq!((attr.get_uid(), attr.get_gid()), (None, None));
+        assert_eq!(attr.without_size().get_size(), None);
+        assert!(!attr.without_size().is_empty());
+        assert!(SetAttr::new().size(0).without_size().is_empty());
+
+        let attr = SetAttr::new().uid(1000).gid(100);
+        assert_eq!((attr.get_uid(), attr.get_gid()), (Some(1000), Some(100)));
+    }
+}
@@ -0,0 +1,465 @@
+use crate::handle::OpenState;
+use crate::{
+    FallocateMode, OpenFlags, SeekHint, SetAttr, VfsError, VfsFileRef, VfsNodeAttr, VfsNodeOps,
+    VfsNodeRef, VfsPage, VfsResult,
+};
+
+/// A byte range of another file, exposed as a file.
+///
+/// Offset `0` of the slice is offset `offset` of the wrapped node, and the
+/// slice ends after `len` bytes or at the end of the node, whichever comes
+/// first. Reads and writes are translated and clipped to the range, so the
+/// slice can be used for partitions of a disk image or sub-images of a
+/// firmware blob.
+///
+/// A bounded slice never grows nor shrinks: a write starting at its end
+/// fails with [`VfsError::StorageFull`] and it cannot be truncated, as a
+/// partition, so that the data of the node after it is never touched. An
+/// unbounded slice (`len` is `None`) extends to the end of the node and is
+/// resized with it.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_vfs::{SliceNode, VfsError, VfsNodeOps, VfsNodeRef};
+/// # use axfs_vfs::VfsResult;
+/// # use std::sync::Mutex;
+/// # struct Disk(Mutex<[u8; 16]>);
+/// # impl VfsNodeOps for Disk {
+/// #     fn get_attr(&self) -> VfsResult<axfs_vfs::VfsNodeAttr> {
+/// #         Ok(axfs_vfs::VfsNodeAttr::new_file(16, 1))
+/// #     }
+/// #     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+/// #         let data = self.0.lock().unwrap();
+/// #         let n = buf.len().min(16 - offset as usize);
+/// #         buf[..n].copy_from_slice(&data[offset as usize..offset as usize + n]);
+/// #         Ok(n)
+/// #     }
+/// #     fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+/// #         let mut data = self.0.lock().unwrap();
+/// #         let n = buf.len().min(16 - offset as usize);
+/// #         data[offset as usize..offset as usize + n].copy_from_slice(&buf[..n]);
+/// #         Ok(n)
+/// #     }
+/// # }
+/// # let disk: VfsNodeRef = Arc::new(Disk(Mutex::new([0; 16])));
+///
+/// // the second half of a 16-byte disk
+/// let part = SliceNode::new(disk.clone(), 8, Some(8));
+/// assert_eq!(part.write_at(6, b"abcd"), Ok(2));
+/// assert_eq!(part.write_at(8, b"x"), Err(VfsError::StorageFull));
+///
+/// let mut buf = [0; 2];
+/// assert_eq!(disk.read_at(14, &mut buf), Ok(2));
+/// assert_eq!(&buf, b"ab");
+/// ```
+pub struct SliceNode {
+    node: VfsNodeRef,
+    offset: u64,
+    len: Option<u64>,
+}
+
+impl SliceNode {
+    /// Creates a slice of `node`.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - The node to slice
+    /// * `offset` - The offset in the node of the start of the slice
+    /// * `len` - The length of the slice, or `None` to extend it to the end
+    ///   of the node
+    pub fn new(node: VfsNodeRef, offset: u64, len: Option<u64>) -> Self {
+        Self { node, offset, len }
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &VfsNodeRef {
+        &self.node
+    }
+
+    /// Returns the offset in the node of the start of the slice.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the maximum length of the slice, `None` if it is unbounded.
+    pub fn max_len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Returns the current size of the slice, the part of the range inside
+    /// the node.
+    pub fn size(&self) -> VfsResult<u64> {
+        let size = self.node.get_attr()?.size().saturating_sub(self.offset);
+        Ok(match self.len {
+            Some(len) => size.min(len),
+            None => size,
+        })
+    }
+
+    /// Returns the offset in the node of `offset` in the slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the offset in the node
+    /// overflows.
+    fn node_offset(&self, offset: u64) -> VfsResult<u64> {
+        self.offset
+            .checked_add(offset)
+            .ok_or(VfsError::InvalidInput)
+    }
+}
+
+impl VfsNodeOps for SliceNode {
+    /// Opens the node.
+    ///
+    /// With [`OpenFlags::TRUNC`] and [`OpenFlags::WRITE`], an unbounded
+    /// slice is truncated instead of the whole node, and a bounded slice is
+    /// left as is, like a block device. A per-open file returned by the
+    /// node is not used, so that accesses stay within the slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the node if it can't be opened or truncated. The
+    /// node is released again if the truncation fails.
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        let file = self.node.open(flags - OpenFlags::TRUNC)?;
+        if self.len.is_none() && flags.contains(OpenFlags::TRUNC | OpenFlags::WRITE) {
+            self.truncate(0).inspect_err(|_| {
+                let res = match &file {
+                    Some(file) => file.release().and(self.node.release()),
+                    None => self.node.release(),
+                };
+                if let Err(e) = res {
+                    log::warn!("failed to release node after failed truncation: {e:?}");
+                }
+            })?;
+        }
+        Ok(None)
+    }
+
+    /// Closes the node.
+    fn release(&self) -> VfsResult {
+        self.node.release()
+    }
+
+    /// Returns the open state of the node.
+    fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+        self.node.open_state()
+    }
+
+    /// Returns the attributes of the node, with the size of the slice.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let attr = self.node.get_attr()?;
+        let size = self.size()?;
+        Ok(
+            VfsNodeAttr::new(attr.perm(), attr.file_type(), size, size.div_ceil(512))
+                .with_ino(attr.ino())
+                .with_nlink(attr.nlink())
+                .with_owner(attr.uid(), attr.gid())
+                .with_times(attr.atime(), attr.mtime(), attr.ctime()),
+        )
+    }
+
+    /// Changes the attributes of the node.
+    ///
+    /// A size change truncates the slice, see
+    /// [`truncate()`](Self::truncate).
+    fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+        let Some(size) = attr.get_size() else {
+            return self.node.set_attr(attr);
+        };
+        self.truncate(size)?;
+        let rest = attr.without_size();
+        if rest.is_empty() {
+            return Ok(());
+        }
+        self.node.set_attr(&rest)
+    }
+
+    /// Reads data from the slice at the given offset.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes read, which is `0` at or past the end of
+    /// the slice.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        let size = self.size()?;
+        if offset >= size {
+            return Ok(0);
+        }
+        let len = (buf.len() as u64).min(size - offset) as usize;
+        self.node
+            .read_at(self.node_offset(offset)?, &mut buf[..len])
+    }
+
+    /// Writes data to the slice at the given offset.
+    ///
+    /// Writes to a bounded slice are clipped to its end.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::StorageFull`] if `offset` is at or past the end
+    /// of a bounded slice and `buf` is not empty,
+    /// [`VfsError::InvalidInput`] if the offset in the node overflows, or
+    /// the error of the node.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        let buf = match self.len {
+            Some(_) if buf.is_empty() => return Ok(0),
+            Some(len) if offset >= len => return Err(VfsError::StorageFull),
+            Some(len) => &buf[..(buf.len() as u64).min(len - offset) as usize],
+            None => buf,
+        };
+        self.node.write_at(self.node_offset(offset)?, buf)
+    }
+
+    /// Truncates an unbounded slice to `size` bytes.
+    ///
+    /// The node is resized to end at the new end of the slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if the slice is bounded, since
+    /// resizing the node would discard or move the data after the slice, or
+    /// if the new size of the node overflows. Otherwise returns the error of
+    /// the node.
+    fn truncate(&self, size: u64) -> VfsResult {
+        if self.len.is_some() {
+            return Err(VfsError::InvalidInput);
+        }
+        self.node.truncate(self.node_offset(size)?)
+    }
+
+    /// Finds the next region of data or the next hole of the slice.
+    ///
+    /// The end of the slice is a hole, even if the node has data after it.
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        let size = self.size()?;
+        if offset >= size {
+            return Ok(None);
+        }
+        let found = self.node.seek_hint(self.node_offset(offset)?, hint)?;
+        Ok(match found.map(|pos| pos.saturating_sub(self.offset)) {
+            Some(pos) if pos < size => Some(pos),
+            _ if hint == SeekHint::Hole => Some(size),
+            _ => None,
+        })
+    }
+
+    /// Allocates or deallocates a range of the slice.
+    ///
+    /// The range is clipped to the end of a bounded slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `len` is `0`, or the error of
+    /// the node.
+    fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        if len == 0 {
+            return Err(VfsError::InvalidInput);
+        }
+        let len = match self.len {
+            Some(max) if offset >= max => return Ok(()),
+            Some(max) => len.min(max - offset),
+            None => len,
+        };
+        self.node.fallocate(self.node_offset(offset)?, len, mode)
+    }
+
+    /// Prefetches a range of the slice.
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        let size = self.size()?;
+        if offset >= size {
+            return Ok(());
+        }
+        let len = match len {
+            0 => size - offset,
+            len => len.min(size - offset),
+        };
+        self.node.readahead(self.node_offset(offset)?, len)
+    }
+
+    /// Synchronizes the node.
+    fn fsync(&self) -> VfsResult {
+        self.node.fsync()
+    }
+
+    /// Synchronizes the data of the node.
+    fn fsync_data(&self) -> VfsResult {
+        self.node.fsync_data()
+    }
+
+    /// Returns a page of the slice to map in memory.
+    ///
+    /// The page of the node is shared with the mapping, so a page of a
+    /// bounded slice must be inside the range: a page crossing its end
+    /// would expose the data of the node after the slice.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `offset` is beyond the end of
+    /// the slice, if the page crosses the end of a bounded slice, or if the
+    /// slice does not start on a page boundary of the node.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        let page_size = crate::page::PAGE_SIZE as u64;
+        if !self.offset.is_multiple_of(page_size) || offset >= self.size()? {
+            return Err(VfsError::InvalidInput);
+        }
+        if let Some(len) = self.len {
+            if offset.checked_add(page_size).is_none_or(|end| end > len) {
+                return Err(VfsError::InvalidInput);
+            }
+        }
+        self.node.get_page(self.node_offset(offset)?)
+    }
+
+    crate::impl_vfs_non_dir_default! {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{VfsNodePerm, VfsNodeType};
+    use alloc::{sync::Arc, vec::Vec};
+    use spin::Mutex;
+
+    struct Blob(Mutex<Vec<u8>>);
+
+    impl VfsNodeOps for Blob {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            let perm = VfsNodePerm::from_bits_truncate(0o644);
+            let size = self.0.lock().len() as u64;
+            Ok(VfsNodeAttr::new(perm, VfsNodeType::File, size, 1))
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            let data = self.0.lock();
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            Ok(n)
+        }
+
+        fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            let mut data = self.0.lock();
+            let end = offset as usize + buf.len();
+            if data.len() < end {
+                data.resize(end, 0);
+            }
+            data[offset as usize..end].copy_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn truncate(&self, size: u64) -> VfsResult {
+            self.0.lock().resize(size as usize, 0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_slice_offsets() {
+        let blob = Arc::new(Blob(Mutex::new(b"0123456789".to_vec())));
+        let slice = SliceNode::new(blob.clone(), 4, Some(4));
+        assert_eq!(slice.get_attr().unwrap().size(), 4);
+        assert_eq!(slice.get_attr().unwrap().perm().bits(), 0o644);
+
+        let mut buf = [0; 8];
+        assert_eq!(slice.read_at(1, &mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"567");
+        assert_eq!(slice.write_at(2, b"abc"), Ok(2));
+        assert_eq!(slice.write_at(4, b"x"), Err(VfsError::StorageFull));
+        assert_eq!(slice.write_at(4, b""), Ok(0));
+        assert_eq!(&*blob.0.lock(), b"012345ab89");
+        assert_eq!(slice.fallocate(4, 1, FallocateMode::empty()), Ok(()));
+        assert_eq!(
+            slice.fallocate(0, 1, FallocateMode::empty()),
+            Err(VfsError::Unsupported)
+        );
+
+        // a bounded slice never resizes the node
+        assert_eq!(slice.truncate(1), Err(VfsError::InvalidInput));
+        assert_eq!(
+            slice.set_attr(&SetAttr::new().size(0)),
+            Err(VfsError::InvalidInput)
+        );
+        slice.open(OpenFlags::WRITE | OpenFlags::TRUNC).unwrap();
+        assert_eq!(&*blob.0.lock(), b"012345ab89");
+        assert_eq!(slice.get_attr().unwrap().size(), 4);
+    }
+
+    #[test]
+    fn test_slice_failed_truncation() {
+        use core::sync::atomic::{AtomicUsize, Ordering};
+
+        // a node whose size is fixed, counting its opens
+        #[derive(Default)]
+        struct Fixed(AtomicUsize);
+
+        impl VfsNodeOps for Fixed {
+            fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Ok(None)
+            }
+
+            fn release(&self) -> VfsResult {
+                self.0.fetch_sub(1, Ordering::Relaxed);
+                Ok(())
+            }
+
+            fn truncate(&self, _size: u64) -> VfsResult {
+                Err(VfsError::PermissionDenied)
+            }
+        }
+
+        let node = Arc::new(Fixed::default());
+        let slice = SliceNode::new(node.clone(), 2, None);
+        assert_eq!(
+            slice.open(OpenFlags::WRITE | OpenFlags::TRUNC).err(),
+            Some(VfsError::PermissionDenied)
+        );
+        assert_eq!(node.0.load(Ordering::Relaxed), 0);
+        slice.open(OpenFlags::WRITE).unwrap();
+        assert_eq!(node.0.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_slice_offset_overflow() {
+        let blob = Arc::new(Blob(Mutex::new(b"0123".to_vec())));
+        let slice = SliceNode::new(blob, u64::MAX, None);
+        assert_eq!(slice.write_at(1, b"x"), Err(VfsError::InvalidInput));
+        assert_eq!(slice.truncate(1), Err(VfsError::InvalidInput));
+        assert_eq!(slice.get_attr().unwrap().size(), 0);
+    }
+
+    #[test]
+    fn test_unbounded_slice() {
+        let blob = Arc::new(Blob(Mutex::new(b"header".to_vec())));
+        let body = SliceNode::new(blob.clone(), 6, None);
+        assert_eq!(body.get_attr().unwrap().size(), 0);
+        assert_eq!(body.write_at(0, b"body"), Ok(4));
+        assert_eq!(body.get_attr().unwrap().size(), 4);
+        assert_eq!(&*blob.0.lock(), b"headerbody");
+
+        body.open(OpenFlags::WRITE | OpenFlags::TRUNC).unwrap();
+        assert_eq!(&*blob.0.lock(), b"header");
+        body.set_attr(&SetAttr::new().size(2)).unwrap();
+        assert_eq!(blob.0.lock().len(), 8);
+        // past the end of the node
+        let mut buf = [0; 4];
+        let far = SliceNode::new(blob, 20, Some(4));
+        assert_eq!(far.read_at(0, &mut buf), Ok(0));
+        assert_eq!(far.get_attr().unwrap().size(), 0);
+        assert_eq!(far.seek_hint(0, SeekHint::Data), Ok(None));
+    }
+
+    #[test]
+    fn test_slice_seek_hint() {
+        let blob = Arc::new(Blob(Mutex::new(b"0123456789".to_vec())));
+        let slice = SliceNode::new(blob, 2, Some(4));
+        assert_eq!(slice.seek_hint(1, SeekHint::Data), Ok(Some(1)));
+        // the data after the slice is not part of it
+        assert_eq!(slice.seek_hint(1, SeekHint::Hole), Ok(Some(4)));
+        assert_eq!(slice.seek_hint(4, SeekHint::Hole), Ok(None));
+    }
+}
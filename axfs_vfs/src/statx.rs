@@ -0,0 +1,175 @@
+use core::time::Duration;
+
+use crate::VfsNodeAttr;
+
+bitflags::bitflags! {
+    /// The fields of a [`VfsNodeAttrExt`], as requested from and returned by
+    /// [`VfsNodeOps::get_attr_ext`](crate::VfsNodeOps::get_attr_ext).
+    ///
+    /// The values are those of the Linux `STATX_*` mask bits, so they can be
+    /// passed through the `statx()` system call unchanged.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct AttrMask: u32 {
+        /// The type of the node.
+        const TYPE = 0x1;
+        /// The permissions of the node.
+        const MODE = 0x2;
+        /// The number of hard links.
+        const NLINK = 0x4;
+        /// The user ID of the owner.
+        const UID = 0x8;
+        /// The group ID of the owner.
+        const GID = 0x10;
+        /// The time of last access.
+        const ATIME = 0x20;
+        /// The time of last modification.
+        const MTIME = 0x40;
+        /// The time of last status change.
+        const CTIME = 0x80;
+        /// The inode number.
+        const INO = 0x100;
+        /// The size, in bytes.
+        const SIZE = 0x200;
+        /// The number of 512B blocks allocated.
+        const BLOCKS = 0x400;
+        /// All the fields of `stat()`.
+        const BASIC_STATS = 0x7ff;
+    }
+}
+
+/// Node attributes of which only some fields may be valid, as returned by
+/// [`VfsNodeOps::get_attr_ext`](crate::VfsNodeOps::get_attr_ext).
+///
+/// The type and permissions are always valid. The other fields are only
+/// valid if their bit is in [`mask()`](Self::mask), and their getters
+/// return `None` otherwise.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{AttrMask, VfsNodeAttr, VfsNodeAttrExt};
+///
+/// let attr = VfsNodeAttr::new_file(100, 1).with_ino(7);
+/// let ext = VfsNodeAttrExt::new(attr, AttrMask::TYPE | AttrMask::MODE | AttrMask::SIZE);
+/// assert_eq!(ext.size(), Some(100));
+/// assert_eq!(ext.ino(), None);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct VfsNodeAttrExt {
+    attr: VfsNodeAttr,
+    mask: AttrMask,
+}
+
+impl VfsNodeAttrExt {
+    /// Creates attributes whose fields in `mask` are valid.
+    ///
+    /// The type and permissions are always valid, so they are added to
+    /// `mask`.
+    pub const fn new(attr: VfsNodeAttr, mask: AttrMask) -> Self {
+        Self {
+            attr,
+            mask: mask.union(AttrMask::TYPE).union(AttrMask::MODE),
+        }
+    }
+
+    /// Returns the fields that are valid.
+    pub const fn mask(&self) -> AttrMask {
+        self.mask
+    }
+
+    /// Returns the attributes, whose fields not in [`mask()`](Self::mask)
+    /// hold unspecified values.
+    pub const fn attr(&self) -> &VfsNodeAttr {
+        &self.attr
+    }
+
+    /// Returns the value of a field, if it is valid.
+    fn field<T>(&self, field: AttrMask, value: T) -> Option<T> {
+        self.mask.contains(field).then_some(value)
+    }
+
+    /// Returns the inode number, if it is valid.
+    pub fn ino(&self) -> Option<u64> {
+        self.field(AttrMask::INO, self.attr.ino())
+    }
+
+    /// Returns the number of hard links, if it is valid.
+    pub fn nlink(&self) -> Option<u64> {
+        self.field(AttrMask::NLINK, self.attr.nlink())
+    }
+
+    /// Returns the user ID of the owner, if it is valid.
+    pub fn uid(&self) -> Option<u32> {
+        self.field(AttrMask::UID, self.attr.uid())
+    }
+
+    /// Returns the group ID of the owner, if it is valid.
+    pub fn gid(&self) -> Option<u32> {
+        self.field(AttrMask::GID, self.attr.gid())
+    }
+
+    /// Returns the size, in bytes, if it is valid.
+    pub fn size(&self) -> Option<u64> {
+        self.field(AttrMask::SIZE, self.attr.size())
+    }
+
+    /// Returns the number of 512B blocks allocated, if it is valid.
+    pub fn blocks(&self) -> Option<u64> {
+        self.field(AttrMask::BLOCKS, self.attr.blocks())
+    }
+
+    /// Returns the time of last access, if it is valid.
+    pub fn atime(&self) -> Option<Duration> {
+        self.field(AttrMask::ATIME, self.attr.atime())
+    }
+
+    /// Returns the time of last modification, if it is valid.
+    pub fn mtime(&self) -> Option<Duration> {
+        self.field(AttrMask::MTIME, self.attr.mtime())
+    }
+
+    /// Returns the time of last status change, if it is valid.
+    pub fn ctime(&self) -> Option<Duration> {
+        self.field(AttrMask::CTIME, self.attr.ctime())
+    }
+}
+
+impl From<VfsNodeAttr> for VfsNodeAttrExt {
+    /// Converts complete attributes, with all the fields of `stat()`.
+    fn from(attr: VfsNodeAttr) -> Self {
+        Self::new(attr, AttrMask::BASIC_STATS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attr_ext_mask() {
+        let attr = VfsNodeAttr::new_file(10, 1)
+            .with_ino(3)
+            .with_nlink(2)
+            .with_times(
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(3),
+            );
+        let ext = VfsNodeAttrExt::new(attr, AttrMask::MTIME | AttrMask::NLINK);
+        assert_eq!(
+            ext.mask(),
+            AttrMask::TYPE | AttrMask::MODE | AttrMask::MTIME | AttrMask::NLINK
+        );
+        assert!(ext.attr().is_file());
+        assert_eq!(ext.mtime(), Some(Duration::from_secs(2)));
+        assert_eq!(ext.nlink(), Some(2));
+        assert_eq!(ext.atime(), None);
+        assert_eq!(ext.size(), None);
+        assert_eq!(ext.blocks(), None);
+
+        let full = VfsNodeAttrExt::from(attr);
+        assert_eq!(full.mask(), AttrMask::BASIC_STATS);
+        assert_eq!(full.ino(), Some(3));
+        assert_eq!(full.ctime(), Some(Duration::from_secs(3)));
+    }
+}
@@ -1,21 +1,218 @@
+use alloc::boxed::Box;
+use core::time::Duration;
+
+use crate::limits::MAX_NAME_LEN;
+
 /// Filesystem attributes.
 ///
 /// This structure contains information about the filesystem, such as
-/// total size, available space, block size, etc.
-///
-/// # Note
+/// total size, available space, block size, etc. It is returned by
+/// [`VfsOps::statfs`](crate::VfsOps::statfs), and mirrors the Linux
+/// `struct statfs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileSystemInfo {
+    /// Magic number of the filesystem type.
+    fs_type: u64,
+    /// Size of a block, in bytes.
+    block_size: u64,
+    /// Total number of blocks.
+    blocks: u64,
+    /// Number of free blocks.
+    blocks_free: u64,
+    /// Total number of inodes.
+    files: u64,
+    /// Number of free inodes.
+    files_free: u64,
+    /// Maximum length of a file name, in bytes.
+    name_len: u64,
+}
+
+bitflags::bitflags! {
+    /// Optional features supported by a filesystem.
+    ///
+    /// Generic code (mount managers, archivers, test suites) can check these
+    /// flags to adapt its behavior, instead of probing the filesystem with
+    /// operations that may have side effects.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct VfsFeatures: u32 {
+        /// Symbolic links can be created.
+        const SYMLINK = 1 << 0;
+        /// Hard links can be created.
+        const HARDLINK = 1 << 1;
+        /// Extended attributes are supported.
+        const XATTR = 1 << 2;
+        /// Names that only differ in case refer to different nodes.
+        const CASE_SENSITIVE = 1 << 3;
+        /// The directory tree cannot be modified.
+        const READ_ONLY = 1 << 4;
+        /// Nodes can be looked up by inode number with
+        /// [`VfsOps::open_by_ino`](crate::VfsOps::open_by_ino).
+        const OPEN_BY_INO = 1 << 5;
+        /// Changes can be watched with
+        /// [`VfsOps::subscribe`](crate::VfsOps::subscribe).
+        const WATCH = 1 << 6;
+    }
+}
+
+bitflags::bitflags! {
+    /// Protection flags of a node, as set by `chattr`.
+    ///
+    /// The values are those of the Linux `FS_*_FL` inode flags, so they can
+    /// be passed through the `FS_IOC_GETFLAGS` and `FS_IOC_SETFLAGS` ioctls
+    /// unchanged.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct VfsNodeFlags: u32 {
+        /// The node cannot be modified, removed, renamed or linked to, and
+        /// no entry can be added to or removed from a directory.
+        const IMMUTABLE = 0x10;
+        /// A file can only be written at its end, and entries can only be
+        /// added to a directory. The node cannot be removed, renamed or
+        /// linked to.
+        const APPEND = 0x20;
+    }
+}
+
+bitflags::bitflags! {
+    /// The access mode and options of an open, passed to
+    /// [`VfsNodeOps::open`](crate::VfsNodeOps::open).
+    ///
+    /// The values are not those of Linux, whose access modes are not bit
+    /// flags; use [`from_linux()`](Self::from_linux) to convert the flags
+    /// of an `open()` system call.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct OpenFlags: u32 {
+        /// The node is opened for reading.
+        const READ = 1 << 0;
+        /// The node is opened for writing.
+        const WRITE = 1 << 1;
+        /// Writes go to the end of the file.
+        const APPEND = 1 << 2;
+        /// The file is truncated to zero length if opened for writing.
+        const TRUNC = 1 << 3;
+        /// Operations on the node fail with
+        /// [`WouldBlock`](crate::VfsError::WouldBlock) instead of waiting.
+        const NONBLOCK = 1 << 4;
+        /// The node was created by this open, which must fail if it already
+        /// existed.
+        const EXCL = 1 << 5;
+    }
+}
+
+impl OpenFlags {
+    /// Converts the flags of a Linux `open()` system call.
+    ///
+    /// Flags without a counterpart (such as `O_CREAT`, handled by the
+    /// caller) are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::OpenFlags;
+    ///
+    /// // O_WRONLY | O_CREAT | O_TRUNC
+    /// let flags = OpenFlags::from_linux(0o1101);
+    /// assert_eq!(flags, OpenFlags::WRITE | OpenFlags::TRUNC);
+    /// ```
+    pub const fn from_linux(flags: u32) -> Self {
+        let mut bits = match flags & 0o3 {
+            0 => Self::READ.bits(),
+            1 => Self::WRITE.bits(),
+            _ => Self::READ.bits() | Self::WRITE.bits(),
+        };
+        if flags & 0o200 != 0 {
+            bits |= Self::EXCL.bits();
+        }
+        if flags & 0o1000 != 0 {
+            bits |= Self::TRUNC.bits();
+        }
+        if flags & 0o2000 != 0 {
+            bits |= Self::APPEND.bits();
+        }
+        if flags & 0o4000 != 0 {
+            bits |= Self::NONBLOCK.bits();
+        }
+        Self::from_bits_retain(bits)
+    }
+
+    /// Returns whether the node is opened for writing.
+    pub const fn is_writable(&self) -> bool {
+        self.contains(Self::WRITE)
+    }
+}
+
+bitflags::bitflags! {
+    /// The operation of [`VfsNodeOps::fallocate`](crate::VfsNodeOps::fallocate).
+    ///
+    /// Without flags, the range is allocated and the file is extended to
+    /// cover it. The values are those of the Linux `FALLOC_FL_*` flags.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct FallocateMode: u32 {
+        /// The size of the file is not changed, even if the range extends
+        /// beyond its end.
+        const KEEP_SIZE = 0x01;
+        /// The range is deallocated and reads back as zeros. Must be
+        /// combined with [`KEEP_SIZE`](Self::KEEP_SIZE).
+        const PUNCH_HOLE = 0x02;
+    }
+}
+
+bitflags::bitflags! {
+    /// The options of [`VfsNodeOps::rename_flags`](crate::VfsNodeOps::rename_flags).
+    ///
+    /// Without flags, an existing destination is replaced. The values are
+    /// those of the Linux `RENAME_*` flags of `renameat2()`.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct RenameFlags: u32 {
+        /// Fail with [`AlreadyExists`](crate::VfsError::AlreadyExists) if
+        /// the destination exists, instead of replacing it.
+        const NOREPLACE = 0x01;
+        /// Atomically swap the source and the destination, which must both
+        /// exist. They may be of different types.
+        const EXCHANGE = 0x02;
+    }
+}
+
+/// The kind of region searched by
+/// [`VfsNodeOps::seek_hint`](crate::VfsNodeOps::seek_hint), like the
+/// `SEEK_DATA` and `SEEK_HOLE` values of `lseek()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekHint {
+    /// The start of the next region containing data.
+    Data,
+    /// The start of the next hole. The end of the file is a hole.
+    Hole,
+}
+
+/// Filesystem capabilities.
 ///
-/// Currently this struct is not used and is reserved for future use.
-#[non_exhaustive]
-pub struct FileSystemInfo;
+/// This structure describes which optional features a filesystem supports,
+/// and the limits it imposes. It is returned by
+/// [`VfsOps::capabilities`](crate::VfsOps::capabilities).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VfsCapabilities {
+    /// Supported features.
+    features: VfsFeatures,
+    /// Maximum length of a file name, in bytes.
+    max_name_len: usize,
+}
 
 /// Node (file/directory) attributes.
 ///
-/// This structure contains metadata about a VFS node, including its
-/// permissions, type, size, and the number of blocks allocated.
+/// This structure contains metadata about a VFS node, including its inode
+/// number, permissions, owner, type, size, the number of blocks allocated,
+/// and its timestamps.
+///
+/// New attributes are added as private fields, so filesystems should build
+/// it with [`VfsNodeAttr::builder()`] (or one of the constructors) rather
+/// than rely on its layout.
 #[allow(dead_code)]
+#[non_exhaustive]
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VfsNodeAttr {
+    /// Inode number, `0` if unknown.
+    ino: u64,
     /// File permission mode.
     mode: VfsNodePerm,
     /// File type.
@@ -24,6 +221,32 @@ pub struct VfsNodeAttr {
     size: u64,
     /// Number of 512B blocks allocated.
     blocks: u64,
+    /// Number of hard links.
+    nlink: u64,
+    /// User ID of the owner.
+    uid: u32,
+    /// Group ID of the owner.
+    gid: u32,
+    /// Time of last access, since the Unix epoch.
+    atime: Duration,
+    /// Time of last modification, since the Unix epoch.
+    mtime: Duration,
+    /// Time of last status change, since the Unix epoch.
+    ctime: Duration,
+}
+
+/// A builder of [`VfsNodeAttr`], returned by [`VfsNodeAttr::builder()`].
+///
+/// Every attribute left unset keeps the value of [`VfsNodeAttr::new`]: a
+/// regular file of size `0`, owned by the superuser, with one link, an
+/// unknown inode number and all timestamps at the Unix epoch. The
+/// permission defaults to [`VfsNodePerm::default_dir()`] for directories
+/// and [`VfsNodePerm::default_file()`] otherwise.
+#[derive(Debug, Clone, Copy)]
+#[must_use]
+pub struct VfsNodeAttrBuilder {
+    attr: VfsNodeAttr,
+    perm: Option<VfsNodePerm>,
 }
 
 bitflags::bitflags! {
@@ -33,6 +256,7 @@ bitflags::bitflags! {
     /// Permissions are divided into three categories: owner, group, and others.
     /// Each category has read, write, and execute permissions.
     #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct VfsNodePerm: u16 {
         /// Owner has read permission.
         const OWNER_READ = 0o400;
@@ -64,6 +288,7 @@ bitflags::bitflags! {
 /// and more.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum VfsNodeType {
     /// FIFO (named pipe)
     Fifo = 0o1,
@@ -79,15 +304,291 @@ pub enum VfsNodeType {
     SymLink = 0o12,
     /// Socket
     Socket = 0o14,
+    /// Whiteout, marking an entry of a lower layer of an overlay as removed
+    /// (`DT_WHT`)
+    Whiteout = 0o16,
+    /// Unknown type, for directory entries whose type is not known without
+    /// looking the node up (`DT_UNKNOWN`)
+    Unknown = 0,
 }
 
 /// Directory entry.
 ///
 /// This structure represents a single entry in a directory, containing
-/// the entry's name and type. The name is limited to 63 bytes.
+/// the entry's inode number, name and type. Names of up to
+/// [`INLINE_NAME_LEN`](Self::INLINE_NAME_LEN) bytes are stored in the entry, longer ones on the
+/// heap, so names of any length are returned whole.
+#[derive(Clone)]
 pub struct VfsDirEntry {
+    d_ino: u64,
     d_type: VfsNodeType,
-    d_name: [u8; 63],
+    d_name: DirEntryName,
+}
+
+/// The length of the longest name stored in a [`VfsDirEntry`] without
+/// allocating.
+const INLINE_NAME_LEN: usize = VfsDirEntry::INLINE_NAME_LEN;
+
+/// The name of a [`VfsDirEntry`], inline if it is short enough.
+#[derive(Clone)]
+enum DirEntryName {
+    /// A name of at most [`INLINE_NAME_LEN`] bytes.
+    Inline { len: u8, buf: [u8; INLINE_NAME_LEN] },
+    /// A longer name.
+    Heap(Box<str>),
+}
+
+impl DirEntryName {
+    /// Stores `name`, on the heap if it does not fit inline.
+    fn new(name: &str) -> Self {
+        if name.len() > INLINE_NAME_LEN {
+            return Self::Heap(name.into());
+        }
+        let mut buf = [0; INLINE_NAME_LEN];
+        buf[..name.len()].copy_from_slice(name.as_bytes());
+        Self::Inline {
+            len: name.len() as u8,
+            buf,
+        }
+    }
+
+    /// Returns the bytes of the name.
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Inline { len, buf } => &buf[..*len as usize],
+            Self::Heap(name) => name.as_bytes(),
+        }
+    }
+}
+
+impl VfsCapabilities {
+    /// Creates a new `VfsCapabilities` with the given features and maximum
+    /// name length.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - The supported features
+    /// * `max_name_len` - The maximum length of a file name, in bytes
+    ///
+    /// # Returns
+    ///
+    /// A new `VfsCapabilities` instance.
+    pub const fn new(features: VfsFeatures, max_name_len: usize) -> Self {
+        Self {
+            features,
+            max_name_len,
+        }
+    }
+
+    /// Returns the supported features.
+    ///
+    /// # Returns
+    ///
+    /// A `VfsFeatures` with a bit set for every supported feature.
+    pub const fn features(&self) -> VfsFeatures {
+        self.features
+    }
+
+    /// Whether all the given features are supported.
+    ///
+    /// # Arguments
+    ///
+    /// * `features` - The features to check
+    ///
+    /// # Returns
+    ///
+    /// `true` if every feature in `features` is supported, `false` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::{VfsCapabilities, VfsFeatures};
+    ///
+    /// let caps = VfsCapabilities::new(VfsFeatures::SYMLINK, 255);
+    /// assert!(caps.supports(VfsFeatures::SYMLINK));
+    /// assert!(!caps.supports(VfsFeatures::SYMLINK | VfsFeatures::HARDLINK));
+    /// ```
+    pub const fn supports(&self, features: VfsFeatures) -> bool {
+        self.features.contains(features)
+    }
+
+    /// Returns the maximum length of a file name, in bytes.
+    ///
+    /// # Returns
+    ///
+    /// The maximum name length.
+    pub const fn max_name_len(&self) -> usize {
+        self.max_name_len
+    }
+}
+
+impl Default for VfsCapabilities {
+    /// Returns the capabilities of a basic filesystem: case-sensitive names
+    /// of up to [`MAX_NAME_LEN`] bytes, and no optional features.
+    fn default() -> Self {
+        Self::new(VfsFeatures::CASE_SENSITIVE, MAX_NAME_LEN)
+    }
+}
+
+impl FileSystemInfo {
+    /// Creates a new `FileSystemInfo` with no blocks and no inodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `fs_type` - The magic number of the filesystem type, as returned by
+    ///   [`VfsOps::fs_magic`](crate::VfsOps::fs_magic)
+    /// * `block_size` - The size of a block, in bytes
+    /// * `name_len` - The maximum length of a file name, in bytes
+    ///
+    /// # Returns
+    ///
+    /// A new `FileSystemInfo` instance.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::FileSystemInfo;
+    ///
+    /// let info = FileSystemInfo::new(0x8584_58f6, 4096, 255)
+    ///     .with_blocks(100, 40)
+    ///     .with_files(10, 0);
+    /// assert_eq!(info.block_size(), 4096);
+    /// assert_eq!(info.blocks_used(), 60);
+    /// assert_eq!(info.files(), 10);
+    /// ```
+    pub const fn new(fs_type: u64, block_size: u64, name_len: u64) -> Self {
+        Self {
+            fs_type,
+            block_size,
+            blocks: 0,
+            blocks_free: 0,
+            files: 0,
+            files_free: 0,
+            name_len,
+        }
+    }
+
+    /// Sets the total and free numbers of blocks.
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - The total number of blocks
+    /// * `free` - The number of free blocks, at most `total`
+    ///
+    /// # Returns
+    ///
+    /// The updated `FileSystemInfo`.
+    pub const fn with_blocks(mut self, total: u64, free: u64) -> Self {
+        self.blocks = total;
+        self.blocks_free = free;
+        self
+    }
+
+    /// Sets the total and free numbers of inodes.
+    ///
+    /// # Arguments
+    ///
+    /// * `total` - The total number of inodes
+    /// * `free` - The number of free inodes, at most `total`
+    ///
+    /// # Returns
+    ///
+    /// The updated `FileSystemInfo`.
+    pub const fn with_files(mut self, total: u64, free: u64) -> Self {
+        self.files = total;
+        self.files_free = free;
+        self
+    }
+
+    /// Returns the magic number of the filesystem type.
+    pub const fn fs_type(&self) -> u64 {
+        self.fs_type
+    }
+
+    /// Returns the size of a block, in bytes.
+    pub const fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// Returns the total number of blocks.
+    pub const fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// Returns the number of free blocks.
+    pub const fn blocks_free(&self) -> u64 {
+        self.blocks_free
+    }
+
+    /// Returns the number of blocks in use.
+    pub const fn blocks_used(&self) -> u64 {
+        self.blocks.saturating_sub(self.blocks_free)
+    }
+
+    /// Returns the total number of inodes.
+    pub const fn files(&self) -> u64 {
+        self.files
+    }
+
+    /// Returns the number of free inodes.
+    pub const fn files_free(&self) -> u64 {
+        self.files_free
+    }
+
+    /// Returns the maximum length of a file name, in bytes.
+    pub const fn name_len(&self) -> u64 {
+        self.name_len
+    }
+}
+
+/// A file mode creation mask, the permission bits to clear on the nodes
+/// created by a task.
+///
+/// Only the low 9 bits, the read, write and execute permissions, are kept.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::{Umask, VfsNodePerm};
+///
+/// let umask = Umask::new(0o027);
+/// assert_eq!(VfsNodePerm::default_file().apply_umask(umask).mode(), 0o640);
+/// assert_eq!(VfsNodePerm::default_dir().apply_umask(umask).mode(), 0o750);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Umask(u16);
+
+impl Umask {
+    /// The usual mask, `0o022`, which clears the write permission of the
+    /// group and others.
+    pub const DEFAULT: Self = Self(0o022);
+
+    /// Creates a mask clearing the permission bits of `bits`.
+    ///
+    /// # Arguments
+    ///
+    /// * `bits` - The permission bits to clear; only the low 9 bits are used
+    pub const fn new(bits: u16) -> Self {
+        Self(bits & 0o777)
+    }
+
+    /// Returns the permission bits cleared by the mask.
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+}
+
+impl Default for Umask {
+    /// Returns [`Umask::DEFAULT`].
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl From<u16> for Umask {
+    fn from(bits: u16) -> Self {
+        Self::new(bits)
+    }
 }
 
 impl VfsNodePerm {
@@ -153,6 +654,18 @@ impl VfsNodePerm {
         self.bits() as u32
     }
 
+    /// Returns the permission without the bits cleared by `umask`.
+    ///
+    /// This gives the permission of a new node, created with this
+    /// permission requested by a task whose mask is `umask`.
+    ///
+    /// # Arguments
+    ///
+    /// * `umask` - The file mode creation mask of the task
+    pub const fn apply_umask(self, umask: Umask) -> Self {
+        Self::from_bits_truncate(self.bits() & !umask.bits())
+    }
+
     /// Returns a 9-bytes string representation of the permission.
     ///
     /// The representation follows the standard Unix `ls -l` format:
@@ -290,6 +803,16 @@ impl VfsNodeType {
         matches!(self, Self::Socket)
     }
 
+    /// Returns `true` if this node type is a whiteout.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the node type is [`Whiteout`](Self::Whiteout), `false`
+    /// otherwise.
+    pub const fn is_whiteout(self) -> bool {
+        matches!(self, Self::Whiteout)
+    }
+
     /// Returns a character representation of the node type.
     ///
     /// This follows the standard Unix `ls -l` format:
@@ -300,6 +823,8 @@ impl VfsNodeType {
     /// - `c` for character device
     /// - `b` for block device
     /// - `s` for socket
+    /// - `w` for whiteout, as in BSD
+    /// - `?` for an unknown type
     ///
     /// # Returns
     ///
@@ -323,14 +848,97 @@ impl VfsNodeType {
             Self::File => '-',
             Self::SymLink => 'l',
             Self::Socket => 's',
+            Self::Whiteout => 'w',
+            Self::Unknown => '?',
         }
     }
+
+    /// The mask of the file type bits of `st_mode` (`S_IFMT`).
+    pub const MODE_MASK: u32 = 0o170000;
+
+    /// Returns the node type of the file type bits of an `st_mode`.
+    ///
+    /// The permission bits are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - An `st_mode` value, such as `0o100644`
+    ///
+    /// # Returns
+    ///
+    /// The node type, or `None` if the type bits are not a known type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::VfsNodeType;
+    ///
+    /// assert_eq!(VfsNodeType::from_mode(0o040755), Some(VfsNodeType::Dir));
+    /// assert_eq!(VfsNodeType::from_mode(0o644), None);
+    /// ```
+    pub const fn from_mode(mode: u32) -> Option<Self> {
+        Some(match (mode & Self::MODE_MASK) >> 12 {
+            0o1 => Self::Fifo,
+            0o2 => Self::CharDevice,
+            0o4 => Self::Dir,
+            0o6 => Self::BlockDevice,
+            0o10 => Self::File,
+            0o12 => Self::SymLink,
+            0o14 => Self::Socket,
+            0o16 => Self::Whiteout,
+            _ => return None,
+        })
+    }
+
+    /// Returns the file type bits of `st_mode` for this type, such as
+    /// `S_IFDIR` (`0o040000`) for [`Dir`](Self::Dir).
+    ///
+    /// [`Whiteout`](Self::Whiteout) gives the BSD `S_IFWHT` (`0o160000`),
+    /// and [`Unknown`](Self::Unknown) gives `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::VfsNodeType;
+    ///
+    /// assert_eq!(VfsNodeType::File.to_mode_bits(), 0o100000);
+    /// ```
+    pub const fn to_mode_bits(self) -> u32 {
+        (self as u32) << 12
+    }
 }
 
 impl VfsNodeAttr {
+    /// Returns a builder of attributes, to set only the attributes the
+    /// filesystem knows about.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::{VfsNodeAttr, VfsNodeType};
+    ///
+    /// let attr = VfsNodeAttr::builder()
+    ///     .ty(VfsNodeType::Dir)
+    ///     .size(4096)
+    ///     .ino(2)
+    ///     .build();
+    /// assert!(attr.is_dir());
+    /// assert_eq!((attr.size(), attr.ino()), (4096, 2));
+    /// assert_eq!(attr.perm().mode(), 0o755);
+    /// ```
+    pub const fn builder() -> VfsNodeAttrBuilder {
+        VfsNodeAttrBuilder::new()
+    }
+
     /// Creates a new `VfsNodeAttr` with the given permission mode, type, size
     /// and number of blocks.
     ///
+    /// All timestamps are set to the Unix epoch, use
+    /// [`with_times`](Self::with_times) to fill them. The node is owned by
+    /// the superuser, use [`with_owner`](Self::with_owner) to change it. The
+    /// inode number is unknown (`0`), use [`with_ino`](Self::with_ino) to
+    /// set it.
+    ///
     /// # Arguments
     ///
     /// * `mode` - The permission mode for the node
@@ -343,10 +951,17 @@ impl VfsNodeAttr {
     /// A new `VfsNodeAttr` instance.
     pub const fn new(mode: VfsNodePerm, ty: VfsNodeType, size: u64, blocks: u64) -> Self {
         Self {
+            ino: 0,
             mode,
             ty,
             size,
             blocks,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -365,10 +980,17 @@ impl VfsNodeAttr {
     /// A new `VfsNodeAttr` instance for a file.
     pub const fn new_file(size: u64, blocks: u64) -> Self {
         Self {
+            ino: 0,
             mode: VfsNodePerm::default_file(),
             ty: VfsNodeType::File,
             size,
             blocks,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
@@ -388,13 +1010,181 @@ impl VfsNodeAttr {
     /// A new `VfsNodeAttr` instance for a directory.
     pub const fn new_dir(size: u64, blocks: u64) -> Self {
         Self {
+            ino: 0,
             mode: VfsNodePerm::default_dir(),
             ty: VfsNodeType::Dir,
             size,
             blocks,
+            nlink: 1,
+            uid: 0,
+            gid: 0,
+            atime: Duration::ZERO,
+            mtime: Duration::ZERO,
+            ctime: Duration::ZERO,
         }
     }
 
+    /// Returns a copy of the attributes with the given timestamps.
+    ///
+    /// # Arguments
+    ///
+    /// * `atime` - The time of last access
+    /// * `mtime` - The time of last modification
+    /// * `ctime` - The time of last status change
+    ///
+    /// # Returns
+    ///
+    /// The updated `VfsNodeAttr`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::VfsNodeAttr;
+    /// use core::time::Duration;
+    ///
+    /// let t = Duration::from_secs(1);
+    /// let attr = VfsNodeAttr::new_file(0, 0).with_times(t, t * 2, t * 3);
+    /// assert_eq!(attr.mtime(), Duration::from_secs(2));
+    /// ```
+    pub const fn with_times(self, atime: Duration, mtime: Duration, ctime: Duration) -> Self {
+        Self {
+            atime,
+            mtime,
+            ctime,
+            ..self
+        }
+    }
+
+    /// Returns a copy of the attributes with the given number of hard links.
+    ///
+    /// # Arguments
+    ///
+    /// * `nlink` - The number of directory entries referring to the node
+    ///
+    /// # Returns
+    ///
+    /// The updated `VfsNodeAttr`.
+    pub const fn with_nlink(self, nlink: u64) -> Self {
+        Self { nlink, ..self }
+    }
+
+    /// Returns a copy of the attributes with the given inode number.
+    ///
+    /// # Arguments
+    ///
+    /// * `ino` - The inode number, unique among the nodes of the filesystem
+    ///
+    /// # Returns
+    ///
+    /// The updated `VfsNodeAttr`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::VfsNodeAttr;
+    ///
+    /// let attr = VfsNodeAttr::new_file(0, 0);
+    /// assert_eq!(attr.ino(), 0);
+    /// assert_eq!(attr.with_ino(42).ino(), 42);
+    /// ```
+    pub const fn with_ino(self, ino: u64) -> Self {
+        Self { ino, ..self }
+    }
+
+    /// Returns the inode number of the node.
+    ///
+    /// # Returns
+    ///
+    /// The inode number, `0` unless the filesystem reports it with
+    /// [`with_ino()`](Self::with_ino).
+    pub const fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    /// Returns a copy of the attributes with the given owner.
+    ///
+    /// # Arguments
+    ///
+    /// * `uid` - The user ID of the owner
+    /// * `gid` - The group ID of the owner
+    ///
+    /// # Returns
+    ///
+    /// The updated `VfsNodeAttr`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::VfsNodeAttr;
+    ///
+    /// let attr = VfsNodeAttr::new_file(0, 0);
+    /// assert_eq!((attr.uid(), attr.gid()), (0, 0));
+    /// let attr = attr.with_owner(1000, 100);
+    /// assert_eq!((attr.uid(), attr.gid()), (1000, 100));
+    /// ```
+    pub const fn with_owner(self, uid: u32, gid: u32) -> Self {
+        Self { uid, gid, ..self }
+    }
+
+    /// Returns the user ID of the owner of the node.
+    ///
+    /// # Returns
+    ///
+    /// The user ID, `0` unless the filesystem reports it with
+    /// [`with_owner()`](Self::with_owner).
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// Returns the group ID of the owner of the node.
+    ///
+    /// # Returns
+    ///
+    /// The group ID, `0` unless the filesystem reports it with
+    /// [`with_owner()`](Self::with_owner).
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    /// Returns the number of hard links to the node.
+    ///
+    /// This is `1` unless the filesystem reports it with
+    /// [`with_nlink()`](Self::with_nlink).
+    ///
+    /// # Returns
+    ///
+    /// The number of directory entries referring to the node.
+    pub const fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    /// Returns the time of last access.
+    ///
+    /// # Returns
+    ///
+    /// The time since the Unix epoch.
+    pub const fn atime(&self) -> Duration {
+        self.atime
+    }
+
+    /// Returns the time of last modification of the content.
+    ///
+    /// # Returns
+    ///
+    /// The time since the Unix epoch.
+    pub const fn mtime(&self) -> Duration {
+        self.mtime
+    }
+
+    /// Returns the time of last status change (content or metadata).
+    ///
+    /// # Returns
+    ///
+    /// The time since the Unix epoch.
+    pub const fn ctime(&self) -> Duration {
+        self.ctime
+    }
+
     /// Returns the size of the node.
     ///
     /// # Returns
@@ -433,6 +1223,20 @@ impl VfsNodeAttr {
         self.mode = perm
     }
 
+    /// Returns the `st_mode` of the node: its file type bits and
+    /// permission bits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::VfsNodeAttr;
+    ///
+    /// assert_eq!(VfsNodeAttr::new_dir(0, 0).st_mode(), 0o040755);
+    /// ```
+    pub const fn st_mode(&self) -> u32 {
+        self.ty.to_mode_bits() | self.mode.mode()
+    }
+
     /// Returns the type of the node.
     ///
     /// # Returns
@@ -461,25 +1265,124 @@ impl VfsNodeAttr {
     }
 }
 
+impl VfsNodeAttrBuilder {
+    /// Creates a builder with every attribute unset.
+    pub const fn new() -> Self {
+        Self {
+            attr: VfsNodeAttr::new(VfsNodePerm::empty(), VfsNodeType::File, 0, 0),
+            perm: None,
+        }
+    }
+
+    /// Sets the inode number.
+    pub const fn ino(mut self, ino: u64) -> Self {
+        self.attr.ino = ino;
+        self
+    }
+
+    /// Sets the permission mode.
+    pub const fn perm(mut self, perm: VfsNodePerm) -> Self {
+        self.perm = Some(perm);
+        self
+    }
+
+    /// Sets the type of the node.
+    pub const fn ty(mut self, ty: VfsNodeType) -> Self {
+        self.attr.ty = ty;
+        self
+    }
+
+    /// Sets the total size, in bytes.
+    pub const fn size(mut self, size: u64) -> Self {
+        self.attr.size = size;
+        self
+    }
+
+    /// Sets the number of 512-byte blocks allocated.
+    pub const fn blocks(mut self, blocks: u64) -> Self {
+        self.attr.blocks = blocks;
+        self
+    }
+
+    /// Sets the number of hard links.
+    pub const fn nlink(mut self, nlink: u64) -> Self {
+        self.attr.nlink = nlink;
+        self
+    }
+
+    /// Sets the user and group IDs of the owner.
+    pub const fn owner(mut self, uid: u32, gid: u32) -> Self {
+        self.attr.uid = uid;
+        self.attr.gid = gid;
+        self
+    }
+
+    /// Sets the time of last access.
+    pub const fn atime(mut self, atime: Duration) -> Self {
+        self.attr.atime = atime;
+        self
+    }
+
+    /// Sets the time of last modification.
+    pub const fn mtime(mut self, mtime: Duration) -> Self {
+        self.attr.mtime = mtime;
+        self
+    }
+
+    /// Sets the time of last status change.
+    pub const fn ctime(mut self, ctime: Duration) -> Self {
+        self.attr.ctime = ctime;
+        self
+    }
+
+    /// Builds the attributes.
+    pub const fn build(self) -> VfsNodeAttr {
+        let mode = match self.perm {
+            Some(perm) => perm,
+            None if self.attr.ty.is_dir() => VfsNodePerm::default_dir(),
+            None => VfsNodePerm::default_file(),
+        };
+        VfsNodeAttr { mode, ..self.attr }
+    }
+}
+
+impl Default for VfsNodeAttrBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl VfsDirEntry {
+    /// The length of the longest name stored in the entry itself, longer
+    /// ones being allocated on the heap.
+    pub const INLINE_NAME_LEN: usize = 46;
+
     /// Creates an empty `VfsDirEntry`.
     ///
-    /// The default entry has type `VfsNodeType::File` and an empty name.
+    /// The default entry has type `VfsNodeType::File`, an empty name and an
+    /// unknown inode number.
     ///
     /// # Returns
     ///
     /// A new `VfsDirEntry` with default values.
     pub const fn default() -> Self {
         Self {
+            d_ino: 0,
             d_type: VfsNodeType::File,
-            d_name: [0; 63],
+            d_name: DirEntryName::Inline {
+                len: 0,
+                buf: [0; INLINE_NAME_LEN],
+            },
         }
     }
 
     /// Creates a new `VfsDirEntry` with the given name and type.
     ///
-    /// The name is truncated to 63 bytes if it exceeds that length.
-    /// A warning is logged if truncation occurs.
+    /// The inode number is unknown (`0`), use [`with_ino`](Self::with_ino)
+    /// to set it.
+    ///
+    /// Names longer than [`INLINE_NAME_LEN`](Self::INLINE_NAME_LEN) bytes are allocated on the
+    /// heap.
     ///
     /// # Arguments
     ///
@@ -498,18 +1401,41 @@ impl VfsDirEntry {
     /// let entry = VfsDirEntry::new("test.txt", VfsNodeType::File);
     /// assert_eq!(entry.entry_type(), VfsNodeType::File);
     /// assert_eq!(entry.name_as_bytes(), b"test.txt");
+    ///
+    /// let long = "n".repeat(300);
+    /// assert_eq!(VfsDirEntry::new(&long, VfsNodeType::File).name(), long);
     /// ```
     pub fn new(name: &str, ty: VfsNodeType) -> Self {
-        let mut d_name = [0; 63];
-        if name.len() > d_name.len() {
-            log::warn!(
-                "directory entry name too long: {} > {}",
-                name.len(),
-                d_name.len()
-            );
+        Self {
+            d_ino: 0,
+            d_type: ty,
+            d_name: DirEntryName::new(name),
         }
-        d_name[..name.len()].copy_from_slice(name.as_bytes());
-        Self { d_type: ty, d_name }
+    }
+
+    /// Returns the entry with the given inode number.
+    ///
+    /// # Arguments
+    ///
+    /// * `ino` - The inode number of the node the entry refers to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use axfs_vfs::{VfsDirEntry, VfsNodeType};
+    ///
+    /// let entry = VfsDirEntry::new("a", VfsNodeType::File).with_ino(7);
+    /// assert_eq!(entry.ino(), 7);
+    /// ```
+    pub fn with_ino(mut self, ino: u64) -> Self {
+        self.d_ino = ino;
+        self
+    }
+
+    /// Returns the inode number of the node the entry refers to, `0` if
+    /// unknown.
+    pub fn ino(&self) -> u64 {
+        self.d_ino
     }
 
     /// Returns the type of the entry.
@@ -523,18 +1449,64 @@ impl VfsDirEntry {
 
     /// Converts the name of the entry to a byte slice.
     ///
-    /// The returned slice contains only the name up to the first null terminator.
-    ///
     /// # Returns
     ///
     /// A byte slice containing the entry name.
     pub fn name_as_bytes(&self) -> &[u8] {
-        let len = self
-            .d_name
-            .iter()
-            .position(|&c| c == 0)
-            .unwrap_or(self.d_name.len());
-        &self.d_name[..len]
+        self.d_name.as_bytes()
+    }
+
+    /// Returns the name of the entry.
+    pub fn name(&self) -> &str {
+        match &self.d_name {
+            // always valid, as built from a `&str`
+            DirEntryName::Inline { .. } => {
+                core::str::from_utf8(self.d_name.as_bytes()).unwrap_or_default()
+            }
+            DirEntryName::Heap(name) => name,
+        }
+    }
+
+    /// Returns the length of the name of the entry in bytes.
+    pub fn name_len(&self) -> usize {
+        self.d_name.as_bytes().len()
+    }
+
+    /// Returns whether the name of the entry is allocated on the heap, that
+    /// is longer than [`INLINE_NAME_LEN`](Self::INLINE_NAME_LEN) bytes.
+    pub fn is_name_on_heap(&self) -> bool {
+        matches!(self.d_name, DirEntryName::Heap(_))
+    }
+}
+
+/// The serialized form of a [`VfsDirEntry`], whose name is stored inline or
+/// on the heap.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "VfsDirEntry")]
+struct DirEntryRepr<N> {
+    ino: u64,
+    ty: VfsNodeType,
+    name: N,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for VfsDirEntry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DirEntryRepr {
+            ino: self.ino(),
+            ty: self.entry_type(),
+            name: self.name(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VfsDirEntry {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = DirEntryRepr::<alloc::string::String>::deserialize(deserializer)?;
+        Ok(Self::new(&repr.name, repr.ty).with_ino(repr.ino))
     }
 }
 
@@ -542,6 +1514,51 @@ impl VfsDirEntry {
 mod tests {
     use super::*;
 
+    // VfsCapabilities tests
+    #[test]
+    fn test_capabilities_default() {
+        let caps = VfsCapabilities::default();
+        assert_eq!(caps.features(), VfsFeatures::CASE_SENSITIVE);
+        assert!(!caps.supports(VfsFeatures::SYMLINK));
+        assert!(!caps.supports(VfsFeatures::READ_ONLY));
+        assert_eq!(caps.max_name_len(), MAX_NAME_LEN);
+    }
+
+    #[test]
+    fn test_capabilities_supports() {
+        let caps = VfsCapabilities::new(VfsFeatures::SYMLINK | VfsFeatures::HARDLINK, 63);
+        assert!(caps.supports(VfsFeatures::SYMLINK));
+        assert!(caps.supports(VfsFeatures::HARDLINK));
+        assert!(caps.supports(VfsFeatures::empty()));
+        assert!(!caps.supports(VfsFeatures::SYMLINK | VfsFeatures::XATTR));
+        assert_eq!(caps.max_name_len(), 63);
+    }
+
+    #[test]
+    fn test_fs_info() {
+        let info = FileSystemInfo::new(0x1373, 512, 63);
+        assert_eq!(info.fs_type(), 0x1373);
+        assert_eq!(info.block_size(), 512);
+        assert_eq!(info.name_len(), 63);
+        assert_eq!((info.blocks(), info.blocks_free(), info.files()), (0, 0, 0));
+
+        let info = info.with_blocks(8, 3).with_files(5, 2);
+        assert_eq!((info.blocks(), info.blocks_free()), (8, 3));
+        assert_eq!(info.blocks_used(), 5);
+        assert_eq!((info.files(), info.files_free()), (5, 2));
+    }
+
+    #[test]
+    fn test_node_flags() {
+        assert_eq!(VfsNodeFlags::default(), VfsNodeFlags::empty());
+        assert_eq!(VfsNodeFlags::IMMUTABLE.bits(), 0x10);
+        assert_eq!(VfsNodeFlags::APPEND.bits(), 0x20);
+        assert_eq!(
+            VfsNodeFlags::from_bits_truncate(0x31),
+            VfsNodeFlags::IMMUTABLE | VfsNodeFlags::APPEND
+        );
+    }
+
     // VfsNodePerm tests
     #[test]
     fn test_perm_default_file() {
@@ -577,6 +1594,17 @@ mod tests {
         assert_eq!(perm.mode(), 0o666u32);
     }
 
+    #[test]
+    fn test_perm_apply_umask() {
+        let perm = VfsNodePerm::from_bits_truncate(0o777);
+        assert_eq!(perm.apply_umask(Umask::DEFAULT).mode(), 0o755);
+        assert_eq!(perm.apply_umask(Umask::new(0)).mode(), 0o777);
+        // only the permission bits are kept in the mask
+        assert_eq!(Umask::new(0o7077).bits(), 0o077);
+        assert_eq!(Umask::from(0o7077), Umask::new(0o077));
+        assert_eq!(Umask::default(), Umask::DEFAULT);
+    }
+
     #[test]
     fn test_perm_rwx_buf() {
         let perm = VfsNodePerm::default_file();
@@ -660,9 +1688,37 @@ mod tests {
         assert_eq!(VfsNodeType::File.as_char(), '-');
         assert_eq!(VfsNodeType::SymLink.as_char(), 'l');
         assert_eq!(VfsNodeType::Socket.as_char(), 's');
+        assert_eq!(VfsNodeType::Whiteout.as_char(), 'w');
+        assert_eq!(VfsNodeType::Unknown.as_char(), '?');
     }
 
     // VfsNodeAttr tests
+    #[test]
+    fn test_node_attr_builder() {
+        let attr = VfsNodeAttr::builder().build();
+        assert!(attr.is_file());
+        assert_eq!(attr.perm().mode(), 0o666);
+        assert_eq!((attr.ino(), attr.size(), attr.nlink()), (0, 0, 1));
+
+        let t = Duration::from_secs(5);
+        let attr = VfsNodeAttr::builder()
+            .ty(VfsNodeType::SymLink)
+            .perm(VfsNodePerm::from_bits_truncate(0o777))
+            .size(11)
+            .blocks(1)
+            .nlink(3)
+            .owner(1000, 100)
+            .mtime(t)
+            .build();
+        assert_eq!(attr.st_mode(), 0o120777);
+        assert_eq!((attr.size(), attr.blocks(), attr.nlink()), (11, 1, 3));
+        assert_eq!((attr.uid(), attr.gid()), (1000, 100));
+        assert_eq!((attr.atime(), attr.mtime()), (Duration::ZERO, t));
+
+        let attr = VfsNodeAttr::builder().ty(VfsNodeType::Dir).build();
+        assert_eq!(attr.perm().mode(), 0o755);
+    }
+
     #[test]
     fn test_node_attr_new() {
         let perm = VfsNodePerm::OWNER_READ | VfsNodePerm::OWNER_WRITE;
@@ -703,6 +1759,32 @@ mod tests {
         assert!(attr.perm().contains(VfsNodePerm::OTHER_EXEC));
     }
 
+    #[test]
+    fn test_node_attr_times() {
+        let attr = VfsNodeAttr::new_dir(0, 0);
+        assert_eq!(attr.atime(), Duration::ZERO);
+        assert_eq!(attr.mtime(), Duration::ZERO);
+        assert_eq!(attr.ctime(), Duration::ZERO);
+
+        let (a, m, c) = (
+            Duration::from_secs(3),
+            Duration::from_secs(2),
+            Duration::from_secs(1),
+        );
+        let attr = attr.with_times(a, m, c);
+        assert_eq!((attr.atime(), attr.mtime(), attr.ctime()), (a, m, c));
+        assert!(attr.is_dir());
+    }
+
+    #[test]
+    fn test_node_attr_nlink() {
+        let attr = VfsNodeAttr::new_file(0, 0);
+        assert_eq!(attr.nlink(), 1);
+        let attr = attr.with_nlink(3);
+        assert_eq!(attr.nlink(), 3);
+        assert!(attr.is_file());
+    }
+
     #[test]
     fn test_node_attr_is_file() {
         let attr = VfsNodeAttr::new_file(100, 1);
@@ -763,8 +1845,11 @@ mod tests {
     #[test]
     fn test_dir_entry_name_as_bytes_with_null() {
         let entry = VfsDirEntry::new("hello", VfsNodeType::File);
-        // d_name should contain null terminator
-        assert_eq!(entry.d_name[5], 0);
+        // short names are stored inline, padded with nulls
+        match &entry.d_name {
+            DirEntryName::Inline { len, buf } => assert_eq!((*len, buf[5]), (5, 0)),
+            DirEntryName::Heap(_) => panic!("short name on the heap"),
+        }
         // name_as_bytes should only return the actual name
         assert_eq!(entry.name_as_bytes(), b"hello");
     }
@@ -775,4 +1860,88 @@ mod tests {
         assert_eq!(entry.name_as_bytes().len(), 0);
         assert_eq!(entry.entry_type(), VfsNodeType::File);
     }
+
+    #[test]
+    fn test_dir_entry_heap_name() {
+        let inline = "i".repeat(VfsDirEntry::INLINE_NAME_LEN);
+        let entry = VfsDirEntry::new(&inline, VfsNodeType::File);
+        assert!(!entry.is_name_on_heap());
+        assert_eq!(entry.name(), inline);
+
+        for len in [VfsDirEntry::INLINE_NAME_LEN + 1, 255, 1000] {
+            let name = "h".repeat(len);
+            let entry = VfsDirEntry::new(&name, VfsNodeType::Dir).with_ino(3);
+            assert!(entry.is_name_on_heap());
+            assert_eq!(entry.name_len(), len);
+            assert_eq!(entry.name(), name);
+            assert_eq!(entry.clone().name_as_bytes(), name.as_bytes());
+        }
+        assert_eq!(VfsDirEntry::new("é", VfsNodeType::File).name(), "é");
+    }
+
+    #[test]
+    fn test_node_type_mode_bits() {
+        for ty in [
+            VfsNodeType::Fifo,
+            VfsNodeType::CharDevice,
+            VfsNodeType::Dir,
+            VfsNodeType::BlockDevice,
+            VfsNodeType::File,
+            VfsNodeType::SymLink,
+            VfsNodeType::Socket,
+            VfsNodeType::Whiteout,
+        ] {
+            let bits = ty.to_mode_bits();
+            assert_eq!(bits & !VfsNodeType::MODE_MASK, 0);
+            assert_eq!(VfsNodeType::from_mode(bits | 0o7777), Some(ty));
+        }
+        assert_eq!(VfsNodeType::SymLink.to_mode_bits(), 0o120000);
+        assert_eq!(VfsNodeType::from_mode(0o030000), None);
+        assert_eq!(VfsNodeType::from_mode(0), None);
+        assert_eq!(VfsNodeType::Unknown.to_mode_bits(), 0);
+        assert!(VfsNodeType::Whiteout.is_whiteout());
+
+        let attr = VfsNodeAttr::new(
+            VfsNodePerm::from_bits_truncate(0o600),
+            VfsNodeType::CharDevice,
+            0,
+            0,
+        );
+        assert_eq!(attr.st_mode(), 0o020600);
+        assert_eq!(VfsNodeAttr::new_file(0, 0).st_mode(), 0o100666);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let attr = VfsNodeAttr::new_file(100, 1).with_ino(7).with_nlink(2);
+        let json = serde_json::to_string(&attr).unwrap();
+        let back: VfsNodeAttr = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&back).unwrap(), json);
+        assert_eq!((back.ino(), back.size(), back.nlink()), (7, 100, 2));
+        assert_eq!(back.perm().bits(), 0o666);
+        assert!(back.is_file());
+
+        let ty: VfsNodeType = serde_json::from_str("\"SymLink\"").unwrap();
+        assert_eq!(ty, VfsNodeType::SymLink);
+
+        let info = FileSystemInfo::new(0x858458f6, 4096, 255);
+        let json = serde_json::to_string(&info).unwrap();
+        assert_eq!(serde_json::from_str::<FileSystemInfo>(&json).unwrap(), info);
+
+        let long = "n".repeat(100);
+        for name in ["a.txt", long.as_str()] {
+            let entry = VfsDirEntry::new(name, VfsNodeType::Dir).with_ino(3);
+            let json = serde_json::to_string(&entry).unwrap();
+            let back: VfsDirEntry = serde_json::from_str(&json).unwrap();
+            assert_eq!(back.name(), name);
+            assert_eq!(back.entry_type(), VfsNodeType::Dir);
+            assert_eq!(back.ino(), 3);
+            assert_eq!(back.is_name_on_heap(), entry.is_name_on_heap());
+        }
+        assert_eq!(
+            serde_json::to_string(&VfsDirEntry::new("a", VfsNodeType::File)).unwrap(),
+            r#"{"ino":0,"ty":"File","name":"a"}"#
+        );
+    }
 }
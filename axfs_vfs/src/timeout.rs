@@ -0,0 +1,693 @@
+//! Per-operation deadlines for unreliable backends.
+//!
+//! A filesystem backed by a network or a device that can stall may never
+//! return from an operation. [`TimeoutFs`] wraps such a filesystem, and
+//! gives each operation on it, and on its nodes, a deadline of its own. The
+//! [`OpTimer`] supplied by the caller is armed with the identifier and the
+//! deadline of each operation, so that it can interrupt the wait of the
+//! backend for that operation, for example by programming a timer interrupt
+//! that aborts the pending request. The concurrent operations do not share
+//! their deadlines.
+//!
+//! The deadlines are best-effort. An operation that fails after its
+//! deadline fails with [`VfsError::TimedOut`], as the failure may come from
+//! the interruption of the backend. An operation that succeeds late returns
+//! its result as is, since its effects cannot be undone, such as the data
+//! consumed by a read of a device or a pipe, or the waker left registered
+//! by [`register_waker()`](VfsNodeOps::register_waker). The missed deadline
+//! is reported to [`OpTimer::overrun`] instead.
+//!
+//! The deadlines are only enforced by the [`OpTimer`]: the wrapper itself
+//! cannot interrupt a backend, so an operation that never returns still
+//! hangs, unless the timer aborts it.
+//!
+//! The per-open files and the directory streams of the nodes are returned
+//! as is, and are not covered by the deadlines.
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+
+use crate::export::ExportHandle;
+use crate::notify::{WatchId, WatchMask, WatchSink};
+use crate::{
+    impl_vfs_forward, AttrMask, DeviceId, DirStream, FallocateMode, FileSystemInfo, IoSlice,
+    IoSliceMut, MountOptions, OpenFlags, PollEvents, ReadDirOptions, ReadHint, RenameFlags,
+    SeekHint, SetAttr, VfsCapabilities, VfsClock, VfsContext, VfsDirEntry, VfsError, VfsFileRef,
+    VfsNodeAttr, VfsNodeAttrExt, VfsNodeFlags, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps,
+    VfsPage, VfsResult,
+};
+
+/// The identifier of an operation bounded by an [`OpTimer`], unique in its
+/// [`TimeoutFs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OpId(u64);
+
+impl OpId {
+    /// Returns the raw value of the identifier, such as to tag the request
+    /// sent to the backend.
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+/// The timer bounding the operations of a [`TimeoutFs`].
+///
+/// The time is read with [`VfsClock::now`]; it only needs to be monotonic.
+/// Several operations may be armed at the same time, each with its own
+/// deadline.
+pub trait OpTimer: VfsClock {
+    /// Called before an operation, with its deadline.
+    ///
+    /// It is called by the task running the operation, right before it
+    /// enters the backend, so the timer can tie `op` to that task. An
+    /// implementation able to interrupt the backend should interrupt the
+    /// operation `op` once `deadline` has passed, such as by waking the task
+    /// with an error. The default implementation does nothing, so the late
+    /// operations are only detected when they return, and an operation that
+    /// never returns hangs.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The identifier of the operation
+    /// * `deadline` - The time, as returned by [`now()`](VfsClock::now), at
+    ///   which the operation times out
+    fn arm(&self, _op: OpId, _deadline: Duration) {}
+
+    /// Called after the operation `op`, armed by [`arm()`](Self::arm),
+    /// returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The identifier of the operation
+    fn disarm(&self, _op: OpId) {}
+
+    /// Called after [`disarm()`](Self::disarm) when the operation `op`
+    /// succeeded after its deadline.
+    ///
+    /// The result of the operation is returned as is, so this is the only
+    /// report of the missed deadline, such as to log the slow backend or to
+    /// count the overruns. The default implementation does nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The identifier of the operation
+    /// * `late` - How long after its deadline the operation returned
+    fn overrun(&self, _op: OpId, _late: Duration) {}
+}
+
+/// The deadline policy shared by a [`TimeoutFs`] and its nodes.
+struct Guard {
+    timer: Arc<dyn OpTimer>,
+    timeout: Duration,
+    next_op: AtomicU64,
+}
+
+impl Guard {
+    fn new(timer: Arc<dyn OpTimer>, timeout: Duration) -> Self {
+        Self {
+            timer,
+            timeout,
+            next_op: AtomicU64::new(0),
+        }
+    }
+
+    /// Runs `op` with a deadline of its own.
+    ///
+    /// A late success is returned as is, since its effects took place, and
+    /// reported to [`OpTimer::overrun`]. A late failure becomes
+    /// [`VfsError::TimedOut`].
+    fn run<R>(&self, op: impl FnOnce() -> VfsResult<R>) -> VfsResult<R> {
+        let id = OpId(self.next_op.fetch_add(1, Ordering::Relaxed));
+        let deadline = self.timer.now().saturating_add(self.timeout);
+        self.timer.arm(id, deadline);
+        let result = op();
+        self.timer.disarm(id);
+        let now = self.timer.now();
+        if now <= deadline {
+            return result;
+        }
+        if result.is_ok() {
+            self.timer.overrun(id, now - deadline);
+        }
+        result.map_err(|_| VfsError::TimedOut)
+    }
+}
+
+/// A filesystem whose operations failing after a deadline fail with
+/// [`VfsError::TimedOut`].
+///
+/// The deadlines are best-effort: the operations succeeding late are only
+/// reported to [`OpTimer::overrun`], see the [module documentation](self).
+///
+/// The nodes returned by the filesystem are wrapped in [`TimeoutNode`]s,
+/// with the same deadline.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::time::Duration;
+/// use axfs_vfs::clock::ManualClock;
+/// use axfs_vfs::timeout::{OpTimer, TimeoutFs};
+/// use axfs_vfs::{VfsClock, VfsError, VfsNodeOps, VfsNodeRef, VfsOps, VfsResult};
+///
+/// struct Timer(ManualClock);
+///
+/// impl VfsClock for Timer {
+///     fn now(&self) -> Duration {
+///         self.0.now()
+///     }
+/// }
+///
+/// impl OpTimer for Timer {}
+///
+/// struct SlowFs(Arc<Timer>);
+///
+/// impl VfsOps for SlowFs {
+///     fn root_dir(&self) -> VfsNodeRef {
+///         unimplemented!()
+///     }
+///     fn statfs(&self) -> VfsResult<axfs_vfs::FileSystemInfo> {
+///         // the backend stalls for 10 seconds
+///         self.0 .0.advance(Duration::from_secs(10));
+///         Err(VfsError::Io)
+///     }
+/// }
+///
+/// let timer = Arc::new(Timer(ManualClock::new(Duration::ZERO)));
+/// let fs = TimeoutFs::new(Arc::new(SlowFs(timer.clone())), timer, Duration::from_secs(1));
+/// assert_eq!(fs.statfs().err(), Some(VfsError::TimedOut));
+/// ```
+pub struct TimeoutFs {
+    inner: Arc<dyn VfsOps>,
+    guard: Arc<Guard>,
+}
+
+impl TimeoutFs {
+    /// Wraps the filesystem `inner`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The filesystem to wrap
+    /// * `timer` - The timer measuring and bounding the operations
+    /// * `timeout` - The maximum duration of an operation
+    pub fn new(inner: Arc<dyn VfsOps>, timer: Arc<dyn OpTimer>, timeout: Duration) -> Self {
+        Self {
+            inner,
+            guard: Arc::new(Guard::new(timer, timeout)),
+        }
+    }
+
+    /// Returns the wrapped filesystem.
+    pub fn inner(&self) -> &Arc<dyn VfsOps> {
+        &self.inner
+    }
+
+    /// Returns the maximum duration of an operation.
+    pub fn timeout(&self) -> Duration {
+        self.guard.timeout
+    }
+
+    /// Wraps a node of the filesystem.
+    fn wrap(&self, node: VfsNodeRef) -> VfsNodeRef {
+        TimeoutNode::wrap(node, &self.guard)
+    }
+}
+
+impl VfsOps for TimeoutFs {
+    fn mount(&self, path: &str, mount_point: VfsNodeRef, options: &MountOptions) -> VfsResult {
+        self.guard
+            .run(|| self.inner.mount(path, mount_point, options))
+    }
+
+    fn remount(&self, options: &MountOptions) -> VfsResult {
+        self.guard.run(|| self.inner.remount(options))
+    }
+
+    fn umount(&self) -> VfsResult {
+        self.guard.run(|| self.inner.umount())
+    }
+
+    fn shutdown(&self) -> VfsResult {
+        self.guard.run(|| self.inner.shutdown())
+    }
+
+    fn format(&self) -> VfsResult {
+        self.guard.run(|| self.inner.format())
+    }
+
+    fn statfs(&self) -> VfsResult<FileSystemInfo> {
+        self.guard.run(|| self.inner.statfs())
+    }
+
+    fn freeze(&self) -> VfsResult {
+        self.guard.run(|| self.inner.freeze())
+    }
+
+    fn thaw(&self) -> VfsResult {
+        self.guard.run(|| self.inner.thaw())
+    }
+
+    fn capabilities(&self) -> VfsCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn fs_type(&self) -> &str {
+        self.inner.fs_type()
+    }
+
+    fn fs_magic(&self) -> u64 {
+        self.inner.fs_magic()
+    }
+
+    fn open_by_ino(&self, ino: u64) -> VfsResult<VfsNodeRef> {
+        let node = self.guard.run(|| self.inner.open_by_ino(ino))?;
+        Ok(self.wrap(node))
+    }
+
+    fn encode_fh(&self, node: &VfsNodeRef) -> VfsResult<ExportHandle> {
+        // the inner filesystem only knows the inner nodes
+        let node = match node.as_any().downcast_ref::<TimeoutNode>() {
+            Some(node) => node.inner.clone(),
+            None => node.clone(),
+        };
+        self.guard.run(|| self.inner.encode_fh(&node))
+    }
+
+    fn open_by_handle(&self, fh: &ExportHandle) -> VfsResult<VfsNodeRef> {
+        let node = self.guard.run(|| self.inner.open_by_handle(fh))?;
+        Ok(self.wrap(node))
+    }
+
+    fn subscribe(
+        &self,
+        path: &str,
+        mask: WatchMask,
+        sink: Arc<dyn WatchSink>,
+    ) -> VfsResult<WatchId> {
+        self.guard.run(|| self.inner.subscribe(path, mask, sink))
+    }
+
+    fn unsubscribe(&self, id: WatchId) -> VfsResult {
+        self.guard.run(|| self.inner.unsubscribe(id))
+    }
+
+    fn root_dir(&self) -> VfsNodeRef {
+        self.wrap(self.inner.root_dir())
+    }
+}
+
+/// A node of a [`TimeoutFs`], whose operations failing after a deadline
+/// fail with [`VfsError::TimedOut`].
+///
+/// The nodes it returns, from a lookup or as its parent, are wrapped too.
+/// Releasing the node is never bounded, so that its resources are always
+/// freed.
+pub struct TimeoutNode {
+    inner: VfsNodeRef,
+    guard: Arc<Guard>,
+}
+
+impl TimeoutNode {
+    fn wrap(inner: VfsNodeRef, guard: &Arc<Guard>) -> VfsNodeRef {
+        Arc::new(Self {
+            inner,
+            guard: guard.clone(),
+        })
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &VfsNodeRef {
+        &self.inner
+    }
+}
+
+impl VfsNodeOps for TimeoutNode {
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        self.guard.run(|| self.inner.open(flags))
+    }
+
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        self.guard.run(|| self.inner.get_attr())
+    }
+
+    fn set_attr(&self, attr: &SetAttr) -> VfsResult {
+        self.guard.run(|| self.inner.set_attr(attr))
+    }
+
+    fn get_attr_ext(&self, mask: AttrMask) -> VfsResult<VfsNodeAttrExt> {
+        self.guard.run(|| self.inner.get_attr_ext(mask))
+    }
+
+    fn get_flags(&self) -> VfsResult<VfsNodeFlags> {
+        self.guard.run(|| self.inner.get_flags())
+    }
+
+    fn set_flags(&self, flags: VfsNodeFlags) -> VfsResult {
+        self.guard.run(|| self.inner.set_flags(flags))
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.read_at(offset, buf))
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.write_at(offset, buf))
+    }
+
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.read_vectored_at(offset, bufs))
+    }
+
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        self.guard
+            .run(|| self.inner.write_vectored_at(offset, bufs))
+    }
+
+    fn read_direct_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.read_direct_at(offset, buf))
+    }
+
+    fn write_direct_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.write_direct_at(offset, buf))
+    }
+
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        self.guard.run(|| self.inner.seek_hint(offset, hint))
+    }
+
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        self.guard.run(|| self.inner.readahead(offset, len))
+    }
+
+    fn fsync(&self) -> VfsResult {
+        self.guard.run(|| self.inner.fsync())
+    }
+
+    fn fsync_data(&self) -> VfsResult {
+        self.guard.run(|| self.inner.fsync_data())
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.guard.run(|| self.inner.truncate(size))
+    }
+
+    fn fallocate(&self, offset: u64, len: u64, mode: FallocateMode) -> VfsResult {
+        self.guard.run(|| self.inner.fallocate(offset, len, mode))
+    }
+
+    fn copy_file_range(
+        &self,
+        src_off: u64,
+        dst: &dyn VfsNodeOps,
+        dst_off: u64,
+        len: usize,
+    ) -> VfsResult<usize> {
+        self.guard
+            .run(|| self.inner.copy_file_range(src_off, dst, dst_off, len))
+    }
+
+    fn ioctl(&self, cmd: u32, arg: usize) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.ioctl(cmd, arg))
+    }
+
+    fn poll(&self) -> VfsResult<PollEvents> {
+        self.guard.run(|| self.inner.poll())
+    }
+
+    fn register_waker(&self, events: PollEvents, waker: &Waker) -> VfsResult {
+        self.guard.run(|| self.inner.register_waker(events, waker))
+    }
+
+    fn read_hinted(&self, offset: u64, buf: &mut [u8], hint: ReadHint) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.read_hinted(offset, buf, hint))
+    }
+
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        self.guard.run(|| self.inner.get_page(offset))
+    }
+
+    fn read_link(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.read_link(buf))
+    }
+
+    fn set_mount_point(&self, mounted: bool) -> VfsResult {
+        self.guard.run(|| self.inner.set_mount_point(mounted))
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        let parent = self.inner.parent()?;
+        Some(Self::wrap(parent, &self.guard))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let node = self.guard.run(|| self.inner.clone().lookup(path))?;
+        Ok(Self::wrap(node, &self.guard))
+    }
+
+    fn lookup_step(self: Arc<Self>, path: &str) -> VfsResult<(VfsNodeRef, usize)> {
+        let (node, consumed) = self.guard.run(|| self.inner.clone().lookup_step(path))?;
+        Ok((Self::wrap(node, &self.guard), consumed))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.guard.run(|| self.inner.create(path, ty))
+    }
+
+    fn create_exclusive(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.guard.run(|| self.inner.create_exclusive(path, ty))
+    }
+
+    fn lookup_ctx(self: Arc<Self>, ctx: Option<&VfsContext>, path: &str) -> VfsResult<VfsNodeRef> {
+        let node = self
+            .guard
+            .run(|| self.inner.clone().lookup_ctx(ctx, path))?;
+        Ok(Self::wrap(node, &self.guard))
+    }
+
+    fn create_ctx(&self, ctx: Option<&VfsContext>, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.guard.run(|| self.inner.create_ctx(ctx, path, ty))
+    }
+
+    fn mknod(&self, path: &str, ty: VfsNodeType, dev: DeviceId) -> VfsResult {
+        self.guard.run(|| self.inner.mknod(path, ty, dev))
+    }
+
+    fn create_symlink(&self, path: &str, target: &str) -> VfsResult {
+        self.guard.run(|| self.inner.create_symlink(path, target))
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.guard.run(|| self.inner.remove(path))
+    }
+
+    fn remove_ctx(&self, ctx: Option<&VfsContext>, path: &str) -> VfsResult {
+        self.guard.run(|| self.inner.remove_ctx(ctx, path))
+    }
+
+    fn read_dir(&self, start_idx: usize, dirents: &mut [VfsDirEntry]) -> VfsResult<usize> {
+        self.guard.run(|| self.inner.read_dir(start_idx, dirents))
+    }
+
+    fn read_dir_opts(
+        &self,
+        start_idx: usize,
+        dirents: &mut [VfsDirEntry],
+        opts: &ReadDirOptions,
+    ) -> VfsResult<usize> {
+        self.guard
+            .run(|| self.inner.read_dir_opts(start_idx, dirents, opts))
+    }
+
+    fn read_dir_buf(&self, cursor: u64, buf: &mut [u8]) -> VfsResult<(usize, u64)> {
+        self.guard.run(|| self.inner.read_dir_buf(cursor, buf))
+    }
+
+    fn open_dir(self: Arc<Self>) -> VfsResult<Box<dyn DirStream>> {
+        self.guard.run(|| self.inner.clone().open_dir())
+    }
+
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.guard.run(|| self.inner.link(src_path, dst_path))
+    }
+
+    fn create_tmpfile(&self) -> VfsResult<VfsNodeRef> {
+        let node = self.guard.run(|| self.inner.create_tmpfile())?;
+        Ok(Self::wrap(node, &self.guard))
+    }
+
+    fn link_into(self: Arc<Self>, dir: &VfsNodeRef, name: &str) -> VfsResult {
+        // the inner node only knows the inner directories
+        let dir = match dir.as_any().downcast_ref::<TimeoutNode>() {
+            Some(dir) => dir.inner.clone(),
+            None => dir.clone(),
+        };
+        self.guard.run(|| self.inner.clone().link_into(&dir, name))
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.guard.run(|| self.inner.rename(src_path, dst_path))
+    }
+
+    fn rename_flags(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult {
+        self.guard
+            .run(|| self.inner.rename_flags(src_path, dst_path, flags))
+    }
+
+    impl_vfs_forward! {
+        inner;
+        except open, get_attr, set_attr, get_attr_ext, get_flags, set_flags, read_at, write_at,
+        read_vectored_at, write_vectored_at, read_direct_at, write_direct_at, seek_hint, readahead,
+        fsync, fsync_data, truncate, fallocate, copy_file_range, ioctl, poll, register_waker,
+        read_hinted, get_page, read_link, set_mount_point, parent, lookup, lookup_step, create,
+        create_exclusive, lookup_ctx, create_ctx, mknod, create_symlink, remove, remove_ctx,
+        read_dir, read_dir_opts, read_dir_buf, open_dir, link, create_tmpfile, link_into, rename,
+        rename_flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::impl_vfs_non_dir_default;
+    use alloc::vec::Vec;
+    use spin::Mutex;
+
+    #[derive(Default)]
+    struct Timer {
+        clock: ManualClock,
+        armed: Mutex<Vec<(OpId, Duration)>>,
+        overruns: Mutex<Vec<Duration>>,
+    }
+
+    impl VfsClock for Timer {
+        fn now(&self) -> Duration {
+            self.clock.now()
+        }
+    }
+
+    impl OpTimer for Timer {
+        fn arm(&self, op: OpId, deadline: Duration) {
+            assert_eq!(deadline, self.now() + Duration::from_secs(1));
+            let mut armed = self.armed.lock();
+            assert!(armed.iter().all(|&(id, _)| id != op));
+            armed.push((op, deadline));
+        }
+
+        fn disarm(&self, op: OpId) {
+            let mut armed = self.armed.lock();
+            let pos = armed.iter().position(|&(id, _)| id == op).unwrap();
+            armed.remove(pos);
+        }
+
+        fn overrun(&self, _op: OpId, late: Duration) {
+            self.overruns.lock().push(late);
+        }
+    }
+
+    /// A file whose reads and writes take as many seconds as the offset,
+    /// and fail from offset `3`.
+    ///
+    /// A read at offset `0` reads the file again at offset `1`, through the
+    /// wrapper held in the second field, while the first read is armed.
+    struct SlowFile(Arc<Timer>, Mutex<Option<VfsNodeRef>>);
+
+    impl VfsNodeOps for SlowFile {
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            self.0.clock.advance(Duration::from_secs(offset));
+            if offset == 0 {
+                if let Some(outer) = self.1.lock().clone() {
+                    assert_eq!(self.0.armed.lock().len(), 1);
+                    assert_eq!(outer.read_at(1, buf), Ok(buf.len()));
+                    // the nested read did not cancel the deadline of this one
+                    assert_eq!(self.0.armed.lock().len(), 1);
+                }
+            }
+            if offset >= 3 {
+                return Err(VfsError::Io);
+            }
+            Ok(buf.len())
+        }
+
+        fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            self.0.clock.advance(Duration::from_secs(offset));
+            if offset >= 3 {
+                return Err(VfsError::Io);
+            }
+            Ok(buf.len())
+        }
+
+        impl_vfs_non_dir_default! {}
+    }
+
+    struct Fs(Arc<Timer>);
+
+    impl VfsOps for Fs {
+        fn root_dir(&self) -> VfsNodeRef {
+            Arc::new(SlowFile(self.0.clone(), Mutex::new(None)))
+        }
+    }
+
+    fn new_fs() -> (Arc<Timer>, TimeoutFs) {
+        let timer = Arc::new(Timer::default());
+        let fs = TimeoutFs::new(
+            Arc::new(Fs(timer.clone())),
+            timer.clone(),
+            Duration::from_secs(1),
+        );
+        (timer, fs)
+    }
+
+    #[test]
+    fn test_timeout() {
+        let (timer, fs) = new_fs();
+        assert_eq!(fs.timeout(), Duration::from_secs(1));
+        let file = fs.root_dir();
+        assert!(file.as_any().is::<TimeoutNode>());
+        let mut buf = [0; 4];
+        assert_eq!(file.read_at(0, &mut buf), Ok(4));
+        assert_eq!(file.read_at(1, &mut buf), Ok(4));
+        assert!(timer.overruns.lock().is_empty());
+        // the late read consumed the data, so it is not reported as failed,
+        // only as late
+        assert_eq!(file.read_at(2, &mut buf), Ok(4));
+        assert_eq!(*timer.overruns.lock(), [Duration::from_secs(1)]);
+        assert_eq!(file.read_at(3, &mut buf), Err(VfsError::TimedOut));
+        assert_eq!(timer.overruns.lock().len(), 1);
+        assert_eq!(
+            file.clone().lookup("x").err(),
+            Some(VfsError::NotADirectory)
+        );
+        assert!(timer.armed.lock().is_empty());
+        assert_eq!(timer.now(), Duration::from_secs(6));
+    }
+
+    #[test]
+    fn test_timeout_late_mutation() {
+        let (timer, fs) = new_fs();
+        let file = fs.root_dir();
+        // the late write took effect, so it is not reported as failed
+        assert_eq!(file.write_at(2, b"data"), Ok(4));
+        assert_eq!(*timer.overruns.lock(), [Duration::from_secs(1)]);
+        assert_eq!(file.write_at(3, b"data"), Err(VfsError::TimedOut));
+        assert!(timer.armed.lock().is_empty());
+    }
+
+    #[test]
+    fn test_timeout_nested() {
+        let (timer, fs) = new_fs();
+        let file = fs.root_dir();
+        let inner = file.as_any().downcast_ref::<TimeoutNode>().unwrap();
+        let slow = inner.inner().as_any().downcast_ref::<SlowFile>().unwrap();
+        *slow.1.lock() = Some(file.clone());
+        // the nested read ends after 1 second, right at the deadline of the
+        // outer read
+        assert_eq!(file.read_at(0, &mut [0; 4]), Ok(4));
+        assert!(timer.armed.lock().is_empty());
+        *slow.1.lock() = None;
+    }
+}
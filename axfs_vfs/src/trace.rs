@@ -0,0 +1,860 @@
+//! Tracing of filesystem operations for postmortem debugging.
+//!
+//! A [`TraceRing`] keeps the last operations of the filesystems in memory,
+//! overwriting the oldest ones. Each [`TraceRecord`] holds the operation,
+//! the node and a hash of the path it was applied to, its result and its
+//! time, which is enough to reconstruct what led to a crash without a live
+//! debugger.
+//!
+//! Tracing is off until a ring is installed with [`set_global_ring`];
+//! filesystems then report their operations with [`record`], at the cost
+//! of an atomic load when it is off. The panic handler of the kernel
+//! writes the ring out with [`dump_global`].
+//!
+//! A node can also be wrapped in a [`TracedNode`], which reports the reads,
+//! writes and namespace operations on it, and on the nodes looked up
+//! through it, to a [`VfsTracer`] with their latency. This is meant to
+//! debug the I/O of an application without changing the filesystem. A
+//! [`TraceRing`] is itself a tracer.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//! use axfs_vfs::trace::{self, TraceOp, TraceRing};
+//! use axfs_vfs::VfsError;
+//!
+//! let ring = Arc::new(TraceRing::new(64));
+//! trace::set_global_ring(Some(ring.clone()));
+//! let _ = trace::record(TraceOp::Lookup, 1, "etc/passwd", Err::<(), _>(VfsError::NotFound));
+//!
+//! let mut out = String::new();
+//! trace::dump_global(&mut out).unwrap();
+//! assert!(out.contains("lookup ino=1"));
+//! # trace::set_global_ring(None);
+//! ```
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::time::Duration;
+
+use spin::{Mutex, RwLock};
+
+use crate::{
+    impl_vfs_forward, DeviceId, IoSlice, IoSliceMut, ReadHint, RenameFlags, VfsContext, VfsError,
+    VfsNodeOps, VfsNodeRef, VfsNodeType, VfsResult,
+};
+
+/// A traced operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceOp {
+    /// A path was looked up.
+    Lookup,
+    /// A file or directory was created.
+    Create,
+    /// A device node was created.
+    Mknod,
+    /// A symbolic link was created.
+    Symlink,
+    /// A hard link was created.
+    Link,
+    /// A node was moved.
+    Rename,
+    /// A node was removed.
+    Remove,
+    /// A file was read.
+    Read,
+    /// A file was written.
+    Write,
+    /// A file was truncated.
+    Truncate,
+}
+
+impl TraceOp {
+    /// Returns the name of the operation, as written in dumps.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Lookup => "lookup",
+            Self::Create => "create",
+            Self::Mknod => "mknod",
+            Self::Symlink => "symlink",
+            Self::Link => "link",
+            Self::Rename => "rename",
+            Self::Remove => "remove",
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::Truncate => "truncate",
+        }
+    }
+}
+
+/// An operation kept in a [`TraceRing`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// The sequence number of the record in its ring, starting at 0.
+    pub seq: u64,
+    /// The operation.
+    pub op: TraceOp,
+    /// The inode number of the node the operation was applied to, the
+    /// directory for the operations on entries.
+    pub ino: u64,
+    /// The [`hash_path`] of the path argument of the operation, relative to
+    /// the node `ino`, or 0 if it has none. Paths are hashed so that records
+    /// have a fixed size and do not leak file names in crash reports.
+    pub path_hash: u64,
+    /// The result of the operation.
+    pub result: Result<(), VfsError>,
+    /// The time of the operation, from the [global clock](crate::clock::now).
+    pub time: Duration,
+}
+
+/// Writes the record as one line, such as
+/// `#12 [5.000250] create ino=1 path=d7a8e7f0c2b1c3f5 -> ok`.
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} [{}.{:06}] {} ino={} path={:016x} -> ",
+            self.seq,
+            self.time.as_secs(),
+            self.time.subsec_micros(),
+            self.op.name(),
+            self.ino,
+            self.path_hash,
+        )?;
+        match self.result {
+            Ok(()) => f.write_str("ok"),
+            Err(e) => write!(f, "{e:?}"),
+        }
+    }
+}
+
+/// The records of a [`TraceRing`], with the position of the next one.
+struct RingState {
+    records: Vec<TraceRecord>,
+    next_seq: u64,
+}
+
+/// A fixed-size buffer of the last traced operations.
+///
+/// Once full, each new record overwrites the oldest one, so the memory used
+/// never grows.
+pub struct TraceRing {
+    capacity: usize,
+    state: Mutex<RingState>,
+}
+
+impl TraceRing {
+    /// Creates an empty ring.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - The number of records kept, at least 1
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            state: Mutex::new(RingState {
+                records: Vec::with_capacity(capacity),
+                next_seq: 0,
+            }),
+        }
+    }
+
+    /// Returns the number of records kept.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of records in the ring.
+    pub fn len(&self) -> usize {
+        self.state.lock().records.len()
+    }
+
+    /// Returns whether no operation was recorded since the ring was created
+    /// or cleared.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of records overwritten by newer ones.
+    pub fn dropped(&self) -> u64 {
+        let state = self.state.lock();
+        state.next_seq - state.records.len() as u64
+    }
+
+    /// Adds a record, overwriting the oldest one if the ring is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The operation
+    /// * `ino` - The inode number of the node the operation was applied to
+    /// * `path_hash` - The [`hash_path`] of the path argument, or 0
+    /// * `result` - The result of the operation
+    /// * `time` - The time of the operation
+    pub fn push(
+        &self,
+        op: TraceOp,
+        ino: u64,
+        path_hash: u64,
+        result: Result<(), VfsError>,
+        time: Duration,
+    ) {
+        let mut state = self.state.lock();
+        let seq = state.next_seq;
+        let record = TraceRecord {
+            seq,
+            op,
+            ino,
+            path_hash,
+            result,
+            time,
+        };
+        state.next_seq += 1;
+        if state.records.len() < self.capacity {
+            state.records.push(record);
+        } else {
+            state.records[(seq % self.capacity as u64) as usize] = record;
+        }
+    }
+
+    /// Returns the records in the ring, oldest first.
+    pub fn records(&self) -> Vec<TraceRecord> {
+        let state = self.state.lock();
+        let mut records = state.records.clone();
+        records.sort_unstable_by_key(|record| record.seq);
+        records
+    }
+
+    /// Removes all records, and restarts the sequence numbers at 0.
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.records.clear();
+        state.next_seq = 0;
+    }
+
+    /// Writes the records, oldest first, one per line.
+    ///
+    /// The ring is not locked if it is busy, such as when the panic to
+    /// report happened while recording: a line says so instead of
+    /// deadlocking.
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - The output, such as the console of the kernel
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `out`.
+    pub fn dump(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        let Some(state) = self.state.try_lock() else {
+            return writeln!(out, "vfs trace: ring busy");
+        };
+        let dropped = state.next_seq - state.records.len() as u64;
+        writeln!(
+            out,
+            "vfs trace: {} records, {dropped} dropped",
+            state.records.len()
+        )?;
+        let start = match state.records.len() < self.capacity {
+            true => 0,
+            false => (state.next_seq % self.capacity as u64) as usize,
+        };
+        let (older, newer) = state.records.split_at(start);
+        for record in newer.iter().chain(older) {
+            writeln!(out, "{record}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the hash of a path kept in a [`TraceRecord`].
+///
+/// This is the FNV-1a hash of the path, which is cheap and stable across
+/// builds, so that a hash can be matched with a path known to the reader of
+/// a dump.
+pub fn hash_path(path: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in path.as_bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100_0000_01b3);
+    }
+    hash
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static GLOBAL_RING: RwLock<Option<Arc<TraceRing>>> = RwLock::new(None);
+
+/// Installs the ring that receives the operations of all filesystems.
+///
+/// # Arguments
+///
+/// * `ring` - The new ring, or `None` to turn tracing off
+pub fn set_global_ring(ring: Option<Arc<TraceRing>>) {
+    let mut global = GLOBAL_RING.write();
+    ENABLED.store(ring.is_some(), Ordering::Release);
+    *global = ring;
+}
+
+/// Returns the ring installed with [`set_global_ring`], if any.
+pub fn global_ring() -> Option<Arc<TraceRing>> {
+    GLOBAL_RING.read().clone()
+}
+
+/// Records an operation in the global ring, if tracing is on.
+///
+/// # Arguments
+///
+/// * `op` - The operation
+/// * `ino` - The inode number of the node the operation was applied to
+/// * `path` - The path argument of the operation, relative to the node,
+///   empty if it has none
+/// * `result` - The result of the operation
+///
+/// # Returns
+///
+/// Returns `result`, so that an operation is traced by wrapping its result.
+pub fn record<T>(op: TraceOp, ino: u64, path: &str, result: VfsResult<T>) -> VfsResult<T> {
+    if ENABLED.load(Ordering::Acquire) {
+        if let Some(ring) = GLOBAL_RING.read().as_ref() {
+            let path_hash = match path {
+                "" => 0,
+                _ => hash_path(path),
+            };
+            let status = result.as_ref().map(|_| ()).map_err(|e| *e);
+            ring.push(op, ino, path_hash, status, crate::clock::now());
+        }
+    }
+    result
+}
+
+/// Writes the records of the global ring, such as from a panic handler.
+///
+/// # Arguments
+///
+/// * `out` - The output, such as the console of the kernel
+///
+/// # Errors
+///
+/// Returns the errors of `out`.
+pub fn dump_global(out: &mut dyn fmt::Write) -> fmt::Result {
+    let Some(global) = GLOBAL_RING.try_read() else {
+        return writeln!(out, "vfs trace: ring busy");
+    };
+    match global.as_ref() {
+        Some(ring) => ring.dump(out),
+        None => writeln!(out, "vfs trace: off"),
+    }
+}
+
+/// An operation reported by a [`TracedNode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent<'a> {
+    /// The operation.
+    pub op: TraceOp,
+    /// The inode number of the node the operation was applied to, the
+    /// directory for the operations on entries.
+    pub ino: u64,
+    /// The path argument of the operation, relative to the node `ino`,
+    /// empty if it has none. For a rename or a link, this is the source.
+    pub path: &'a str,
+    /// The offset of a read or a write, 0 for the other operations.
+    pub offset: u64,
+    /// The number of bytes read or written, 0 for the other operations
+    /// and for failed reads and writes.
+    pub bytes: usize,
+    /// The result of the operation.
+    pub result: Result<(), VfsError>,
+    /// The time the operation started, from [`VfsTracer::now`].
+    pub start: Duration,
+    /// The time the operation took.
+    pub latency: Duration,
+}
+
+/// The receiver of the operations of [`TracedNode`]s.
+pub trait VfsTracer: Send + Sync {
+    /// Called after each traced operation.
+    ///
+    /// This is called on the path of the operation, so it should be cheap,
+    /// and must not operate on traced nodes itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The operation and its outcome
+    fn on_op(&self, event: &TraceEvent<'_>);
+
+    /// Returns the current time, to measure the latency of the operations.
+    ///
+    /// The default implementation reads the [global clock](crate::clock::now),
+    /// a tracer can return a finer monotonic time instead.
+    fn now(&self) -> Duration {
+        crate::clock::now()
+    }
+}
+
+/// Records the events in the ring, without their latency.
+impl VfsTracer for TraceRing {
+    fn on_op(&self, event: &TraceEvent<'_>) {
+        let path_hash = match event.path {
+            "" => 0,
+            path => hash_path(path),
+        };
+        self.push(event.op, event.ino, path_hash, event.result, event.start);
+    }
+}
+
+/// A node whose operations are reported to a [`VfsTracer`].
+///
+/// The reads, writes, truncations, lookups and the operations creating,
+/// linking, renaming or removing entries are traced; the other operations
+/// are forwarded as is. The nodes it returns, from a lookup, as its parent
+/// or as an anonymous file, are wrapped too, with the same tracer, so that
+/// wrapping the root directory of a filesystem traces all of it.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use axfs_vfs::trace::{TraceEvent, TraceOp, TracedNode, VfsTracer};
+/// use axfs_vfs::{impl_vfs_non_dir_default, VfsNodeOps, VfsResult};
+///
+/// struct Zero;
+///
+/// impl VfsNodeOps for Zero {
+///     fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+///         buf.fill(0);
+///         Ok(buf.len())
+///     }
+///     impl_vfs_non_dir_default! {}
+/// }
+///
+/// #[derive(Default)]
+/// struct Log(Mutex<Vec<(TraceOp, usize)>>);
+///
+/// impl VfsTracer for Log {
+///     fn on_op(&self, event: &TraceEvent<'_>) {
+///         self.0.lock().unwrap().push((event.op, event.bytes));
+///     }
+/// }
+///
+/// let log = Arc::new(Log::default());
+/// let node = TracedNode::new(Arc::new(Zero), log.clone());
+/// node.read_at(0, &mut [1; 16]).unwrap();
+/// assert_eq!(*log.0.lock().unwrap(), [(TraceOp::Read, 16)]);
+/// ```
+pub struct TracedNode<N: ?Sized = dyn VfsNodeOps> {
+    inner: Arc<N>,
+    tracer: Arc<dyn VfsTracer>,
+    ino: u64,
+}
+
+impl<N: VfsNodeOps + ?Sized> TracedNode<N> {
+    /// Wraps the node `inner`.
+    ///
+    /// The inode number reported in the events is read once, here, and is
+    /// `0` if the attributes of the node are not available.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - The node to trace
+    /// * `tracer` - The receiver of the operations
+    pub fn new(inner: Arc<N>, tracer: Arc<dyn VfsTracer>) -> Self {
+        let ino = inner.get_attr().map_or(0, |attr| attr.ino());
+        Self { inner, tracer, ino }
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &Arc<N> {
+        &self.inner
+    }
+
+    /// Returns the tracer receiving the operations.
+    pub fn tracer(&self) -> &Arc<dyn VfsTracer> {
+        &self.tracer
+    }
+
+    /// Wraps a node returned by the wrapped node.
+    fn wrap(&self, node: VfsNodeRef) -> VfsNodeRef {
+        Arc::new(TracedNode::new(node, self.tracer.clone()))
+    }
+
+    /// Runs and reports an operation with a path argument.
+    fn run<R>(&self, op: TraceOp, path: &str, f: impl FnOnce() -> VfsResult<R>) -> VfsResult<R> {
+        let start = self.tracer.now();
+        let result = f();
+        self.report(op, path, 0, 0, result.as_ref().map(|_| ()), start);
+        result
+    }
+
+    /// Runs and reports a read or a write.
+    fn run_io(
+        &self,
+        op: TraceOp,
+        offset: u64,
+        f: impl FnOnce() -> VfsResult<usize>,
+    ) -> VfsResult<usize> {
+        let start = self.tracer.now();
+        let result = f();
+        let bytes = *result.as_ref().unwrap_or(&0);
+        self.report(op, "", offset, bytes, result.as_ref().map(|_| ()), start);
+        result
+    }
+
+    fn report(
+        &self,
+        op: TraceOp,
+        path: &str,
+        offset: u64,
+        bytes: usize,
+        result: Result<(), &VfsError>,
+        start: Duration,
+    ) {
+        self.tracer.on_op(&TraceEvent {
+            op,
+            ino: self.ino,
+            path,
+            offset,
+            bytes,
+            result: result.map_err(|e| *e),
+            start,
+            latency: self.tracer.now().saturating_sub(start),
+        });
+    }
+}
+
+impl<N: VfsNodeOps + ?Sized + 'static> VfsNodeOps for TracedNode<N> {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.run_io(TraceOp::Read, offset, || self.inner.read_at(offset, buf))
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.run_io(TraceOp::Write, offset, || self.inner.write_at(offset, buf))
+    }
+
+    fn read_vectored_at(&self, offset: u64, bufs: &mut [IoSliceMut]) -> VfsResult<usize> {
+        self.run_io(TraceOp::Read, offset, || {
+            self.inner.read_vectored_at(offset, bufs)
+        })
+    }
+
+    fn write_vectored_at(&self, offset: u64, bufs: &[IoSlice]) -> VfsResult<usize> {
+        self.run_io(TraceOp::Write, offset, || {
+            self.inner.write_vectored_at(offset, bufs)
+        })
+    }
+
+    fn read_direct_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.run_io(TraceOp::Read, offset, || {
+            self.inner.read_direct_at(offset, buf)
+        })
+    }
+
+    fn write_direct_at(&self, offset: u64, buf: &[u8]) -> VfsResult<usize> {
+        self.run_io(TraceOp::Write, offset, || {
+            self.inner.write_direct_at(offset, buf)
+        })
+    }
+
+    fn truncate(&self, size: u64) -> VfsResult {
+        self.run(TraceOp::Truncate, "", || self.inner.truncate(size))
+    }
+
+    fn read_hinted(&self, offset: u64, buf: &mut [u8], hint: ReadHint) -> VfsResult<usize> {
+        self.run_io(TraceOp::Read, offset, || {
+            self.inner.read_hinted(offset, buf, hint)
+        })
+    }
+
+    fn parent(&self) -> Option<VfsNodeRef> {
+        let parent = self.inner.parent()?;
+        Some(self.wrap(parent))
+    }
+
+    fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+        let node = self.run(TraceOp::Lookup, path, || self.inner.clone().lookup(path))?;
+        Ok(self.wrap(node))
+    }
+
+    fn lookup_step(self: Arc<Self>, path: &str) -> VfsResult<(VfsNodeRef, usize)> {
+        let (node, consumed) = self.run(TraceOp::Lookup, path, || {
+            self.inner.clone().lookup_step(path)
+        })?;
+        Ok((self.wrap(node), consumed))
+    }
+
+    fn create(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.run(TraceOp::Create, path, || self.inner.create(path, ty))
+    }
+
+    fn create_exclusive(&self, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.run(TraceOp::Create, path, || {
+            self.inner.create_exclusive(path, ty)
+        })
+    }
+
+    fn lookup_ctx(self: Arc<Self>, ctx: Option<&VfsContext>, path: &str) -> VfsResult<VfsNodeRef> {
+        let node = self.run(TraceOp::Lookup, path, || {
+            self.inner.clone().lookup_ctx(ctx, path)
+        })?;
+        Ok(self.wrap(node))
+    }
+
+    fn create_ctx(&self, ctx: Option<&VfsContext>, path: &str, ty: VfsNodeType) -> VfsResult {
+        self.run(TraceOp::Create, path, || {
+            self.inner.create_ctx(ctx, path, ty)
+        })
+    }
+
+    fn mknod(&self, path: &str, ty: VfsNodeType, dev: DeviceId) -> VfsResult {
+        self.run(TraceOp::Mknod, path, || self.inner.mknod(path, ty, dev))
+    }
+
+    fn create_symlink(&self, path: &str, target: &str) -> VfsResult {
+        self.run(TraceOp::Symlink, path, || {
+            self.inner.create_symlink(path, target)
+        })
+    }
+
+    fn remove(&self, path: &str) -> VfsResult {
+        self.run(TraceOp::Remove, path, || self.inner.remove(path))
+    }
+
+    fn remove_ctx(&self, ctx: Option<&VfsContext>, path: &str) -> VfsResult {
+        self.run(TraceOp::Remove, path, || self.inner.remove_ctx(ctx, path))
+    }
+
+    fn link(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.run(TraceOp::Link, src_path, || {
+            self.inner.link(src_path, dst_path)
+        })
+    }
+
+    fn create_tmpfile(&self) -> VfsResult<VfsNodeRef> {
+        let node = self.run(TraceOp::Create, "", || self.inner.create_tmpfile())?;
+        Ok(self.wrap(node))
+    }
+
+    fn link_into(self: Arc<Self>, dir: &VfsNodeRef, name: &str) -> VfsResult {
+        // the inner node only knows the inner directories
+        let dir = match dir.as_any().downcast_ref::<TracedNode>() {
+            Some(dir) => dir.inner.clone(),
+            None => dir.clone(),
+        };
+        self.run(TraceOp::Link, name, || {
+            self.inner.clone().link_into(&dir, name)
+        })
+    }
+
+    fn rename(&self, src_path: &str, dst_path: &str) -> VfsResult {
+        self.run(TraceOp::Rename, src_path, || {
+            self.inner.rename(src_path, dst_path)
+        })
+    }
+
+    fn rename_flags(&self, src_path: &str, dst_path: &str, flags: RenameFlags) -> VfsResult {
+        self.run(TraceOp::Rename, src_path, || {
+            self.inner.rename_flags(src_path, dst_path, flags)
+        })
+    }
+
+    impl_vfs_forward! {
+        inner;
+        except read_at, write_at, read_vectored_at, write_vectored_at, read_direct_at,
+        write_direct_at, truncate, read_hinted, parent, lookup, lookup_step, create,
+        create_exclusive, lookup_ctx, create_ctx, mknod, create_symlink, remove, remove_ctx, link,
+        create_tmpfile, link_into, rename, rename_flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::{impl_vfs_dir_default, impl_vfs_non_dir_default, VfsClock, VfsNodeAttr};
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn test_ring_wraps() {
+        let ring = TraceRing::new(3);
+        assert!(ring.is_empty());
+        for i in 0..5 {
+            let result = if i == 3 {
+                Err(VfsError::NotFound)
+            } else {
+                Ok(())
+            };
+            ring.push(TraceOp::Write, i, 0, result, Duration::from_millis(i));
+        }
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.dropped(), 2);
+        let records = ring.records();
+        let seqs: Vec<_> = records.iter().map(|r| r.seq).collect();
+        assert_eq!(seqs, [2, 3, 4]);
+        assert_eq!(records[1].result, Err(VfsError::NotFound));
+
+        let mut out = String::new();
+        ring.dump(&mut out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines[0], "vfs trace: 3 records, 2 dropped");
+        assert_eq!(
+            lines[1],
+            "#2 [0.002000] write ino=2 path=0000000000000000 -> ok"
+        );
+        assert!(lines[2].ends_with("-> NotFound"));
+        assert!(lines[3].starts_with("#4 "));
+
+        ring.clear();
+        assert!(ring.is_empty());
+        assert_eq!(TraceRing::new(0).capacity(), 1);
+    }
+
+    #[test]
+    fn test_dump_busy_ring() {
+        let ring = TraceRing::new(4);
+        let _guard = ring.state.lock();
+        let mut out = String::new();
+        ring.dump(&mut out).unwrap();
+        assert_eq!(out, "vfs trace: ring busy\n");
+    }
+
+    #[test]
+    fn test_global_ring() {
+        assert_eq!(record(TraceOp::Read, 1, "a", Ok(5)), Ok(5));
+        let ring = Arc::new(TraceRing::new(8));
+        set_global_ring(Some(ring.clone()));
+        assert_eq!(
+            record::<()>(TraceOp::Remove, 7, "a/b", Err(VfsError::NotFound)),
+            Err(VfsError::NotFound)
+        );
+        assert_eq!(record(TraceOp::Read, 7, "", Ok(5)), Ok(5));
+        set_global_ring(None);
+        record(TraceOp::Read, 7, "", Ok(())).unwrap();
+
+        let records = ring.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].path_hash, hash_path("a/b"));
+        assert_eq!(records[0].result, Err(VfsError::NotFound));
+        assert_eq!(records[1].path_hash, 0);
+        assert_eq!(records[1].result, Ok(()));
+
+        let mut out = String::new();
+        dump_global(&mut out).unwrap();
+        assert_eq!(out, "vfs trace: off\n");
+    }
+
+    /// An owned [`TraceEvent`].
+    struct Event {
+        op: TraceOp,
+        path: String,
+        bytes: usize,
+        result: Result<(), VfsError>,
+        latency: Duration,
+    }
+
+    /// A tracer keeping the events, timed by a manual clock.
+    #[derive(Default)]
+    struct Log {
+        clock: ManualClock,
+        events: Mutex<Vec<Event>>,
+    }
+
+    impl VfsTracer for Log {
+        fn on_op(&self, event: &TraceEvent<'_>) {
+            self.events.lock().push(Event {
+                op: event.op,
+                path: event.path.to_string(),
+                bytes: event.bytes,
+                result: event.result,
+                latency: event.latency,
+            });
+        }
+
+        fn now(&self) -> Duration {
+            self.clock.now()
+        }
+    }
+
+    /// A directory holding one file, `f`, whose reads take one second.
+    struct Dir(Arc<Log>);
+
+    impl VfsNodeOps for Dir {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_dir(0, 0).with_ino(1))
+        }
+
+        fn lookup(self: Arc<Self>, path: &str) -> VfsResult<VfsNodeRef> {
+            match path {
+                "f" => Ok(Arc::new(File(self.0.clone()))),
+                _ => Err(VfsError::NotFound),
+            }
+        }
+
+        impl_vfs_dir_default! {}
+    }
+
+    struct File(Arc<Log>);
+
+    impl VfsNodeOps for File {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            Ok(VfsNodeAttr::new_file(0, 0).with_ino(2))
+        }
+
+        fn read_at(&self, _offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            self.0.clock.advance(Duration::from_secs(1));
+            Ok(buf.len() / 2)
+        }
+
+        impl_vfs_non_dir_default! {}
+    }
+
+    #[test]
+    fn test_traced_node() {
+        let log = Arc::new(Log::default());
+        let root: VfsNodeRef = Arc::new(TracedNode::new(Arc::new(Dir(log.clone())), log.clone()));
+        assert_eq!(root.clone().lookup("x").err(), Some(VfsError::NotFound));
+        let file = root.clone().lookup("f").unwrap();
+        assert!(file.as_any().is::<TracedNode>());
+        assert_eq!(file.read_at(0, &mut [0; 8]), Ok(4));
+        assert_eq!(file.get_attr().unwrap().ino(), 2);
+
+        let events = log.events.lock();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].op, TraceOp::Lookup);
+        assert_eq!(events[0].result, Err(VfsError::NotFound));
+        assert_eq!((events[1].path.as_str(), events[1].result), ("f", Ok(())));
+        assert_eq!(events[1].latency, Duration::ZERO);
+        assert_eq!(events[2].op, TraceOp::Read);
+        assert_eq!(events[2].bytes, 4);
+        assert_eq!(events[2].latency, Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_traced_lookup_step() {
+        let log = Arc::new(Log::default());
+        let root = Arc::new(TracedNode::new(Arc::new(Dir(log.clone())), log.clone()));
+        let (file, consumed) = root.lookup_step("./f").unwrap();
+        assert_eq!(consumed, 2);
+        assert!(file.as_any().is::<TracedNode>());
+
+        let events = log.events.lock();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            (events[0].op, events[0].path.as_str()),
+            (TraceOp::Lookup, "./f")
+        );
+    }
+
+    #[test]
+    fn test_ring_tracer() {
+        let ring = Arc::new(TraceRing::new(4));
+        let log = Arc::new(Log::default());
+        let root = Arc::new(TracedNode::new(Arc::new(Dir(log)), ring.clone()));
+        root.lookup("f").unwrap();
+        let records = ring.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].op, records[0].ino), (TraceOp::Lookup, 1));
+        assert_eq!(records[0].path_hash, hash_path("f"));
+    }
+}
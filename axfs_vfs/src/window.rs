@@ -0,0 +1,259 @@
+use crate::handle::OpenState;
+use crate::{
+    FallocateMode, OpenFlags, SeekHint, SetAttr, SliceNode, VfsError, VfsFileRef, VfsNodeAttr,
+    VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsPage, VfsResult,
+};
+
+/// A read-only window into a file.
+///
+/// The window exposes the content of a node, or a byte range of it,
+/// without copying it, like a read-only bind mount of a single file. It is
+/// meant to give untrusted tasks access to a log or a firmware blob: writes,
+/// truncation and attribute changes fail with
+/// [`VfsError::ReadOnlyFilesystem`], and reads never go past the range.
+///
+/// The window is a read-only [`SliceNode`]: offsets in the window are
+/// relative to the start of the range, and the size of the window is the
+/// part of the range inside the file, so it follows the file if it grows or
+/// shrinks.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+/// use axfs_vfs::{FileWindow, VfsError, VfsNodeOps, VfsNodeRef};
+/// # use axfs_vfs::VfsResult;
+/// # struct Blob;
+/// # impl VfsNodeOps for Blob {
+/// #     fn get_attr(&self) -> VfsResult<axfs_vfs::VfsNodeAttr> {
+/// #         Ok(axfs_vfs::VfsNodeAttr::new_file(12, 1))
+/// #     }
+/// #     fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+/// #         let data = &b"HDR:payload!"[offset as usize..];
+/// #         let n = buf.len().min(data.len());
+/// #         buf[..n].copy_from_slice(&data[..n]);
+/// #         Ok(n)
+/// #     }
+/// # }
+/// # let blob: VfsNodeRef = Arc::new(Blob);
+///
+/// // expose the payload after a 4-byte header
+/// let payload = FileWindow::new(blob).with_range(4, Some(7));
+/// let mut buf = [0; 16];
+/// assert_eq!(payload.read_at(0, &mut buf), Ok(7));
+/// assert_eq!(&buf[..7], b"payload");
+/// assert_eq!(payload.write_at(0, b"x"), Err(VfsError::ReadOnlyFilesystem));
+/// ```
+pub struct FileWindow {
+    slice: SliceNode,
+}
+
+impl FileWindow {
+    /// Creates a read-only window into the whole content of `node`.
+    pub fn new(node: VfsNodeRef) -> Self {
+        Self {
+            slice: SliceNode::new(node, 0, None),
+        }
+    }
+
+    /// Restricts the window to a byte range of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The offset in the file of the start of the window
+    /// * `len` - The length of the window, or `None` to extend it to the
+    ///   end of the file
+    pub fn with_range(self, start: u64, len: Option<u64>) -> Self {
+        Self {
+            slice: SliceNode::new(self.slice.inner().clone(), start, len),
+        }
+    }
+
+    /// Returns the wrapped node.
+    pub fn inner(&self) -> &VfsNodeRef {
+        self.slice.inner()
+    }
+}
+
+impl VfsNodeOps for FileWindow {
+    /// Opens the window for reading.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::ReadOnlyFilesystem`] if `flags` contains
+    /// [`OpenFlags::WRITE`] or [`OpenFlags::TRUNC`]. A per-open file
+    /// returned by the node is not used, so that accesses stay within the
+    /// window.
+    fn open(&self, flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        if flags.intersects(OpenFlags::WRITE | OpenFlags::TRUNC) {
+            return Err(VfsError::ReadOnlyFilesystem);
+        }
+        self.slice.open(flags)
+    }
+
+    /// Closes the node.
+    fn release(&self) -> VfsResult {
+        self.slice.release()
+    }
+
+    /// Returns the open state of the node.
+    fn open_state(&self) -> Option<(&OpenState, &dyn VfsNodeOps)> {
+        self.slice.open_state()
+    }
+
+    /// Returns the attributes of the node, with the size of the window and
+    /// without write permissions.
+    fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+        let mut attr = self.slice.get_attr()?;
+        let write = VfsNodePerm::OWNER_WRITE | VfsNodePerm::GROUP_WRITE | VfsNodePerm::OTHER_WRITE;
+        attr.set_perm(attr.perm() - write);
+        Ok(attr)
+    }
+
+    /// Changing the attributes is not allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::ReadOnlyFilesystem`].
+    fn set_attr(&self, _attr: &SetAttr) -> VfsResult {
+        Err(VfsError::ReadOnlyFilesystem)
+    }
+
+    /// Reads data from the window at the given offset.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of bytes read, which is `0` at or past the end of
+    /// the window.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+        self.slice.read_at(offset, buf)
+    }
+
+    /// Writing is not allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::ReadOnlyFilesystem`].
+    fn write_at(&self, _offset: u64, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::ReadOnlyFilesystem)
+    }
+
+    /// Truncating is not allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::ReadOnlyFilesystem`].
+    fn truncate(&self, _size: u64) -> VfsResult {
+        Err(VfsError::ReadOnlyFilesystem)
+    }
+
+    /// Finds the next region of data or the next hole of the window.
+    fn seek_hint(&self, offset: u64, hint: SeekHint) -> VfsResult<Option<u64>> {
+        self.slice.seek_hint(offset, hint)
+    }
+
+    /// Allocating or deallocating storage is not allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::ReadOnlyFilesystem`].
+    fn fallocate(&self, _offset: u64, _len: u64, _mode: FallocateMode) -> VfsResult {
+        Err(VfsError::ReadOnlyFilesystem)
+    }
+
+    /// Prefetches a range of the window.
+    fn readahead(&self, offset: u64, len: u64) -> VfsResult {
+        self.slice.readahead(offset, len)
+    }
+
+    /// Flushes nothing, the window cannot be modified.
+    fn fsync(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Flushes nothing, the window cannot be modified.
+    fn fsync_data(&self) -> VfsResult {
+        Ok(())
+    }
+
+    /// Returns a page of the window to map in memory.
+    ///
+    /// The pages of the node are shared, so they are returned
+    /// [read-only](VfsPage::into_read_only).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`VfsError::InvalidInput`] if `offset` is not aligned or is
+    /// beyond the end of the window, if the page crosses the end of a
+    /// bounded window, or if the window does not start on a page boundary
+    /// of the file.
+    fn get_page(&self, offset: u64) -> VfsResult<VfsPage> {
+        Ok(self.slice.get_page(offset)?.into_read_only())
+    }
+
+    crate::impl_vfs_non_dir_default! {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VfsNodeType;
+    use alloc::sync::Arc;
+    use spin::Mutex;
+
+    struct Blob(Mutex<alloc::vec::Vec<u8>>);
+
+    impl VfsNodeOps for Blob {
+        fn get_attr(&self) -> VfsResult<VfsNodeAttr> {
+            let perm = VfsNodePerm::from_bits_truncate(0o664);
+            Ok(
+                VfsNodeAttr::new(perm, VfsNodeType::File, self.0.lock().len() as u64, 1)
+                    .with_ino(7),
+            )
+        }
+
+        fn read_at(&self, offset: u64, buf: &mut [u8]) -> VfsResult<usize> {
+            let data = self.0.lock();
+            let start = (offset as usize).min(data.len());
+            let n = buf.len().min(data.len() - start);
+            buf[..n].copy_from_slice(&data[start..start + n]);
+            Ok(n)
+        }
+
+        fn write_at(&self, _offset: u64, buf: &[u8]) -> VfsResult<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_file_window() {
+        let blob = Arc::new(Blob(Mutex::new(b"0123456789".to_vec())));
+        let window = FileWindow::new(blob.clone()).with_range(2, Some(5));
+        let attr = window.get_attr().unwrap();
+        assert_eq!((attr.size(), attr.ino()), (5, 7));
+        assert_eq!(attr.perm().bits(), 0o444);
+
+        let mut buf = [0; 8];
+        assert_eq!(window.read_at(3, &mut buf), Ok(2));
+        assert_eq!(&buf[..2], b"56");
+        assert_eq!(window.read_at(5, &mut buf), Ok(0));
+        assert_eq!(window.truncate(0), Err(VfsError::ReadOnlyFilesystem));
+        assert!(window.open(OpenFlags::READ).is_ok());
+        assert_eq!(
+            window.open(OpenFlags::READ | OpenFlags::WRITE).err(),
+            Some(VfsError::ReadOnlyFilesystem)
+        );
+        // the window follows the file
+        let tail = FileWindow::new(blob.clone()).with_range(8, None);
+        assert_eq!(tail.get_attr().unwrap().size(), 2);
+        blob.write_at(10, b"ab").unwrap();
+        assert_eq!(tail.get_attr().unwrap().size(), 4);
+        assert_eq!(
+            FileWindow::new(blob)
+                .with_range(20, None)
+                .read_at(0, &mut buf),
+            Ok(0)
+        );
+    }
+}
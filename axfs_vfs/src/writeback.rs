@@ -0,0 +1,435 @@
+//! Central scheduling of dirty data writeback.
+//!
+//! Filesystems and caches that buffer writes register themselves as
+//! [`WritebackTarget`]s with a [`WritebackScheduler`], and report when they
+//! become dirty with [`WritebackScheduler::mark_dirty`]. The kernel then
+//! drives periodic flushing with [`WritebackScheduler::sync_all`], which only
+//! writes back the targets that have been dirty for long enough.
+//!
+//! Timestamps are read from the global [`clock`](crate::clock), unless the
+//! scheduler is created with its own clock.
+//!
+//! No thread is spawned by this crate: a kernel timer or worker thread polls
+//! the scheduler, or any other [`PollFlush`] implementation, and re-arms
+//! itself for the deadline reported in the returned [`FlushStatus`].
+//!
+//! # Examples
+//!
+//! ```
+//! use axfs_vfs::writeback::{WritebackScheduler, WritebackTarget};
+//! use axfs_vfs::VfsResult;
+//! use core::time::Duration;
+//! use std::sync::Arc;
+//!
+//! struct Cache;
+//!
+//! impl WritebackTarget for Cache {
+//!     fn writeback(&self) -> VfsResult {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! static SCHEDULER: WritebackScheduler = WritebackScheduler::new();
+//!
+//! let cache: Arc<dyn WritebackTarget> = Arc::new(Cache);
+//! let id = SCHEDULER.register(&cache);
+//! SCHEDULER.mark_dirty(id);
+//! // in the periodic flusher:
+//! SCHEDULER.sync_all(Duration::from_secs(30)).unwrap();
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use spin::Mutex;
+
+use crate::{VfsClock, VfsResult};
+
+/// An object buffering writes that can be flushed by a
+/// [`WritebackScheduler`].
+pub trait WritebackTarget: Send + Sync {
+    /// Writes back all dirty data.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if all data has been written back, or an error
+    /// otherwise.
+    fn writeback(&self) -> VfsResult;
+}
+
+/// The outcome of a [`PollFlush::poll_flush`] call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushStatus {
+    /// The number of targets written back.
+    pub flushed: usize,
+    /// When the oldest data still dirty becomes due for writeback, or `None`
+    /// if everything is clean.
+    pub next_due: Option<Duration>,
+}
+
+/// An object buffering writes that is flushed by polling.
+///
+/// This lets a kernel timer or worker thread drive periodic writeback,
+/// without the filesystem spawning threads itself.
+///
+/// # Examples
+///
+/// ```
+/// use axfs_vfs::writeback::{PollFlush, WritebackScheduler};
+/// use core::time::Duration;
+///
+/// let scheduler = WritebackScheduler::new();
+/// // in the timer handler:
+/// let status = scheduler.poll_flush(Duration::from_secs(30)).unwrap();
+/// if let Some(deadline) = status.next_due {
+///     // re-arm the timer for `deadline`
+///     # let _ = deadline;
+/// }
+/// ```
+pub trait PollFlush: Send + Sync {
+    /// Writes back the data that has been dirty for at least `older_than`.
+    ///
+    /// # Arguments
+    ///
+    /// * `older_than` - The minimum dirty age, [`Duration::ZERO`] writes back
+    ///   all dirty data
+    ///
+    /// # Returns
+    ///
+    /// Returns what was written back and when to poll again on success, or
+    /// the first error otherwise. Data that failed to be written back stays
+    /// dirty.
+    fn poll_flush(&self, older_than: Duration) -> VfsResult<FlushStatus>;
+}
+
+/// The identifier of a target registered with a [`WritebackScheduler`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WritebackId(u64);
+
+struct Entry {
+    target: Weak<dyn WritebackTarget>,
+    /// When the target became dirty, or `None` if it is clean.
+    dirty_since: Option<Duration>,
+}
+
+/// Tracks the dirty state of registered targets and flushes them.
+///
+/// Targets are held weakly: a target that is dropped is forgotten by the
+/// next [`sync_all()`](Self::sync_all).
+pub struct WritebackScheduler {
+    clock: Option<Arc<dyn VfsClock>>,
+    next_id: AtomicU64,
+    entries: Mutex<BTreeMap<u64, Entry>>,
+}
+
+impl WritebackScheduler {
+    /// Creates a scheduler with no targets.
+    pub const fn new() -> Self {
+        Self {
+            clock: None,
+            next_id: AtomicU64::new(0),
+            entries: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Creates a scheduler with no targets, reading timestamps from `clock`
+    /// instead of the global clock.
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - The time source used to age dirty targets
+    pub fn with_clock(clock: Arc<dyn VfsClock>) -> Self {
+        Self {
+            clock: Some(clock),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the current time.
+    fn now(&self) -> Duration {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => crate::clock::now(),
+        }
+    }
+
+    /// Registers a target, initially clean.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The target to register
+    ///
+    /// # Returns
+    ///
+    /// Returns the identifier used to mark the target dirty and to
+    /// unregister it.
+    pub fn register(&self, target: &Arc<dyn WritebackTarget>) -> WritebackId {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let entry = Entry {
+            target: Arc::downgrade(target),
+            dirty_since: None,
+        };
+        self.entries.lock().insert(id, entry);
+        WritebackId(id)
+    }
+
+    /// Unregisters a target, without writing it back.
+    ///
+    /// Does nothing if the target is not registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The identifier returned by [`register()`](Self::register)
+    pub fn unregister(&self, id: WritebackId) {
+        self.entries.lock().remove(&id.0);
+    }
+
+    /// Records that a target holds dirty data.
+    ///
+    /// The target stays dirty since the first call after it was last
+    /// written back. Does nothing if the target is not registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The identifier returned by [`register()`](Self::register)
+    pub fn mark_dirty(&self, id: WritebackId) {
+        let now = self.now();
+        if let Some(entry) = self.entries.lock().get_mut(&id.0) {
+            entry.dirty_since.get_or_insert(now);
+        }
+    }
+
+    /// Returns since when a target holds dirty data.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The identifier returned by [`register()`](Self::register)
+    ///
+    /// # Returns
+    ///
+    /// Returns the time the target became dirty, or `None` if it is clean
+    /// or not registered.
+    pub fn dirty_since(&self, id: WritebackId) -> Option<Duration> {
+        self.entries.lock().get(&id.0)?.dirty_since
+    }
+
+    /// Writes back the targets that have been dirty for at least
+    /// `older_than`.
+    ///
+    /// Targets are written back without holding the scheduler lock, so they
+    /// may be marked dirty again meanwhile. A target whose writeback fails
+    /// stays dirty since the same time, and the other targets are still
+    /// written back.
+    ///
+    /// # Arguments
+    ///
+    /// * `older_than` - The minimum dirty age, [`Duration::ZERO`] writes back
+    ///   every dirty target
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of targets written back on success, or the first
+    /// error otherwise.
+    pub fn sync_all(&self, older_than: Duration) -> VfsResult<usize> {
+        let now = self.now();
+        let mut due = Vec::new();
+        self.entries.lock().retain(|&id, entry| {
+            let Some(target) = entry.target.upgrade() else {
+                return false;
+            };
+            if let Some(since) = entry.dirty_since {
+                if now.saturating_sub(since) >= older_than {
+                    entry.dirty_since = None;
+                    due.push((id, since, target));
+                }
+            }
+            true
+        });
+
+        let mut count = 0;
+        let mut result = Ok(());
+        for (id, since, target) in due {
+            match target.writeback() {
+                Ok(()) => count += 1,
+                Err(e) => {
+                    if let Some(entry) = self.entries.lock().get_mut(&id) {
+                        entry.dirty_since = Some(since);
+                    }
+                    result = result.and(Err(e));
+                }
+            }
+        }
+        result.map(|()| count)
+    }
+
+    /// Returns when the oldest dirty target becomes due for writeback.
+    ///
+    /// # Arguments
+    ///
+    /// * `older_than` - The minimum dirty age before writeback
+    ///
+    /// # Returns
+    ///
+    /// Returns the earliest time a dirty target has been dirty for
+    /// `older_than`, which may be in the past, or `None` if all targets are
+    /// clean.
+    pub fn next_due(&self, older_than: Duration) -> Option<Duration> {
+        self.entries
+            .lock()
+            .values()
+            .filter_map(|entry| entry.dirty_since)
+            .min()
+            .map(|since| since.saturating_add(older_than))
+    }
+}
+
+impl PollFlush for WritebackScheduler {
+    /// Writes back the due targets with [`sync_all()`](Self::sync_all).
+    fn poll_flush(&self, older_than: Duration) -> VfsResult<FlushStatus> {
+        let flushed = self.sync_all(older_than)?;
+        Ok(FlushStatus {
+            flushed,
+            next_due: self.next_due(older_than),
+        })
+    }
+}
+
+impl Default for WritebackScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VfsError;
+    use core::sync::atomic::AtomicBool;
+
+    #[derive(Default)]
+    struct Target {
+        flushes: AtomicU64,
+        fail: AtomicBool,
+    }
+
+    impl WritebackTarget for Target {
+        fn writeback(&self) -> VfsResult {
+            if self.fail.load(Ordering::Relaxed) {
+                return Err(VfsError::Io);
+            }
+            self.flushes.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sync_all() {
+        let scheduler = WritebackScheduler::new();
+        let target = Arc::new(Target::default());
+        let dyn_target: Arc<dyn WritebackTarget> = target.clone();
+        let id = scheduler.register(&dyn_target);
+
+        // clean targets are not written back
+        assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(0));
+        scheduler.mark_dirty(id);
+        assert!(scheduler.dirty_since(id).is_some());
+        assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(1));
+        assert_eq!(target.flushes.load(Ordering::Relaxed), 1);
+        assert_eq!(scheduler.dirty_since(id), None);
+
+        // failed writebacks stay dirty
+        scheduler.mark_dirty(id);
+        target.fail.store(true, Ordering::Relaxed);
+        assert_eq!(scheduler.sync_all(Duration::ZERO), Err(VfsError::Io));
+        assert!(scheduler.dirty_since(id).is_some());
+
+        scheduler.unregister(id);
+        assert_eq!(scheduler.dirty_since(id), None);
+        assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(0));
+    }
+
+    #[test]
+    fn test_sync_all_older_than() {
+        use crate::clock::ManualClock;
+
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(100)));
+        let scheduler = WritebackScheduler::with_clock(clock.clone());
+        let old: Arc<dyn WritebackTarget> = Arc::new(Target::default());
+        let young: Arc<dyn WritebackTarget> = Arc::new(Target::default());
+        let old_id = scheduler.register(&old);
+        let young_id = scheduler.register(&young);
+
+        scheduler.mark_dirty(old_id);
+        clock.advance(Duration::from_secs(20));
+        scheduler.mark_dirty(young_id);
+        // marking again does not rejuvenate the target
+        scheduler.mark_dirty(old_id);
+        assert_eq!(
+            scheduler.dirty_since(old_id),
+            Some(Duration::from_secs(100))
+        );
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(scheduler.sync_all(Duration::from_secs(30)), Ok(1));
+        assert_eq!(scheduler.dirty_since(old_id), None);
+        assert_eq!(
+            scheduler.dirty_since(young_id),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(1));
+    }
+
+    #[test]
+    fn test_dropped_target_forgotten() {
+        let scheduler = WritebackScheduler::new();
+        let target: Arc<dyn WritebackTarget> = Arc::new(Target::default());
+        let id = scheduler.register(&target);
+        scheduler.mark_dirty(id);
+        drop(target);
+        assert_eq!(scheduler.sync_all(Duration::ZERO), Ok(0));
+        assert!(scheduler.entries.lock().is_empty());
+    }
+
+    #[test]
+    fn test_poll_flush() {
+        use crate::clock::ManualClock;
+
+        let clock = Arc::new(ManualClock::new(Duration::from_secs(100)));
+        let scheduler = WritebackScheduler::with_clock(clock.clone());
+        let target = Arc::new(Target::default());
+        let dyn_target: Arc<dyn WritebackTarget> = target.clone();
+        let id = scheduler.register(&dyn_target);
+        let expire = Duration::from_secs(30);
+
+        assert_eq!(scheduler.poll_flush(expire), Ok(FlushStatus::default()));
+        scheduler.mark_dirty(id);
+        assert_eq!(scheduler.next_due(expire), Some(Duration::from_secs(130)));
+
+        // not due yet: nothing is written back
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(
+            scheduler.poll_flush(expire),
+            Ok(FlushStatus {
+                flushed: 0,
+                next_due: Some(Duration::from_secs(130)),
+            })
+        );
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(
+            scheduler.poll_flush(expire),
+            Ok(FlushStatus {
+                flushed: 1,
+                next_due: None,
+            })
+        );
+        assert_eq!(target.flushes.load(Ordering::Relaxed), 1);
+
+        target.fail.store(true, Ordering::Relaxed);
+        scheduler.mark_dirty(id);
+        assert_eq!(scheduler.poll_flush(Duration::ZERO), Err(VfsError::Io));
+        assert_eq!(scheduler.next_due(Duration::ZERO), Some(clock.now()));
+    }
+}
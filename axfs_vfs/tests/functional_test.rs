@@ -4,7 +4,8 @@
 //! of the axfs_vfs crate using mock implementations.
 
 use axfs_vfs::{
-    VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef, VfsNodeType, VfsOps, VfsResult,
+    OpenFlags, VfsDirEntry, VfsError, VfsFileRef, VfsNodeAttr, VfsNodeOps, VfsNodePerm, VfsNodeRef,
+    VfsNodeRefExt, VfsNodeType, VfsOps, VfsResult,
 };
 use std::sync::Arc;
 
@@ -37,8 +38,8 @@ impl MockDirectory {
 }
 
 impl VfsNodeOps for MockDirectory {
-    fn open(&self) -> VfsResult {
-        Ok(())
+    fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        Ok(None)
     }
 
     fn release(&self) -> VfsResult {
@@ -68,6 +69,10 @@ impl VfsNodeOps for MockDirectory {
     fn as_any(&self) -> &dyn core::any::Any {
         self
     }
+
+    fn as_any_arc(self: Arc<Self>) -> Option<Arc<dyn core::any::Any + Send + Sync>> {
+        Some(self)
+    }
 }
 
 /// Mock file node for testing
@@ -80,8 +85,8 @@ impl MockFile {
 }
 
 impl VfsNodeOps for MockFile {
-    fn open(&self) -> VfsResult {
-        Ok(())
+    fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        Ok(None)
     }
 
     fn release(&self) -> VfsResult {
@@ -112,6 +117,10 @@ impl VfsNodeOps for MockFile {
     fn as_any(&self) -> &dyn core::any::Any {
         self
     }
+
+    fn as_any_arc(self: Arc<Self>) -> Option<Arc<dyn core::any::Any + Send + Sync>> {
+        Some(self)
+    }
 }
 
 // ============== Functional Tests ==============
@@ -120,7 +129,21 @@ impl VfsNodeOps for MockFile {
 fn test_vfs_ops_root_dir() {
     let fs = MockFileSystem::new();
     let root = fs.root_dir();
-    assert!(root.open().is_ok());
+    assert!(root.open(OpenFlags::READ).is_ok());
+}
+
+#[test]
+fn test_vfs_ops_freeze_unsupported() {
+    let fs = MockFileSystem::new();
+    assert_eq!(fs.freeze(), Err(VfsError::Unsupported));
+    assert_eq!(fs.thaw(), Err(VfsError::Unsupported));
+}
+
+#[test]
+fn test_vfs_ops_fs_type_default() {
+    let fs = MockFileSystem::new();
+    assert_eq!(fs.fs_type(), "unknown");
+    assert_eq!(fs.fs_magic(), 0);
 }
 
 #[test]
@@ -128,7 +151,7 @@ fn test_vfs_node_ops_directory_lifecycle() {
     let dir = Arc::new(MockDirectory::new());
 
     // Test open and release
-    assert!(dir.open().is_ok());
+    assert!(dir.open(OpenFlags::READ).is_ok());
     assert!(dir.release().is_ok());
 
     // Test get_attr
@@ -147,7 +170,7 @@ fn test_vfs_node_ops_directory_lookup() {
     let result = dir.lookup("test.txt");
     assert!(result.is_ok());
     let node = result.unwrap();
-    assert!(node.open().is_ok());
+    assert!(node.open(OpenFlags::READ).is_ok());
 }
 
 #[test]
@@ -172,7 +195,7 @@ fn test_vfs_node_ops_file_lifecycle() {
     let file = Arc::new(MockFile::new());
 
     // Test open and release
-    assert!(file.open().is_ok());
+    assert!(file.open(OpenFlags::READ).is_ok());
     assert!(file.release().is_ok());
 
     // Test get_attr
@@ -231,6 +254,21 @@ fn test_vfs_node_ops_as_any_downcast() {
     assert!(file_any.downcast_ref::<MockDirectory>().is_none());
 }
 
+#[test]
+fn test_vfs_node_ref_typed_downcast() {
+    let dir: VfsNodeRef = Arc::new(MockDirectory::new());
+
+    assert!(dir.downcast_ref::<MockDirectory>().is_some());
+    assert!(dir.downcast_ref::<MockFile>().is_none());
+
+    let typed: Arc<MockDirectory> = dir.clone().downcast().unwrap();
+    assert!(Arc::ptr_eq(&(typed as VfsNodeRef), &dir));
+    assert_eq!(
+        dir.downcast::<MockFile>().err(),
+        Some(VfsError::InvalidInput)
+    );
+}
+
 // ============== Error Handling Tests ==============
 
 #[test]
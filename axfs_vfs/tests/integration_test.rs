@@ -5,7 +5,10 @@
 //! These tests verify the behavior of VFS in real-world scenarios.
 
 use axerrno::ax_err;
-use axfs_vfs::{VfsDirEntry, VfsNodeAttr, VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult};
+use axfs_vfs::{
+    FallocateMode, MountOptions, OpenFlags, VfsDirEntry, VfsError, VfsFileRef, VfsNodeAttr,
+    VfsNodeOps, VfsNodeRef, VfsNodeType, VfsOps, VfsResult,
+};
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
@@ -41,8 +44,8 @@ impl SimulatedFile {
 }
 
 impl VfsNodeOps for SimulatedFile {
-    fn open(&self) -> VfsResult {
-        Ok(())
+    fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        Ok(None)
     }
 
     fn release(&self) -> VfsResult {
@@ -114,8 +117,8 @@ impl SimulatedDirectory {
 }
 
 impl VfsNodeOps for SimulatedDirectory {
-    fn open(&self) -> VfsResult {
-        Ok(())
+    fn open(&self, _flags: OpenFlags) -> VfsResult<Option<VfsFileRef>> {
+        Ok(None)
     }
 
     fn release(&self) -> VfsResult {
@@ -214,7 +217,7 @@ impl SimulatedFilesystem {
 }
 
 impl VfsOps for SimulatedFilesystem {
-    fn mount(&self, _path: &str, _mount_point: VfsNodeRef) -> VfsResult {
+    fn mount(&self, _path: &str, _mount_point: VfsNodeRef, _options: &MountOptions) -> VfsResult {
         self.setup()
     }
 
@@ -238,7 +241,7 @@ fn test_system_filesystem_lifecycle() {
     let fs = SimulatedFilesystem::new();
 
     // Test mount
-    let result = fs.mount("/", fs.root_dir());
+    let result = fs.mount("/", fs.root_dir(), &MountOptions::new());
     assert!(result.is_ok());
 
     // Test umount
@@ -249,7 +252,7 @@ fn test_system_filesystem_lifecycle() {
 #[test]
 fn test_system_directory_structure_setup() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
     let dirents: &mut [VfsDirEntry] =
@@ -263,7 +266,7 @@ fn test_system_directory_structure_setup() {
 #[test]
 fn test_system_file_create_read_write() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
     root.create("system_test.txt", VfsNodeType::File).unwrap();
@@ -286,7 +289,7 @@ fn test_system_file_create_read_write() {
 #[test]
 fn test_system_file_operations_sequence() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
 
@@ -317,7 +320,7 @@ fn test_system_file_operations_sequence() {
 #[test]
 fn test_system_directory_operations() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
 
@@ -341,7 +344,7 @@ fn test_system_directory_operations() {
 #[test]
 fn test_system_file_rename() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
 
@@ -370,7 +373,7 @@ fn test_system_file_rename() {
 #[test]
 fn test_system_directory_pagination() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
 
@@ -399,7 +402,7 @@ fn test_system_directory_pagination() {
 #[test]
 fn test_system_file_truncate_extend() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
     root.create("truncate_test.txt", VfsNodeType::File).unwrap();
@@ -429,7 +432,7 @@ fn test_system_file_truncate_extend() {
 #[test]
 fn test_system_file_fsync() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
     root.create("fsync_test.txt", VfsNodeType::File).unwrap();
@@ -446,7 +449,7 @@ fn test_system_file_fsync() {
 #[test]
 fn test_system_node_attributes() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
 
@@ -475,7 +478,7 @@ fn test_system_node_attributes() {
 #[test]
 fn test_system_error_handling() {
     let fs = SimulatedFilesystem::new();
-    fs.mount("/", fs.root_dir()).unwrap();
+    fs.mount("/", fs.root_dir(), &MountOptions::new()).unwrap();
 
     let root = fs.root_dir();
 
@@ -491,3 +494,43 @@ fn test_system_error_handling() {
     let result = root.rename("nonexistent.txt", "new.txt");
     assert!(result.is_err());
 }
+
+#[test]
+fn test_system_default_type_errors() {
+    let fs = SimulatedFilesystem::new();
+    let root = fs.root_dir();
+    root.create("file.txt", VfsNodeType::File).unwrap();
+    let file = root.clone().lookup("file.txt").unwrap();
+
+    // File operations not implemented by a directory
+    let mut buf = [0; 4];
+    assert_eq!(root.read_at(0, &mut buf), Err(VfsError::IsADirectory));
+    assert_eq!(root.write_at(0, b"data"), Err(VfsError::IsADirectory));
+    assert_eq!(root.truncate(0), Err(VfsError::IsADirectory));
+    assert_eq!(
+        root.fallocate(0, 1, FallocateMode::empty()),
+        Err(VfsError::IsADirectory)
+    );
+
+    // Directory operations not implemented by a file
+    assert_eq!(
+        file.clone().lookup("x").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        file.create("x", VfsNodeType::File),
+        Err(VfsError::NotADirectory)
+    );
+    assert_eq!(file.remove("x"), Err(VfsError::NotADirectory));
+    assert_eq!(file.rename("x", "y"), Err(VfsError::NotADirectory));
+    assert_eq!(file.create_symlink("x", "y"), Err(VfsError::NotADirectory));
+    let mut dirents = [VfsDirEntry::default()];
+    assert_eq!(file.read_dir(0, &mut dirents), Err(VfsError::NotADirectory));
+
+    // Operations the node type allows but the node does not implement
+    assert_eq!(
+        file.fallocate(0, 1, FallocateMode::empty()),
+        Err(VfsError::Unsupported)
+    );
+    assert_eq!(root.link("a", "b"), Err(VfsError::Unsupported));
+}
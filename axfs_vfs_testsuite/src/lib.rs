@@ -0,0 +1,517 @@
+//! Conformance tests for implementations of the [`axfs_vfs`] traits.
+//!
+//! The checks of this crate describe the behavior expected from every
+//! filesystem, so that devfs, ramfs and third-party filesystems agree on
+//! path resolution, error codes and directory listing. Each check panics
+//! with a description of the first mismatch, so they are meant to be run
+//! from a test:
+//!
+//! ```
+//! use axfs_vfs::VfsOps;
+//!
+//! // called by a test with the filesystem to check
+//! fn conformance(fs: &dyn VfsOps) {
+//!     axfs_vfs_testsuite::run(fs);
+//! }
+//! ```
+//!
+//! The checks only rely on the root directory of the filesystem:
+//!
+//! - [`check_root()`] and [`check_read_dir()`] read the existing tree, and
+//!   apply to all filesystems.
+//! - The other checks create their nodes in a scratch directory, which is
+//!   removed when they pass, and kept for inspection otherwise. They are
+//!   skipped on the filesystems advertising [`VfsFeatures::READ_ONLY`], for
+//!   which [`check_read_only()`] is run instead.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use axfs_vfs::{VfsDirEntry, VfsError, VfsFeatures, VfsNodeRef, VfsNodeType, VfsOps};
+
+/// The number of entries created by [`check_read_dir_pagination()`].
+pub const PAGINATION_ENTRIES: usize = 40;
+
+/// Runs all the checks that apply to `fs`.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn run(fs: &dyn VfsOps) {
+    check_root(fs);
+    check_read_dir(fs);
+    if is_read_only(fs) {
+        check_read_only(fs);
+        return;
+    }
+    check_create_remove(fs);
+    check_read_write(fs);
+    check_paths(fs);
+    check_type_errors(fs);
+    check_rename(fs);
+    check_read_dir_pagination(fs);
+}
+
+/// Returns whether `fs` advertises [`VfsFeatures::READ_ONLY`].
+fn is_read_only(fs: &dyn VfsOps) -> bool {
+    fs.capabilities().supports(VfsFeatures::READ_ONLY)
+}
+
+/// Returns the inode number of `node`.
+fn ino(node: &VfsNodeRef) -> u64 {
+    node.get_attr().expect("get_attr failed").ino()
+}
+
+/// Reads all the entries of `dir`, `count` at a time.
+fn read_entries(dir: &VfsNodeRef, count: usize) -> Vec<(String, VfsNodeType)> {
+    let mut entries = Vec::new();
+    let mut buf: Vec<_> = (0..count).map(|_| VfsDirEntry::default()).collect();
+    loop {
+        let n = dir
+            .read_dir(entries.len(), &mut buf)
+            .expect("read_dir failed");
+        assert!(n <= count, "read_dir returned more entries than asked");
+        entries.extend(
+            buf[..n]
+                .iter()
+                .map(|e| (e.name().to_string(), e.entry_type())),
+        );
+        if n < count {
+            return entries;
+        }
+    }
+}
+
+/// A directory created for a check, removed with its content by
+/// [`finish()`](Self::finish) when the check passes.
+struct Scratch {
+    root: VfsNodeRef,
+    name: String,
+}
+
+impl Scratch {
+    /// Creates the scratch directory of the check `check` in the root of
+    /// `fs`.
+    fn new(fs: &dyn VfsOps, check: &str) -> Self {
+        let root = fs.root_dir();
+        let name = format!("vfs-testsuite-{check}");
+        root.create(&name, VfsNodeType::Dir)
+            .expect("cannot create the scratch directory");
+        Self { root, name }
+    }
+
+    /// Returns the path of `rel` from the root directory.
+    fn path(&self, rel: &str) -> String {
+        format!("{}/{}", self.name, rel)
+    }
+
+    /// Returns the scratch directory.
+    fn dir(&self) -> VfsNodeRef {
+        self.root.clone().lookup(&self.name).unwrap()
+    }
+
+    /// Removes the node at `path` and everything below it.
+    fn remove_all(dir: &VfsNodeRef, path: &str) {
+        let node = dir.clone().lookup(path).unwrap();
+        if node.get_attr().unwrap().is_dir() {
+            for (name, _) in read_entries(&node, 16) {
+                if name != "." && name != ".." {
+                    Self::remove_all(dir, &format!("{path}/{name}"));
+                }
+            }
+        }
+        dir.remove(path).unwrap();
+    }
+
+    /// Removes the scratch directory and its content.
+    fn finish(self) {
+        Self::remove_all(&self.root, &self.name);
+    }
+}
+
+/// Checks the root directory and the lookup of `.`, `..` and missing
+/// names.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_root(fs: &dyn VfsOps) {
+    let root = fs.root_dir();
+    let attr = root.get_attr().expect("get_attr of the root failed");
+    assert!(attr.is_dir(), "the root is not a directory");
+    for path in [".", "./", "./."] {
+        let node = root.clone().lookup(path).unwrap();
+        assert_eq!(ino(&node), attr.ino(), "lookup({path:?}) is not the root");
+    }
+    assert_eq!(
+        root.clone().lookup("vfs-testsuite-missing").err(),
+        Some(VfsError::NotFound),
+        "lookup of a missing name"
+    );
+    assert_eq!(
+        root.clone().lookup("vfs-testsuite-missing/child").err(),
+        Some(VfsError::NotFound),
+        "lookup below a missing name"
+    );
+}
+
+/// Checks that directory listings start with `.` and `..`, and do not
+/// depend on the number of entries read at a time.
+///
+/// Entries that are not directories must also refuse to be looked into.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_read_dir(fs: &dyn VfsOps) {
+    let root = fs.root_dir();
+    let all = read_entries(&root, 64);
+    assert!(all.len() >= 2, "read_dir does not return `.` and `..`");
+    assert_eq!(all[0], (".".to_string(), VfsNodeType::Dir));
+    assert_eq!(all[1], ("..".to_string(), VfsNodeType::Dir));
+    assert_eq!(read_entries(&root, 1), all, "read_dir one entry at a time");
+    assert_eq!(
+        read_entries(&root, 3),
+        all,
+        "read_dir three entries at a time"
+    );
+    assert_eq!(
+        root.read_dir(all.len() + 10, &mut [VfsDirEntry::default()]),
+        Ok(0),
+        "read_dir past the end"
+    );
+
+    for (name, ty) in &all[2..] {
+        let node = root.clone().lookup(name).unwrap();
+        assert_eq!(
+            node.get_attr().unwrap().file_type(),
+            *ty,
+            "type of the entry {name:?}"
+        );
+        if !ty.is_dir() && !ty.is_symlink() {
+            assert_eq!(
+                root.clone().lookup(&format!("{name}/child")).err(),
+                Some(VfsError::NotADirectory),
+                "lookup below the non-directory {name:?}"
+            );
+        }
+    }
+}
+
+/// Checks that a read-only filesystem refuses to create nodes.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_read_only(fs: &dyn VfsOps) {
+    let root = fs.root_dir();
+    for ty in [VfsNodeType::File, VfsNodeType::Dir] {
+        assert!(
+            root.create("vfs-testsuite-ro", ty).is_err(),
+            "a read-only filesystem created a {ty:?}"
+        );
+        assert_eq!(
+            root.clone().lookup("vfs-testsuite-ro").err(),
+            Some(VfsError::NotFound)
+        );
+    }
+}
+
+/// Checks the creation and removal of files and directories, and their
+/// error codes.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_create_remove(fs: &dyn VfsOps) {
+    let s = Scratch::new(fs, "create");
+    let root = fs.root_dir();
+
+    root.create(&s.path("file"), VfsNodeType::File).unwrap();
+    root.create(&s.path("dir"), VfsNodeType::Dir).unwrap();
+    assert!(root
+        .clone()
+        .lookup(&s.path("file"))
+        .unwrap()
+        .get_attr()
+        .unwrap()
+        .is_file());
+    assert!(root
+        .clone()
+        .lookup(&s.path("dir"))
+        .unwrap()
+        .get_attr()
+        .unwrap()
+        .is_dir());
+    for name in ["file", "dir"] {
+        for ty in [VfsNodeType::File, VfsNodeType::Dir] {
+            assert_eq!(
+                root.create(&s.path(name), ty),
+                Err(VfsError::AlreadyExists),
+                "create over the existing {name:?}"
+            );
+        }
+    }
+    assert_eq!(
+        root.create(&s.path("missing/file"), VfsNodeType::File),
+        Err(VfsError::NotFound),
+        "create below a missing directory"
+    );
+
+    root.create(&s.path("dir/child"), VfsNodeType::File)
+        .unwrap();
+    assert_eq!(
+        root.remove(&s.path("dir")),
+        Err(VfsError::DirectoryNotEmpty),
+        "remove a non-empty directory"
+    );
+    root.remove(&s.path("dir/child")).unwrap();
+    root.remove(&s.path("dir")).unwrap();
+    root.remove(&s.path("file")).unwrap();
+    for name in ["file", "dir"] {
+        assert_eq!(
+            root.clone().lookup(&s.path(name)).err(),
+            Some(VfsError::NotFound),
+            "lookup of the removed {name:?}"
+        );
+        assert_eq!(
+            root.remove(&s.path(name)),
+            Err(VfsError::NotFound),
+            "remove the removed {name:?}"
+        );
+    }
+    assert_eq!(read_entries(&s.dir(), 8).len(), 2);
+    s.finish();
+}
+
+/// Checks reads, writes and truncations of a file.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_read_write(fs: &dyn VfsOps) {
+    let s = Scratch::new(fs, "rw");
+    let root = fs.root_dir();
+    root.create(&s.path("file"), VfsNodeType::File).unwrap();
+    let file = root.clone().lookup(&s.path("file")).unwrap();
+
+    assert_eq!(file.write_at(0, b"hello"), Ok(5));
+    assert_eq!(file.get_attr().unwrap().size(), 5);
+    let mut buf = [0xff; 8];
+    assert_eq!(file.read_at(0, &mut buf), Ok(5), "read at the start");
+    assert_eq!(&buf[..5], b"hello");
+    assert_eq!(file.read_at(5, &mut buf), Ok(0), "read at the end");
+    assert_eq!(file.read_at(100, &mut buf), Ok(0), "read past the end");
+
+    // writing past the end fills the gap with zeros
+    assert_eq!(file.write_at(8, b"!"), Ok(1));
+    assert_eq!(file.get_attr().unwrap().size(), 9);
+    let mut buf = [0xff; 9];
+    assert_eq!(file.read_at(0, &mut buf), Ok(9));
+    assert_eq!(&buf, b"hello\0\0\0!");
+
+    file.truncate(2).unwrap();
+    assert_eq!(file.get_attr().unwrap().size(), 2);
+    file.truncate(4).unwrap();
+    let mut buf = [0xff; 8];
+    assert_eq!(file.read_at(0, &mut buf), Ok(4));
+    assert_eq!(&buf[..4], b"he\0\0", "truncate does not zero the extension");
+
+    // the data is shared by all references to the file
+    let again = s.dir().lookup("file").unwrap();
+    assert_eq!(ino(&again), ino(&file));
+    assert_eq!(again.get_attr().unwrap().size(), 4);
+    s.finish();
+}
+
+/// Checks the resolution of paths with redundant separators, `.` and `..`
+/// components.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_paths(fs: &dyn VfsOps) {
+    let s = Scratch::new(fs, "paths");
+    let root = fs.root_dir();
+    root.create(&s.path("a"), VfsNodeType::Dir).unwrap();
+    root.create(&s.path("a/b"), VfsNodeType::Dir).unwrap();
+    root.create(&s.path("a/b/file"), VfsNodeType::File).unwrap();
+    let file = ino(&root.clone().lookup(&s.path("a/b/file")).unwrap());
+    let b = ino(&root.clone().lookup(&s.path("a/b")).unwrap());
+
+    for path in [
+        "a//b/file",
+        "./a/b/file",
+        "a/./b/file",
+        "a/b/../b/file",
+        "a/../a/b/./file",
+    ] {
+        let node = s.dir().lookup(path);
+        assert_eq!(node.map(|n| ino(&n)), Ok(file), "lookup({path:?})");
+    }
+    for path in ["a/b/", "a/b//", "a/b/.", "a/b/file/.."] {
+        let node = s.dir().lookup(path);
+        if path == "a/b/file/.." {
+            assert_eq!(
+                node.err(),
+                Some(VfsError::NotADirectory),
+                "lookup({path:?}) goes through a file"
+            );
+        } else {
+            assert_eq!(node.map(|n| ino(&n)), Ok(b), "lookup({path:?})");
+        }
+    }
+    let a = s.dir().lookup("a").unwrap();
+    assert_eq!(ino(&a.clone().lookup("..").unwrap()), ino(&s.dir()));
+
+    // creations and removals resolve paths the same way
+    s.dir().create("a/./b//new", VfsNodeType::File).unwrap();
+    assert!(s.dir().lookup("a/b/new").is_ok());
+    s.dir().remove("a/../a/b/new").unwrap();
+    assert_eq!(s.dir().lookup("a/b/new").err(), Some(VfsError::NotFound));
+    s.finish();
+}
+
+/// Checks that file operations fail on directories and directory
+/// operations fail on files, with the right error codes.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_type_errors(fs: &dyn VfsOps) {
+    let s = Scratch::new(fs, "types");
+    let dir = s.dir();
+    dir.create("file", VfsNodeType::File).unwrap();
+    let file = dir.clone().lookup("file").unwrap();
+
+    let mut buf = [0; 4];
+    assert_eq!(dir.read_at(0, &mut buf), Err(VfsError::IsADirectory));
+    assert_eq!(dir.write_at(0, &buf), Err(VfsError::IsADirectory));
+    assert_eq!(dir.truncate(0), Err(VfsError::IsADirectory));
+
+    assert_eq!(
+        file.create("child", VfsNodeType::File),
+        Err(VfsError::NotADirectory)
+    );
+    assert_eq!(file.remove("child"), Err(VfsError::NotADirectory));
+    assert_eq!(
+        file.read_dir(0, &mut [VfsDirEntry::default()]),
+        Err(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        file.clone().lookup("child").err(),
+        Some(VfsError::NotADirectory)
+    );
+    assert_eq!(
+        dir.create("file/child", VfsNodeType::File),
+        Err(VfsError::NotADirectory),
+        "create below a file"
+    );
+    s.finish();
+}
+
+/// Checks the renaming of files and directories.
+///
+/// A rename replaces an existing file at the destination in one step: the
+/// destination then refers to the source node, and the source name is
+/// gone.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_rename(fs: &dyn VfsOps) {
+    let s = Scratch::new(fs, "rename");
+    let root = fs.root_dir();
+    let dir = s.dir();
+    dir.create("src", VfsNodeType::File).unwrap();
+    dir.create("dst", VfsNodeType::File).unwrap();
+    dir.create("sub", VfsNodeType::Dir).unwrap();
+    let src = dir.clone().lookup("src").unwrap();
+    src.write_at(0, b"source").unwrap();
+    let src_ino = ino(&src);
+
+    // replace an existing file
+    root.rename(&s.path("src"), &s.path("dst")).unwrap();
+    assert_eq!(dir.clone().lookup("src").err(), Some(VfsError::NotFound));
+    let dst = dir.clone().lookup("dst").unwrap();
+    assert_eq!(ino(&dst), src_ino, "rename did not move the node");
+    let mut buf = [0; 6];
+    assert_eq!(dst.read_at(0, &mut buf), Ok(6));
+    assert_eq!(&buf, b"source");
+
+    // move across directories, and back
+    root.rename(&s.path("dst"), &s.path("sub/moved")).unwrap();
+    assert_eq!(ino(&dir.clone().lookup("sub/moved").unwrap()), src_ino);
+    root.rename(&s.path("sub/moved"), &s.path("back")).unwrap();
+    assert_eq!(ino(&dir.clone().lookup("back").unwrap()), src_ino);
+
+    // renaming a node to itself changes nothing
+    root.rename(&s.path("back"), &s.path("back")).unwrap();
+    assert_eq!(ino(&dir.clone().lookup("back").unwrap()), src_ino);
+
+    // directories keep their content
+    dir.create("sub/child", VfsNodeType::File).unwrap();
+    root.rename(&s.path("sub"), &s.path("renamed")).unwrap();
+    assert!(dir.clone().lookup("renamed/child").is_ok());
+    assert_eq!(dir.clone().lookup("sub").err(), Some(VfsError::NotFound));
+
+    assert_eq!(
+        root.rename(&s.path("missing"), &s.path("other")),
+        Err(VfsError::NotFound),
+        "rename a missing node"
+    );
+    assert_eq!(
+        root.rename(&s.path("renamed"), &s.path("renamed/inside")),
+        Err(VfsError::InvalidInput),
+        "rename a directory into itself"
+    );
+    dir.create("nonempty", VfsNodeType::Dir).unwrap();
+    dir.create("nonempty/x", VfsNodeType::File).unwrap();
+    assert_eq!(
+        root.rename(&s.path("renamed"), &s.path("nonempty")),
+        Err(VfsError::DirectoryNotEmpty),
+        "rename over a non-empty directory"
+    );
+    assert!(dir.clone().lookup("renamed/child").is_ok());
+    s.finish();
+}
+
+/// Checks that listing a large directory in pages returns every entry
+/// exactly once.
+///
+/// # Panics
+///
+/// Panics if `fs` does not conform.
+pub fn check_read_dir_pagination(fs: &dyn VfsOps) {
+    let s = Scratch::new(fs, "readdir");
+    let dir = s.dir();
+    let mut expected: Vec<String> = (0..PAGINATION_ENTRIES)
+        .map(|i| format!("entry-{i:02}"))
+        .collect();
+    for name in &expected {
+        dir.create(name, VfsNodeType::File).unwrap();
+    }
+    expected.sort();
+
+    for count in [1, 3, 7, PAGINATION_ENTRIES + 2, 64] {
+        let entries = read_entries(&dir, count);
+        assert_eq!(entries.len(), PAGINATION_ENTRIES + 2, "pages of {count}");
+        let mut names: Vec<String> = entries[2..].iter().map(|(n, _)| n.clone()).collect();
+        names.sort();
+        assert_eq!(names, expected, "pages of {count}");
+        assert!(entries[2..].iter().all(|(_, ty)| *ty == VfsNodeType::File));
+    }
+
+    // an empty buffer reads nothing, at any position
+    assert_eq!(dir.read_dir(0, &mut []), Ok(0));
+    assert_eq!(dir.read_dir(5, &mut []), Ok(0));
+    let mut one = vec![VfsDirEntry::default()];
+    assert_eq!(dir.read_dir(PAGINATION_ENTRIES + 2, &mut one), Ok(0));
+    s.finish();
+}